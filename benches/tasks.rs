@@ -0,0 +1,113 @@
+//! How long tasks.md takes to parse, group and draw as it grows: `cargo bench`, or
+//! `cargo bench -- parse` for one group. Criterion keeps the last run in target/criterion
+//! and says how far each benchmark moved from it.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+use std::hint::black_box;
+use yarmtl::task::{self, Task};
+
+// The TUI is part of the binary rather than the library
+#[allow(dead_code)]
+#[path = "../src/tui.rs"]
+mod tui;
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+/// A line with every kind of marker, the most work for the parser.
+const FULL_LINE: &str = "- [ ] review the q3 report [id:aaaa01] !2030-03-14 @2030-03-10 #work #reports >office \
+                         [created:2030-01-02] [repeat:weekly] ~2h //numbers from finance first $4";
+
+/// tasks.md with `count` tasks looking like real ones: under a few projects, some done,
+/// some with subtasks, deadlines spread over a year.
+fn tasks_md(count: usize) -> String {
+    let mut content = String::from("# tasks\n\n");
+    for i in 0..count {
+        if i % (count / 4).max(1) == 0 {
+            content.push_str(&format!("\n## project {}\n", i / (count / 4).max(1)));
+        }
+        let indent = if i % 5 == 4 { "  " } else { "" };
+        let checkbox = if i % 7 == 0 { "x" } else { " " };
+        let mut line = format!("{}- [{}] task number {} with some words [id:{:06x}]", indent, checkbox, i, i);
+        if i % 3 == 0 {
+            line.push_str(&format!(" !2030-{:02}-{:02}", i % 12 + 1, i % 28 + 1));
+        }
+        if i % 4 == 0 {
+            line.push_str(&format!(" #tag{} >home", i % 10));
+        }
+        line.push_str(" [created:2030-01-01]");
+        if i % 6 == 0 {
+            line.push_str(" //a note about it $3");
+        }
+        content.push_str(&line);
+        content.push('\n');
+    }
+    content
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    group.bench_function("full line", |b| b.iter(|| task::parse_task_line(black_box(FULL_LINE))));
+    group.bench_function("plain line", |b| b.iter(|| task::parse_task_line(black_box("- [ ] call bob [id:aaaa01]"))));
+    group.bench_function("Task::parse", |b| b.iter(|| Task::parse(black_box("call bob !2030-03-14 #phone $2"))));
+    group.finish();
+}
+
+fn load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load");
+    let dir = std::env::temp_dir().join(format!("yarmtl-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for size in SIZES {
+        let content = tasks_md(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::new("parse_tasks", size), &content, |b, content| {
+            b.iter(|| task::parse_tasks(black_box(content)))
+        });
+        let file = dir.join(format!("tasks-{}.md", size));
+        std::fs::write(&file, &content).unwrap();
+        group.bench_with_input(BenchmarkId::new("from file", size), &file, |b, file| {
+            b.iter(|| task::load_tasks_from_file(black_box(file)))
+        });
+    }
+    group.finish();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// The TUI as it opens on `size` tasks, without reading tasks.md or anything else.
+fn app(size: usize) -> tui::App {
+    let mut app = tui::App::default();
+    app.tasks = task::parse_tasks(&tasks_md(size));
+    app
+}
+
+fn group(c: &mut Criterion) {
+    let mut group = c.benchmark_group("group");
+    for size in SIZES {
+        let app = app(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::new("by deadline", size), &app, |b, app| b.iter(|| app.get_grouped_tasks()));
+        group.bench_with_input(BenchmarkId::new("by project", size), &app, |b, app| b.iter(|| app.get_project_groups()));
+        group.bench_with_input(BenchmarkId::new("display rows", size), &app, |b, app| b.iter(|| app.get_display_rows()));
+    }
+    group.finish();
+}
+
+fn draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw");
+    for size in SIZES {
+        let mut app = app(size);
+        app.list_state.select(Some(0));
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_function(BenchmarkId::new("task list", size), |b| {
+            b.iter(|| {
+                terminal.draw(|f| tui::ui(f, &mut app)).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, load, group, draw);
+criterion_main!(benches);
@@ -0,0 +1,100 @@
+use crate::attachments;
+use crate::encryption;
+use crate::task::Task;
+use crate::subtasks;
+use chrono::NaiveDate;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub fn archive_path(tasks_dir: &Path) -> PathBuf {
+    encryption::file_in(tasks_dir, "archive.md")
+}
+
+/// Splits off the completed tasks whose `done` date is more than `after_days` days
+/// before `today`. Subtasks only move together with their parent, and only when the
+/// whole subtree qualifies, so neither file ends up with orphans.
+pub fn split_archivable(tasks: Vec<Task>, today: NaiveDate, after_days: u32) -> (Vec<Task>, Vec<Task>) {
+    let is_old = |task: &Task| {
+        task.completed && task.done.is_some_and(|done| (today - done).num_days() > after_days as i64)
+    };
+
+    let mut archive = vec![false; tasks.len()];
+    for index in 0..tasks.len() {
+        if tasks[index].indent_level != 0 {
+            continue;
+        }
+        let subtree = index..subtasks::subtask_range(&tasks, index).end;
+        if tasks[subtree.clone()].iter().all(is_old) {
+            archive[subtree].iter_mut().for_each(|a| *a = true);
+        }
+    }
+
+    let (archived, kept): (Vec<_>, Vec<_>) = tasks.into_iter().zip(archive).partition(|(_, archive)| *archive);
+    (
+        kept.into_iter().map(|(task, _)| task).collect(),
+        archived.into_iter().map(|(task, _)| task).collect(),
+    )
+}
+
+/// Appends tasks to archive.md, creating it with a heading the first time, and moves
+/// their attachments along with them.
+pub fn append_to_archive(tasks_dir: &Path, tasks: &[Task]) -> io::Result<()> {
+    let path = archive_path(tasks_dir);
+    let mut content = encryption::read(&path).unwrap_or_else(|_| "# archive\n\n".to_string());
+    for task in tasks {
+        content.push_str(&task.to_markdown());
+        content.push('\n');
+    }
+    encryption::write(&path, &content)?;
+    for task in tasks {
+        attachments::archive_attachments(tasks_dir, &task.id)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_old_finished_subtrees_are_archived() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 30).unwrap();
+        let task = |line: &str, level: usize| {
+            let mut task = Task::parse(line);
+            task.completed = task.done.is_some();
+            task.indent_level = level;
+            task
+        };
+        let mut tasks = vec![
+            task("old trip [done:2025-05-01]", 0),
+            task("old flights [done:2025-04-20]", 1),
+            task("mixed project [done:2025-05-01]", 0),
+            task("recent part [done:2025-06-29]", 1),
+            task("open", 0),
+            task("done without date", 0),
+        ];
+        tasks[5].completed = true;
+
+        let (kept, archived) = split_archivable(tasks, today, 30);
+        let texts = |tasks: &[Task]| tasks.iter().map(|t| t.text.clone()).collect::<Vec<_>>();
+        assert_eq!(texts(&archived), ["old trip", "old flights"]);
+        assert_eq!(texts(&kept), ["mixed project", "recent part", "open", "done without date"]);
+    }
+
+    #[test]
+    fn test_archived_tasks_take_their_attachments_along() {
+        let tasks_dir = tempfile::tempdir().unwrap();
+        let tasks_dir = tasks_dir.path();
+        let source = tasks_dir.join("receipt.pdf");
+        std::fs::write(&source, "paid").unwrap();
+        attachments::attach_file(tasks_dir, "aaaa01", &source).unwrap();
+
+        let task = Task::parse("pay rent [attach:receipt.pdf] [id:aaaa01]");
+        append_to_archive(tasks_dir, &[task, Task::parse("no attachments [id:aaaa02]")]).unwrap();
+
+        assert!(!attachments::attachments_dir(tasks_dir, "aaaa01").exists());
+        let archived = attachments::archived_attachments_dir(tasks_dir, "aaaa01").join("receipt.pdf");
+        assert_eq!(std::fs::read_to_string(archived).unwrap(), "paid");
+        assert!(encryption::read(&archive_path(tasks_dir)).unwrap().contains("pay rent"));
+    }
+}
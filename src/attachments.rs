@@ -0,0 +1,181 @@
+use crate::task::Task;
+use regex::Regex;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+
+/// A `[label](target)` link in the text or notes of a task.
+static MARKDOWN_LINK_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[([^\[\]]*)\]\(([^()\s]+)\)").unwrap());
+
+/// Something a task points at: a file copied in with `yarmtl attach`, a path or URL
+/// in an `[attach:...]` marker, or a markdown link in the text or notes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub label: String,
+    /// URL or path, as handed to the opener
+    pub target: String,
+}
+
+/// Attachments live next to tasks.md, one folder per task id.
+pub fn attachments_dir(tasks_dir: &Path, task_id: &str) -> PathBuf {
+    tasks_dir.join("attachments").join(task_id)
+}
+
+/// The attachments of archived tasks are moved next to archive.md, under `archive/`.
+pub fn archived_attachments_dir(tasks_dir: &Path, task_id: &str) -> PathBuf {
+    tasks_dir.join("archive").join(task_id)
+}
+
+/// Copies `file` into the task's attachments folder and returns the name it was stored under.
+pub fn attach_file(tasks_dir: &Path, task_id: &str, file: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    if !file.is_file() {
+        return Err(format!("{} is not a file", file.display()).into());
+    }
+
+    let name = file
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format!("{} has no usable file name", file.display()))?
+        .to_string();
+
+    // The name ends up inside an [attach:...] marker in tasks.md
+    if name.contains(']') {
+        return Err(format!("file names containing ']' can't be attached: {}", name).into());
+    }
+
+    let dir = attachments_dir(tasks_dir, task_id);
+    let target = dir.join(&name);
+    if target.exists() {
+        return Err(format!("{} is already attached to this task", name).into());
+    }
+
+    fs::create_dir_all(&dir)?;
+    fs::copy(file, &target)?;
+    Ok(name)
+}
+
+/// Everything the task points at, in the order of its line in tasks.md: links in the
+/// text, `[attach:...]` markers, links in the notes.
+pub fn task_attachments(tasks_dir: &Path, task: &Task) -> Vec<Attachment> {
+    let links = |text: &str| -> Vec<Attachment> {
+        MARKDOWN_LINK_RE
+            .captures_iter(text)
+            .map(|c| Attachment {
+                label: if c[1].is_empty() { c[2].to_string() } else { c[1].to_string() },
+                target: resolve_target(tasks_dir, &c[2]),
+            })
+            .collect()
+    };
+
+    let mut all = links(&task.text);
+    all.extend(task.attachments.iter().map(|name| Attachment {
+        label: name.clone(),
+        target: resolve_target(&attachments_dir(tasks_dir, &task.id), name),
+    }));
+    if let Some(notes) = &task.notes {
+        all.extend(links(notes));
+    }
+    all
+}
+
+/// URLs and absolute or `~/` paths are kept, anything else is relative to `base`.
+fn resolve_target(base: &Path, target: &str) -> String {
+    if target.contains("://") || target.starts_with("mailto:") || Path::new(target).is_absolute() {
+        target.to_string()
+    } else if let Some(rest) = target.strip_prefix("~/") {
+        PathBuf::from(env::var("HOME").unwrap_or_default()).join(rest).display().to_string()
+    } else {
+        base.join(target).display().to_string()
+    }
+}
+
+/// Opens a URL or file with the platform's default application, without waiting for it.
+pub fn open(target: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reap it once it exits, the opener usually hands off to another process right away
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Deletes the task's attachments folder, if it has one.
+pub fn remove_attachments(tasks_dir: &Path, task_id: &str) -> io::Result<()> {
+    let dir = attachments_dir(tasks_dir, task_id);
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// Moves the task's attachments folder, if it has one, to where archived tasks keep theirs.
+pub fn archive_attachments(tasks_dir: &Path, task_id: &str) -> io::Result<()> {
+    let dir = attachments_dir(tasks_dir, task_id);
+    if !dir.exists() {
+        return Ok(());
+    }
+    let target = archived_attachments_dir(tasks_dir, task_id);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(dir, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attach_and_remove() {
+        let temp = tempfile::tempdir().unwrap();
+        let tasks_dir = temp.path();
+        let source = tasks_dir.join("notes.txt");
+        fs::write(&source, "hello").unwrap();
+
+        let name = attach_file(tasks_dir, "abcd1234", &source).unwrap();
+        assert_eq!(name, "notes.txt");
+        assert!(attachments_dir(tasks_dir, "abcd1234").join("notes.txt").exists());
+
+        // Attaching the same name twice would silently overwrite the first copy
+        assert!(attach_file(tasks_dir, "abcd1234", &source).is_err());
+
+        remove_attachments(tasks_dir, "abcd1234").unwrap();
+        assert!(!attachments_dir(tasks_dir, "abcd1234").exists());
+    }
+
+    #[test]
+    fn test_links_and_references() {
+        let tasks_dir = Path::new("/tasks");
+        let task = Task::parse(
+            "read [the spec](https://example.com//spec#intro) [attach:~/docs/a b.pdf] [attach:notes.txt] [id:abcd1234] //also [](docs/faq.md)",
+        );
+        assert_eq!(task.text, "read [the spec](https://example.com//spec#intro)");
+
+        let targets: Vec<(String, String)> = task_attachments(tasks_dir, &task)
+            .into_iter()
+            .map(|a| (a.label, a.target))
+            .collect();
+        let home = env::var("HOME").unwrap_or_default();
+        assert_eq!(targets, [
+            ("the spec".to_string(), "https://example.com//spec#intro".to_string()),
+            ("~/docs/a b.pdf".to_string(), format!("{}/docs/a b.pdf", home)),
+            ("notes.txt".to_string(), "/tasks/attachments/abcd1234/notes.txt".to_string()),
+            ("docs/faq.md".to_string(), "/tasks/docs/faq.md".to_string()),
+        ]);
+    }
+}
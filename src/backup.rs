@@ -0,0 +1,326 @@
+use crate::config::load_config;
+use crate::encryption;
+use crate::error::YarmtlError;
+use crate::git;
+use crate::ids;
+use crate::journal::Journal;
+use crate::paths::{get_email_config_path, get_state_dir, get_sync_dir};
+use crate::setup;
+use chrono::Local;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use toml_edit::{DocumentMut, Item, TableLike, Value};
+
+/// The config files of the tasks directory, backed up without their secrets.
+const CONFIG_FILES: [&str; 5] = ["config.toml", "todoist_config.toml", "caldav_config.toml", "google_config.toml", "github_config.toml"];
+
+/// email_config.toml is in the config directory rather than in the tasks directory; in
+/// a backup it is at the top with the others.
+const EMAIL_CONFIG: &str = "email_config.toml";
+
+/// What pairs the tasks with the services they sync with, in the state directory. In a
+/// backup they are at the top with the others.
+const SYNC_FILES: [&str; 4] = [".sync_metadata.json", ".caldav_metadata.json", ".google_metadata.json", ".github_metadata.json"];
+
+/// Keys of the config files that hold passwords and tokens. The keyring has the rest.
+const SECRET_KEYS: [&str; 4] = ["password", "token", "secret", "client_secret"];
+
+/// Names of the backups made before something that is hard to undo start with this.
+const AUTOMATIC: &str = "auto-";
+
+/// The `[backup]` table of config.toml.
+///
+/// ```toml
+/// [backup]
+/// keep = 10
+/// sync_deletions = 5
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// How many of the backups made before imports, restores and syncs deleting tasks are kept
+    pub keep: usize,
+    /// A sync deleting at least this many tasks from tasks.md makes a backup first; 0 never does
+    pub sync_deletions: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig { keep: 10, sync_deletions: 5 }
+    }
+}
+
+/// Where the backups are kept, outside the tasks directory so a sync can't take them along.
+pub fn backups_dir() -> PathBuf {
+    setup::data_dir().join("backups")
+}
+
+/// A new file in the backups directory, named after now: `yarmtl-2025-07-01-093000.tar.gz`.
+pub fn new_backup_path(prefix: &str) -> PathBuf {
+    let name = format!("{}yarmtl-{}", prefix, Local::now().format("%Y-%m-%d-%H%M%S"));
+    let dir = backups_dir();
+    let mut path = dir.join(format!("{}.tar.gz", name));
+    let mut taken = 1;
+    while path.exists() {
+        taken += 1;
+        path = dir.join(format!("{}-{}.tar.gz", name, taken));
+    }
+    path
+}
+
+/// The backups there are, the newest first.
+pub fn list_backups() -> Vec<PathBuf> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(backups_dir())
+        .map(|entries| entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect())
+        .unwrap_or_default();
+    backups.retain(|path| path.to_string_lossy().ends_with(".tar.gz"));
+    backups.sort_by_key(|path| std::cmp::Reverse(fs::metadata(path).and_then(|m| m.modified()).ok()));
+    backups
+}
+
+/// Writes a tarball of the tasks directory `tasks_dir` to `output`: the task files, archive
+/// and trash included, the config files without their secrets and the sync metadata of its
+/// state directory `state_dir`, with the email config at `email_config`. Returns the names
+/// of the files in it.
+pub fn write_backup(tasks_dir: &Path, state_dir: &Path, email_config: &Path, output: &Path) -> Result<Vec<String>, YarmtlError> {
+    let mut files: Vec<(String, PathBuf)> = Vec::new();
+    let task_files = encryption::TASK_FILES.iter().flat_map(|name| [name.to_string(), format!("{}.age", name)]);
+    for name in task_files.chain(git::task_files(tasks_dir)).chain([ids::FILE_NAME.to_string()]) {
+        if !files.iter().any(|(taken, _)| *taken == name) {
+            files.push((name.clone(), tasks_dir.join(&name)));
+        }
+    }
+    files.extend(SYNC_FILES.map(|name| (name.to_string(), state_dir.join(name))));
+    let configs = CONFIG_FILES.map(|name| (name.to_string(), tasks_dir.join(name)));
+    files.extend(configs.into_iter().chain([(EMAIL_CONFIG.to_string(), email_config.to_path_buf())]));
+
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(dir).map_err(YarmtlError::io("create", dir))?;
+    }
+    let file = fs::File::create(output).map_err(YarmtlError::io("write", output))?;
+    let mut tarball = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mut written = Vec::new();
+    for (name, path) in files {
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(YarmtlError::io("read", &path)(e)),
+        };
+        let content = match name.ends_with(".toml") {
+            true => without_secrets(&path, &content)?,
+            false => content,
+        };
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(Local::now().timestamp() as u64);
+        header.set_cksum();
+        tarball.append_data(&mut header, &name, content.as_slice()).map_err(YarmtlError::io("write", output))?;
+        written.push(name);
+    }
+    tarball.into_inner().and_then(|gzip| gzip.finish()).map_err(YarmtlError::io("write", output))?;
+    Ok(written)
+}
+
+/// Puts the files of the backup at `archive` back into `tasks_dir`, the sync metadata into
+/// `state_dir` and email_config.toml at `email_config`. A config file keeps the secrets it has here, since the backup has
+/// none. Returns the names of the files restored.
+pub fn restore_backup(archive: &Path, tasks_dir: &Path, state_dir: &Path, email_config: &Path) -> Result<Vec<String>, YarmtlError> {
+    // All of it is read before anything is written, so a broken backup changes nothing
+    let file = fs::File::open(archive).map_err(YarmtlError::io("read", archive))?;
+    let mut tarball = tar::Archive::new(GzDecoder::new(file));
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    for entry in tarball.entries().map_err(YarmtlError::io("read", archive))? {
+        let mut entry = entry.map_err(YarmtlError::io("read", archive))?;
+        let name = entry.path().map_err(YarmtlError::io("read", archive))?.to_string_lossy().to_string();
+        if !is_backed_up(&name) {
+            return Err(YarmtlError::parse(archive, format!("a yarmtl backup doesn't hold {}", name)));
+        }
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).map_err(YarmtlError::io("read", archive))?;
+        files.push((name, content));
+    }
+
+    let path_of = |name: &str| match name {
+        EMAIL_CONFIG => email_config.to_path_buf(),
+        name if SYNC_FILES.contains(&name) => state_dir.join(name),
+        name => tasks_dir.join(name),
+    };
+    // tasks.md.age would be read instead of a restored tasks.md, and the other way around
+    let other_of = |name: &str| match name.strip_suffix(".age") {
+        Some(plain) => tasks_dir.join(plain),
+        None => tasks_dir.join(format!("{}.age", name)),
+    };
+    let is_tasks = |name: &str| name.ends_with(".md") || name.ends_with(".md.age");
+    let mut written: Vec<PathBuf> = files.iter().map(|(name, _)| path_of(name)).collect();
+    written.extend(files.iter().filter(|(name, _)| is_tasks(name)).map(|(name, _)| other_of(name)));
+    let mut journal = Journal::begin(tasks_dir, "restore", &written)?;
+
+    let mut restored = Vec::new();
+    for (name, content) in files {
+        let path = path_of(&name);
+        let content = match name.ends_with(".toml") && path.exists() {
+            true => with_secrets_of(&path, &content)?,
+            false => content,
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(YarmtlError::io("create", dir))?;
+        }
+        journal.write(&path, &content)?;
+        if is_tasks(&name) {
+            journal.remove(&other_of(&name))?;
+        }
+        restored.push(name);
+    }
+    journal.finish()?;
+    Ok(restored)
+}
+
+/// A backup before something that is hard to undo, `reason` ending up in its name. Only
+/// the newest `keep` of them are kept. Returns where it is.
+pub fn automatic(reason: &str) -> Result<PathBuf, YarmtlError> {
+    let config = load_config().backup;
+    let path = new_backup_path(&format!("{}{}-", AUTOMATIC, reason));
+    write_backup(&get_sync_dir(), &get_state_dir(), &get_email_config_path(), &path)?;
+    let automatic = list_backups().into_iter().filter(|path| {
+        path.file_name().is_some_and(|name| name.to_string_lossy().starts_with(AUTOMATIC))
+    });
+    for old in automatic.skip(config.keep.max(1)) {
+        fs::remove_file(&old).map_err(YarmtlError::io("remove", &old))?;
+    }
+    Ok(path)
+}
+
+/// Whether a backup would have a file by this name: a task file in the tasks directory, a
+/// config file or sync metadata. Nothing else is restored from one.
+fn is_backed_up(name: &str) -> bool {
+    let inside = Path::new(name).components().all(|c| matches!(c, Component::Normal(_)));
+    let known = CONFIG_FILES.contains(&name) || SYNC_FILES.contains(&name) || [EMAIL_CONFIG, ids::FILE_NAME].contains(&name);
+    let task_file = (name.ends_with(".md") || name.ends_with(".md.age")) && !name.starts_with(".git");
+    inside && (known || task_file)
+}
+
+/// The TOML config at `path`, holding `content`, with its passwords and tokens taken out.
+/// Its comments and layout stay as they are.
+fn without_secrets(path: &Path, content: &[u8]) -> Result<Vec<u8>, YarmtlError> {
+    let mut config: DocumentMut = String::from_utf8_lossy(content).parse().map_err(|e| YarmtlError::parse(path, e))?;
+    remove_secrets(config.as_table_mut());
+    Ok(config.to_string().into_bytes())
+}
+
+fn remove_secrets(table: &mut dyn TableLike) {
+    let secret: Vec<String> = table.iter().filter(|(key, _)| is_secret(table, key)).map(|(key, _)| key.to_string()).collect();
+    for key in secret {
+        table.remove(&key);
+    }
+    for (_, item) in table.iter_mut() {
+        tables_mut(item).into_iter().for_each(remove_secrets);
+    }
+}
+
+/// Whether `key` of `table` is a password or a token. The url of a slack or discord
+/// channel is one too: it is what lets anyone post to it.
+fn is_secret(table: &dyn TableLike, key: &str) -> bool {
+    let service = table.get("service").and_then(Item::as_str);
+    SECRET_KEYS.contains(&key) || (key == "url" && service.is_some_and(|service| ["slack", "discord"].contains(&service)))
+}
+
+/// The restored `content` of the config at `path`, with the secrets the config has now put
+/// back in.
+fn with_secrets_of(path: &Path, content: &[u8]) -> Result<Vec<u8>, YarmtlError> {
+    let parse = |bytes: &[u8]| String::from_utf8_lossy(bytes).parse::<DocumentMut>();
+    let mut restored = parse(content).map_err(|e| YarmtlError::parse(path, e))?;
+    // A config that doesn't parse anymore has no secrets worth keeping
+    if let Ok(Ok(current)) = fs::read(path).map(|bytes| parse(&bytes)) {
+        keep_secrets(restored.as_table_mut(), current.as_table());
+    }
+    Ok(restored.to_string().into_bytes())
+}
+
+fn keep_secrets(restored: &mut dyn TableLike, current: &dyn TableLike) {
+    for (key, item) in current.iter() {
+        match restored.get_mut(key) {
+            Some(restored) => {
+                for (restored, current) in tables_mut(restored).into_iter().zip(tables(item)) {
+                    keep_secrets(restored, current);
+                }
+            }
+            None if is_secret(current, key) => {
+                restored.insert(key, item.clone());
+            }
+            None => {}
+        }
+    }
+}
+
+/// The tables `item` is: itself when it is one, those in it when it is an array of them.
+fn tables(item: &Item) -> Vec<&dyn TableLike> {
+    match item {
+        Item::ArrayOfTables(tables) => tables.iter().map(|table| table as &dyn TableLike).collect(),
+        Item::Value(Value::Array(values)) => values.iter().filter_map(|value| value.as_inline_table().map(|table| table as &dyn TableLike)).collect(),
+        item => item.as_table_like().into_iter().collect(),
+    }
+}
+
+fn tables_mut(item: &mut Item) -> Vec<&mut dyn TableLike> {
+    match item {
+        Item::ArrayOfTables(tables) => tables.iter_mut().map(|table| table as &mut dyn TableLike).collect(),
+        Item::Value(Value::Array(values)) => {
+            values.iter_mut().filter_map(|value| value.as_inline_table_mut().map(|table| table as &mut dyn TableLike)).collect()
+        }
+        item => item.as_table_like_mut().into_iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_and_restore_without_secrets() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let tasks_dir = dir.join("tasks");
+        fs::create_dir_all(tasks_dir.join("work")).unwrap();
+        let tasks = "# tasks\n<!-- include: work/tasks.md -->\n- [ ] pay rent [id:aaaa01]\n";
+        fs::write(tasks_dir.join("tasks.md"), tasks).unwrap();
+        fs::write(tasks_dir.join("work/tasks.md"), "- [ ] report [id:bbbb01]\n").unwrap();
+        let state_dir = dir.join("state");
+        fs::create_dir_all(&state_dir).unwrap();
+        fs::write(state_dir.join(".sync_metadata.json"), "{}").unwrap();
+        let config = "# phone\n[[push]]\nservice = \"gotify\"\nserver = \"https://push.example\"\ntoken = \"hunter2\"\n";
+        fs::write(tasks_dir.join("config.toml"), config).unwrap();
+        let archive = dir.join("backup.tar.gz");
+
+        let written = write_backup(&tasks_dir, &state_dir, &dir.join("email_config.toml"), &archive).unwrap();
+        assert_eq!(written, ["tasks.md", "work/tasks.md", ".sync_metadata.json", "config.toml"]);
+        let mut names = Vec::new();
+        let mut tarball = tar::Archive::new(GzDecoder::new(fs::File::open(&archive).unwrap()));
+        for entry in tarball.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            assert!(!content.contains("hunter2"));
+            names.push(entry.path().unwrap().to_string_lossy().to_string());
+        }
+        assert_eq!(names, written);
+
+        // Restored over tasks encrypted since: the config keeps the token it has
+        fs::remove_file(tasks_dir.join("tasks.md")).unwrap();
+        fs::write(tasks_dir.join("tasks.md.age"), "encrypted").unwrap();
+        fs::write(tasks_dir.join("config.toml"), config.replace("hunter2", "correct-horse")).unwrap();
+        fs::remove_file(state_dir.join(".sync_metadata.json")).unwrap();
+        restore_backup(&archive, &tasks_dir, &state_dir, &dir.join("email_config.toml")).unwrap();
+        assert_eq!(fs::read_to_string(state_dir.join(".sync_metadata.json")).unwrap(), "{}");
+        assert_eq!(fs::read_to_string(tasks_dir.join("tasks.md")).unwrap(), tasks);
+        assert!(!tasks_dir.join("tasks.md.age").exists());
+        assert_eq!(fs::read_to_string(tasks_dir.join("config.toml")).unwrap(), config.replace("hunter2", "correct-horse"));
+
+        assert!(!is_backed_up("../tasks.md") && !is_backed_up(".git/hooks/pre-commit") && !is_backed_up("notes.txt"));
+    }
+}
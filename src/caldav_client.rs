@@ -0,0 +1,550 @@
+use crate::clock;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use reqwest::{Client, Method, RequestBuilder, Response, Url};
+use std::time::Duration;
+use thiserror::Error;
+
+const DAV_NS: &str = "DAV:";
+const CALDAV_NS: &str = "urn:ietf:params:xml:ns:caldav";
+
+/// Asks for every task of the list with its ETag and iCalendar data.
+const LIST_TASKS_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/><c:calendar-data/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"/></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+
+/// Asks for the events of a calendar that happen between two UTC times, `{start}` and `{end}`.
+const LIST_EVENTS_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><c:calendar-data/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT">
+    <c:time-range start="{start}" end="{end}"/>
+  </c:comp-filter></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+
+/// Properties of a VTODO that are written from yarmtl's side; the others are kept as
+/// the server has them.
+const MANAGED: &[&str] = &[
+    "DTSTAMP",
+    "LAST-MODIFIED",
+    "SUMMARY",
+    "DESCRIPTION",
+    "CATEGORIES",
+    "PRIORITY",
+    "STATUS",
+];
+
+/// iCalendar's UTC date-time, as in DTSTAMP and COMPLETED.
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Error, Debug)]
+pub enum CalDavError {
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error("Authentication failed, check the username and password")]
+    Auth,
+
+    #[error("Server error: {status} - {message}")]
+    Server { status: u16, message: String },
+
+    #[error("Unreadable answer from the server: {0}")]
+    Xml(#[from] roxmltree::Error),
+
+    #[error("Not a URL: {0}")]
+    Url(String),
+}
+
+impl CalDavError {
+    /// The server is failing or out of reach, so the rest of a sync would fail the same way.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            CalDavError::Server { status, .. } => *status >= 500,
+            CalDavError::Network(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+}
+
+/// A task of a CalDAV task list: the VTODO of one calendar object, with what yarmtl reads from it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VTodo {
+    /// Path of the calendar object on the server
+    pub href: String,
+    /// Version of the object as of listing it, None for one not on the server yet
+    pub etag: Option<String>,
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    /// Only the day of DUE, its time if any is kept on the server as long as the day stays
+    pub due: Option<NaiveDate>,
+    pub categories: Vec<String>,
+    /// 1 (highest) to 9, None when undefined
+    pub priority: Option<u8>,
+    pub completed: bool,
+    /// UID of the task this is a subtask of
+    pub parent_uid: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+    /// The calendar object as the server sent it, for the properties yarmtl doesn't know
+    pub raw: Option<String>,
+}
+
+impl VTodo {
+    /// Reads the first VTODO of an iCalendar object.
+    pub fn parse(ics: &str) -> Option<VTodo> {
+        let mut todo = VTodo { raw: Some(ics.to_string()), ..VTodo::default() };
+        let mut found = false;
+        // Components inside the VTODO, like VALARM, have properties of their own
+        let mut nested = 0;
+        let mut status = None;
+        let mut completed_at = false;
+        for line in unfold(ics) {
+            let (name, params, value) = split_line(&line);
+            if !found {
+                found = name == "BEGIN" && value.eq_ignore_ascii_case("VTODO");
+                continue;
+            }
+            match name.as_str() {
+                "BEGIN" => nested += 1,
+                "END" if nested > 0 => nested -= 1,
+                "END" => break,
+                _ if nested > 0 => {}
+                "UID" => todo.uid = value.to_string(),
+                "SUMMARY" => todo.summary = unescape(value),
+                "DESCRIPTION" => todo.description = Some(unescape(value)).filter(|d| !d.is_empty()),
+                // In UTC it is moved to the local day, in a TZID taken as it is
+                "DUE" => todo.due = clock::local_day(value),
+                "CATEGORIES" => todo.categories.extend(split_list(value)),
+                "PRIORITY" => todo.priority = value.trim().parse().ok().filter(|p| (1..=9).contains(p)),
+                "STATUS" => status = Some(value.trim().to_ascii_uppercase()),
+                "COMPLETED" => completed_at = true,
+                "RELATED-TO" if is_parent_relation(&params) => todo.parent_uid = Some(value.to_string()),
+                "LAST-MODIFIED" => {
+                    todo.last_modified = NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT).ok().map(|t| t.and_utc())
+                }
+                _ => {}
+            }
+        }
+        todo.completed = match status.as_deref() {
+            Some(status) => status == "COMPLETED",
+            None => completed_at,
+        };
+        found.then_some(todo)
+    }
+
+    /// The calendar object to send to the server. A task read from it keeps everything
+    /// yarmtl doesn't manage, a new one is a calendar of its own.
+    pub fn to_ics(&self) -> String {
+        let now = Utc::now().format(TIMESTAMP_FORMAT).to_string();
+        let lines = match &self.raw {
+            Some(raw) => self.update_lines(raw, &now),
+            None => {
+                let mut lines = vec![
+                    "BEGIN:VCALENDAR".to_string(),
+                    "VERSION:2.0".to_string(),
+                    "PRODID:-//yarmtl//yarmtl//EN".to_string(),
+                    "BEGIN:VTODO".to_string(),
+                    format!("UID:{}", self.uid),
+                    format!("CREATED:{}", now),
+                ];
+                lines.extend(self.managed_lines(&now));
+                if let Some(due) = self.due {
+                    lines.push(format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+                }
+                if self.completed {
+                    lines.push(format!("COMPLETED:{}", now));
+                    lines.push("PERCENT-COMPLETE:100".to_string());
+                }
+                lines.extend(["END:VTODO".to_string(), "END:VCALENDAR".to_string()]);
+                lines
+            }
+        };
+        lines.iter().map(|line| fold(line) + "\r\n").collect()
+    }
+
+    /// `raw` with the managed properties of its VTODO written anew.
+    fn update_lines(&self, raw: &str, now: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        let (mut in_todo, mut done) = (false, false);
+        let mut nested = 0;
+        let (mut kept_due, mut kept_completed) = (false, false);
+        for line in unfold(raw) {
+            let (name, params, value) = split_line(&line);
+            if !in_todo {
+                in_todo = !done && name == "BEGIN" && value.eq_ignore_ascii_case("VTODO");
+                lines.push(line);
+                continue;
+            }
+            match name.as_str() {
+                "BEGIN" => nested += 1,
+                "END" if nested > 0 => nested -= 1,
+                "END" => {
+                    lines.extend(self.managed_lines(now));
+                    if let Some(due) = self.due.filter(|_| !kept_due) {
+                        lines.push(format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+                    }
+                    if self.completed && !kept_completed {
+                        lines.push(format!("COMPLETED:{}", now));
+                    }
+                    if self.completed {
+                        lines.push("PERCENT-COMPLETE:100".to_string());
+                    }
+                    lines.push(line);
+                    (in_todo, done) = (false, true);
+                    continue;
+                }
+                _ if nested > 0 => {}
+                _ if MANAGED.contains(&name.as_str()) => continue,
+                "RELATED-TO" if is_parent_relation(&params) => continue,
+                // Its time stays as long as the day is the same
+                "DUE" => {
+                    kept_due = self.due.is_some_and(|due| value.starts_with(&due.format("%Y%m%d").to_string()));
+                    if !kept_due {
+                        continue;
+                    }
+                }
+                // When the task was completed, for as long as it is
+                "COMPLETED" if !self.completed => continue,
+                "COMPLETED" => kept_completed = true,
+                // 100 once completed, and no longer once reopened
+                "PERCENT-COMPLETE" if self.completed || value.trim() == "100" => continue,
+                _ => {}
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn managed_lines(&self, now: &str) -> Vec<String> {
+        let mut lines = vec![
+            format!("DTSTAMP:{}", now),
+            format!("LAST-MODIFIED:{}", now),
+            format!("SUMMARY:{}", escape(&self.summary)),
+        ];
+        if let Some(description) = &self.description {
+            lines.push(format!("DESCRIPTION:{}", escape(description)));
+        }
+        if !self.categories.is_empty() {
+            let categories: Vec<String> = self.categories.iter().map(|c| escape(c)).collect();
+            lines.push(format!("CATEGORIES:{}", categories.join(",")));
+        }
+        if let Some(priority) = self.priority {
+            lines.push(format!("PRIORITY:{}", priority));
+        }
+        lines.push(format!("STATUS:{}", if self.completed { "COMPLETED" } else { "NEEDS-ACTION" }));
+        if let Some(parent_uid) = &self.parent_uid {
+            lines.push(format!("RELATED-TO;RELTYPE=PARENT:{}", parent_uid));
+        }
+        lines
+    }
+}
+
+/// The logical lines of an iCalendar object, the folded ones joined back up.
+pub fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Folds a line into parts of at most 75 octets, as iCalendar wants, never within a character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+/// A content line's upper-cased name, its parameters and its value. Quoted parameter
+/// values may hold the `:` and `;` that otherwise separate them.
+pub fn split_line(line: &str) -> (String, Vec<&str>, &str) {
+    let mut quoted = false;
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                parts.push(&line[start..i]);
+                start = i + 1;
+            }
+            ':' if !quoted => {
+                parts.push(&line[start..i]);
+                let name = parts.remove(0).to_ascii_uppercase();
+                return (name, parts, &line[i + 1..]);
+            }
+            _ => {}
+        }
+    }
+    (line.to_ascii_uppercase(), Vec::new(), "")
+}
+
+/// RELATED-TO points at the parent unless its RELTYPE says otherwise.
+fn is_parent_relation(params: &[&str]) -> bool {
+    params
+        .iter()
+        .filter_map(|p| p.split_once('='))
+        .find(|(name, _)| name.eq_ignore_ascii_case("RELTYPE"))
+        .is_none_or(|(_, value)| value.eq_ignore_ascii_case("PARENT"))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+pub fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(escaped) => text.push(escaped),
+            None => {}
+        }
+    }
+    text
+}
+
+/// The values of a list like CATEGORIES, split at the commas that aren't escaped.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                items.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    items
+        .into_iter()
+        .map(|item| unescape(item.trim()))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Reads the tasks out of the multistatus answer to a calendar-query.
+pub fn parse_multistatus(xml: &str) -> Result<Vec<VTodo>, CalDavError> {
+    let todos = calendar_objects(xml)?
+        .into_iter()
+        .filter_map(|(href, etag, data)| VTodo::parse(&data).map(|todo| VTodo { href, etag, ..todo }))
+        .collect();
+    Ok(todos)
+}
+
+/// The href, ETag and iCalendar data of each calendar object in a multistatus answer.
+fn calendar_objects(xml: &str) -> Result<Vec<(String, Option<String>, String)>, CalDavError> {
+    let document = roxmltree::Document::parse(xml)?;
+    let is = |node: &roxmltree::Node, namespace: &str, name: &str| {
+        node.is_element() && node.tag_name().namespace() == Some(namespace) && node.tag_name().name() == name
+    };
+
+    let mut objects = Vec::new();
+    for response in document.descendants().filter(|n| is(n, DAV_NS, "response")) {
+        let text = |namespace: &str, name: &str| {
+            response
+                .descendants()
+                .find(|n| is(n, namespace, name))
+                .and_then(|n| n.text())
+                .map(|t| t.trim().to_string())
+        };
+        if let (Some(href), Some(data)) = (text(DAV_NS, "href"), text(CALDAV_NS, "calendar-data")) {
+            objects.push((href, text(DAV_NS, "getetag"), data));
+        }
+    }
+    Ok(objects)
+}
+
+/// Talks to one task list (a calendar collection holding VTODOs) of a CalDAV server.
+pub struct CalDavClient {
+    client: Client,
+    /// The collection, ending in a `/` so hrefs resolve against it
+    url: Url,
+    username: String,
+    password: String,
+}
+
+impl CalDavClient {
+    pub fn new(url: &str, username: String, password: String) -> Result<Self, CalDavError> {
+        let url = match url.ends_with('/') {
+            true => url.to_string(),
+            false => format!("{}/", url),
+        };
+        let url = Url::parse(&url).map_err(|_| CalDavError::Url(url))?;
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Ok(CalDavClient { client, url, username, password })
+    }
+
+    /// Where a new task named `name` goes in the collection.
+    pub fn href_for(&self, name: &str) -> String {
+        match self.url.join(&format!("{}.ics", name)) {
+            Ok(url) => url.path().to_string(),
+            Err(_) => format!("{}{}.ics", self.url.path(), name),
+        }
+    }
+
+    fn request(&self, method: Method, href: &str) -> Result<RequestBuilder, CalDavError> {
+        let url = self.url.join(href).map_err(|_| CalDavError::Url(href.to_string()))?;
+        Ok(self.client.request(method, url).basic_auth(&self.username, Some(&self.password)))
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response, CalDavError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            return Err(CalDavError::Auth);
+        }
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(CalDavError::Server { status: status.as_u16(), message });
+        }
+        Ok(response)
+    }
+
+    /// Every task of the list, completed ones included.
+    pub async fn list_tasks(&self) -> Result<Vec<VTodo>, CalDavError> {
+        let report = Method::from_bytes(b"REPORT").expect("REPORT is a valid method");
+        let request = self
+            .request(report, "")?
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(LIST_TASKS_QUERY);
+        let body = self.send(request).await?.text().await?;
+        parse_multistatus(&body)
+    }
+
+    /// The iCalendar data of the events happening between `start` and `end`, for a
+    /// calendar rather than a task list.
+    pub async fn list_events(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<String>, CalDavError> {
+        let report = Method::from_bytes(b"REPORT").expect("REPORT is a valid method");
+        let query = LIST_EVENTS_QUERY
+            .replace("{start}", &start.format(TIMESTAMP_FORMAT).to_string())
+            .replace("{end}", &end.format(TIMESTAMP_FORMAT).to_string());
+        let request = self
+            .request(report, "")?
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(query);
+        let body = self.send(request).await?.text().await?;
+        Ok(calendar_objects(&body)?.into_iter().map(|(_, _, data)| data).collect())
+    }
+
+    /// Writes a task, only over the version listed when it has an `etag` and only where
+    /// there is none yet when it doesn't, so edits made meanwhile aren't overwritten.
+    pub async fn put_task(&self, todo: &VTodo) -> Result<(), CalDavError> {
+        let request = self
+            .request(Method::PUT, &todo.href)?
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            .body(todo.to_ics());
+        let request = match &todo.etag {
+            Some(etag) => request.header("If-Match", etag),
+            None => request.header("If-None-Match", "*"),
+        };
+        self.send(request).await?;
+        Ok(())
+    }
+
+    pub async fn delete_task(&self, todo: &VTodo) -> Result<(), CalDavError> {
+        let mut request = self.request(Method::DELETE, &todo.href)?;
+        if let Some(etag) = &todo.etag {
+            request = request.header("If-Match", etag);
+        }
+        self.send(request).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vtodo_keeps_what_it_doesnt_manage() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:nc-1\r\nSUMMARY:call bob\\, then \r\n alice\r\n\
+                   CATEGORIES:work,ctx_phone\r\nDUE;TZID=Europe/Berlin:20250610T170000\r\nPRIORITY:7\r\n\
+                   X-APPLE-SORT-ORDER:42\r\nRELATED-TO;RELTYPE=SIBLING:nc-0\r\nRELATED-TO:nc-parent\r\n\
+                   BEGIN:VALARM\r\nDESCRIPTION:alarm\r\nEND:VALARM\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let todo = VTodo::parse(ics).unwrap();
+        assert_eq!(todo.summary, "call bob, then alice");
+        assert_eq!(todo.categories, ["work", "ctx_phone"]);
+        assert_eq!(todo.due, NaiveDate::from_ymd_opt(2025, 6, 10));
+        assert_eq!(todo.priority, Some(7));
+        assert_eq!(todo.parent_uid.as_deref(), Some("nc-parent"));
+        assert_eq!(todo.description, None);
+        assert!(!todo.completed);
+
+        let edited = VTodo { summary: "call bob; twice".to_string(), completed: true, parent_uid: None, ..todo };
+        let written = edited.to_ics();
+        assert!(written.contains("SUMMARY:call bob\\; twice\r\n"));
+        assert!(written.contains("DUE;TZID=Europe/Berlin:20250610T170000"));
+        assert!(written.contains("X-APPLE-SORT-ORDER:42"));
+        assert!(written.contains("RELATED-TO;RELTYPE=SIBLING:nc-0"));
+        assert!(!written.contains("nc-parent"));
+        assert!(written.contains("BEGIN:VALARM\r\nDESCRIPTION:alarm\r\nEND:VALARM"));
+
+        let reread = VTodo::parse(&written).unwrap();
+        assert!(reread.completed);
+        assert_eq!((reread.summary.as_str(), reread.due), ("call bob; twice", edited.due));
+
+        let long = VTodo { summary: "é".repeat(60), ..VTodo::default() };
+        assert!(long.to_ics().split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(VTodo::parse(&long.to_ics()).unwrap().summary, long.summary);
+    }
+
+    #[test]
+    fn test_multistatus() {
+        let xml = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+              <d:response>
+                <d:href>/dav/tasks/a.ics</d:href>
+                <d:propstat><d:prop>
+                  <d:getetag>"1"</d:getetag>
+                  <cal:calendar-data><![CDATA[BEGIN:VCALENDAR
+BEGIN:VTODO
+UID:a
+SUMMARY:water plants
+STATUS:COMPLETED
+END:VTODO
+END:VCALENDAR
+]]></cal:calendar-data>
+                </d:prop></d:propstat>
+              </d:response>
+              <d:response><d:href>/dav/tasks/</d:href></d:response>
+            </d:multistatus>"#;
+        let todos = parse_multistatus(xml).unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].href, "/dav/tasks/a.ics");
+        assert_eq!(todos[0].etag.as_deref(), Some("\"1\""));
+        assert!(todos[0].completed);
+    }
+}
@@ -0,0 +1,317 @@
+use crate::caldav_client::{CalDavClient, CalDavError, VTodo};
+use crate::hash::StableHasher;
+use crate::ids;
+use crate::paths::sync_config_path;
+use crate::sync_engine::{ConflictStrategy, RemoteTask, SyncContext, SyncProgress, SyncProvider, CONTEXT_LABEL_PREFIX};
+use crate::sync_metadata::SyncMetadata;
+use crate::task::Task;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// The server, account and app password, see `paths::sync_config_path`.
+pub const CONFIG_FILE: &str = "caldav_config.toml";
+
+/// The UIDs of tasks created from yarmtl are their id with this after it, so a task
+/// the pairing lost still finds its way back.
+const UID_SUFFIX: &str = "@yarmtl";
+
+/// caldav_config.toml: the task list to sync with.
+///
+/// ```toml
+/// url = "https://cloud.example.com/remote.php/dav/calendars/me/tasks/"
+/// username = "me"
+/// password = "an app password"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CalDavConfig {
+    /// The task list's collection
+    pub url: String,
+    pub username: String,
+    /// An app password, on servers that have them
+    pub password: String,
+    #[serde(default)]
+    pub conflict_strategy: ConflictStrategy,
+}
+
+impl CalDavConfig {
+    /// None until caldav_config.toml is written.
+    pub fn load(sync_dir: &Path) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let path = sync_config_path(sync_dir, CONFIG_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(toml::from_str(&fs::read_to_string(path)?)?))
+    }
+}
+
+/// A CalDAV task list for the `SyncEngine`: deadlines are DUE, tags and contexts
+/// CATEGORIES, importance PRIORITY and subtasks point at their parent with RELATED-TO.
+pub struct CalDavProvider {
+    client: CalDavClient,
+}
+
+impl CalDavProvider {
+    pub fn new(config: &CalDavConfig) -> Result<Self, CalDavError> {
+        let client = CalDavClient::new(&config.url, config.username.clone(), config.password.clone())?;
+        Ok(CalDavProvider { client })
+    }
+}
+
+impl SyncProvider for CalDavProvider {
+    type Remote = VTodo;
+    type Error = CalDavError;
+
+    /// Pairs of tasks.md's tasks with the hrefs of their CalDAV tasks, kept apart from Todoist's
+    const METADATA_FILE: &'static str = ".caldav_metadata.json";
+    const SUBTASKS: bool = true;
+    /// Subtasks have no order on CalDAV
+    const SUBTASK_ORDER: bool = false;
+    const LISTS_COMPLETED: bool = true;
+    const DELETES: bool = true;
+
+    fn is_unavailable(error: &CalDavError) -> bool {
+        error.is_unavailable()
+    }
+
+    async fn fetch(
+        &mut self,
+        _metadata: &mut SyncMetadata,
+        _local_tasks: &[Task],
+        on_progress: &mut impl FnMut(SyncProgress),
+    ) -> Result<Vec<VTodo>, CalDavError> {
+        on_progress(SyncProgress::FetchingTasks);
+        self.client.list_tasks().await
+    }
+
+    async fn create(&mut self, task: &Task, context: &SyncContext<'_, VTodo>) -> Result<VTodo, CalDavError> {
+        let todo = VTodo { href: self.client.href_for(&task.id), ..to_vtodo(task, parent_uid(task, context), None) };
+        self.client.put_task(&todo).await?;
+        Ok(todo)
+    }
+
+    /// Written over the version listed, so edits made there meanwhile aren't overwritten.
+    async fn update(&mut self, href: &str, task: &Task, context: &SyncContext<'_, VTodo>) -> Result<VTodo, CalDavError> {
+        let existing = context.remote_tasks.get(href);
+        let todo = VTodo { href: href.to_string(), ..to_vtodo(task, parent_uid(task, context), existing) };
+        self.client.put_task(&todo).await?;
+        Ok(todo)
+    }
+
+    async fn complete(&mut self, todo: &mut VTodo, completed: bool) -> Result<(), CalDavError> {
+        let updated = VTodo { completed, ..todo.clone() };
+        self.client.put_task(&updated).await?;
+        *todo = updated;
+        Ok(())
+    }
+
+    async fn delete(&mut self, href: &str, context: &SyncContext<'_, VTodo>) -> Result<(), CalDavError> {
+        match context.remote_tasks.get(href) {
+            Some(todo) => self.client.delete_task(todo).await,
+            None => Ok(()),
+        }
+    }
+
+    fn to_task(&self, todo: &VTodo) -> Task {
+        to_task(todo)
+    }
+
+    /// Only what CalDAV carries is taken.
+    fn pull(&self, local: &Task, todo: &VTodo) -> Task {
+        let pulled = to_task(todo);
+        Task {
+            text: pulled.text,
+            deadline: pulled.deadline,
+            tags: pulled.tags,
+            contexts: pulled.contexts,
+            notes: pulled.notes,
+            importance: pulled.importance,
+            ..local.clone()
+        }
+    }
+
+    fn remote_hash(&self, todo: &VTodo) -> String {
+        compute_remote_hash(todo)
+    }
+
+    /// RELATED-TO names the parent by its UID.
+    fn remote_parent(&self, todo: &VTodo, remote_tasks: &[&VTodo]) -> Option<String> {
+        let uid = todo.parent_uid.as_deref()?;
+        remote_tasks.iter().find(|t| t.uid == uid).map(|parent| parent.href.clone())
+    }
+}
+
+impl RemoteTask for VTodo {
+    const SERVICE: &'static str = "CalDAV";
+
+    fn title(&self) -> &str {
+        &self.summary
+    }
+
+    fn remote_id(&self) -> Option<&str> {
+        Some(&self.href)
+    }
+
+    fn yarmtl_id(&self) -> Option<String> {
+        yarmtl_id_of(&self.uid).map(str::to_string)
+    }
+
+    fn is_completed(&self) -> bool {
+        self.completed
+    }
+
+    fn modified_at(&self) -> Option<DateTime<Utc>> {
+        self.last_modified
+    }
+}
+
+/// The UID of the remote task paired with the task's parent.
+fn parent_uid(task: &Task, context: &SyncContext<'_, VTodo>) -> Option<String> {
+    task.parent_id
+        .as_ref()
+        .and_then(|id| context.metadata.get_todoist_id(id))
+        .and_then(|href| context.remote_tasks.get(href))
+        .map(|parent| parent.uid.clone())
+}
+
+/// `existing` is the server's version of the task, when it has one.
+fn to_vtodo(task: &Task, parent_uid: Option<String>, existing: Option<&VTodo>) -> VTodo {
+    let categories = task
+        .tags
+        .iter()
+        .cloned()
+        .chain(task.contexts.iter().map(|context| format!("{}{}", CONTEXT_LABEL_PREFIX, context)))
+        .collect();
+    let existing = existing.cloned().unwrap_or_else(|| VTodo { uid: uid_for(&task.id), ..VTodo::default() });
+
+    VTodo {
+        summary: task.text.clone(),
+        description: task.notes.clone(),
+        due: task.deadline,
+        categories,
+        priority: task.importance,
+        completed: task.completed && !task.cancelled,
+        parent_uid,
+        ..existing
+    }
+}
+
+fn to_task(todo: &VTodo) -> Task {
+    let mut tags = Vec::new();
+    let mut contexts = Vec::new();
+    for category in &todo.categories {
+        match category.strip_prefix(CONTEXT_LABEL_PREFIX) {
+            Some(context) if !context.is_empty() => contexts.push(context.to_string()),
+            _ => tags.push(category.clone()),
+        }
+    }
+    // Like Todoist, the server's dates aren't taken, they are when yarmtl first saw the task
+    let today = chrono::Local::now().date_naive();
+
+    Task {
+        id: yarmtl_id_of(&todo.uid).map_or_else(ids::generate, str::to_string),
+        text: todo.summary.clone(),
+        deadline: todo.due,
+        tags,
+        contexts,
+        reminder: None,
+        repeat: None,
+        last_fired: None,
+        completed: todo.completed,
+        cancelled: false,
+        notes: todo.description.clone(),
+        // PRIORITY 6 to 9 are all as unimportant as yarmtl goes
+        importance: todo.priority.map(|p| p.min(5)),
+        indent_level: 0,
+        parent_id: None,
+        project: None,
+        source: None,
+        attachments: Vec::new(),
+        created: Some(today),
+        done: todo.completed.then_some(today),
+        estimate: None,
+        spent: None,
+        pinned: false,
+        flag: None,
+        count: None,
+        warn: None,
+    }
+}
+
+fn uid_for(yarmtl_id: &str) -> String {
+    format!("{}{}", yarmtl_id, UID_SUFFIX)
+}
+
+/// The yarmtl id in the UID of a task created from yarmtl.
+fn yarmtl_id_of(uid: &str) -> Option<&str> {
+    uid.strip_suffix(UID_SUFFIX).filter(|id| ids::is_valid(id))
+}
+
+/// Hash of what yarmtl reads from a CalDAV task, to notice edits made there.
+fn compute_remote_hash(todo: &VTodo) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = StableHasher::default();
+    todo.summary.hash(&mut hasher);
+    todo.description.hash(&mut hasher);
+    todo.due.hash(&mut hasher);
+    let mut categories = todo.categories.clone();
+    categories.sort();
+    categories.hash(&mut hasher);
+    todo.priority.map(|p| p.min(5)).hash(&mut hasher);
+    todo.completed.hash(&mut hasher);
+    todo.parent_uid.hash(&mut hasher);
+
+    format!("{:x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_engine::{compute_task_hash, SyncAction, SyncEngine};
+
+    #[test]
+    fn test_tasks_map_to_vtodos_and_back() {
+        let task = Task::parse("call bob #work >phone !2025-06-10 $2 [id:abc123]");
+        let todo = to_vtodo(&task, None, None);
+        assert_eq!(todo.uid, "abc123@yarmtl");
+        assert_eq!(todo.categories, ["work", "ctx_phone"]);
+        assert_eq!(todo.priority, Some(2));
+
+        let back = to_task(&VTodo::parse(&todo.to_ics()).unwrap());
+        assert_eq!(back.id, "abc123");
+        assert_eq!(
+            (back.text.as_str(), back.deadline, &back.tags, &back.contexts, back.importance),
+            ("call bob", task.deadline, &task.tags, &task.contexts, Some(2))
+        );
+        assert_eq!(compute_task_hash(&back), compute_task_hash(&task));
+    }
+
+    #[test]
+    fn test_detect_changes() {
+        let config = CalDavConfig {
+            url: "https://dav.example.com/tasks/".to_string(),
+            username: "me".to_string(),
+            password: "secret".to_string(),
+            conflict_strategy: ConflictStrategy::KeepBoth,
+        };
+        let temp = tempfile::tempdir().unwrap();
+        let sync_dir = temp.path();
+        let mut sync = SyncEngine::new(CalDavProvider::new(&config).unwrap(), sync_dir, config.conflict_strategy).unwrap();
+        let local = Task::parse("call bob [id:abc123]");
+        let remote = VTodo { href: "/tasks/abc123.ics".to_string(), ..to_vtodo(&local, None, None) };
+        sync.record_pair(local.id.clone(), &local, &remote);
+        let theirs = VTodo { href: "/tasks/nc-1.ics".to_string(), uid: "nc-1".to_string(), summary: "water plants".to_string(), ..VTodo::default() };
+        let done_there = VTodo { href: "/tasks/nc-2.ics".to_string(), uid: "nc-2".to_string(), completed: true, ..theirs.clone() };
+
+        let actions = sync.detect_changes(std::slice::from_ref(&local), &[remote.clone(), theirs, done_there]);
+        assert!(matches!(&actions[..], [SyncAction::CreateInYarmtl(todo)] if todo.uid == "nc-1"));
+
+        let edited = VTodo { completed: true, ..remote.clone() };
+        let actions = sync.detect_changes(std::slice::from_ref(&local), &[edited]);
+        assert!(matches!(actions[..], [SyncAction::UpdateYarmtl { .. }]));
+        assert!(matches!(sync.detect_changes(&[local], &[])[..], [SyncAction::DeleteFromYarmtl { .. }]));
+        assert!(matches!(sync.detect_changes(&[], &[remote])[..], [SyncAction::DeleteRemote { .. }]));
+    }
+}
@@ -0,0 +1,397 @@
+use crate::caldav_client::{split_line, unescape, unfold, CalDavClient, CalDavError};
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Days a multi-day event is shown on at most, so a year-long one doesn't fill the agenda.
+const MAX_EVENT_DAYS: i64 = 14;
+
+/// A calendar shown next to the tasks of the week agenda, one `[[calendars]]` table of
+/// config.toml. Nothing is ever written to it.
+///
+/// ```toml
+/// [[calendars]]
+/// name = "work"
+/// url = "https://calendar.example.com/me/work.ics"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CalendarSubscription {
+    pub name: String,
+    /// An iCalendar file (webcal:// works too) or a CalDAV calendar collection
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// One day of an event: a recurring event has one for each time it happens, a
+/// multi-day one for each of its days.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    /// Name of the calendar it is from
+    pub calendar: String,
+    pub summary: String,
+    pub day: NaiveDate,
+    /// When it starts, in local time; None for all-day events
+    pub time: Option<NaiveTime>,
+}
+
+impl CalendarEvent {
+    /// The line shown in the agenda.
+    pub fn describe(&self) -> String {
+        match self.time {
+            Some(time) => format!("📅 {} {} ({})", time.format("%H:%M"), self.summary, self.calendar),
+            None => format!("📅 {} ({})", self.summary, self.calendar),
+        }
+    }
+}
+
+/// How a recurring event repeats, the parts of an RRULE yarmtl understands.
+#[derive(Debug, Clone, PartialEq)]
+struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<NaiveDate>,
+    /// With an ordinal for monthly ones, like 2TU for the second Tuesday
+    by_day: Vec<(Option<i32>, Weekday)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Recurrence {
+    fn parse(value: &str) -> Option<Recurrence> {
+        let mut recurrence = Recurrence { frequency: Frequency::Daily, interval: 1, count: None, until: None, by_day: Vec::new() };
+        let mut frequency = None;
+        for part in value.split(';') {
+            let (name, value) = part.split_once('=')?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Some(Frequency::Daily),
+                        "WEEKLY" => Some(Frequency::Weekly),
+                        "MONTHLY" => Some(Frequency::Monthly),
+                        "YEARLY" => Some(Frequency::Yearly),
+                        // Hourly and finer aren't worth a line in a day's agenda each
+                        _ => return None,
+                    }
+                }
+                "INTERVAL" => recurrence.interval = value.parse().ok().filter(|i| *i > 0)?,
+                "COUNT" => recurrence.count = value.parse().ok(),
+                "UNTIL" => recurrence.until = parse_day(value),
+                "BYDAY" => recurrence.by_day = value.split(',').filter_map(parse_weekday).collect(),
+                _ => {}
+            }
+        }
+        recurrence.frequency = frequency?;
+        Some(recurrence)
+    }
+
+    /// The days the event happens on, from its first one up to `to`.
+    fn days(&self, start: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let mut days = Vec::new();
+        let interval = self.interval as i64;
+        let mut taken = 0;
+        for period in 0.. {
+            let candidates = match self.frequency {
+                Frequency::Daily => vec![start + Duration::days(period * interval)],
+                Frequency::Weekly => {
+                    let week = start - Duration::days(start.weekday().num_days_from_monday() as i64)
+                        + Duration::weeks(period * interval);
+                    let mut weekdays: Vec<Weekday> = self.by_day.iter().map(|(_, day)| *day).collect();
+                    if weekdays.is_empty() {
+                        weekdays.push(start.weekday());
+                    }
+                    weekdays.sort_by_key(|day| day.num_days_from_monday());
+                    weekdays.iter().map(|day| week + Duration::days(day.num_days_from_monday() as i64)).collect()
+                }
+                Frequency::Monthly => {
+                    let months = start.month0() as i64 + period * interval;
+                    let (year, month) = (start.year() + (months / 12) as i32, (months % 12) as u32 + 1);
+                    if self.by_day.is_empty() {
+                        NaiveDate::from_ymd_opt(year, month, start.day()).into_iter().collect()
+                    } else {
+                        let mut in_month: Vec<NaiveDate> =
+                            self.by_day.iter().filter_map(|&(nth, day)| nth_weekday(year, month, nth, day)).flatten().collect();
+                        in_month.sort();
+                        in_month
+                    }
+                }
+                Frequency::Yearly => {
+                    NaiveDate::from_ymd_opt(start.year() + (period * interval) as i32, start.month(), start.day())
+                        .into_iter()
+                        .collect()
+                }
+            };
+            let Some(first) = candidates.first() else {
+                // A 31st in a short month, or a 29th of February
+                if period > 10_000 {
+                    break;
+                }
+                continue;
+            };
+            if *first > to || self.until.is_some_and(|until| *first > until) {
+                break;
+            }
+            for day in candidates.into_iter().filter(|day| *day >= start) {
+                if day > to || self.until.is_some_and(|until| day > until) || self.count.is_some_and(|count| taken >= count) {
+                    return days;
+                }
+                taken += 1;
+                days.push(day);
+            }
+        }
+        days
+    }
+}
+
+/// "TU", "2TU" or "-1FR" of a BYDAY.
+fn parse_weekday(value: &str) -> Option<(Option<i32>, Weekday)> {
+    let split = value.len().checked_sub(2)?;
+    let (nth, day) = value.split_at(split);
+    let day = match day.to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let nth = match nth.trim_start_matches('+') {
+        "" => None,
+        nth => Some(nth.parse().ok()?),
+    };
+    Some((nth, day))
+}
+
+/// The days of the month that are that weekday: all of them, or the nth (counted from
+/// the end when negative).
+fn nth_weekday(year: i32, month: u32, nth: Option<i32>, weekday: Weekday) -> Option<Vec<NaiveDate>> {
+    let all: Vec<NaiveDate> = (1..=31)
+        .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .filter(|date| date.weekday() == weekday)
+        .collect();
+    match nth {
+        None => Some(all),
+        Some(nth) if nth > 0 => all.get(nth as usize - 1).map(|day| vec![*day]),
+        Some(nth) => all.len().checked_sub(nth.unsigned_abs() as usize).map(|i| vec![all[i]]),
+    }
+}
+
+fn parse_day(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()
+}
+
+/// A DTSTART or DTEND as a local day and time, None for a date. UTC times are made
+/// local; times in a TZID are taken as they are, close enough for an agenda.
+fn parse_start(params: &[&str], value: &str) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    let is_date = params.iter().any(|p| p.eq_ignore_ascii_case("VALUE=DATE")) || value.len() == 8;
+    if is_date {
+        return Some((parse_day(value)?, None));
+    }
+    let time = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()?;
+    let time = match value.ends_with('Z') {
+        true => Local.from_utc_datetime(&time).naive_local(),
+        false => time,
+    };
+    Some((time.date(), Some(time.time())))
+}
+
+/// One VEVENT, as read.
+#[derive(Default)]
+struct VEvent {
+    uid: String,
+    summary: String,
+    start: Option<(NaiveDate, Option<NaiveTime>)>,
+    end: Option<NaiveDate>,
+    /// None also for RRULEs yarmtl doesn't understand, the event is shown on its first day only
+    recurrence: Option<Recurrence>,
+    /// Days EXDATE takes out of the series
+    excluded: HashSet<NaiveDate>,
+    /// The day of the occurrence this one replaces
+    recurrence_id: Option<NaiveDate>,
+    cancelled: bool,
+}
+
+fn parse_vevents(ics: &str) -> Vec<VEvent> {
+    let mut events = Vec::new();
+    let mut event: Option<VEvent> = None;
+    // Components inside the VEVENT, like VALARM, have properties of their own
+    let mut nested = 0;
+    for line in unfold(ics) {
+        let (name, params, value) = split_line(&line);
+        let Some(current) = event.as_mut() else {
+            if name == "BEGIN" && value.eq_ignore_ascii_case("VEVENT") {
+                event = Some(VEvent::default());
+            }
+            continue;
+        };
+        match name.as_str() {
+            "BEGIN" => nested += 1,
+            "END" if nested > 0 => nested -= 1,
+            "END" => events.extend(event.take()),
+            _ if nested > 0 => {}
+            "UID" => current.uid = value.to_string(),
+            "SUMMARY" => current.summary = unescape(value),
+            "DTSTART" => current.start = parse_start(&params, value),
+            "DTEND" => current.end = parse_start(&params, value).map(|(day, _)| day),
+            "RRULE" => current.recurrence = Recurrence::parse(value),
+            "EXDATE" => current.excluded.extend(value.split(',').filter_map(parse_day)),
+            "RECURRENCE-ID" => current.recurrence_id = parse_start(&params, value).map(|(day, _)| day),
+            "STATUS" => current.cancelled = value.trim().eq_ignore_ascii_case("CANCELLED"),
+            _ => {}
+        }
+    }
+    events
+}
+
+/// The days between `from` and `to` the events of an iCalendar object happen on.
+pub fn parse_events(ics: &str, calendar: &str, from: NaiveDate, to: NaiveDate) -> Vec<CalendarEvent> {
+    let vevents = parse_vevents(ics);
+    // Occurrences moved or cancelled one by one are left out of the series
+    let moved: HashSet<(&str, NaiveDate)> =
+        vevents.iter().filter_map(|e| e.recurrence_id.map(|day| (e.uid.as_str(), day))).collect();
+
+    let mut events = Vec::new();
+    for vevent in &vevents {
+        let Some((start, time)) = vevent.start else {
+            continue;
+        };
+        if vevent.cancelled {
+            continue;
+        }
+        // DTEND is exclusive for all-day events; timed ones are shown on the day they start
+        let length = match (time, vevent.end) {
+            (None, Some(end)) => (end - start).num_days().clamp(1, MAX_EVENT_DAYS),
+            _ => 1,
+        };
+        let starts = match &vevent.recurrence {
+            Some(recurrence) if vevent.recurrence_id.is_none() => recurrence.days(start, to),
+            _ => vec![start],
+        };
+        for first in starts {
+            if vevent.excluded.contains(&first) || (vevent.recurrence.is_some() && moved.contains(&(vevent.uid.as_str(), first))) {
+                continue;
+            }
+            for day in (0..length).map(|offset| first + Duration::days(offset)) {
+                if (from..=to).contains(&day) {
+                    events.push(CalendarEvent { calendar: calendar.to_string(), summary: vevent.summary.clone(), day, time });
+                }
+            }
+        }
+    }
+    events
+}
+
+/// The events of every calendar between `from` and `to`, all-day ones first on each day,
+/// and what went wrong with those that couldn't be read.
+pub async fn fetch_events(
+    calendars: &[CalendarSubscription],
+    from: NaiveDate,
+    to: NaiveDate,
+) -> (Vec<CalendarEvent>, Vec<String>) {
+    let mut events = Vec::new();
+    let mut errors = Vec::new();
+    for calendar in calendars {
+        match fetch_calendar(calendar, from, to).await {
+            Ok(objects) => events.extend(objects.iter().flat_map(|ics| parse_events(ics, &calendar.name, from, to))),
+            Err(e) => errors.push(format!("{}: {}", calendar.name, e)),
+        }
+    }
+    events.sort_by_key(|e| (e.day, e.time));
+    (events, errors)
+}
+
+/// The iCalendar objects of a calendar: the file at its URL, or when what is there is
+/// no iCalendar file, the events a CalDAV collection has in those days.
+async fn fetch_calendar(calendar: &CalendarSubscription, from: NaiveDate, to: NaiveDate) -> Result<Vec<String>, CalDavError> {
+    let url = match calendar.url.strip_prefix("webcal://") {
+        Some(rest) => format!("https://{}", rest),
+        None => calendar.url.clone(),
+    };
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .expect("Failed to create HTTP client");
+    let mut request = client.get(&url);
+    if let Some(username) = &calendar.username {
+        request = request.basic_auth(username, calendar.password.as_ref());
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        return Err(CalDavError::Auth);
+    }
+    if status.is_success() {
+        let body = response.text().await?;
+        if body.trim_start().starts_with("BEGIN:VCALENDAR") {
+            return Ok(vec![body]);
+        }
+    }
+
+    let client = CalDavClient::new(
+        &url,
+        calendar.username.clone().unwrap_or_default(),
+        calendar.password.clone().unwrap_or_default(),
+    )?;
+    let start = Utc.from_utc_datetime(&from.and_time(NaiveTime::MIN)) - Duration::days(1);
+    let end = Utc.from_utc_datetime(&to.and_time(NaiveTime::MIN)) + Duration::days(2);
+    client.list_events(start, end).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, d).unwrap()
+    }
+
+    #[test]
+    fn test_events_in_the_week() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:standup\r\nSUMMARY:standup\r\n\
+                   DTSTART;TZID=Europe/Berlin:20250602T093000\r\nRRULE:FREQ=WEEKLY;BYDAY=MO,WE,FR\r\n\
+                   EXDATE;TZID=Europe/Berlin:20250611T093000\r\nBEGIN:VALARM\r\nSUMMARY:alarm\r\nEND:VALARM\r\nEND:VEVENT\r\n\
+                   BEGIN:VEVENT\r\nUID:standup\r\nRECURRENCE-ID;TZID=Europe/Berlin:20250613T093000\r\nSUMMARY:late standup\r\n\
+                   DTSTART;TZID=Europe/Berlin:20250613T110000\r\nEND:VEVENT\r\n\
+                   BEGIN:VEVENT\r\nUID:trip\r\nSUMMARY:offsite\\, day 1-2\r\nDTSTART;VALUE=DATE:20250608\r\n\
+                   DTEND;VALUE=DATE:20250610\r\nEND:VEVENT\r\n\
+                   BEGIN:VEVENT\r\nUID:gone\r\nSUMMARY:cancelled\r\nSTATUS:CANCELLED\r\nDTSTART:20250610T120000\r\nEND:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+        let mut events = parse_events(ics, "work", day(9), day(15));
+        events.sort_by_key(|e| (e.day, e.time));
+        let seen: Vec<(NaiveDate, Option<NaiveTime>, &str)> =
+            events.iter().map(|e| (e.day, e.time, e.summary.as_str())).collect();
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0);
+        assert_eq!(
+            seen,
+            [
+                (day(9), None, "offsite, day 1-2"),
+                (day(9), at(9, 30), "standup"),
+                (day(13), at(11, 0), "late standup"),
+            ]
+        );
+        assert_eq!(events[1].describe(), "📅 09:30 standup (work)");
+    }
+
+    #[test]
+    fn test_recurrence_rules() {
+        let monthly = Recurrence::parse("FREQ=MONTHLY;BYDAY=-1FR;COUNT=3").unwrap();
+        assert_eq!(
+            monthly.days(day(27), NaiveDate::from_ymd_opt(2026, 1, 1).unwrap()),
+            [day(27), NaiveDate::from_ymd_opt(2025, 7, 25).unwrap(), NaiveDate::from_ymd_opt(2025, 8, 29).unwrap()]
+        );
+        let daily = Recurrence::parse("FREQ=DAILY;INTERVAL=2;UNTIL=20250607T000000Z").unwrap();
+        assert_eq!(daily.days(day(1), day(30)), [day(1), day(3), day(5), day(7)]);
+        let yearly = Recurrence::parse("FREQ=YEARLY").unwrap();
+        assert_eq!(yearly.days(NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(), day(30)).len(), 1);
+        assert!(Recurrence::parse("FREQ=HOURLY").is_none());
+    }
+}
@@ -0,0 +1,120 @@
+use crate::dates;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Where "now" comes from. Overdue and due-today decisions, relative dates like
+/// `!tomorrow` and the reminders ask a clock instead of calling `Local::now()`, so
+/// tests can pin the day.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+
+    fn today(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+}
+
+/// The system time, what everything outside of tests uses.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// The `[time]` section of config.toml:
+///
+/// ```toml
+/// [time]
+/// zone = "Europe/Berlin"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct TimeConfig {
+    /// The time zone of the tz database "today" is in, for the TUI, the command line and the
+    /// daemon alike; the system's when left out
+    pub zone: Option<String>,
+}
+
+/// Where the tz database usually is, unless TZDIR says.
+const ZONEINFO: [&str; 3] = ["/usr/share/zoneinfo", "/usr/lib/zoneinfo", "/usr/share/lib/zoneinfo"];
+
+/// Makes `zone` the local time of this process, which `Local`, and so every clock, reads.
+/// It sets TZ, so it has to run before there are other threads, the tokio runtime's too.
+pub fn set_zone(zone: &str) -> Result<(), String> {
+    let dirs = std::env::var_os("TZDIR").map(PathBuf::from).into_iter().chain(ZONEINFO.map(PathBuf::from));
+    let known = zone == "UTC" || (!zone.contains("..") && dirs.into_iter().any(|dir| dir.join(zone).is_file()));
+    if !known {
+        return Err(format!("{} isn't a time zone of the tz database, like Europe/Berlin", zone));
+    }
+    // SAFETY: called first thing in main, nothing else is running to read the environment
+    unsafe { std::env::set_var("TZ", zone) };
+    Ok(())
+}
+
+/// The day in `zone` of a date or time from elsewhere: `2025-07-01`, `2025-07-01T09:00:00`
+/// taken as it is, or with an offset, `2025-07-01T23:30:00Z`, `2025-07-01T09:00-04:00` and
+/// iCalendar's `20250701T233000Z`, moved into `zone` first.
+pub fn day_in<Tz: TimeZone>(value: &str, zone: &Tz) -> Option<NaiveDate> {
+    let value = value.trim();
+    let with_offset = DateTime::parse_from_rfc3339(value).or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M%:z"));
+    if let Ok(time) = with_offset {
+        return Some(time.with_timezone(zone).date_naive());
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ") {
+        return Some(zone.from_utc_datetime(&time).date_naive());
+    }
+    value
+        .get(..10)
+        .and_then(dates::parse_iso)
+        .or_else(|| NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok())
+}
+
+/// [`day_in`] the local time zone, the one of `[time]` when it's set.
+pub fn local_day(value: &str) -> Option<NaiveDate> {
+    day_in(value, &Local)
+}
+
+/// A clock stopped at one moment.
+#[cfg(test)]
+pub struct FixedClock(pub DateTime<Local>);
+
+#[cfg(test)]
+impl FixedClock {
+    /// Noon on `date`, which exists on every day DST or not.
+    pub fn on(date: NaiveDate) -> Self {
+        use chrono::TimeZone;
+        FixedClock(Local.from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap()).earliest().unwrap())
+    }
+}
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Local> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    #[test]
+    fn test_days_of_times_with_offsets() {
+        let berlin = FixedOffset::east_opt(2 * 3600).unwrap();
+        let day = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+        // 23:30 in New York is the next morning in Berlin
+        assert_eq!(day_in("2025-07-01T23:30:00-04:00", &berlin), day(2025, 7, 2));
+        assert_eq!(day_in("2025-07-01T23:30:00Z", &berlin), day(2025, 7, 2));
+        assert_eq!(day_in("2025-07-01T21:30+00:00", &berlin), day(2025, 7, 1));
+        assert_eq!(day_in("20250701T233000Z", &berlin), day(2025, 7, 2));
+        assert_eq!(day_in("20250701T233000", &berlin), day(2025, 7, 1));
+        assert_eq!(day_in("2025-07-01T23:30:00", &berlin), day(2025, 7, 1));
+        assert_eq!(day_in("2025-07-01", &berlin), day(2025, 7, 1));
+        assert_eq!(day_in("tomorrow", &berlin), None);
+        assert!(set_zone("Mars/Olympus_Mons").is_err());
+        assert!(set_zone("../../etc/passwd").is_err());
+    }
+}
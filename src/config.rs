@@ -0,0 +1,222 @@
+use crate::backup::BackupConfig;
+use crate::calendar::CalendarSubscription;
+use crate::clock::TimeConfig;
+use crate::dates::{DateDialect, DateDisplay};
+use crate::email_actions::EmailActionsConfig;
+use crate::encryption::EncryptionConfig;
+use crate::index::IndexConfig;
+use crate::notifications::NotificationsConfig;
+use crate::paths::get_config_path;
+use crate::push::PushChannel;
+use crate::quiet::QuietConfig;
+use crate::reminder_email::ReminderEmailConfig;
+use crate::rules::Rule;
+use crate::status_line::{self, StatusWidget};
+use crate::task::TaskFormat;
+use crate::webhooks::WebhookConfig;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// General settings from `config.toml` in the tasks directory.
+/// Every section is optional so an empty or missing file means "all defaults".
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub ui: UiConfig,
+    pub tasks: TasksConfig,
+    pub git: GitConfig,
+    /// Escalation rules, one `[[rules]]` table each, applied in order
+    pub rules: Vec<Rule>,
+    /// Read-only calendars whose events the week agenda shows, one `[[calendars]]` table each
+    pub calendars: Vec<CalendarSubscription>,
+    /// URLs task events are posted to, one `[[webhooks]]` table each
+    pub webhooks: Vec<WebhookConfig>,
+    /// Desktop notifications for reminders and due or overdue tasks
+    pub notifications: NotificationsConfig,
+    /// ntfy, Pushover or Gotify, for the reminder digest and alerts; one `[[push]]` table each
+    pub push: Vec<PushChannel>,
+    /// Links in the reminder emails to check a task off or snooze it
+    pub email_actions: EmailActionsConfig,
+    /// What the reminder email holds, and the weekly summary
+    pub reminder_email: ReminderEmailConfig,
+    /// Quiet hours and days off, when the daemon raises and pushes nothing
+    pub quiet: QuietConfig,
+    /// Where the key for an encrypted tasks.md.age is
+    pub encryption: EncryptionConfig,
+    /// Whether `yarmtl search` uses the SQLite index
+    pub index: IndexConfig,
+    /// How dates are shown, in which language, and the day weeks start on
+    pub dates: DateDisplay,
+    /// The time zone "today" is in
+    pub time: TimeConfig,
+    /// How many automatic backups are kept, and when a sync makes one
+    pub backup: BackupConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct UiConfig {
+    /// "auto" (detect from the terminal), "dark" or "light"
+    pub theme: ThemeSetting,
+    /// Widgets shown in the status row, left to right
+    pub status_line: Vec<StatusWidget>,
+    /// Length of a pomodoro started with `p`
+    pub pomodoro_minutes: u64,
+    /// How the task list is drawn: "inline" (one line of text per task) or "table"
+    pub layout: ListLayout,
+    /// ASCII markers like [!] and due: instead of emoji, in the TUI, on the command line and in emails
+    pub ascii: bool,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            theme: ThemeSetting::Auto,
+            status_line: status_line::default_widgets(),
+            pomodoro_minutes: 25,
+            layout: ListLayout::Inline,
+            ascii: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct TasksConfig {
+    /// Mark a parent task done once its last subtask is completed
+    pub auto_complete_parents: bool,
+    /// Move tasks completed more than this many days ago to archive.md; 0 never archives
+    pub archive_after_days: u32,
+    /// Warn when the estimates (~2h) of the work due today add up to more; 0 never warns
+    pub daily_capacity_hours: u32,
+    /// How dates like `4/3` and `next friday` read: "us" (the default) or "uk"
+    pub date_dialect: DateDialect,
+    /// How tasks.md is written: "yarmtl" (the default) or "obsidian", for the Obsidian Tasks plugin
+    pub format: TaskFormat,
+    /// Days before a deadline its reminders start, unless the task says otherwise with [warn:3d]; 0 never
+    pub warn_days: u32,
+}
+
+impl Default for TasksConfig {
+    fn default() -> Self {
+        TasksConfig {
+            auto_complete_parents: false,
+            archive_after_days: 0,
+            daily_capacity_hours: 8,
+            date_dialect: DateDialect::Us,
+            format: TaskFormat::Yarmtl,
+            warn_days: 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct GitConfig {
+    /// Commit tasks.md after every change (and push when a remote is set up)
+    pub auto_commit: bool,
+    /// Who the commits are by; user.name and user.email from the git config when left out
+    pub name: Option<String>,
+    pub email: Option<String>,
+    /// Where origin points, to sync the tasks between machines through; it's pulled from
+    /// when the tui and the daemon start and pushed to in the background after each commit
+    pub remote: Option<String>,
+    /// The branch on origin, the one checked out here when left out
+    pub branch: Option<String>,
+    /// When the tui commits what it saved; the command line commits each change right away
+    pub commit: CommitPolicy,
+    /// How long the saves have to stop for before a `debounce` commit
+    pub debounce_seconds: u64,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        GitConfig {
+            auto_commit: true,
+            name: None,
+            email: None,
+            remote: None,
+            branch: None,
+            commit: CommitPolicy::Immediate,
+            debounce_seconds: 30,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitPolicy {
+    /// A commit for every save
+    Immediate,
+    /// One commit once the saves stop for `debounce_seconds`
+    Debounce,
+    /// One commit when the tui is closed
+    Session,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeSetting {
+    Auto,
+    Dark,
+    Light,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ListLayout {
+    Inline,
+    Table,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let content = fs::read_to_string(path)?;
+        let config: Config = toml::from_str(&content)?;
+        Ok(config)
+    }
+}
+
+/// config.toml of the tasks directory, the defaults when it's missing or invalid.
+pub fn load_config() -> Config {
+    tracing::trace!("reading {}", get_config_path().display());
+    Config::load(&get_config_path()).unwrap_or_else(|e| {
+        tracing::warn!("ignoring invalid {}: {}", get_config_path().display(), e);
+        Config::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config_uses_defaults() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.ui.theme, ThemeSetting::Auto);
+        assert!(config.git.auto_commit);
+        assert_eq!(config.git.commit, CommitPolicy::Immediate);
+    }
+
+    #[test]
+    fn test_theme_override() {
+        let config: Config = toml::from_str("[ui]\ntheme = \"light\"\n").unwrap();
+        assert_eq!(config.ui.theme, ThemeSetting::Light);
+        assert_eq!(config.ui.status_line, status_line::default_widgets());
+    }
+
+    #[test]
+    fn test_status_line_widgets() {
+        let config: Config = toml::from_str("[ui]\nstatus_line = [\"goal\", \"pomodoro\"]\n").unwrap();
+        assert_eq!(config.ui.status_line, vec![StatusWidget::Goal, StatusWidget::Pomodoro]);
+        assert_eq!(config.ui.layout, ListLayout::Inline);
+
+        let config: Config = toml::from_str("[ui]\nlayout = \"table\"\n").unwrap();
+        assert_eq!(config.ui.layout, ListLayout::Table);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::clock;
+use crate::ids::{self, IdMap};
+use crate::task::Task;
+use crate::taskwarrior::ImportSummary;
+use chrono::NaiveDate;
+use std::collections::{HashMap, HashSet};
+
+/// The columns of an export, in this order. Later versions only ever add columns at the
+/// end, so spreadsheets and scripts reading them by position keep working.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Column {
+    Id,
+    Text,
+    /// "open", "done" or "cancelled"
+    Status,
+    Deadline,
+    Reminder,
+    /// Space-separated, without the #
+    Tags,
+    /// 1 (highest) to 5, like `$1` to `$5`
+    Importance,
+    Notes,
+    Created,
+    /// The day it was completed or cancelled
+    Completed,
+}
+
+impl Column {
+    pub const ALL: [Column; 10] = [
+        Column::Id,
+        Column::Text,
+        Column::Status,
+        Column::Deadline,
+        Column::Reminder,
+        Column::Tags,
+        Column::Importance,
+        Column::Notes,
+        Column::Created,
+        Column::Completed,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Text => "text",
+            Column::Status => "status",
+            Column::Deadline => "deadline",
+            Column::Reminder => "reminder",
+            Column::Tags => "tags",
+            Column::Importance => "importance",
+            Column::Notes => "notes",
+            Column::Created => "created",
+            Column::Completed => "completed",
+        }
+    }
+
+    /// The column called `name`, in any case.
+    pub fn from_name(name: &str) -> Option<Column> {
+        Column::ALL.into_iter().find(|column| column.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    fn value(self, task: &Task) -> String {
+        let day = |date: Option<NaiveDate>| date.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default();
+        match self {
+            Column::Id => task.id.clone(),
+            Column::Text => task.text.clone(),
+            Column::Status => match (task.completed, task.cancelled) {
+                (_, true) => "cancelled",
+                (true, false) => "done",
+                (false, false) => "open",
+            }
+            .to_string(),
+            Column::Deadline => day(task.deadline),
+            Column::Reminder => day(task.reminder),
+            Column::Tags => task.tags.join(" "),
+            Column::Importance => task.importance.map(|importance| importance.to_string()).unwrap_or_default(),
+            Column::Notes => task.notes.clone().unwrap_or_default(),
+            Column::Created => day(task.created),
+            Column::Completed => day(task.completed.then_some(task.done).flatten()),
+        }
+    }
+}
+
+/// Every task, completed and cancelled ones too, one row each under a header row.
+pub fn export(tasks: &[Task]) -> String {
+    let mut out = row(Column::ALL.iter().map(|column| column.name().to_string()));
+    for task in tasks {
+        out.push_str(&row(Column::ALL.iter().map(|column| column.value(task))));
+    }
+    out
+}
+
+/// One line of CSV, quoting the fields that need it.
+fn row(fields: impl Iterator<Item = String>) -> String {
+    let fields: Vec<String> = fields
+        .map(|field| match field.contains([',', '"', '\n', '\r']) {
+            true => format!("\"{}\"", field.replace('"', "\"\"")),
+            false => field,
+        })
+        .collect();
+    format!("{}\n", fields.join(","))
+}
+
+/// A CSV file read for importing: which of yarmtl's columns each of its columns is, and
+/// its rows with the line each starts on.
+#[derive(Debug)]
+pub struct Sheet {
+    pub headers: Vec<String>,
+    columns: Vec<Option<Column>>,
+    rows: Vec<(usize, Vec<String>)>,
+}
+
+impl Sheet {
+    /// The columns that aren't yarmtl's, left out of the import.
+    pub fn ignored(&self) -> Vec<&str> {
+        self.headers.iter().zip(&self.columns).filter(|(_, column)| column.is_none()).map(|(header, _)| header.as_str()).collect()
+    }
+}
+
+/// Reads a CSV file with a header row. Its columns are yarmtl's by name, or as `mapping`
+/// says: "Due=deadline,Task=text" maps the Due column to deadline and Task to text.
+pub fn parse(input: &str, mapping: Option<&str>) -> Result<Sheet, String> {
+    let mut mapped: HashMap<String, Column> = HashMap::new();
+    for pair in mapping.unwrap_or_default().split(',').filter(|pair| !pair.trim().is_empty()) {
+        let (header, name) = pair.split_once('=').ok_or_else(|| format!("\"{}\" in the mapping isn't column=field", pair.trim()))?;
+        let column = Column::from_name(name).ok_or_else(|| {
+            let names: Vec<&str> = Column::ALL.iter().map(|column| column.name()).collect();
+            format!("\"{}\" in the mapping isn't one of {}", name.trim(), names.join(", "))
+        })?;
+        mapped.insert(header.trim().to_lowercase(), column);
+    }
+
+    let mut records = records(input.trim_start_matches('\u{feff}'))?.into_iter();
+    let (_, headers) = records.next().ok_or("the file is empty, it needs a header row")?;
+    let headers: Vec<String> = headers.into_iter().map(|header| header.trim().to_string()).collect();
+    let columns: Vec<Option<Column>> = headers
+        .iter()
+        .map(|header| mapped.get(&header.to_lowercase()).copied().or_else(|| Column::from_name(header)))
+        .collect();
+    let mut seen = HashSet::new();
+    if let Some(column) = columns.iter().flatten().find(|column| !seen.insert(**column)) {
+        return Err(format!("more than one column is {}", column.name()));
+    }
+    if !columns.contains(&Some(Column::Text)) && !columns.contains(&Some(Column::Id)) {
+        return Err("no column is text or id, --map says which one is".to_string());
+    }
+    let rows = records.filter(|(_, fields)| fields.iter().any(|field| !field.trim().is_empty())).collect();
+    Ok(Sheet { headers, columns, rows })
+}
+
+/// The records of a CSV file with the line each starts on, quoted fields taking commas,
+/// quotes doubled and line breaks.
+fn records(input: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut records = Vec::new();
+    let (mut fields, mut field) = (Vec::new(), String::new());
+    let (mut line, mut start, mut quoted) = (1, 1, false);
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                fields.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut fields)));
+                line += 1;
+                start = line;
+            }
+            ('\n', true) => {
+                line += 1;
+                field.push(c);
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("the quote opened on line {} is never closed", start));
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push((start, fields));
+    }
+    Ok(records)
+}
+
+/// Adds the rows of `sheet` to `tasks`, or updates the task a row's id is, or was before
+/// the tui gave it a new one. Only the columns in the sheet change. An error stops the
+/// import halfway, so save `tasks` only on Ok.
+pub fn import(tasks: &mut Vec<Task>, sheet: &Sheet, id_map: &IdMap, today: NaiveDate) -> Result<ImportSummary, String> {
+    let mut summary = ImportSummary::default();
+    for (line, fields) in &sheet.rows {
+        let value = |column: Column| {
+            let index = sheet.columns.iter().position(|c| *c == Some(column))?;
+            Some(fields.get(index).map(|field| field.trim()).unwrap_or_default())
+        };
+        let id = value(Column::Id).filter(|id| !id.is_empty());
+        let existing = id.and_then(|id| {
+            let id = id_map.lookup(id).filter(|_| !tasks.iter().any(|task| task.id == id)).unwrap_or(id);
+            tasks.iter().position(|task| task.id == id)
+        });
+
+        let mut task = match existing {
+            Some(index) => tasks[index].clone(),
+            None => {
+                let mut task = Task::parse("");
+                let taken: HashSet<String> = tasks.iter().map(|task| task.id.clone()).collect();
+                // A task from another tasks.md keeps its id when it's free here
+                task.id = match id.filter(|id| ids::is_valid(id) && !taken.contains(*id)) {
+                    Some(id) => id.to_string(),
+                    None => ids::new_id(&taken),
+                };
+                task.created = Some(today);
+                task
+            }
+        };
+        let before = task.clone();
+        for column in Column::ALL.into_iter().filter(|column| !matches!(column, Column::Id | Column::Status | Column::Completed)) {
+            if let Some(value) = value(column) {
+                set(&mut task, column, value).map_err(|e| format!("line {}: {}", line, e))?;
+            }
+        }
+        let completed = match value(Column::Completed).filter(|value| !value.is_empty()) {
+            Some(value) => Some(date(value).map_err(|e| format!("line {}: {}", line, e))?),
+            None => None,
+        };
+        match value(Column::Status).map(str::to_lowercase).as_deref() {
+            Some("open") => task.set_completed(false, today),
+            Some("done") if !task.completed || task.cancelled => {
+                task.cancelled = false;
+                task.set_completed(true, completed.unwrap_or(today));
+            }
+            Some("cancelled") if !task.cancelled => task.set_cancelled(true, completed.unwrap_or(today)),
+            Some("done") | Some("cancelled") | Some("") | None => {}
+            Some(status) => return Err(format!("line {}: status is \"{}\", not open, done or cancelled", line, status)),
+        }
+        if let Some(completed) = completed.filter(|_| task.completed) {
+            task.done = Some(completed);
+        }
+        if task.text.is_empty() {
+            return Err(format!("line {}: the task has no text", line));
+        }
+
+        match existing {
+            Some(index) if task != before => {
+                tasks[index] = task;
+                summary.updated += 1;
+            }
+            Some(_) => {}
+            None => {
+                tasks.push(task);
+                summary.added += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Writes a cell over what the task has in that column; an empty one clears it.
+fn set(task: &mut Task, column: Column, value: &str) -> Result<(), String> {
+    let optional = |value: &str| Some(value.to_string()).filter(|value| !value.is_empty());
+    let day = |value: &str| optional(value).map(|value| date(&value)).transpose();
+    match column {
+        Column::Text => task.text = value.split_whitespace().collect::<Vec<_>>().join(" "),
+        Column::Deadline => task.deadline = day(value)?,
+        Column::Reminder => task.reminder = day(value)?,
+        Column::Created => task.created = day(value)?,
+        Column::Tags => {
+            task.tags = value
+                .split([' ', ','])
+                .map(|tag| tag.trim_start_matches('#'))
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect()
+        }
+        Column::Importance => {
+            task.importance = match optional(value) {
+                None => None,
+                Some(value) => Some(value.trim_start_matches('$').parse().ok().filter(|i| (1..=5).contains(i)).ok_or_else(|| {
+                    format!("importance is \"{}\", not 1 to 5", value)
+                })?),
+            }
+        }
+        Column::Notes => task.notes = optional(&value.split_whitespace().collect::<Vec<_>>().join(" ")),
+        Column::Id | Column::Status | Column::Completed => {}
+    }
+    Ok(())
+}
+
+/// A day as 2025-06-01, the way spreadsheets write it when told to, or a time with an
+/// offset like 2025-06-01T09:00:00Z, as the day it is here.
+fn date(value: &str) -> Result<NaiveDate, String> {
+    clock::local_day(value).ok_or_else(|| format!("\"{}\" isn't a date like 2025-06-01", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exported_tasks_import_back_and_mapped_columns() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        let mut tasks = vec![
+            Task::parse("call bob, about \"the\" report #work #phone !2025-06-10 $2 // ask first [id:abc123]"),
+            Task::parse("pay rent [id:abc124] [created:2025-05-01]"),
+        ];
+        tasks[1].set_completed(true, today);
+        let exported = export(&tasks);
+        assert_eq!(
+            exported,
+            "id,text,status,deadline,reminder,tags,importance,notes,created,completed\n\
+             abc123,\"call bob, about \"\"the\"\" report\",open,2025-06-10,,work phone,2,ask first,,\n\
+             abc124,pay rent,done,,,,,,2025-05-01,2025-06-01\n"
+        );
+
+        let mut imported = tasks.clone();
+        let sheet = parse(&exported, None).unwrap();
+        assert_eq!(import(&mut imported, &sheet, &IdMap::default(), today), Ok(ImportSummary::default()));
+        assert_eq!(imported, tasks);
+
+        // A collaborator's sheet: their own headers, a note over two lines, a new row
+        let sheet = "Ref,Task,Due,Owner,status\r\nabc123,call bob,2025-06-12,ann,done\r\n,\"plan the\nparty\",,ann,\r\n";
+        let sheet = parse(sheet, Some("Ref=id, Task=text, Due=deadline")).unwrap();
+        assert_eq!(sheet.ignored(), ["Owner"]);
+        let summary = import(&mut imported, &sheet, &IdMap::default(), today).unwrap();
+        assert_eq!(summary, ImportSummary { added: 1, updated: 1 });
+        assert_eq!((imported[0].text.as_str(), imported[0].deadline), ("call bob", NaiveDate::from_ymd_opt(2025, 6, 12)));
+        assert!(imported[0].completed && imported[0].tags == ["work", "phone"]);
+        assert_eq!(imported[2].text, "plan the party");
+
+        let bad = parse("text,deadline\nsomething,next week\n", None).unwrap();
+        assert_eq!(import(&mut imported, &bad, &IdMap::default(), today), Err("line 2: \"next week\" isn't a date like 2025-06-01".to_string()));
+        assert!(parse("id,text\n\"abc123,oops\n", None).unwrap_err().contains("line 2"));
+    }
+}
@@ -0,0 +1,218 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// In the state directory, holding the pid of the running daemon.
+pub const PID_FILE: &str = "daemon.pid";
+
+/// What the daemon did, in the state directory; rotated to daemon.log.1 and on.
+pub const LOG_FILE: &str = "daemon.log";
+
+/// When the daemon started and how each job last went, for `yarmtl daemon status`.
+const STATUS_FILE: &str = "daemon_status.json";
+
+/// The log is rotated once it grows past this.
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// Rotated logs kept besides daemon.log.
+const KEEP_LOGS: usize = 3;
+
+/// Jobs run concurrently, and each rewrites the status file.
+static STATUS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Status {
+    pub pid: u32,
+    pub started: DateTime<Local>,
+    /// The last run of each job, by name
+    pub runs: BTreeMap<String, Run>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Run {
+    pub at: DateTime<Local>,
+    pub ok: bool,
+    /// What it did, or why it failed
+    pub message: Option<String>,
+}
+
+/// The running daemon, owning the pid file; dropping it removes the file.
+#[derive(Debug)]
+pub struct Daemon {
+    dir: PathBuf,
+}
+
+impl Daemon {
+    /// Takes the pid file in the state directory `dir`, unless another daemon is running for it.
+    pub fn start(dir: &Path) -> Result<Daemon, String> {
+        if let Some(pid) = running_pid(dir) {
+            return Err(format!("the daemon is already running (pid {})", pid));
+        }
+        let pid = std::process::id();
+        fs::write(dir.join(PID_FILE), pid.to_string()).map_err(|e| format!("couldn't write {}: {}", PID_FILE, e))?;
+        let status = Status { pid, started: Local::now(), runs: BTreeMap::new() };
+        let daemon = Daemon { dir: dir.to_path_buf() };
+        daemon.save_status(&status);
+        daemon.log(&format!("started, pid {}", pid));
+        Ok(daemon)
+    }
+
+    /// Appends a line to the log, rotating it when it grew too long. A log that can't be
+    /// written is told about on stderr.
+    pub fn log(&self, message: &str) {
+        let line = format!("{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+        if let Err(e) = append(&self.dir.join(LOG_FILE), &line, MAX_LOG_BYTES) {
+            eprintln!("{} (couldn't write {}: {})", line, LOG_FILE, e);
+        }
+    }
+
+    /// Keeps how a run of `job` went. Runs that did something or failed are logged;
+    /// quiet ones, `Ok(None)`, only update the status.
+    pub fn record(&self, job: &str, outcome: Result<Option<String>, String>) {
+        match &outcome {
+            Ok(Some(message)) => self.log(&format!("{}: {}", job, message)),
+            Ok(None) => {}
+            Err(e) => self.log(&format!("{} failed: {}", job, e)),
+        }
+        let _guard = STATUS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(mut status) = load_status(&self.dir) else { return };
+        let (ok, message) = match outcome {
+            Ok(message) => (true, message),
+            Err(e) => (false, Some(e)),
+        };
+        // A quiet run keeps the message of the last one that did something
+        let message = message.or_else(|| status.runs.get(job).filter(|run| run.ok && ok).and_then(|run| run.message.clone()));
+        status.runs.insert(job.to_string(), Run { at: Local::now(), ok, message });
+        self.save_status(&status);
+    }
+
+    fn save_status(&self, status: &Status) {
+        let written = serde_json::to_string_pretty(status)
+            .map_err(std::io::Error::other)
+            .and_then(|json| fs::write(self.dir.join(STATUS_FILE), json));
+        if let Err(e) = written {
+            self.log(&format!("couldn't write {}: {}", STATUS_FILE, e));
+        }
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        self.log("stopped");
+        let _ = fs::remove_file(self.dir.join(PID_FILE));
+    }
+}
+
+/// Appends a line to the log at `path`, rotating it like daemon.log.
+pub fn append_log(path: &Path, line: &str) -> std::io::Result<()> {
+    append(path, line, MAX_LOG_BYTES)
+}
+
+fn append(path: &Path, line: &str, max_bytes: u64) -> std::io::Result<()> {
+    if fs::metadata(path).is_ok_and(|metadata| metadata.len() > max_bytes) {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+        for n in (1..KEEP_LOGS).rev() {
+            let _ = fs::rename(rotated(n), rotated(n + 1));
+        }
+        fs::rename(path, rotated(1))?;
+    }
+    let mut log = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(log, "{}", line)
+}
+
+pub fn load_status(dir: &Path) -> Option<Status> {
+    serde_json::from_str(&fs::read_to_string(dir.join(STATUS_FILE)).ok()?).ok()
+}
+
+/// The pid of the daemon running for the tasks directory. A pid file left behind by one
+/// that was killed doesn't count.
+pub fn running_pid(dir: &Path) -> Option<u32> {
+    let pid: u32 = fs::read_to_string(dir.join(PID_FILE)).ok()?.trim().parse().ok()?;
+    is_alive(pid).then_some(pid)
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else { return false };
+    // Signal 0 only checks; EPERM is a process that is there but not ours
+    let checked = unsafe { libc::kill(pid, 0) };
+    checked == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Asks the daemon to stop, as Ctrl+C would.
+#[cfg(unix)]
+pub fn stop(pid: u32) -> Result<(), String> {
+    let pid = libc::pid_t::try_from(pid).map_err(|e| e.to_string())?;
+    match unsafe { libc::kill(pid, libc::SIGTERM) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error().to_string()),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn stop(_pid: u32) -> Result<(), String> {
+    Err("stopping the daemon from another process only works on unix, stop it with Ctrl+C".to_string())
+}
+
+/// Resolves on Ctrl+C, or SIGTERM from `yarmtl daemon stop`, systemd and the like.
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file_status_and_log() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+
+        let daemon = Daemon::start(dir).unwrap();
+        assert_eq!(running_pid(dir), Some(std::process::id()));
+        assert!(Daemon::start(dir).is_err());
+
+        daemon.record("webhooks", Ok(Some("sent 2".to_string())));
+        daemon.record("webhooks", Ok(None));
+        daemon.record("sync", Err("offline".to_string()));
+        let runs = load_status(dir).unwrap().runs;
+        assert!(runs["webhooks"].ok && runs["webhooks"].message.as_deref() == Some("sent 2"));
+        assert!(!runs["sync"].ok);
+        drop(daemon);
+        assert_eq!(running_pid(dir), None);
+
+        let log = dir.join("small.log");
+        for line in ["one", "two", "three"] {
+            append(&log, line, 3).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&log).unwrap(), "three\n");
+        assert_eq!(fs::read_to_string(dir.join("small.log.2")).unwrap(), "one\n");
+    }
+}
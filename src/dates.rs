@@ -0,0 +1,371 @@
+use crate::clock;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Datelike, Months, NaiveDate, TimeZone, Utc, Weekday};
+use chrono_english::{parse_date_string, Dialect};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// How ambiguous dates read: `4/3` is April 3rd in "us" and March 4th in "uk", and
+/// `next friday` is the coming Friday in "us" but the one after it in "uk".
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DateDialect {
+    #[default]
+    Us,
+    Uk,
+}
+
+static DIALECT: OnceLock<DateDialect> = OnceLock::new();
+
+/// Set once at startup from `date_dialect` in the `[tasks]` section of config.toml.
+pub fn set_dialect(dialect: DateDialect) {
+    let _ = DIALECT.set(dialect);
+}
+
+pub fn dialect() -> DateDialect {
+    DIALECT.get().copied().unwrap_or_default()
+}
+
+/// The language of day and month names where dates are shown.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Fr,
+    Es,
+    It,
+    Nl,
+    Pt,
+}
+
+/// Day names from Monday and month names from January, each long and short.
+struct Names {
+    days: [&'static str; 7],
+    short_days: [&'static str; 7],
+    months: [&'static str; 12],
+    short_months: [&'static str; 12],
+}
+
+impl Locale {
+    fn names(self) -> &'static Names {
+        match self {
+            Locale::En => &Names {
+                days: ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"],
+                short_days: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+                months: ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"],
+                short_months: ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"],
+            },
+            Locale::De => &Names {
+                days: ["Montag", "Dienstag", "Mittwoch", "Donnerstag", "Freitag", "Samstag", "Sonntag"],
+                short_days: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+                months: ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November", "Dezember"],
+                short_months: ["Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez"],
+            },
+            Locale::Fr => &Names {
+                days: ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+                short_days: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+                months: ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+                short_months: ["janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.", "déc."],
+            },
+            Locale::Es => &Names {
+                days: ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"],
+                short_days: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+                months: ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+                short_months: ["ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic"],
+            },
+            Locale::It => &Names {
+                days: ["lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato", "domenica"],
+                short_days: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+                months: ["gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto", "settembre", "ottobre", "novembre", "dicembre"],
+                short_months: ["gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic"],
+            },
+            Locale::Nl => &Names {
+                days: ["maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag", "zondag"],
+                short_days: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+                months: ["januari", "februari", "maart", "april", "mei", "juni", "juli", "augustus", "september", "oktober", "november", "december"],
+                short_months: ["jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec"],
+            },
+            Locale::Pt => &Names {
+                days: ["segunda-feira", "terça-feira", "quarta-feira", "quinta-feira", "sexta-feira", "sábado", "domingo"],
+                short_days: ["seg", "ter", "qua", "qui", "sex", "sáb", "dom"],
+                months: ["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto", "setembro", "outubro", "novembro", "dezembro"],
+                short_months: ["jan", "fev", "mar", "abr", "mai", "jun", "jul", "ago", "set", "out", "nov", "dez"],
+            },
+        }
+    }
+}
+
+/// The day weeks start on, for "this week", `end of week` and the weekly review.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+}
+
+/// How dates are shown, the `[dates]` section of config.toml: in the TUI, the list and the
+/// reminder emails. tasks.md keeps writing 2025-07-01 whatever it says.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DateDisplay {
+    /// A chrono format, "%Y-%m-%d" by default; "%d.%m.%Y" or "%d/%m/%Y" elsewhere
+    pub format: String,
+    /// Where there is little room, next to tasks in the TUI: "%m/%d" by default
+    pub short_format: String,
+    /// Headings, like the day the reminder email is for: "%A, %B %-d" by default
+    pub long_format: String,
+    /// The language of the names %a, %A, %b and %B stand for
+    pub locale: Locale,
+    pub week_start: WeekStart,
+}
+
+impl Default for DateDisplay {
+    fn default() -> Self {
+        DateDisplay {
+            format: "%Y-%m-%d".to_string(),
+            short_format: "%m/%d".to_string(),
+            long_format: "%A, %B %-d".to_string(),
+            locale: Locale::En,
+            week_start: WeekStart::Monday,
+        }
+    }
+}
+
+impl DateDisplay {
+    /// `date` in `pattern`, the names in it in the locale.
+    pub fn localized(&self, date: NaiveDate, pattern: &str) -> String {
+        let names = self.locale.names();
+        let day = date.weekday().num_days_from_monday() as usize;
+        let month = date.month0() as usize;
+        let mut localized = String::with_capacity(pattern.len());
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                localized.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('A') => localized.push_str(names.days[day]),
+                Some('a') => localized.push_str(names.short_days[day]),
+                Some('B') => localized.push_str(names.months[month]),
+                Some('b' | 'h') => localized.push_str(names.short_months[month]),
+                Some(next) => {
+                    localized.push('%');
+                    localized.push(next);
+                }
+                None => localized.push('%'),
+            }
+        }
+        date.format(&localized).to_string()
+    }
+
+    pub fn date(&self, date: NaiveDate) -> String {
+        self.localized(date, &self.format)
+    }
+
+    pub fn short(&self, date: NaiveDate) -> String {
+        self.localized(date, &self.short_format)
+    }
+
+    pub fn long(&self, date: NaiveDate) -> String {
+        self.localized(date, &self.long_format)
+    }
+
+    /// The short name of the day of `date`, like "Mon".
+    pub fn weekday(&self, date: NaiveDate) -> &'static str {
+        self.locale.names().short_days[date.weekday().num_days_from_monday() as usize]
+    }
+
+    /// The first day of the week `date` is in.
+    pub fn week_start(&self, date: NaiveDate) -> NaiveDate {
+        let into_week = match self.week_start {
+            WeekStart::Monday => date.weekday().num_days_from_monday(),
+            WeekStart::Sunday => date.weekday().num_days_from_sunday(),
+        };
+        date - chrono::Duration::days(into_week as i64)
+    }
+}
+
+static DISPLAY: OnceLock<DateDisplay> = OnceLock::new();
+
+/// Set once at startup from the `[dates]` section of config.toml. A format chrono can't
+/// read is left at its default, rather than failing each time a date is shown.
+pub fn set_display(mut display: DateDisplay) {
+    let defaults = DateDisplay::default();
+    for (format, default) in [
+        (&mut display.format, defaults.format),
+        (&mut display.short_format, defaults.short_format),
+        (&mut display.long_format, defaults.long_format),
+    ] {
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+            tracing::warn!("ignoring the date format {:?}, chrono can't read it", format);
+            *format = default;
+        }
+    }
+    let _ = DISPLAY.set(display);
+}
+
+pub fn display() -> &'static DateDisplay {
+    DISPLAY.get_or_init(DateDisplay::default)
+}
+
+/// `2025-07-01`, read without going through chrono's format strings: tasks.md holds one
+/// or more on most of its lines. Anything else is None.
+pub fn parse_iso(text: &str) -> Option<NaiveDate> {
+    let digits = |range: std::ops::Range<usize>| {
+        let part = text.get(range)?;
+        part.bytes().all(|b| b.is_ascii_digit()).then(|| part.parse::<u32>().ok()).flatten()
+    };
+    let dashes = text.len() == 10 && text.as_bytes()[4] == b'-' && text.as_bytes()[7] == b'-';
+    if !dashes {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(digits(0..4)? as i32, digits(5..7)?, digits(8..10)?)
+}
+
+/// Reads the date after a `!` or `@`, relative to `today`. The phrases chrono-english
+/// doesn't know are handled here, anything else goes to it.
+pub fn parse(phrase: &str, today: NaiveDate, dialect: DateDialect) -> Option<NaiveDate> {
+    // chrono-english slices by bytes and panics on anything but ASCII
+    if !phrase.is_ascii() {
+        return None;
+    }
+    if let Some(date) = parse_iso(phrase) {
+        return Some(date);
+    }
+    // A time from another zone, like 2025-07-01T09:00-04:00: the day it is here then
+    if phrase.contains('T')
+        && let Some(date) = clock::local_day(phrase) {
+        return Some(date);
+    }
+    let phrase = phrase.trim().to_ascii_lowercase();
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    if let Some(date) = own_phrase(&words, today) {
+        return Some(date);
+    }
+
+    // chrono-english reads a bare number as a year
+    if phrase.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        return NaiveDate::parse_from_str(&phrase, "%Y-%m-%d").ok();
+    }
+    let dialect = match dialect {
+        DateDialect::Us => Dialect::Us,
+        DateDialect::Uk => Dialect::Uk,
+    };
+    // Noon, so no time of day can move the result to another date
+    let now = Utc.from_utc_datetime(&today.and_hms_opt(12, 0, 0)?);
+    parse_date_string(&phrase, now, dialect).ok().map(|d| d.date_naive())
+}
+
+fn own_phrase(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    match words {
+        ["today"] => Some(today),
+        ["tomorrow"] => today.succ_opt(),
+        ["yesterday"] => today.pred_opt(),
+        ["end", "of", period] => end_of(period, today),
+        ["in", "a" | "an", unit] => later(today, 1, unit),
+        ["in", count, unit] => later(today, count.parse().ok()?, unit),
+        [word] => next_weekday(word, today).or_else(|| quarter_end(word, None, today)),
+        [quarter, year] => quarter_end(quarter, Some(year.parse().ok()?), today),
+        _ => None,
+    }
+}
+
+/// `fri` or `friday`: the coming one, never today.
+fn next_weekday(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let weekday: Weekday = word.parse().ok()?;
+    today.iter_days().skip(1).find(|d| d.weekday() == weekday)
+}
+
+/// `end of week` is the last day of the week, `end of month`, `end of quarter` and `end of year` their last day.
+fn end_of(period: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match period {
+        "week" => display().week_start(today).checked_add_days(chrono::Days::new(6)),
+        "month" => last_of_month(today),
+        "quarter" => last_of_month(NaiveDate::from_ymd_opt(today.year(), quarter_of(today) * 3, 1)?),
+        "year" => NaiveDate::from_ymd_opt(today.year(), 12, 31),
+        _ => None,
+    }
+}
+
+/// `in 3 days`, `in 2 weeks`, `in a month`, `in 1 year`.
+fn later(today: NaiveDate, count: u32, unit: &str) -> Option<NaiveDate> {
+    match unit.strip_suffix('s').unwrap_or(unit) {
+        "day" => today.checked_add_days(chrono::Days::new(count as u64)),
+        "week" => today.checked_add_days(chrono::Days::new(count as u64 * 7)),
+        "month" => today.checked_add_months(Months::new(count)),
+        "year" => today.checked_add_months(Months::new(count.checked_mul(12)?)),
+        _ => None,
+    }
+}
+
+/// `q3` is the last day of the third quarter: this year's, or next year's once it is over.
+fn quarter_end(word: &str, year: Option<i32>, today: NaiveDate) -> Option<NaiveDate> {
+    let quarter: u32 = word.strip_prefix('q')?.parse().ok().filter(|q| (1..=4).contains(q))?;
+    let end = |year| last_of_month(NaiveDate::from_ymd_opt(year, quarter * 3, 1)?);
+    match year {
+        Some(year) => end(year),
+        None => end(today.year()).filter(|d| *d >= today).or_else(|| end(today.year() + 1)),
+    }
+}
+
+fn quarter_of(date: NaiveDate) -> u32 {
+    (date.month() - 1) / 3 + 1
+}
+
+fn last_of_month(date: NaiveDate) -> Option<NaiveDate> {
+    date.with_day(1)?.checked_add_months(Months::new(1))?.pred_opt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phrases_from_a_fixed_today() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+        // A Friday
+        let today = date(2025, 6, 13).unwrap();
+        let us = |phrase| parse(phrase, today, DateDialect::Us);
+
+        assert_eq!(us("tomorrow"), date(2025, 6, 14));
+        assert_eq!(us("fri"), date(2025, 6, 20));
+        assert_eq!(us("Mon"), date(2025, 6, 16));
+        assert_eq!(us("end of week"), date(2025, 6, 15));
+        assert_eq!(us("end of month"), date(2025, 6, 30));
+        assert_eq!(us("end of quarter"), date(2025, 6, 30));
+        assert_eq!(us("end of year"), date(2025, 12, 31));
+        assert_eq!(us("in 3 weeks"), date(2025, 7, 4));
+        assert_eq!(us("in a month"), date(2025, 7, 13));
+        assert_eq!(us("in 2 days"), date(2025, 6, 15));
+        assert_eq!(us("Q3"), date(2025, 9, 30));
+        assert_eq!(us("q1"), date(2026, 3, 31));
+        assert_eq!(us("q1 2025"), date(2025, 3, 31));
+        assert_eq!(us("2025-07-01"), date(2025, 7, 1));
+        assert_eq!((parse_iso("2025-02-29"), parse_iso("2025-7-1"), parse_iso("2025-07-01x")), (None, None, None));
+        assert_eq!(us("q5"), None);
+        assert_eq!(us("in 3 fortnights"), None);
+        assert_eq!(us("2025"), None);
+
+        assert_eq!(us("next friday"), date(2025, 6, 20));
+        assert_eq!(parse("next friday", today, DateDialect::Uk), date(2025, 6, 27));
+        assert_eq!(us("4/3"), date(2025, 4, 3));
+        assert_eq!(parse("4/3", today, DateDialect::Uk), date(2025, 3, 4));
+    }
+
+    #[test]
+    fn test_date_display() {
+        let friday = NaiveDate::from_ymd_opt(2025, 10, 3).unwrap();
+        let display: DateDisplay = toml::from_str("format = \"%d.%m.%Y\"\nlong_format = \"%A, %-d. %B\"\nlocale = \"de\"\nweek_start = \"sunday\"\n").unwrap();
+        assert_eq!(display.date(friday), "03.10.2025");
+        assert_eq!(display.short(friday), "10/03");
+        assert_eq!(display.long(friday), "Freitag, 3. Oktober");
+        assert_eq!(display.localized(friday, "%a %b %% %Y"), "Fr Okt % 2025");
+        assert_eq!(display.week_start(friday), NaiveDate::from_ymd_opt(2025, 9, 28).unwrap());
+        assert_eq!(DateDisplay::default().week_start(friday), NaiveDate::from_ymd_opt(2025, 9, 29).unwrap());
+        assert_eq!(DateDisplay::default().long(friday), "Friday, October 3");
+    }
+}
@@ -0,0 +1,394 @@
+use crate::config::{Config, load_config};
+use crate::daemon;
+use crate::email::{self, load_email_config};
+use crate::email_auth::{self, SmtpAuth};
+use crate::git;
+use crate::links::{self, LinkTarget};
+use crate::output;
+use crate::paths::{get_config_path, get_email_config_path, get_state_dir, get_sync_dir, get_tasks_file_path, get_todoist_config_path};
+use crate::reminder_email;
+use crate::setup;
+use crate::store::TaskStore;
+use crate::task::Task;
+use crate::todoist_auth::{self, TodoistAuth};
+use crate::todoist_sync::{TodoistConfig, load_todoist_config};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Something wrong with tasks.md that yarmtl can't fix on its own.
+#[derive(Debug)]
+pub struct Problem {
+    pub task_id: String,
+    pub task_text: String,
+    pub message: String,
+}
+
+pub fn check_links(tasks: &[Task]) -> Vec<Problem> {
+    links::broken_links(tasks)
+        .into_iter()
+        .map(|(index, reference, target)| {
+            let message = match target {
+                LinkTarget::Ambiguous(count) => {
+                    format!("link [[id:{}]] matches {} tasks, use more of the id", reference, count)
+                }
+                _ => format!("link [[id:{}]] points to a task that doesn't exist", reference),
+            };
+            Problem {
+                task_id: tasks[index].id.clone(),
+                task_text: tasks[index].text.clone(),
+                message,
+            }
+        })
+        .collect()
+}
+
+/// Tasks with the id of a task above them. The tui gives them new ids when it loads tasks.md.
+pub fn check_ids(tasks: &[Task]) -> Vec<Problem> {
+    let mut seen = HashSet::new();
+    tasks
+        .iter()
+        .filter(|task| !seen.insert(task.id.as_str()))
+        .map(|task| Problem {
+            task_id: task.id.clone(),
+            task_text: task.text.clone(),
+            message: "another task has the same id, the tui gives this one a new id".to_string(),
+        })
+        .collect()
+}
+
+pub fn check_tasks(tasks: &[Task]) -> Vec<Problem> {
+    let mut problems = check_links(tasks);
+    problems.extend(check_ids(tasks));
+    problems
+}
+
+/// How one thing the doctor looked at is, with what to do about it when it isn't fine.
+#[derive(Debug, PartialEq)]
+pub enum Finding {
+    Fine(String),
+    /// Works, but likely not the way it was meant to
+    Warning { message: String, fix: String },
+    /// Keeps something from working
+    Problem { message: String, fix: String },
+}
+
+impl Finding {
+    fn warning(message: impl Into<String>, fix: impl Into<String>) -> Finding {
+        Finding::Warning { message: message.into(), fix: fix.into() }
+    }
+
+    fn problem(message: impl Into<String>, fix: impl Into<String>) -> Finding {
+        Finding::Problem { message: message.into(), fix: fix.into() }
+    }
+}
+
+/// tasks.md: whether it reads, and what `check_tasks` finds in it.
+pub fn check_tasks_file(task_file: &Path) -> Vec<Finding> {
+    let store = match TaskStore::open(task_file) {
+        Ok(store) => store,
+        Err(e) => return vec![Finding::problem(e.to_string(), "fix the line it names, or put a backup back with `yarmtl restore`")],
+    };
+    let tasks = store.tasks();
+    let problems = check_tasks(tasks);
+    if problems.is_empty() {
+        return vec![Finding::Fine(format!("No problems found in {} tasks", tasks.len()))];
+    }
+    problems
+        .into_iter()
+        .map(|problem| {
+            let fix = match problem.message.contains("same id") {
+                true => "open the tui once, or give it an id of its own by hand",
+                false => "point the link at the id of the task it means, `yarmtl search` finds it",
+            };
+            Finding::problem(format!("\"{}\" [{}]: {}", problem.task_text, problem.task_id, problem.message), fix)
+        })
+        .collect()
+}
+
+/// config.toml, which yarmtl reads as all defaults when it's invalid.
+pub fn check_config(config_file: &Path) -> Vec<Finding> {
+    match Config::load(config_file) {
+        Ok(_) if !config_file.exists() => vec![Finding::Fine("No config.toml, everything is as by default".to_string())],
+        Ok(_) => vec![Finding::Fine(format!("{} reads fine", config_file.display()))],
+        Err(e) => vec![Finding::problem(
+            format!("{} is invalid, yarmtl uses the defaults instead: {}", config_file.display(), e),
+            "fix the line it names",
+        )],
+    }
+}
+
+/// The repository the tasks are committed to.
+pub fn check_git(sync_dir: &Path, auto_commit: bool) -> Vec<Finding> {
+    if !git::is_enabled() {
+        return vec![Finding::Fine("Git is off with --no-git".to_string())];
+    }
+    if !auto_commit {
+        return vec![Finding::Fine("Nothing is committed, auto_commit is off in the [git] table".to_string())];
+    }
+    if !git::is_repository(sync_dir) {
+        return vec![Finding::Fine("No repository yet, the next change makes one".to_string())];
+    }
+    let health = match git::health(sync_dir) {
+        Ok(health) => health,
+        Err(e) => return vec![Finding::problem(e.to_string(), "see whether `git status` works in the tasks directory")],
+    };
+
+    let mut findings = Vec::new();
+    if let Some(command) = health.unfinished {
+        let abort = match command {
+            "bisect" => "git bisect reset".to_string(),
+            command => format!("git {} --abort", command),
+        };
+        findings.push(Finding::problem(
+            format!("A git {} is unfinished in {}, nothing is committed until it's done", command, health.path.display()),
+            format!("finish it, or give it up with `{}`", abort),
+        ));
+    }
+    if !health.conflicts.is_empty() {
+        findings.push(Finding::problem(
+            format!("{} have conflicts", health.conflicts.join(", ")),
+            "resolve them, `git add` them and commit",
+        ));
+    }
+    let Some(branch) = health.branch else {
+        findings.push(Finding::problem("HEAD isn't on a branch, commits end up on none", "`git switch` to the branch of the tasks"));
+        return findings;
+    };
+    if let Ok(state) = git::git_state(sync_dir) {
+        if !state.changes.is_empty() {
+            findings.push(Finding::warning(
+                format!("Uncommitted changes to {}", state.changes.join(", ")),
+                "the next change in yarmtl commits the task files, commit anything else with git",
+            ));
+        }
+        if let Some(ahead) = state.ahead.filter(|ahead| *ahead > 0) {
+            findings.push(Finding::warning(format!("{} commit(s) not pushed to origin", ahead), "`git push`, or make a change and yarmtl pushes"));
+        }
+    }
+    if findings.is_empty() {
+        let remote = health.remote.map(|url| format!(", pushed to {}", url)).unwrap_or_default();
+        findings.push(Finding::Fine(format!("Committing to {} in {}{}", branch, health.path.display(), remote)));
+    }
+    findings
+}
+
+/// email_config.toml, and with `send_test` an email to its addresses to see that they get it.
+pub async fn check_email(send_test: bool) -> Vec<Finding> {
+    let setup = "run `yarmtl --setup-email` again";
+    if !get_email_config_path().exists() {
+        return vec![Finding::Fine("Email isn't set up, `yarmtl --setup-email` does".to_string())];
+    }
+    let config = match load_email_config() {
+        Ok(config) => config,
+        Err(e) => return vec![Finding::problem(e.to_string(), setup)],
+    };
+
+    let mut findings = Vec::new();
+    let recipients = reminder_email::recipients(&config.to_email, &config.routes);
+    for address in std::iter::once(config.from_email.as_str()).chain(recipients.iter().copied()) {
+        if let Err(e) = address.parse::<lettre::Address>() {
+            findings.push(Finding::problem(format!("{} isn't an email address: {}", address, e), "correct it in email_config.toml"));
+        }
+    }
+    match config.auth {
+        SmtpAuth::Password if email_auth::PASSWORD_STORE.get().is_err() => {
+            findings.push(Finding::problem("No SMTP password is stored", setup));
+        }
+        SmtpAuth::Gmail | SmtpAuth::Outlook if config.client_id.is_none() => {
+            findings.push(Finding::problem("email_config.toml has no client_id to sign in with", setup));
+        }
+        _ => {}
+    }
+    if !findings.is_empty() {
+        return findings;
+    }
+
+    if !send_test {
+        findings.push(Finding::Fine(format!(
+            "Reminders go from {} to {} through {}:{}, `yarmtl doctor --send-test-email` tries it",
+            config.from_email,
+            recipients.join(", "),
+            config.smtp_server,
+            config.smtp_port
+        )));
+        return findings;
+    }
+    let text = "yarmtl doctor sent this to see that the reminders get through.".to_string();
+    let html = format!("<p>{}</p>", text);
+    match email::mail(&config, &recipients, "✅ yarmtl test email", text, html).await {
+        Ok(()) => findings.push(Finding::Fine(format!("Sent a test email to {}", recipients.join(", ")))),
+        Err(e) => findings.push(Finding::problem(e.to_string(), format!("check smtp_server and smtp_port in email_config.toml, then {}", setup))),
+    }
+    findings
+}
+
+/// todoist_config.toml and whether Todoist takes the token.
+pub async fn check_todoist() -> Vec<Finding> {
+    let setup = "run `yarmtl --setup-todoist` again";
+    if !get_todoist_config_path().exists() {
+        return vec![Finding::Fine("Todoist sync isn't set up, `yarmtl --setup-todoist` does".to_string())];
+    }
+    let Some(config) = load_todoist_config() else {
+        return vec![Finding::problem("todoist_config.toml is invalid", setup)];
+    };
+    if !config.enabled {
+        return vec![Finding::Fine("Todoist sync is off, enabled = false in todoist_config.toml".to_string())];
+    }
+    let token = match TodoistAuth::get_token() {
+        Ok(token) => token,
+        Err(e) => return vec![Finding::problem(e.to_string(), setup)],
+    };
+    match TodoistAuth::verify_token(&token).await {
+        Ok(true) => vec![Finding::Fine("Todoist accepts the token".to_string())],
+        Ok(false) => vec![Finding::problem("Todoist turns the token down", format!("make a new one in Todoist's settings, under Integrations, and {}", setup))],
+        Err(e) => vec![Finding::warning(format!("Couldn't reach Todoist: {}", e), "check the connection and try again")],
+    }
+}
+
+/// The system keyring, where tokens and passwords go.
+pub fn check_keyring() -> Vec<Finding> {
+    match todoist_auth::keyring_available() {
+        Ok(()) => vec![Finding::Fine("The system keyring keeps the tokens and passwords".to_string())],
+        Err(e) => vec![Finding::warning(
+            format!("No system keyring ({}), tokens and passwords are kept in files in {}", e, setup::data_dir().display()),
+            "start a secret service like gnome-keyring or KWallet, then set the integrations up again",
+        )],
+    }
+}
+
+/// Whether the daemon runs, how its jobs went, and whether its schedule in config.toml makes sense.
+pub fn check_daemon(state_dir: &Path, config: &Config, todoist: Option<&TodoistConfig>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let notifications = &config.notifications;
+    let quiet = &config.quiet;
+    for (name, hour) in [("from_hour of [notifications]", Some(notifications.from_hour)), ("start_hour of [quiet]", quiet.start_hour), ("end_hour of [quiet]", quiet.end_hour)] {
+        if let Some(hour) = hour.filter(|hour| *hour > 23) {
+            findings.push(Finding::problem(format!("{} is {}, which isn't an hour", name, hour), "make it 0 to 23"));
+        }
+    }
+    match (quiet.start_hour, quiet.end_hour) {
+        (Some(start), Some(end)) if start == end => {
+            findings.push(Finding::warning("start_hour and end_hour of [quiet] are the same, so there are no quiet hours", "make end_hour the hour they end"));
+        }
+        (Some(_), None) | (None, Some(_)) => {
+            findings.push(Finding::warning("[quiet] has only one of start_hour and end_hour, so there are no quiet hours", "set both"));
+        }
+        _ => {}
+    }
+    if notifications.enabled && notifications.from_hour < 24 && quiet.is_quiet_hour(notifications.from_hour) {
+        findings.push(Finding::warning(
+            format!("Notifications start at {}:00, in the quiet hours, so they wait until {}:00", notifications.from_hour, quiet.end_hour.unwrap_or(0)),
+            "move from_hour of [notifications] out of the quiet hours",
+        ));
+    }
+
+    let syncs = todoist.filter(|todoist| todoist.enabled && todoist.sync_interval_minutes > 0);
+    let needed = get_email_config_path().exists() || notifications.enabled && !config.push.is_empty() || !config.webhooks.is_empty() || syncs.is_some();
+    match daemon::running_pid(state_dir) {
+        Some(pid) => findings.push(Finding::Fine(format!("The daemon is running, pid {}", pid))),
+        None if needed => findings.push(Finding::warning(
+            "The daemon isn't running, so no reminders, pushes, webhooks or scheduled syncs go out",
+            "`yarmtl daemon install` starts it at every login, `yarmtl daemon start` just now",
+        )),
+        None => findings.push(Finding::Fine("The daemon isn't running, nothing set up needs it".to_string())),
+    }
+    let log = state_dir.join(daemon::LOG_FILE);
+    for (job, run) in daemon::load_status(state_dir).map(|status| status.runs).unwrap_or_default() {
+        if !run.ok {
+            findings.push(Finding::warning(
+                format!("Its last {} run failed at {}: {}", job, run.at.format("%Y-%m-%d %H:%M"), run.message.unwrap_or_default()),
+                format!("see {}", log.display()),
+            ));
+        }
+    }
+    findings
+}
+
+/// Runs every check, printing what each found with what to do about it. With
+/// `send_test_email`, sends an email to the addresses of email_config.toml too. Returns
+/// false when something is broken; warnings alone don't count.
+pub async fn run_doctor(send_test_email: bool) -> bool {
+    let sync_dir = get_sync_dir();
+    let task_file = get_tasks_file_path();
+    let config = load_config();
+    let sections = [
+        (format!("🩺 Checking {}", task_file.display()), check_tasks_file(&task_file)),
+        ("⚙️  config.toml".to_string(), check_config(&get_config_path())),
+        ("🌿 Git".to_string(), check_git(&sync_dir, config.git.auto_commit)),
+        ("📧 Email".to_string(), check_email(send_test_email).await),
+        ("🔄 Todoist".to_string(), check_todoist().await),
+        ("🔑 Keyring".to_string(), check_keyring()),
+        ("⏰ Daemon".to_string(), check_daemon(&get_state_dir(), &config, load_todoist_config().as_ref())),
+    ];
+
+    let (mut problems, mut warnings) = (0, 0);
+    for (index, (title, findings)) in sections.iter().enumerate() {
+        if index > 0 {
+            output::say("");
+        }
+        output::say(title);
+        for finding in findings {
+            match finding {
+                Finding::Fine(message) => output::say(&format!("✅ {}", message)),
+                Finding::Warning { message, fix } => {
+                    warnings += 1;
+                    output::say(&format!("⚠️  {}\n   → {}", message, fix));
+                }
+                Finding::Problem { message, fix } => {
+                    problems += 1;
+                    output::say(&format!("❌ {}\n   → {}", message, fix));
+                }
+            }
+        }
+    }
+
+    if problems + warnings > 0 {
+        output::say(&format!("\n{} problem(s) and {} warning(s) found", problems, warnings));
+    }
+    problems == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dangling_link_is_reported() {
+        let tasks = vec![
+            Task::parse("first [id:aaaa1111]"),
+            Task::parse("after [[id:aaaa]] [id:bbbb2222]"),
+            Task::parse("blocked by [[id:dead]] [id:cccc3333]"),
+        ];
+
+        let problems = check_tasks(&tasks);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].task_id, "cccc3333");
+        assert!(problems[0].message.contains("[[id:dead]]"));
+
+        let copied = vec![Task::parse("first [id:aaaa1111]"), Task::parse("copy [id:aaaa1111]")];
+        assert_eq!(check_tasks(&copied)[0].task_text, "copy");
+    }
+
+    #[test]
+    fn test_daemon_schedule_sanity() {
+        let temp = tempfile::tempdir().unwrap();
+        let state_dir = temp.path();
+        let mut config = Config::default();
+        config.quiet.start_hour = Some(22);
+        config.quiet.end_hour = Some(9);
+        config.notifications.from_hour = 8;
+        let findings = check_daemon(state_dir, &config, None);
+        assert!(matches!(&findings[0], Finding::Warning { message, .. } if message.contains("wait until 9:00")));
+
+        config.quiet.end_hour = Some(24);
+        config.notifications.from_hour = 30;
+        let findings = check_daemon(state_dir, &config, None);
+        let problems: Vec<&Finding> = findings.iter().filter(|finding| matches!(finding, Finding::Problem { .. })).collect();
+        assert_eq!(problems.len(), 2);
+
+        config.quiet.end_hour = None;
+        config.notifications.from_hour = 8;
+        let findings = check_daemon(state_dir, &config, None);
+        assert!(matches!(&findings[0], Finding::Warning { message, .. } if message.contains("only one of")));
+    }
+}
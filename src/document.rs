@@ -0,0 +1,563 @@
+use crate::encryption;
+use crate::ids;
+use crate::paths;
+use crate::subtasks::{self, TaskNode};
+use crate::task::{self, Task};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// What a new tasks.md starts with.
+const NEW_FILE: &str = "# tasks\n\n";
+
+/// In the state directory of the tasks directory, locked by whoever is writing tasks.md.
+const LOCK_FILE: &str = ".tasks.lock";
+
+/// tasks.md as the user wrote it: the task lines yarmtl owns and everything around
+/// them (headings, free-form notes, blank lines), which saving leaves in place.
+///
+/// It keeps the tasks its lines hold: rendering again only renders the tasks that changed
+/// since, every other line stays as it was written, and `reparse` only parses the lines that
+/// changed, which matters to a tasks.md holding years of tasks.
+pub struct Document {
+    /// The part above the first `## ` heading, then one per project heading
+    sections: Vec<Section>,
+}
+
+struct Section {
+    project: Option<String>,
+    /// Starting with the heading itself
+    lines: Vec<Line>,
+}
+
+enum Line {
+    /// A task line, with the task it holds. It stays as it is for as long as the task
+    /// does, whether `render` wrote it or the user did, maybe with `!friday` or an Obsidian
+    /// `🔁 every week`; the task is only written the way yarmtl writes it once it changes.
+    Task { task: Box<Task>, text: String },
+    Other(String),
+}
+
+impl Document {
+    pub fn parse(content: &str) -> Self {
+        Document::parse_known(content, &HashMap::new())
+    }
+
+    /// `content` parsed, with the task lines this document already holds taken as they are
+    /// instead of being parsed again: what a file read or written by yarmtl and changed
+    /// since by something else is read with.
+    pub fn reparse(&self, content: &str) -> Self {
+        let mut seen = task::LinesSeen::default();
+        let known = self.task_lines().map(|(text, task)| ((text, seen.count(text)), task)).collect();
+        Document::parse_known(content, &known)
+    }
+
+    /// `content` parsed, the lines in `known` being the lines of those tasks, with how
+    /// many of the same came before them.
+    fn parse_known(content: &str, known: &HashMap<(&str, usize), &Task>) -> Self {
+        let content = if content.trim().is_empty() { NEW_FILE } else { content };
+        let mut sections = vec![Section { project: None, lines: Vec::new() }];
+        let mut seen = task::LinesSeen::default();
+        for text in content.lines() {
+            if let Some(project) = task::project_heading(text) {
+                sections.push(Section { project: Some(project.to_string()), lines: Vec::new() });
+            }
+            let before = seen.count(text);
+            let line = match known.get(&(text, before)) {
+                Some(&task) => Line::Task { task: Box::new(task.clone()), text: text.to_string() },
+                None => match task::parse_task_line_after(text, before) {
+                    Some(task) => Line::Task { task: Box::new(task), text: text.to_string() },
+                    None => Line::Other(text.to_string()),
+                },
+            };
+            sections.last_mut().unwrap().lines.push(line);
+        }
+        Document { sections }
+    }
+
+    /// The tasks, as `task::parse_tasks` reads them from the content.
+    pub fn tasks(&self) -> Vec<Task> {
+        let mut tasks = Vec::new();
+        for section in &self.sections {
+            for line in &section.lines {
+                if let Line::Task { task, .. } = line {
+                    tasks.push(Task { project: section.project.clone(), ..(**task).clone() });
+                }
+            }
+        }
+        subtasks::link_parents(tasks)
+    }
+
+    /// The text of every task line and its task.
+    fn task_lines(&self) -> impl Iterator<Item = (&str, &Task)> {
+        self.sections.iter().flat_map(|section| &section.lines).filter_map(|line| match line {
+            Line::Task { task, text } => Some((text.as_str(), &**task)),
+            Line::Other(_) => None,
+        })
+    }
+
+    /// The document with its task lines replaced by `tasks`. A task stays in the section
+    /// it was in as long as its project doesn't change, otherwise it goes to the first
+    /// section of its project, which is added at the end when there is none yet.
+    pub fn render(&self, tasks: &[Task]) -> String {
+        self.rewrite(tasks).0
+    }
+
+    /// `render`, along with the document of what it rendered.
+    pub fn rewrite(&self, tasks: &[Task]) -> (String, Document) {
+        let mut was_in: HashMap<&str, usize> = HashMap::new();
+        for (index, section) in self.sections.iter().enumerate() {
+            for line in &section.lines {
+                if let Line::Task { task, .. } = line {
+                    was_in.entry(task.id.as_str()).or_insert(index);
+                }
+            }
+        }
+
+        // Subtasks go wherever their top-level task goes
+        let mut buckets: Vec<Vec<TaskNode>> = self.sections.iter().map(|_| Vec::new()).collect();
+        let mut new_projects: Vec<String> = Vec::new();
+        for node in subtasks::build_tree(tasks.to_vec()) {
+            let project = node.task.project.as_deref();
+            let in_project = |index: &usize| self.sections[*index].project.as_deref() == project;
+            let target = match was_in.get(node.task.id.as_str()).filter(|i| in_project(i)) {
+                Some(&index) => index,
+                None => match (0..self.sections.len()).find(in_project) {
+                    Some(index) => index,
+                    None => {
+                        let project = project.unwrap_or_default();
+                        let position = new_projects.iter().position(|p| p == project).unwrap_or_else(|| {
+                            new_projects.push(project.to_string());
+                            buckets.push(Vec::new());
+                            new_projects.len() - 1
+                        });
+                        self.sections.len() + position
+                    }
+                },
+            };
+            buckets[target].push(node);
+        }
+
+        // The lines of the tasks that didn't change since, as they were written. The tasks
+        // are compared the way they are read, in their section's project and under their parent
+        let mut unchanged: HashMap<String, (&str, Task)> = HashMap::new();
+        for ((text, _), before) in self.task_lines().zip(self.tasks()) {
+            unchanged.entry(before.id.clone()).or_insert((text, before));
+        }
+        let mut line_of = |task: Task| -> Line {
+            let kept = unchanged.get(&task.id).filter(|(_, before)| *before == task);
+            let text = kept.map_or_else(|| task.to_markdown(), |(text, _)| text.to_string());
+            Line::Task { task: Box::new(task), text }
+        };
+
+        let mut sections = Vec::new();
+        let mut buckets = buckets.into_iter();
+        let last = self.sections.len() - 1;
+        for (index, section) in self.sections.iter().enumerate() {
+            let tasks = subtasks::flatten(buckets.next().unwrap());
+            sections.push(section.render(tasks, index == last, &mut line_of));
+        }
+        for (project, nodes) in new_projects.into_iter().zip(buckets) {
+            let above = sections.last_mut().map(|section: &mut Section| &mut section.lines);
+            if let Some(above) = above.filter(|lines| !lines.last().is_some_and(|line| line.text().is_empty())) {
+                above.push(Line::Other(String::new()));
+            }
+            let mut lines = vec![Line::Other(format!("## {}", project))];
+            lines.extend(subtasks::flatten(nodes).into_iter().map(&mut line_of));
+            sections.push(Section { project: Some(project), lines });
+        }
+        let document = Document { sections };
+        (document.text(), document)
+    }
+
+    /// The file the document is.
+    fn text(&self) -> String {
+        let mut content = String::new();
+        for line in self.sections.iter().flat_map(|section| &section.lines) {
+            content.push_str(line.text());
+            content.push('\n');
+        }
+        content
+    }
+}
+
+impl Line {
+    fn text(&self) -> &str {
+        match self {
+            Line::Task { text, .. } | Line::Other(text) => text,
+        }
+    }
+}
+
+impl Section {
+    /// Lines above the section's first task stay at its top and every other line stays
+    /// right above the task it preceded; when that task is gone, it moves down to the
+    /// next one that is still there, or to the end of the section.
+    fn render(&self, tasks: Vec<Task>, is_last: bool, line_of: &mut impl FnMut(Task) -> Line) -> Section {
+        let kept: HashSet<&str> = tasks.iter().map(|t| t.id.as_str()).collect();
+        let mut leading = Vec::new();
+        let mut above: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut pending = Vec::new();
+        let mut seen_task = false;
+        for line in &self.lines {
+            match line {
+                Line::Other(text) if seen_task => pending.push(text.as_str()),
+                Line::Other(text) => leading.push(text.as_str()),
+                Line::Task { task, .. } => {
+                    seen_task = true;
+                    if kept.contains(task.id.as_str()) {
+                        above.entry(task.id.as_str()).or_default().append(&mut pending);
+                    }
+                }
+            }
+        }
+        // In a section that had no tasks, new ones go before the blank lines separating it from the next
+        if !seen_task && !is_last {
+            while leading.last().is_some_and(|line| line.trim().is_empty()) {
+                pending.insert(0, leading.pop().unwrap());
+            }
+        }
+
+        let other = |text: &str| Line::Other(text.to_string());
+        let mut lines: Vec<Line> = leading.into_iter().map(other).collect();
+        for task in tasks {
+            lines.extend(above.remove(task.id.as_str()).unwrap_or_default().into_iter().map(other));
+            lines.push(line_of(task));
+        }
+        lines.extend(pending.into_iter().map(other));
+        Section { project: self.project.clone(), lines }
+    }
+}
+
+/// Merges two versions of a task file that both started from `base`, as a `git pull` finds
+/// them when each side changed it: the tasks by id with `merge_tasks`, the edit made here
+/// winning where both changed one, and the lines around them as they are here, followed by
+/// those only the other side added. A task each side added with the same id keeps the id
+/// here and gets a new one there.
+pub fn merge_task_files(base: &str, ours: &str, theirs: &str) -> String {
+    let (base_tasks, our_tasks, mut their_tasks) = (task::parse_tasks(base), task::parse_tasks(ours), task::parse_tasks(theirs));
+    let in_base: HashSet<&str> = base_tasks.iter().map(|t| t.id.as_str()).collect();
+    let mut taken: HashSet<String> = base_tasks.iter().chain(&our_tasks).chain(&their_tasks).map(|t| t.id.clone()).collect();
+    let mut renamed: HashMap<String, String> = HashMap::new();
+    for task in &mut their_tasks {
+        let clash = !in_base.contains(task.id.as_str()) && our_tasks.iter().any(|ours| ours.id == task.id && ours != task);
+        if clash {
+            let id = ids::new_id(&taken);
+            taken.insert(id.clone());
+            renamed.insert(std::mem::replace(&mut task.id, id.clone()), id);
+        }
+    }
+    for task in &mut their_tasks {
+        if let Some(id) = task.parent_id.as_ref().and_then(|parent| renamed.get(parent)) {
+            task.parent_id = Some(id.clone());
+        }
+    }
+
+    let merged = merge_tasks(&base_tasks, &our_tasks, &their_tasks, Prefer::Memory);
+    let mut content = Document::parse(ours).render(&merged);
+    let known: HashSet<&str> = base.lines().chain(ours.lines()).chain(content.lines()).collect();
+    let added: Vec<&str> = theirs
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !known.contains(line) && task::parse_task_line(line).is_none())
+        .collect();
+    if !added.is_empty() {
+        content.push('\n');
+        for line in added {
+            content.push_str(line);
+            content.push('\n');
+        }
+    }
+    content
+}
+
+/// `content` with `line`, a task without a project, where `Document::render` would put it:
+/// at the end of the part above the first `## ` heading. Only that part is looked at, so
+/// it takes no longer on a tasks.md holding years of tasks.
+pub fn insert_task_line(content: &str, line: &str) -> String {
+    if content.trim().is_empty() {
+        return format!("{}{}\n", NEW_FILE, line);
+    }
+    // Right after the last task there, or the last line that isn't blank when there's none
+    let (mut after_task, mut after_text) = (None, 0);
+    let mut offset = 0;
+    for text in content.split_inclusive('\n') {
+        if text.starts_with("## ") {
+            break;
+        }
+        offset += text.len();
+        if task::parse_task_line(text.trim_end()).is_some() {
+            after_task = Some(offset);
+        } else if !text.trim().is_empty() {
+            after_text = offset;
+        }
+    }
+    let (before, after) = content.split_at(after_task.unwrap_or(after_text));
+    let newline = if before.ends_with('\n') { "" } else { "\n" };
+    format!("{}{}{}\n{}", before, newline, line, after)
+}
+
+/// The file a line of tasks.md pulls the tasks of into it, `<!-- include: work/tasks.md -->`,
+/// relative to tasks.md. Markdown viewers show nothing for the line.
+pub fn include_line(line: &str) -> Option<&str> {
+    let comment = line.trim().strip_prefix("<!--")?.strip_suffix("-->")?;
+    let path = comment.trim().strip_prefix("include:")?.trim();
+    (!path.is_empty()).then_some(path)
+}
+
+/// The files tasks.md includes, in the order it names them, each once.
+pub fn includes(content: &str) -> Vec<String> {
+    let mut includes: Vec<String> = Vec::new();
+    for path in content.lines().filter_map(include_line) {
+        if !includes.iter().any(|included| included == path) {
+            includes.push(path.to_string());
+        }
+    }
+    includes
+}
+
+/// Waits for and takes the lock on tasks.md at `path`, so a sync in the background and
+/// a save in the TUI or the CLI take turns writing it. Held until the file is dropped.
+pub fn lock(path: &Path) -> io::Result<fs::File> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let state_dir = paths::state_dir_of(dir);
+    fs::create_dir_all(&state_dir)?;
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(state_dir.join(LOCK_FILE))?;
+    lock.lock()?;
+    Ok(lock)
+}
+
+/// Whose edit stays when a task was changed both in memory and in the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Prefer {
+    Memory,
+    File,
+}
+
+/// Saves `tasks`, read from tasks.md as `base` and changed in memory since, without
+/// losing what was written to the file in the meantime, and keeping the headings and
+/// notes already in the file. Returns the tasks as saved.
+pub fn save_merged(path: &Path, base: &[Task], tasks: &[Task], prefer: Prefer) -> io::Result<Vec<Task>> {
+    save_document(path, &mut Document::parse(""), base, tasks, prefer)
+}
+
+/// `save_merged` for whoever keeps the file's `Document` around: `document` is the file
+/// as it was read or last written, so only the lines changed since are parsed and only the
+/// tasks changed since are rendered. It is then the file as written.
+pub fn save_document(path: &Path, document: &mut Document, base: &[Task], tasks: &[Task], prefer: Prefer) -> io::Result<Vec<Task>> {
+    let _lock = lock(path)?;
+    *document = document.reparse(&encryption::read(path).unwrap_or_default());
+    let in_file = document.tasks();
+    let merged = if in_file == base {
+        tasks.to_vec()
+    } else {
+        merge_tasks(base, tasks, &in_file, prefer)
+    };
+    let (content, written) = document.rewrite(&merged);
+    encryption::write(path, &content)?;
+    *document = written;
+    Ok(merged)
+}
+
+/// Three-way merge by id of two versions of the tasks that both started from `base`.
+/// A change on one side only is kept, as is an edit to a task the other side removed;
+/// `prefer` settles tasks changed on both. The order and nesting are the ones in
+/// memory, tasks only the file has go under their parent or at the end.
+pub fn merge_tasks(base: &[Task], memory: &[Task], file: &[Task], prefer: Prefer) -> Vec<Task> {
+    let by_id = |tasks: &'_ [Task]| -> HashMap<String, usize> {
+        tasks.iter().enumerate().map(|(i, t)| (t.id.clone(), i)).collect()
+    };
+    let (in_base, in_memory, in_file) = (by_id(base), by_id(memory), by_id(file));
+
+    let mut merged = Vec::new();
+    for task in memory {
+        let before = in_base.get(&task.id).map(|&i| &base[i]);
+        let other = in_file.get(&task.id).map(|&i| &file[i]);
+        match (before, other) {
+            (Some(before), None) if task == before => {}
+            (Some(before), Some(other)) if other != before && (task == before || prefer == Prefer::File) => {
+                merged.push(Task {
+                    indent_level: task.indent_level,
+                    parent_id: task.parent_id.clone(),
+                    ..other.clone()
+                });
+            }
+            _ => merged.push(task.clone()),
+        }
+    }
+
+    for task in file {
+        let removed_unchanged = in_base.get(&task.id).is_some_and(|&i| &base[i] == task);
+        if in_memory.contains_key(&task.id) || removed_unchanged {
+            continue;
+        }
+        let parent = task.parent_id.as_ref().and_then(|id| merged.iter().position(|t| &t.id == id));
+        match parent {
+            Some(parent) => {
+                let at = subtasks::subtask_range(&merged, parent).end;
+                let indent_level = merged[parent].indent_level + 1;
+                merged.insert(at, Task { indent_level, ..task.clone() });
+            }
+            None => merged.push(Task { indent_level: 0, ..task.clone() }),
+        }
+    }
+    subtasks::link_parents(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_keeps_other_lines() {
+        let content = "# tasks\n\nsome intro\n\n\
+            ## Work\n\
+            - [ ] report [id:aaaa0001]\n\
+            \x20 - [ ] charts [id:aaaa0002]\n\
+            \n\
+            ## Home\n\
+            > remember the bins\n\
+            - [ ] dishes [id:aaaa0003]\n\
+            \n\
+            footer\n";
+        let document = Document::parse(content);
+        let mut tasks = task::parse_tasks(content);
+        assert_eq!(document.render(&tasks), content);
+
+        // Lines above a removed task move down to the next task, or to the end.
+        // New tasks go to the end of their project's section, which is added when missing
+        tasks.remove(2);
+        tasks.remove(0);
+        let in_project = |line: &str, project: Option<&str>| Task {
+            project: project.map(str::to_string),
+            ..Task::parse(line)
+        };
+        tasks.push(in_project("new one [id:aaaa0004]", Some("Work")));
+        tasks.push(in_project("weeding [id:aaaa0005]", Some("Garden")));
+        tasks.push(in_project("loose end [id:aaaa0006]", None));
+        assert_eq!(
+            document.render(&tasks),
+            "# tasks\n\nsome intro\n\
+             - [ ] loose end [id:aaaa0006]\n\
+             \n\
+             ## Work\n\
+             - [ ] charts [id:aaaa0002]\n\
+             - [ ] new one [id:aaaa0004]\n\
+             \n\
+             ## Home\n\
+             > remember the bins\n\
+             \n\
+             footer\n\
+             \n\
+             ## Garden\n\
+             - [ ] weeding [id:aaaa0005]\n"
+        );
+    }
+
+    #[test]
+    fn test_insert_task_line_where_render_would() {
+        let new = Task::parse("call bob [id:aaaa0009]");
+        for content in [
+            "# tasks\n\n- [ ] one [id:aaaa0001]\n  - [ ] sub [id:aaaa0002]\nsome notes\n\n## Work\n- [ ] two [id:aaaa0003]\n",
+            "# tasks\n\n## Work\n- [ ] two [id:aaaa0003]\n",
+            "- [ ] one [id:aaaa0001]",
+            "",
+        ] {
+            let mut tasks = task::parse_tasks(content);
+            tasks.push(new.clone());
+            let rendered = match content {
+                "" => Document::parse(NEW_FILE).render(&tasks),
+                _ => Document::parse(content).render(&tasks),
+            };
+            assert_eq!(insert_task_line(content, &new.to_markdown()), rendered, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn test_merge_keeps_both_sides() {
+        let base = task::parse_tasks(
+            "- [ ] report [id:aaaa0001]\n  - [ ] charts [id:aaaa0002]\n- [ ] dishes [id:aaaa0003]\n- [ ] bins [id:aaaa0004]\n",
+        );
+        // Edited the report, removed the dishes and added a task in memory...
+        let memory = task::parse_tasks(
+            "- [ ] report by friday [id:aaaa0001]\n  - [ ] charts [id:aaaa0002]\n- [ ] bins [id:aaaa0004]\n- [ ] call bob [id:aaaa0005]\n",
+        );
+        // ...while a sync edited the report and the charts, removed the bins and added a subtask
+        let file = task::parse_tasks(
+            "- [ ] report by monday [id:aaaa0001]\n  - [x] charts [id:aaaa0002]\n  - [ ] tables [id:aaaa0006]\n- [ ] dishes [id:aaaa0003]\n",
+        );
+
+        let merged = merge_tasks(&base, &memory, &file, Prefer::Memory);
+        let lines: Vec<String> = merged.iter().map(Task::to_markdown).collect();
+        assert_eq!(
+            lines,
+            [
+                "- [ ] report by friday [id:aaaa0001]",
+                "  - [x] charts [id:aaaa0002]",
+                "  - [ ] tables [id:aaaa0006]",
+                "- [ ] call bob [id:aaaa0005]",
+            ]
+        );
+        assert_eq!(merge_tasks(&base, &memory, &file, Prefer::File)[0].text, "report by monday");
+        assert_eq!(merge_tasks(&base, &memory, &base, Prefer::File), memory);
+    }
+
+    #[test]
+    fn test_merge_task_files_from_two_machines() {
+        let base = "# tasks\n\n- [ ] pay rent [id:aaaa01]\n- [ ] call bob [id:aaaa02]\n";
+        let ours = "# tasks\n\n- [x] pay rent [id:aaaa01]\n- [ ] call bob [id:aaaa02]\n- [ ] water plants [id:bbbb01]\n";
+        let theirs = "# tasks\nbills first\n\n- [ ] pay rent [id:aaaa01]\n- [ ] call bob today [id:aaaa02]\n- [ ] book flights [id:bbbb01]\n";
+
+        let merged = merge_task_files(base, ours, theirs);
+        let tasks = task::parse_tasks(&merged);
+        let texts: Vec<&str> = tasks.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, ["pay rent", "call bob today", "water plants", "book flights"]);
+        assert!(tasks[0].completed);
+        assert_eq!(tasks[2].id, "bbbb01");
+        assert_ne!(tasks[3].id, "bbbb01");
+        assert!(merged.starts_with("# tasks\n\n- [x] pay rent") && merged.ends_with("\nbills first\n"));
+    }
+
+    #[test]
+    fn test_saving_unchanged_tasks_keeps_their_lines() {
+        let content = "# tasks\n\n\
+            - [ ] water plants 🔁 every week 📅 2030-03-12\n\
+            - [ ] call bob   !2030-03-14 #phone\n\
+            \x20 - [x] find his number\n\
+            ## Work\n\
+            - [ ] report [id:aaaa0001] $2\n";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.md");
+        fs::write(&path, content).unwrap();
+        let mut document = Document::parse(content);
+        let mut tasks = document.tasks();
+        save_document(&path, &mut document, &tasks, &tasks, Prefer::Memory).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+
+        // Only the line of the task that changed is written again
+        tasks[3].completed = true;
+        let written = document.render(&tasks);
+        let changed: Vec<(&str, &str)> = content.lines().zip(written.lines()).filter(|(a, b)| a != b).collect();
+        assert_eq!(changed, [("- [ ] report [id:aaaa0001] $2", tasks[3].to_markdown().as_str())]);
+    }
+
+    #[test]
+    fn test_reparse_reads_what_parse_reads() {
+        let content = "# tasks\n\n## Work\n- [ ] report [id:aaaa0001]\n  - [ ] charts [id:aaaa0002]\n\n## Home\n- [ ] dishes [id:aaaa0003]\n";
+        let mut tasks = Document::parse(content).tasks();
+        assert_eq!(tasks, task::parse_tasks(content));
+
+        tasks[2].completed = true;
+        let (written, document) = Document::parse(content).rewrite(&tasks);
+        assert_eq!(written, content.replace("- [ ] dishes", "- [x] dishes"));
+        assert_eq!(document.tasks(), tasks);
+
+        // Changed since by something else: the lines it doesn't know are parsed
+        let edited = written.replace("report", "report by friday").replace("## Home", "## Chores");
+        assert_eq!(document.reparse(&edited).tasks(), task::parse_tasks(&edited));
+    }
+}
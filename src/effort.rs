@@ -0,0 +1,106 @@
+use crate::task::{Task, format_duration};
+use chrono::NaiveDate;
+use std::time::Duration;
+
+/// Estimated work on the agenda of `day`: the open tasks due that day or earlier.
+pub fn planned_for(tasks: &[Task], day: NaiveDate) -> Duration {
+    tasks
+        .iter()
+        .filter(|t| !t.completed && t.deadline.is_some_and(|d| d <= day))
+        .filter_map(|t| t.estimate)
+        .sum()
+}
+
+/// `daily_capacity_hours` from `[tasks]`, 0 turns the warning off.
+pub fn capacity(hours: u32) -> Option<Duration> {
+    (hours > 0).then(|| Duration::from_secs(hours as u64 * 3600))
+}
+
+/// "~3h30m", with a warning when that is more than fits in a day.
+pub fn describe_planned(planned: Duration, capacity: Option<Duration>) -> String {
+    match capacity {
+        Some(capacity) if planned > capacity => {
+            format!("~{} ⚠ over the {} capacity", format_duration(planned), format_duration(capacity))
+        }
+        _ => format!("~{}", format_duration(planned)),
+    }
+}
+
+/// "+1h15m", "-30m" or "±0".
+fn format_difference(estimate: Duration, spent: Duration) -> String {
+    if spent > estimate {
+        format!("+{}", format_duration(spent - estimate))
+    } else if spent < estimate {
+        format!("-{}", format_duration(estimate - spent))
+    } else {
+        "±0".to_string()
+    }
+}
+
+/// Estimates against pomodoro time for the tasks that have both, the ones that took
+/// longest past their estimate first.
+pub fn render_estimate_report(tasks: &[Task]) -> String {
+    let mut tracked: Vec<(&Task, Duration, Duration)> = tasks
+        .iter()
+        .filter_map(|t| Some((t, t.estimate?, t.spent?)))
+        .collect();
+    tracked.sort_by_key(|(_, estimate, spent)| std::cmp::Reverse(spent.as_secs() as i64 - estimate.as_secs() as i64));
+
+    let mut out = "# estimates vs. time spent\n\n".to_string();
+    if tracked.is_empty() {
+        out.push_str("No task has both an estimate (~2h) and pomodoro time yet.\n");
+        return out;
+    }
+
+    out.push_str("| task | estimate | spent | off by |\n|---|---|---|---|\n");
+    for (task, estimate, spent) in &tracked {
+        out.push_str(&format!(
+            "| {}{} | {} | {} | {} |\n",
+            task.text,
+            if task.completed { " ✓" } else { "" },
+            format_duration(*estimate),
+            format_duration(*spent),
+            format_difference(*estimate, *spent)
+        ));
+    }
+
+    let estimated: Duration = tracked.iter().map(|(_, estimate, _)| *estimate).sum();
+    let spent: Duration = tracked.iter().map(|(_, _, spent)| *spent).sum();
+    out.push_str(&format!(
+        "\n**{}** estimated, **{}** spent ({}% of the estimate).\n",
+        format_duration(estimated),
+        format_duration(spent),
+        spent.as_secs() * 100 / estimated.as_secs()
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_planned_and_report() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 10).unwrap();
+        let task = |line: &str| Task::parse(line);
+        let mut done = task("done already ~1h !2025-06-10 [spent:2h]");
+        done.completed = true;
+        let tasks = vec![
+            task("overdue ~2h !2025-06-01 [spent:1h30m]"),
+            task("today ~45m !2025-06-10"),
+            task("tomorrow ~3h !2025-06-11"),
+            task("no deadline ~1h"),
+            done,
+        ];
+
+        let planned = planned_for(&tasks, today);
+        assert_eq!(planned, Duration::from_secs(165 * 60));
+        assert_eq!(describe_planned(planned, capacity(8)), "~2h45m");
+        assert_eq!(describe_planned(planned, capacity(2)), "~2h45m ⚠ over the 2h capacity");
+        assert_eq!(describe_planned(planned, capacity(0)), "~2h45m");
+
+        let report = render_estimate_report(&tasks);
+        assert!(report.contains("| done already ✓ | 1h | 2h | +1h |\n| overdue | 2h | 1h30m | -30m |"));
+        assert!(report.contains("**3h** estimated, **3h30m** spent (116% of the estimate)"));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::email_auth;
+use crate::error::YarmtlError;
+use crate::output;
+use crate::paths::get_email_config_path;
+use crate::reminder_email;
+use lettre::message::MultiPart;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Deserialize, Serialize)]
+pub struct EmailConfig {
+    pub smtp_server: String,
+    pub smtp_port: u16,
+    pub username: String,
+    /// Only read to move it to the keyring, see `load_email_config`
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    pub from_email: String,
+    /// One address or a list; they get the tasks no route takes
+    pub to_email: reminder_email::Addresses,
+    /// "password", kept in the keyring, or "gmail" or "outlook" to sign in with OAuth2
+    #[serde(default)]
+    pub auth: email_auth::SmtpAuth,
+    /// The OAuth client signing in to gmail or outlook
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Where the tasks with some tags go instead, one `[[routes]]` table each
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<reminder_email::EmailRoute>,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        EmailConfig {
+            smtp_server: "smtp.gmail.com".to_string(),
+            smtp_port: 587,
+            username: "your_email@gmail.com".to_string(),
+            password: None,
+            from_email: "your_email@gmail.com".to_string(),
+            to_email: reminder_email::Addresses::One("your_email@gmail.com".to_string()),
+            auth: email_auth::SmtpAuth::Password,
+            client_id: None,
+            routes: Vec::new(),
+        }
+    }
+}
+
+pub fn load_email_config() -> Result<EmailConfig, YarmtlError> {
+    let config_file = get_email_config_path();
+    if !config_file.exists() {
+        return Err(YarmtlError::Email("Email config file not found. Run with --setup-email first.".to_string()));
+    }
+    
+    let content = fs::read_to_string(&config_file).map_err(YarmtlError::io("read", &config_file))?;
+    let mut config: EmailConfig = toml::from_str(&content).map_err(|e| YarmtlError::parse(&config_file, e))?;
+    // A password left in the file from before moves to the keyring
+    if let Some(password) = config.password.take() {
+        match email_auth::PASSWORD_STORE.store(&password) {
+            Ok(()) => {
+                let content = toml::to_string_pretty(&config).map_err(|e| YarmtlError::parse(&config_file, e))?;
+                fs::write(&config_file, content).map_err(YarmtlError::io("write", &config_file))?;
+                tracing::info!("moved the SMTP password from email_config.toml to the keyring");
+            }
+            Err(e) => tracing::warn!("couldn't move the SMTP password out of email_config.toml: {}", e),
+        }
+    }
+    Ok(config)
+}
+
+/// Sends an email with a plain text and an HTML part; text clients show the first.
+pub async fn mail(config: &EmailConfig, to: &[&str], subject: &str, text: String, html: String) -> Result<(), YarmtlError> {
+    let failed = |e: &dyn std::fmt::Display| YarmtlError::Email(e.to_string());
+    let address = |address: &str| address.parse().map_err(|e| YarmtlError::Email(format!("{} isn't an email address: {}", address, e)));
+    let mut email = Message::builder().from(address(&config.from_email)?);
+    for to in to {
+        email = email.to(address(to)?);
+    }
+    // Emoji in emails too turn into markers under --ascii
+    let (subject, text, html) = (output::symbols(subject), output::symbols(&text), output::symbols(&html));
+    let email = email.subject(subject).multipart(MultiPart::alternative_plain_html(text, html)).map_err(|e| failed(&e))?;
+
+    let (creds, mechanisms) = email_auth::smtp_credentials(config.auth, &config.username, config.client_id.as_deref())
+        .await
+        .map_err(|e| failed(&e))?;
+    // 465 speaks TLS from the start, the rest (587 mostly) upgrades with STARTTLS
+    let relay = match config.smtp_port {
+        465 => SmtpTransport::relay(&config.smtp_server),
+        _ => SmtpTransport::starttls_relay(&config.smtp_server),
+    };
+    let mailer = relay
+        .map_err(|e| failed(&e))?
+        .port(config.smtp_port)
+        .credentials(creds)
+        .authentication(mechanisms)
+        .build();
+    mailer.send(&email).map_err(|e| YarmtlError::Email(format!("{} didn't take the email: {}", config.smtp_server, e)))?;
+    Ok(())
+}
@@ -0,0 +1,231 @@
+use crate::notifications;
+use crate::server::{self, TaskEdit};
+use crate::store::TaskStore;
+use crate::task::Task;
+use crate::todoist_auth::{AuthError, SecretStore};
+use crate::links::locate_task;
+use crate::paths::get_tasks_file_path;
+use crate::webhooks;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Html,
+    routing::get,
+    Router,
+};
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// The key the links of the reminder emails are signed with, made by the first email sent with them.
+const SECRET_STORE: SecretStore = SecretStore {
+    service: "yarmtl-email-actions",
+    username: "link-secret",
+    file_name: ".email_actions_secret",
+};
+
+/// How long a link works after the email went out.
+const VALID_DAYS: u64 = 7;
+
+/// The `[email_actions]` table of config.toml.
+///
+/// ```toml
+/// [email_actions]
+/// url = "http://nas.local:8788"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct EmailActionsConfig {
+    /// Where the links point: the daemon, as reached from wherever the email is read. No links without it
+    pub url: Option<String>,
+    pub bind: String,
+    /// Where the daemon answers the links; 0 leaves them to `yarmtl serve`, which answers them too
+    pub port: u16,
+}
+
+impl Default for EmailActionsConfig {
+    fn default() -> Self {
+        EmailActionsConfig { url: None, bind: "127.0.0.1".to_string(), port: 8788 }
+    }
+}
+
+/// What a link in a reminder email does to its task.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Done,
+    /// Remind again tomorrow
+    Snooze,
+}
+
+impl Action {
+    fn as_str(self) -> &'static str {
+        match self {
+            Action::Done => "done",
+            Action::Snooze => "snooze",
+        }
+    }
+
+    fn parse(action: &str) -> Option<Action> {
+        match action {
+            "done" => Some(Action::Done),
+            "snooze" => Some(Action::Snooze),
+            _ => None,
+        }
+    }
+}
+
+/// The link secret, made and stored the first time.
+pub fn secret() -> Result<String, AuthError> {
+    if let Ok(secret) = SECRET_STORE.get() {
+        return Ok(secret);
+    }
+    let secret = uuid::Uuid::new_v4().simple().to_string();
+    SECRET_STORE.store(&secret)?;
+    Ok(secret)
+}
+
+fn signature(secret: &str, payload: &str) -> String {
+    // Without openssl's HMAC there is nothing to sign with, and nothing a link could be checked by
+    webhooks::signature(secret, payload).map(|s| s.trim_start_matches("sha256=").to_string()).unwrap_or_default()
+}
+
+/// The token of a link: the action, the task id and the last day it works, signed.
+pub fn token(secret: &str, action: Action, id: &str, expires: NaiveDate) -> String {
+    let payload = format!("{}.{}.{}", action.as_str(), id, expires.format("%Y%m%d"));
+    let signature = signature(secret, &payload);
+    format!("{}.{}", payload, signature)
+}
+
+/// The action and task id of a token, if it was signed with `secret` and still works `today`.
+pub fn verify(secret: &str, token: &str, today: NaiveDate) -> Result<(Action, String), &'static str> {
+    let (payload, given) = token.rsplit_once('.').ok_or("this link is broken")?;
+    let expected = signature(secret, payload);
+    // Compared in full, like the api token
+    let matches = !expected.is_empty()
+        && given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0;
+    if !matches {
+        return Err("this link is broken");
+    }
+    let mut parts = payload.split('.');
+    let (Some(action), Some(id), Some(expires)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err("this link is broken");
+    };
+    let expires = NaiveDate::parse_from_str(expires, "%Y%m%d").map_err(|_| "this link is broken")?;
+    if today > expires {
+        return Err("this link has expired, the next reminder email brings new ones");
+    }
+    Ok((Action::parse(action).ok_or("this link is broken")?, id.to_string()))
+}
+
+/// The links checking the task off and snoozing it, working for a week from `today`.
+pub fn urls(url: &str, secret: &str, task: &Task, today: NaiveDate) -> (String, String) {
+    let url = url.trim_end_matches('/');
+    let expires = today + Days::new(VALID_DAYS);
+    let link = |action| format!("{}/act/{}", url, token(secret, action, &task.id, expires));
+    (link(Action::Done), link(Action::Snooze))
+}
+
+/// The lines a reminder email gives a task, linking to its actions.
+pub fn links(url: &str, secret: &str, task: &Task, today: NaiveDate) -> String {
+    let (done, snooze) = urls(url, secret, task, today);
+    format!("  ✅ Done: {}\n  😴 Tomorrow: {}\n", done, snooze)
+}
+
+fn page(status: StatusCode, body: &str) -> (StatusCode, Html<String>) {
+    let html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width\">\
+         <title>yarmtl</title></head><body style=\"font-family: sans-serif; margin: 2em\">{}</body></html>\n",
+        body
+    );
+    (status, Html(html))
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Asks before doing anything: mail scanners open the links in an email to check them.
+async fn confirm(State(secret): State<Arc<String>>, Path(token): Path<String>) -> (StatusCode, Html<String>) {
+    let today = chrono::Local::now().date_naive();
+    let (action, id) = match verify(&secret, &token, today) {
+        Ok(verified) => verified,
+        Err(e) => return page(StatusCode::FORBIDDEN, &format!("<p>❌ Sorry, {}.</p>", e)),
+    };
+    let store = match TaskStore::open(&get_tasks_file_path()) {
+        Ok(store) => store,
+        Err(e) => return page(StatusCode::INTERNAL_SERVER_ERROR, &format!("<p>❌ {}</p>", escape_html(&e.to_string()))),
+    };
+    let tasks = store.tasks();
+    let Ok(index) = locate_task(tasks, &id) else {
+        return page(StatusCode::NOT_FOUND, "<p>❌ That task is gone from tasks.md.</p>");
+    };
+    let button = match action {
+        Action::Done => "✅ Mark it done",
+        Action::Snooze => "😴 Remind me tomorrow",
+    };
+    page(
+        StatusCode::OK,
+        &format!(
+            "<p>{}</p><form method=\"post\"><button type=\"submit\" style=\"font-size: 1.2em\">{}</button></form>",
+            escape_html(&tasks[index].text),
+            button
+        ),
+    )
+}
+
+async fn act(State(secret): State<Arc<String>>, Path(token): Path<String>) -> (StatusCode, Html<String>) {
+    let today = chrono::Local::now().date_naive();
+    let (action, id) = match verify(&secret, &token, today) {
+        Ok(verified) => verified,
+        Err(e) => return page(StatusCode::FORBIDDEN, &format!("<p>❌ Sorry, {}.</p>", e)),
+    };
+    // Both commit and sync like any other change
+    let done = tokio::task::spawn_blocking(move || match action {
+        Action::Done => {
+            let edit = TaskEdit { completed: Some(true), ..TaskEdit::default() };
+            server::edit(&id, edit).map(|task| format!("✅ Done: {}", task.text)).map_err(|e| e.to_string())
+        }
+        Action::Snooze => notifications::remind_tomorrow(&id)
+            .map(|text| format!("😴 You'll be reminded tomorrow: {}", text))
+            .map_err(|e| e.to_string()),
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    match done {
+        Ok(message) => page(StatusCode::OK, &format!("<p>{}</p>", escape_html(&message))),
+        Err(e) => page(StatusCode::INTERNAL_SERVER_ERROR, &format!("<p>❌ Couldn't do it: {}</p>", escape_html(&e))),
+    }
+}
+
+/// `/act/<token>`, the links of the reminder emails: a page asking to confirm, which posts back.
+pub fn router(secret: String) -> Router {
+    Router::new().route("/act/:token", get(confirm).post(act)).with_state(Arc::new(secret))
+}
+
+/// Answers the links until the process stops, for the daemon.
+pub async fn serve(config: &EmailActionsConfig, secret: String) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind((config.bind.as_str(), config.port)).await?;
+    axum::serve(listener, router(secret)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_links() {
+        let day = |d| NaiveDate::from_ymd_opt(2025, 6, d).unwrap();
+        let token = token("s3cret", Action::Snooze, "aaaa01", day(10));
+        assert_eq!(verify("s3cret", &token, day(10)), Ok((Action::Snooze, "aaaa01".to_string())));
+        assert!(verify("s3cret", &token, day(11)).unwrap_err().contains("expired"));
+        assert!(verify("other", &token, day(10)).is_err());
+        // Another task, or another action, doesn't carry the signature over
+        assert!(verify("s3cret", &token.replace("aaaa01", "aaaa02"), day(10)).is_err());
+        assert!(verify("s3cret", &token.replace("snooze", "done"), day(10)).is_err());
+
+        let task = Task::parse("water plants [id:aaaa03]");
+        let lines = links("http://nas.local:8788/", "s3cret", &task, day(1));
+        assert!(lines.starts_with("  ✅ Done: http://nas.local:8788/act/done.aaaa03.20250608."));
+    }
+}
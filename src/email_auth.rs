@@ -0,0 +1,109 @@
+use crate::oauth::{DeviceFlow, Provider};
+use crate::todoist_auth::SecretStore;
+use lettre::transport::smtp::authentication::{Credentials, Mechanism};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// The SMTP password, for `auth = "password"`.
+pub const PASSWORD_STORE: SecretStore = SecretStore {
+    service: "yarmtl-email",
+    username: "smtp-password",
+    file_name: ".email_password",
+};
+
+/// The secret of the OAuth client, which Google wants along with its id.
+pub const CLIENT_SECRET_STORE: SecretStore = SecretStore {
+    service: "yarmtl-email",
+    username: "oauth-client-secret",
+    file_name: ".email_client_secret",
+};
+
+/// What every email gets its access token from, kept by `yarmtl --setup-email`.
+const REFRESH_TOKEN_STORE: SecretStore = SecretStore {
+    service: "yarmtl-email",
+    username: "oauth-refresh-token",
+    file_name: ".email_oauth_token",
+};
+
+/// How yarmtl signs in to the SMTP server.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpAuth {
+    /// The password in the keyring
+    #[default]
+    Password,
+    /// XOAUTH2 with a Google account
+    Gmail,
+    /// XOAUTH2 with a Microsoft 365 or Outlook.com account
+    Outlook,
+}
+
+/// Sending mail, nothing else.
+const GMAIL: Provider = Provider {
+    name: "Google",
+    device_code_url: "https://oauth2.googleapis.com/device/code",
+    token_url: "https://oauth2.googleapis.com/token",
+    scope: "https://mail.google.com/",
+    setup_command: "yarmtl --setup-email",
+    denied: "Sending mail through Google was not allowed",
+    refresh_token_store: REFRESH_TOKEN_STORE,
+};
+
+/// Sending mail, and a refresh token to keep doing it.
+const OUTLOOK: Provider = Provider {
+    name: "Microsoft",
+    device_code_url: "https://login.microsoftonline.com/common/oauth2/v2.0/devicecode",
+    token_url: "https://login.microsoftonline.com/common/oauth2/v2.0/token",
+    scope: "https://outlook.office.com/SMTP.Send offline_access",
+    setup_command: "yarmtl --setup-email",
+    denied: "Sending mail through Microsoft was not allowed",
+    refresh_token_store: REFRESH_TOKEN_STORE,
+};
+
+impl SmtpAuth {
+    fn provider(self) -> Option<&'static Provider> {
+        match self {
+            SmtpAuth::Password => None,
+            SmtpAuth::Gmail => Some(&GMAIL),
+            SmtpAuth::Outlook => Some(&OUTLOOK),
+        }
+    }
+}
+
+/// Signs in to send mail, for an OAuth client the user registers: a "TVs and Limited
+/// Input devices" client with Google, a public client with Microsoft. None for password
+/// sign-in.
+pub fn sign_in(auth: SmtpAuth, client_id: String) -> Option<DeviceFlow> {
+    // Google wants the client's secret along with its id
+    let client_secret = (auth == SmtpAuth::Gmail).then(|| CLIENT_SECRET_STORE.get().ok()).flatten();
+    Some(DeviceFlow::new(auth.provider()?, client_id, client_secret))
+}
+
+/// What to log in to the SMTP server with: the password from the keyring, or an access
+/// token for XOAUTH2.
+pub async fn smtp_credentials(
+    auth: SmtpAuth,
+    username: &str,
+    client_id: Option<&str>,
+) -> Result<(Credentials, Vec<Mechanism>), Box<dyn Error>> {
+    if auth == SmtpAuth::Password {
+        let password = PASSWORD_STORE.get().map_err(|_| "No SMTP password stored, run 'yarmtl --setup-email'")?;
+        return Ok((Credentials::new(username.to_string(), password), vec![Mechanism::Plain, Mechanism::Login]));
+    }
+    let client_id = client_id.ok_or("email_config.toml has no client_id to sign in with, run 'yarmtl --setup-email'")?;
+    let oauth = sign_in(auth, client_id.to_string()).ok_or("not an OAuth sign-in")?;
+    let access_token = oauth.access_token().await?;
+    Ok((Credentials::new(username.to_string(), access_token), vec![Mechanism::Xoauth2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_oauth_sign_ins_have_a_provider() {
+        assert!(SmtpAuth::Password.provider().is_none());
+        assert_eq!(SmtpAuth::Gmail.provider().map(|p| p.name), Some("Google"));
+        assert_eq!(SmtpAuth::Outlook.provider().map(|p| p.name), Some("Microsoft"));
+    }
+}
@@ -0,0 +1,184 @@
+use crate::config::load_config;
+use crate::todoist_auth::SecretStore;
+use age::secrecy::ExposeSecret;
+use age::x25519::Identity;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The key tasks.md.age is encrypted with, when `identity_file` doesn't say where it is.
+const KEY_STORE: SecretStore = SecretStore {
+    service: "yarmtl-age",
+    username: "identity",
+    file_name: ".age_identity",
+};
+
+/// The files `yarmtl encrypt` encrypts: tasks.md and where its tasks go when they're done
+/// with. Included files are encrypted when their name ends in .age.
+pub const TASK_FILES: [&str; 3] = ["tasks.md", "archive.md", ".trash.md"];
+
+/// The key, read once: every read and save of tasks.md.age needs it.
+static IDENTITY: OnceLock<Identity> = OnceLock::new();
+
+/// The `[encryption]` table of config.toml. Whether the tasks are encrypted is up to
+/// `yarmtl encrypt` and `yarmtl decrypt`; this only says where the key is.
+///
+/// ```toml
+/// [encryption]
+/// identity_file = "/home/me/.config/age/tasks.txt"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// An age identity file, as `age-keygen` writes them, instead of the keyring
+    pub identity_file: Option<PathBuf>,
+}
+
+/// Whether the file at `path` is kept encrypted, by its name.
+pub fn is_encrypted(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "age")
+}
+
+/// `name` in `dir`, or `name.age` when that is what there is.
+pub fn file_in(dir: &Path, name: &str) -> PathBuf {
+    let encrypted = dir.join(format!("{}.age", name));
+    match encrypted.exists() {
+        true => encrypted,
+        false => dir.join(name),
+    }
+}
+
+/// What the file at `path` holds, decrypted when it is encrypted.
+pub fn read(path: &Path) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    match is_encrypted(path) {
+        true => decrypt(&identity()?, &bytes),
+        false => String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+    }
+}
+
+/// Writes `content` to the file at `path`, encrypted when it is kept encrypted.
+pub fn write(path: &Path, content: &str) -> io::Result<()> {
+    match is_encrypted(path) {
+        true => fs::write(path, encrypt(&identity()?, content)?),
+        false => fs::write(path, content),
+    }
+}
+
+pub fn encrypt(identity: &Identity, content: &str) -> io::Result<Vec<u8>> {
+    age::encrypt(&identity.to_public(), content.as_bytes()).map_err(io::Error::other)
+}
+
+pub fn decrypt(identity: &Identity, bytes: &[u8]) -> io::Result<String> {
+    let plain = age::decrypt(identity, bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    String::from_utf8(plain).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The key from `identity_file`, else from the keyring.
+pub fn identity() -> io::Result<Identity> {
+    if let Some(identity) = IDENTITY.get() {
+        return Ok(identity.clone());
+    }
+    let key = match load_config().encryption.identity_file {
+        Some(path) => {
+            let content = fs::read_to_string(&path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{} can't be read: {}", path.display(), e)))?;
+            let key = content.lines().map(str::trim).find(|line| line.starts_with("AGE-SECRET-KEY-"));
+            let missing = || io::Error::new(io::ErrorKind::NotFound, format!("{} holds no age key", path.display()));
+            key.ok_or_else(missing)?.to_string()
+        }
+        None => KEY_STORE.get().map_err(|_| {
+            let hint = "no key in the keyring, set identity_file in the [encryption] table of config.toml";
+            io::Error::new(io::ErrorKind::NotFound, hint)
+        })?,
+    };
+    let identity: Identity = key.parse().map_err(|e: &str| io::Error::new(io::ErrorKind::InvalidData, format!("the key isn't an age key: {}", e)))?;
+    Ok(IDENTITY.get_or_init(|| identity).clone())
+}
+
+/// The key there is, or a new one kept in the keyring. Returns it and whether it is new,
+/// so it can be shown once to be backed up.
+pub fn identity_or_new() -> io::Result<(Identity, bool)> {
+    match identity() {
+        Ok(identity) => return Ok((identity, false)),
+        // A key file that is set but unreadable, or a key that is broken, is a mistake to
+        // fix rather than a reason for another key
+        Err(e) if load_config().encryption.identity_file.is_some() || KEY_STORE.get().is_ok() => return Err(e),
+        Err(_) => {}
+    }
+    let identity = Identity::generate();
+    KEY_STORE.store(identity.to_string().expose_secret()).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok((IDENTITY.get_or_init(|| identity).clone(), true))
+}
+
+/// The key, the way identity files and `identity_file` hold it.
+pub fn secret_key(identity: &Identity) -> String {
+    identity.to_string().expose_secret().to_string()
+}
+
+/// Encrypts the task files in `dir` with `identity`: each is written as name.age, read back
+/// to be sure it decrypts, and only then removed. Returns the files encrypted.
+pub fn encrypt_dir(dir: &Path, identity: &Identity) -> io::Result<Vec<PathBuf>> {
+    let mut encrypted = Vec::new();
+    for name in TASK_FILES {
+        let plain = dir.join(name);
+        if !plain.exists() {
+            continue;
+        }
+        let content = fs::read_to_string(&plain)?;
+        let path = dir.join(format!("{}.age", name));
+        fs::write(&path, encrypt(identity, &content)?)?;
+        if decrypt(identity, &fs::read(&path)?)? != content {
+            return Err(io::Error::other(format!("{} doesn't decrypt to what it was, {} is kept", path.display(), name)));
+        }
+        fs::remove_file(&plain)?;
+        encrypted.push(path);
+    }
+    Ok(encrypted)
+}
+
+/// Decrypts the encrypted task files in `dir` back into plain files. Returns the files
+/// decrypted.
+pub fn decrypt_dir(dir: &Path, identity: &Identity) -> io::Result<Vec<PathBuf>> {
+    let mut decrypted = Vec::new();
+    for name in TASK_FILES {
+        let path = dir.join(format!("{}.age", name));
+        if !path.exists() {
+            continue;
+        }
+        let plain = dir.join(name);
+        fs::write(&plain, decrypt(identity, &fs::read(&path)?)?)?;
+        fs::remove_file(&path)?;
+        decrypted.push(plain);
+    }
+    Ok(decrypted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypts_the_task_files_and_back() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let tasks = "# tasks\n\n- [ ] see the doctor [id:aaaa01]\n";
+        fs::write(dir.join("tasks.md"), tasks).unwrap();
+        fs::write(dir.join("archive.md"), "# archive\n\n").unwrap();
+        let identity = Identity::generate();
+
+        let encrypted = encrypt_dir(dir, &identity).unwrap();
+        assert_eq!(encrypted, vec![dir.join("tasks.md.age"), dir.join("archive.md.age")]);
+        assert_eq!(file_in(dir, "tasks.md"), dir.join("tasks.md.age"));
+        assert!(!dir.join("tasks.md").exists());
+        let bytes = fs::read(dir.join("tasks.md.age")).unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("doctor"));
+        assert!(decrypt(&Identity::generate(), &bytes).is_err());
+
+        decrypt_dir(dir, &identity).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("tasks.md")).unwrap(), tasks);
+        assert_eq!(file_in(dir, "tasks.md"), dir.join("tasks.md"));
+    }
+}
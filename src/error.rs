@@ -0,0 +1,103 @@
+use chrono::{DateTime, Local};
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// What went wrong, worded to be shown as it is: after a ❌ on the command line, in a toast
+/// in the TUI.
+#[derive(Error, Debug)]
+pub enum YarmtlError {
+    #[error("couldn't {action} {}: {}", path.display(), describe(source))]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+
+    #[error("{} isn't valid: {message}", path.display())]
+    Parse { path: PathBuf, message: String },
+
+    #[error("no task with id {0}")]
+    NoTask(String),
+
+    #[error("{count} tasks have ids starting with {prefix}, give more of the id")]
+    AmbiguousId { prefix: String, count: usize },
+
+    #[error("couldn't watch {} for changes: {message}", path.display())]
+    Watch { path: PathBuf, message: String },
+
+    #[error("git {action} failed in {}: {message}", path.display())]
+    Git {
+        action: &'static str,
+        path: PathBuf,
+        message: String,
+    },
+
+    #[error("committed, but couldn't push to {remote}: {message}. You may need to run 'git push' in {}", path.display())]
+    Push {
+        remote: String,
+        path: PathBuf,
+        message: String,
+    },
+
+    #[error("the task index {} can't be used: {message}. It can be deleted, it is made again from tasks.md", path.display())]
+    Index { path: PathBuf, message: String },
+
+    #[error("the {operation} started {} didn't finish, `yarmtl recover` finishes it or takes it back", started.format("%Y-%m-%d %H:%M"))]
+    Unfinished { operation: String, started: DateTime<Local> },
+
+    #[error("sync failed: {0}")]
+    Sync(String),
+
+    #[error("{0}")]
+    Email(String),
+}
+
+impl YarmtlError {
+    /// For `map_err`: `fs::write(&path, text).map_err(YarmtlError::io("write", &path))`.
+    pub fn io(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> YarmtlError {
+        let path = path.to_path_buf();
+        move |source| YarmtlError::Io { action, path, source }
+    }
+
+    /// For `map_err` on git2 calls: `repo.index().map_err(YarmtlError::git("add", &dir))`.
+    pub fn git(action: &'static str, path: &Path) -> impl FnOnce(git2::Error) -> YarmtlError {
+        let path = path.to_path_buf();
+        move |error| YarmtlError::Git { action, path, message: error.message().to_string() }
+    }
+
+    /// For `map_err` on the SQLite calls of the task index.
+    pub fn index(path: &Path) -> impl FnOnce(rusqlite::Error) -> YarmtlError {
+        let path = path.to_path_buf();
+        move |error| YarmtlError::Index { path, message: error.to_string() }
+    }
+
+    pub fn parse(path: &Path, error: impl std::fmt::Display) -> YarmtlError {
+        YarmtlError::Parse { path: path.to_path_buf(), message: error.to_string() }
+    }
+}
+
+/// The io error, said the way it most often happens to tasks.md.
+fn describe(error: &io::Error) -> String {
+    match error.kind() {
+        io::ErrorKind::ReadOnlyFilesystem => "the file system is read-only".to_string(),
+        io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        io::ErrorKind::NotFound => "it doesn't exist".to_string(),
+        io::ErrorKind::StorageFull => "the disk is full".to_string(),
+        _ => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let read_only = io::Error::from(io::ErrorKind::ReadOnlyFilesystem);
+        let error = YarmtlError::io("write", Path::new("/mnt/tasks/tasks.md"))(read_only);
+        assert_eq!(error.to_string(), "couldn't write /mnt/tasks/tasks.md: the file system is read-only");
+        let error = YarmtlError::parse(Path::new("email_config.toml"), "missing field `smtp_server`");
+        assert_eq!(error.to_string(), "email_config.toml isn't valid: missing field `smtp_server`");
+    }
+}
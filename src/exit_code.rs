@@ -0,0 +1,14 @@
+//! What the exit status of yarmtl means, for cron jobs and scripts to branch on. These
+//! numbers stay what they are, new ones are only ever added.
+
+/// Done, or nothing to do
+pub const OK: i32 = 0;
+/// Something went wrong, and stderr says what
+pub const ERROR: i32 = 1;
+/// `yarmtl check`: open tasks are past their deadline
+pub const OVERDUE: i32 = 2;
+/// `yarmtl check`: no task is overdue, but some are due today
+pub const DUE_TODAY: i32 = 3;
+/// The command line was wrong: an unknown command or flag, or a value missing or invalid.
+/// Not 2 like other programs built on clap, which `yarmtl check` took
+pub const USAGE: i32 = 64;
@@ -0,0 +1,730 @@
+use crate::config::{GitConfig, load_config};
+use crate::document;
+use crate::encryption;
+use crate::error::YarmtlError;
+use crate::paths::{get_sync_dir, get_tasks_file_path};
+use chrono::{DateTime, NaiveDate};
+use git2::build::CheckoutBuilder;
+use git2::{
+    BranchType, Commit, Cred, CredentialType, ErrorCode, FetchOptions, Index, IndexEntry, IndexTime, Oid, PushOptions,
+    Remote, RemoteCallbacks, Repository, RepositoryState, Signature, Sort, StatusOptions,
+};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::process::{Command, Stdio};
+
+/// The bits of an index entry's flags that tell which side of a conflict it is,
+/// GIT_INDEX_ENTRY_STAGEMASK of libgit2.
+const INDEX_STAGE_MASK: u16 = 0x3000;
+
+/// The bits of an index entry's flags holding the length of its path, GIT_INDEX_ENTRY_NAMEMASK.
+const INDEX_NAME_MASK: usize = 0xfff;
+
+/// Set by `--no-git`: nothing is committed, pulled or pushed and no repository is made,
+/// whatever config.toml says.
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns git off for the rest of the run, `--no-git`.
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// False after `disable`.
+pub fn is_enabled() -> bool {
+    !DISABLED.load(Ordering::Relaxed)
+}
+
+/// What a call to `git_commit_tasks_with_message` did, so callers can report it
+/// in whatever way suits them (stdout for the CLI, toasts for the TUI).
+#[derive(Debug, Default)]
+pub struct GitCommitOutcome {
+    pub initialized: bool,
+    pub committed: bool,
+    /// A push to origin was started in the background
+    pub pushing: bool,
+}
+
+/// What `git_pull` did.
+#[derive(Debug, PartialEq)]
+pub enum PullOutcome {
+    /// There is no origin to pull from
+    NoRemote,
+    UpToDate,
+    FastForwarded,
+    /// Both sides had new commits, merged in a commit that still has to be pushed
+    Merged,
+    /// Git is turned off with `--no-git`
+    Disabled,
+}
+
+impl fmt::Display for PullOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PullOutcome::NoRemote => "no remote",
+            PullOutcome::UpToDate => "up to date",
+            PullOutcome::FastForwarded => "fast-forwarded",
+            PullOutcome::Merged => "merged",
+            PullOutcome::Disabled => "git is off",
+        })
+    }
+}
+
+/// Initializes the task repository if needed. Returns true when a new repository was created.
+/// A tasks directory inside another repository, like one of dotfiles or notes, is committed
+/// to that one.
+pub fn git_repo_check() -> Result<bool, YarmtlError> {
+    let sync_dir = get_sync_dir();
+    if is_repository(&sync_dir) {
+        return Ok(false);
+    }
+
+    let repo = init(&sync_dir)?;
+    // Create initial commit if tasks.md exists
+    if get_tasks_file_path().exists() {
+        commit(&repo, &sync_dir, "🎉 Initial YARMTL tasks commit", &load_config().git)?;
+    }
+    Ok(true)
+}
+
+/// Whether the tasks directory is in a repository, its own or one it is a folder of.
+pub fn is_repository(sync_dir: &Path) -> bool {
+    Repository::discover(sync_dir).is_ok()
+}
+
+/// The repository of the tasks directory: its own, or the one it is a folder of.
+fn open(sync_dir: &Path, action: &'static str) -> Result<Repository, YarmtlError> {
+    Repository::discover(sync_dir).map_err(YarmtlError::git(action, sync_dir))
+}
+
+/// The repository of the tasks directory, created when it isn't in one yet.
+fn init(sync_dir: &Path) -> Result<Repository, YarmtlError> {
+    match is_repository(sync_dir) {
+        true => open(sync_dir, "open"),
+        false => Repository::init(sync_dir).map_err(YarmtlError::git("init", sync_dir)),
+    }
+}
+
+/// Where the tasks directory is in the working tree of `repo`: empty when the repository
+/// is its own.
+fn folder_in(repo: &Repository, sync_dir: &Path) -> PathBuf {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let workdir = repo.workdir().map(canonical).unwrap_or_default();
+    canonical(sync_dir).strip_prefix(&workdir).map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// `file` of the tasks directory as `repo` knows it, with `/` between folders on every system.
+fn repo_path(folder: &Path, file: &str) -> String {
+    let path = folder.join(file);
+    let parts: Vec<String> = path.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+    parts.join("/")
+}
+
+/// tasks.md and the files it includes from inside the tasks directory, what the commits
+/// take in. Files included from elsewhere aren't in its repository. tasks.md.age is one of
+/// them either way, so encrypting or decrypting tasks.md commits the other one as removed.
+pub fn task_files(sync_dir: &Path) -> Vec<String> {
+    let content = encryption::read(&encryption::file_in(sync_dir, "tasks.md")).unwrap_or_default();
+    let inside = |path: &Path| path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    let included = document::includes(&content)
+        .into_iter()
+        .filter(|path| inside(Path::new(path)) && sync_dir.join(path).is_file());
+    ["tasks.md", "tasks.md.age"].into_iter().map(str::to_string).chain(included).collect()
+}
+
+pub fn git_commit_tasks() -> Result<GitCommitOutcome, YarmtlError> {
+    git_commit_tasks_with_message(None)
+}
+
+pub fn git_commit_tasks_with_message(custom_message: Option<&str>) -> Result<GitCommitOutcome, YarmtlError> {
+    let config = load_config().git;
+    if !config.auto_commit || !is_enabled() {
+        return Ok(GitCommitOutcome::default());
+    }
+
+    let mut outcome = GitCommitOutcome {
+        initialized: git_repo_check()?,
+        ..GitCommitOutcome::default()
+    };
+
+    let sync_dir = get_sync_dir();
+    let repo = open(&sync_dir, "open")?;
+
+    let message = match custom_message {
+        Some(custom_msg) => custom_msg.to_string(),
+        None => updated_message(),
+    };
+
+    // A new repository starts out with what would have been committed
+    outcome.committed = commit(&repo, &sync_dir, &message, &config)?;
+    if outcome.committed {
+        tracing::info!("committed \"{}\"", message.lines().next().unwrap_or_default());
+    }
+    if !outcome.committed && !outcome.initialized {
+        // No changes to commit
+        return Ok(outcome);
+    }
+
+    // Push to remote if it exists, without waiting for the network
+    if config.remote.is_some() || repo.find_remote("origin").is_ok() {
+        outcome.pushing = request_push_after_commit();
+    }
+
+    Ok(outcome)
+}
+
+/// One commit message for several saves: the message of the only one, or a line counting
+/// them over a list of their messages. None without messages, for the usual one.
+pub fn combined_message(messages: &[String]) -> Option<String> {
+    match messages {
+        [] => None,
+        [message] => Some(message.clone()),
+        _ => {
+            let list: Vec<String> = messages.iter().map(|message| format!("- {}", message)).collect();
+            Some(format!("📝 {} changes to the tasks\n\n{}", messages.len(), list.join("\n")))
+        }
+    }
+}
+
+fn updated_message() -> String {
+    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+    format!("📝 Updated tasks - {}", timestamp)
+}
+
+/// Stages the task files and commits them, and only them: whatever else is staged, in a
+/// repository the tasks directory is a folder of, stays staged. Returns false when that is
+/// what the last commit holds already.
+fn commit(repo: &Repository, sync_dir: &Path, message: &str, config: &GitConfig) -> Result<bool, YarmtlError> {
+    let failed = |action| YarmtlError::git(action, sync_dir);
+    let parent = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit().map_err(failed("commit"))?),
+        Err(e) if matches!(e.code(), ErrorCode::UnbornBranch | ErrorCode::NotFound) => None,
+        Err(e) => return Err(failed("commit")(e)),
+    };
+
+    // The tree of the commit is the last one with the task files as they are now
+    let mut staged = repo.index().map_err(failed("add"))?;
+    let mut tree = Index::new().map_err(failed("commit"))?;
+    if let Some(parent) = &parent {
+        tree.read_tree(&parent.tree().map_err(failed("commit"))?).map_err(failed("commit"))?;
+    }
+    let folder = folder_in(repo, sync_dir);
+    for file in task_files(sync_dir) {
+        let path = repo_path(&folder, &file);
+        let full = sync_dir.join(&file);
+        // A file that is gone is committed as removed
+        if !full.exists() {
+            staged.remove_path(Path::new(&path)).map_err(failed("add"))?;
+            let _ = tree.remove_path(Path::new(&path));
+            continue;
+        }
+        staged.add_path(Path::new(&path)).map_err(failed("add"))?;
+        let blob = repo.blob_path(&full).map_err(failed("add"))?;
+        let size = fs::metadata(&full).map_err(YarmtlError::io("read", &full))?.len();
+        tree.add(&file_entry(&path, blob, size)).map_err(failed("commit"))?;
+    }
+    staged.write().map_err(failed("add"))?;
+
+    let tree = tree.write_tree_to(repo).map_err(failed("commit"))?;
+    let unchanged = match &parent {
+        Some(parent) => parent.tree_id() == tree,
+        None => repo.find_tree(tree).map_err(failed("commit"))?.is_empty(),
+    };
+    if unchanged {
+        return Ok(false);
+    }
+
+    let tree = repo.find_tree(tree).map_err(failed("commit"))?;
+    let signature = signature(repo, config).map_err(failed("commit"))?;
+    let parents: Vec<&Commit> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).map_err(failed("commit"))?;
+    Ok(true)
+}
+
+/// An index entry for a plain file at `path` of the working tree holding `blob`.
+fn file_entry(path: &str, blob: Oid, size: u64) -> IndexEntry {
+    IndexEntry {
+        ctime: IndexTime::new(0, 0),
+        mtime: IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode: 0o100644,
+        uid: 0,
+        gid: 0,
+        file_size: size as u32,
+        id: blob,
+        flags: path.len().min(INDEX_NAME_MASK) as u16,
+        flags_extended: 0,
+        path: path.as_bytes().to_vec(),
+    }
+}
+
+/// Who the commits are by: `name` and `email` from the `[git]` table of config.toml, else
+/// user.name and user.email from the git config, else YARMTL.
+fn signature(repo: &Repository, config: &GitConfig) -> Result<Signature<'static>, git2::Error> {
+    let git_config = repo.config().ok();
+    let from_git = |key: &str| git_config.as_ref().and_then(|git_config| git_config.get_string(key).ok());
+    let name = config.name.clone().or_else(|| from_git("user.name")).unwrap_or_else(|| "YARMTL".to_string());
+    let email = config.email.clone().or_else(|| from_git("user.email")).unwrap_or_else(|| "yarmtl@local".to_string());
+    Signature::now(&name, &email)
+}
+
+/// Files with uncommitted changes (untracked files left out) and how many commits are
+/// waiting to be pushed.
+#[derive(Debug, Default)]
+pub struct GitState {
+    pub changes: Vec<String>,
+    /// None when the branch has no upstream
+    pub ahead: Option<usize>,
+}
+
+pub fn git_state(sync_dir: &Path) -> Result<GitState, YarmtlError> {
+    let failed = |action| YarmtlError::git(action, sync_dir);
+    let repo = open(sync_dir, "status")?;
+
+    // In a repository the tasks directory is a folder of, only the changes in it
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let folder = folder_in(&repo, sync_dir);
+    if folder != Path::new("") {
+        options.pathspec(repo_path(&folder, ""));
+    }
+    let statuses = repo.statuses(Some(&mut options)).map_err(failed("status"))?;
+    let changes = statuses.iter().filter_map(|entry| entry.path().map(str::to_string)).collect();
+
+    // No upstream just means there is nothing to compare against
+    let ahead = || -> Option<usize> {
+        let branch = git2::Branch::wrap(repo.head().ok()?);
+        let upstream = branch.upstream().ok()?;
+        let (ahead, _) = repo.graph_ahead_behind(branch.get().target()?, upstream.get().target()?).ok()?;
+        Some(ahead)
+    };
+
+    Ok(GitState { changes, ahead: ahead() })
+}
+
+/// What could keep the tasks from being committed, pulled or pushed, for `yarmtl doctor`.
+#[derive(Debug, Default)]
+pub struct Health {
+    /// The repository, which may be one the tasks directory is a folder of
+    pub path: PathBuf,
+    /// The git command in the middle of something, "merge" or "rebase"
+    pub unfinished: Option<&'static str>,
+    /// Files with conflicts left to resolve
+    pub conflicts: Vec<String>,
+    /// The branch checked out, None when HEAD isn't on one
+    pub branch: Option<String>,
+    /// Where origin points
+    pub remote: Option<String>,
+}
+
+pub fn health(sync_dir: &Path) -> Result<Health, YarmtlError> {
+    let failed = |action| YarmtlError::git(action, sync_dir);
+    let repo = open(sync_dir, "open")?;
+    let unfinished = match repo.state() {
+        RepositoryState::Clean => None,
+        RepositoryState::Merge => Some("merge"),
+        RepositoryState::Revert | RepositoryState::RevertSequence => Some("revert"),
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => Some("cherry-pick"),
+        RepositoryState::Bisect => Some("bisect"),
+        RepositoryState::ApplyMailbox | RepositoryState::ApplyMailboxOrRebase => Some("am"),
+        RepositoryState::Rebase | RepositoryState::RebaseInteractive | RepositoryState::RebaseMerge => Some("rebase"),
+    };
+    let index = repo.index().map_err(failed("status"))?;
+    let conflicts = index
+        .conflicts()
+        .and_then(|conflicts| conflicts.collect::<Result<Vec<_>, _>>())
+        .map_err(failed("status"))?
+        .into_iter()
+        .filter_map(|conflict| conflict.our.or(conflict.their).or(conflict.ancestor))
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+        .collect();
+    let detached = repo.head_detached().unwrap_or(false);
+    let remote = repo.find_remote("origin").ok().and_then(|origin| origin.url().map(str::to_string));
+    Ok(Health {
+        path: repo.workdir().unwrap_or(repo.path()).to_path_buf(),
+        unfinished,
+        conflicts,
+        branch: current_branch(&repo).filter(|_| !detached),
+        remote,
+    })
+}
+
+/// origin, pointed at `remote` from config.toml first when that is set, and the branch on
+/// it the tasks go to. None without an origin.
+fn origin<'r>(repo: &'r Repository, config: &GitConfig) -> Result<Option<(Remote<'r>, String)>, git2::Error> {
+    if let Some(url) = &config.remote {
+        match repo.find_remote("origin") {
+            Ok(origin) if origin.url() == Some(url.as_str()) => {}
+            Ok(_) => repo.remote_set_url("origin", url)?,
+            Err(_) => {
+                repo.remote("origin", url)?;
+            }
+        }
+    }
+    let Ok(remote) = repo.find_remote("origin") else {
+        return Ok(None);
+    };
+    let branch = config.branch.clone().or_else(|| current_branch(repo)).unwrap_or_else(|| "main".to_string());
+    Ok(Some((remote, branch)))
+}
+
+/// The branch checked out, also when nothing is committed on it yet.
+fn current_branch(repo: &Repository) -> Option<String> {
+    let head = repo.find_reference("HEAD").ok()?;
+    head.symbolic_target()?.strip_prefix("refs/heads/").map(str::to_string)
+}
+
+/// Has the branch checked out follow `branch` of origin once there is one, so the status
+/// line can count the commits to push.
+fn track(repo: &Repository, branch: &str) {
+    let Some(local) = current_branch(repo) else { return };
+    if let Ok(mut local) = repo.find_branch(&local, BranchType::Local)
+        && local.upstream().is_err()
+        && repo.find_branch(&format!("origin/{}", branch), BranchType::Remote).is_ok()
+    {
+        let _ = local.set_upstream(Some(&format!("origin/{}", branch)));
+    }
+}
+
+/// Pushes the current branch to origin. Returns false when there is nothing to push to.
+pub fn git_push_if_remote_exists(sync_dir: &Path) -> Result<bool, YarmtlError> {
+    if !is_enabled() {
+        return Ok(false);
+    }
+    let repo = open(sync_dir, "push")?;
+
+    // No remote configured, skip push
+    let Some((mut remote, branch)) = origin(&repo, &load_config().git).map_err(YarmtlError::git("push", sync_dir))? else {
+        return Ok(false);
+    };
+
+    // No commits yet, skip push
+    let Some(local) = current_branch(&repo).filter(|_| repo.head().is_ok()) else {
+        return Ok(false);
+    };
+
+    let mut callbacks = remote_callbacks(&repo);
+    // A push the remote turns down still succeeds as far as libgit2 is concerned
+    callbacks.push_update_reference(|_, refused| match refused {
+        Some(reason) => Err(git2::Error::from_str(reason)),
+        None => Ok(()),
+    });
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+
+    // The commit itself succeeded, so report this as a push problem only
+    let refspec = format!("refs/heads/{}:refs/heads/{}", local, branch);
+    remote.push(&[refspec], Some(&mut options)).map_err(|e| YarmtlError::Push {
+        remote: "origin".to_string(),
+        path: sync_dir.to_path_buf(),
+        message: e.message().trim_end_matches('.').to_string(),
+    })?;
+    track(&repo, &branch);
+    tracing::info!("pushed {} to origin/{}", local, branch);
+
+    Ok(true)
+}
+
+/// Starts `yarmtl pull --after-commit` to push in the background. Returns whether it started.
+pub fn request_push_after_commit() -> bool {
+    let started = env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .args(["pull", "--after-commit"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    });
+    if let Err(e) = &started {
+        tracing::warn!("couldn't start pushing to the remote repository: {}", e);
+    }
+    started.is_ok()
+}
+
+/// `yarmtl pull --after-commit`: pushes, and when origin turns that down because another
+/// machine pushed first, pulls their commits in and pushes again.
+pub fn push_after_commit(sync_dir: &Path) -> Result<bool, YarmtlError> {
+    match git_push_if_remote_exists(sync_dir) {
+        Ok(pushed) => Ok(pushed),
+        Err(refused) => match git_pull(sync_dir) {
+            Ok(PullOutcome::Merged | PullOutcome::FastForwarded) => git_push_if_remote_exists(sync_dir),
+            _ => Err(refused),
+        },
+    }
+}
+
+/// Fetches the branch from origin and brings it in: fast-forwarding when nothing was
+/// committed here since, merging otherwise. Task files changed on both sides are merged
+/// with `document::merge_task_files`; any other file changed on both sides stops the pull.
+/// Changes to the task files not committed yet are committed first, unless `auto_commit` is off.
+pub fn git_pull(sync_dir: &Path) -> Result<PullOutcome, YarmtlError> {
+    if !is_enabled() {
+        return Ok(PullOutcome::Disabled);
+    }
+    let config = load_config().git;
+    let failed = |action| YarmtlError::git(action, sync_dir);
+    let repo = init(sync_dir)?;
+    let Some((mut remote, branch)) = origin(&repo, &config).map_err(failed("pull"))? else {
+        return Ok(PullOutcome::NoRemote);
+    };
+
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(remote_callbacks(&repo));
+    let refspec = format!("+refs/heads/{0}:refs/remotes/origin/{0}", branch);
+    remote.fetch(&[refspec], Some(&mut options), None).map_err(failed("fetch"))?;
+    let Ok(fetched) = repo.find_reference(&format!("refs/remotes/origin/{}", branch)) else {
+        // Nobody pushed the branch yet
+        return Ok(PullOutcome::UpToDate);
+    };
+
+    if config.auto_commit {
+        commit(&repo, sync_dir, &updated_message(), &config)?;
+    }
+    let theirs = repo.reference_to_annotated_commit(&fetched).map_err(failed("merge"))?;
+    let (analysis, _) = repo.merge_analysis(&[&theirs]).map_err(failed("merge"))?;
+    if analysis.is_up_to_date() {
+        track(&repo, &branch);
+        return Ok(PullOutcome::UpToDate);
+    }
+
+    let tasks_file = sync_dir.join("tasks.md");
+    let _lock = document::lock(&tasks_file).map_err(YarmtlError::io("lock", &tasks_file))?;
+    let (target, outcome) = if analysis.is_fast_forward() || analysis.is_unborn() {
+        (theirs.id(), PullOutcome::FastForwarded)
+    } else {
+        (merge(&repo, sync_dir, theirs.id(), &branch, &config)?, PullOutcome::Merged)
+    };
+
+    // Checking out first leaves the branch where it was when that would overwrite changes here
+    let commit = repo.find_commit(target).map_err(failed("checkout"))?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe())).map_err(failed("checkout"))?;
+    let head = repo.find_reference("HEAD").map_err(failed("pull"))?;
+    let branch_ref = head.symbolic_target().ok_or_else(|| failed("pull")(git2::Error::from_str("HEAD isn't on a branch")))?;
+    repo.reference(branch_ref, target, true, &format!("pull: {}", outcome)).map_err(failed("pull"))?;
+    track(&repo, &branch);
+    tracing::info!("pulled origin/{}: {}", branch, outcome);
+    Ok(outcome)
+}
+
+/// Merges `theirs` into the commit checked out, in a commit of its own. Returns that commit.
+fn merge(repo: &Repository, sync_dir: &Path, theirs: Oid, branch: &str, config: &GitConfig) -> Result<Oid, YarmtlError> {
+    let failed = |action| YarmtlError::git(action, sync_dir);
+    let ours = repo.head().and_then(|head| head.peel_to_commit()).map_err(failed("merge"))?;
+    let theirs = repo.find_commit(theirs).map_err(failed("merge"))?;
+    let mut index = repo.merge_commits(&ours, &theirs, None).map_err(failed("merge"))?;
+
+    let conflicts: Vec<_> = index.conflicts().and_then(|conflicts| conflicts.collect()).map_err(failed("merge"))?;
+    for conflict in conflicts {
+        let path = [&conflict.our, &conflict.their, &conflict.ancestor]
+            .into_iter()
+            .find_map(|entry| entry.as_ref().map(|entry| String::from_utf8_lossy(&entry.path).into_owned()))
+            .unwrap_or_default();
+        let by_hand = |why: &str| YarmtlError::Git {
+            action: "merge",
+            path: sync_dir.to_path_buf(),
+            message: format!("{} {}, merge it by hand", path, why),
+        };
+        if conflict.our.is_none() || conflict.their.is_none() {
+            return Err(by_hand("was deleted on one side and changed on the other"));
+        }
+        let folder = repo_path(&folder_in(repo, sync_dir), "");
+        if !path.starts_with(&folder) || (!path.ends_with(".md") && !path.ends_with(".md.age")) {
+            return Err(by_hand("changed on both sides"));
+        }
+        // Encrypted task files are merged decrypted, and encrypted again
+        let identity = match encryption::is_encrypted(Path::new(&path)) {
+            true => Some(encryption::identity().map_err(YarmtlError::io("decrypt", &sync_dir.join(&path)))?),
+            false => None,
+        };
+        let text = |entry: &Option<git2::IndexEntry>| -> Result<String, YarmtlError> {
+            let Some(blob) = entry.as_ref().and_then(|entry| repo.find_blob(entry.id).ok()) else {
+                return Ok(String::new());
+            };
+            match &identity {
+                Some(identity) => encryption::decrypt(identity, blob.content()).map_err(YarmtlError::io("decrypt", &sync_dir.join(&path))),
+                None => Ok(String::from_utf8_lossy(blob.content()).into_owned()),
+            }
+        };
+        let merged = document::merge_task_files(&text(&conflict.ancestor)?, &text(&conflict.our)?, &text(&conflict.their)?);
+        let merged = match &identity {
+            Some(identity) => encryption::encrypt(identity, &merged).map_err(YarmtlError::io("encrypt", &sync_dir.join(&path)))?,
+            None => merged.into_bytes(),
+        };
+
+        let Some(mut entry) = conflict.our else { continue };
+        entry.id = repo.blob(&merged).map_err(failed("merge"))?;
+        entry.file_size = merged.len() as u32;
+        // Back to a plain entry from the stage of the conflict it was in
+        entry.flags &= !INDEX_STAGE_MASK;
+        index.conflict_remove(Path::new(&path)).map_err(failed("merge"))?;
+        index.add(&entry).map_err(failed("merge"))?;
+    }
+
+    let tree = index.write_tree_to(repo).and_then(|tree| repo.find_tree(tree)).map_err(failed("merge"))?;
+    let signature = signature(repo, config).map_err(failed("merge"))?;
+    let message = format!("🔀 Merged tasks from origin/{}", branch);
+    repo.commit(None, &signature, &signature, &message, &tree, &[&ours, &theirs]).map_err(failed("merge"))
+}
+
+/// Logs in to a remote the way git would: ssh keys from the agent, passwords and tokens from
+/// the credential helper.
+pub fn remote_callbacks(repo: &Repository) -> RemoteCallbacks<'static> {
+    let git_config = repo.config().ok();
+    let mut tried = CredentialType::empty();
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username_from_url, allowed| {
+        // ssh remotes without a user in them log in as git, like github's
+        let username = username_from_url.unwrap_or("git");
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username);
+        }
+        // libgit2 asks again after a login is turned down, so each way is tried once
+        let untried = allowed - tried;
+        if untried.contains(CredentialType::SSH_KEY) {
+            tried |= CredentialType::SSH_KEY;
+            return Cred::ssh_key_from_agent(username);
+        }
+        if untried.contains(CredentialType::USER_PASS_PLAINTEXT)
+            && let Some(git_config) = &git_config
+        {
+            tried |= CredentialType::USER_PASS_PLAINTEXT;
+            return Cred::credential_helper(git_config, url, username_from_url);
+        }
+        if untried.contains(CredentialType::DEFAULT) {
+            tried |= CredentialType::DEFAULT;
+            return Cred::default();
+        }
+        Err(git2::Error::from_str("no ssh key in the agent or credentials from the credential helper were accepted"))
+    });
+    callbacks
+}
+
+/// A file as one commit left it.
+#[derive(Debug, Clone)]
+pub struct Version {
+    /// The commit's short hash
+    pub commit: String,
+    pub date: NaiveDate,
+    /// The first line of the commit message
+    pub summary: String,
+    pub content: String,
+}
+
+/// Every committed version of `file`, oldest first: those of the commits that changed it,
+/// leaving out the ones it's missing from. While it was encrypted, the versions are those of
+/// `file`.age, decrypted.
+pub fn file_history(sync_dir: &Path, file: &str) -> Result<Vec<Version>, YarmtlError> {
+    let failed = |action| YarmtlError::git(action, sync_dir);
+    let repo = open(sync_dir, "log")?;
+    if repo.head().is_err() {
+        // Nothing committed yet
+        return Ok(Vec::new());
+    }
+
+    let mut walk = repo.revwalk().map_err(failed("log"))?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME | Sort::REVERSE).map_err(failed("log"))?;
+    walk.push_head().map_err(failed("log"))?;
+
+    let encrypted = format!("{}.age", file);
+    let folder = folder_in(&repo, sync_dir);
+    let (plain_path, encrypted_path) = (repo_path(&folder, file), repo_path(&folder, &encrypted));
+    let paths = [Path::new(&plain_path), Path::new(&encrypted_path)];
+    let version = |commit: &Commit| {
+        let tree = commit.tree().ok()?;
+        paths.iter().find_map(|path| tree.get_path(path).ok().map(|entry| (entry.id(), encryption::is_encrypted(path))))
+    };
+    let mut history = Vec::new();
+    for id in walk {
+        let commit = repo.find_commit(id.map_err(failed("log"))?).map_err(failed("log"))?;
+        let before = commit.parent(0).ok().and_then(|parent| version(&parent));
+        let Some((blob, is_encrypted)) = version(&commit).filter(|version| Some(*version) != before) else {
+            continue;
+        };
+        let blob = repo.find_blob(blob).map_err(failed("show"))?;
+        let content = match is_encrypted {
+            true => encryption::identity()
+                .and_then(|identity| encryption::decrypt(&identity, blob.content()))
+                .map_err(YarmtlError::io("decrypt", &sync_dir.join(&encrypted)))?,
+            false => String::from_utf8_lossy(blob.content()).into_owned(),
+        };
+        // The day as it was where the commit was made, like `git log --date=short`
+        let when = commit.author().when();
+        let local = when.seconds() + i64::from(when.offset_minutes()) * 60;
+        if let Some(day) = DateTime::from_timestamp(local, 0) {
+            history.push(Version {
+                commit: commit.id().to_string()[..7].to_string(),
+                date: day.date_naive(),
+                summary: commit.summary().unwrap_or_default().to_string(),
+                content,
+            });
+        }
+    }
+
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_commits_only_changes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let repo = Repository::init(dir).unwrap();
+        let config = GitConfig { name: Some("Ada".to_string()), email: Some("ada@example.com".to_string()), ..GitConfig::default() };
+
+        assert!(!commit(&repo, dir, "nothing yet", &config).unwrap());
+        fs::write(dir.join("tasks.md"), "# tasks\n\n- [ ] pay rent [id:aaaa01]\n").unwrap();
+        assert!(commit(&repo, dir, "first", &config).unwrap());
+        assert!(!commit(&repo, dir, "again", &config).unwrap());
+        fs::write(dir.join("tasks.md"), "# tasks\n\n- [x] pay rent [id:aaaa01]\n").unwrap();
+        fs::write(dir.join("notes.txt"), "not a task file\n").unwrap();
+        assert!(commit(&repo, dir, "second", &config).unwrap());
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!((head.message(), head.author().name()), (Some("second"), Some("Ada")));
+        assert!(git_state(dir).unwrap().changes.is_empty());
+        let history = file_history(dir, "tasks.md").unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history[1].content.contains("- [x] pay rent"));
+        assert_eq!(history[1].summary, "second");
+
+        let messages = ["➕ Added task: \"a\"".to_string(), "✓ Completed task: \"b\"".to_string()];
+        assert_eq!(combined_message(&messages[..1]).as_deref(), Some("➕ Added task: \"a\""));
+        let combined = combined_message(&messages).unwrap();
+        assert!(combined.starts_with("📝 2 changes to the tasks\n\n- ➕ Added task"));
+    }
+
+    #[test]
+    fn test_commits_only_the_tasks_of_a_folder_of_another_repository() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let tasks_dir = dir.join("notes/tasks");
+        fs::create_dir_all(&tasks_dir).unwrap();
+        let repo = Repository::init(dir).unwrap();
+        let config = GitConfig { name: Some("Ada".to_string()), email: Some("ada@example.com".to_string()), ..GitConfig::default() };
+
+        // Someone else's work staged in the dotfiles repository stays out of the commit
+        fs::write(dir.join(".vimrc"), "set number\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(".vimrc")).unwrap();
+        index.write().unwrap();
+        assert!(is_repository(&tasks_dir));
+        assert_eq!(init(&tasks_dir).unwrap().path(), repo.path());
+        assert!(!tasks_dir.join(".git").exists());
+
+        fs::write(tasks_dir.join("tasks.md"), "- [ ] pay rent [id:aaaa01]\n").unwrap();
+        assert!(commit(&repo, &tasks_dir, "first", &config).unwrap());
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(tree.get_path(Path::new("notes/tasks/tasks.md")).is_ok());
+        assert!(tree.get_path(Path::new(".vimrc")).is_err());
+        assert!(repo.index().unwrap().get_path(Path::new(".vimrc"), 0).is_some());
+        assert_eq!(git_state(&tasks_dir).unwrap().changes, Vec::<String>::new());
+        assert_eq!(file_history(&tasks_dir, "tasks.md").unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,198 @@
+use chrono::{DateTime, Utc};
+use reqwest::{Client, Method};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use thiserror::Error;
+
+const API_BASE_URL: &str = "https://api.github.com";
+
+/// Issues per request, the most GitHub sends at once.
+const PAGE_SIZE: usize = 100;
+
+#[derive(Error, Debug)]
+pub enum GitHubError {
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error("Authentication failed, run 'yarmtl --setup-github' again")]
+    Auth,
+
+    #[error("API error: {status} - {message}")]
+    Api { status: u16, message: String },
+}
+
+impl GitHubError {
+    /// GitHub is refusing or out of reach, so the rest of a sync would fail the same way.
+    /// Running out of the rate limit is a 403 that says so.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            GitHubError::Api { status, message } => {
+                *status == 429 || *status >= 500 || (*status == 403 && message.contains("rate limit"))
+            }
+            GitHubError::Network(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitHubUser {
+    pub login: String,
+}
+
+/// An issue of a repository. Only what yarmtl syncs is read.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct GitHubIssue {
+    /// "owner/repo#number", set by the client: what the issue is paired by
+    #[serde(skip)]
+    pub reference: String,
+    /// "owner/repo", set by the client
+    #[serde(skip)]
+    pub repo: String,
+    pub number: u64,
+    #[serde(default)]
+    pub title: String,
+    pub body: Option<String>,
+    /// "open" or "closed"
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub html_url: String,
+    /// RFC 3339
+    pub updated_at: Option<String>,
+    /// Only there on pull requests, which the issues endpoints list too
+    #[serde(default)]
+    pub pull_request: Option<serde_json::Value>,
+}
+
+impl GitHubIssue {
+    pub fn is_closed(&self) -> bool {
+        self.state == "closed"
+    }
+
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+        let updated = DateTime::parse_from_rfc3339(self.updated_at.as_deref()?).ok()?;
+        Some(updated.with_timezone(&Utc))
+    }
+
+    fn in_repo(mut self, repo: &str) -> Self {
+        self.reference = format!("{}#{}", repo, self.number);
+        self.repo = repo.to_string();
+        self
+    }
+}
+
+/// The repository and number in a "owner/repo#number" reference.
+pub fn parse_reference(reference: &str) -> Option<(&str, u64)> {
+    let (repo, number) = reference.rsplit_once('#')?;
+    Some((repo, number.parse().ok()?))
+}
+
+pub struct GitHubClient {
+    client: Client,
+    token: String,
+}
+
+impl GitHubClient {
+    pub fn new(token: String) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            // GitHub turns away requests without one
+            .user_agent(concat!("yarmtl/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        GitHubClient { client, token }
+    }
+
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        endpoint: &str,
+        query: &[(&str, String)],
+        body: Option<serde_json::Value>,
+    ) -> Result<T, GitHubError> {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", API_BASE_URL, endpoint))
+            .bearer_auth(&self.token)
+            .header("Accept", "application/vnd.github+json")
+            .query(query);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(GitHubError::Auth);
+        }
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(GitHubError::Api { status: status.as_u16(), message });
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| GitHubError::Api { status: status.as_u16(), message: e.to_string() })
+    }
+
+    /// The user the token belongs to.
+    pub async fn current_user(&self) -> Result<GitHubUser, GitHubError> {
+        self.request(Method::GET, "/user", &[], None).await
+    }
+
+    /// Issues of `repo` assigned to `login`, closed ones included. Pull requests are left out.
+    pub async fn list_assigned_issues(&self, repo: &str, login: &str) -> Result<Vec<GitHubIssue>, GitHubError> {
+        let endpoint = format!("/repos/{}/issues", repo);
+        let mut issues = Vec::new();
+        for page in 1.. {
+            let query = [
+                ("assignee", login.to_string()),
+                ("state", "all".to_string()),
+                ("per_page", PAGE_SIZE.to_string()),
+                ("page", page.to_string()),
+            ];
+            let listed: Vec<GitHubIssue> = self.request(Method::GET, &endpoint, &query, None).await?;
+            let last_page = listed.len() < PAGE_SIZE;
+            issues.extend(listed.into_iter().filter(|i| i.pull_request.is_none()).map(|i| i.in_repo(repo)));
+            if last_page {
+                break;
+            }
+        }
+        Ok(issues)
+    }
+
+    /// Opens an issue assigned to `assignee`, so it is listed with the others.
+    pub async fn create_issue(
+        &self,
+        repo: &str,
+        title: &str,
+        body: Option<&str>,
+        assignee: &str,
+    ) -> Result<GitHubIssue, GitHubError> {
+        let endpoint = format!("/repos/{}/issues", repo);
+        let body = json!({ "title": title, "body": body, "assignees": [assignee] });
+        let issue: GitHubIssue = self.request(Method::POST, &endpoint, &[], Some(body)).await?;
+        Ok(issue.in_repo(repo))
+    }
+
+    pub async fn rename_issue(&self, repo: &str, number: u64, title: &str) -> Result<GitHubIssue, GitHubError> {
+        self.edit_issue(repo, number, json!({ "title": title })).await
+    }
+
+    /// Closes the issue as completed, or reopens it.
+    pub async fn close_issue(&self, repo: &str, number: u64, closed: bool) -> Result<GitHubIssue, GitHubError> {
+        let body = match closed {
+            true => json!({ "state": "closed", "state_reason": "completed" }),
+            false => json!({ "state": "open" }),
+        };
+        self.edit_issue(repo, number, body).await
+    }
+
+    async fn edit_issue(&self, repo: &str, number: u64, body: serde_json::Value) -> Result<GitHubIssue, GitHubError> {
+        let endpoint = format!("/repos/{}/issues/{}", repo, number);
+        let issue: GitHubIssue = self.request(Method::PATCH, &endpoint, &[], Some(body)).await?;
+        Ok(issue.in_repo(repo))
+    }
+}
@@ -0,0 +1,311 @@
+use crate::github_client::{parse_reference, GitHubClient, GitHubError, GitHubIssue};
+use crate::hash::StableHasher;
+use crate::ids;
+use crate::paths::sync_config_path;
+use crate::sync_engine::{ConflictStrategy, RemoteTask, SyncContext, SyncProgress, SyncProvider};
+use crate::sync_metadata::SyncMetadata;
+use crate::task::Task;
+use crate::todoist_auth::SecretStore;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// The repositories to sync, see `paths::sync_config_path`. The token is kept apart, in
+/// `TOKEN_STORE`.
+pub const CONFIG_FILE: &str = "github_config.toml";
+
+/// The personal access token, kept out of the tasks directory.
+pub const TOKEN_STORE: SecretStore = SecretStore {
+    service: "yarmtl-github",
+    username: "api-token",
+    file_name: ".github_token",
+};
+
+/// Tasks with this tag become issues, with `create_issues` on.
+const ISSUE_TAG: &str = "gh";
+
+/// github_config.toml, written by `yarmtl --setup-github`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GitHubConfig {
+    /// "owner/repo" of each repository whose issues assigned to you become tasks
+    pub repos: Vec<String>,
+    /// Whether tasks tagged #gh are opened as issues
+    #[serde(default)]
+    pub create_issues: bool,
+    #[serde(default)]
+    pub conflict_strategy: ConflictStrategy,
+}
+
+impl GitHubConfig {
+    /// None until `yarmtl --setup-github` wrote github_config.toml.
+    pub fn load(sync_dir: &Path) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let path = sync_config_path(sync_dir, CONFIG_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(toml::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    pub fn save(&self, sync_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(sync_config_path(sync_dir, CONFIG_FILE), toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl RemoteTask for GitHubIssue {
+    const SERVICE: &'static str = "GitHub";
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn remote_id(&self) -> Option<&str> {
+        Some(&self.reference)
+    }
+
+    fn is_completed(&self) -> bool {
+        self.is_closed()
+    }
+
+    fn modified_at(&self) -> Option<DateTime<Utc>> {
+        self.updated_at()
+    }
+}
+
+/// The issues assigned to you in the configured repositories, for the `SyncEngine`.
+/// Titles and whether the issue is closed sync both ways; the rest stays where it is.
+/// Issues are never deleted, only closed with the task.
+pub struct GitHubProvider {
+    client: GitHubClient,
+    repos: Vec<String>,
+    create_issues: bool,
+    /// Who the token belongs to, asked for on the first fetch
+    login: Option<String>,
+}
+
+impl GitHubProvider {
+    pub fn new(config: &GitHubConfig, token: String) -> Self {
+        GitHubProvider {
+            client: GitHubClient::new(token),
+            repos: config.repos.clone(),
+            create_issues: config.create_issues,
+            login: None,
+        }
+    }
+
+    async fn login(&mut self) -> Result<String, GitHubError> {
+        if let Some(login) = &self.login {
+            return Ok(login.clone());
+        }
+        let login = self.client.current_user().await?.login;
+        self.login = Some(login.clone());
+        Ok(login)
+    }
+
+    /// The repository a new issue goes to: the one the task is also tagged with, or the first.
+    fn repo_for(&self, task: &Task) -> Option<&str> {
+        self.repos
+            .iter()
+            .find(|repo| task.tags.contains(&repo_tag(repo)))
+            .or(self.repos.first())
+            .map(String::as_str)
+    }
+}
+
+impl SyncProvider for GitHubProvider {
+    type Remote = GitHubIssue;
+    type Error = GitHubError;
+
+    /// Pairs of tasks.md's tasks with their issues, kept apart from Todoist's
+    const METADATA_FILE: &'static str = ".github_metadata.json";
+    const SUBTASKS: bool = false;
+    const SUBTASK_ORDER: bool = false;
+    const LISTS_COMPLETED: bool = true;
+    /// Issues can only be closed, and closing one for a task taken out of tasks.md would
+    /// tell everyone else it was done
+    const DELETES: bool = false;
+
+    fn is_unavailable(error: &GitHubError) -> bool {
+        error.is_unavailable()
+    }
+
+    async fn fetch(
+        &mut self,
+        _metadata: &mut SyncMetadata,
+        _local_tasks: &[Task],
+        on_progress: &mut impl FnMut(SyncProgress),
+    ) -> Result<Vec<GitHubIssue>, GitHubError> {
+        on_progress(SyncProgress::FetchingTasks);
+        let login = self.login().await?;
+        let mut issues = Vec::new();
+        for repo in &self.repos {
+            issues.extend(self.client.list_assigned_issues(repo, &login).await?);
+        }
+        Ok(issues)
+    }
+
+    async fn create(&mut self, task: &Task, _context: &SyncContext<'_, GitHubIssue>) -> Result<GitHubIssue, GitHubError> {
+        let login = self.login().await?;
+        let repo = self.repo_for(task).unwrap_or_default().to_string();
+        self.client.create_issue(&repo, &task.text, task.notes.as_deref(), &login).await
+    }
+
+    async fn update(
+        &mut self,
+        reference: &str,
+        task: &Task,
+        _context: &SyncContext<'_, GitHubIssue>,
+    ) -> Result<GitHubIssue, GitHubError> {
+        let (repo, number) = issue_of(reference)?;
+        self.client.rename_issue(repo, number, &task.text).await
+    }
+
+    async fn complete(&mut self, issue: &mut GitHubIssue, completed: bool) -> Result<(), GitHubError> {
+        *issue = self.client.close_issue(&issue.repo, issue.number, completed).await?;
+        Ok(())
+    }
+
+    /// Never asked for, see `DELETES`.
+    async fn delete(&mut self, _reference: &str, _context: &SyncContext<'_, GitHubIssue>) -> Result<(), GitHubError> {
+        Ok(())
+    }
+
+    fn to_task(&self, issue: &GitHubIssue) -> Task {
+        to_task(issue)
+    }
+
+    fn pull(&self, local: &Task, issue: &GitHubIssue) -> Task {
+        Task { text: issue.title.clone(), ..local.clone() }
+    }
+
+    fn local_hash(&self, task: &Task) -> String {
+        local_hash(task)
+    }
+
+    fn remote_hash(&self, issue: &GitHubIssue) -> String {
+        remote_hash(issue)
+    }
+
+    /// Only tasks tagged #gh, and only when issues are to be opened.
+    fn creates(&self, task: &Task) -> bool {
+        self.create_issues && !self.repos.is_empty() && task.tags.iter().any(|t| t == ISSUE_TAG)
+    }
+}
+
+/// The tag of a repository's tasks: its name without the owner, in characters a tag can have.
+fn repo_tag(repo: &str) -> String {
+    let name = repo.rsplit('/').next().unwrap_or(repo);
+    name.chars().map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '-' }).collect()
+}
+
+fn issue_of(reference: &str) -> Result<(&str, u64), GitHubError> {
+    parse_reference(reference).ok_or_else(|| GitHubError::Api {
+        status: 404,
+        message: format!("{} isn't an issue", reference),
+    })
+}
+
+fn to_task(issue: &GitHubIssue) -> Task {
+    // GitHub's dates aren't taken, they are when yarmtl first saw the issue
+    let today = chrono::Local::now().date_naive();
+    let completed = issue.is_closed();
+    Task {
+        id: ids::generate(),
+        text: issue.title.clone(),
+        deadline: None,
+        tags: vec![repo_tag(&issue.repo)],
+        contexts: Vec::new(),
+        reminder: None,
+        repeat: None,
+        last_fired: None,
+        completed,
+        cancelled: false,
+        notes: None,
+        importance: None,
+        indent_level: 0,
+        parent_id: None,
+        project: None,
+        source: None,
+        attachments: vec![issue.html_url.clone()],
+        created: Some(today),
+        done: completed.then_some(today),
+        estimate: None,
+        spent: None,
+        pinned: false,
+        flag: None,
+        count: None,
+        warn: None,
+    }
+}
+
+/// Hash of what goes to GitHub from a local task, so edits to the rest don't count.
+fn local_hash(task: &Task) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = StableHasher::default();
+    task.text.hash(&mut hasher);
+    (task.completed && !task.cancelled).hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+/// Hash of what yarmtl reads from an issue, to notice edits made there.
+fn remote_hash(issue: &GitHubIssue) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = StableHasher::default();
+    issue.title.hash(&mut hasher);
+    issue.is_closed().hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_engine::{SyncAction, SyncEngine};
+
+    fn issue(repo: &str, number: u64, title: &str, state: &str) -> GitHubIssue {
+        GitHubIssue {
+            reference: format!("{}#{}", repo, number),
+            repo: repo.to_string(),
+            number,
+            title: title.to_string(),
+            state: state.to_string(),
+            html_url: format!("https://github.com/{}/issues/{}", repo, number),
+            ..GitHubIssue::default()
+        }
+    }
+
+    #[test]
+    fn test_assigned_issues_become_tasks() {
+        let config = GitHubConfig {
+            repos: vec!["uraniumcovid/yarmtl".to_string(), "me/dot.files".to_string()],
+            create_issues: true,
+            conflict_strategy: ConflictStrategy::KeepBoth,
+        };
+        let temp = tempfile::tempdir().unwrap();
+        let sync_dir = temp.path();
+        let provider = GitHubProvider::new(&config, "token".to_string());
+        let mut sync = SyncEngine::new(provider, sync_dir, config.conflict_strategy).unwrap();
+
+        let opened = issue("me/dot.files", 7, "fix the prompt", "open");
+        let task = to_task(&opened);
+        assert_eq!(task.tags, ["dot-files"]);
+        assert_eq!(task.attachments, ["https://github.com/me/dot.files/issues/7"]);
+        assert_eq!(Task::parse(&task.to_markdown()).tags, task.tags);
+
+        // Only #gh tasks become issues, and closed issues never seen before stay on GitHub
+        let local = Task::parse("fix the prompt #dot-files [id:abc123]");
+        sync.record_pair(local.id.clone(), &local, &opened);
+        let tagged = Task::parse("write docs #gh #yarmtl");
+        let untagged = Task::parse("buy milk");
+        let old = issue("uraniumcovid/yarmtl", 1, "old bug", "closed");
+        let actions = sync.detect_changes(&[local.clone(), tagged, untagged], &[opened.clone(), old]);
+        assert!(matches!(&actions[..], [SyncAction::CreateRemote(task)] if task.text == "write docs"));
+
+        // Closing the issue checks the task off; deleting the task leaves the issue be
+        let closed = GitHubIssue { state: "closed".to_string(), ..opened.clone() };
+        assert!(matches!(sync.detect_changes(std::slice::from_ref(&local), &[closed])[..], [SyncAction::UpdateYarmtl { .. }]));
+        assert!(sync.detect_changes(&[], &[opened]).is_empty());
+    }
+}
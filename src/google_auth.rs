@@ -0,0 +1,24 @@
+use crate::oauth::{DeviceFlow, Provider};
+use crate::todoist_auth::SecretStore;
+
+/// Reading and writing the user's task lists, nothing else.
+const GOOGLE_TASKS: Provider = Provider {
+    name: "Google",
+    device_code_url: "https://oauth2.googleapis.com/device/code",
+    token_url: "https://oauth2.googleapis.com/token",
+    scope: "https://www.googleapis.com/auth/tasks",
+    setup_command: "yarmtl --setup-google",
+    denied: "Access to Google Tasks was denied",
+    // What every sync gets its access token from
+    refresh_token_store: SecretStore {
+        service: "yarmtl-google",
+        username: "refresh-token",
+        file_name: ".google_token",
+    },
+};
+
+/// Signs in to Google Tasks, for an OAuth client of the "TVs and Limited Input devices"
+/// type the user sets up in the Google Cloud console.
+pub fn sign_in(client_id: String, client_secret: String) -> DeviceFlow {
+    DeviceFlow::new(&GOOGLE_TASKS, client_id, Some(client_secret))
+}
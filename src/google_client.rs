@@ -0,0 +1,200 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use reqwest::{Client, Method};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use thiserror::Error;
+
+const API_BASE_URL: &str = "https://tasks.googleapis.com/tasks/v1";
+
+/// Tasks per request, the most Google sends at once.
+const PAGE_SIZE: usize = 100;
+
+#[derive(Error, Debug)]
+pub enum GoogleError {
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error("Authentication failed, run 'yarmtl --setup-google' again")]
+    Auth,
+
+    #[error("API error: {status} - {message}")]
+    Api { status: u16, message: String },
+}
+
+impl GoogleError {
+    /// Google is refusing or out of reach, so the rest of a sync would fail the same way.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            GoogleError::Api { status, .. } => *status == 429 || *status >= 500,
+            GoogleError::Network(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GoogleTaskList {
+    pub id: String,
+    pub title: String,
+}
+
+/// A task of a Google Tasks list. Only what yarmtl syncs is read.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoogleTask {
+    pub id: String,
+    #[serde(default)]
+    pub title: String,
+    pub notes: Option<String>,
+    /// RFC 3339 at midnight UTC: Google Tasks keeps no time of day for due dates
+    pub due: Option<String>,
+    /// "needsAction" or "completed"
+    #[serde(default)]
+    pub status: String,
+    /// RFC 3339
+    pub updated: Option<String>,
+}
+
+impl GoogleTask {
+    pub fn due_date(&self) -> Option<NaiveDate> {
+        self.due.as_deref()?.get(..10).and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.status == "completed"
+    }
+
+    pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+        let updated = DateTime::parse_from_rfc3339(self.updated.as_deref()?).ok()?;
+        Some(updated.with_timezone(&Utc))
+    }
+}
+
+/// What is sent for a task, everything yarmtl syncs so nothing stale is left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoogleTaskUpdate {
+    pub title: String,
+    pub notes: Option<String>,
+    pub due: Option<NaiveDate>,
+    pub completed: bool,
+}
+
+impl GoogleTaskUpdate {
+    fn to_json(&self) -> serde_json::Value {
+        let mut body = json!({
+            "title": self.title,
+            "notes": self.notes,
+            "due": self.due.map(|d| format!("{}T00:00:00.000Z", d.format("%Y-%m-%d"))),
+            "status": if self.completed { "completed" } else { "needsAction" },
+        });
+        // Reopening only takes when the completion time goes too
+        if !self.completed {
+            body["completed"] = serde_json::Value::Null;
+        }
+        body
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Page<T> {
+    #[serde(default = "Vec::new")]
+    items: Vec<T>,
+    next_page_token: Option<String>,
+}
+
+pub struct GoogleClient {
+    client: Client,
+    access_token: String,
+}
+
+impl GoogleClient {
+    pub fn new(access_token: String) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        GoogleClient { client, access_token }
+    }
+
+    async fn request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: Method,
+        endpoint: &str,
+        query: &[(&str, String)],
+        body: Option<serde_json::Value>,
+    ) -> Result<T, GoogleError> {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", API_BASE_URL, endpoint))
+            .bearer_auth(&self.access_token)
+            .query(query);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(GoogleError::Auth);
+        }
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(GoogleError::Api { status: status.as_u16(), message });
+        }
+        // DELETE answers with no body at all
+        let text = response.text().await?;
+        serde_json::from_str(if text.is_empty() { "null" } else { &text })
+            .map_err(|e| GoogleError::Api { status: status.as_u16(), message: e.to_string() })
+    }
+
+    /// Every page of a listing.
+    async fn list_all<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, String)],
+    ) -> Result<Vec<T>, GoogleError> {
+        let mut items = Vec::new();
+        let mut page_token = None;
+        loop {
+            let mut query = query.to_vec();
+            query.push(("maxResults", PAGE_SIZE.to_string()));
+            if let Some(token) = page_token {
+                query.push(("pageToken", token));
+            }
+            let page: Page<T> = self.request(Method::GET, endpoint, &query, None).await?;
+            items.extend(page.items);
+            match page.next_page_token {
+                Some(token) => page_token = Some(token),
+                None => return Ok(items),
+            }
+        }
+    }
+
+    pub async fn list_task_lists(&self) -> Result<Vec<GoogleTaskList>, GoogleError> {
+        self.list_all("/users/@me/lists", &[]).await
+    }
+
+    /// Every task of the list, completed ones included, even those cleared from view.
+    pub async fn list_tasks(&self, list_id: &str) -> Result<Vec<GoogleTask>, GoogleError> {
+        let query = [("showCompleted", "true".to_string()), ("showHidden", "true".to_string())];
+        self.list_all(&format!("/lists/{}/tasks", list_id), &query).await
+    }
+
+    pub async fn create_task(&self, list_id: &str, task: &GoogleTaskUpdate) -> Result<GoogleTask, GoogleError> {
+        let endpoint = format!("/lists/{}/tasks", list_id);
+        self.request(Method::POST, &endpoint, &[], Some(task.to_json())).await
+    }
+
+    pub async fn update_task(&self, list_id: &str, id: &str, task: &GoogleTaskUpdate) -> Result<GoogleTask, GoogleError> {
+        let endpoint = format!("/lists/{}/tasks/{}", list_id, id);
+        self.request(Method::PATCH, &endpoint, &[], Some(task.to_json())).await
+    }
+
+    pub async fn delete_task(&self, list_id: &str, id: &str) -> Result<(), GoogleError> {
+        let endpoint = format!("/lists/{}/tasks/{}", list_id, id);
+        self.request::<Option<serde_json::Value>>(Method::DELETE, &endpoint, &[], None).await?;
+        Ok(())
+    }
+}
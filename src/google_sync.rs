@@ -0,0 +1,259 @@
+use crate::google_client::{GoogleClient, GoogleError, GoogleTask, GoogleTaskUpdate};
+use crate::hash::StableHasher;
+use crate::ids;
+use crate::paths::sync_config_path;
+use crate::sync_engine::{ConflictStrategy, RemoteTask, SyncContext, SyncProgress, SyncProvider};
+use crate::sync_metadata::SyncMetadata;
+use crate::task::Task;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// The OAuth client and the task list, see `paths::sync_config_path`. The refresh token
+/// is kept apart, by `google_auth`.
+pub const CONFIG_FILE: &str = "google_config.toml";
+
+/// google_config.toml, written by `yarmtl --setup-google`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct GoogleConfig {
+    /// The OAuth client of the "TVs and Limited Input devices" type to sign in with
+    pub client_id: String,
+    pub client_secret: String,
+    /// The task list to sync with
+    pub task_list: String,
+    /// Its name as picked at setup
+    #[serde(default)]
+    pub task_list_name: String,
+    #[serde(default)]
+    pub conflict_strategy: ConflictStrategy,
+}
+
+impl GoogleConfig {
+    /// None until `yarmtl --setup-google` wrote google_config.toml.
+    pub fn load(sync_dir: &Path) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let path = sync_config_path(sync_dir, CONFIG_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(toml::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    pub fn save(&self, sync_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(sync_config_path(sync_dir, CONFIG_FILE), toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl RemoteTask for GoogleTask {
+    const SERVICE: &'static str = "Google Tasks";
+
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn remote_id(&self) -> Option<&str> {
+        Some(&self.id)
+    }
+
+    fn is_completed(&self) -> bool {
+        self.is_completed()
+    }
+
+    fn modified_at(&self) -> Option<DateTime<Utc>> {
+        self.updated_at()
+    }
+}
+
+/// A Google Tasks list for the `SyncEngine`: titles, notes, due dates and completion.
+/// Tags, contexts, importance and nesting have no place there and stay in tasks.md.
+pub struct GoogleProvider {
+    client: GoogleClient,
+    list_id: String,
+}
+
+impl GoogleProvider {
+    pub fn new(config: &GoogleConfig, access_token: String) -> Self {
+        GoogleProvider { client: GoogleClient::new(access_token), list_id: config.task_list.clone() }
+    }
+}
+
+impl SyncProvider for GoogleProvider {
+    type Remote = GoogleTask;
+    type Error = GoogleError;
+
+    /// Pairs of tasks.md's tasks with the ids of their Google tasks, kept apart from Todoist's
+    const METADATA_FILE: &'static str = ".google_metadata.json";
+    const SUBTASKS: bool = false;
+    const SUBTASK_ORDER: bool = false;
+    const LISTS_COMPLETED: bool = true;
+    const DELETES: bool = true;
+
+    fn is_unavailable(error: &GoogleError) -> bool {
+        error.is_unavailable()
+    }
+
+    async fn fetch(
+        &mut self,
+        _metadata: &mut SyncMetadata,
+        _local_tasks: &[Task],
+        on_progress: &mut impl FnMut(SyncProgress),
+    ) -> Result<Vec<GoogleTask>, GoogleError> {
+        on_progress(SyncProgress::FetchingTasks);
+        self.client.list_tasks(&self.list_id).await
+    }
+
+    async fn create(&mut self, task: &Task, _context: &SyncContext<'_, GoogleTask>) -> Result<GoogleTask, GoogleError> {
+        self.client.create_task(&self.list_id, &to_update(task)).await
+    }
+
+    async fn update(&mut self, id: &str, task: &Task, _context: &SyncContext<'_, GoogleTask>) -> Result<GoogleTask, GoogleError> {
+        self.client.update_task(&self.list_id, id, &to_update(task)).await
+    }
+
+    async fn complete(&mut self, remote: &mut GoogleTask, completed: bool) -> Result<(), GoogleError> {
+        let update = GoogleTaskUpdate {
+            title: remote.title.clone(),
+            notes: remote.notes.clone(),
+            due: remote.due_date(),
+            completed,
+        };
+        *remote = self.client.update_task(&self.list_id, &remote.id, &update).await?;
+        Ok(())
+    }
+
+    async fn delete(&mut self, id: &str, _context: &SyncContext<'_, GoogleTask>) -> Result<(), GoogleError> {
+        self.client.delete_task(&self.list_id, id).await
+    }
+
+    fn to_task(&self, remote: &GoogleTask) -> Task {
+        to_task(remote, ids::generate())
+    }
+
+    /// Google's title, notes and due date.
+    fn pull(&self, local: &Task, remote: &GoogleTask) -> Task {
+        Task {
+            text: remote.title.clone(),
+            notes: remote.notes.clone().filter(|n| !n.is_empty()),
+            deadline: remote.due_date(),
+            ..local.clone()
+        }
+    }
+
+    fn local_hash(&self, task: &Task) -> String {
+        local_hash(task)
+    }
+
+    fn remote_hash(&self, remote: &GoogleTask) -> String {
+        remote_hash(remote)
+    }
+}
+
+fn to_update(task: &Task) -> GoogleTaskUpdate {
+    GoogleTaskUpdate {
+        title: task.text.clone(),
+        notes: task.notes.clone(),
+        due: task.deadline,
+        // Cancelled tasks weren't done, Google leaves them be
+        completed: task.completed && !task.cancelled,
+    }
+}
+
+fn to_task(remote: &GoogleTask, id: String) -> Task {
+    // Google's dates aren't taken, they are when yarmtl first saw the task
+    let today = chrono::Local::now().date_naive();
+    let completed = remote.is_completed();
+    Task {
+        id,
+        text: remote.title.clone(),
+        deadline: remote.due_date(),
+        tags: Vec::new(),
+        contexts: Vec::new(),
+        reminder: None,
+        repeat: None,
+        last_fired: None,
+        completed,
+        cancelled: false,
+        notes: remote.notes.clone().filter(|n| !n.is_empty()),
+        importance: None,
+        indent_level: 0,
+        parent_id: None,
+        project: None,
+        source: None,
+        attachments: Vec::new(),
+        created: Some(today),
+        done: completed.then_some(today),
+        estimate: None,
+        spent: None,
+        pinned: false,
+        flag: None,
+        count: None,
+        warn: None,
+    }
+}
+
+/// Hash of what goes to Google from a local task, so edits to the rest don't count.
+fn local_hash(task: &Task) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = StableHasher::default();
+    task.text.hash(&mut hasher);
+    task.notes.hash(&mut hasher);
+    task.deadline.hash(&mut hasher);
+    (task.completed && !task.cancelled).hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+/// Hash of what yarmtl reads from a Google task, to notice edits made there.
+fn remote_hash(remote: &GoogleTask) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = StableHasher::default();
+    remote.title.hash(&mut hasher);
+    remote.notes.as_deref().filter(|n| !n.is_empty()).hash(&mut hasher);
+    remote.due_date().hash(&mut hasher);
+    remote.is_completed().hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_engine::{SyncAction, SyncEngine};
+
+    #[test]
+    fn test_only_synced_fields_count() {
+        let config = GoogleConfig {
+            client_id: "id".to_string(),
+            client_secret: "secret".to_string(),
+            task_list: "list".to_string(),
+            task_list_name: "My Tasks".to_string(),
+            conflict_strategy: ConflictStrategy::KeepBoth,
+        };
+        let temp = tempfile::tempdir().unwrap();
+        let sync_dir = temp.path();
+        let provider = GoogleProvider::new(&config, "token".to_string());
+        let mut sync = SyncEngine::new(provider, sync_dir, config.conflict_strategy).unwrap();
+
+        let local = Task::parse("call bob !2025-06-10 [id:abc123]");
+        let remote = GoogleTask {
+            id: "g1".to_string(),
+            title: "call bob".to_string(),
+            due: Some("2025-06-10T00:00:00.000Z".to_string()),
+            status: "needsAction".to_string(),
+            ..GoogleTask::default()
+        };
+        sync.record_pair(local.id.clone(), &local, &remote);
+        let new_there = GoogleTask { id: "g2".to_string(), title: "water plants".to_string(), ..remote.clone() };
+        let done_there = GoogleTask { id: "g3".to_string(), status: "completed".to_string(), ..new_there.clone() };
+
+        // Tags and importance stay in tasks.md
+        let retagged = Task::parse("call bob !2025-06-10 #work $1 [id:abc123]");
+        let actions = sync.detect_changes(&[retagged], &[remote.clone(), new_there, done_there]);
+        assert!(matches!(&actions[..], [SyncAction::CreateInYarmtl(task)] if task.id == "g2"));
+
+        let checked_off = GoogleTask { status: "completed".to_string(), ..remote.clone() };
+        assert!(matches!(sync.detect_changes(std::slice::from_ref(&local), &[checked_off])[..], [SyncAction::UpdateYarmtl { .. }]));
+        assert!(matches!(sync.detect_changes(&[local], &[])[..], [SyncAction::DeleteFromYarmtl { .. }]));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::git::Version;
+use crate::task::{self, Task};
+use chrono::NaiveDate;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// What a commit did to a task, going by how its line changed from one version of
+/// tasks.md to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Created,
+    /// Back after being removed, by an undo or a `git revert`
+    Restored,
+    Renamed { from: String, to: String },
+    Deadline { from: Option<NaiveDate>, to: Option<NaiveDate> },
+    Reminder { from: Option<NaiveDate>, to: Option<NaiveDate> },
+    Completed,
+    Cancelled,
+    Reopened,
+    /// Any other change to the line, with the line as it was after
+    Edited(String),
+    /// Deleted, or archived
+    Removed,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let date = |date: &NaiveDate| date.format("%Y-%m-%d").to_string();
+        let moved = |f: &mut fmt::Formatter, what, from: &Option<NaiveDate>, to: &Option<NaiveDate>| match (from, to) {
+            (None, Some(to)) => write!(f, "{} set to {}", what, date(to)),
+            (Some(from), Some(to)) => write!(f, "{} moved from {} to {}", what, date(from), date(to)),
+            _ => write!(f, "{} taken off", what),
+        };
+        match self {
+            Change::Created => write!(f, "created"),
+            Change::Restored => write!(f, "restored"),
+            Change::Renamed { from, to } => write!(f, "renamed from \"{}\" to \"{}\"", from, to),
+            Change::Deadline { from, to } => moved(f, "deadline", from, to),
+            Change::Reminder { from, to } => moved(f, "reminder", from, to),
+            Change::Completed => write!(f, "completed"),
+            Change::Cancelled => write!(f, "cancelled"),
+            Change::Reopened => write!(f, "reopened"),
+            Change::Edited(line) => write!(f, "edited: {}", line.trim()),
+            Change::Removed => write!(f, "removed (deleted or archived)"),
+        }
+    }
+}
+
+/// A change to a task and the commit that made it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub date: NaiveDate,
+    pub commit: String,
+    pub change: Change,
+}
+
+/// How a task's line changed between two versions of tasks.md; `seen` is whether it was
+/// ever there before `before`.
+pub fn changes(before: Option<&Task>, after: Option<&Task>, seen: bool) -> Vec<Change> {
+    let (before, after) = match (before, after) {
+        (None, None) => return Vec::new(),
+        (None, Some(_)) if seen => return vec![Change::Restored],
+        (None, Some(_)) => return vec![Change::Created],
+        (Some(_), None) => return vec![Change::Removed],
+        (Some(before), Some(after)) => (before, after),
+    };
+
+    let mut changes = Vec::new();
+    if before.text != after.text {
+        changes.push(Change::Renamed { from: before.text.clone(), to: after.text.clone() });
+    }
+    if before.deadline != after.deadline {
+        changes.push(Change::Deadline { from: before.deadline, to: after.deadline });
+    }
+    if before.reminder != after.reminder {
+        changes.push(Change::Reminder { from: before.reminder, to: after.reminder });
+    }
+    match (before.completed, after.completed) {
+        (false, true) if after.cancelled => changes.push(Change::Cancelled),
+        (false, true) => changes.push(Change::Completed),
+        (true, false) => changes.push(Change::Reopened),
+        _ => {}
+    }
+
+    // What's left, leaving out where the task sits and the reminders going out
+    let rest = Task {
+        text: before.text.clone(),
+        deadline: before.deadline,
+        reminder: before.reminder,
+        completed: before.completed,
+        cancelled: before.cancelled,
+        done: before.done,
+        last_fired: before.last_fired,
+        indent_level: before.indent_level,
+        parent_id: before.parent_id.clone(),
+        project: before.project.clone(),
+        ..after.clone()
+    };
+    if rest != *before {
+        changes.push(Change::Edited(after.to_markdown()));
+    }
+    changes
+}
+
+/// Everything that happened to the task `id`, oldest first.
+pub fn task_timeline(versions: &[Version], id: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut before: Option<Task> = None;
+    let mut seen = false;
+    for version in versions {
+        let after = task::parse_tasks(&version.content).into_iter().find(|task| task.id == id);
+        for change in changes(before.as_ref(), after.as_ref(), seen) {
+            events.push(Event { date: version.date, commit: version.commit.clone(), change });
+        }
+        seen |= after.is_some();
+        before = after;
+    }
+    events
+}
+
+/// How many tasks a commit added, closed, removed and otherwise changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub added: usize,
+    pub closed: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = [(self.added, "added"), (self.closed, "closed"), (self.removed, "removed"), (self.changed, "changed")];
+        let parts: Vec<String> = counts.iter().filter(|(count, _)| *count > 0).map(|(count, what)| format!("{} {}", count, what)).collect();
+        match parts.is_empty() {
+            true => write!(f, "no task changes"),
+            false => write!(f, "{}", parts.join(", ")),
+        }
+    }
+}
+
+/// What changed from one version of tasks.md to the next.
+pub fn summarize(before: &[Task], after: &[Task]) -> Summary {
+    let by_id = |tasks: &[Task]| -> HashMap<String, Task> { tasks.iter().map(|t| (t.id.clone(), t.clone())).collect() };
+    let (before, after) = (by_id(before), by_id(after));
+    let ids: HashSet<&String> = before.keys().chain(after.keys()).collect();
+
+    let mut summary = Summary::default();
+    for id in ids {
+        let changes = changes(before.get(id), after.get(id), false);
+        if changes.contains(&Change::Created) {
+            summary.added += 1;
+        } else if changes.contains(&Change::Removed) {
+            summary.removed += 1;
+        } else if changes.iter().any(|change| matches!(change, Change::Completed | Change::Cancelled)) {
+            summary.closed += 1;
+        } else if !changes.is_empty() {
+            summary.changed += 1;
+        }
+    }
+    summary
+}
+
+/// The commits that changed tasks.md, newest first, each with what it changed.
+pub fn recent_commits(versions: &[Version], limit: usize) -> Vec<(&Version, Summary)> {
+    let mut before: Vec<Task> = Vec::new();
+    let mut commits = Vec::new();
+    for version in versions {
+        let after = task::parse_tasks(&version.content);
+        commits.push((version, summarize(&before, &after)));
+        before = after;
+    }
+    commits.into_iter().rev().take(limit).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(commit: &str, day: u32, content: &str) -> Version {
+        Version {
+            commit: commit.to_string(),
+            date: NaiveDate::from_ymd_opt(2025, 6, day).unwrap(),
+            summary: format!("commit {}", commit),
+            content: format!("# tasks\n\n{}", content),
+        }
+    }
+
+    #[test]
+    fn test_timeline_of_a_task() {
+        let versions = [
+            version("a1", 1, "- [ ] pay rent [id:aaaa01]\n"),
+            version("a2", 2, "- [ ] pay rent [id:aaaa01]\n- [ ] call bob [id:aaaa02]\n"),
+            version("a3", 3, "- [ ] pay the rent !2025-07-01 [id:aaaa01]\n- [ ] call bob [id:aaaa02]\n"),
+            version("a4", 5, "- [x] pay the rent !2025-07-01 #home [id:aaaa01]\n"),
+            version("a5", 6, ""),
+        ];
+
+        let timeline = task_timeline(&versions, "aaaa01");
+        let changes: Vec<String> = timeline.iter().map(|event| format!("{} {}", event.commit, event.change)).collect();
+        assert_eq!(
+            changes,
+            [
+                "a1 created",
+                "a3 renamed from \"pay rent\" to \"pay the rent\"",
+                "a3 deadline set to 2025-07-01",
+                "a4 completed",
+                "a4 edited: - [x] pay the rent [id:aaaa01] !2025-07-01 #home",
+                "a5 removed (deleted or archived)",
+            ]
+        );
+
+        let recent = recent_commits(&versions, 3);
+        let summaries: Vec<String> = recent.iter().map(|(version, summary)| format!("{} {}", version.commit, summary)).collect();
+        assert_eq!(summaries, ["a5 1 removed", "a4 1 closed, 1 removed", "a3 1 changed"]);
+    }
+}
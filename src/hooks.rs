@@ -0,0 +1,271 @@
+use crate::paths;
+use crate::recurrence::Recurrence;
+use crate::server::ApiTask;
+use crate::sync_engine::SyncReport;
+use crate::task::Task;
+use chrono::NaiveDate;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// In the config directory of the tasks directory, never in the tasks directory itself:
+/// anyone who can push to its repository could put an executable there.
+pub const HOOKS_DIR: &str = "hooks";
+
+/// The overdue tasks on-overdue ran for, and for which deadline, in the state directory.
+const OVERDUE_STATE_FILE: &str = ".overdue_hooks.json";
+
+/// When a hook runs; the hook is the executable named after it in `HOOKS_DIR`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookPoint {
+    /// Before a task is added; can refuse it or change it
+    Add,
+    /// Before a task is checked off; can refuse it or change the task
+    Complete,
+    /// Once the deadline of an open task has passed; can change the task
+    Overdue,
+    /// After a sync, with the sync's report instead of a task
+    Sync,
+}
+
+impl HookPoint {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            HookPoint::Add => "on-add",
+            HookPoint::Complete => "on-complete",
+            HookPoint::Overdue => "on-overdue",
+            HookPoint::Sync => "on-sync",
+        }
+    }
+}
+
+/// Where the hooks of the tasks directory `tasks_dir` are.
+pub fn hooks_dir_of(tasks_dir: &Path) -> PathBuf {
+    paths::config_dir_of(tasks_dir).join(HOOKS_DIR)
+}
+
+/// The hook for `point` of the tasks directory, if there is one. Hooks older versions ran
+/// from the tasks directory are only warned about.
+fn hook_path(tasks_dir: &Path, point: HookPoint) -> Option<PathBuf> {
+    let hooks_dir = hooks_dir_of(tasks_dir);
+    let path = find_hook(&hooks_dir, point);
+    let before = tasks_dir.join(HOOKS_DIR).join(point.file_name());
+    if path.is_none() && before.is_file() {
+        tracing::warn!("not running {}: hooks are in {} now", before.display(), hooks_dir.display());
+    }
+    path
+}
+
+/// The hook for `point` in `hooks_dir`, if there is one. Like git, yarmtl skips hooks that
+/// aren't executable, and it refuses to run one someone else could have written.
+fn find_hook(hooks_dir: &Path, point: HookPoint) -> Option<PathBuf> {
+    let path = hooks_dir.join(point.file_name());
+    let metadata = fs::metadata(&path).ok().filter(|m| m.is_file())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return None;
+        }
+        if let Err(e) = only_writable_by_user(hooks_dir).and_then(|()| only_writable_by_user(&path)) {
+            tracing::warn!("not running {}: {}", point.file_name(), e);
+            return None;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+    Some(path)
+}
+
+/// Whether `path` is the user's and nobody else can write to it.
+#[cfg(unix)]
+fn only_writable_by_user(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    // SAFETY: geteuid can't fail and touches no memory
+    if metadata.uid() != unsafe { libc::geteuid() } {
+        return Err(format!("{} belongs to someone else", path.display()));
+    }
+    if metadata.mode() & 0o022 != 0 {
+        return Err(format!("{} is writable by its group or everyone", path.display()));
+    }
+    Ok(())
+}
+
+/// Runs the hook with `input` on stdin. What it printed, or why it failed: what it wrote
+/// to stderr, else its exit status.
+fn run(tasks_dir: &Path, point: HookPoint, path: &Path, input: &str) -> Result<String, String> {
+    let name = point.file_name();
+    let mut child = Command::new(path)
+        .current_dir(tasks_dir)
+        .env("YARMTL_HOOK", name)
+        .env("YARMTL_TASKS_DIR", tasks_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{} couldn't run: {}", name, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook may exit without reading it all
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let output = child.wait_with_output().map_err(|e| format!("{} failed: {}", name, e))?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(match stderr.is_empty() {
+        true => format!("{} refused ({})", name, output.status),
+        false => format!("{} refused: {}", name, stderr),
+    })
+}
+
+/// Runs the hook for `point` on the task, as json on stdin. A hook that exits with an
+/// error refuses the change, the error says why. What it prints is json with the fields
+/// of the task to change, or the whole task changed; nothing leaves the task as it is.
+pub fn run_task_hook(tasks_dir: &Path, point: HookPoint, task: &Task) -> Result<Task, String> {
+    match hook_path(tasks_dir, point) {
+        Some(path) => run_task_hook_at(tasks_dir, point, &path, task),
+        None => Ok(task.clone()),
+    }
+}
+
+/// `run_task_hook` with the hook at `path`.
+fn run_task_hook_at(tasks_dir: &Path, point: HookPoint, path: &Path, task: &Task) -> Result<Task, String> {
+    let input = serde_json::to_value(ApiTask::from(task)).map_err(|e| e.to_string())?;
+    let output = run(tasks_dir, point, path, &input.to_string())?;
+    if output.trim().is_empty() {
+        return Ok(task.clone());
+    }
+    let changes: Value = serde_json::from_str(&output)
+        .map_err(|e| format!("{} printed something that isn't json: {}", point.file_name(), e))?;
+    changed(task, input, changes).map_err(|e| format!("{} printed {}", point.file_name(), e))
+}
+
+/// `task` with the fields in `changes` laid over `original`, its json. Only what can be
+/// typed into a task changes; the id, the dates it was added and done on and whether it
+/// is completed stay.
+fn changed(task: &Task, original: Value, changes: Value) -> Result<Task, String> {
+    let (Value::Object(mut fields), Value::Object(changes)) = (original, changes) else {
+        return Err("json that isn't an object".to_string());
+    };
+    fields.extend(changes);
+    let changed: ApiTask = serde_json::from_value(Value::Object(fields)).map_err(|e| e.to_string())?;
+    if changed.text.trim().is_empty() {
+        return Err("a task without text".to_string());
+    }
+
+    let mut task = task.clone();
+    task.text = changed.text;
+    task.project = changed.project;
+    task.tags = changed.tags;
+    task.contexts = changed.contexts;
+    task.deadline = changed.deadline;
+    task.reminder = changed.reminder;
+    task.repeat = match changed.repeat {
+        Some(repeat) => {
+            let anchor = task.deadline.unwrap_or_else(|| chrono::Local::now().date_naive());
+            Some(Recurrence::parse_rule(&repeat, anchor).ok_or_else(|| format!("a repeat it can't read, {}", repeat))?)
+        }
+        None => None,
+    };
+    task.importance = changed.importance.filter(|i| (1..=5).contains(i));
+    task.notes = changed.notes.filter(|n| !n.trim().is_empty());
+    task.pinned = changed.pinned;
+    Ok(task)
+}
+
+/// Runs on-sync with the report of a sync, as json on stdin. A failing hook is only told about.
+pub fn run_sync_hook(tasks_dir: &Path, report: &SyncReport) {
+    let Some(path) = hook_path(tasks_dir, HookPoint::Sync) else { return };
+    let input = serde_json::json!({ "service": report.service, "summary": report.summary(), "report": report });
+    if let Err(e) = run(tasks_dir, HookPoint::Sync, &path, &input.to_string()) {
+        tracing::warn!("{}", e);
+    }
+}
+
+/// Runs on-overdue for each open task whose deadline passed since it last ran for it,
+/// and again when a moved deadline passes. The first run only takes note of the tasks
+/// already overdue. Returns the indices of the tasks the hook changed.
+pub fn run_overdue_hooks(tasks_dir: &Path, tasks: &mut [Task], today: NaiveDate) -> std::io::Result<Vec<usize>> {
+    if hook_path(tasks_dir, HookPoint::Overdue).is_none() {
+        return Ok(Vec::new());
+    }
+    let state_path = paths::state_dir_of(tasks_dir).join(OVERDUE_STATE_FILE);
+    let before: Option<BTreeMap<String, NaiveDate>> =
+        fs::read_to_string(&state_path).ok().and_then(|content| serde_json::from_str(&content).ok());
+
+    let mut reported = BTreeMap::new();
+    let mut changed = Vec::new();
+    for (index, task) in tasks.iter_mut().enumerate() {
+        let Some(deadline) = task.deadline.filter(|d| !task.completed && *d < today) else { continue };
+        let due = before.as_ref().is_some_and(|before| before.get(&task.id) != Some(&deadline));
+        reported.insert(task.id.clone(), deadline);
+        if !due {
+            continue;
+        }
+        match run_task_hook(tasks_dir, HookPoint::Overdue, task) {
+            Ok(updated) if updated != *task => {
+                *task = updated;
+                changed.push(index);
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("{}", e),
+        }
+    }
+    fs::write(state_path, serde_json::to_string(&reported)?)?;
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hooks_refuse_and_change_tasks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tasks_dir = tempfile::tempdir().unwrap();
+        let hooks_dir = tasks_dir.path().join(HOOKS_DIR);
+        fs::create_dir(&hooks_dir).unwrap();
+        fs::set_permissions(&hooks_dir, fs::Permissions::from_mode(0o755)).unwrap();
+        let install = |point: HookPoint, script: &str| {
+            let path = hooks_dir.join(point.file_name());
+            fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        let run_hook = |point: HookPoint, task: &Task| {
+            let path = find_hook(&hooks_dir, point).unwrap();
+            run_task_hook_at(tasks_dir.path(), point, &path, task)
+        };
+
+        let task = Task::parse("call bank #money [id:aaaa01]");
+        assert!(find_hook(&hooks_dir, HookPoint::Add).is_none());
+
+        install(HookPoint::Add, r#"cat > /dev/null; echo '{"tags": ["money", "inbox"], "importance": 2}'"#);
+        let added = run_hook(HookPoint::Add, &task).unwrap();
+        assert_eq!((added.tags, added.importance, added.id), (vec!["money".to_string(), "inbox".to_string()], Some(2), task.id.clone()));
+
+        install(HookPoint::Complete, "grep -q '\"importance\":null' && echo 'set an importance first' >&2 && exit 1");
+        assert_eq!(run_hook(HookPoint::Complete, &task), Err("on-complete refused: set an importance first".to_string()));
+
+        // Not executable, so skipped
+        let overdue = install(HookPoint::Overdue, "exit 1");
+        fs::set_permissions(&overdue, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(find_hook(&hooks_dir, HookPoint::Overdue).is_none());
+
+        // Writable by others, or in a directory that is, so refused
+        let sync = install(HookPoint::Sync, "exit 0");
+        fs::set_permissions(&sync, fs::Permissions::from_mode(0o775)).unwrap();
+        assert!(find_hook(&hooks_dir, HookPoint::Sync).is_none());
+        fs::set_permissions(&sync, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(find_hook(&hooks_dir, HookPoint::Sync).is_some());
+        fs::set_permissions(&hooks_dir, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(find_hook(&hooks_dir, HookPoint::Sync).is_none());
+    }
+}
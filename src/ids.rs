@@ -0,0 +1,175 @@
+use crate::hash;
+use crate::links::{self, LinkTarget};
+use crate::task::Task;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Crockford's base32 in lower case: no i, l, o or u to misread. Hex ids from older
+/// versions only use characters from it, so they stay valid.
+pub const ALPHABET: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+
+/// Length of new ids, about a billion of them.
+pub const LENGTH: usize = 6;
+
+/// Longer ids, like the full UUIDs of older versions, are replaced by short ones.
+pub const MAX_LENGTH: usize = 8;
+
+/// Where replaced ids are recorded, next to tasks.md.
+pub const FILE_NAME: &str = "ids.json";
+
+/// Whether `[id:...]` holds an id: base32 characters and dashes.
+pub fn is_valid(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|b| b == b'-' || ALPHABET.contains(&b))
+}
+
+/// A random id of `LENGTH` characters.
+pub fn generate() -> String {
+    // 32 divides 256, so every character is equally likely
+    uuid::Uuid::new_v4().as_bytes()[..LENGTH]
+        .iter()
+        .map(|b| ALPHABET[*b as usize % ALPHABET.len()] as char)
+        .collect()
+}
+
+/// The id of a task line without one, made from the line and from how many lines the same
+/// came `before` it, so it is the same every time tasks.md is read: the id `--list` shows
+/// finds the task, though it is only written to the file once the task changes.
+pub fn from_line(line: &str, before: usize) -> String {
+    let hash = hash::fnv1a(line.bytes().chain(before.to_le_bytes()));
+    (0..LENGTH).map(|i| ALPHABET[(hash >> (5 * i)) as usize % ALPHABET.len()] as char).collect()
+}
+
+/// A random id that is neither one of `taken` nor a prefix of one, or the other way round.
+pub fn new_id(taken: &HashSet<String>) -> String {
+    loop {
+        let id = generate();
+        if !taken.iter().any(|t| t.starts_with(&id) || id.starts_with(t.as_str())) {
+            return id;
+        }
+    }
+}
+
+/// Replaced ids and what replaced them, so references to the old ones still find their
+/// task and the Todoist sync keeps its pairing.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct IdMap {
+    pub renamed: BTreeMap<String, String>,
+}
+
+impl IdMap {
+    pub fn load(tasks_dir: &Path) -> Self {
+        fs::read_to_string(tasks_dir.join(FILE_NAME))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, tasks_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(tasks_dir.join(FILE_NAME), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The id that replaced the one `reference` is a prefix of, when there is just one.
+    pub fn lookup(&self, reference: &str) -> Option<&str> {
+        let mut matches = self.renamed.iter().filter(|(old, _)| old.starts_with(reference));
+        match (matches.next(), matches.next()) {
+            (Some((_, new)), None) => Some(new),
+            _ => None,
+        }
+    }
+}
+
+/// `fix_ids` with the map kept in `tasks_dir`.
+pub fn fix_task_ids(tasks_dir: &Path, tasks: &mut [Task]) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let mut map = IdMap::load(tasks_dir);
+    let renames = fix_ids(tasks, &mut map);
+    if renames.iter().any(|(old, _)| old.len() > MAX_LENGTH) {
+        map.save(tasks_dir)?;
+    }
+    Ok(renames)
+}
+
+/// Gives new ids to the tasks whose id is too long or already used by a task above
+/// them. Long ids keep the replacement recorded in `map`, and links to them are
+/// rewritten. Returns the (old, new) pairs.
+pub fn fix_ids(tasks: &mut [Task], map: &mut IdMap) -> Vec<(String, String)> {
+    let mut seen = HashSet::new();
+    let to_fix: Vec<usize> = (0..tasks.len())
+        .filter(|&i| !seen.insert(tasks[i].id.clone()) || tasks[i].id.len() > MAX_LENGTH)
+        .collect();
+    let mut taken: HashSet<String> = seen.into_iter().filter(|id| id.len() <= MAX_LENGTH).collect();
+
+    let mut renames = Vec::new();
+    for index in to_fix {
+        let old = tasks[index].id.clone();
+        let long = old.len() > MAX_LENGTH;
+        let new = match map.renamed.get(&old) {
+            Some(recorded) if long && !taken.contains(recorded) => recorded.clone(),
+            _ => new_id(&taken),
+        };
+        if long {
+            map.renamed.insert(old.clone(), new.clone());
+        }
+        taken.insert(new.clone());
+        tasks[index].id = new.clone();
+        renames.push((old, new));
+    }
+
+    let long_renames: Vec<&(String, String)> = renames.iter().filter(|(old, _)| old.len() > MAX_LENGTH).collect();
+    if !long_renames.is_empty() {
+        let snapshot = tasks.to_vec();
+        let relink = |text: &str| {
+            links::LINK_RE
+                .replace_all(text, |caps: &regex::Captures| {
+                    let reference = &caps[1];
+                    let renamed = long_renames.iter().find(|(old, _)| old.starts_with(reference));
+                    match renamed {
+                        Some((_, new)) if links::resolve(&snapshot, reference) == LinkTarget::Missing => {
+                            format!("[[id:{}]]", new)
+                        }
+                        _ => caps[0].to_string(),
+                    }
+                })
+                .into_owned()
+        };
+        for task in tasks.iter_mut() {
+            task.text = relink(&task.text);
+            task.notes = task.notes.as_deref().map(relink);
+        }
+    }
+    renames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fix_ids() {
+        let uuid = "3fa1aaaa-0000-4000-8000-000000000000";
+        let mut tasks = vec![
+            Task::parse(&format!("old one [id:{}]", uuid)),
+            Task::parse("first [id:bbbb1111]"),
+            Task::parse("copy of first [id:bbbb1111]"),
+            Task::parse("after [[id:3fa1aaaa]] and [[id:bbbb]]"),
+        ];
+        assert!(tasks.iter().all(|t| is_valid(&t.id)));
+        assert_eq!(tasks[3].id.len(), LENGTH);
+
+        let mut map = IdMap::default();
+        let renames = fix_ids(&mut tasks, &mut map);
+        assert_eq!(renames.len(), 2);
+        assert_eq!(tasks[1].id, "bbbb1111");
+        assert_ne!(tasks[2].id, "bbbb1111");
+        let new = tasks[0].id.clone();
+        assert_eq!(map.lookup("3fa1"), Some(new.as_str()));
+        assert_eq!(tasks[3].text, format!("after [[id:{}]] and [[id:bbbb]]", new));
+
+        // The same replacement every time the old id turns up again
+        let mut again = vec![Task::parse(&format!("old one [id:{}]", uuid))];
+        assert_eq!(fix_ids(&mut again, &mut map)[0].1, new);
+        assert!(fix_ids(&mut tasks, &mut map).is_empty());
+    }
+}
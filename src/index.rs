@@ -0,0 +1,385 @@
+use crate::encryption;
+use crate::error::YarmtlError;
+use crate::store::TaskStore;
+use crate::task::{self, Task, TaskFormat};
+use chrono::NaiveDate;
+use rusqlite::{Connection, params, params_from_iter};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS files (path TEXT PRIMARY KEY, modified INTEGER NOT NULL, size INTEGER NOT NULL);
+    CREATE TABLE IF NOT EXISTS tasks (
+        id TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        line TEXT NOT NULL,
+        project TEXT,
+        source TEXT,
+        completed INTEGER NOT NULL,
+        deadline TEXT
+    );
+    CREATE INDEX IF NOT EXISTS tasks_deadline ON tasks (deadline);
+    CREATE TABLE IF NOT EXISTS tags (id TEXT NOT NULL, tag TEXT NOT NULL COLLATE NOCASE);
+    CREATE INDEX IF NOT EXISTS tags_tag ON tags (tag);
+    CREATE INDEX IF NOT EXISTS tags_id ON tags (id);
+    CREATE VIRTUAL TABLE IF NOT EXISTS task_words USING fts5 (id UNINDEXED, words);
+";
+
+/// The `[index]` table of config.toml.
+///
+/// ```toml
+/// [index]
+/// enabled = true
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// Answer `yarmtl search` from an SQLite index of the tasks instead of reading tasks.md
+    pub enabled: bool,
+}
+
+/// What `yarmtl search` looks for. Every part that is set has to match.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    /// Words the task's text, notes, project, tags or contexts have words starting with
+    pub words: String,
+    pub tag: Option<String>,
+    pub project: Option<String>,
+    /// Only tasks with a deadline on or before this day
+    pub due_by: Option<NaiveDate>,
+    /// Completed and cancelled tasks too
+    pub completed: bool,
+}
+
+impl Query {
+    /// Whether `task` is one the query looks for, the way the index answers it: what
+    /// `search` does without one.
+    pub fn matches(&self, task: &Task) -> bool {
+        let words = search_words(task).to_lowercase();
+        let words: Vec<&str> = words.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).collect();
+        (self.completed || !task.completed)
+            && query_words(&self.words).iter().all(|wanted| words.iter().any(|word| word.starts_with(wanted.as_str())))
+            && self.tag.as_ref().is_none_or(|tag| task.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+            && self.project.as_ref().is_none_or(|project| task.project.as_ref() == Some(project))
+            && self.due_by.is_none_or(|day| task.deadline.is_some_and(|deadline| deadline <= day))
+    }
+}
+
+/// How many tasks an update wrote to the index, the others being as they were.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IndexUpdate {
+    pub added: usize,
+    pub changed: usize,
+    pub removed: usize,
+}
+
+/// What is in the index, for `yarmtl index`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexStats {
+    pub tasks: usize,
+    pub open: usize,
+    pub overdue: usize,
+    /// The tags of open tasks, the most used first
+    pub tags: Vec<(String, usize)>,
+}
+
+/// An SQLite copy of the tasks, for searching years of them without reading tasks.md.
+/// tasks.md stays what the tasks are: the index is brought up to date from it before it
+/// answers, writing only the tasks that changed, and can be deleted at any time.
+pub struct TaskIndex {
+    connection: Connection,
+    path: PathBuf,
+}
+
+impl TaskIndex {
+    pub fn open(path: &Path) -> Result<TaskIndex, YarmtlError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(YarmtlError::io("create", dir))?;
+        }
+        let connection = Connection::open(path).map_err(YarmtlError::index(path))?;
+        connection.execute_batch(SCHEMA).map_err(YarmtlError::index(path))?;
+        Ok(TaskIndex { connection, path: path.to_path_buf() })
+    }
+
+    /// The index in ~/.local/share/yarmtl, outside the tasks directory so it isn't synced.
+    pub fn open_default() -> Result<TaskIndex, YarmtlError> {
+        TaskIndex::open(&crate::setup::index_path())
+    }
+
+    /// Whether the index can hold the tasks of `tasks_file`: not when they are encrypted,
+    /// since it would keep them in the clear.
+    pub fn can_index(tasks_file: &Path) -> bool {
+        !encryption::is_encrypted(tasks_file)
+    }
+
+    /// Brings the index up to date with tasks.md at `tasks_file` and the files it includes.
+    /// They are only read when one of them changed since the last update.
+    pub fn update(&mut self, tasks_file: &Path) -> Result<IndexUpdate, YarmtlError> {
+        if self.is_current(tasks_file)? {
+            return Ok(IndexUpdate::default());
+        }
+        let store = TaskStore::open(tasks_file)?;
+        let index = YarmtlError::index(&self.path);
+        let transaction = self.connection.transaction().map_err(index)?;
+        let update = write_tasks(&transaction, &store).map_err(YarmtlError::index(&self.path))?;
+        transaction.commit().map_err(YarmtlError::index(&self.path))?;
+        Ok(update)
+    }
+
+    /// Forgets everything in the index and reads the tasks again.
+    pub fn rebuild(&mut self, tasks_file: &Path) -> Result<IndexUpdate, YarmtlError> {
+        let clear = "DELETE FROM files; DELETE FROM tasks; DELETE FROM tags; DELETE FROM task_words;";
+        self.connection.execute_batch(clear).map_err(YarmtlError::index(&self.path))?;
+        self.update(tasks_file)
+    }
+
+    /// The tasks `query` looks for, in the order of tasks.md.
+    pub fn search(&self, query: &Query) -> Result<Vec<Task>, YarmtlError> {
+        let mut sql = String::from("SELECT line, project, source FROM tasks WHERE 1 = 1");
+        let mut values: Vec<String> = Vec::new();
+        if !query.completed {
+            sql.push_str(" AND completed = 0");
+        }
+        let words = query_words(&query.words);
+        if !words.is_empty() {
+            sql.push_str(" AND id IN (SELECT id FROM task_words WHERE task_words MATCH ?)");
+            values.push(words.iter().map(|word| format!("\"{}\"*", word.replace('"', "\"\""))).collect::<Vec<_>>().join(" "));
+        }
+        if let Some(tag) = &query.tag {
+            sql.push_str(" AND id IN (SELECT id FROM tags WHERE tag = ?)");
+            values.push(tag.clone());
+        }
+        if let Some(project) = &query.project {
+            sql.push_str(" AND project = ?");
+            values.push(project.clone());
+        }
+        if let Some(day) = query.due_by {
+            sql.push_str(" AND deadline <= ?");
+            values.push(day.format("%Y-%m-%d").to_string());
+        }
+        sql.push_str(" ORDER BY position");
+
+        let index = || YarmtlError::index(&self.path);
+        let mut statement = self.connection.prepare(&sql).map_err(index())?;
+        let rows = statement.query_map(params_from_iter(values), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?))
+        });
+        let mut tasks = Vec::new();
+        for row in rows.map_err(index())? {
+            let (line, project, source) = row.map_err(index())?;
+            if let Some(task) = task::parse_task_line(&line) {
+                tasks.push(Task { project, source, ..task });
+            }
+        }
+        Ok(tasks)
+    }
+
+    pub fn stats(&self, today: NaiveDate) -> Result<IndexStats, YarmtlError> {
+        let index = || YarmtlError::index(&self.path);
+        let count = |sql: &str, values: &[&str]| -> Result<usize, YarmtlError> {
+            let count: i64 = self.connection.query_row(sql, params_from_iter(values), |row| row.get(0)).map_err(index())?;
+            Ok(count as usize)
+        };
+        let today = today.format("%Y-%m-%d").to_string();
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT tag, COUNT(*) AS uses FROM tags JOIN tasks USING (id) WHERE completed = 0 \
+                 GROUP BY tag ORDER BY uses DESC, tag",
+            )
+            .map_err(index())?;
+        let tags = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize)))
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(index())?;
+        Ok(IndexStats {
+            tasks: count("SELECT COUNT(*) FROM tasks", &[])?,
+            open: count("SELECT COUNT(*) FROM tasks WHERE completed = 0", &[])?,
+            overdue: count("SELECT COUNT(*) FROM tasks WHERE completed = 0 AND deadline < ?", &[&today])?,
+            tags,
+        })
+    }
+
+    /// How many open tasks are past their deadline, and how many are due `today`.
+    pub fn due_counts(&self, today: NaiveDate) -> Result<(usize, usize), YarmtlError> {
+        let today = today.format("%Y-%m-%d").to_string();
+        let sql = "SELECT COUNT(*) FILTER (WHERE deadline < ?1), COUNT(*) FILTER (WHERE deadline = ?1) FROM tasks WHERE completed = 0";
+        let (overdue, due): (i64, i64) =
+            self.connection.query_row(sql, [&today], |row| Ok((row.get(0)?, row.get(1)?))).map_err(YarmtlError::index(&self.path))?;
+        Ok((overdue as usize, due as usize))
+    }
+
+    /// Whether every file the index was last updated from is still as it was, tasks.md at
+    /// `tasks_file` being one of them. A tasks.md including another file has to change for
+    /// the files it includes to change.
+    fn is_current(&self, tasks_file: &Path) -> Result<bool, YarmtlError> {
+        let index = || YarmtlError::index(&self.path);
+        let mut statement = self.connection.prepare("SELECT path, modified, size FROM files").map_err(index())?;
+        let files = statement
+            .query_map([], |row| Ok((PathBuf::from(row.get::<_, String>(0)?), (row.get(1)?, row.get(2)?))))
+            .and_then(|rows| rows.collect::<Result<Vec<(PathBuf, (i64, i64))>, _>>())
+            .map_err(index())?;
+        let has_tasks_file = files.iter().any(|(path, _)| path == tasks_file);
+        Ok(has_tasks_file && files.iter().all(|(path, stamp)| file_stamp(path) == Some(*stamp)))
+    }
+}
+
+/// Writes the tasks of `store` that aren't in the index as they are, and forgets the ones
+/// that aren't in the store anymore.
+fn write_tasks(connection: &Connection, store: &TaskStore) -> rusqlite::Result<IndexUpdate> {
+    let mut indexed: HashMap<String, (i64, String, Option<String>, Option<String>)> = HashMap::new();
+    {
+        let mut statement = connection.prepare("SELECT id, position, line, project, source FROM tasks")?;
+        let rows = statement.query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))))?;
+        for row in rows {
+            let (id, row) = row?;
+            indexed.insert(id, row);
+        }
+    }
+
+    let mut update = IndexUpdate::default();
+    let mut move_task = connection.prepare("UPDATE tasks SET position = ?2 WHERE id = ?1")?;
+    let mut insert_task = connection.prepare("INSERT OR REPLACE INTO tasks VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+    let mut insert_tag = connection.prepare("INSERT INTO tags VALUES (?1, ?2)")?;
+    let mut insert_words = connection.prepare("INSERT INTO task_words VALUES (?1, ?2)")?;
+    for (position, task) in store.tasks().iter().enumerate() {
+        let position = position as i64;
+        // The way yarmtl writes it, whatever the format of tasks.md
+        let line = task.to_markdown_as(TaskFormat::Yarmtl);
+        match indexed.remove(&task.id) {
+            Some((at, before, project, source)) if before == line && project == task.project && source == task.source => {
+                if at != position {
+                    move_task.execute(params![task.id, position])?;
+                }
+                continue;
+            }
+            Some(_) => {
+                forget(connection, &task.id)?;
+                update.changed += 1;
+            }
+            None => update.added += 1,
+        }
+        let deadline = task.deadline.map(|day| day.format("%Y-%m-%d").to_string());
+        insert_task.execute(params![task.id, position, line, task.project, task.source, task.completed, deadline])?;
+        for tag in &task.tags {
+            insert_tag.execute(params![task.id, tag])?;
+        }
+        insert_words.execute(params![task.id, search_words(task)])?;
+    }
+    for id in indexed.keys() {
+        forget(connection, id)?;
+        update.removed += 1;
+    }
+
+    connection.execute("DELETE FROM files", [])?;
+    for file in store.files() {
+        if let Some((modified, size)) = file_stamp(&file) {
+            connection.execute("INSERT INTO files VALUES (?1, ?2, ?3)", params![file.to_string_lossy(), modified, size])?;
+        }
+    }
+    Ok(update)
+}
+
+fn forget(connection: &Connection, id: &str) -> rusqlite::Result<()> {
+    for table in ["tasks", "tags", "task_words"] {
+        connection.execute(&format!("DELETE FROM {} WHERE id = ?1", table), [id])?;
+    }
+    Ok(())
+}
+
+/// When the file was last written and how long it is, or None when there is no file.
+fn file_stamp(path: &Path) -> Option<(i64, i64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((modified.as_nanos() as i64, metadata.len() as i64))
+}
+
+/// What the words of a query are looked for in.
+fn search_words(task: &Task) -> String {
+    let mut words = vec![task.text.as_str()];
+    words.extend(task.notes.as_deref());
+    words.extend(task.project.as_deref());
+    words.extend(task.tags.iter().map(String::as_str));
+    words.extend(task.contexts.iter().map(String::as_str));
+    words.join(" ")
+}
+
+fn query_words(words: &str) -> Vec<String> {
+    let words = words.to_lowercase();
+    words.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).map(str::to_string).collect()
+}
+
+/// `TaskIndex::search` when there is an index, otherwise the tasks of tasks.md `query`
+/// matches.
+pub fn search(tasks_file: &Path, query: &Query, use_index: bool) -> Result<Vec<Task>, YarmtlError> {
+    if use_index && TaskIndex::can_index(tasks_file) {
+        let mut index = TaskIndex::open_default()?;
+        index.update(tasks_file)?;
+        return index.search(query);
+    }
+    let store = TaskStore::open(tasks_file)?;
+    Ok(store.tasks().iter().filter(|task| query.matches(task)).cloned().collect())
+}
+
+/// `TaskIndex::due_counts` for `yarmtl prompt`, without reading tasks.md unless it changed
+/// since the index was last brought up to date. Encrypted tasks aren't indexed, so they
+/// are read and counted, which takes longer.
+pub fn due_counts(tasks_file: &Path, today: NaiveDate) -> Result<(usize, usize), YarmtlError> {
+    if TaskIndex::can_index(tasks_file) {
+        let mut index = TaskIndex::open_default()?;
+        index.update(tasks_file)?;
+        return index.due_counts(today);
+    }
+    // open_tasks keeps the completed parents of open subtasks, the index doesn't count them
+    let tasks = TaskStore::open_tasks(tasks_file)?;
+    let due = |when: fn(NaiveDate, NaiveDate) -> bool| {
+        tasks.iter().filter(|task| !task.completed && task.deadline.is_some_and(|deadline| when(deadline, today))).count()
+    };
+    Ok((due(|deadline, today| deadline < today), due(|deadline, today| deadline == today)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_answers_like_the_tasks_and_updates_what_changed() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("tasks.md");
+        let content = "# tasks\n\n- [ ] renew passport !2025-07-01 #admin [id:aaaa01]\n\
+                       - [x] passport photos #admin [id:aaaa02]\n\n## Home\n- [ ] water the plants [id:aaaa03]\n";
+        fs::write(&path, content).unwrap();
+        let mut index = TaskIndex::open(&dir.join("index.sqlite")).unwrap();
+        assert_eq!(index.update(&path).unwrap(), IndexUpdate { added: 3, changed: 0, removed: 0 });
+
+        let queries = [
+            Query { words: "PASS".into(), ..Query::default() },
+            Query { words: "pass".into(), completed: true, ..Query::default() },
+            Query { tag: Some("admin".into()), completed: true, ..Query::default() },
+            Query { project: Some("Home".into()), ..Query::default() },
+            Query { due_by: NaiveDate::from_ymd_opt(2025, 7, 1), ..Query::default() },
+            Query { words: "port".into(), ..Query::default() },
+        ];
+        let tasks = TaskStore::open(&path).unwrap().tasks().to_vec();
+        for query in &queries {
+            let found: Vec<Task> = tasks.iter().filter(|task| query.matches(task)).cloned().collect();
+            assert_eq!(index.search(query).unwrap(), found, "{:?}", query);
+        }
+        assert_eq!(index.search(&queries[1]).unwrap().len(), 2);
+        assert!(index.search(&queries[5]).unwrap().is_empty());
+
+        fs::write(&path, content.replace("water the plants", "water the roses").replace("- [x] passport photos #admin [id:aaaa02]\n", ""))
+            .unwrap();
+        assert_eq!(index.update(&path).unwrap(), IndexUpdate { added: 0, changed: 1, removed: 1 });
+        assert_eq!(index.search(&Query { words: "roses".into(), ..Query::default() }).unwrap()[0].id, "aaaa03");
+        assert_eq!(index.update(&path).unwrap(), IndexUpdate::default());
+        let today = NaiveDate::from_ymd_opt(2025, 7, 2).unwrap();
+        assert_eq!(index.stats(today).unwrap(), IndexStats { tasks: 2, open: 2, overdue: 1, tags: vec![("admin".into(), 1)] });
+        assert_eq!(index.due_counts(today).unwrap(), (1, 0));
+        assert_eq!(index.due_counts(NaiveDate::from_ymd_opt(2025, 7, 1).unwrap()).unwrap(), (0, 1));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::error::YarmtlError;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// In the tasks directory while an operation that writes several files runs.
+const JOURNAL_DIR: &str = ".journal";
+
+/// What the operation is and how far it got, next to the copies of the files.
+const RECORD_FILE: &str = "journal.json";
+
+/// What an operation has done to one of its files so far.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum After {
+    /// Not written yet, or written without its content known
+    Pending,
+    Written,
+    Removed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Record {
+    operation: String,
+    started: DateTime<Local>,
+    files: Vec<PathBuf>,
+    /// Whether each file was there before the operation, so rolling back removes it
+    existed: Vec<bool>,
+    after: Vec<After>,
+    /// What it did so far that can't be taken back from here, like a task created in Todoist
+    steps: Vec<String>,
+}
+
+/// A write-ahead journal for an operation that writes more than one file, like a sync
+/// writing tasks.md and .sync_metadata.json: the files as they were are copied before
+/// it starts, and each as it will be before it is written. When yarmtl stops halfway, the
+/// journal is still there the next time, for `roll_forward` or `roll_back` to leave the
+/// files agreeing with each other.
+pub struct Journal {
+    dir: PathBuf,
+    record: Record,
+}
+
+impl Journal {
+    /// Starts the journal of `operation`, which is going to write `files`. Fails while the
+    /// journal of another one is still there.
+    pub fn begin(tasks_dir: &Path, operation: &str, files: &[PathBuf]) -> Result<Journal, YarmtlError> {
+        if let Some(unfinished) = Journal::pending(tasks_dir) {
+            return Err(YarmtlError::Unfinished { operation: unfinished.record.operation, started: unfinished.record.started });
+        }
+        let dir = tasks_dir.join(JOURNAL_DIR);
+        // A journal that never got its record was left before anything was written
+        if dir.exists() {
+            fs::remove_dir_all(&dir).map_err(YarmtlError::io("remove", &dir))?;
+        }
+        let mut existed = Vec::new();
+        for (index, file) in files.iter().enumerate() {
+            let copy = dir.join("before").join(index.to_string());
+            let content = match fs::read(file) {
+                Ok(content) => Some(content),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(YarmtlError::io("read", file)(e)),
+            };
+            if let Some(content) = &content {
+                write_synced(&copy, content).map_err(YarmtlError::io("write", &copy))?;
+            }
+            existed.push(content.is_some());
+        }
+        let record = Record {
+            operation: operation.to_string(),
+            started: Local::now(),
+            files: files.to_vec(),
+            existed,
+            after: vec![After::Pending; files.len()],
+            steps: Vec::new(),
+        };
+        let journal = Journal { dir, record };
+        journal.save()?;
+        Ok(journal)
+    }
+
+    /// The journal of an operation that didn't finish, if there is one.
+    pub fn pending(tasks_dir: &Path) -> Option<Journal> {
+        let dir = tasks_dir.join(JOURNAL_DIR);
+        let record = serde_json::from_slice(&fs::read(dir.join(RECORD_FILE)).ok()?).ok()?;
+        Some(Journal { dir, record })
+    }
+
+    /// Notes down a step taken.
+    pub fn step(&mut self, step: String) -> Result<(), YarmtlError> {
+        self.record.steps.push(step);
+        self.save()
+    }
+
+    /// Writes `file`, one of the operation's, with `content`, which the journal keeps first.
+    pub fn write(&mut self, file: &Path, content: &[u8]) -> Result<(), YarmtlError> {
+        let index = self.index_of(file);
+        let copy = self.dir.join("after").join(index.to_string());
+        write_synced(&copy, content).map_err(YarmtlError::io("write", &copy))?;
+        self.record.after[index] = After::Written;
+        self.save()?;
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir).map_err(YarmtlError::io("create", dir))?;
+        }
+        fs::write(file, content).map_err(YarmtlError::io("write", file))
+    }
+
+    /// Removes `file`, one of the operation's.
+    pub fn remove(&mut self, file: &Path) -> Result<(), YarmtlError> {
+        let index = self.index_of(file);
+        self.record.after[index] = After::Removed;
+        self.save()?;
+        match fs::remove_file(file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(YarmtlError::io("remove", file)(e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Notes down `file` as it is now, after something else wrote it, like a `TaskStore`
+    /// merging its save with the file.
+    pub fn wrote(&mut self, file: &Path) -> Result<(), YarmtlError> {
+        let content = fs::read(file).map_err(YarmtlError::io("read", file))?;
+        let index = self.index_of(file);
+        let copy = self.dir.join("after").join(index.to_string());
+        write_synced(&copy, &content).map_err(YarmtlError::io("write", &copy))?;
+        self.record.after[index] = After::Written;
+        self.save()
+    }
+
+    /// The operation is done and its files agree: the journal goes.
+    pub fn finish(self) -> Result<(), YarmtlError> {
+        fs::remove_dir_all(&self.dir).map_err(YarmtlError::io("remove", &self.dir))
+    }
+
+    /// What the operation was, "Todoist sync".
+    pub fn operation(&self) -> &str {
+        &self.record.operation
+    }
+
+    pub fn started(&self) -> DateTime<Local> {
+        self.record.started
+    }
+
+    pub fn steps(&self) -> &[String] {
+        &self.record.steps
+    }
+
+    /// The operation's files, each with what it had done to it.
+    pub fn files(&self) -> Vec<(&Path, After)> {
+        self.record.files.iter().map(PathBuf::as_path).zip(self.record.after.iter().copied()).collect()
+    }
+
+    /// Finishes what the operation had written or was about to write, leaving the files it
+    /// hadn't got to as they are. Returns the files written or removed.
+    pub fn roll_forward(self) -> Result<Vec<PathBuf>, YarmtlError> {
+        let mut written = Vec::new();
+        for (index, file) in self.record.files.iter().enumerate() {
+            let copy = self.dir.join("after").join(index.to_string());
+            match self.record.after[index] {
+                After::Pending => continue,
+                After::Written => restore(&copy, file)?,
+                After::Removed => remove(file)?,
+            }
+            written.push(file.clone());
+        }
+        self.finish()?;
+        Ok(written)
+    }
+
+    /// Puts every file of the operation back as it was before it. Returns the files written or removed.
+    pub fn roll_back(self) -> Result<Vec<PathBuf>, YarmtlError> {
+        for (index, file) in self.record.files.iter().enumerate() {
+            match self.record.existed[index] {
+                true => restore(&self.dir.join("before").join(index.to_string()), file)?,
+                false => remove(file)?,
+            }
+        }
+        let files = self.record.files.clone();
+        self.finish()?;
+        Ok(files)
+    }
+
+    fn index_of(&self, file: &Path) -> usize {
+        let index = self.record.files.iter().position(|f| f == file);
+        index.unwrap_or_else(|| panic!("{} isn't one of the files of the {} journal", file.display(), self.record.operation))
+    }
+
+    fn save(&self) -> Result<(), YarmtlError> {
+        let path = self.dir.join(RECORD_FILE);
+        let record = serde_json::to_vec_pretty(&self.record).map_err(|e| YarmtlError::parse(&path, e))?;
+        write_synced(&path, &record).map_err(YarmtlError::io("write", &path))
+    }
+}
+
+/// Writes `content` to a file next to `path` first and then moves it over `path`, so a
+/// crash leaves either the old file or the new one, and flushes it to the disk.
+fn write_synced(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temporary = path.with_extension("tmp");
+    let mut file = fs::File::create(&temporary)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)
+}
+
+fn restore(copy: &Path, file: &Path) -> Result<(), YarmtlError> {
+    let content = fs::read(copy).map_err(YarmtlError::io("read", copy))?;
+    write_synced(file, &content).map_err(YarmtlError::io("write", file))
+}
+
+fn remove(file: &Path) -> Result<(), YarmtlError> {
+    match fs::remove_file(file) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(YarmtlError::io("remove", file)(e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unfinished_operations_roll_forward_or_back() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let (tasks, metadata) = (dir.join("tasks.md"), dir.join(".sync_metadata.json"));
+        let files = [tasks.clone(), metadata.clone()];
+        fs::write(&tasks, "- [ ] call bob [id:aaaa01]\n").unwrap();
+
+        // Stops after writing tasks.md, before the metadata that goes with it
+        let mut journal = Journal::begin(dir, "Todoist sync", &files).unwrap();
+        journal.step("created in Todoist: \"call bob\"".to_string()).unwrap();
+        fs::write(&tasks, "- [ ] call bob [id:aaaa01]\n- [ ] from todoist [id:bbbb01]\n").unwrap();
+        journal.wrote(&tasks).unwrap();
+        drop(journal);
+        assert!(matches!(Journal::begin(dir, "Todoist sync", &files), Err(YarmtlError::Unfinished { .. })));
+
+        let pending = Journal::pending(dir).unwrap();
+        assert_eq!(pending.steps(), ["created in Todoist: \"call bob\""]);
+        assert_eq!(pending.files(), [(tasks.as_path(), After::Written), (metadata.as_path(), After::Pending)]);
+        fs::write(&tasks, "half writ").unwrap();
+        assert_eq!(pending.roll_forward().unwrap(), std::slice::from_ref(&tasks));
+        assert_eq!(fs::read_to_string(&tasks).unwrap(), "- [ ] call bob [id:aaaa01]\n- [ ] from todoist [id:bbbb01]\n");
+
+        // Stops while writing the metadata: back to before it started
+        let mut journal = Journal::begin(dir, "Todoist sync", &files).unwrap();
+        fs::write(&tasks, "- [x] call bob [id:aaaa01]\n").unwrap();
+        journal.wrote(&tasks).unwrap();
+        journal.write(&metadata, b"{}").unwrap();
+        drop(journal);
+        Journal::pending(dir).unwrap().roll_back().unwrap();
+        assert_eq!(fs::read_to_string(&tasks).unwrap(), "- [ ] call bob [id:aaaa01]\n- [ ] from todoist [id:bbbb01]\n");
+        assert!(!metadata.exists());
+        assert!(Journal::pending(dir).is_none());
+    }
+}
@@ -0,0 +1,215 @@
+/// One documented key binding (or syntax element) shown in the help popup.
+pub struct KeyHelp {
+    pub keys: &'static str,
+    pub action: &'static str,
+}
+
+pub struct HelpSection {
+    pub title: &'static str,
+    pub entries: &'static [KeyHelp],
+}
+
+const fn key(keys: &'static str, action: &'static str) -> KeyHelp {
+    KeyHelp { keys, action }
+}
+
+/// Everything the help popup shows. Add new bindings here when adding them to
+/// `run_app`, the popup is generated from this table; a test fails for keys of the
+/// main views that are missing from it.
+pub const HELP_SECTIONS: &[HelpSection] = &[
+    HelpSection {
+        title: "Navigation",
+        entries: &[
+            key("j/↓", "Next task"),
+            key("k/↑", "Previous task"),
+            key("Enter/Space", "Toggle task completion"),
+        ],
+    },
+    HelpSection {
+        title: "Task Management",
+        entries: &[
+            key("a/i", "Add new task"),
+            key("d/Del", "Delete selected task (asks y/n)"),
+            key("T", "Trash: browse and restore deleted tasks"),
+            key("c", "Toggle show completed tasks"),
+            key("x", "Cancel the selected task: dropped, not done (x again reopens it)"),
+            key("X", "Toggle the list of cancelled tasks"),
+            key("r", "Reload tasks from file"),
+            key("n", "View task notes, links and backlinks (j/k + Enter to jump)"),
+            key("o", "Open the task's first attachment or link"),
+            key("s/S", "Sync with Todoist (in the background, progress in the status line)"),
+            key("p", "Start/stop a pomodoro on the selected task, logging the time spent"),
+            key("*", "Pin/unpin the selected task, pinned tasks come first in every view"),
+            key("+/-", "Count a step of the selected task's [count:3/10] up or down"),
+            key("t", "Toggle tags & contexts menu"),
+            key("P", "Group tasks by ## project heading instead of by deadline"),
+            key("Esc", "Clear tag or context filter"),
+        ],
+    },
+    HelpSection {
+        title: "Week Agenda",
+        entries: &[
+            key("w", "Toggle week agenda (overdue + next 7 days)"),
+            key("</H", "Move selected task one day earlier"),
+            key(">/L", "Move selected task one day later"),
+            key("Esc", "Back to task list"),
+        ],
+    },
+    HelpSection {
+        title: "Zen Mode",
+        entries: &[
+            key("Z", "Toggle zen mode: only today's tasks, most important first"),
+            key("j/k", "Move between tasks"),
+            key("Enter/Space", "Mark task done"),
+            key("Z/Esc", "Back to task list"),
+        ],
+    },
+    HelpSection {
+        title: "Trash",
+        entries: &[
+            key("Enter/r", "Restore selected task"),
+            key("T/Esc", "Back to task list"),
+        ],
+    },
+    HelpSection {
+        title: "Status Bar",
+        entries: &[
+            key("b/click", "Show details of a status bar segment"),
+            key("←/→", "Previous/next segment"),
+        ],
+    },
+    HelpSection {
+        title: "Task Syntax",
+        entries: &[
+            key("!2025-10-01", "Set deadline"),
+            key("@today", "Set reminder for today"),
+            key("@tomorrow", "Set reminder for tomorrow"),
+            key("@2025-10-01", "Set reminder for date"),
+            key("@every monday", "Repeating reminder: day, weekday, monday…sunday, 15th"),
+            key("#work #urgent", "Add multiple tags"),
+            key(">home >errands", "Add contexts: where the task can be done"),
+            key("//note text", "Add task notes"),
+            key("$1", "Set importance (1=very important, 5=not important)"),
+            key("~30m ~2h", "Estimate how long the task takes"),
+            key("[[id:3fa1]]", "Link to another task (any unique id prefix)"),
+            key("[docs](https://…)", "Link to a web page or file, o opens it"),
+            key("[attach:~/a.pdf]", "Attach a file by path or URL"),
+            key("[pin]", "Pin the task on top of every view"),
+            key("[count:0/10]", "Count steps towards a target, done when it's reached"),
+            key("[flag:red] [flag:🔥]", "Color dot or emoji in front of the task"),
+        ],
+    },
+    HelpSection {
+        title: "Help",
+        entries: &[
+            key("j/k", "Scroll"),
+            key("PgDn/PgUp", "Scroll a page"),
+            key("/", "Filter, Enter to keep the filter, Esc to clear it"),
+            key("h/F1/q", "Close this help"),
+        ],
+    },
+    HelpSection {
+        title: "Other",
+        entries: &[
+            key("h/F1", "Toggle this help"),
+            key("!", "Show what was logged: warnings, and more with --verbose"),
+            key("q", "Quit"),
+        ],
+    },
+];
+
+pub enum HelpLine {
+    Section(&'static str),
+    Entry(&'static KeyHelp),
+    Blank,
+}
+
+/// The help popup's lines, keeping only entries that match `filter` (case-insensitive).
+/// A section whose title matches keeps all of its entries.
+pub fn help_lines(filter: &str) -> Vec<HelpLine> {
+    let filter = filter.trim().to_lowercase();
+    let mut lines = Vec::new();
+
+    for section in HELP_SECTIONS {
+        let section_matches = section.title.to_lowercase().contains(&filter);
+        let entries: Vec<&'static KeyHelp> = section
+            .entries
+            .iter()
+            .filter(|entry| {
+                section_matches
+                    || entry.keys.to_lowercase().contains(&filter)
+                    || entry.action.to_lowercase().contains(&filter)
+            })
+            .collect();
+
+        if entries.is_empty() {
+            continue;
+        }
+        if !lines.is_empty() {
+            lines.push(HelpLine::Blank);
+        }
+        lines.push(HelpLine::Section(section.title));
+        lines.extend(entries.into_iter().map(HelpLine::Entry));
+    }
+
+    lines
+}
+
+/// Width of the keys column, so actions line up across sections.
+pub fn keys_column_width() -> usize {
+    HELP_SECTIONS
+        .iter()
+        .flat_map(|section| section.entries)
+        .map(|entry| entry.keys.chars().count())
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_keeps_matching_entries_and_their_sections() {
+        let lines = help_lines("POMODORO");
+        assert_eq!(lines.len(), 2);
+        assert!(matches!(lines[0], HelpLine::Section("Task Management")));
+        assert!(matches!(lines[1], HelpLine::Entry(entry) if entry.keys == "p"));
+
+        // A matching section title keeps the whole section
+        let week = help_lines("week agenda");
+        assert!(week.iter().filter(|l| matches!(l, HelpLine::Entry(_))).count() >= 4);
+
+        assert!(help_lines("no such binding").is_empty());
+    }
+    #[test]
+    fn test_every_key_of_the_main_views_is_documented() {
+        // The dispatch in run_app is a match per view, read here from the source
+        let source = include_str!("tui.rs");
+        let start = source.find("fn run_app").unwrap();
+        let dispatch = &source[start..];
+        let dispatch = &dispatch[dispatch.find("InputMode::Normal =>").unwrap()..dispatch.find("InputMode::Editing =>").unwrap()];
+
+        let documented: Vec<&str> = HELP_SECTIONS
+            .iter()
+            .flat_map(|section| section.entries)
+            .flat_map(|entry| entry.keys.split('/'))
+            .collect();
+        let key_re = regex::Regex::new(r"KeyCode::(?:Char\('(.)'\)|F\((\d+)\)|(\w+))").unwrap();
+        let missing: Vec<String> = key_re
+            .captures_iter(dispatch)
+            .map(|c| match (c.get(1), c.get(2), c.get(3).map(|m| m.as_str())) {
+                (Some(char), _, _) if char.as_str() == " " => "Space".to_string(),
+                (Some(char), _, _) => char.as_str().to_string(),
+                (_, Some(number), _) => format!("F{}", number.as_str()),
+                (_, _, Some("Down")) => "↓".to_string(),
+                (_, _, Some("Up")) => "↑".to_string(),
+                (_, _, Some("Delete")) => "Del".to_string(),
+                (_, _, Some(name)) => name.to_string(),
+                _ => unreachable!(),
+            })
+            .filter(|key| !documented.contains(&key.as_str()))
+            .collect();
+        assert!(missing.is_empty(), "keys run_app handles that the help doesn't show: {:?}", missing);
+    }
+}
@@ -0,0 +1,84 @@
+//! yarmtl, yet another rust markdown todo list: tasks.md and everything around it. The
+//! `yarmtl` command line and its TUI are built on this crate.
+//!
+//! [`Task`] is one line of tasks.md. [`TaskStore`] reads the file and saves it, merging with
+//! whatever else changed it meanwhile.
+//! [`SyncEngine`] syncs it with todoist, caldav, google tasks or github issues,
+//! [`notifications`], [`push`] and [`reminder_email`] remind of it, and [`server`] serves
+//! it over http.
+
+pub mod archive;
+pub mod attachments;
+pub mod backup;
+pub mod caldav_client;
+pub mod caldav_sync;
+pub mod calendar;
+pub mod clock;
+pub mod config;
+pub mod csv;
+pub mod daemon;
+pub mod dates;
+pub mod doctor;
+pub mod document;
+pub mod effort;
+pub mod email;
+pub mod email_actions;
+pub mod email_auth;
+pub mod encryption;
+pub mod error;
+pub mod exit_code;
+pub mod git;
+pub mod github_client;
+pub mod github_sync;
+pub mod google_auth;
+pub mod google_client;
+pub mod google_sync;
+pub mod hash;
+pub mod history;
+pub mod hooks;
+pub mod ids;
+pub mod index;
+pub mod journal;
+pub mod keymap;
+pub mod links;
+pub mod logging;
+pub mod mcp;
+pub mod notifications;
+pub mod oauth;
+pub mod output;
+pub mod paths;
+pub mod push;
+pub mod quiet;
+pub mod recurrence;
+pub mod reminder_email;
+pub mod report;
+pub mod rules;
+pub mod server;
+pub mod service;
+pub mod setup;
+pub mod site;
+pub mod stats;
+pub mod status_line;
+pub mod store;
+pub mod subtasks;
+pub mod sync_engine;
+pub mod sync_metadata;
+pub mod task;
+pub mod task_table;
+pub mod taskwarrior;
+pub mod telegram;
+pub mod theme;
+pub mod todoist_auth;
+pub mod todoist_client;
+pub mod todoist_sync;
+pub mod todoist_types;
+pub mod trash;
+pub mod watch;
+pub mod webhooks;
+
+pub use clock::{Clock, SystemClock};
+pub use config::{Config, load_config};
+pub use error::YarmtlError;
+pub use store::TaskStore;
+pub use sync_engine::SyncEngine;
+pub use task::Task;
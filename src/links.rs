@@ -0,0 +1,127 @@
+use crate::error::YarmtlError;
+use crate::ids;
+use crate::paths::get_sync_dir;
+use crate::task::Task;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Pattern for a reference to another task, `[[id:3fa1]]`. Any unique prefix of the id works.
+pub const LINK_PATTERN: &str = r"\[\[id:([0-9a-z-]+)\]\]";
+
+/// `LINK_PATTERN`, compiled once.
+pub static LINK_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(LINK_PATTERN).unwrap());
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    Found(usize),
+    Missing,
+    /// The prefix matches this many tasks
+    Ambiguous(usize),
+}
+
+/// The referenced id prefixes in a piece of text, in order of appearance.
+pub fn extract_links(text: &str) -> Vec<String> {
+    LINK_RE.captures_iter(text).map(|c| c[1].to_string()).collect()
+}
+
+/// References from both the task text and its notes.
+pub fn task_links(task: &Task) -> Vec<String> {
+    let mut links = extract_links(&task.text);
+    if let Some(notes) = &task.notes {
+        links.extend(extract_links(notes));
+    }
+    links
+}
+
+/// The task with the id, or the only one whose id starts with it.
+pub fn resolve(tasks: &[Task], reference: &str) -> LinkTarget {
+    if let Some(index) = tasks.iter().position(|t| t.id == reference) {
+        return LinkTarget::Found(index);
+    }
+    let mut matches = tasks.iter().enumerate().filter(|(_, t)| t.id.starts_with(reference));
+    match (matches.next(), matches.count()) {
+        (None, _) => LinkTarget::Missing,
+        (Some((index, _)), 0) => LinkTarget::Found(index),
+        (Some(_), others) => LinkTarget::Ambiguous(others + 1),
+    }
+}
+
+/// Indices of the tasks that link to `index`.
+pub fn backlinks(tasks: &[Task], index: usize) -> Vec<usize> {
+    tasks
+        .iter()
+        .enumerate()
+        .filter(|(i, task)| {
+            *i != index
+                && task_links(task)
+                    .iter()
+                    .any(|reference| resolve(tasks, reference) == LinkTarget::Found(index))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Every reference that doesn't point at exactly one task, as (task index, reference, target).
+pub fn broken_links(tasks: &[Task]) -> Vec<(usize, String, LinkTarget)> {
+    let mut broken = Vec::new();
+    for (index, task) in tasks.iter().enumerate() {
+        for reference in task_links(task) {
+            let target = resolve(tasks, &reference);
+            if !matches!(target, LinkTarget::Found(_)) {
+                broken.push((index, reference, target));
+            }
+        }
+    }
+    broken
+}
+
+/// The task `reference` points at: by its id, a unique prefix of it, or an id it had
+/// before it got a short one. The error says why there is no such task.
+pub fn locate_task(tasks: &[Task], reference: &str) -> Result<usize, YarmtlError> {
+    let target = match resolve(tasks, reference) {
+        LinkTarget::Missing => match ids::IdMap::load(&get_sync_dir()).lookup(reference) {
+            Some(new_id) => resolve(tasks, new_id),
+            None => LinkTarget::Missing,
+        },
+        target => target,
+    };
+    match target {
+        LinkTarget::Found(index) => Ok(index),
+        LinkTarget::Missing => Err(YarmtlError::NoTask(reference.to_string())),
+        LinkTarget::Ambiguous(count) => Err(YarmtlError::AmbiguousId { prefix: reference.to_string(), count }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tasks() -> Vec<Task> {
+        vec![
+            Task::parse("write report [id:3fa1aaaa]"),
+            Task::parse("send [[id:3fa1]] to boss [id:bbbb1111] //see also [[id:ccc]]"),
+            Task::parse("unrelated [id:3fa2cccc] [[id:3fa]]"),
+        ]
+    }
+
+    #[test]
+    fn test_links_do_not_replace_task_id() {
+        let task = &tasks()[1];
+        assert_eq!(task.id, "bbbb1111");
+        assert_eq!(task.text, "send [[id:3fa1]] to boss");
+        assert_eq!(task_links(task), vec!["3fa1", "ccc"]);
+    }
+
+    #[test]
+    fn test_resolve_backlinks_and_broken() {
+        let tasks = tasks();
+        assert_eq!(resolve(&tasks, "3fa1"), LinkTarget::Found(0));
+        assert_eq!(resolve(&tasks, "3fa"), LinkTarget::Ambiguous(2));
+        assert_eq!(backlinks(&tasks, 0), vec![1]);
+
+        let broken = broken_links(&tasks);
+        assert_eq!(broken.len(), 2);
+        assert_eq!(broken[0], (1, "ccc".to_string(), LinkTarget::Missing));
+        assert_eq!(broken[1], (2, "3fa".to_string(), LinkTarget::Ambiguous(2)));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::daemon;
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+use tracing::field::{Field, Visit};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+/// Lines kept for the log pane of the TUI.
+const KEEP_LINES: usize = 500;
+
+/// From the quietest: what `--verbose` adds to, one level per -v.
+const LEVELS: [Level; 5] = [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG, Level::TRACE];
+
+/// Where what is logged goes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sink {
+    /// stderr, for the command line: warnings and errors, more with `--verbose`
+    Stderr,
+    /// Kept for the log pane while the TUI has the screen, where stderr would break it
+    Buffer,
+    /// Appended to a file rotated like daemon.log: what the daemon did, with the time
+    File(PathBuf),
+}
+
+impl Sink {
+    /// The least important level logged without `--verbose`.
+    fn level(&self) -> usize {
+        match self {
+            Sink::Stderr => 1,
+            Sink::Buffer | Sink::File(_) => 2,
+        }
+    }
+}
+
+/// One thing logged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    pub at: DateTime<Local>,
+    pub level: Level,
+    /// The module it came from: yarmtl::git
+    pub target: String,
+    pub message: String,
+}
+
+impl LogLine {
+    /// The line as the command line shows it: "Warning: couldn't push", like before there
+    /// was a log.
+    pub fn text(&self) -> String {
+        match self.level {
+            Level::ERROR => format!("Error: {}", self.message),
+            Level::WARN => format!("Warning: {}", self.message),
+            Level::INFO => self.message.clone(),
+            level => format!("{} {}: {}", level.as_str().to_lowercase(), self.target, self.message),
+        }
+    }
+}
+
+static SINK: Mutex<Sink> = Mutex::new(Sink::Stderr);
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+static BUFFER: Mutex<VecDeque<LogLine>> = Mutex::new(VecDeque::new());
+/// Warnings and errors of the buffer the TUI hasn't shown yet
+static ALERTS: Mutex<Vec<LogLine>> = Mutex::new(Vec::new());
+
+/// Starts logging to stderr, `verbosity` being how many times `--verbose` was given. Only
+/// the first call counts.
+pub fn init(verbosity: u8) {
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+    let _ = tracing::subscriber::set_global_default(tracing_subscriber::registry().with(Log));
+}
+
+/// Sends what is logged from now on to `sink`.
+pub fn set_sink(sink: Sink) {
+    *SINK.lock().unwrap_or_else(|e| e.into_inner()) = sink;
+}
+
+/// The lines of the buffer, the oldest first.
+pub fn recent() -> Vec<LogLine> {
+    BUFFER.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+}
+
+/// The warnings and errors logged to the buffer since the last call, for toasts.
+pub fn take_alerts() -> Vec<LogLine> {
+    std::mem::take(&mut *ALERTS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Whether `metadata` is logged to `sink`. Other crates only get their warnings through
+/// before -vvv, their debug output would drown yarmtl's.
+fn wanted(sink: &Sink, metadata: &Metadata) -> bool {
+    let verbosity = VERBOSITY.load(Ordering::Relaxed) as usize;
+    let level = match metadata.target().starts_with("yarmtl") {
+        true => sink.level() + verbosity,
+        false if verbosity >= 3 => sink.level() + verbosity,
+        false => sink.level().min(1),
+    };
+    *metadata.level() <= LEVELS[level.min(LEVELS.len() - 1)]
+}
+
+/// The layer putting events where the sink says.
+struct Log;
+
+impl<S: Subscriber> Layer<S> for Log {
+    // The sink changes while yarmtl runs, so nothing is decided once for a call site
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        wanted(&SINK.lock().unwrap_or_else(|e| e.into_inner()), metadata)
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = Message::default();
+        event.record(&mut message);
+        let metadata = event.metadata();
+        let line = LogLine {
+            at: Local::now(),
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: message.text + &message.fields,
+        };
+
+        let sink = SINK.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        match sink {
+            Sink::Stderr => eprintln!("{}", line.text()),
+            Sink::Buffer => {
+                if line.level <= Level::WARN {
+                    ALERTS.lock().unwrap_or_else(|e| e.into_inner()).push(line.clone());
+                }
+                let mut buffer = BUFFER.lock().unwrap_or_else(|e| e.into_inner());
+                if buffer.len() == KEEP_LINES {
+                    buffer.pop_front();
+                }
+                buffer.push_back(line);
+            }
+            Sink::File(path) => {
+                let text = match line.level {
+                    Level::INFO => line.message.clone(),
+                    _ => line.text(),
+                };
+                let written = format!("{} {}", line.at.format("%Y-%m-%d %H:%M:%S"), text);
+                if let Err(e) = daemon::append_log(&path, &written) {
+                    eprintln!("{} (couldn't write {}: {})", written, path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+/// The message of an event, and its other fields after it as ` key=value`.
+#[derive(Default)]
+struct Message {
+    text: String,
+    fields: String,
+}
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.text = format!("{:?}", value),
+            name => {
+                let _ = write!(self.fields, " {}={:?}", name, value);
+            }
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.text = value.to_string(),
+            name => {
+                let _ = write!(self.fields, " {}={}", name, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffered_while_the_tui_has_the_screen() {
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(Log), || {
+            set_sink(Sink::Buffer);
+            tracing::debug!("not without --verbose");
+            tracing::info!(job = "reminders", "sent");
+            tracing::warn!("couldn't push to {}", "ntfy");
+            set_sink(Sink::Stderr);
+        });
+        let lines = recent();
+        let texts: Vec<String> = lines.iter().map(LogLine::text).collect();
+        assert_eq!(texts, ["sent job=reminders", "Warning: couldn't push to ntfy"]);
+        assert_eq!(lines[1].target, "yarmtl::logging::tests");
+        assert_eq!(take_alerts(), lines[1..]);
+        assert!(take_alerts().is_empty());
+    }
+}
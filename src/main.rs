@@ -2,288 +2,1791 @@
 // clap = { version = "4.0", features = ["derive"] }
 
 mod tui;
-mod todoist_types;
-mod todoist_auth;
-mod todoist_client;
-mod sync_metadata;
-mod todoist_sync;
 
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
-use std::env;
-use chrono::{Local, NaiveDate, Datelike};
-use regex::Regex;
-use chrono_english::{parse_date_string, Dialect};
-use serde::{Deserialize, Serialize};
-use lettre::{Message, SmtpTransport, Transport};
-use lettre::transport::smtp::authentication::Credentials;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::Datelike;
 use tokio_cron_scheduler::{JobScheduler, Job};
-use std::sync::OnceLock;
-use uuid::Uuid;
+use std::sync::Arc;
+use yarmtl::{
+    attachments, backup, caldav_client, caldav_sync, csv, daemon, dates, doctor, document, effort, email_actions,
+    email_auth, encryption, exit_code, github_client, github_sync, google_auth, google_client, google_sync, history, hooks, ids, index, logging,
+    mcp, notifications, output, push, recurrence, reminder_email, report, rules, server, service, site, stats, status_line,
+    sync_engine, sync_metadata, task, taskwarrior, telegram, todoist_auth, todoist_sync, todoist_types,
+    webhooks,
+};
+use yarmtl::clock::{self, Clock, SystemClock};
+use yarmtl::config::load_config;
+use yarmtl::email::{EmailConfig, load_email_config, mail};
+use yarmtl::error::YarmtlError;
+use yarmtl::git::{self, PullOutcome, git_commit_tasks_with_message};
+use yarmtl::journal::{After, Journal};
+use yarmtl::links::locate_task;
+use yarmtl::paths::{
+    get_config_path, get_email_config_path, get_state_dir, get_sync_dir, get_tasks_file_path, get_todoist_config_path, get_working_dir,
+    migrate_state, set_working_dir, sync_config_path,
+};
+use yarmtl::store::TaskStore;
+use yarmtl::sync_engine::{SyncBackend, after_sync, wait_for_sync_lock};
+use yarmtl::task::Task;
+use yarmtl::watch::FileWatcher;
+use yarmtl::todoist_sync::{
+    TodoistConfig, load_todoist_config, request_sync_after_save, run_todoist_sync, sync_after_save, trigger_todoist_sync,
+};
+
+/// println! and eprintln! through `output`, for `--quiet` to leave out the decoration.
+macro_rules! say {
+    () => { yarmtl::output::say("") };
+    ($($arg:tt)*) => { yarmtl::output::say(&format!($($arg)*)) };
+}
+
+macro_rules! say_err {
+    () => { yarmtl::output::say_err("") };
+    ($($arg:tt)*) => { yarmtl::output::say_err(&format!($($arg)*)) };
+}
 
-// Global state for working directory
-static WORKING_DIR: OnceLock<PathBuf> = OnceLock::new();
+#[derive(Parser)]
+#[command(name = "yarmtl")]
+#[command(author, version, about = "yet another rust markdown todo list", long_about = None)]
+#[command(after_help = "Exit codes: 0 done, 1 error, 2 overdue tasks and 3 tasks due today (yarmtl check), 64 wrong usage")]
+struct Cli {
+    /// task text to add (if empty, launch tui)
+    task: Option<String>,
+    
+    /// list all tasks
+    #[arg(short, long)]
+    list: bool,
+    
+    /// show completed tasks too
+    #[arg(short, long)]
+    done: bool,
+    
+    /// send email reminders for overdue/due tasks
+    #[arg(short, long)]
+    email: bool,
+    
+    /// email the summary of the past week right away
+    #[arg(long)]
+    weekly_summary: bool,
+
+    /// setup email configuration
+    #[arg(long)]
+    setup_email: bool,
+
+    /// setup todoist api integration
+    #[arg(long)]
+    setup_todoist: bool,
+
+    /// sign in to google tasks and pick the task list to sync with
+    #[arg(long)]
+    setup_google: bool,
+
+    /// store a github token and pick the repositories whose assigned issues become tasks
+    #[arg(long)]
+    setup_github: bool,
+
+    /// show whether todoist sync is set up, how many tasks are paired and when it last ran
+    #[arg(long)]
+    sync_status: bool,
+
+    /// run as daemon, sending emails at 5 AM daily, raising desktop notifications and syncing with todoist on an interval
+    #[arg(long)]
+    daemon: bool,
+    
+    /// path to directory containing tasks.md (creates if doesn't exist)
+    #[arg(short, long, value_name = "DIR")]
+    path: Option<String>,
+
+    /// don't commit, pull, push or make a git repository, whatever config.toml says
+    #[arg(long, global = true, env = "YARMTL_NO_GIT", value_parser = clap::builder::BoolishValueParser::new())]
+    no_git: bool,
+
+    /// log more: -v for what yarmtl does, -vv for debug output, -vvv for the libraries too
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// print without emoji, symbols and blank lines, for scripts and cron jobs
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// print [!], [*], due: and tag: instead of emoji, here and in the TUI and emails
+    #[arg(long, global = true)]
+    ascii: bool,
+
+    /// color the listed tasks: auto (on a terminal, unless NO_COLOR is set), always or never
+    #[arg(long, global = true, value_enum, default_value_t = output::ColorChoice::Auto)]
+    color: output::ColorChoice,
+
+    /// only list tasks under this `## ` heading of tasks.md; with a task text, add it there
+    #[arg(long, value_name = "NAME")]
+    project: Option<String>,
+
+    /// only list tasks with this context, like `home` for >home
+    #[arg(long, value_name = "NAME")]
+    context: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
 
-fn set_working_dir(path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    let work_dir = if let Some(p) = path {
-        let pb = PathBuf::from(p);
-        if !pb.exists() {
-            fs::create_dir_all(&pb)?;
-            println!("📁 Created directory: {}", pb.display());
+#[derive(Subcommand)]
+enum Commands {
+    /// add a task, optionally under a `## ` project heading
+    Add {
+        /// task text, with the usual markers
+        text: String,
+
+        /// project to add the task to, the section is created when missing
+        #[arg(long, value_name = "NAME")]
+        project: Option<String>,
+    },
+
+    /// add a task from a window manager hotkey: quiet, quick on any tasks.md, the commit left to the background
+    Capture {
+        /// task text, with the usual markers; one line is read from stdin when there's none
+        words: Vec<String>,
+
+        /// commit the tasks capture added, and ask for the sync and webhooks
+        #[arg(long, hide = true)]
+        after_capture: bool,
+    },
+
+    /// serve a local http api: the tasks under /api with a token, a home assistant sensor at /ha/sensor
+    Serve {
+        /// address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: String,
+
+        /// port to listen on
+        #[arg(long, default_value_t = 8765)]
+        port: u16,
+
+        /// replace the api token with a new one, which is printed
+        #[arg(long)]
+        new_token: bool,
+    },
+
+    /// copy a file into the task's attachments folder
+    Attach {
+        /// task id, or any unique prefix of it
+        id: String,
+
+        /// file to attach
+        file: PathBuf,
+    },
+
+    /// open the first attachment or link of a task with the default application
+    Open {
+        /// task id, or any unique prefix of it
+        id: String,
+    },
+
+    /// move a task's [count:3/10] counter, checking the task off when it reaches the target
+    Bump {
+        /// task id, or any unique prefix of it
+        id: String,
+
+        /// steps to count, negative to count back
+        #[arg(long, default_value_t = 1, allow_hyphen_values = true)]
+        by: i32,
+    },
+
+    /// write every task as json like `task export` of taskwarrior does, as csv for spreadsheets, or as a static html site
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Taskwarrior)]
+        format: ExportFormat,
+
+        /// write to a file instead of stdout; the directory of the site for html
+        #[arg(short, long, visible_alias = "out", value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// add or update tasks from json like `task export` writes, or from csv with a header row
+    Import {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Taskwarrior)]
+        format: ExportFormat,
+
+        /// file to read, stdin when left out
+        file: Option<PathBuf>,
+
+        /// which csv column is which field when their names differ, like "Task=text,Due=deadline"
+        #[arg(long, value_name = "COLUMN=FIELD,...")]
+        map: Option<String>,
+    },
+
+    /// taskwarrior hook that copies the tasks added or changed there into tasks.md
+    Hook {
+        event: HookEvent,
+    },
+
+    /// answer model context protocol requests on stdin, so an llm assistant can list, search, add and complete tasks
+    Mcp,
+
+    /// sync with todoist (or another --backend) now and print what changed, even when auto-sync is off
+    Sync {
+        /// only print what the sync would do
+        #[arg(long)]
+        dry_run: bool,
+
+        /// what to sync with
+        #[arg(long, value_enum, default_value = "todoist")]
+        backend: SyncBackend,
+
+        /// ask before each change, on either side
+        #[arg(long, short, conflicts_with = "dry_run")]
+        interactive: bool,
+
+        /// sync once the saves stop, unless a later save asked for a sync too
+        #[arg(long, hide = true, conflicts_with_all = ["dry_run", "interactive"])]
+        after_save: Option<String>,
+    },
+
+    /// exit with 2 when open tasks are overdue, 3 when some are due today, and 0 when neither, for scripts
+    Check,
+
+    /// print how many tasks are overdue and due today, in milliseconds from the index, for a shell prompt or tmux
+    Prompt {
+        /// what to print instead, with {overdue} and {today} in it, like "{overdue}!{today}"; printed even when both are 0
+        #[arg(long)]
+        format: Option<String>,
+    },
+
+    /// check tasks.md, git, email, Todoist, the keyring and the daemon for problems, and say how to fix them
+    Doctor {
+        /// send an email to the addresses of email_config.toml to see that they get it
+        #[arg(long)]
+        send_test_email: bool,
+    },
+
+    /// post the [[webhooks]] for tasks created, completed or past their deadline since the last run
+    Webhooks {
+        /// send the deliveries from webhooks_failed.jsonl again instead
+        #[arg(long)]
+        retry_failed: bool,
+    },
+
+    /// publish the day's reminders to the [[push]] channels (ntfy, pushover, gotify) taking the digest
+    Push,
+
+    /// answer the bot of the telegram [[push]] channel, adding the tasks its chat sends
+    Telegram,
+
+    /// raise desktop notifications for reminders and due or overdue tasks not told about yet
+    Notify {
+        /// keep running until the notifications are closed, so their buttons work
+        #[arg(long)]
+        wait: bool,
+    },
+
+    /// apply the escalation [[rules]] from config.toml and commit what they changed
+    Triage,
+
+    /// fetch the tasks from the git remote and merge them with the ones here, then push the merge
+    Pull {
+        /// push what was just committed, pulling first if the remote has commits this doesn't
+        #[arg(long, hide = true)]
+        after_commit: bool,
+    },
+
+    /// list the recent commits of tasks.md and what they changed, or what happened to one task
+    History {
+        /// task id, or any unique prefix of it, to show the timeline of
+        id: Option<String>,
+
+        /// how many commits to list
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// list the tasks with words starting with the given ones, from the sqlite index when [index] is enabled
+    Search {
+        /// words to look for in the text, notes, project, tags and contexts
+        words: Vec<String>,
+
+        /// only tasks with this tag
+        #[arg(long, value_name = "NAME")]
+        tag: Option<String>,
+
+        /// only tasks under this `## ` heading of tasks.md
+        #[arg(long, value_name = "NAME")]
+        project: Option<String>,
+
+        /// only tasks due by this day, like 2025-07-01 or friday
+        #[arg(long, value_name = "DATE")]
+        due: Option<String>,
+
+        /// completed and cancelled tasks too
+        #[arg(short, long)]
+        done: bool,
+    },
+
+    /// bring the sqlite search index up to date with tasks.md and show what is in it
+    Index {
+        /// forget the index and build it again from tasks.md
+        #[arg(long)]
+        rebuild: bool,
+    },
+
+    /// write a tarball of the tasks, archive, trash, config (without passwords and tokens) and sync metadata
+    Backup {
+        /// where to write it, the backups folder in ~/.local/share/yarmtl when left out
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// put the files of a backup back, backing up the ones there are first; lists the backups when no file is given
+    Restore {
+        /// the backup, a path or the name of one in the backups folder
+        file: Option<PathBuf>,
+    },
+
+    /// finish or take back a sync or restore that stopped halfway; asks which unless told
+    Recover {
+        /// write what it was about to write
+        #[arg(long, conflicts_with = "back")]
+        forward: bool,
+        /// put the files back as they were before it
+        #[arg(long)]
+        back: bool,
+    },
+
+    /// encrypt tasks.md, archive.md and the trash with age, making a key in the keyring if there is none
+    Encrypt,
+
+    /// decrypt the encrypted tasks.md, archive.md and trash back into plain markdown
+    Decrypt,
+
+    /// generate reports and reviews from the git history of tasks.md
+    Report {
+        #[command(subcommand)]
+        kind: ReportKind,
+    },
+
+    /// start the daemon, or see how it is doing or stop it
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum DaemonAction {
+    /// run the daemon in the foreground, like --daemon
+    Start,
+
+    /// whether the daemon runs, and when each of its jobs last ran and how it went
+    Status,
+
+    /// stop the running daemon, letting its jobs finish
+    Stop,
+
+    /// start the daemon at login from now on: a systemd user unit, a launchd agent on macOS, a Scheduled Task on windows
+    Install {
+        /// only print what would be installed
+        #[arg(long)]
+        print: bool,
+    },
+
+    /// stop the daemon and take out what `install` put in
+    Uninstall,
+}
+
+#[derive(Subcommand)]
+enum ReportKind {
+    /// year-in-review: completions, busiest weeks, slipped deadlines, tags and streaks
+    Year {
+        /// year to review
+        year: i32,
+
+        /// output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+
+        /// write the report to a file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// weekly review: completed, added, still overdue and carried-over tasks, and each tag's share
+    Week {
+        /// any day of the week to review, like 2025-06-02 or "last monday"; this week by default
+        #[arg(long, value_name = "DATE")]
+        of: Option<String>,
+
+        /// output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+
+        /// write the report to a file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// monthly review, like `week` for a month
+    Month {
+        /// any day of the month to review; this month by default
+        #[arg(long, value_name = "DATE")]
+        of: Option<String>,
+
+        /// output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        format: ReportFormat,
+
+        /// write the report to a file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// estimates (~2h) against the pomodoro time spent on each task
+    Estimates {
+        /// write the report to a file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Taskwarrior,
+    /// id, text, status, deadline, reminder, tags, importance, notes, created, completed
+    Csv,
+    /// a page of every task, and one per project and tag, to publish (export only)
+    Html,
+}
+
+/// The taskwarrior hooks `yarmtl hook` can be, like ~/.task/hooks/on-add.yarmtl
+#[derive(Clone, Copy, ValueEnum)]
+enum HookEvent {
+    OnAdd,
+    OnModify,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+fn main() {
+    // Before the runtime starts its threads, as it sets TZ for them to read
+    if let Some(zone) = load_config().time.zone
+        && let Err(e) = clock::set_zone(&zone) {
+        say_err!("⚠️  {}, [time] in {} is left out", e, get_config_path().display());
+    }
+    run();
+}
+
+#[tokio::main]
+async fn run() {
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        std::process::exit(if e.use_stderr() { exit_code::USAGE } else { exit_code::OK })
+    });
+    logging::init(cli.verbose);
+    output::set_quiet(cli.quiet);
+    output::set_color(cli.color);
+    
+    // Set up working directory first
+    if let Err(e) = set_working_dir(cli.path.as_deref()) {
+        say_err!("Error setting up working directory: {}", e);
+        std::process::exit(exit_code::ERROR);
+    }
+    let config = load_config();
+    output::set_ascii(cli.ascii || config.ui.ascii);
+    
+    // Export, the taskwarrior hook, mcp and the prompt write json, csv or the line of the prompt
+    // to stdout, nothing else may go there; capture runs from a hotkey with nobody to read it
+    let json_out = matches!(
+        cli.command,
+        Some(
+            Commands::Export { output: None, .. }
+                | Commands::Hook { .. }
+                | Commands::Mcp
+                | Commands::Prompt { .. }
+                | Commands::Capture { .. }
+        )
+    );
+    if cli.path.is_some() && !json_out && !cli.quiet {
+        say!("📂 Working directory: {}", get_working_dir().display());
+    }
+    // Older versions kept what only this machine needs in the tasks directory
+    for (from, to) in migrate_state() {
+        if !json_out && !cli.quiet {
+            say!("📦 Moved {} to {}", from.display(), to.display());
         }
-        if !pb.is_dir() {
-            return Err(format!("Path {} is not a directory", pb.display()).into());
+    }
+    if cli.no_git {
+        git::disable();
+    }
+    dates::set_dialect(config.tasks.date_dialect);
+    dates::set_display(config.dates);
+    task::set_format(config.tasks.format);
+    task::set_warn_days(config.tasks.warn_days);
+    let clock = SystemClock;
+
+    // A sync or restore that stopped halfway may have left the files out of step. While a
+    // sync holds its lock, the journal is its own
+    if !json_out
+        && !matches!(cli.command, Some(Commands::Recover { .. }))
+        && let Some(journal) = Journal::pending(&get_sync_dir())
+        && let Ok(Some(_sync)) = sync_engine::try_lock_sync(&get_sync_dir())
+    {
+        use std::io::IsTerminal;
+        if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+            describe_unfinished(&journal);
+            finish_recovery(journal, ask_recovery());
+        } else {
+            say_err!("⚠️  {}", YarmtlError::Unfinished { operation: journal.operation().to_string(), started: journal.started() });
+        }
+    }
+    
+    if let Some(command) = cli.command {
+        match command {
+            Commands::Serve { bind, port, new_token } => {
+                if let Err(e) = server::run_server(&bind, port, new_token).await {
+                    say_err!("Server failed: {}", e);
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Add { text, project } => {
+                say!("adding task: {}", text);
+                if let Err(e) = add_task(&text, project.as_deref(), &clock) {
+                    say_err!("❌ {}", e);
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Capture { after_capture: true, .. } => commit_captured_tasks(),
+            Commands::Capture { words, after_capture: false } => {
+                if !capture_task(&words, &clock) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Attach { id, file } => {
+                if !attach_to_task(&id, &file) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Open { id } => {
+                if !open_task_attachment(&id) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Bump { id, by } => {
+                if !bump_task(&id, by, &clock) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Report { kind: ReportKind::Year { year, format, output } } => {
+                if !year_report(year, format, output.as_deref()) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Report { kind: ReportKind::Week { of, format, output } } => {
+                if !review_report(report::Period::Week, of.as_deref(), format, output.as_deref(), &clock) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Report { kind: ReportKind::Month { of, format, output } } => {
+                if !review_report(report::Period::Month, of.as_deref(), format, output.as_deref(), &clock) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Report { kind: ReportKind::Estimates { output } } => {
+                let Some(store) = open_store() else { std::process::exit(exit_code::ERROR) };
+                let rendered = effort::render_estimate_report(store.tasks());
+                match output {
+                    Some(path) => match fs::write(&path, rendered) {
+                        Ok(()) => say!("📊 Wrote estimates report to {}", path.display()),
+                        Err(e) => {
+                            say_err!("Failed to write report: {}", e);
+                            std::process::exit(exit_code::ERROR);
+                        }
+                    },
+                    None => print!("{}", rendered),
+                }
+            }
+            Commands::Triage => {
+                if !triage_tasks(&clock) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::History { id, limit } => {
+                if !print_history(id.as_deref(), limit) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Search { words, tag, project, due, done } => {
+                if !search_tasks(words.join(" "), tag, project, due.as_deref(), done, &clock) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Index { rebuild } => {
+                if !update_index(rebuild, &clock) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Backup { output } => {
+                if !backup_tasks(output) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Restore { file } => {
+                if !restore_tasks(file.as_deref()) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Recover { forward, back } => {
+                if !recover_tasks(forward, back) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Encrypt => {
+                if !encrypt_tasks() {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Decrypt => {
+                if !decrypt_tasks() {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Pull { after_commit: true } => {
+                if let Err(e) = git::push_after_commit(&get_sync_dir()) {
+                    say_err!("❌ {}", e);
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Pull { after_commit: false } => {
+                if !pull_tasks() {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Export { format, output } => {
+                if !export_tasks(format, output.as_deref(), &clock) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Import { format, file, map } => {
+                if !import_tasks(format, file.as_deref(), map.as_deref(), &clock) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Hook { event } => taskwarrior_hook(event, &clock),
+            Commands::Mcp => {
+                if let Err(e) = mcp::run() {
+                    say_err!("MCP server failed: {}", e);
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Sync { after_save: Some(request), .. } => sync_after_save(&request).await,
+            Commands::Sync { dry_run, interactive, backend, after_save: None } => {
+                let synced = match backend {
+                    SyncBackend::Todoist => sync_todoist_now(dry_run, interactive).await,
+                    SyncBackend::Caldav => sync_caldav_now(dry_run, interactive).await,
+                    SyncBackend::Google => sync_google_now(dry_run, interactive).await,
+                    SyncBackend::Github => sync_github_now(dry_run, interactive).await,
+                };
+                if !synced {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Check => std::process::exit(check_due(&clock)),
+            Commands::Prompt { format } => {
+                if !print_prompt(format.as_deref(), &clock) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Doctor { send_test_email } => {
+                if !doctor::run_doctor(send_test_email).await {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Webhooks { retry_failed } => {
+                if !send_webhooks(retry_failed, &clock).await {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Push => {
+                if let Err(e) = send_reminders(&clock, false, true).await {
+                    say_err!("Failed to push reminders: {}", e);
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Daemon { action: DaemonAction::Start } => {
+                if let Err(e) = run_daemon().await {
+                    say_err!("Daemon failed: {}", e);
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Daemon { action: DaemonAction::Status } => {
+                if !print_daemon_status() {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Daemon { action: DaemonAction::Stop } => {
+                if !stop_daemon().await {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Daemon { action: DaemonAction::Install { print } } => {
+                if !install_service(print) {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Daemon { action: DaemonAction::Uninstall } => match service::uninstall(service::ServiceKind::current()) {
+                Ok(removed) => say!("✓ Removed {}", removed),
+                Err(e) => {
+                    say_err!("Couldn't uninstall the daemon: {}", e);
+                    std::process::exit(exit_code::ERROR);
+                }
+            },
+            Commands::Telegram => {
+                if !run_telegram_bot().await {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+            Commands::Notify { wait } => {
+                if !notify_desktop(wait, &clock).await {
+                    std::process::exit(exit_code::ERROR);
+                }
+            }
+        }
+        return;
+    }
+
+    if cli.setup_email {
+        setup_email_config().await;
+        return;
+    }
+
+    if cli.sync_status {
+        print_todoist_sync_status().await;
+        return;
+    }
+
+    if cli.setup_todoist {
+        setup_todoist_config().await;
+        return;
+    }
+
+    if cli.setup_google {
+        setup_google_tasks().await;
+        return;
+    }
+
+    if cli.setup_github {
+        setup_github_issues().await;
+        return;
+    }
+
+    if cli.daemon {
+        if let Err(e) = run_daemon().await {
+            say_err!("Daemon failed: {}", e);
+            std::process::exit(exit_code::ERROR);
+        }
+        return;
+    }
+    
+    if cli.email {
+        if let Err(e) = send_reminders(&SystemClock, true, false).await {
+            say_err!("Failed to send email reminders: {}", e);
+            std::process::exit(exit_code::ERROR);
+        }
+        return;
+    }
+
+    if cli.weekly_summary {
+        if let Err(e) = send_weekly_summary(&SystemClock).await {
+            say_err!("Failed to send the weekly summary: {}", e);
+            std::process::exit(exit_code::ERROR);
+        }
+        return;
+    }
+    
+    match cli.task {
+        Some(text) => {
+            say!("adding task: {}", text);
+            if let Err(e) = add_task(&text, cli.project.as_deref(), &clock) {
+                say_err!("❌ {}", e);
+                std::process::exit(exit_code::ERROR);
+            }
+        }
+        None => {
+            if cli.list {
+                if let Err(e) = list_tasks(cli.done, cli.project.as_deref(), cli.context.as_deref(), &clock) {
+                    say_err!("❌ {}", e);
+                    std::process::exit(exit_code::ERROR);
+                }
+            } else {
+                say!("🚀 Launching YARMTL TUI...");
+                if let Err(e) = tui::run_tui(&get_sync_dir()) {
+                    say_err!("TUI failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+pub fn add_task(text: &str, project: Option<&str>, clock: &dyn Clock) -> Result<(), YarmtlError> {
+    let mut store = TaskStore::open_default()?;
+
+    // Parse the task as a regular task
+    let mut task = Task::parse_at(text, clock);
+    task.created = Some(clock.today());
+    task.project = project.map(str::to_string);
+    let task = match hooks::run_task_hook(&get_sync_dir(), hooks::HookPoint::Add, &task) {
+        Ok(task) => task,
+        Err(e) => {
+            say_err!("❌ Not added, {}", e);
+            return Ok(());
         }
-        pb.canonicalize()?
-    } else {
-        env::current_dir()?
     };
+    let task = store.add(task)?;
+    
+    // Auto-commit the task addition with descriptive message
+    let commit_message = format!("➕ Added task: \"{}\"", task.text);
     
-    let _ = WORKING_DIR.set(work_dir);
+    match git_commit_tasks_with_message(Some(&commit_message)) {
+        Ok(outcome) => {
+            if outcome.initialized {
+                say!("🔧 Initialized git repository for task versioning in {}", get_sync_dir().display());
+            }
+            if outcome.pushing {
+                say!("🚀 Pushing changes to remote repository in the background");
+            }
+        }
+        Err(e) => tracing::warn!("{}", e),
+    }
+    
+    say!("✓ added task: \"{}\"", task.text);
+    if let Some(deadline) = task.deadline {
+        say!("  📅 deadline: {}", deadline.format("%Y-%m-%d"));
+    }
+    if !task.tags.is_empty() {
+        say!("  🏷️  tags: {}", task.tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" "));
+    }
+    if let Some(reminder) = task.reminder {
+        say!("  🔔 reminder: {}", reminder.format("%Y-%m-%d"));
+    }
+    if let Some(ref notes) = task.notes {
+        say!("  💬 notes: {}", notes);
+    }
+    if let Some(importance) = task.importance {
+        say!("  ⭐ importance: ${}", importance);
+    }
     Ok(())
 }
 
-fn get_working_dir() -> PathBuf {
-    WORKING_DIR.get().cloned().unwrap_or_else(|| env::current_dir().unwrap())
+
+/// `yarmtl capture`: adds the task in `words`, or in the line on stdin, printing nothing.
+/// The tasks in tasks.md aren't read, see `TaskStore::append`, and the commit,
+/// sync and webhooks happen in `yarmtl capture --after-capture` in the background, so the
+/// hotkey is back at once. Add hooks don't run. Nothing to add, like a dmenu closed with
+/// escape, isn't an error.
+fn capture_task(words: &[String], clock: &dyn Clock) -> bool {
+    let mut text = words.join(" ");
+    if words.is_empty()
+        && let Err(e) = std::io::stdin().read_line(&mut text)
+    {
+        say_err!("❌ Couldn't read the task from stdin: {}", e);
+        return false;
+    }
+    let text = text.trim();
+    if text.is_empty() {
+        return true;
+    }
+    let mut task = Task::parse_at(text, clock);
+    task.created = Some(clock.today());
+    let tasks_file = get_tasks_file_path();
+    // The line and what to commit it with go together, see `commit_captured_tasks`
+    let captured = document::lock(&tasks_file).map_err(YarmtlError::io("lock", &tasks_file)).and_then(|_lock| {
+        TaskStore::append_locked(&tasks_file, &task)?;
+        let path = captured_messages_path();
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&path).map_err(YarmtlError::io("write", &path))?;
+        let message = format!("➕ Added task: \"{}\"\n", task.text);
+        std::io::Write::write_all(&mut file, message.as_bytes()).map_err(YarmtlError::io("write", &path))
+    });
+    if let Err(e) = captured {
+        say_err!("❌ {}", e);
+        return false;
+    }
+    let started = std::env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .args((!git::is_enabled()).then_some("--no-git"))
+            .args(["capture", "--after-capture"])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+    });
+    if let Err(e) = started {
+        tracing::warn!("couldn't start committing the captured task: {}", e);
+    }
+    true
 }
 
-fn get_sync_dir() -> PathBuf {
-    PathBuf::from(env::var("HOME").unwrap_or_default())
-        .join(".local/share/yarmtl/yarmtl-tasks")
+/// In the state directory, the commit messages of the tasks `capture` added and that
+/// aren't committed yet, a line each.
+fn captured_messages_path() -> PathBuf {
+    get_state_dir().join("captured.txt")
+}
+
+/// `yarmtl capture --after-capture`: what `add` does after saving, for the tasks `capture`
+/// added. The commit is made under the lock on tasks.md, as the lines and their messages
+/// were written, so it has the lines its messages are about and no others: when captures
+/// come quicker than their commits, the first commit takes them all and the others find
+/// nothing left to commit.
+fn commit_captured_tasks() {
+    let tasks_file = get_tasks_file_path();
+    let committed = document::lock(&tasks_file).map_err(YarmtlError::io("lock", &tasks_file)).and_then(|_lock| {
+        let path = captured_messages_path();
+        let messages: Vec<String> = match fs::read_to_string(&path) {
+            Ok(content) => content.lines().map(str::to_string).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(YarmtlError::io("read", &path)(e)),
+        };
+        git_commit_tasks_with_message(git::combined_message(&messages).as_deref())?;
+        fs::remove_file(&path).map_err(YarmtlError::io("remove", &path))
+    });
+    if let Err(e) = committed {
+        tracing::warn!("{}", e);
+    }
+    request_sync_after_save();
+    webhooks::request_webhooks_after_save();
+}
+
+/// `yarmtl history`. Returns whether there was a history to show.
+fn print_history(reference: Option<&str>, limit: usize) -> bool {
+    let versions = match git::file_history(&get_sync_dir(), "tasks.md") {
+        Ok(versions) => versions,
+        Err(e) => {
+            say_err!("❌ {}", e);
+            return false;
+        }
+    };
+
+    let Some(reference) = reference else {
+        if versions.is_empty() {
+            say!("No commits of tasks.md yet");
+        }
+        for (version, summary) in history::recent_commits(&versions, limit) {
+            say!("{} {}  {}  ({})", version.date.format("%Y-%m-%d"), version.commit, version.summary, summary);
+        }
+        return true;
+    };
+
+    // A task gone from tasks.md can still be looked up by its whole id
+    let found = TaskStore::open_default().ok().and_then(|store| {
+        let index = locate_task(store.tasks(), reference).ok()?;
+        Some(store.tasks()[index].clone())
+    });
+    let id = match found {
+        Some(task) => {
+            say!("📜 {} [id:{}]", task.text, task.id);
+            task.id
+        }
+        None => reference.to_string(),
+    };
+    let timeline = history::task_timeline(&versions, &id);
+    if timeline.is_empty() {
+        say_err!("❌ no task with id {} in the history of tasks.md", id);
+        return false;
+    }
+    for event in timeline {
+        say!("{} {}  {}", event.date.format("%Y-%m-%d"), event.commit, event.change);
+    }
+    true
 }
 
-fn get_tasks_file_path() -> PathBuf {
+/// `yarmtl search`. Returns whether the search could be made.
+fn search_tasks(words: String, tag: Option<String>, project: Option<String>, due: Option<&str>, done: bool, clock: &dyn Clock) -> bool {
+    let today = clock.today();
+    let due_by = match due.map(|phrase| dates::parse(phrase, today, dates::dialect()).ok_or(phrase)).transpose() {
+        Ok(day) => day,
+        Err(phrase) => {
+            say_err!("❌ {} isn't a date", phrase);
+            return false;
+        }
+    };
+    let query = index::Query { words, tag, project, due_by, completed: done };
+    let found = match index::search(&get_tasks_file_path(), &query, load_config().index.enabled) {
+        Ok(found) => found,
+        Err(e) => {
+            say_err!("❌ {}", e);
+            return false;
+        }
+    };
+    for task in &found {
+        print_task(task, task.completed, today);
+    }
+    match found.len() {
+        0 => say!("no tasks found!"),
+        1 => say!("1 task"),
+        count => say!("{} tasks", count),
+    }
+    true
+}
+
+/// `yarmtl index`. Returns whether the index is up to date.
+fn update_index(rebuild: bool, clock: &dyn Clock) -> bool {
+    let tasks_file = get_tasks_file_path();
+    if !index::TaskIndex::can_index(&tasks_file) {
+        say_err!("❌ The tasks are encrypted, the index would keep them in the clear");
+        return false;
+    }
+    let indexed = index::TaskIndex::open_default().and_then(|mut task_index| {
+        let update = match rebuild {
+            true => task_index.rebuild(&tasks_file)?,
+            false => task_index.update(&tasks_file)?,
+        };
+        Ok((update, task_index.stats(clock.today())?))
+    });
+    let (update, stats) = match indexed {
+        Ok(indexed) => indexed,
+        Err(e) => {
+            say_err!("❌ {}", e);
+            return false;
+        }
+    };
+    if update != index::IndexUpdate::default() {
+        say!("📇 {} added, {} changed, {} removed", update.added, update.changed, update.removed);
+    }
+    say!("📇 {} tasks indexed: {} open, {} overdue", stats.tasks, stats.open, stats.overdue);
+    if !stats.tags.is_empty() {
+        let tags: Vec<String> = stats.tags.iter().take(10).map(|(tag, count)| format!("#{} {}", tag, count)).collect();
+        say!("🏷️  {}", tags.join(", "));
+    }
+    if !load_config().index.enabled {
+        say!("`yarmtl search` only uses it with enabled = true in the [index] table of config.toml");
+    }
+    true
+}
+
+/// `yarmtl backup`. Returns whether the backup was written.
+fn backup_tasks(output: Option<PathBuf>) -> bool {
+    let output = output.unwrap_or_else(|| backup::new_backup_path(""));
+    match backup::write_backup(&get_sync_dir(), &get_state_dir(), &get_email_config_path(), &output) {
+        Ok(files) => {
+            say!("💾 Backed up {} files to {}", files.len(), output.display());
+            true
+        }
+        Err(e) => {
+            say_err!("❌ {}", e);
+            false
+        }
+    }
+}
+
+/// `yarmtl restore`. Returns whether the backup was restored, or listed the backups.
+fn restore_tasks(file: Option<&Path>) -> bool {
+    let Some(file) = file else {
+        let backups = backup::list_backups();
+        if backups.is_empty() {
+            say!("No backups in {} yet", backup::backups_dir().display());
+        }
+        for path in backups {
+            say!("{}", path.display());
+        }
+        return true;
+    };
+    let archive = match file.exists() {
+        true => file.to_path_buf(),
+        false => backup::backups_dir().join(file),
+    };
     let sync_dir = get_sync_dir();
-    
-    if !sync_dir.exists() {
-        if let Err(e) = fs::create_dir_all(&sync_dir) {
-            eprintln!("Error: Failed to create sync directory {}: {}", sync_dir.display(), e);
-            eprintln!("Please ensure you have write permissions to {}", sync_dir.parent().unwrap_or(&sync_dir).display());
-            std::process::exit(1);
+    let _sync = match sync_engine::try_lock_sync(&sync_dir) {
+        Ok(Some(lock)) => lock,
+        Ok(None) => {
+            say_err!("❌ A sync is running, restore once it is done");
+            return false;
+        }
+        Err(e) => {
+            say_err!("❌ {}", YarmtlError::io("lock", &sync_dir)(e));
+            return false;
+        }
+    };
+    let restored = backup::automatic("restore").and_then(|before| {
+        say!("💾 Backed up the files there are to {}", before.display());
+        let tasks_file = get_tasks_file_path();
+        let _lock = document::lock(&tasks_file).map_err(YarmtlError::io("lock", &tasks_file))?;
+        backup::restore_backup(&archive, &sync_dir, &get_state_dir(), &get_email_config_path())
+    });
+    let restored = match restored {
+        Ok(restored) => restored,
+        Err(e) => {
+            say_err!("❌ {}", e);
+            return false;
         }
+    };
+    for name in &restored {
+        say!("♻️  Restored {}", name);
     }
-    
-    sync_dir.join("tasks.md")
+    let name = archive.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    if let Err(e) = git_commit_tasks_with_message(Some(&format!("♻️ Restored the tasks from {}", name))) {
+        tracing::warn!("{}", e);
+    }
+    true
+}
+
+/// `yarmtl recover`. Returns whether nothing is left unfinished, or it was left for later.
+fn recover_tasks(forward: bool, back: bool) -> bool {
+    let sync_dir = get_sync_dir();
+    let Some(journal) = Journal::pending(&sync_dir) else {
+        say!("✅ Nothing to recover, everything finished");
+        return true;
+    };
+    let _sync = match sync_engine::try_lock_sync(&sync_dir) {
+        Ok(Some(lock)) => lock,
+        Ok(None) => {
+            say!("🔄 A sync is running, it is the one writing the files");
+            return true;
+        }
+        Err(e) => {
+            say_err!("❌ {}", YarmtlError::io("lock", &sync_dir)(e));
+            return false;
+        }
+    };
+    describe_unfinished(&journal);
+    let answer = match (forward, back) {
+        (true, _) => 'f',
+        (_, true) => 'b',
+        _ => ask_recovery(),
+    };
+    finish_recovery(journal, answer)
 }
 
-fn get_email_config_path() -> PathBuf {
-    get_working_dir().join("email_config.toml")
+/// What the operation that didn't finish did, and to which files.
+fn describe_unfinished(journal: &Journal) {
+    say!("⚠️  The {} started {} didn't finish", journal.operation(), journal.started().format("%Y-%m-%d %H:%M"));
+    for step in journal.steps() {
+        say!("   done: {}", step);
+    }
+    for (file, after) in journal.files() {
+        let state = match after {
+            After::Pending => "not written yet",
+            After::Written => "written",
+            After::Removed => "removed",
+        };
+        say!("   {}: {}", file.display(), state);
+    }
 }
 
-fn get_todoist_config_path() -> PathBuf {
-    get_sync_dir().join("todoist_config.toml")
+/// Asks whether to roll forward ('f'), back ('b') or leave it for later ('l', or the end
+/// of input).
+fn ask_recovery() -> char {
+    use std::io::{self, Write};
+
+    print!("  Roll it [f]orward, [b]ack, or decide [l]ater? ");
+    io::stdout().flush().unwrap();
+
+    let mut answer = String::new();
+    if !matches!(io::stdin().read_line(&mut answer), Ok(read) if read > 0) {
+        return 'l';
+    }
+    match answer.trim().to_lowercase().as_str() {
+        "f" | "forward" => 'f',
+        "b" | "back" => 'b',
+        _ => 'l',
+    }
+}
+
+/// Rolls `journal` forward or back as `answer` says, committing the files. Returns
+/// whether it didn't fail.
+fn finish_recovery(journal: Journal, answer: char) -> bool {
+    let operation = journal.operation().to_string();
+    let tasks_file = get_tasks_file_path();
+    let _lock = match document::lock(&tasks_file) {
+        Ok(lock) => lock,
+        Err(e) => {
+            say_err!("❌ {}", YarmtlError::io("lock", &tasks_file)(e));
+            return false;
+        }
+    };
+    let (rolled, direction) = match answer {
+        'f' => (journal.roll_forward(), "forward"),
+        'b' => (journal.roll_back(), "back"),
+        _ => {
+            say!("⏸️  Left as it is, `yarmtl recover` asks again");
+            return true;
+        }
+    };
+    match rolled {
+        Ok(files) => {
+            for file in files {
+                say!("♻️  Rolled {} {}", direction, file.display());
+            }
+            if answer == 'b' {
+                say!("   What it did on the other side stays, the next sync sees it");
+            }
+            if let Err(e) = git_commit_tasks_with_message(Some(&format!("♻️ Rolled the unfinished {} {}", operation, direction))) {
+                tracing::warn!("{}", e);
+            }
+            true
+        }
+        Err(e) => {
+            say_err!("❌ {}", e);
+            false
+        }
+    }
+}
+
+/// `yarmtl encrypt`. Returns whether the tasks are encrypted now.
+fn encrypt_tasks() -> bool {
+    let sync_dir = get_sync_dir();
+    if sync_dir.join("tasks.md.age").exists() {
+        say!("🔒 The tasks are encrypted already");
+        return true;
+    }
+    let (identity, new) = match encryption::identity_or_new() {
+        Ok(key) => key,
+        Err(e) => {
+            say_err!("❌ No key to encrypt with: {}", e);
+            return false;
+        }
+    };
+    if new {
+        say!("🔑 Made a new key and put it in the keyring:\n\n    {}\n", encryption::secret_key(&identity));
+        say!("   Keep a copy of it somewhere safe, the tasks can't be read without it. To read them on");
+        say!("   another machine, save it to a file there and set identity_file in [encryption] to it.\n");
+    }
+
+    let tasks_file = sync_dir.join("tasks.md");
+    let encrypted = document::lock(&tasks_file).and_then(|_lock| encryption::encrypt_dir(&sync_dir, &identity));
+    let encrypted = match encrypted {
+        Ok(encrypted) => encrypted,
+        Err(e) => {
+            say_err!("❌ {}", YarmtlError::io("encrypt", &tasks_file)(e));
+            return false;
+        }
+    };
+    for path in &encrypted {
+        say!("🔒 Encrypted {}", path.display());
+    }
+    if let Err(e) = git_commit_tasks_with_message(Some("🔒 Encrypted the tasks")) {
+        tracing::warn!("{}", e);
+    }
+    if git::is_repository(&sync_dir) {
+        say!("⚠ Commits from before still hold the tasks unencrypted, and so does any remote they were pushed to");
+    }
+    true
+}
+
+/// `yarmtl decrypt`. Returns whether the tasks are plain markdown now.
+fn decrypt_tasks() -> bool {
+    let sync_dir = get_sync_dir();
+    if !sync_dir.join("tasks.md.age").exists() {
+        say!("✓ The tasks aren't encrypted");
+        return true;
+    }
+    let tasks_file = sync_dir.join("tasks.md.age");
+    let decrypted = encryption::identity()
+        .and_then(|identity| document::lock(&tasks_file).and_then(|_lock| encryption::decrypt_dir(&sync_dir, &identity)));
+    let decrypted = match decrypted {
+        Ok(decrypted) => decrypted,
+        Err(e) => {
+            say_err!("❌ {}", YarmtlError::io("decrypt", &tasks_file)(e));
+            return false;
+        }
+    };
+    for path in &decrypted {
+        say!("🔓 Decrypted {}", path.display());
+    }
+    if let Err(e) = git_commit_tasks_with_message(Some("🔓 Decrypted the tasks")) {
+        tracing::warn!("{}", e);
+    }
+    true
+}
+
+/// `yarmtl pull`. Returns whether it went through.
+fn pull_tasks() -> bool {
+    let sync_dir = get_sync_dir();
+    match git::git_pull(&sync_dir) {
+        Ok(PullOutcome::NoRemote) => {
+            say_err!("❌ No remote to pull from, set `remote` in the [git] table of config.toml");
+            false
+        }
+        Ok(PullOutcome::Disabled) => {
+            say_err!("❌ Git is off with --no-git");
+            false
+        }
+        Ok(PullOutcome::UpToDate) => {
+            say!("✓ Already up to date");
+            true
+        }
+        Ok(PullOutcome::FastForwarded) => {
+            say!("⬇️ Pulled the tasks from the remote");
+            true
+        }
+        Ok(PullOutcome::Merged) => {
+            say!("🔀 Merged the tasks from the remote with the ones here");
+            match git::git_push_if_remote_exists(&sync_dir) {
+                Ok(_) => {
+                    say!("🚀 Pushed the merge");
+                    true
+                }
+                Err(e) => {
+                    say_err!("❌ {}", e);
+                    false
+                }
+            }
+        }
+        Err(e) => {
+            say_err!("❌ {}", e);
+            false
+        }
+    }
+}
+
+/// tasks.md, telling why when it can't be read.
+fn open_store() -> Option<TaskStore> {
+    TaskStore::open_default().map_err(|e| say_err!("❌ {}", e)).ok()
+}
+
+/// The task `reference` points at, telling why when there is no such task.
+fn find_task(tasks: &[Task], reference: &str) -> Option<usize> {
+    locate_task(tasks, reference).map_err(|why| say_err!("❌ {}", why)).ok()
+}
+
+fn open_task_attachment(reference: &str) -> bool {
+    let Some(store) = open_store() else { return false };
+    let Some(index) = find_task(store.tasks(), reference) else { return false };
+    let task = &store.tasks()[index];
+
+    match attachments::task_attachments(&get_sync_dir(), task).first() {
+        Some(attachment) => match attachments::open(&attachment.target) {
+            Ok(()) => say!("📎 opening {}", attachment.target),
+            Err(e) => {
+                say_err!("Failed to open {}: {}", attachment.target, e);
+                return false;
+            }
+        },
+        None => {
+            say_err!("\"{}\" has no attachments or links", task.text);
+            return false;
+        }
+    }
+    true
+}
+
+fn bump_task(reference: &str, step: i32, clock: &dyn Clock) -> bool {
+    let Some(mut store) = open_store() else { return false };
+    let Some(index) = find_task(store.tasks(), reference) else { return false };
+    let task = store.tasks()[index].clone();
+    if task.count.is_none() {
+        say_err!("\"{}\" has no [count:] counter, add one like [count:0/10]", task.text);
+        return false;
+    }
+
+    let mut bumped = task.clone();
+    bumped.bump(step, clock.today());
+    if bumped.completed && !task.completed {
+        match hooks::run_task_hook(&get_sync_dir(), hooks::HookPoint::Complete, &bumped) {
+            Ok(hooked) => bumped = hooked,
+            Err(e) => {
+                say_err!("❌ Not counted, {}", e);
+                return false;
+            }
+        }
+    }
+    store.tasks_mut()[index] = bumped.clone();
+    if let Err(e) = store.save() {
+        say_err!("❌ {}", e);
+        return false;
+    }
+
+    let (done, target) = bumped.count.unwrap_or_default();
+    let commit_message = if bumped.completed && !task.completed {
+        format!("✅ Marked task complete: \"{}\" ({}/{})", bumped.text, done, target)
+    } else {
+        format!("🔢 Counted \"{}\" to {}/{}", bumped.text, done, target)
+    };
+    if let Err(e) = git_commit_tasks_with_message(Some(&commit_message)) {
+        tracing::warn!("{}", e);
+    }
+
+    say!("🔢 \"{}\" {}/{}{}", bumped.text, done, target, if bumped.completed { format!(" {} done", output::deco("✅")) } else { String::new() });
+    true
+}
+
+pub fn attach_to_task(reference: &str, file: &Path) -> bool {
+    let sync_dir = get_sync_dir();
+    let Some(mut store) = open_store() else { return false };
+    let Some(index) = find_task(store.tasks(), reference) else { return false };
+    let task = store.tasks()[index].clone();
+
+    let name = match attachments::attach_file(&sync_dir, &task.id, file) {
+        Ok(name) => name,
+        Err(e) => {
+            say_err!("Failed to attach file: {}", e);
+            return false;
+        }
+    };
+
+    store.tasks_mut()[index].attachments.push(name.clone());
+    if let Err(e) = store.save() {
+        say_err!("❌ {}", e);
+        return false;
+    }
+
+    let commit_message = format!("📎 Attached {} to task: \"{}\"", name, task.text);
+    if let Err(e) = git_commit_tasks_with_message(Some(&commit_message)) {
+        tracing::warn!("{}", e);
+    }
+
+    say!("📎 attached {} to \"{}\"", name, task.text);
+    say!("  📂 {}", attachments::attachments_dir(&sync_dir, &task.id).join(&name).display());
+    true
+}
+
+fn triage_tasks(clock: &dyn Clock) -> bool {
+    let rules = load_config().rules;
+    if rules.is_empty() {
+        say!("No [[rules]] in {}", get_config_path().display());
+        return true;
+    }
+
+    let Some(mut store) = open_store() else { return false };
+    let escalations = rules::apply_rules(&rules, store.tasks_mut(), clock.today());
+    if escalations.is_empty() {
+        say!("✓ No task needs escalating");
+        return true;
+    }
+
+    // The messages name the tasks as they were escalated, before a merge may move them
+    let tasks = store.tasks().to_vec();
+    if let Err(e) = store.save() {
+        say_err!("❌ {}", e);
+        return false;
+    }
+    if let Err(e) = git_commit_tasks_with_message(Some(&rules::commit_message(&tasks, &escalations))) {
+        tracing::warn!("{}", e);
+    }
+
+    for escalation in &escalations {
+        say!("⚡ {} ({})", tasks[escalation.task_index].text, escalation.changes);
+    }
+    true
 }
 
-
-#[derive(Deserialize, Serialize)]
-struct EmailConfig {
-    smtp_server: String,
-    smtp_port: u16,
-    username: String,
-    password: String,
-    from_email: String,
-    to_email: String,
+/// `yarmtl export`: every task in taskwarrior's json, as csv, or as a site in the
+/// directory `output`.
+fn export_tasks(format: ExportFormat, output: Option<&Path>, clock: &dyn Clock) -> bool {
+    let Some(store) = open_store() else { return false };
+    let tasks = store.tasks();
+    let exported = match format {
+        ExportFormat::Taskwarrior => taskwarrior::export(tasks, &ids::IdMap::load(&get_sync_dir())),
+        ExportFormat::Csv => csv::export(tasks),
+        ExportFormat::Html => return export_site(tasks, output, clock),
+    };
+    match output {
+        Some(path) => match fs::write(path, exported) {
+            Ok(()) => say!("📤 Exported {} task(s) to {}", tasks.len(), path.display()),
+            Err(e) => {
+                say_err!("Failed to write export: {}", e);
+                return false;
+            }
+        },
+        None => print!("{}", exported),
+    }
+    true
 }
 
-impl Default for EmailConfig {
-    fn default() -> Self {
-        EmailConfig {
-            smtp_server: "smtp.gmail.com".to_string(),
-            smtp_port: 587,
-            username: "your_email@gmail.com".to_string(),
-            password: "your_app_password".to_string(),
-            from_email: "your_email@gmail.com".to_string(),
-            to_email: "your_email@gmail.com".to_string(),
+/// `yarmtl export --format html`: the pages of `site::render` written to `output`.
+fn export_site(tasks: &[Task], output: Option<&Path>, clock: &dyn Clock) -> bool {
+    let Some(dir) = output else {
+        say_err!("❌ The site is a directory of pages, say which with --out, like --out site/");
+        return false;
+    };
+    let pages = site::render(tasks, clock.today());
+    for page in &pages {
+        let path = dir.join(&page.path);
+        let written = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| fs::write(&path, &page.html));
+        if let Err(e) = written {
+            say_err!("❌ Failed to write {}: {}", path.display(), e);
+            return false;
         }
     }
+    say!("🌐 Wrote {} task(s) to {} page(s) in {}, open {}", tasks.len(), pages.len(), dir.display(), dir.join("index.html").display());
+    true
 }
 
-#[derive(Deserialize, Serialize)]
-struct TodoistConfig {
-    enabled: bool,
-    project_id: Option<String>,
-    auto_sync: bool,
-    last_sync_timestamp: Option<String>,
+/// What `yarmtl import` read.
+enum Imported<'a> {
+    Taskwarrior(&'a [taskwarrior::TwTask]),
+    Csv(&'a csv::Sheet),
 }
 
-impl Default for TodoistConfig {
-    fn default() -> Self {
-        TodoistConfig {
-            enabled: true,
-            project_id: None,
-            auto_sync: true,
-            last_sync_timestamp: None,
+/// `yarmtl import`: adds and updates tasks from taskwarrior's json or from csv, its
+/// columns mapped by `map`. False when nothing could be imported.
+fn import_tasks(format: ExportFormat, file: Option<&Path>, map: Option<&str>, clock: &dyn Clock) -> bool {
+    use std::io::Read;
+
+    if map.is_some() && !matches!(format, ExportFormat::Csv) {
+        say_err!("❌ --map is for --format csv");
+        return false;
+    }
+    if matches!(format, ExportFormat::Html) {
+        say_err!("❌ Tasks can't be imported from html, only exported to it");
+        return false;
+    }
+    let mut input = String::new();
+    let read = match file {
+        Some(path) => fs::read_to_string(path).map(|content| input = content),
+        None => std::io::stdin().read_to_string(&mut input).map(|_| ()),
+    };
+    if let Err(e) = read {
+        say_err!("Failed to read tasks to import: {}", e);
+        return false;
+    }
+    let (tasks, sheet) = match format {
+        ExportFormat::Taskwarrior => match taskwarrior::parse(&input) {
+            Ok(tasks) => (tasks, None),
+            Err(e) => {
+                say_err!("Not taskwarrior json: {}", e);
+                return false;
+            }
+        },
+        ExportFormat::Csv | ExportFormat::Html => match csv::parse(&input, map) {
+            Ok(sheet) => (Vec::new(), Some(sheet)),
+            Err(e) => {
+                say_err!("❌ Not imported, {}", e);
+                return false;
+            }
+        },
+    };
+    let imported = match &sheet {
+        Some(sheet) => {
+            let ignored = sheet.ignored();
+            if !ignored.is_empty() {
+                say!("⏭  Leaving out the columns {}, --map says which field each is", ignored.join(", "));
+            }
+            Imported::Csv(sheet)
+        }
+        None => Imported::Taskwarrior(&tasks),
+    };
+    // Rows that can't be read stop the import before there is anything to back up
+    if let Imported::Csv(sheet) = imported
+        && let Some(store) = open_store()
+        && let Err(e) = csv::import(&mut store.tasks().to_vec(), sheet, &ids::IdMap::load(&get_sync_dir()), clock.today())
+    {
+        say_err!("❌ Not imported, {}", e);
+        return false;
+    }
+    match backup::automatic("import") {
+        Ok(path) => say!("💾 Backed up the tasks to {} first", path.display()),
+        Err(e) => {
+            say_err!("❌ Not imported, couldn't back up the tasks first: {}", e);
+            return false;
+        }
+    }
+    match save_imported_tasks(imported, clock) {
+        Ok(summary) => {
+            say!("📥 Imported {} new and {} changed task(s)", summary.added, summary.updated);
+            true
+        }
+        Err(e) => {
+            say_err!("Failed to update tasks file: {}", e);
+            false
         }
     }
 }
 
-#[derive(Parser)]
-#[command(name = "yarmtl")]
-#[command(author, version, about = "yet another rust markdown todo list", long_about = None)]
-struct Cli {
-    /// task text to add (if empty, launch tui)
-    task: Option<String>,
-    
-    /// list all tasks
-    #[arg(short, long)]
-    list: bool,
-    
-    /// show completed tasks too
-    #[arg(short, long)]
-    done: bool,
-    
-    /// send email reminders for overdue/due tasks
-    #[arg(short, long)]
-    email: bool,
-    
-    /// setup email configuration
-    #[arg(long)]
-    setup_email: bool,
-
-    /// setup todoist api integration
-    #[arg(long)]
-    setup_todoist: bool,
-
-    /// run as daemon, sending emails at 5 AM daily
-    #[arg(long)]
-    daemon: bool,
-    
-    /// path to directory containing tasks.md (creates if doesn't exist)
-    #[arg(short, long, value_name = "DIR")]
-    path: Option<String>,
-}
+/// `yarmtl hook on-add|on-modify`, run by taskwarrior with the task as json on stdin
+/// (the original and the changed one for on-modify). The task goes back out unchanged
+/// as taskwarrior wants; what yarmtl made of it is the message after it.
+fn taskwarrior_hook(event: HookEvent, clock: &dyn Clock) {
+    use std::io::Read;
 
-#[tokio::main]
-async fn main() {
-    let cli = Cli::parse();
-    
-    // Set up working directory first
-    if let Err(e) = set_working_dir(cli.path.as_deref()) {
-        eprintln!("Error setting up working directory: {}", e);
+    let mut input = String::new();
+    if std::io::stdin().read_to_string(&mut input).is_err() {
         return;
     }
-    
-    if cli.path.is_some() {
-        println!("📂 Working directory: {}", get_working_dir().display());
-    }
-    
-    if cli.setup_email {
-        setup_email_config();
+    let Some(line) = input.lines().rfind(|line| !line.trim().is_empty()) else {
         return;
+    };
+    println!("{}", line);
+    let verb = match event {
+        HookEvent::OnAdd => "added",
+        HookEvent::OnModify => "updated",
+    };
+    // Taskwarrior goes ahead either way, the hook only says so when yarmtl couldn't follow
+    match taskwarrior::parse(line).map_err(|e| e.to_string()).and_then(|tasks| {
+        save_imported_tasks(Imported::Taskwarrior(&tasks), clock).map_err(|e| e.to_string()).map(|_| tasks)
+    }) {
+        Ok(tasks) => tasks.iter().for_each(|t| println!("yarmtl: {} \"{}\"", verb, t.description)),
+        Err(e) => println!("yarmtl: couldn't copy the task to tasks.md: {}", e),
     }
+}
 
-    if cli.setup_todoist {
-        setup_todoist_config().await;
-        return;
+fn save_imported_tasks(
+    imported: Imported,
+    clock: &dyn Clock,
+) -> Result<taskwarrior::ImportSummary, Box<dyn std::error::Error>> {
+    let sync_dir = get_sync_dir();
+    let mut store = TaskStore::open_default()?;
+    let mut id_map = ids::IdMap::load(&sync_dir);
+    let (summary, from) = match imported {
+        Imported::Taskwarrior(tasks) => (taskwarrior::import(store.tasks_mut(), tasks, &mut id_map, clock.today()), "Taskwarrior"),
+        Imported::Csv(sheet) => (csv::import(store.tasks_mut(), sheet, &id_map, clock.today())?, "CSV"),
+    };
+    if summary == taskwarrior::ImportSummary::default() {
+        return Ok(summary);
     }
 
-    if cli.daemon {
-        if let Err(e) = run_daemon().await {
-            eprintln!("Daemon failed: {}", e);
+    store.save()?;
+    id_map.save(&sync_dir)?;
+    let commit_message = format!("📥 Imported {} new and {} changed task(s) from {}", summary.added, summary.updated, from);
+    if let Err(e) = git_commit_tasks_with_message(Some(&commit_message)) {
+        tracing::warn!("{}", e);
+    }
+    Ok(summary)
+}
+
+fn year_report(year: i32, format: ReportFormat, output: Option<&Path>) -> bool {
+    let history = match stats::load_task_history(&get_sync_dir()) {
+        Ok(history) => history,
+        Err(e) => {
+            say_err!("Failed to read task history: {}", e);
+            return false;
         }
-        return;
+    };
+
+    let report = report::build_year_report(&stats::completions_from_history(&history), year);
+    let rendered = match format {
+        ReportFormat::Markdown => report::render_markdown(&report),
+        ReportFormat::Html => report::render_html(&report),
+    };
+
+    match output {
+        Some(path) => match fs::write(path, rendered) {
+            Ok(()) => say!("📊 Wrote {} review to {}", year, path.display()),
+            Err(e) => {
+                say_err!("Failed to write report: {}", e);
+                return false;
+            }
+        },
+        None => print!("{}", rendered),
     }
-    
-    if cli.email {
-        if let Err(e) = send_email_reminders().await {
-            eprintln!("Failed to send email reminders: {}", e);
+    true
+}
+
+/// `yarmtl check`: says how many tasks are overdue and due today, and returns the exit
+/// code telling it.
+fn check_due(clock: &dyn Clock) -> i32 {
+    let tasks_file = get_tasks_file_path();
+    if !tasks_file.exists() {
+        say!("✓ No tasks");
+        return exit_code::OK;
+    }
+    match index::due_counts(&tasks_file, clock.today()) {
+        Ok((0, 0)) => {
+            say!("✓ Nothing overdue or due today");
+            exit_code::OK
+        }
+        Ok((0, today)) => {
+            say!("📅 {} due today", today);
+            exit_code::DUE_TODAY
+        }
+        Ok((overdue, today)) => {
+            say!("⏰ {} overdue, {} due today", overdue, today);
+            exit_code::OVERDUE
+        }
+        Err(e) => {
+            say_err!("❌ {}", e);
+            exit_code::ERROR
         }
-        return;
     }
-    
-    match cli.task {
-        Some(text) => {
-            println!("adding task: {}", text);
-            add_task(&text);
+}
+
+/// `yarmtl prompt`: "3 overdue, 5 today", or nothing when no task is either, or `format`
+/// filled in. Whatever goes wrong is left out of the prompt, for `-v` to show.
+fn print_prompt(format: Option<&str>, clock: &dyn Clock) -> bool {
+    let tasks_file = get_tasks_file_path();
+    if !tasks_file.exists() {
+        return true;
+    }
+    let (overdue, today) = match index::due_counts(&tasks_file, clock.today()) {
+        Ok(counts) => counts,
+        Err(e) => {
+            tracing::info!("{}", e);
+            return false;
         }
+    };
+    match format {
+        Some(format) => say!("{}", format.replace("{overdue}", &overdue.to_string()).replace("{today}", &today.to_string())),
         None => {
-            if cli.list {
-                list_tasks(cli.done);
-            } else {
-                println!("🚀 Launching YARMTL TUI...");
-                if let Err(e) = tui::run_tui(&get_sync_dir()) {
-                    eprintln!("TUI failed: {}", e);
-                }
+            let parts: Vec<String> = [(overdue, "overdue"), (today, "today")]
+                .iter()
+                .filter(|(count, _)| *count > 0)
+                .map(|(count, what)| format!("{} {}", count, what))
+                .collect();
+            if !parts.is_empty() {
+                say!("{}", parts.join(", "));
             }
         }
     }
+    true
 }
 
-pub fn add_task(text: &str) {
-    let task_file = get_tasks_file_path();
-    
-    if !task_file.exists() {
-        fs::write(&task_file, "# tasks\n\n").expect("couldn't create tasks file");
-    }
-    
-    let mut content = fs::read_to_string(&task_file)
-        .expect("couldn't read tasks file");
-    
-    // Parse the task as a regular task
-    let task = Task::parse(text);
-    let new_task = format!("{}\n", task.to_markdown());
-    content.push_str(&new_task);
-    
-    fs::write(&task_file, content)
-        .expect("couldn't write tasks file");
-    
-    // Auto-commit the task addition with descriptive message
-    let task = Task::parse(text);
-    let commit_message = format!("➕ Added task: \"{}\"", task.text);
-    
-    if let Err(e) = git_commit_tasks_with_message(Some(&commit_message)) {
-        eprintln!("Warning: Failed to commit task to git: {}", e);
-    }
-    
-    let task = Task::parse(text);
-    println!("✓ added task: \"{}\"", task.text);
-    if let Some(deadline) = task.deadline {
-        println!("  📅 deadline: {}", deadline.format("%Y-%m-%d"));
-    }
-    if !task.tags.is_empty() {
-        println!("  🏷️  tags: {}", task.tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" "));
-    }
-    if let Some(reminder) = task.reminder {
-        println!("  🔔 reminder: {}", reminder.format("%Y-%m-%d"));
-    }
-    if let Some(ref notes) = task.notes {
-        println!("  💬 notes: {}", notes);
-    }
-    if let Some(importance) = task.importance {
-        println!("  ⭐ importance: ${}", importance);
-    }
+/// `yarmtl report week` and `month`. Returns whether the review was written.
+fn review_report(period: report::Period, of: Option<&str>, format: ReportFormat, output: Option<&Path>, clock: &dyn Clock) -> bool {
+    let today = clock.today();
+    let day = match of.map(|phrase| dates::parse(phrase, today, dates::dialect()).ok_or(phrase)).transpose() {
+        Ok(day) => day.unwrap_or(today),
+        Err(phrase) => {
+            say_err!("❌ {} isn't a date", phrase);
+            return false;
+        }
+    };
+    let history = match stats::load_task_history(&get_sync_dir()) {
+        Ok(history) => history,
+        Err(e) => {
+            say_err!("❌ Failed to read task history: {}", e);
+            return false;
+        }
+    };
+    let Some(store) = open_store() else { return false };
 
-    // Trigger Todoist sync
-    if is_todoist_sync_enabled() {
-        tokio::spawn(async move {
-            if let Err(e) = trigger_todoist_sync().await {
-                eprintln!("⚠ Todoist sync failed: {}", e);
+    let review = report::build_review(&history, store.tasks(), period, day, today);
+    let rendered = match format {
+        ReportFormat::Markdown => report::render_review_markdown(&review),
+        ReportFormat::Html => report::render_review_html(&review),
+    };
+    match output {
+        Some(path) => match fs::write(path, rendered) {
+            Ok(()) => say!("📊 Wrote the review of the {} to {}", review.title().to_lowercase(), path.display()),
+            Err(e) => {
+                say_err!("❌ Failed to write report: {}", e);
+                return false;
             }
-        });
+        },
+        None => print!("{}", rendered),
     }
+    true
 }
 
-
-
-pub fn list_tasks(show_completed: bool) {
-    let task_file = get_tasks_file_path();
-    
-    if !task_file.exists() {
-        println!("no tasks file found. add a task first!");
-        return;
+pub fn list_tasks(show_completed: bool, project: Option<&str>, context: Option<&str>, clock: &dyn Clock) -> Result<(), YarmtlError> {
+    if !get_tasks_file_path().exists() {
+        say!("no tasks file found. add a task first!");
+        return Ok(());
     }
     
-    let content = fs::read_to_string(&task_file)
-        .expect("couldn't read tasks file");
+    // Years of completed tasks aren't read when they aren't shown
+    let all = match show_completed {
+        true => TaskStore::open(&get_tasks_file_path())?.tasks().to_vec(),
+        false => TaskStore::open_tasks(&get_tasks_file_path())?,
+    };
     
-    let today = chrono::Local::now().date_naive();
+    let today = clock.today();
     let tomorrow = today + chrono::Duration::days(1);
-    let week_start = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+    let week_start = dates::display().week_start(today);
     let week_end = week_start + chrono::Duration::days(6);
     
     // Collect and categorize tasks
+    let mut pinned_tasks = Vec::new();
     let mut overdue_tasks = Vec::new();
     let mut today_tasks = Vec::new();
     let mut tomorrow_tasks = Vec::new();
@@ -291,13 +1794,18 @@ pub fn list_tasks(show_completed: bool) {
     let mut later_tasks = Vec::new();
     let mut no_deadline_tasks = Vec::new();
     let mut completed_tasks = Vec::new();
+    let mut cancelled_tasks = Vec::new();
     
-    for line in content.lines() {
-        let trimmed_line = line.trim_start();
-        if trimmed_line.starts_with("- [ ]") {
-            let task_text = trimmed_line.strip_prefix("- [ ] ").unwrap_or(trimmed_line);
-            let task = Task::parse(task_text);
-            
+    let tasks: Vec<Task> = all
+        .into_iter()
+        .filter(|task| project.is_none() || task.project.as_deref() == project)
+        .filter(|task| context.is_none_or(|c| task.contexts.iter().any(|t| t == c.trim_start_matches('>'))))
+        .collect();
+    let planned = effort::planned_for(&tasks, today);
+    for task in tasks {
+        if task.pinned && !task.completed {
+            pinned_tasks.push(task);
+        } else if !task.completed {
             if let Some(deadline) = task.deadline {
                 if deadline < today {
                     overdue_tasks.push(task);
@@ -313,577 +1821,867 @@ pub fn list_tasks(show_completed: bool) {
             } else {
                 no_deadline_tasks.push(task);
             }
-        } else if trimmed_line.starts_with("- [x]") && show_completed {
-            let task_text = trimmed_line.strip_prefix("- [x] ").unwrap_or(trimmed_line);
-            let mut task = Task::parse(task_text);
-            task.completed = true;
+        } else if show_completed && task.cancelled {
+            cancelled_tasks.push(task);
+        } else if show_completed {
             completed_tasks.push(task);
         }
     }
     
     // Display tasks by category
     let mut has_any_tasks = false;
+
+    if !pinned_tasks.is_empty() {
+        say!("{}", output::paint("📌 PINNED:", output::Style::Heading));
+        for task in pinned_tasks {
+            print_task(&task, false, today);
+        }
+        say!();
+        has_any_tasks = true;
+    }
     
     if !overdue_tasks.is_empty() {
-        println!("⚠️  OVERDUE:");
+        say!("{}", output::paint("⚠️  OVERDUE:", output::Style::Overdue));
         for task in overdue_tasks {
-            print_task(&task, false);
+            print_task(&task, false, today);
         }
-        println!();
+        say!();
         has_any_tasks = true;
     }
     
     if !today_tasks.is_empty() {
-        println!("🔴 TODAY:");
+        say!("{}", output::paint("🔴 TODAY:", output::Style::Today));
         for task in today_tasks {
-            print_task(&task, false);
+            print_task(&task, false, today);
         }
-        println!();
+        say!();
         has_any_tasks = true;
     }
+
+    if !planned.is_zero() {
+        let capacity = effort::capacity(load_config().tasks.daily_capacity_hours);
+        say!("⏱  estimated work due today: {}", effort::describe_planned(planned, capacity));
+        say!();
+    }
     
     if !tomorrow_tasks.is_empty() {
-        println!("🟡 TOMORROW:");
+        say!("{}", output::paint("🟡 TOMORROW:", output::Style::Heading));
         for task in tomorrow_tasks {
-            print_task(&task, false);
+            print_task(&task, false, today);
         }
-        println!();
+        say!();
         has_any_tasks = true;
     }
     
     if !this_week_tasks.is_empty() {
-        println!("📅 THIS WEEK:");
+        say!("{}", output::paint("📅 THIS WEEK:", output::Style::Heading));
         for task in this_week_tasks {
-            print_task(&task, false);
+            print_task(&task, false, today);
         }
-        println!();
+        say!();
         has_any_tasks = true;
     }
     
     if !later_tasks.is_empty() {
-        println!("🔮 LATER:");
+        say!("{}", output::paint("🔮 LATER:", output::Style::Heading));
         for task in later_tasks {
-            print_task(&task, false);
+            print_task(&task, false, today);
         }
-        println!();
+        say!();
         has_any_tasks = true;
     }
     
     if !no_deadline_tasks.is_empty() {
-        println!("📝 NO DEADLINE:");
+        say!("{}", output::paint("📝 NO DEADLINE:", output::Style::Heading));
         for task in no_deadline_tasks {
-            print_task(&task, false);
+            print_task(&task, false, today);
         }
-        println!();
+        say!();
         has_any_tasks = true;
     }
     
     if show_completed && !completed_tasks.is_empty() {
-        println!("✅ COMPLETED:");
+        say!("{}", output::paint("✅ COMPLETED:", output::Style::Muted));
         for task in completed_tasks {
-            print_task(&task, true);
+            print_task(&task, true, today);
+        }
+        say!();
+        has_any_tasks = true;
+    }
+
+    if !cancelled_tasks.is_empty() {
+        say!("{}", output::paint("🚫 CANCELLED:", output::Style::Muted));
+        for task in cancelled_tasks {
+            print_task(&task, true, today);
         }
-        println!();
+        say!();
         has_any_tasks = true;
     }
     
     if !has_any_tasks {
-        println!("no tasks found!");
+        say!("no tasks found!");
     }
+    Ok(())
 }
 
-fn print_task(task: &Task, is_completed: bool) {
-    let checkbox = if is_completed { "☑" } else { "☐" };
-    let today = chrono::Local::now().date_naive();
-    let id_display = if task.id.len() > 8 { &task.id[..8] } else { &task.id };
-    
-    // Remove importance marker from displayed text since we show it separately
-    let display_text = {
-        let importance_re = Regex::new(r"\s*\$[1-5]").unwrap();
-        importance_re.replace(&task.text, "").trim().to_string()
+fn print_task(task: &Task, is_completed: bool, today: chrono::NaiveDate) {
+    let checkbox = match (is_completed, task.cancelled) {
+        (_, true) => "☒",
+        (true, false) => "☑",
+        (false, false) => "☐",
     };
-    print!("  {}  {} [{}]", checkbox, display_text, id_display);
+    let text = format!("{}  {}", checkbox, task.text);
+    let mut line = match is_completed {
+        true => format!("  {} [{}]", output::paint(&text, output::Style::Muted), task.id),
+        false => format!("  {} [{}]", text, task.id),
+    };
+    if let Some((done, target)) = task.count {
+        line.push_str(&format!(" [{}/{}]", done, target));
+    }
     
     if let Some(deadline) = task.deadline {
         if !is_completed {
             if deadline < today {
-                print!(" ⚠️ !{} (overdue)", deadline.format("%Y-%m-%d"));
+                let due = format!("{} !{} (overdue)", output::deco("⚠️"), dates::display().date(deadline));
+                line.push_str(&format!(" {}", output::paint(&due, output::Style::Overdue)));
             } else if deadline == today {
-                print!(" 🔴 !{} (due today)", deadline.format("%Y-%m-%d"));
+                let due = format!("{} !{} (due today)", output::deco("🔴"), dates::display().date(deadline));
+                line.push_str(&format!(" {}", output::paint(&due, output::Style::Today)));
             } else {
-                print!(" 📅 !{}", deadline.format("%Y-%m-%d"));
+                line.push_str(&format!(" {} !{}", output::deco("📅"), dates::display().date(deadline)));
             }
         } else {
-            print!(" 📅 !{}", deadline.format("%Y-%m-%d"));
+            line.push_str(&format!(" {} !{}", output::deco("📅"), dates::display().date(deadline)));
         }
     }
     
     if !task.tags.is_empty() {
         for tag in &task.tags {
-            print!(" 🏷️ #{}", tag);
+            line.push_str(&format!(" {}", output::paint(&format!("{} #{}", output::deco("🏷️"), tag), output::Style::Tag)));
         }
     }
+
+    for context in &task.contexts {
+        line.push_str(&format!(" {} >{}", output::deco("📍"), context));
+    }
+    if let Some(source) = &task.source {
+        line.push_str(&format!(" {} {}", output::deco("📄"), source));
+    }
     
     if let Some(reminder) = task.reminder {
-        print!(" 🔔 @{}", reminder.format("%Y-%m-%d"));
+        line.push_str(&format!(" {} @{}", output::deco("🔔"), dates::display().date(reminder)));
+    }
+    if let Some(repeat) = task.repeat {
+        line.push_str(&format!(" {} @{}", output::deco("🔁"), repeat));
+    }
+
+    if let Some(estimate) = task.estimate {
+        line.push_str(&format!(" {} ~{}", output::deco("⏱"), task::format_duration(estimate)));
+    }
+    if let Some(spent) = task.spent {
+        line.push_str(&format!(" (spent {})", task::format_duration(spent)));
     }
     
     if let Some(ref notes) = task.notes {
-        print!(" //{}", notes);
+        line.push_str(&format!(" //{}", notes));
     }
     
     if let Some(importance) = task.importance {
-        print!(" ⭐ ${}", importance);
+        line.push_str(&format!(" {} ${}", output::deco("⭐"), importance));
+    }
+
+    if let Some(done) = task.done.filter(|_| is_completed) {
+        let closed = if task.cancelled { "cancelled" } else { "completed" };
+        line.push_str(&format!(" ({} {})", closed, stats::relative_day(done, today)));
+    } else if let Some(created) = task.created {
+        line.push_str(&format!(" (added {})", stats::relative_day(created, today)));
     }
     
-    println!();
-}
-
-#[derive(Debug, Clone, std::hash::Hash)]
-pub struct Task {
-    pub id: String,
-    pub text: String,
-    pub deadline: Option<NaiveDate>,
-    pub tags: Vec<String>,
-    pub reminder: Option<NaiveDate>,
-    pub completed: bool,
-    pub notes: Option<String>,
-    pub importance: Option<u8>,
-}
-
-impl Task {
-    pub fn parse(input: &str) -> Self {
-        let deadline_re = Regex::new(r"!(\d{4}-\d{2}-\d{2})").unwrap();
-        let tags_re = Regex::new(r"#([\w-]+)").unwrap();
-        let reminder_date_re = Regex::new(r"@(\d{4}-\d{2}-\d{2})").unwrap();
-        let id_re = Regex::new(r"\[id:([a-f0-9-]+)\]").unwrap();
-        let importance_re = Regex::new(r"\$([1-5])").unwrap();
-        
-        // Use a simpler approach: parse notes with regex that stops at metadata
-        let notes_re = Regex::new(r"//([^!@#$]+)").unwrap();
-        let notes = notes_re.find(input)
-            .map(|m| m.as_str().trim_start_matches("//").trim().to_string())
-            .filter(|s| !s.is_empty());
-        
-        // Extract existing ID or generate new one
-        let task_id = id_re.find(input)
-            .map(|m| m.as_str().trim_start_matches("[id:").trim_end_matches("]").to_string())
-            .unwrap_or_else(|| {
-                // Generate a short random hash (8 characters)
-                Uuid::new_v4().simple().to_string()[..8].to_string()
-            });
-        
-        let deadline = deadline_re.find(input)
-            .and_then(|m| NaiveDate::parse_from_str(m.as_str().trim_start_matches('!'), "%Y-%m-%d").ok())
-            .or_else(|| {
-                // Try natural language parsing for deadlines
-                Self::extract_natural_deadline(input)
-            });
-        
-        // Extract all tags (multiple #tags)
-        let tags: Vec<String> = tags_re.find_iter(input)
-            .map(|m| m.as_str().trim_start_matches('#').to_string())
-            .collect();
-        
-        let reminder = reminder_date_re.find(input)
-            .and_then(|m| NaiveDate::parse_from_str(m.as_str().trim_start_matches('@'), "%Y-%m-%d").ok())
-            .or_else(|| {
-                // Try natural language parsing for reminders
-                Self::extract_natural_reminder(input)
-            });
-        
-        // Extract importance level
-        let importance = importance_re.find(input)
-            .and_then(|m| m.as_str().trim_start_matches('$').parse::<u8>().ok());
-        
-        let mut clean_text = input.to_string();
-        clean_text = deadline_re.replace_all(&clean_text, "").to_string();
-        clean_text = Self::remove_natural_deadline(&clean_text);
-        clean_text = tags_re.replace_all(&clean_text, "").to_string();
-        clean_text = reminder_date_re.replace_all(&clean_text, "").to_string();
-        clean_text = Self::remove_natural_reminder(&clean_text);
-        clean_text = notes_re.replace_all(&clean_text, "").to_string();
-        clean_text = id_re.replace_all(&clean_text, "").to_string();
-        clean_text = importance_re.replace_all(&clean_text, "").to_string();
-        clean_text = clean_text.trim().to_string();
-        
-        Task {
-            id: task_id,
-            text: clean_text,
-            deadline,
-            tags,
-            reminder,
-            completed: false,
-            notes,
-            importance,
+    say!("{}", line);
+}
+
+/// `yarmtl webhooks`: posts the events since the last run, or with `retry_failed` the
+/// deliveries in the dead-letter log. Returns false when something couldn't be sent.
+/// Long-polls the bot of the first telegram [[push]] channel for tasks, until stopped.
+async fn run_telegram_bot() -> bool {
+    let bot = load_config().push.into_iter().find_map(|channel| match channel.service {
+        push::PushService::Telegram { token, chat_id } => Some((token, chat_id)),
+        _ => None,
+    });
+    let Some((token, chat_id)) = bot else {
+        say_err!("No [[push]] channel with service = \"telegram\" in {}", get_config_path().display());
+        return false;
+    };
+    match telegram::Bot::new(&token).run(chat_id).await {
+        Ok(()) => true,
+        Err(e) => {
+            say_err!("Telegram bot failed: {}", e);
+            false
         }
     }
-    
-    pub fn to_markdown(&self) -> String {
-        let checkbox = if self.completed { "[x]" } else { "[ ]" };
-        let id_display = if self.id.len() > 8 { &self.id[..8] } else { &self.id };
-        let mut result = format!("- {} {} [id:{}]", checkbox, self.text, id_display);
-        
-        if let Some(ref deadline) = self.deadline {
-            result.push_str(&format!(" !{}", deadline.format("%Y-%m-%d")));
-        }
-        
-        for tag in &self.tags {
-            result.push_str(&format!(" #{}", tag));
-        }
-        
-        if let Some(ref reminder) = self.reminder {
-            result.push_str(&format!(" @{}", reminder.format("%Y-%m-%d")));
-        }
-
-        if let Some(ref notes) = self.notes {
-            result.push_str(&format!(" //{}", notes));
-        }
-
-        if let Some(importance) = self.importance {
-            result.push_str(&format!(" ${}", importance));
-        }
-        
-        result
-    }
-
-    fn extract_natural_deadline(input: &str) -> Option<NaiveDate> {
-        // Find text after ! that isn't a date format
-        if let Some(start) = input.find('!') {
-            let after_exclaim = &input[start + 1..];
-            
-            // Find the end of the deadline phrase (before #, @, //, or end of string)
-            let end_pos = after_exclaim
-                .find("//")
-                .or_else(|| after_exclaim.find(|c| c == '#' || c == '@'))
-                .unwrap_or(after_exclaim.len());
-            
-            let deadline_text = after_exclaim[..end_pos].trim();
-            
-            if !deadline_text.is_empty() && !deadline_text.chars().all(|c| c.is_digit(10) || c == '-') {
-                match deadline_text {
-                    "today" => return Some(chrono::Local::now().date_naive()),
-                    "tomorrow" => return Some(chrono::Local::now().date_naive() + chrono::Duration::days(1)),
-                    "yesterday" => return Some(chrono::Local::now().date_naive() - chrono::Duration::days(1)),
-                    _ => {
-                        // Try parsing with chrono-english
-                        if let Ok(parsed_date) = parse_date_string(deadline_text, Local::now(), Dialect::Us) {
-                            return Some(parsed_date.date_naive());
-                        }
-                    }
+}
+
+/// Raises the desktop notifications due now, and with `wait` handles their buttons until
+/// they are closed.
+async fn notify_desktop(wait: bool, clock: &dyn Clock) -> bool {
+    let Some(store) = open_store() else { return false };
+    let tasks = load_config().quiet.rolled(store.tasks());
+    let now = clock.now().naive_local();
+    let config = load_config().notifications;
+    // The notification server is talked to with blocking calls
+    let raised = tokio::task::spawn_blocking(move || {
+        notifications::notify_pending(&get_state_dir(), &tasks, now, &config).map_err(|e| e.to_string())
+    })
+    .await
+    .expect("notification thread panicked");
+    match raised {
+        Ok((count, waiting)) => {
+            say!("🔔 Raised {} notification(s)", count);
+            if wait {
+                for thread in waiting {
+                    let _ = thread.join();
                 }
             }
+            true
         }
-        None
+        Err(e) => {
+            say_err!("Failed to raise notifications: {}", e);
+            false
+        }
+    }
+}
+
+async fn send_webhooks(retry_failed: bool, clock: &dyn Clock) -> bool {
+    let hooks = load_config().webhooks;
+    if hooks.is_empty() {
+        say_err!("No [[webhooks]] in {}", get_config_path().display());
+        return false;
+    }
+    let state_dir = get_state_dir();
+    if retry_failed {
+        return match webhooks::retry_failed(&state_dir, &hooks).await {
+            Ok((sent, 0)) => {
+                say!("✓ Sent {} failed webhook(s)", sent);
+                true
+            }
+            Ok((sent, left)) => {
+                say_err!("Sent {} failed webhook(s), {} still failing in {}", sent, left, state_dir.join(webhooks::DEAD_LETTER_FILE).display());
+                false
+            }
+            Err(e) => {
+                say_err!("Failed to send webhooks: {}", e);
+                false
+            }
+        };
+    }
+    let Some(store) = open_store() else { return false };
+    match webhooks::dispatch(&state_dir, store.tasks(), &hooks, clock.today()).await {
+        Ok(sent) => {
+            say!("✓ Sent {} webhook(s)", sent);
+            true
+        }
+        Err(e) => {
+            say_err!("Failed to send webhooks: {}", e);
+            false
+        }
+    }
+}
+
+/// `yarmtl sync`: syncs right away, telling what is missing when it can't. With
+/// `dry_run` it only lists the planned actions, with `interactive` it asks about each.
+/// Returns whether the sync (or the dry run) ran.
+async fn sync_todoist_now(dry_run: bool, interactive: bool) -> bool {
+    match load_todoist_config() {
+        None => {
+            say_err!("❌ Todoist sync isn't set up. Run 'yarmtl --setup-todoist' first.");
+            return false;
+        }
+        Some(config) if !config.enabled => {
+            say_err!("❌ Todoist sync is disabled, set enabled = true in {}", get_todoist_config_path().display());
+            return false;
+        }
+        Some(_) => {}
+    }
+    let api_token = match todoist_auth::TodoistAuth::get_token() {
+        Ok(token) => token,
+        Err(e) => {
+            say_err!("❌ {}", e);
+            return false;
+        }
+    };
+
+    if dry_run {
+        let config = load_todoist_config().unwrap_or_default();
+        let provider = todoist_sync::TodoistProvider::new(api_token, config.incremental, config.sync);
+        let planned = match sync_engine::SyncEngine::new(provider, &get_sync_dir(), config.conflict_strategy) {
+            Ok(mut sync) => sync.plan(&get_tasks_file_path()).await,
+            Err(e) => Err(e),
+        };
+        return print_sync_plan::<todoist_types::TodoistTask>(planned);
+    }
+
+    say!("🔄 Syncing with Todoist...");
+    print_sync_result::<todoist_types::TodoistTask>(
+        run_todoist_sync(api_token, print_waiting(), approve_sync_actions(interactive)).await.map_err(Into::into),
+    )
+}
+
+/// `yarmtl sync --backend caldav`: syncs with the task list of caldav_config.toml,
+/// the same way as with Todoist.
+async fn sync_caldav_now(dry_run: bool, interactive: bool) -> bool {
+    let sync_dir = get_sync_dir();
+    let config_path = sync_config_path(&sync_dir, caldav_sync::CONFIG_FILE);
+    let config = match caldav_sync::CalDavConfig::load(&sync_dir) {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            say_err!("❌ CalDAV sync isn't set up. Write url, username and password to {}", config_path.display());
+            return false;
+        }
+        Err(e) => {
+            say_err!("❌ Couldn't read {}: {}", config_path.display(), e);
+            return false;
+        }
+    };
+    let tasks_file = get_tasks_file_path();
+
+    if dry_run {
+        let planned = match new_caldav_sync(&config, &sync_dir) {
+            Ok(mut sync) => sync.plan(&tasks_file).await,
+            Err(e) => Err(e),
+        };
+        return print_sync_plan(planned);
+    }
+
+    say!("🔄 Syncing with CalDAV...");
+    let result = match wait_for_sync_lock(print_waiting()).await {
+        // Paired tasks are read once the lock is held, a sync that just finished may have added some
+        Ok(_lock) => match new_caldav_sync(&config, &sync_dir) {
+            Ok(mut sync) => sync.sync_with_approval(&tasks_file, |_| {}, approve_sync_actions(interactive)).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e.into()),
+    };
+    if let Ok(report) = &result {
+        after_sync(report);
+    }
+    print_sync_result::<caldav_client::VTodo>(result)
+}
+
+fn new_caldav_sync(
+    config: &caldav_sync::CalDavConfig,
+    sync_dir: &Path,
+) -> Result<sync_engine::SyncEngine<caldav_sync::CalDavProvider>, Box<dyn std::error::Error>> {
+    let provider = caldav_sync::CalDavProvider::new(config)?;
+    sync_engine::SyncEngine::new(provider, sync_dir, config.conflict_strategy)
+}
+
+/// `yarmtl sync --backend google`: syncs with the Google Tasks list picked at setup.
+async fn sync_google_now(dry_run: bool, interactive: bool) -> bool {
+    let sync_dir = get_sync_dir();
+    let config = match google_sync::GoogleConfig::load(&sync_dir) {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            say_err!("❌ Google Tasks sync isn't set up. Run 'yarmtl --setup-google' first.");
+            return false;
+        }
+        Err(e) => {
+            say_err!("❌ Couldn't read {}: {}", sync_config_path(&sync_dir, google_sync::CONFIG_FILE).display(), e);
+            return false;
+        }
+    };
+    let auth = google_auth::sign_in(config.client_id.clone(), config.client_secret.clone());
+    let access_token = match auth.access_token().await {
+        Ok(token) => token,
+        Err(e) => {
+            say_err!("❌ {}", e);
+            return false;
+        }
+    };
+    let tasks_file = get_tasks_file_path();
+
+    if dry_run {
+        let planned = match new_google_sync(&config, access_token, &sync_dir) {
+            Ok(mut sync) => sync.plan(&tasks_file).await,
+            Err(e) => Err(e),
+        };
+        return print_sync_plan(planned);
+    }
+
+    say!("🔄 Syncing with Google Tasks...");
+    let result = match wait_for_sync_lock(print_waiting()).await {
+        Ok(_lock) => match new_google_sync(&config, access_token, &sync_dir) {
+            Ok(mut sync) => sync.sync_with_approval(&tasks_file, |_| {}, approve_sync_actions(interactive)).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e.into()),
+    };
+    if let Ok(report) = &result {
+        after_sync(report);
+    }
+    print_sync_result::<google_client::GoogleTask>(result)
+}
+
+fn new_google_sync(
+    config: &google_sync::GoogleConfig,
+    access_token: String,
+    sync_dir: &Path,
+) -> Result<sync_engine::SyncEngine<google_sync::GoogleProvider>, Box<dyn std::error::Error>> {
+    let provider = google_sync::GoogleProvider::new(config, access_token);
+    sync_engine::SyncEngine::new(provider, sync_dir, config.conflict_strategy)
+}
+
+/// `yarmtl sync --backend github`: syncs with the issues assigned to you in the
+/// repositories picked at setup.
+async fn sync_github_now(dry_run: bool, interactive: bool) -> bool {
+    let sync_dir = get_sync_dir();
+    let config = match github_sync::GitHubConfig::load(&sync_dir) {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            say_err!("❌ GitHub issues sync isn't set up. Run 'yarmtl --setup-github' first.");
+            return false;
+        }
+        Err(e) => {
+            say_err!("❌ Couldn't read {}: {}", sync_config_path(&sync_dir, github_sync::CONFIG_FILE).display(), e);
+            return false;
+        }
+    };
+    let token = match github_sync::TOKEN_STORE.get() {
+        Ok(token) => token,
+        Err(_) => {
+            say_err!("❌ No GitHub token, run 'yarmtl --setup-github'");
+            return false;
+        }
+    };
+    let tasks_file = get_tasks_file_path();
+
+    if dry_run {
+        let planned = match new_github_sync(&config, token, &sync_dir) {
+            Ok(mut sync) => sync.plan(&tasks_file).await,
+            Err(e) => Err(e),
+        };
+        return print_sync_plan(planned);
     }
 
-    fn extract_natural_reminder(input: &str) -> Option<NaiveDate> {
-        // Find text after @ that isn't a date format
-        if let Some(start) = input.find('@') {
-            let after_at = &input[start + 1..];
-            
-            // Find the end of the reminder phrase (before #, !, //, or end of string)
-            let end_pos = after_at
-                .find("//")
-                .or_else(|| after_at.find(|c| c == '#' || c == '!'))
-                .unwrap_or(after_at.len());
-            
-            let reminder_text = after_at[..end_pos].trim();
-            
-            if !reminder_text.is_empty() && !reminder_text.chars().all(|c| c.is_digit(10) || c == '-') {
-                match reminder_text {
-                    "today" => return Some(chrono::Local::now().date_naive()),
-                    "tomorrow" => return Some(chrono::Local::now().date_naive() + chrono::Duration::days(1)),
-                    "yesterday" => return Some(chrono::Local::now().date_naive() - chrono::Duration::days(1)),
-                    _ => {
-                        // Try parsing with chrono-english
-                        if let Ok(parsed_date) = parse_date_string(reminder_text, Local::now(), Dialect::Us) {
-                            return Some(parsed_date.date_naive());
-                        }
-                    }
-                }
-            }
-        }
-        None
+    say!("🔄 Syncing with GitHub issues...");
+    let result = match wait_for_sync_lock(print_waiting()).await {
+        Ok(_lock) => match new_github_sync(&config, token, &sync_dir) {
+            Ok(mut sync) => sync.sync_with_approval(&tasks_file, |_| {}, approve_sync_actions(interactive)).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e.into()),
+    };
+    if let Ok(report) = &result {
+        after_sync(report);
     }
+    print_sync_result::<github_client::GitHubIssue>(result)
+}
 
-    fn remove_natural_deadline(input: &str) -> String {
-        if let Some(start) = input.find('!') {
-            let before = &input[..start];
-            let after_exclaim = &input[start + 1..];
-            
-            let end_pos = after_exclaim
-                .find("//")
-                .or_else(|| after_exclaim.find(|c| c == '#' || c == '@'))
-                .unwrap_or(after_exclaim.len());
-            
-            let deadline_text = after_exclaim[..end_pos].trim();
-            
-            if !deadline_text.is_empty() && !deadline_text.chars().all(|c| c.is_digit(10) || c == '-') {
-                // Remove the natural language deadline
-                let after = &after_exclaim[end_pos..];
-                return format!("{}{}", before, after);
-            }
+fn new_github_sync(
+    config: &github_sync::GitHubConfig,
+    token: String,
+    sync_dir: &Path,
+) -> Result<sync_engine::SyncEngine<github_sync::GitHubProvider>, Box<dyn std::error::Error>> {
+    let provider = github_sync::GitHubProvider::new(config, token);
+    sync_engine::SyncEngine::new(provider, sync_dir, config.conflict_strategy)
+}
+
+/// Tells once that the sync waits for another one.
+fn print_waiting() -> impl FnMut(sync_engine::SyncProgress) {
+    let mut waiting = false;
+    move |progress| {
+        if matches!(progress, sync_engine::SyncProgress::Waiting) && !waiting {
+            say!("⏳ Waiting for another sync to finish...");
+            waiting = true;
         }
-        input.to_string()
     }
+}
 
-    fn remove_natural_reminder(input: &str) -> String {
-        if let Some(start) = input.find('@') {
-            let before = &input[..start];
-            let after_at = &input[start + 1..];
-            
-            let end_pos = after_at
-                .find("//")
-                .or_else(|| after_at.find(|c| c == '#' || c == '!'))
-                .unwrap_or(after_at.len());
-            
-            let reminder_text = after_at[..end_pos].trim();
-            
-            if !reminder_text.is_empty() && !reminder_text.chars().all(|c| c.is_digit(10) || c == '-') {
-                // Remove the natural language reminder
-                let after = &after_at[end_pos..];
-                return format!("{}{}", before, after);
+/// Takes every action, or with `interactive` the ones agreed to at the prompt.
+fn approve_sync_actions<R: sync_engine::RemoteTask>(interactive: bool) -> impl FnMut(&sync_engine::SyncAction<R>) -> bool {
+    let mut skip_rest = false;
+    move |action| {
+        if !interactive {
+            return true;
+        }
+        if skip_rest {
+            return false;
+        }
+        match confirm_sync_action(action) {
+            'y' => true,
+            'q' => {
+                skip_rest = true;
+                false
             }
+            _ => false,
         }
-        input.to_string()
     }
-
 }
 
-pub fn git_repo_check() -> Result<(), String> {
-    let sync_dir = get_sync_dir();
-    let git_dir = sync_dir.join(".git");
-    
-    if !git_dir.exists() {
-        Command::new("git")
-            .args(["init"])
-            .current_dir(&sync_dir)
-            .output()
-            .map_err(|e| format!("failed to initialize git: {}", e))?;
-
-        println!("🔧 Initialized git repository for task versioning in {}", sync_dir.display());
-        
-        // Set git user if not configured
-        let _ = Command::new("git")
-            .args(["config", "user.email", "yarmtl@local"])
-            .current_dir(&sync_dir)
-            .output();
-        
-        let _ = Command::new("git")
-            .args(["config", "user.name", "YARMTL"])
-            .current_dir(&sync_dir)
-            .output();
-        
-        // Create initial commit if tasks.md exists
-        let tasks_file = get_tasks_file_path();
-        if tasks_file.exists() {
-            let add_result = Command::new("git")
-                .args(["add", "tasks.md"])
-                .current_dir(&sync_dir)
-                .output()
-                .map_err(|e| format!("git add failed: {}", e))?;
-
-            if !add_result.status.success() {
-                let error = String::from_utf8_lossy(&add_result.stderr);
-                eprintln!("Warning: git add failed: {}", error);
-                return Ok(()); // Don't fail, just warn
-            }
-
-            let commit_result = Command::new("git")
-                .args(["commit", "-m", "🎉 Initial YARMTL tasks commit"])
-                .current_dir(&sync_dir)
-                .output()
-                .map_err(|e| format!("git initial commit failed: {}", e))?;
-            
-            if !commit_result.status.success() {
-                let error = String::from_utf8_lossy(&commit_result.stderr);
-                eprintln!("Warning: git initial commit failed: {}", error);
-                return Ok(()); // Don't fail, just warn
-            }
-            
-            println!("📝 Created initial tasks commit");
+/// Prints how a sync went, returns whether it ran.
+fn print_sync_result<R: sync_engine::RemoteTask>(
+    result: Result<sync_engine::SyncReport, Box<dyn std::error::Error>>,
+) -> bool {
+    match result {
+        Ok(report) => {
+            let mark = if report.failed + report.remaining > 0 { "⚠" } else { "✓" };
+            say!("{} Synced with {}: {}", mark, report.service, report.summary());
+            let details = report.details();
+            if details.is_empty() {
+                say!("  everything was already in sync");
+            }
+            for line in details {
+                say!("  {}", line);
+            }
+            true
+        }
+        Err(e) => {
+            say_err!("❌ {} sync failed: {}", R::SERVICE, e);
+            false
         }
     }
-    Ok(())
 }
 
-pub fn git_commit_tasks() -> Result<(), String> {
-    git_commit_tasks_with_message(None)
+/// Lists what a sync would do, deletions marked, without doing any of it.
+fn print_sync_plan<R: sync_engine::RemoteTask>(
+    planned: Result<Vec<sync_engine::SyncAction<R>>, Box<dyn std::error::Error>>,
+) -> bool {
+    match planned {
+        Ok(actions) if actions.is_empty() => say!("✓ Everything is in sync, nothing to do"),
+        Ok(actions) => {
+            say!("🔎 A sync would do {} thing(s), nothing was changed:", actions.len());
+            for action in &actions {
+                let mark = if action.is_deletion() { "⚠" } else { " " };
+                say!("  {} {}", mark, action.describe());
+            }
+        }
+        Err(e) => {
+            say_err!("❌ Couldn't plan the {} sync: {}", R::SERVICE, e);
+            return false;
+        }
+    }
+    true
 }
 
-pub fn git_commit_tasks_with_message(custom_message: Option<&str>) -> Result<(), String> {
-    git_repo_check()?;
-    
-    let sync_dir = get_sync_dir();
+/// Asks about one sync action: 'y' to take it, 'n' (or anything else) to skip it and
+/// 'q' (or the end of input) to skip it and every one after it.
+fn confirm_sync_action<R: sync_engine::RemoteTask>(action: &sync_engine::SyncAction<R>) -> char {
+    use std::io::{self, Write};
 
-    let add_result = Command::new("git")
-        .args(["add", "tasks.md"])
-        .current_dir(&sync_dir)
-        .output()
-        .map_err(|e| format!("git add failed: {}", e))?;
+    let mark = if action.is_deletion() { "⚠ " } else { "" };
+    print!("  {}{}? [y/N/q] ", mark, action.describe());
+    io::stdout().flush().unwrap();
 
-    if !add_result.status.success() {
-        let error = String::from_utf8_lossy(&add_result.stderr);
-        return Err(format!("git add failed: {}", error));
+    let mut answer = String::new();
+    if !matches!(io::stdin().read_line(&mut answer), Ok(read) if read > 0) {
+        return 'q';
     }
-
-    // Check if there are changes to commit
-    let status_output = Command::new("git")
-        .args(["status", "--porcelain"])
-        .current_dir(&sync_dir)
-        .output()
-        .map_err(|e| format!("git status failed: {}", e))?;
-
-    if status_output.stdout.is_empty() {
-        // No changes to commit
-        return Ok(());
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => 'y',
+        "q" | "quit" => 'q',
+        _ => 'n',
     }
+}
 
-    let message = if let Some(custom_msg) = custom_message {
-        custom_msg.to_string()
-    } else {
-        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-        format!("📝 Updated tasks - {}", timestamp)
+/// `yarmtl --sync-status`: the setup, whether the token still works, the paired tasks
+/// and the last sync.
+async fn print_todoist_sync_status() {
+    let config_path = get_todoist_config_path();
+    let Some(config) = load_todoist_config() else {
+        say!("Todoist sync isn't set up. Run 'yarmtl --setup-todoist' to connect your account.");
+        return;
     };
-
-    let commit_result = Command::new("git")
-        .args(["commit", "-m", &message])
-        .current_dir(&sync_dir)
-        .output()
-        .map_err(|e| format!("git commit failed: {}", e))?;
-
-    if !commit_result.status.success() {
-        let error = String::from_utf8_lossy(&commit_result.stderr);
-        return Err(format!("git commit failed: {}", error));
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    say!("🔄 Todoist sync");
+    say!("  config:    {}", config_path.display());
+    say!("  enabled:   {}, auto-sync {}", on_off(config.enabled), on_off(config.auto_sync));
+    say!("  conflicts: {}", config.conflict_strategy.name());
+    say!("  syncing:   {}", config.sync.describe());
+    say!(
+        "  fetching:  {}",
+        if config.incremental { "changes only (Sync API)" } else { "everything (REST API)" }
+    );
+
+    match todoist_auth::TodoistAuth::get_token() {
+        Err(e) => say!("  token:     {} {}", output::deco("✗"), e),
+        Ok(token) => match todoist_auth::TodoistAuth::verify_token(&token).await {
+            Ok(true) => say!("  token:     {} accepted by Todoist", output::deco("✓")),
+            Ok(false) => say!("  token:     {} rejected by Todoist, run 'yarmtl --setup-todoist' again", output::deco("✗")),
+            Err(e) => say!("  token:     ? couldn't reach Todoist to check it: {}", e),
+        },
     }
 
-    // Try to push to remote if it exists
-    git_push_if_remote_exists(&sync_dir)?;
-
-    Ok(())
+    let metadata_path = get_state_dir().join(".sync_metadata.json");
+    // Loading a missing file gives fresh metadata, which would claim a sync just now
+    let metadata = metadata_path
+        .exists()
+        .then(|| sync_metadata::SyncMetadata::load(&metadata_path).ok())
+        .flatten();
+    let tasks = TaskStore::open(&get_tasks_file_path()).map(|store| store.tasks().len());
+    let paired = metadata.as_ref().map_or(0, |m| m.task_mappings.len());
+    match tasks {
+        Ok(tasks) => say!("  paired:    {} of the {} tasks in tasks.md", paired, tasks),
+        Err(e) => say!("  paired:    {}, {}", paired, e),
+    }
+    match metadata {
+        Some(metadata) => say!(
+            "  last sync: {} ({})",
+            metadata.last_sync.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+            status_line::format_ago(metadata.last_sync, chrono::Utc::now())
+        ),
+        None => say!("  last sync: never"),
+    }
 }
 
-pub fn git_push_if_remote_exists(sync_dir: &PathBuf) -> Result<(), String> {
-    // Check if there's a remote configured
-    let remote_check = Command::new("git")
-        .args(["remote"])
-        .current_dir(sync_dir)
-        .output()
-        .map_err(|e| format!("git remote check failed: {}", e))?;
+/// `yarmtl --setup-email`: writes email_config.toml the first time; once it is filled in,
+/// asks for the SMTP password to keep in the keyring, or signs in to gmail or outlook.
+async fn setup_email_config() {
+    use std::io::{self, Write};
 
-    eprintln!("DEBUG: Remote check output: '{}'", String::from_utf8_lossy(&remote_check.stdout));
+    let config_file = get_email_config_path();
+    if !config_file.exists() {
+        let toml_content = toml::to_string_pretty(&EmailConfig::default()).unwrap();
+        let written = config_file.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| fs::write(&config_file, toml_content));
+        if let Err(e) = written.map_err(YarmtlError::io("write", &config_file)) {
+            say_err!("❌ {}", e);
+            return;
+        }
 
-    if remote_check.stdout.is_empty() {
-        // No remote configured, skip push
-        eprintln!("DEBUG: No remote configured, skipping push");
-        return Ok(());
+        say!("✓ Created {}", config_file.display());
+        say!("Please edit email_config.toml with your email settings, then run 'yarmtl --setup-email' again:");
+        say!("  - smtp_server: Your SMTP server (e.g., smtp.gmail.com)");
+        say!("  - smtp_port: 587 for STARTTLS, 465 for TLS");
+        say!("  - username: Your email account");
+        say!("  - from_email/to_email: Sender and recipient emails");
+        say!("  - auth: \"password\", or \"gmail\" or \"outlook\" to sign in with OAuth2");
+        return;
     }
+    let mut config = match load_email_config() {
+        Ok(config) => config,
+        Err(e) => {
+            say_err!("❌ Couldn't read email_config.toml: {}", e);
+            return;
+        }
+    };
+    let ask = |question: &str| {
+        print!("{}", question);
+        io::stdout().flush().unwrap();
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer).expect("Failed to read answer");
+        answer.trim().to_string()
+    };
 
-    // Check if we're on a branch that tracks a remote
-    let branch_check = Command::new("git")
-        .args(["rev-parse", "--abbrev-ref", "HEAD"])
-        .current_dir(sync_dir)
-        .output()
-        .map_err(|e| format!("git branch check failed: {}", e))?;
-
-    if !branch_check.status.success() {
-        return Ok(()); // No branch yet, skip push
+    if config.auth == email_auth::SmtpAuth::Password {
+        say!("The SMTP password (with gmail, an app password) is kept in the keyring, not in email_config.toml.");
+        let password = ask("SMTP password: ");
+        if password.is_empty() {
+            say_err!("❌ Error: the password can't be empty");
+            return;
+        }
+        match email_auth::PASSWORD_STORE.store(&password) {
+            Ok(()) => say!("✓ Stored the SMTP password"),
+            Err(e) => say_err!("❌ Couldn't store the password: {}", e),
+        }
+        return;
     }
 
-    let current_branch = String::from_utf8_lossy(&branch_check.stdout).trim().to_string();
-
-    // Try to push
-    let push_result = Command::new("git")
-        .args(["push", "origin", &current_branch])
-        .current_dir(sync_dir)
-        .output()
-        .map_err(|e| format!("git push failed: {}", e))?;
-
-    if push_result.status.success() {
-        println!("🚀 Pushed changes to remote repository");
-    } else {
-        let error = String::from_utf8_lossy(&push_result.stderr);
-        // Don't fail the whole operation if push fails, just warn
-        eprintln!("Warning: Failed to push to remote: {}", error);
-        eprintln!("You may need to run 'git push' manually in {}", sync_dir.display());
+    let client_id = match config.client_id.clone() {
+        Some(client_id) => client_id,
+        None => {
+            if config.auth == email_auth::SmtpAuth::Gmail {
+                say!("yarmtl signs in with an OAuth client of your own: in the Google Cloud console, create");
+                say!("an OAuth client ID of the \"TVs and Limited Input devices\" type.\n");
+            } else {
+                say!("yarmtl signs in with an app of your own: register one in the Azure portal, allow public");
+                say!("client flows and give it the SMTP.Send permission of Office 365 Exchange Online.\n");
+            }
+            let client_id = ask("Client ID: ");
+            if config.auth == email_auth::SmtpAuth::Gmail
+                && let Err(e) = email_auth::CLIENT_SECRET_STORE.store(&ask("Client secret: "))
+            {
+                say_err!("❌ Couldn't store the client secret: {}", e);
+                return;
+            }
+            client_id
+        }
+    };
+    if client_id.is_empty() {
+        say_err!("❌ Error: the client ID can't be empty");
+        return;
     }
-
-    Ok(())
-}
-
-pub fn is_todoist_sync_enabled() -> bool {
-    let config_file = get_todoist_config_path();
-    if !config_file.exists() {
-        return false;
+    config.client_id = Some(client_id.clone());
+    if let Err(e) = toml::to_string_pretty(&config).map_err(|e| e.to_string()).and_then(|content| fs::write(&config_file, content).map_err(|e| e.to_string())) {
+        say_err!("❌ Couldn't write email_config.toml: {}", e);
+        return;
     }
 
-    if let Ok(content) = fs::read_to_string(config_file) {
-        if let Ok(config) = toml::from_str::<TodoistConfig>(&content) {
-            return config.enabled && config.auto_sync;
+    let oauth = email_auth::sign_in(config.auth, client_id).expect("an OAuth sign-in");
+    let signed_in = match oauth.request_device_code().await {
+        Ok(code) => {
+            say!("\n🔐 Open {} and enter the code {}", code.verification_url, code.user_code);
+            say!("   Waiting for you to allow access...");
+            oauth.wait_for_approval(&code).await
         }
+        Err(e) => Err(e),
+    };
+    match signed_in {
+        Ok(_) => say!("✓ Signed in, reminders go out through XOAUTH2"),
+        Err(e) => say_err!("❌ {}", e),
     }
-
-    false
 }
 
-pub async fn trigger_todoist_sync() -> Result<(), Box<dyn std::error::Error>> {
-    if !is_todoist_sync_enabled() {
-        return Ok(());
-    }
+/// `yarmtl --setup-google`: asks for the OAuth client, signs in with a code entered on
+/// any device and picks the task list to sync with.
+async fn setup_google_tasks() {
+    use std::io::{self, Write};
 
-    let api_token = match todoist_auth::TodoistAuth::get_token() {
-        Ok(token) => token,
-        Err(_) => return Ok(()), // No token configured, skip sync
+    say!("🔧 Setting up Google Tasks...\n");
+    let sync_dir = get_sync_dir();
+    let existing = google_sync::GoogleConfig::load(&sync_dir).ok().flatten();
+    let ask = |question: &str| {
+        print!("{}", question);
+        io::stdout().flush().unwrap();
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer).expect("Failed to read answer");
+        answer.trim().to_string()
     };
 
-    let sync_dir = get_sync_dir();
-    let tasks_file = get_tasks_file_path();
+    let (client_id, client_secret) = match &existing {
+        Some(config) => (config.client_id.clone(), config.client_secret.clone()),
+        None => {
+            say!("yarmtl signs in with an OAuth client of your own: in the Google Cloud console, enable");
+            say!("the Tasks API and create an OAuth client ID of the \"TVs and Limited Input devices\" type.\n");
+            (ask("Client ID: "), ask("Client secret: "))
+        }
+    };
+    if client_id.is_empty() || client_secret.is_empty() {
+        say_err!("❌ Error: the client ID and secret can't be empty");
+        return;
+    }
 
-    let mut sync = todoist_sync::TodoistSync::new(api_token, &sync_dir)?;
-    let report = sync.sync(&tasks_file).await?;
+    let auth = google_auth::sign_in(client_id.clone(), client_secret.clone());
+    let access_token = match auth.request_device_code().await {
+        Ok(code) => {
+            say!("\n🔐 Open {} and enter the code {}", code.verification_url, code.user_code);
+            say!("   Waiting for you to allow access...");
+            auth.wait_for_approval(&code).await
+        }
+        Err(e) => Err(e),
+    };
+    let access_token = match access_token {
+        Ok(token) => token,
+        Err(e) => {
+            say_err!("❌ {}", e);
+            return;
+        }
+    };
+    say!("✓ Signed in");
 
-    // Commit changes from Todoist sync (silently)
-    if report.created_in_yarmtl + report.updated_in_yarmtl + report.deleted_in_yarmtl > 0 {
-        let commit_msg = format!("🔄 Synced from Todoist: {}", report.summary());
-        let _ = git_commit_tasks_with_message(Some(&commit_msg));
+    let lists = match google_client::GoogleClient::new(access_token).list_task_lists().await {
+        Ok(lists) if !lists.is_empty() => lists,
+        Ok(_) => {
+            say_err!("❌ There are no task lists in Google Tasks to sync with");
+            return;
+        }
+        Err(e) => {
+            say_err!("❌ Couldn't list the task lists: {}", e);
+            return;
+        }
+    };
+    say!("\nTask lists:");
+    for (number, list) in lists.iter().enumerate() {
+        say!("  {}. {}", number + 1, list.title);
     }
+    let list = match ask("Sync with which one? [1] ").as_str() {
+        "" => &lists[0],
+        answer => match answer.parse::<usize>().ok().and_then(|n| lists.get(n.wrapping_sub(1))) {
+            Some(list) => list,
+            None => {
+                say_err!("❌ No task list numbered {}", answer);
+                return;
+            }
+        },
+    };
 
-    Ok(())
-}
-
-fn load_email_config() -> Result<EmailConfig, Box<dyn std::error::Error>> {
-    let config_file = get_email_config_path();
-    if !config_file.exists() {
-        return Err("Email config file not found. Run with --setup-email first.".into());
+    let config = google_sync::GoogleConfig {
+        client_id,
+        client_secret,
+        task_list: list.id.clone(),
+        task_list_name: list.title.clone(),
+        conflict_strategy: existing.map(|c| c.conflict_strategy).unwrap_or_default(),
+    };
+    if let Err(e) = config.save(&sync_dir) {
+        say_err!("❌ Couldn't write {}: {}", sync_config_path(&sync_dir, google_sync::CONFIG_FILE).display(), e);
+        return;
     }
-    
-    let content = fs::read_to_string(config_file)?;
-    let config: EmailConfig = toml::from_str(&content)?;
-    Ok(config)
+    say!("✓ Syncing with \"{}\", run 'yarmtl sync --backend google'", list.title);
 }
 
-fn setup_email_config() {
-    println!("Setting up email configuration...");
+/// `yarmtl --setup-github`: checks and stores a personal access token and asks which
+/// repositories to take assigned issues from.
+async fn setup_github_issues() {
+    use std::io::{self, Write};
+
+    say!("🔧 Setting up GitHub issues...\n");
+    let sync_dir = get_sync_dir();
+    let existing = github_sync::GitHubConfig::load(&sync_dir).ok().flatten();
+    let ask = |question: &str| {
+        print!("{}", question);
+        io::stdout().flush().unwrap();
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer).expect("Failed to read answer");
+        answer.trim().to_string()
+    };
 
-    let config = EmailConfig::default();
-    let toml_content = toml::to_string_pretty(&config).unwrap();
-    let config_file = get_email_config_path();
+    say!("Create a personal access token at https://github.com/settings/tokens that can read");
+    say!("and write the issues of the repositories to sync with.\n");
+    let token = ask("Token: ");
+    if token.is_empty() {
+        say_err!("❌ Error: the token can't be empty");
+        return;
+    }
+    let login = match github_client::GitHubClient::new(token.clone()).current_user().await {
+        Ok(user) => user.login,
+        Err(e) => {
+            say_err!("❌ GitHub turned the token down: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = github_sync::TOKEN_STORE.store(&token) {
+        say_err!("❌ Couldn't store the token: {}", e);
+        return;
+    }
+    say!("✓ Signed in as {}", login);
 
-    fs::write(config_file, toml_content)
-        .expect("couldn't write email config file");
+    let current = existing.as_ref().map(|c| c.repos.join(", ")).unwrap_or_default();
+    let answer = ask(&format!("\nRepositories, as owner/repo separated by commas [{}]: ", current));
+    let repos: Vec<String> = match answer.as_str() {
+        "" => existing.as_ref().map(|c| c.repos.clone()).unwrap_or_default(),
+        answer => answer.split(',').map(|r| r.trim().to_string()).filter(|r| !r.is_empty()).collect(),
+    };
+    if let Some(repo) = repos.iter().find(|r| r.split('/').count() != 2) {
+        say_err!("❌ {} isn't owner/repo", repo);
+        return;
+    }
+    if repos.is_empty() {
+        say_err!("❌ Error: pick at least one repository");
+        return;
+    }
+    let create_issues = ask("Open issues for tasks tagged #gh? [y/N] ").eq_ignore_ascii_case("y");
 
-    println!("✓ Created email_config.toml in {}", get_working_dir().display());
-    println!("Please edit email_config.toml with your email settings:");
-    println!("  - For Gmail: Use app password, not regular password");
-    println!("  - smtp_server: Your SMTP server (e.g., smtp.gmail.com)");
-    println!("  - smtp_port: Usually 587 for TLS");
-    println!("  - username/password: Your email credentials");
-    println!("  - from_email/to_email: Sender and recipient emails");
+    let config = github_sync::GitHubConfig {
+        repos,
+        create_issues,
+        conflict_strategy: existing.map(|c| c.conflict_strategy).unwrap_or_default(),
+    };
+    if let Err(e) = config.save(&sync_dir) {
+        say_err!("❌ Couldn't write {}: {}", sync_config_path(&sync_dir, github_sync::CONFIG_FILE).display(), e);
+        return;
+    }
+    say!("✓ Syncing with {}, run 'yarmtl sync --backend github'", config.repos.join(", "));
 }
 
 async fn setup_todoist_config() {
-    println!("🔧 Setting up Todoist integration...\n");
+    say!("🔧 Setting up Todoist integration...\n");
 
     use std::io::{self, Write};
 
@@ -898,21 +2696,21 @@ async fn setup_todoist_config() {
     let token = token.trim().to_string();
 
     if token.is_empty() {
-        eprintln!("❌ Error: API token cannot be empty");
-        eprintln!("\nTo get your Todoist API token:");
-        eprintln!("  1. Go to https://todoist.com/app/settings/integrations");
-        eprintln!("  2. Scroll down to 'API token'");
-        eprintln!("  3. Copy your token and run this command again");
+        say_err!("❌ Error: API token cannot be empty");
+        say_err!("\nTo get your Todoist API token:");
+        say_err!("  1. Go to https://todoist.com/app/settings/integrations");
+        say_err!("  2. Scroll down to 'API token'");
+        say_err!("  3. Copy your token and run this command again");
         return;
     }
 
-    println!("\n🔐 Verifying token...");
+    say!("\n🔐 Verifying token...");
     match todoist_auth::TodoistAuth::verify_token(&token).await {
         Ok(true) => {
-            println!("✓ Token verified successfully!");
+            say!("✓ Token verified successfully!");
 
             if let Err(e) = todoist_auth::TodoistAuth::store_token(&token) {
-                eprintln!("❌ Failed to store token securely: {}", e);
+                say_err!("❌ Failed to store token securely: {}", e);
                 return;
             }
 
@@ -920,144 +2718,480 @@ async fn setup_todoist_config() {
             let toml_content = toml::to_string_pretty(&config).unwrap();
             let config_file = get_todoist_config_path();
 
-            fs::write(config_file, toml_content)
-                .expect("couldn't write todoist config file");
+            let written = config_file.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| fs::write(&config_file, toml_content));
+        if let Err(e) = written.map_err(YarmtlError::io("write", &config_file)) {
+                say_err!("❌ {}", e);
+                return;
+            }
 
-            println!("✓ Todoist integration configured!");
-            println!("\nConfiguration:");
-            println!("  - Auto-sync: enabled");
-            println!("  - Config file: {}", get_todoist_config_path().display());
-            println!("\nYour tasks will now sync automatically with Todoist!");
+            say!("✓ Todoist integration configured!");
+            say!("\nConfiguration:");
+            say!("  - Auto-sync: enabled");
+            say!("  - Config file: {}", get_todoist_config_path().display());
+            say!("\nYour tasks will now sync automatically with Todoist!");
         }
         Ok(false) => {
-            eprintln!("❌ Invalid API token. Please check your token and try again.");
-            eprintln!("\nTo get your Todoist API token:");
-            eprintln!("  1. Go to https://todoist.com/app/settings/integrations");
-            eprintln!("  2. Scroll down to 'API token'");
-            eprintln!("  3. Copy your token and run this command again");
+            say_err!("❌ Invalid API token. Please check your token and try again.");
+            say_err!("\nTo get your Todoist API token:");
+            say_err!("  1. Go to https://todoist.com/app/settings/integrations");
+            say_err!("  2. Scroll down to 'API token'");
+            say_err!("  3. Copy your token and run this command again");
         }
         Err(e) => {
-            eprintln!("❌ Failed to verify token: {}", e);
-            eprintln!("Please check your internet connection and try again.");
+            say_err!("❌ Failed to verify token: {}", e);
+            say_err!("Please check your internet connection and try again.");
         }
     }
 }
 
 async fn run_daemon() -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔄 Starting YARMTL daemon...");
-    println!("📧 Email reminders will be sent at 5:00 AM daily");
-    println!("📝 Checking for tasks with deadlines and reminder dates");
-    println!("💡 Press Ctrl+C to stop");
+    let daemon = Arc::new(daemon::Daemon::start(&get_state_dir())?);
+    // Warnings of the jobs go to the log with what they did, rather than a terminal no one watches
+    logging::set_sink(logging::Sink::File(get_state_dir().join(daemon::LOG_FILE)));
+    say!("🔄 Starting YARMTL daemon...");
+    say!("📧 Reminders will be sent by email and to the [[push]] channels at 5:00 AM daily");
+    say!("📝 Checking for tasks with deadlines and reminder dates");
+    say!("📜 Logging to {}", get_state_dir().join(daemon::LOG_FILE).display());
+    say!("💡 Press Ctrl+C or run `yarmtl daemon stop` to stop");
     
-    let sched = JobScheduler::new().await?;
+    let mut sched = JobScheduler::new().await?;
     
-    let job = Job::new_async("0 5 * * *", |_uuid, _l| {
-        Box::pin(async {
-            println!("[{}] Running daily email check...", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
-            if let Err(e) = send_email_reminders().await {
-                eprintln!("Failed to send email reminders: {}", e);
+    let reminders = daemon.clone();
+    // Seconds come first, and without a timezone it would be 5 AM UTC
+    let job = Job::new_async_tz("0 0 5 * * *", chrono::Local, move |_uuid, _l| {
+        let daemon = reminders.clone();
+        Box::pin(async move {
+            if load_config().quiet.is_day_off(SystemClock.today()) {
+                daemon.record("reminders", Ok(Some("held back, a day off".to_string())));
+                return;
+            }
+            // Without [[push]] channels for the digest, email is all there is to try
+            let push = load_config().push.iter().any(|channel| channel.wants(push::PushKind::Digest));
+            let email = !push || get_email_config_path().exists();
+            let outcome = send_reminders(&SystemClock, email, push).await;
+            daemon.record("reminders", outcome.map(|()| Some("sent".to_string())).map_err(|e| e.to_string()));
+            if load_config().reminder_email.weekly_summary == Some(SystemClock.today().weekday()) {
+                let outcome = send_weekly_summary(&SystemClock).await;
+                daemon.record("weekly summary", outcome.map(|()| Some("sent".to_string())).map_err(|e| e.to_string()));
             }
         })
     })?;
     
     sched.add(job).await?;
     sched.start().await?;
+
+    let sync_interval = load_todoist_config()
+        .filter(|config| config.enabled && config.auto_sync)
+        .map_or(0, |config| config.sync_interval_minutes);
+    let sync = if sync_interval > 0 {
+        say!("🔄 Syncing with Todoist every {} minutes", sync_interval);
+        let daemon = daemon.clone();
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(sync_interval * 60));
+            loop {
+                interval.tick().await;
+                let outcome = match trigger_todoist_sync().await {
+                    Ok(report) => Ok(report.map(|report| report.summary())),
+                    Err(e) => Err(e.to_string()),
+                };
+                daemon.record("todoist sync", outcome);
+            }
+        }))
+    } else {
+        None
+    };
     
-    // Keep the daemon running
+    // Catches deadlines passing at midnight and what syncs and other tools change
+    let hooks = load_config().webhooks;
+    if !hooks.is_empty() {
+        say!("🪝 Posting task events to {} webhook(s)", hooks.len());
+    }
+    let notify = load_config().notifications;
+    if notify.enabled {
+        say!("🔔 Raising desktop notifications from {}:00", notify.from_hour);
+    }
+    let channels = load_config().push;
+    if !channels.is_empty() {
+        say!("📣 Pushing to {} channel(s)", channels.len());
+    }
+    let actions = load_config().email_actions;
+    let answering = match &actions.url {
+        Some(_) if actions.port != 0 => {
+            let secret = email_actions::secret()?;
+            say!("✉️ Answering the links of the reminder emails on {}:{}", actions.bind, actions.port);
+            let daemon = daemon.clone();
+            Some(tokio::spawn(async move {
+                if let Err(e) = email_actions::serve(&actions, secret).await {
+                    daemon.record("email actions", Err(format!("couldn't listen on {}:{}: {}", actions.bind, actions.port, e)));
+                }
+            }))
+        }
+        _ => None,
+    };
+
+    // Catch up with the other machines before going over the tasks
+    if load_config().git.remote.is_some() {
+        let pulled = tokio::task::spawn_blocking(|| git::git_pull(&get_sync_dir())).await?;
+        if pulled.as_ref().is_ok_and(|outcome| *outcome == PullOutcome::Merged) {
+            git::request_push_after_commit();
+        }
+        daemon.record("git pull", pulled.map(|outcome| Some(outcome.to_string())).map_err(|e| e.to_string()));
+    }
+
+    // Read again only when something else wrote it, which goes over the reminders again
+    let mut store = TaskStore::open_default()?;
+    let mut watcher = match FileWatcher::new(&store.files()) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            daemon.record("watching tasks.md", Err(format!("{}, reading it every minute instead", e)));
+            None
+        }
+    };
+    let shutdown = daemon::shutdown_signal();
+    tokio::pin!(shutdown);
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+        if let Err(e) = store.refresh() {
+            daemon.record("tasks.md", Err(e.to_string()));
+        }
+        // tasks.md may include other files by now
+        if watcher.as_ref().is_some_and(|watcher| watcher.paths() != store.files()) {
+            watcher = FileWatcher::new(&store.files()).ok();
+        }
+        let outcome = run_overdue_hooks(&mut store).map(|changed| (changed > 0).then(|| format!("changed {} task(s)", changed)));
+        daemon.record("on-overdue", outcome.map_err(|e| e.to_string()));
+        // Held back ones go out once the quiet is over
+        let quiet = load_config().quiet;
+        let now = chrono::Local::now().naive_local();
+        if notify.enabled && !quiet.is_quiet(now) {
+            let tasks = quiet.rolled(store.tasks());
+            let config = notify.clone();
+            // The threads waiting for the buttons end with their notification
+            let raised = tokio::task::spawn_blocking(move || {
+                notifications::notify_pending(&get_state_dir(), &tasks, now, &config).map_err(|e| e.to_string())
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+            let outcome = raised.map(|(count, _)| (count > 0).then(|| format!("raised {}", count)));
+            daemon.record("notifications", outcome);
+        }
+        if !channels.is_empty() && !quiet.is_quiet(now) {
+            let tasks = quiet.rolled(store.tasks());
+            let outcome = push::alert_pending(&get_state_dir(), &tasks, now, notify.from_hour, &channels).await;
+            daemon.record("push alerts", outcome.map(|sent| (sent > 0).then(|| format!("sent {}", sent))).map_err(|e| e.to_string()));
+        }
+        if !hooks.is_empty() {
+            let today = SystemClock.today();
+            let outcome = webhooks::dispatch(&get_state_dir(), store.tasks(), &hooks, today).await;
+            daemon.record("webhooks", outcome.map(|sent| (sent > 0).then(|| format!("sent {}", sent))).map_err(|e| e.to_string()));
+        }
+        let changed = async {
+            match &mut watcher {
+                Some(watcher) => watcher.wait().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = tokio::time::sleep(Duration::from_secs(60)) => {}
+            _ = changed => {}
+        }
+    }
+
+    // A sync cut off halfway is picked up by the next one
+    say!("🛑 Stopping the daemon...");
+    if let Some(sync) = sync {
+        sync.abort();
+    }
+    if let Some(answering) = answering {
+        answering.abort();
     }
+    sched.shutdown().await?;
+    Ok(())
 }
 
-async fn send_email_reminders() -> Result<(), Box<dyn std::error::Error>> {
-    let config = load_email_config()?;
-    let task_file = get_tasks_file_path();
-    
-    if !task_file.exists() {
-        println!("No tasks file found.");
-        return Ok(());
+/// `yarmtl daemon status`: whether it runs, and how its jobs last went.
+fn print_daemon_status() -> bool {
+    let dir = get_state_dir();
+    let Some(pid) = daemon::running_pid(&dir) else {
+        say!("⚪ The daemon isn't running; start it with `yarmtl daemon start`");
+        return false;
+    };
+    let status = daemon::load_status(&dir).filter(|status| status.pid == pid);
+    match &status {
+        Some(status) => {
+            let up = chrono::Local::now() - status.started;
+            say!(
+                "🟢 The daemon is running, pid {}, since {} ({}h {}m)",
+                pid,
+                status.started.format("%Y-%m-%d %H:%M"),
+                up.num_hours(),
+                up.num_minutes() % 60
+            );
+        }
+        None => say!("🟢 The daemon is running, pid {}", pid),
     }
-    
-    let content = fs::read_to_string(task_file)?;
-    let today = chrono::Local::now().date_naive();
-    let mut reminder_tasks = Vec::new();
-    
-    for line in content.lines() {
-        if line.starts_with("- [ ]") {
-            let task_text = line.strip_prefix("- [ ] ").unwrap_or(line);
-            let task = Task::parse(task_text);
-            
-            let mut should_remind = false;
-            let mut reminder_reason = String::new();
-            
-            // Check deadline
-            if let Some(deadline) = task.deadline {
-                if deadline <= today {
-                    should_remind = true;
-                    if deadline < today {
-                        reminder_reason = "deadline overdue".to_string();
-                    } else {
-                        reminder_reason = "deadline due today".to_string();
-                    }
-                }
-            }
-            
-            // Check reminder date
-            if let Some(reminder_date) = task.reminder {
-                if reminder_date <= today && !should_remind {
-                    should_remind = true;
-                    reminder_reason = "reminder date reached".to_string();
-                }
-            }
-            
-            if should_remind {
-                reminder_tasks.push((task, reminder_reason));
-            }
+    for (job, run) in status.iter().flat_map(|status| &status.runs) {
+        say!(
+            "  {:<14} {}  {} {}",
+            job,
+            run.at.format("%Y-%m-%d %H:%M"),
+            if run.ok { "✓" } else { "✗" },
+            run.message.as_deref().unwrap_or("")
+        );
+    }
+    say!("📜 Log: {}", dir.join(daemon::LOG_FILE).display());
+    true
+}
+
+/// `yarmtl daemon install`: has the daemon started at login, and now.
+fn install_service(print: bool) -> bool {
+    let kind = service::ServiceKind::current();
+    let service = match service::service_for(&get_sync_dir()) {
+        Ok(service) => service,
+        Err(e) => {
+            say_err!("Couldn't install the daemon: {}", e);
+            return false;
         }
+    };
+    if print {
+        print!("{}", service.definition(kind));
+        return true;
     }
-    
-    if reminder_tasks.is_empty() {
-        println!("No tasks requiring reminders found.");
-        return Ok(());
+    if let Some(pid) = daemon::running_pid(&get_state_dir()) {
+        say_err!("The daemon is already running (pid {}), stop it first with `yarmtl daemon stop`", pid);
+        return false;
     }
-    
-    let mut email_body = String::from("Task Reminders\n\n");
-    
-    for (task, reason) in &reminder_tasks {
-        email_body.push_str(&format!("📌 {}: {}\n", reason.to_uppercase(), task.text));
+    match service.install(kind) {
+        Ok(installed) => {
+            say!("✓ Installed {}, the daemon runs from now on and at every login", installed);
+            say!("💡 `yarmtl daemon status` tells how it is doing, `yarmtl daemon uninstall` takes it out");
+            true
+        }
+        Err(e) => {
+            say_err!("Couldn't install the daemon: {}", e);
+            false
+        }
+    }
+}
+
+/// `yarmtl daemon stop`: asks the daemon to stop and waits a little for it to.
+async fn stop_daemon() -> bool {
+    let dir = get_state_dir();
+    let Some(pid) = daemon::running_pid(&dir) else {
+        say!("⚪ The daemon isn't running");
+        return true;
+    };
+    if let Err(e) = daemon::stop(pid) {
+        say_err!("Couldn't stop the daemon (pid {}): {}", pid, e);
+        return false;
+    }
+    for _ in 0..50 {
+        if daemon::running_pid(&dir).is_none() {
+            say!("🛑 Stopped the daemon (pid {})", pid);
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    say_err!("The daemon (pid {}) is still stopping", pid);
+    false
+}
+
+/// Runs the on-overdue hook for the tasks whose deadline just passed, saving what it changed.
+fn run_overdue_hooks(store: &mut TaskStore) -> Result<usize, Box<dyn std::error::Error>> {
+    let changed = hooks::run_overdue_hooks(&get_sync_dir(), store.tasks_mut(), SystemClock.today())?;
+    if !changed.is_empty() {
+        store.save()?;
+        let commit_message = format!("⏰ on-overdue changed {} task(s)", changed.len());
+        if let Err(e) = git_commit_tasks_with_message(Some(&commit_message)) {
+            tracing::warn!("{}", e);
+        }
+    }
+    Ok(changed.len())
+}
+
+/// The lines of the reminder email for the tasks and why each is in it.
+fn reminder_digest<'a>(reminder_tasks: impl Iterator<Item = &'a (Task, String)>) -> String {
+    let mut digest = String::new();
+    for (task, reason) in reminder_tasks {
+        digest.push_str(&format!("📌 {}: {}\n", reason.to_uppercase(), task.text));
         if let Some(ref deadline) = task.deadline {
-            email_body.push_str(&format!("  📅 Deadline: {}\n", deadline.format("%Y-%m-%d")));
+            digest.push_str(&format!("  📅 Deadline: {}\n", deadline.format("%Y-%m-%d")));
         }
         if let Some(ref reminder) = task.reminder {
-            email_body.push_str(&format!("  🔔 Reminder: {}\n", reminder.format("%Y-%m-%d")));
+            digest.push_str(&format!("  🔔 Reminder: {}\n", reminder.format("%Y-%m-%d")));
+        }
+        if let Some(repeat) = task.repeat {
+            digest.push_str(&format!("  🔁 Repeats: {}\n", repeat));
         }
         if !task.tags.is_empty() {
-            email_body.push_str(&format!("  🏷️  Tags: {}\n", 
+            digest.push_str(&format!("  🏷️  Tags: {}\n", 
                 task.tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ")));
         }
-        email_body.push('\n');
+        digest.push('\n');
+    }
+    digest
+}
+
+/// The Todoist ids of the synced tasks by task id, for links to them; none before a sync.
+fn todoist_ids() -> HashMap<String, String> {
+    let metadata_path = get_state_dir().join(".sync_metadata.json");
+    let metadata = metadata_path.exists().then(|| sync_metadata::SyncMetadata::load(&metadata_path).ok()).flatten();
+    metadata
+        .map(|metadata| metadata.task_mappings.into_iter().map(|(id, info)| (id, info.todoist_id)).collect())
+        .unwrap_or_default()
+}
+
+/// Where the links of the reminder emails point and what they are signed with, when
+/// `[email_actions]` has a url. Emails go out without them when the secret can't be had.
+fn email_action_links() -> Option<(String, String)> {
+    let url = load_config().email_actions.url?;
+    match email_actions::secret() {
+        Ok(secret) => Some((url, secret)),
+        Err(e) => {
+            tracing::warn!("no links to the task actions in the email, {}", e);
+            None
+        }
+    }
+}
+
+/// The plain text reminder email: the digest, each task with its action links when there
+/// are some, then the look ahead.
+fn reminder_text(
+    reminder_tasks: &[&(Task, String)],
+    sections: &[(reminder_email::Section, Vec<&Task>)],
+    actions: Option<&(String, String)>,
+    today: chrono::NaiveDate,
+) -> String {
+    let mut text = String::from("Task Reminders\n\n");
+    match actions {
+        // Each task with links to check it off or snooze it
+        Some((url, secret)) => {
+            for item in reminder_tasks {
+                let entry = reminder_digest(std::iter::once(*item));
+                text.push_str(entry.trim_end_matches('\n'));
+                text.push('\n');
+                text.push_str(&email_actions::links(url, secret, &item.0, today));
+                text.push('\n');
+            }
+        }
+        None => text.push_str(&reminder_digest(reminder_tasks.iter().copied())),
+    }
+    text.push_str(&reminder_email::extra_text(sections));
+    text
+}
+
+/// Emails the summary of the week before `clock`'s today.
+async fn send_weekly_summary(clock: &dyn Clock) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_email_config()?;
+    let store = TaskStore::open(&get_tasks_file_path())?;
+    let summary = reminder_email::weekly_summary(store.tasks(), clock.today());
+    let to: Vec<&str> = config.to_email.list().iter().map(String::as_str).collect();
+    mail(&config, &to, "Weekly Summary - YARMTL", summary.text(), summary.html()).await?;
+    say!("✓ Weekly summary sent: {} task(s) completed", summary.completed.len());
+    Ok(())
+}
+
+/// Sends the day's reminders: by email, and to the [[push]] channels taking the digest.
+/// Repeating reminders count as fired once either got them.
+async fn send_reminders(clock: &dyn Clock, email: bool, push: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let email_config = if email { Some(load_email_config()?) } else { None };
+    let channels: Vec<push::PushChannel> = match push {
+        true => load_config().push.into_iter().filter(|channel| channel.wants(push::PushKind::Digest)).collect(),
+        false => Vec::new(),
+    };
+    if push && channels.is_empty() {
+        return Err(format!("No [[push]] channel taking the digest in {}", get_config_path().display()).into());
+    }
+    if !get_tasks_file_path().exists() {
+        say!("No tasks file found.");
+        return Ok(());
     }
     
-    let email = Message::builder()
-        .from(config.from_email.parse()?)
-        .to(config.to_email.parse()?)
-        .subject("Task Reminders - YARMTL")
-        .body(email_body)?;
+    let mut store = TaskStore::open(&get_tasks_file_path())?;
+    let today = clock.today();
+    let content = load_config().reminder_email;
+    // Deadlines on days off are reminded of as due the working day after
+    let rolled = load_config().quiet.rolled(store.tasks());
+    let reminder_tasks: Vec<(Task, String)> = rolled
+        .iter()
+        .filter(|task| !content.is_quiet(task))
+        .filter_map(|task| Some((task.clone(), recurrence::reminder_reason(task, today)?)))
+        .collect();
     
-    let creds = Credentials::new(config.username, config.password);
-    let mailer = SmtpTransport::relay(&config.smtp_server)?
-        .credentials(creds)
-        .build();
+    if reminder_tasks.is_empty() {
+        say!("No tasks requiring reminders found.");
+        return Ok(());
+    }
     
-    match mailer.send(&email) {
-        Ok(_) => {
-            println!("✓ Email reminders sent successfully!");
-            println!("Sent {} reminder(s)", reminder_tasks.len());
+    let sections = reminder_email::sections(&rolled, today, &content);
+    let links = reminder_email::Links { todoist: todoist_ids(), actions: email_action_links() };
+
+    // The ids of the tasks that went out somewhere
+    let mut delivered = HashSet::new();
+    let mut failure = None;
+    let mut failed = 0;
+    for channel in &channels {
+        let routed: Vec<&(Task, String)> = reminder_tasks.iter().filter(|(task, _)| channel.routes(task)).collect();
+        if routed.is_empty() {
+            continue;
         }
-        Err(e) => {
-            return Err(format!("Failed to send email: {}", e).into());
+        // Without the heading, a phone shows more of the list
+        let digest = reminder_digest(routed.iter().copied());
+        match push::send(channel, "Task Reminders - YARMTL", digest.trim_end(), push::Priority::Normal).await {
+            Ok(()) => {
+                say!("✓ Pushed {} reminder(s) to {}", routed.len(), channel.name());
+                delivered.extend(routed.iter().map(|(task, _)| task.id.clone()));
+            }
+            Err(e) => {
+                tracing::warn!("couldn't push reminders to {}", e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 && delivered.is_empty() {
+        failure = Some("Couldn't push the reminders to any channel".to_string());
+    }
+
+    // Each recipient gets the tasks routed to them, see `reminder_email::receives`
+    if let Some(config) = email_config {
+        for address in reminder_email::recipients(&config.to_email, &config.routes) {
+            let receives = |task: &Task| reminder_email::receives(address, &config.to_email, &config.routes, task);
+            let mine: Vec<&(Task, String)> = reminder_tasks.iter().filter(|(task, _)| receives(task)).collect();
+            if mine.is_empty() {
+                continue;
+            }
+            let mine_sections = reminder_email::only(&sections, receives);
+            let text = reminder_text(&mine, &mine_sections, links.actions.as_ref(), today);
+            let html = reminder_email::html(&mine_sections, &links, today);
+            match mail(&config, &[address], "Task Reminders - YARMTL", text, html).await {
+                Ok(_) => {
+                    say!("✓ Email reminders sent to {}", address);
+                    say!("Sent {} reminder(s)", mine.len());
+                    delivered.extend(mine.iter().map(|(task, _)| task.id.clone()));
+                }
+                Err(e) => failure = Some(format!("Failed to send email to {}: {}", address, e)),
+            }
+        }
+    }
+
+    // Repeating reminders only count as fired once they are out
+    let mut fired = 0;
+    for task in store.tasks_mut().iter_mut().filter(|t| recurrence::is_due(t, today)) {
+        if delivered.contains(&task.id) {
+            task.last_fired = Some(today);
+            fired += 1;
+        }
+    }
+    if fired > 0 {
+        store.save()?;
+        let commit_message = format!("🔁 Sent {} repeating reminder(s)", fired);
+        if let Err(e) = git_commit_tasks_with_message(Some(&commit_message)) {
+            tracing::warn!("{}", e);
         }
     }
     
-    Ok(())
+    match failure {
+        Some(failure) => Err(failure.into()),
+        None => Ok(()),
+    }
 }
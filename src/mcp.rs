@@ -0,0 +1,216 @@
+use crate::server::{self, ApiError, ApiTask, ListQuery, NewTask, TaskEdit};
+use crate::store::TaskStore;
+use crate::task::Task;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+/// The MCP versions yarmtl speaks, newest first. A client asking for another one gets the newest.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// The tools `tools/list` offers, with the json schema of their arguments.
+fn tools() -> Value {
+    let filters = json!({
+        "completed": { "type": "boolean", "description": "Include completed and cancelled tasks" },
+        "project": { "type": "string", "description": "Only tasks under this ## heading of tasks.md" },
+        "context": { "type": "string", "description": "Only tasks with this context, like home for >home" },
+    });
+    json!([
+        {
+            "name": "list_tasks",
+            "description": "List the tasks in tasks.md, open ones unless completed is set.",
+            "inputSchema": { "type": "object", "properties": filters },
+        },
+        {
+            "name": "search_tasks",
+            "description": "Find tasks whose text, notes, tags, contexts or project hold every word of the query.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "completed": { "type": "boolean", "description": "Search completed and cancelled tasks too" },
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "add_task",
+            "description": "Add a task. The text takes yarmtl's markers: !2025-07-01 or !tomorrow for a deadline, \
+                #tag, >context, @date for a reminder, @every monday, ~30m for an estimate, $1 (most important) \
+                to $5 and //notes at the end.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "text": { "type": "string" },
+                    "project": { "type": "string", "description": "## heading to add it under, made when missing" },
+                },
+                "required": ["text"],
+            },
+        },
+        {
+            "name": "complete_task",
+            "description": "Check a task off, or open it again with completed set to false.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string", "description": "The task's id, or any unique prefix of it" },
+                    "completed": { "type": "boolean", "default": true },
+                },
+                "required": ["id"],
+            },
+        },
+    ])
+}
+
+#[derive(Deserialize)]
+struct SearchArguments {
+    query: String,
+    #[serde(default)]
+    completed: bool,
+}
+
+#[derive(Deserialize)]
+struct CompleteArguments {
+    id: String,
+    completed: Option<bool>,
+}
+
+/// Whether every word of `query` is somewhere in the task, ignoring case.
+fn matches_search(task: &Task, query: &str) -> bool {
+    let mut haystack = vec![task.text.as_str()];
+    haystack.extend(task.notes.as_deref());
+    haystack.extend(task.project.as_deref());
+    haystack.extend(task.tags.iter().map(String::as_str));
+    haystack.extend(task.contexts.iter().map(String::as_str));
+    let haystack = haystack.join(" ").to_lowercase();
+    query.to_lowercase().split_whitespace().all(|word| haystack.contains(word))
+}
+
+fn tasks_result(tasks: &[&Task]) -> Value {
+    json!(tasks.iter().map(|task| ApiTask::from(*task)).collect::<Vec<_>>())
+}
+
+/// Runs a tool. An error is either the arguments not fitting the tool, a JSON-RPC error,
+/// or the tool failing, which the assistant is told about in the result.
+fn call_tool(name: &str, arguments: Value) -> Result<Result<Value, String>, (i64, String)> {
+    let parse = |e: serde_json::Error| (INVALID_PARAMS, format!("invalid arguments for {}: {}", name, e));
+    let result = match name {
+        "list_tasks" => {
+            let query: ListQuery = serde_json::from_value(arguments).map_err(parse)?;
+            TaskStore::open_default()
+                .map(|store| tasks_result(&store.tasks().iter().filter(|task| query.matches(task)).collect::<Vec<_>>()))
+                .map_err(ApiError::from)
+        }
+        "search_tasks" => {
+            let search: SearchArguments = serde_json::from_value(arguments).map_err(parse)?;
+            TaskStore::open_default()
+                .map(|store| {
+                    let found: Vec<&Task> = store
+                        .tasks()
+                        .iter()
+                        .filter(|task| (search.completed || !task.completed) && matches_search(task, &search.query))
+                        .collect();
+                    tasks_result(&found)
+                })
+                .map_err(ApiError::from)
+        }
+        "add_task" => {
+            let new: NewTask = serde_json::from_value(arguments).map_err(parse)?;
+            server::add(new).map(|task| json!(ApiTask::from(&task)))
+        }
+        "complete_task" => {
+            let complete: CompleteArguments = serde_json::from_value(arguments).map_err(parse)?;
+            let edit = TaskEdit { completed: Some(complete.completed.unwrap_or(true)), ..TaskEdit::default() };
+            server::edit(&complete.id, edit).map(|task| json!(ApiTask::from(&task)))
+        }
+        _ => return Err((INVALID_PARAMS, format!("no tool {}", name))),
+    };
+    Ok(result.map_err(|e| e.to_string()))
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// The answer to one JSON-RPC message; None for notifications, which get none.
+pub fn handle(message: &Value) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let params = message.get("params").cloned().unwrap_or(json!({}));
+    let result = match message["method"].as_str().unwrap_or_default() {
+        "initialize" => {
+            let asked = params["protocolVersion"].as_str().unwrap_or_default();
+            let version = PROTOCOL_VERSIONS.iter().find(|v| **v == asked).unwrap_or(&PROTOCOL_VERSIONS[0]);
+            json!({
+                "protocolVersion": version,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "yarmtl", "version": env!("CARGO_PKG_VERSION") },
+                "instructions": "yarmtl keeps a markdown todo list in tasks.md. Every change is saved and committed to git.",
+            })
+        }
+        "ping" => json!({}),
+        "tools/list" => json!({ "tools": tools() }),
+        "tools/call" => {
+            let name = params["name"].as_str().unwrap_or_default();
+            let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+            match call_tool(name, arguments) {
+                Ok(Ok(value)) => json!({ "content": [{ "type": "text", "text": value.to_string() }], "isError": false }),
+                Ok(Err(message)) => json!({ "content": [{ "type": "text", "text": message }], "isError": true }),
+                Err((code, message)) => return Some(error(id, code, message)),
+            }
+        }
+        method => return Some(error(id, METHOD_NOT_FOUND, format!("no method {}", method))),
+    };
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+/// `yarmtl mcp`: answers MCP messages on stdin, one json object per line, until it closes.
+pub fn run() -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let answer = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle(&message),
+            Err(e) => Some(error(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+        if let Some(answer) = answer {
+            writeln!(stdout, "{}", answer)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handshake_and_tools() {
+        let initialize = json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2024-11-05" } });
+        let answer = handle(&initialize).unwrap();
+        assert_eq!(answer["result"]["protocolVersion"], "2024-11-05");
+        assert!(answer["result"]["capabilities"]["tools"].is_object());
+        assert!(handle(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).is_none());
+
+        let listed = handle(&json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" })).unwrap();
+        let names: Vec<&str> = listed["result"]["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["list_tasks", "search_tasks", "add_task", "complete_task"]);
+
+        let call = json!({ "jsonrpc": "2.0", "id": "x", "method": "tools/call", "params": { "name": "add_task", "arguments": {} } });
+        assert_eq!(handle(&call).unwrap()["error"]["code"], INVALID_PARAMS);
+        assert_eq!(handle(&json!({ "jsonrpc": "2.0", "id": 3, "method": "nope" })).unwrap()["error"]["code"], METHOD_NOT_FOUND);
+
+        let task = Task::parse("Renew passport #admin >town-hall //bring photos");
+        assert!(matches_search(&task, "passport PHOTOS"));
+        assert!(matches_search(&task, "town admin"));
+        assert!(!matches_search(&task, "passport visa"));
+    }
+}
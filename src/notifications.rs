@@ -0,0 +1,227 @@
+use crate::server::{self, TaskEdit};
+use crate::task::Task;
+use crate::error::YarmtlError;
+use crate::git::git_commit_tasks_with_message;
+use crate::recurrence;
+use crate::store::TaskStore;
+use chrono::{Days, NaiveDate, NaiveDateTime, Timelike};
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::thread::JoinHandle;
+
+/// Which nudges went out for which task, in the state directory.
+const STATE_FILE: &str = ".notifications.json";
+
+/// More nudges at once than this are raised as one notification listing them.
+pub const MAX_NOTIFICATIONS: usize = 3;
+
+/// The threads waiting for the buttons of the notifications raised.
+pub type Waiting = Vec<JoinHandle<()>>;
+
+/// The `[notifications]` table of config.toml.
+///
+/// ```toml
+/// [notifications]
+/// enabled = true
+/// from_hour = 9
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Raise them from `yarmtl --daemon`; `yarmtl notify` always does
+    pub enabled: bool,
+    /// Nothing is raised before this hour; reminders and tasks due today come at it
+    pub from_hour: u32,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        NotificationsConfig { enabled: true, from_hour: 9 }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Nudge {
+    /// The reminder date came, or a repeating reminder is due
+    Reminder,
+    DueToday,
+    /// The deadline passed; again when a moved deadline passes
+    Overdue,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct State {
+    tasks: BTreeMap<String, Notified>,
+}
+
+/// The day each nudge of a task went out for.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+struct Notified {
+    reminder: Option<NaiveDate>,
+    due: Option<NaiveDate>,
+    overdue: Option<NaiveDate>,
+}
+
+impl State {
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+}
+
+/// The nudges due at `now` that `state` hasn't seen go out, one per task with the most
+/// pressing first, and the state to keep for next time.
+pub fn pending<'a>(tasks: &'a [Task], state: &State, now: NaiveDateTime, from_hour: u32) -> (Vec<(Nudge, &'a Task)>, State) {
+    let today = now.date();
+    let mut nudges = Vec::new();
+    let mut next = State::default();
+    for task in tasks {
+        let mut seen = state.tasks.get(&task.id).copied().unwrap_or_default();
+        if !task.completed && now.hour() >= from_hour {
+            let mut fired = Vec::new();
+            let overdue = task.deadline.filter(|deadline| *deadline < today);
+            if overdue.is_some() && seen.overdue != overdue {
+                fired.push(Nudge::Overdue);
+                seen.overdue = overdue;
+            }
+            let due = task.deadline.filter(|deadline| *deadline == today);
+            if due.is_some() && seen.due != due {
+                fired.push(Nudge::DueToday);
+                seen.due = due;
+            }
+            let reminder = task
+                .reminder
+                .filter(|reminder| *reminder <= today)
+                .or_else(|| task.warning().filter(|warning| *warning <= today))
+                .or_else(|| recurrence::is_due(task, today).then_some(today));
+            if reminder.is_some() && seen.reminder != reminder {
+                fired.push(Nudge::Reminder);
+                seen.reminder = reminder;
+            }
+            nudges.extend(fired.first().map(|nudge| (*nudge, task)));
+        }
+        next.tasks.insert(task.id.clone(), seen);
+    }
+    (nudges, next)
+}
+
+pub fn title(nudge: Nudge, task: &Task) -> String {
+    match (nudge, task.deadline) {
+        (Nudge::Overdue, Some(deadline)) => format!("⚠ Overdue since {}", deadline.format("%Y-%m-%d")),
+        (Nudge::DueToday, _) => "📅 Due today".to_string(),
+        _ => "🔔 Reminder".to_string(),
+    }
+}
+
+/// Where the platform has them (freedesktop notifications), buttons to check the task
+/// off or be reminded again tomorrow. Waits for one in a thread of its own.
+fn raise(nudge: Nudge, task: &Task) -> Result<Option<JoinHandle<()>>, notify_rust::error::Error> {
+    let mut notification = Notification::new();
+    notification.appname("yarmtl").summary(&title(nudge, task)).body(&task.text);
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        notification.action("done", "Done").action("tomorrow", "Tomorrow");
+        if nudge == Nudge::Overdue {
+            notification.urgency(notify_rust::Urgency::Critical);
+        }
+        let handle = notification.show()?;
+        let id = task.id.clone();
+        Ok(Some(std::thread::spawn(move || handle.wait_for_action(|action| act(&id, action)))))
+    }
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    {
+        notification.show()?;
+        Ok(None)
+    }
+}
+
+/// What a click on a button of a nudge does.
+fn act(id: &str, action: &str) {
+    let result = match action {
+        "done" => {
+            let edit = TaskEdit { completed: Some(true), ..TaskEdit::default() };
+            server::edit(id, edit).map(|_| ()).map_err(|e| e.to_string())
+        }
+        "tomorrow" => remind_tomorrow(id).map(|_| ()).map_err(|e| e.to_string()),
+        _ => return,
+    };
+    if let Err(e) = result {
+        tracing::warn!("{}", e);
+    }
+}
+
+/// Moves the reminder of the task to tomorrow and commits it. Returns the task's text.
+pub fn remind_tomorrow(id: &str) -> Result<String, YarmtlError> {
+    let tomorrow = chrono::Local::now().date_naive() + Days::new(1);
+    let task = TaskStore::open_default()?.update(id, |task| task.reminder = Some(tomorrow))?;
+    git_commit_tasks_with_message(Some(&format!("🔔 Remind again tomorrow: \"{}\"", task.text)))?;
+    Ok(task.text)
+}
+
+/// Raises the nudges due at `now` that haven't gone out yet. The state is saved first, so
+/// a nudge that can't be raised, with no notification server around, isn't tried again.
+/// Returns how many went out and the threads waiting for their buttons.
+pub fn notify_pending(
+    state_dir: &Path,
+    tasks: &[Task],
+    now: NaiveDateTime,
+    config: &NotificationsConfig,
+) -> Result<(usize, Waiting), Box<dyn std::error::Error>> {
+    let state_path = state_dir.join(STATE_FILE);
+    let (nudges, state) = pending(tasks, &State::load(&state_path), now, config.from_hour);
+    state.save(&state_path)?;
+
+    if nudges.len() > MAX_NOTIFICATIONS {
+        let lines: Vec<String> = nudges.iter().map(|(nudge, task)| format!("{} {}", title(*nudge, task), task.text)).collect();
+        Notification::new()
+            .appname("yarmtl")
+            .summary(&format!("{} tasks need you", nudges.len()))
+            .body(&lines.join("\n"))
+            .show()?;
+        return Ok((nudges.len(), Vec::new()));
+    }
+    let mut waiting = Vec::new();
+    for (nudge, task) in &nudges {
+        waiting.extend(raise(*nudge, task)?);
+    }
+    Ok((nudges.len(), waiting))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_nudges() {
+        let at = |d, h| NaiveDate::from_ymd_opt(2025, 6, d).unwrap().and_hms_opt(h, 0, 0).unwrap();
+        let tasks = vec![
+            Task::parse("write report !2025-06-10 @2025-06-09 [id:aaaa01]"),
+            Task::parse("call mum !2025-06-12 [id:aaaa02]"),
+            Task::parse("water plants @2025-06-12 [id:aaaa03]"),
+            Task::parse("buy milk [id:aaaa04]"),
+        ];
+
+        // Nothing before the hour
+        let (nudges, state) = pending(&tasks, &State::default(), at(12, 7), 9);
+        assert!(nudges.is_empty());
+
+        let (nudges, state) = pending(&tasks, &state, at(12, 9), 9);
+        let seen: Vec<(Nudge, &str)> = nudges.iter().map(|(nudge, task)| (*nudge, task.id.as_str())).collect();
+        assert_eq!(seen, [(Nudge::Overdue, "aaaa01"), (Nudge::DueToday, "aaaa02"), (Nudge::Reminder, "aaaa03")]);
+        assert!(pending(&tasks, &state, at(12, 15), 9).0.is_empty());
+
+        // The next day, the task due yesterday is overdue
+        let (nudges, _) = pending(&tasks, &state, at(13, 9), 9);
+        assert!(matches!(&nudges[..], [(Nudge::Overdue, task)] if task.id == "aaaa02"));
+    }
+}
@@ -0,0 +1,164 @@
+use crate::todoist_auth::SecretStore;
+use reqwest::Client;
+use serde::Deserialize;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+/// Where an OAuth2 device flow signs in, and what for.
+pub struct Provider {
+    pub name: &'static str,
+    pub device_code_url: &'static str,
+    pub token_url: &'static str,
+    pub scope: &'static str,
+    /// The command that signs in again, for the errors that need it
+    pub setup_command: &'static str,
+    /// What the user is told when they didn't let yarmtl in
+    pub denied: &'static str,
+    /// Where the refresh token is kept between runs
+    pub refresh_token_store: SecretStore,
+}
+
+/// What the user is asked to do to let yarmtl in: open `verification_url` anywhere
+/// and enter `user_code` there.
+#[derive(Deserialize, Debug)]
+pub struct DeviceCode {
+    device_code: String,
+    pub user_code: String,
+    /// Microsoft calls it verification_uri
+    #[serde(alias = "verification_uri")]
+    pub verification_url: String,
+    /// Seconds the code is good for
+    expires_in: u64,
+    /// Seconds to wait between polls
+    #[serde(default = "poll_interval_by_default")]
+    interval: u64,
+}
+
+fn poll_interval_by_default() -> u64 {
+    5
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Signs in with the OAuth2 device flow, for an OAuth client the user registers with
+/// the provider, and gets access tokens from the refresh token that leaves behind.
+pub struct DeviceFlow {
+    client: Client,
+    provider: &'static Provider,
+    client_id: String,
+    /// Google's clients have one, Microsoft's public ones don't
+    client_secret: Option<String>,
+}
+
+impl DeviceFlow {
+    pub fn new(provider: &'static Provider, client_id: String, client_secret: Option<String>) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Failed to create HTTP client");
+        DeviceFlow { client, provider, client_id, client_secret }
+    }
+
+    /// Starts signing in.
+    pub async fn request_device_code(&self) -> Result<DeviceCode, Box<dyn Error>> {
+        let response = self
+            .client
+            .post(self.provider.device_code_url)
+            .form(&[("client_id", self.client_id.as_str()), ("scope", self.provider.scope)])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(format!("{} turned down the client id: {}", self.provider.name, response.text().await?).into());
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Polls until the user let yarmtl in, keeps the refresh token and returns an access
+    /// token. Fails when the user says no or the code runs out.
+    pub async fn wait_for_approval(&self, code: &DeviceCode) -> Result<String, Box<dyn Error>> {
+        let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+        let mut interval = code.interval;
+        while Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            let token = self
+                .token(&[
+                    ("device_code", code.device_code.as_str()),
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ])
+                .await?;
+            match token.error.as_deref() {
+                None => {
+                    let refresh_token = token.refresh_token.ok_or_else(|| format!("{} sent no refresh token", self.provider.name))?;
+                    self.provider.refresh_token_store.store(&refresh_token)?;
+                    return token.access_token.ok_or_else(|| format!("{} sent no access token", self.provider.name).into());
+                }
+                Some("authorization_pending") => {}
+                Some("slow_down") => interval += 5,
+                Some(error) => return Err(self.describe(error, token.error_description).into()),
+            }
+        }
+        Err(format!("The code ran out before it was entered, run '{}' again", self.provider.setup_command).into())
+    }
+
+    /// An access token for now, from the refresh token kept by signing in.
+    pub async fn access_token(&self) -> Result<String, Box<dyn Error>> {
+        let store = &self.provider.refresh_token_store;
+        let refresh_token = store
+            .get()
+            .map_err(|_| format!("Not signed in to {}, run '{}'", self.provider.name, self.provider.setup_command))?;
+        let token = self
+            .token(&[("refresh_token", refresh_token.as_str()), ("grant_type", "refresh_token")])
+            .await?;
+        // Microsoft hands out a new refresh token with each access token
+        if let Some(renewed) = token.refresh_token.as_ref().filter(|renewed| **renewed != refresh_token) {
+            store.store(renewed)?;
+        }
+        match (token.access_token, token.error) {
+            (Some(access_token), None) => Ok(access_token),
+            (_, error) => Err(format!(
+                "{}, run '{}' again",
+                self.describe(error.as_deref().unwrap_or("no access token"), token.error_description),
+                self.provider.setup_command
+            )
+            .into()),
+        }
+    }
+
+    async fn token(&self, grant: &[(&str, &str)]) -> Result<TokenResponse, Box<dyn Error>> {
+        let mut form = vec![("client_id", self.client_id.as_str())];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret.as_str()));
+        }
+        form.extend_from_slice(grant);
+        // Errors come as 4xx with a body that tells what they are
+        Ok(self.client.post(self.provider.token_url).form(&form).send().await?.json().await?)
+    }
+
+    fn describe(&self, error: &str, description: Option<String>) -> String {
+        match (error, description) {
+            ("access_denied", _) => self.provider.denied.to_string(),
+            (error, Some(description)) => format!("{} sign-in failed: {} ({})", self.provider.name, description, error),
+            (error, None) => format!("{} sign-in failed: {}", self.provider.name, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_codes_of_google_and_microsoft() {
+        let google = r#"{"device_code": "AH-1", "user_code": "GQVQ-JKEC", "verification_url": "https://www.google.com/device", "expires_in": 1800, "interval": 5}"#;
+        let microsoft = r#"{"device_code": "DAQA", "user_code": "FJ8K2MNPL", "verification_uri": "https://microsoft.com/devicelogin", "expires_in": 900, "message": "To sign in..."}"#;
+        assert_eq!(serde_json::from_str::<DeviceCode>(google).unwrap().user_code, "GQVQ-JKEC");
+        let code: DeviceCode = serde_json::from_str(microsoft).unwrap();
+        assert_eq!((code.verification_url.as_str(), code.interval), ("https://microsoft.com/devicelogin", 5));
+    }
+}
@@ -0,0 +1,328 @@
+//! What the command line prints, and what `--quiet` leaves out of it: the emoji, symbols
+//! and blank lines decorating the output, so scripts can read the rest of it. `--ascii`
+//! (or `ascii = true` under [ui]) keeps the decoration but as plain ASCII markers, for
+//! terminals, logs and consoles that can't show emoji. `--color` and NO_COLOR decide
+//! whether what is listed is colored.
+
+use clap::ValueEnum;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static ASCII: AtomicBool = AtomicBool::new(false);
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Set once at startup from `--quiet`.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Set once at startup from `--ascii` and the [ui] table of config.toml.
+pub fn set_ascii(ascii: bool) {
+    ASCII.store(ascii, Ordering::Relaxed);
+}
+
+pub fn is_ascii() -> bool {
+    ASCII.load(Ordering::Relaxed)
+}
+
+/// When `--color` colors the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    /// on a terminal, unless NO_COLOR is set or TERM is dumb
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color: `auto` looks at NO_COLOR, TERM and whether stdout is a terminal.
+    fn colors(self, no_color: bool, dumb: bool, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => !no_color && !dumb && terminal,
+        }
+    }
+}
+
+/// Set once at startup from `--color`.
+pub fn set_color(choice: ColorChoice) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let dumb = std::env::var_os("TERM").is_some_and(|term| term == "dumb");
+    COLOR.store(choice.colors(no_color, dumb, std::io::stdout().is_terminal()), Ordering::Relaxed);
+}
+
+/// What a part of a listed task is, for its color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    /// Red: a deadline gone by, the heading of the overdue tasks
+    Overdue,
+    /// Yellow: due today
+    Today,
+    /// Green
+    Tag,
+    /// Dim: done and cancelled tasks
+    Muted,
+    /// Bold
+    Heading,
+}
+
+impl Style {
+    fn ansi(self, text: &str) -> String {
+        let code = match self {
+            Style::Overdue => "1;31",
+            Style::Today => "33",
+            Style::Tag => "32",
+            Style::Muted => "2",
+            Style::Heading => "1",
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    }
+}
+
+/// `text` in the color of `style`, when the output is colored. `--quiet` never is.
+pub fn paint(text: &str, style: Style) -> String {
+    match COLOR.load(Ordering::Relaxed) && !is_quiet() {
+        true => style.ansi(text),
+        false => text.to_string(),
+    }
+}
+
+/// The symbols meaning the same thing, what `--ascii` writes for them in text, and in the
+/// one cell the TUI has for them.
+const MARKERS: &[(&str, &str, char)] = &[
+    ("⚠⏰🔴🔥❌✗🚫🛑⛔", "[!]", '!'),
+    ("⭐⏫🔺🔼★", "[*]", '*'),
+    ("✅✓✔☑", "[x]", 'x'),
+    ("☒", "[-]", '-'),
+    ("☐", "[ ]", 'o'),
+    ("📅🗓📆", "due:", '!'),
+    ("🏷", "tag:", '#'),
+    ("→➜⇒↪", "->", '>'),
+    ("←↩", "<-", '<'),
+    ("↑", "^", '^'),
+    ("↓", "v", 'v'),
+    ("►▶", ">", '>'),
+    ("●◉•", "*", '*'),
+    ("○", "o", 'o'),
+    ("━─═⣀", "-", '-'),
+    ("│┃║▏⡇", "|", '|'),
+    ("┌┐└┘╭╮╰╯╔╗╚╝┬┴├┤┼", "+", '+'),
+    ("█▇▆▅▄▃▂▁⣿", "#", '#'),
+];
+
+fn marker(c: char) -> Option<&'static (&'static str, &'static str, char)> {
+    MARKERS.iter().find(|(symbols, _, _)| symbols.contains(c))
+}
+
+/// Emoji and the symbols yarmtl marks lines with: ✅, ☐, ⚠️, →, the ━━━ of headings.
+fn is_decoration(c: char) -> bool {
+    matches!(c,
+        '\u{2190}'..='\u{21FF}' // arrows
+        | '\u{2300}'..='\u{23FF}' // technical symbols like ⏰
+        | '\u{2500}'..='\u{25FF}' // box drawing, blocks and shapes
+        | '\u{2600}'..='\u{27BF}' // symbols and dingbats like ☐ ✓ ❌
+        | '\u{2B00}'..='\u{2BFF}' // stars and more arrows
+        | '\u{1F000}'..='\u{1FAFF}' // emoji
+        | '\u{FE0F}' | '\u{200D}' | '\u{20E3}' // what emoji are put together with
+    )
+}
+
+/// Around decoration yarmtl puts in the middle of a line, next to what the user wrote:
+/// characters for private use, which nothing else prints.
+const DECO_START: char = '\u{E000}';
+const DECO_END: char = '\u{E001}';
+
+/// `symbol` marked as decoration, so `--quiet` can leave it out of the middle of a line
+/// without touching the task text around it.
+pub fn deco(symbol: &str) -> String {
+    format!("{}{}{}", DECO_START, symbol, DECO_END)
+}
+
+/// `text` without the marks `deco` put around its decoration.
+fn unmarked(text: &str) -> String {
+    text.replace([DECO_START, DECO_END], "")
+}
+
+/// `line` without the decoration yarmtl adds, keeping its indentation: the one symbol
+/// it starts with, like ✅ or ☐, what `deco` marked and lines of nothing but symbols,
+/// like the ━━━ of headings. Anything else is kept as it is, emoji in task text too.
+pub fn plain(line: &str) -> String {
+    let rest = line.trim_start();
+    let indent = &line[..line.len() - rest.len()];
+    if unmarked(rest).chars().all(|c| is_decoration(c) || c.is_whitespace()) {
+        return String::new();
+    }
+
+    let mut chars = rest.chars().peekable();
+    if chars.next_if(|c| is_decoration(*c)).is_some() {
+        // What the symbol is put together with, then the spaces after it
+        while let Some(c) = chars.next_if(|c| matches!(c, '\u{FE0F}' | '\u{200D}' | '\u{20E3}' | '\u{1F3FB}'..='\u{1F3FF}')) {
+            if c == '\u{200D}' {
+                chars.next();
+            }
+        }
+        while chars.next_if_eq(&' ').is_some() {}
+    }
+
+    let mut out = indent.to_string();
+    let (mut in_deco, mut removed) = (false, false);
+    for c in chars {
+        match c {
+            DECO_START => in_deco = true,
+            DECO_END => (in_deco, removed) = (false, true),
+            _ if in_deco => {}
+            // The space next to what was left out goes with it
+            ' ' if removed && (out.len() == indent.len() || out.ends_with(' ')) => {}
+            _ => {
+                removed = false;
+                out.push(c);
+            }
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// `text` with ASCII markers for its symbols: `[!]` for warnings and overdue tasks, `[*]`
+/// for stars, `[x]` for done, `due:` and `tag:`. Emoji meaning nothing in particular go,
+/// with the space after them.
+pub fn ascii(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut removed = false;
+    for c in text.chars() {
+        if let Some((_, marker, _)) = marker(c) {
+            out.push_str(marker);
+            removed = false;
+        } else if is_decoration(c) {
+            // What emoji are put together with goes with the marker before it
+            removed = removed || !matches!(c, '\u{FE0F}' | '\u{200D}' | '\u{20E3}');
+        } else if c == ' ' && removed && at_word_start(&out) {
+            continue;
+        } else {
+            removed = removed && c == ' ';
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Whether what comes after `out` starts a word, not counting the colors it ends with.
+fn at_word_start(out: &str) -> bool {
+    let mut rest = out;
+    while let Some(start) = rest.rfind("\x1b[")
+        && rest[start + 2..].strip_suffix('m').is_some_and(|code| code.chars().all(|c| c.is_ascii_digit() || c == ';'))
+    {
+        rest = &rest[..start];
+    }
+    rest.is_empty() || rest.ends_with([' ', '\n'])
+}
+
+/// The one ASCII character the TUI draws in a cell holding `symbol`, when it is decoration.
+pub fn ascii_cell(symbol: &str) -> Option<char> {
+    let c = symbol.chars().next()?;
+    match marker(c) {
+        Some((_, _, cell)) => Some(*cell),
+        None => is_decoration(c).then_some(' '),
+    }
+}
+
+/// `text` as it is shown: as it is, or with ASCII markers under `--ascii`.
+pub fn symbols(text: &str) -> String {
+    match is_ascii() {
+        true => ascii(&unmarked(text)),
+        false => unmarked(text),
+    }
+}
+
+/// The lines of `text` as they are printed: all of them, under `--ascii` with markers for
+/// their symbols, or under `--quiet` plain and without the ones left empty.
+pub fn lines(text: &str) -> Vec<String> {
+    match is_quiet() {
+        false => text.split('\n').map(symbols).collect(),
+        true => plain_lines(text),
+    }
+}
+
+/// The lines of `text` under `--quiet`.
+fn plain_lines(text: &str) -> Vec<String> {
+    text.split('\n').map(plain).filter(|line| !line.trim().is_empty()).collect()
+}
+
+/// println! for what yarmtl tells on the command line.
+pub fn say(text: &str) {
+    let mut stdout = std::io::stdout().lock();
+    for line in lines(text) {
+        // Whatever read it, like `yarmtl --list | head`, has what it wanted
+        if let Err(e) = writeln!(stdout, "{}", line) {
+            match e.kind() {
+                std::io::ErrorKind::BrokenPipe => std::process::exit(0),
+                _ => panic!("failed printing to stdout: {}", e),
+            }
+        }
+    }
+}
+
+/// eprintln! for what yarmtl tells on the command line.
+pub fn say_err(text: &str) {
+    for line in lines(text) {
+        eprintln!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_lines_keep_what_scripts_read() {
+        assert_eq!(plain("📥 Imported 1 new and 0 changed task(s)"), "Imported 1 new and 0 changed task(s)");
+        let listed = format!("  ☐  call bob [aaaa01] {} #work (added today)", deco("🏷️"));
+        assert_eq!(plain(&listed), "  call bob [aaaa01] #work (added today)");
+        assert_eq!(plain("⚠️  Not pushed\n"), "Not pushed");
+        assert_eq!(plain("   → `git push`"), "   `git push`");
+        assert_eq!(plain("━━━━━━"), "");
+        assert_eq!(plain(&format!("🔢 \"pack boxes\" 10/10 {} done", deco("✅"))), "\"pack boxes\" 10/10 done");
+        assert_eq!(plain("id | text   | due"), "id | text   | due");
+        assert_eq!(plain_lines("✅ Synced\n\n━━━\n  2 created"), ["Synced", "  2 created"]);
+    }
+
+    #[test]
+    fn test_plain_keeps_task_text() {
+        assert_eq!(plain("  ☐  rename ☐ → ✓ in ui 🎉 [aaaa01]"), "  rename ☐ → ✓ in ui 🎉 [aaaa01]");
+        assert_eq!(plain("☑  🎉 party"), "🎉 party");
+        assert_eq!(plain("👩‍💻 pair on it"), "pair on it");
+        assert_eq!(plain(&format!("x [aaaa01] {} !2025-04-15 {} #🎉", deco("📅"), deco("🏷️"))), "x [aaaa01] !2025-04-15 #🎉");
+        assert_eq!(unmarked(&format!("x {} #a", deco("🏷️"))), "x 🏷️ #a");
+    }
+
+    #[test]
+    fn test_color_choice() {
+        assert!(ColorChoice::Auto.colors(false, false, true));
+        assert!(!ColorChoice::Auto.colors(false, false, false), "piped");
+        assert!(!ColorChoice::Auto.colors(true, false, true), "NO_COLOR");
+        assert!(!ColorChoice::Auto.colors(false, true, true), "TERM=dumb");
+        assert!(ColorChoice::Always.colors(true, true, false));
+        assert!(!ColorChoice::Never.colors(false, false, true));
+        assert_eq!(Style::Overdue.ansi("!2025-04-15"), "\x1b[1;31m!2025-04-15\x1b[0m");
+    }
+
+    #[test]
+    fn test_ascii_markers() {
+        assert_eq!(ascii("  ☐  file taxes [aaaa01] ⚠️ !2025-04-15 (overdue) 🏷️ #home ⭐ $3"), "  [ ]  file taxes [aaaa01] [!] !2025-04-15 (overdue) tag: #home [*] $3");
+        assert_eq!(ascii("📂 Working directory: ~/notes\n🔑 Token saved, → next"), "Working directory: ~/notes\nToken saved, -> next");
+        assert_eq!(ascii("✅ Done yesterday\n  filed taxes"), "[x] Done yesterday\n  filed taxes");
+        assert_eq!(ascii("crème brûlée"), "crème brûlée");
+        assert_eq!(ascii("\x1b[1m🔮 LATER:\x1b[0m"), "\x1b[1mLATER:\x1b[0m");
+        assert_eq!(ascii_cell("⚠\u{FE0F}"), Some('!'));
+        assert_eq!(ascii_cell("─"), Some('-'));
+        assert_eq!(ascii_cell("🍅"), Some(' '));
+        assert_eq!(ascii_cell("é"), None);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::encryption;
+use crate::hash;
+use crate::setup;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Under the data and config directories, a directory per tasks directory.
+const WORKSPACES_DIR: &str = "workspaces";
+
+const EMAIL_CONFIG: &str = "email_config.toml";
+
+/// Left in the tasks directory by older versions once they were set up.
+const SETTINGS_MARKER: &str = ".yarmtl_settings";
+
+/// What older versions kept in the tasks directory that belongs in the state directory.
+const STATE_FILES: [&str; 13] = [
+    ".sync_metadata.json",
+    ".caldav_metadata.json",
+    ".google_metadata.json",
+    ".github_metadata.json",
+    ".notifications.json",
+    ".push_state.json",
+    ".webhook_state.json",
+    ".overdue_hooks.json",
+    "webhooks_failed.jsonl",
+    "daemon.log",
+    "daemon_status.json",
+    ".tasks.lock",
+    ".sync.lock",
+];
+
+/// Where `--dir` pointed, or where yarmtl runs.
+static WORKING_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_working_dir(path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let work_dir = if let Some(p) = path {
+        let pb = PathBuf::from(p);
+        if !pb.exists() {
+            fs::create_dir_all(&pb)?;
+            println!("📁 Created directory: {}", pb.display());
+        }
+        if !pb.is_dir() {
+            return Err(format!("Path {} is not a directory", pb.display()).into());
+        }
+        pb.canonicalize()?
+    } else {
+        env::current_dir()?
+    };
+    
+    let _ = WORKING_DIR.set(work_dir);
+    Ok(())
+}
+
+pub fn get_working_dir() -> PathBuf {
+    WORKING_DIR.get().cloned().unwrap_or_else(|| env::current_dir().unwrap())
+}
+
+pub fn get_sync_dir() -> PathBuf {
+    setup::load_settings()
+        .map(|settings| settings.tasks_dir)
+        .unwrap_or_else(setup::default_tasks_dir)
+}
+
+pub fn get_tasks_file_path() -> PathBuf {
+    let sync_dir = get_sync_dir();
+    // When it can't be made, writing tasks.md says why
+    if !sync_dir.exists() {
+        let _ = fs::create_dir_all(&sync_dir);
+    }
+    encryption::file_in(&sync_dir, "tasks.md")
+}
+
+/// Where the state of the tasks directory `tasks_dir` that only means something on this
+/// machine is kept: what it was synced with, which notifications went out, the daemon's
+/// log. Outside of it, so it stays out of its git repository and two tasks directories
+/// don't mix theirs up.
+pub fn state_dir_of(tasks_dir: &Path) -> PathBuf {
+    setup::data_dir().join(WORKSPACES_DIR).join(workspace_key(tasks_dir))
+}
+
+/// The state directory of the tasks directory, made if it isn't there yet.
+pub fn get_state_dir() -> PathBuf {
+    let state_dir = state_dir_of(&get_sync_dir());
+    // When it can't be made, writing the first file there says why
+    if !state_dir.exists() {
+        let _ = fs::create_dir_all(&state_dir);
+    }
+    state_dir
+}
+
+/// Where the config of the tasks directory `tasks_dir` that only applies on this machine
+/// is kept, like its state: never in it, so nothing synced into it lands there.
+pub fn config_dir_of(tasks_dir: &Path) -> PathBuf {
+    setup::config_dir().join(WORKSPACES_DIR).join(workspace_key(tasks_dir))
+}
+
+/// email_config.toml of the tasks directory, with the config of this machine rather than
+/// in the tasks directory: the SMTP server can differ from one to the next.
+pub fn get_email_config_path() -> PathBuf {
+    config_dir_of(&get_sync_dir()).join(EMAIL_CONFIG)
+}
+
+/// The settings file `file` of a sync backend, in the tasks directory `tasks_dir` next to
+/// config.toml. Only the task files are committed, so it stays out of git.
+pub fn sync_config_path(tasks_dir: &Path, file: &str) -> PathBuf {
+    tasks_dir.join(file)
+}
+
+pub fn get_todoist_config_path() -> PathBuf {
+    sync_config_path(&get_sync_dir(), "todoist_config.toml")
+}
+
+pub fn get_config_path() -> PathBuf {
+    get_sync_dir().join("config.toml")
+}
+
+/// Moves what older versions kept in the tasks directory, and email_config.toml from the
+/// directory yarmtl ran in, to where it is kept now: the state directory and the config
+/// directory. A `.yarmtl_settings` marker of the tasks directory becomes settings.toml.
+/// Returns what was moved, and where to.
+pub fn migrate_state() -> Vec<(PathBuf, PathBuf)> {
+    let tasks_dir = get_sync_dir();
+    let state_dir = state_dir_of(&tasks_dir);
+    let mut moves: Vec<(PathBuf, PathBuf)> = STATE_FILES.iter().map(|name| (tasks_dir.join(name), state_dir.join(name))).collect();
+    let email_config = get_email_config_path();
+    moves.extend([get_working_dir(), tasks_dir.clone()].map(|dir| (dir.join(EMAIL_CONFIG), email_config.clone())));
+
+    let mut moved = Vec::new();
+    for (from, to) in moves {
+        if from.is_file() && !to.exists() && move_file(&from, &to).is_ok() {
+            moved.push((from, to));
+        }
+    }
+
+    let marker = tasks_dir.join(SETTINGS_MARKER);
+    if marker.exists() && !setup::settings_path().exists() {
+        let settings = setup::Settings { tasks_dir: tasks_dir.clone() };
+        let written = toml::to_string_pretty(&settings).ok().and_then(|content| {
+            fs::create_dir_all(setup::data_dir()).ok()?;
+            fs::write(setup::settings_path(), content).ok()
+        });
+        if written.is_some() && fs::remove_file(&marker).is_ok() {
+            moved.push((marker, setup::settings_path()));
+        }
+    }
+    moved
+}
+
+/// Renames `from` to `to`, copying it when they are on different file systems.
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(dir) = to.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// The name of the state of `tasks_dir`: its own name and a hash of its whole path, so
+/// two tasks directories both called tasks each have theirs.
+fn workspace_key(tasks_dir: &Path) -> String {
+    let path = tasks_dir.canonicalize().unwrap_or_else(|_| tasks_dir.to_path_buf());
+    let hash = hash::fnv1a(path.to_string_lossy().bytes());
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let name: String = name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+    format!("{}-{:08x}", name, hash as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspaces_with_the_same_name_are_kept_apart() {
+        let home = workspace_key(Path::new("/home/me/tasks"));
+        let work = workspace_key(Path::new("/home/me/work/tasks"));
+        assert!(home.starts_with("tasks-") && work.starts_with("tasks-"));
+        assert_ne!(home, work);
+        assert_eq!(home, workspace_key(Path::new("/home/me/tasks")));
+        assert!(workspace_key(Path::new("/home/me/my tasks")).starts_with("my_tasks-"));
+    }
+}
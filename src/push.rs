@@ -0,0 +1,269 @@
+use crate::notifications::{self, MAX_NOTIFICATIONS, Nudge, State};
+use crate::task::Task;
+use crate::telegram::Bot;
+use chrono::NaiveDateTime;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use std::time::Duration;
+
+/// Which alerts went out to the push channels for which task, in the state directory.
+const STATE_FILE: &str = ".push_state.json";
+
+const NTFY_SERVER: &str = "https://ntfy.sh";
+const PUSHOVER_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// Discord turns down longer messages.
+const DISCORD_MAX_CHARS: usize = 2000;
+
+/// What a channel is sent: the morning reminder digest, or an alert for one task.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PushKind {
+    /// The day's reminders in one message, at 5 AM like the email
+    Digest,
+    Reminder,
+    DueToday,
+    Overdue,
+}
+
+impl From<Nudge> for PushKind {
+    fn from(nudge: Nudge) -> Self {
+        match nudge {
+            Nudge::Reminder => PushKind::Reminder,
+            Nudge::DueToday => PushKind::DueToday,
+            Nudge::Overdue => PushKind::Overdue,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "service", rename_all = "lowercase")]
+pub enum PushService {
+    Ntfy {
+        #[serde(default = "ntfy_server")]
+        server: String,
+        topic: String,
+        /// For topics that need an access token
+        token: Option<String>,
+    },
+    Pushover {
+        /// The user (or group) key
+        user: String,
+        /// The application's API token
+        token: String,
+    },
+    Gotify {
+        server: String,
+        /// The application's token
+        token: String,
+    },
+    Telegram {
+        /// The bot's token, from BotFather
+        token: String,
+        /// The chat it writes to, and the only one `yarmtl telegram` takes tasks from
+        chat_id: i64,
+    },
+    Slack {
+        /// The channel's incoming webhook
+        url: String,
+    },
+    Discord {
+        /// The channel's webhook, from its integrations settings
+        url: String,
+    },
+}
+
+fn ntfy_server() -> String {
+    NTFY_SERVER.to_string()
+}
+
+/// A push notification service, one `[[push]]` table of config.toml.
+///
+/// ```toml
+/// [[push]]
+/// service = "ntfy"
+/// topic = "yarmtl-k3m9x2"
+/// send = ["digest", "overdue"]
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct PushChannel {
+    #[serde(flatten)]
+    pub service: PushService,
+    /// What it is sent, everything when empty
+    #[serde(default)]
+    pub send: Vec<PushKind>,
+    /// Only tasks with one of these tags, all of them when empty
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl PushChannel {
+    pub fn wants(&self, kind: PushKind) -> bool {
+        self.send.is_empty() || self.send.contains(&kind)
+    }
+
+    /// Whether the task goes to this channel, by its tags.
+    pub fn routes(&self, task: &Task) -> bool {
+        self.tags.is_empty() || task.tags.iter().any(|tag| self.tags.contains(tag))
+    }
+
+    /// The service and where it goes, for messages.
+    pub fn name(&self) -> String {
+        match &self.service {
+            PushService::Ntfy { server, topic, .. } => format!("ntfy {}/{}", server.trim_end_matches('/'), topic),
+            PushService::Pushover { .. } => "pushover".to_string(),
+            PushService::Gotify { server, .. } => format!("gotify {}", server),
+            PushService::Telegram { chat_id, .. } => format!("telegram chat {}", chat_id),
+            // Their URLs are the secret, so they stay out of messages
+            PushService::Slack { .. } => "slack".to_string(),
+            PushService::Discord { .. } => "discord".to_string(),
+        }
+    }
+}
+
+/// How loudly a message arrives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    Normal,
+    High,
+}
+
+fn client() -> Client {
+    Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent(concat!("yarmtl/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Failed to create HTTP client")
+}
+
+/// Publishes one message to the channel.
+pub async fn send(channel: &PushChannel, title: &str, message: &str, priority: Priority) -> Result<(), String> {
+    let client = client();
+    let high = priority == Priority::High;
+    let request = match &channel.service {
+        // As json, headers can't hold the emoji of the titles
+        PushService::Ntfy { server, topic, token } => {
+            let mut request = client.post(server.trim_end_matches('/')).json(&json!({
+                "topic": topic,
+                "title": title,
+                "message": message,
+                "priority": if high { 4 } else { 3 },
+                "tags": [if high { "warning" } else { "bell" }],
+            }));
+            if let Some(token) = token {
+                request = request.bearer_auth(token);
+            }
+            request
+        }
+        PushService::Pushover { user, token } => client.post(PUSHOVER_URL).form(&[
+            ("token", token.as_str()),
+            ("user", user.as_str()),
+            ("title", title),
+            ("message", message),
+            ("priority", if high { "1" } else { "0" }),
+        ]),
+        PushService::Gotify { server, token } => client
+            .post(format!("{}/message", server.trim_end_matches('/')))
+            .header("X-Gotify-Key", token)
+            .json(&json!({ "title": title, "message": message, "priority": if high { 8 } else { 5 } })),
+        PushService::Telegram { token, chat_id } => {
+            let text = format!("{}\n{}", title, message);
+            return Bot::new(token).send_message(*chat_id, &text).await.map_err(|e| format!("{}: {}", channel.name(), e));
+        }
+        PushService::Slack { url } => {
+            let escape = |text: &str| text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+            client.post(url).json(&json!({ "text": format!("*{}*\n{}", escape(title), escape(message)) }))
+        }
+        PushService::Discord { url } => {
+            let mut content = format!("**{}**\n{}", title, message);
+            if content.chars().count() > DISCORD_MAX_CHARS {
+                content = content.chars().take(DISCORD_MAX_CHARS - 1).collect::<String>() + "…";
+            }
+            client.post(url).json(&json!({ "content": content }))
+        }
+    };
+    match request.send().await {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => Err(format!("{} replied {}", channel.name(), response.status())),
+        Err(e) => Err(format!("{}: {}", channel.name(), e)),
+    }
+}
+
+/// Sends each channel the alerts due at `now` it wants and hasn't had, like the desktop
+/// notifications: once per reminder, due day and missed deadline. Returns how many went out.
+pub async fn alert_pending(
+    state_dir: &Path,
+    tasks: &[Task],
+    now: NaiveDateTime,
+    from_hour: u32,
+    channels: &[PushChannel],
+) -> std::io::Result<usize> {
+    let alerts = [PushKind::Reminder, PushKind::DueToday, PushKind::Overdue];
+    let alerting: Vec<&PushChannel> = channels.iter().filter(|channel| alerts.iter().any(|kind| channel.wants(*kind))).collect();
+    if alerting.is_empty() {
+        return Ok(0);
+    }
+    let state_path = state_dir.join(STATE_FILE);
+    let (nudges, state) = notifications::pending(tasks, &State::load(&state_path), now, from_hour);
+    state.save(&state_path)?;
+
+    let mut sent = 0;
+    for channel in alerting {
+        let mine: Vec<&(Nudge, &Task)> =
+            nudges.iter().filter(|(nudge, task)| channel.wants((*nudge).into()) && channel.routes(task)).collect();
+        let messages: Vec<(String, String, Priority)> = if mine.len() > MAX_NOTIFICATIONS {
+            let lines: Vec<String> = mine.iter().map(|(nudge, task)| format!("{} {}", notifications::title(*nudge, task), task.text)).collect();
+            let overdue = mine.iter().any(|(nudge, _)| *nudge == Nudge::Overdue);
+            vec![(format!("{} tasks need you", mine.len()), lines.join("\n"), if overdue { Priority::High } else { Priority::Normal })]
+        } else {
+            mine.iter()
+                .map(|(nudge, task)| {
+                    let priority = if *nudge == Nudge::Overdue { Priority::High } else { Priority::Normal };
+                    (notifications::title(*nudge, task), task.text.clone(), priority)
+                })
+                .collect()
+        };
+        for (title, message, priority) in messages {
+            match send(channel, &title, &message, priority).await {
+                Ok(()) => sent += 1,
+                Err(e) => tracing::warn!("couldn't push to {}", e),
+            }
+        }
+    }
+    Ok(sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_channels_from_config() {
+        let config = r#"
+            [[push]]
+            service = "ntfy"
+            topic = "yarmtl-k3m9x2"
+            send = ["digest", "overdue"]
+
+            [[push]]
+            service = "gotify"
+            server = "https://gotify.example.com/"
+            token = "AbC"
+
+            [[push]]
+            service = "slack"
+            url = "https://hooks.slack.com/services/T0/B0/x"
+            tags = ["work"]
+        "#;
+        let channels = toml::from_str::<Config>(config).unwrap().push;
+        assert_eq!(channels[0].name(), "ntfy https://ntfy.sh/yarmtl-k3m9x2");
+        assert!(channels[0].wants(PushKind::Overdue) && !channels[0].wants(PushKind::Reminder));
+        assert!(channels[1].wants(PushKind::DueToday));
+        assert!(channels[2].routes(&Task::parse("send invoice #work")));
+        assert!(!channels[2].routes(&Task::parse("buy milk #home")) && channels[1].routes(&Task::parse("buy milk")));
+        assert!(toml::from_str::<Config>("[[push]]\nservice = \"pager\"\n").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::task::Task;
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// The `[quiet]` table of config.toml: when the daemon leaves you alone.
+///
+/// ```toml
+/// [quiet]
+/// start_hour = 22
+/// end_hour = 7
+/// weekends = true
+/// holidays = ["2025-12-25", "2025-12-24..2026-01-01"]
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct QuietConfig {
+    /// No notifications from this hour on, until `end_hour`; they may wrap past midnight
+    pub start_hour: Option<u32>,
+    pub end_hour: Option<u32>,
+    /// Saturdays and Sundays are days off
+    pub weekends: bool,
+    /// Days off, one date or a range of them, both ends included
+    pub holidays: Vec<Holiday>,
+}
+
+/// A day off, or a run of them.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Holiday {
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+}
+
+impl TryFrom<String> for Holiday {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let date = |date: &str| {
+            NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").map_err(|_| format!("{} isn't a date like 2025-12-25", date.trim()))
+        };
+        let (first, last) = match text.split_once("..") {
+            Some((first, last)) => (date(first)?, date(last)?),
+            None => (date(&text)?, date(&text)?),
+        };
+        if last < first {
+            return Err(format!("{} ends before it starts", text));
+        }
+        Ok(Holiday { first, last })
+    }
+}
+
+impl From<Holiday> for String {
+    fn from(holiday: Holiday) -> Self {
+        match holiday.first == holiday.last {
+            true => holiday.first.format("%Y-%m-%d").to_string(),
+            false => format!("{}..{}", holiday.first.format("%Y-%m-%d"), holiday.last.format("%Y-%m-%d")),
+        }
+    }
+}
+
+impl QuietConfig {
+    pub fn is_day_off(&self, day: NaiveDate) -> bool {
+        (self.weekends && matches!(day.weekday(), Weekday::Sat | Weekday::Sun))
+            || self.holidays.iter().any(|holiday| holiday.first <= day && day <= holiday.last)
+    }
+
+    /// Whether notifications hold off at `now`: on a day off, or in the quiet hours.
+    pub fn is_quiet(&self, now: NaiveDateTime) -> bool {
+        self.is_quiet_hour(now.hour()) || self.is_day_off(now.date())
+    }
+
+    /// Whether `hour` is in the quiet hours, on any day.
+    pub fn is_quiet_hour(&self, hour: u32) -> bool {
+        match (self.start_hour, self.end_hour) {
+            (Some(start), Some(end)) if start <= end => hour >= start && hour < end,
+            (Some(start), Some(end)) => hour >= start || hour < end,
+            _ => false,
+        }
+    }
+
+    /// The day itself, or the working day after it.
+    pub fn working_day(&self, mut day: NaiveDate) -> NaiveDate {
+        // A year of days off in a row is a misconfiguration, not a reason to hang
+        for _ in 0..366 {
+            if !self.is_day_off(day) {
+                break;
+            }
+            day = day + Days::new(1);
+        }
+        day
+    }
+
+    /// The tasks as the reminders see them: a deadline on a day off is due the working day
+    /// after. Only for reminding; never saved.
+    pub fn rolled(&self, tasks: &[Task]) -> Vec<Task> {
+        tasks
+            .iter()
+            .map(|task| Task { deadline: task.deadline.map(|deadline| self.working_day(deadline)), ..task.clone() })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_days_off_and_quiet_hours() {
+        let config: Config = toml::from_str(
+            "[quiet]\nstart_hour = 22\nend_hour = 7\nweekends = true\nholidays = [\"2025-12-25\", \"2025-12-29..2025-12-31\"]\n",
+        )
+        .unwrap();
+        let quiet = config.quiet;
+        let day = |m, d| NaiveDate::from_ymd_opt(2025, m, d).unwrap();
+        let at = |d, h| day(12, d).and_hms_opt(h, 0, 0).unwrap();
+
+        // 2025-12-22 is a Monday
+        assert!(!quiet.is_quiet(at(22, 9)));
+        assert!(quiet.is_quiet(at(22, 23)) && quiet.is_quiet(at(23, 6)));
+        assert!(quiet.is_quiet(at(25, 12)) && quiet.is_quiet(at(27, 12)));
+
+        // Friday the 26th is a working day; the weekend and the holidays after it roll to Jan 1st
+        assert_eq!(quiet.working_day(day(12, 26)), day(12, 26));
+        assert_eq!(quiet.working_day(day(12, 27)), NaiveDate::from_ymd_opt(2026, 1, 1).unwrap());
+        let rolled = quiet.rolled(&[Task::parse("wrap presents !2025-12-25")]);
+        assert_eq!(rolled[0].deadline, Some(day(12, 26)));
+
+        assert_eq!(String::from(quiet.holidays[1]), "2025-12-29..2025-12-31");
+        assert!(toml::from_str::<Config>("[quiet]\nholidays = [\"2025-12-31..2025-12-29\"]\n").is_err());
+    }
+}
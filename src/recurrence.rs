@@ -0,0 +1,209 @@
+use crate::task::Task;
+use chrono::{Datelike, NaiveDate, Weekday};
+
+/// How often a standing reminder (`@every monday`) fires. It is independent of the
+/// deadline, the task stays open and nags again on the next matching day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Recurrence {
+    Daily,
+    /// Monday to Friday
+    Weekdays,
+    Weekly(Weekday),
+    /// Day of the month, the last day in months that are shorter
+    Monthly(u32),
+}
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("monday", Weekday::Mon),
+    ("tuesday", Weekday::Tue),
+    ("wednesday", Weekday::Wed),
+    ("thursday", Weekday::Thu),
+    ("friday", Weekday::Fri),
+    ("saturday", Weekday::Sat),
+    ("sunday", Weekday::Sun),
+];
+
+impl Recurrence {
+    /// What follows "every": `day`, `weekday`, `monday` (or `mon`), `15th`.
+    pub fn parse(word: &str) -> Option<Self> {
+        let word = word.to_lowercase();
+        match word.as_str() {
+            "day" => return Some(Recurrence::Daily),
+            "weekday" => return Some(Recurrence::Weekdays),
+            _ => {}
+        }
+        if let Some((_, weekday)) = WEEKDAYS.iter().find(|(name, _)| *name == word || name[..3] == word) {
+            return Some(Recurrence::Weekly(*weekday));
+        }
+        let day = ["st", "nd", "rd", "th"].iter().find_map(|suffix| word.strip_suffix(suffix))?;
+        let day: u32 = day.parse().ok().filter(|d| (1..=31).contains(d))?;
+        (ordinal(day) == word).then_some(Recurrence::Monthly(day))
+    }
+
+    /// An Obsidian Tasks recurrence rule: `every day`, `every weekday`, `every week on
+    /// Monday`, `every month on the 15th`, or the `every monday` and `every 15th` yarmtl
+    /// writes. A bare `every week` or `every month` repeats on the day of `anchor`, the
+    /// due date. `when done` at the end is accepted, the reminder repeats either way.
+    pub fn parse_rule(phrase: &str, anchor: NaiveDate) -> Option<Self> {
+        let phrase = phrase.to_lowercase();
+        let phrase = phrase.strip_suffix(" when done").unwrap_or(&phrase);
+        let words: Vec<&str> = phrase.split_whitespace().collect();
+        match words[..] {
+            ["every", "week"] => Some(Recurrence::Weekly(anchor.weekday())),
+            ["every", "month"] => Some(Recurrence::Monthly(anchor.day())),
+            ["every", "week", "on", day] => Recurrence::parse(day).filter(|r| matches!(r, Recurrence::Weekly(_))),
+            ["every", "month", "on", "the", day] => {
+                Recurrence::parse(day).filter(|r| matches!(r, Recurrence::Monthly(_)))
+            }
+            ["every", word] => Recurrence::parse(word),
+            _ => None,
+        }
+    }
+
+    /// The rule the other way round, in the words Obsidian Tasks understands.
+    pub fn to_rule(self) -> String {
+        match self {
+            Recurrence::Weekly(weekday) => {
+                let name = WEEKDAYS.iter().find(|(_, w)| *w == weekday).map_or("monday", |(name, _)| name);
+                format!("every week on {}{}", name[..1].to_uppercase(), &name[1..])
+            }
+            Recurrence::Monthly(day) => format!("every month on the {}", ordinal(day)),
+            _ => self.to_string(),
+        }
+    }
+
+    pub fn matches(&self, date: NaiveDate) -> bool {
+        match self {
+            Recurrence::Daily => true,
+            Recurrence::Weekdays => date.weekday().num_days_from_monday() < 5,
+            Recurrence::Weekly(weekday) => date.weekday() == *weekday,
+            Recurrence::Monthly(day) => date.day() == (*day).min(days_in_month(date)),
+        }
+    }
+
+    /// The first day on or after `from` the reminder fires.
+    pub fn next_fire(&self, from: NaiveDate) -> NaiveDate {
+        // A month and a bit always holds a match
+        from.iter_days().take(32).find(|d| self.matches(*d)).unwrap_or(from)
+    }
+}
+
+impl std::fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Recurrence::Daily => write!(f, "every day"),
+            Recurrence::Weekdays => write!(f, "every weekday"),
+            Recurrence::Weekly(weekday) => {
+                let name = WEEKDAYS.iter().find(|(_, w)| w == weekday).map_or("monday", |(name, _)| name);
+                write!(f, "every {}", name)
+            }
+            Recurrence::Monthly(day) => write!(f, "every {}", ordinal(*day)),
+        }
+    }
+}
+
+fn ordinal(day: u32) -> String {
+    let suffix = match (day % 10, day % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", day, suffix)
+}
+
+fn days_in_month(date: NaiveDate) -> u32 {
+    let first = date.with_day(1).unwrap();
+    let next = first.checked_add_months(chrono::Months::new(1)).unwrap_or(first);
+    (next - first).num_days() as u32
+}
+
+/// Whether the task's repeating reminder should nag on `today`: it has fired on no
+/// matching day since it last did, or since the task was added. Days missed while
+/// nothing was running fire once, on the next run.
+pub fn is_due(task: &Task, today: NaiveDate) -> bool {
+    let Some(repeat) = task.repeat else { return false };
+    if task.completed {
+        return false;
+    }
+    let from = match (task.last_fired, task.created) {
+        (Some(fired), _) => fired.succ_opt().unwrap_or(fired),
+        (None, Some(created)) => created,
+        (None, None) => today,
+    };
+    repeat.next_fire(from) <= today
+}
+
+/// An open task with a reminder set for `day`, its deadline warning starting that day, or
+/// a repeating one that is due.
+pub fn has_reminder_on(task: &Task, day: NaiveDate) -> bool {
+    !task.completed && (task.reminder == Some(day) || task.warning() == Some(day) || is_due(task, day))
+}
+
+/// Why the task goes into the reminder email on `today`, None when it doesn't.
+pub fn reminder_reason(task: &Task, today: NaiveDate) -> Option<String> {
+    if task.completed {
+        return None;
+    }
+    match task.deadline {
+        Some(deadline) if deadline < today => return Some("deadline overdue".to_string()),
+        Some(deadline) if deadline == today => return Some("deadline due today".to_string()),
+        _ => {}
+    }
+    if task.reminder.is_some_and(|reminder| reminder <= today) {
+        return Some("reminder date reached".to_string());
+    }
+    if let Some(deadline) = task.deadline.filter(|_| task.warning().is_some_and(|warning| warning <= today)) {
+        return Some(match (deadline - today).num_days() {
+            1 => "deadline tomorrow".to_string(),
+            days => format!("deadline in {} days", days),
+        });
+    }
+    task.repeat.filter(|_| is_due(task, today)).map(|repeat| format!("reminder {}", repeat))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, FixedClock};
+
+    #[test]
+    fn test_parse_and_fire() {
+        let date = |m, d| NaiveDate::from_ymd_opt(2025, m, d).unwrap();
+        assert_eq!(Recurrence::parse("Monday"), Some(Recurrence::Weekly(Weekday::Mon)));
+        assert_eq!(Recurrence::parse("fri"), Some(Recurrence::Weekly(Weekday::Fri)));
+        assert_eq!(Recurrence::parse("31st"), Some(Recurrence::Monthly(31)));
+        assert_eq!(Recurrence::parse("2st"), None);
+        assert_eq!(Recurrence::parse("32nd"), None);
+        assert_eq!(Recurrence::Monthly(22).to_string(), "every 22nd");
+
+        // 2025-06-14 is a Saturday
+        assert_eq!(Recurrence::Weekdays.next_fire(date(6, 14)), date(6, 16));
+        assert_eq!(Recurrence::Monthly(31).next_fire(date(2, 1)), date(2, 28));
+
+        let mut task = Task::parse("water plants @every monday [created:2025-06-10]");
+        assert!(!is_due(&task, date(6, 15)));
+        assert!(is_due(&task, date(6, 16)));
+        // Missed on Monday, still due on Wednesday until it has fired
+        assert!(is_due(&task, date(6, 18)));
+        task.last_fired = Some(date(6, 18));
+        assert!(!is_due(&task, date(6, 22)));
+        assert!(is_due(&task, date(6, 23)));
+    }
+
+    #[test]
+    fn test_reminder_reasons() {
+        let clock = FixedClock::on(NaiveDate::from_ymd_opt(2025, 6, 16).unwrap());
+        let reason = |line: &str| reminder_reason(&Task::parse_at(line, &clock), clock.today());
+        assert_eq!(reason("pay rent !today @yesterday").as_deref(), Some("deadline due today"));
+        assert_eq!(reason("call back !yesterday").as_deref(), Some("deadline overdue"));
+        assert_eq!(reason("book flights !tomorrow @today").as_deref(), Some("reminder date reached"));
+        assert_eq!(reason("water plants @every monday").as_deref(), Some("reminder every monday"));
+        assert_eq!(reason("read !tomorrow @tomorrow"), None);
+        assert_eq!(reason("file taxes !2025-06-19 [warn:3d]").as_deref(), Some("deadline in 3 days"));
+        assert_eq!(reason("file taxes !2025-06-17 [warn:1w]").as_deref(), Some("deadline tomorrow"));
+        assert_eq!(reason("file taxes !2025-06-20 [warn:3d]"), None);
+        assert_eq!(reason("water plants @every tuesday"), None);
+    }
+}
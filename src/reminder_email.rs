@@ -0,0 +1,492 @@
+use crate::dates;
+use crate::email_actions::{self, escape_html};
+use crate::recurrence;
+use crate::task::Task;
+use chrono::{Days, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+const TODOIST_TASK_URL: &str = "https://app.todoist.com/app/task";
+
+/// The `[reminder_email]` table of config.toml, what the email holds.
+///
+/// ```toml
+/// [reminder_email]
+/// lookahead_days = 3
+/// quiet_tags = ["someday"]
+/// completed_yesterday = true
+/// weekly_summary = "sunday"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReminderEmailConfig {
+    /// How far ahead it looks for deadlines coming up, 0 for not at all
+    pub lookahead_days: u64,
+    /// Tasks with one of these tags are left out of the reminders, by email or pushed
+    pub quiet_tags: Vec<String>,
+    /// List what was checked off the day before
+    pub completed_yesterday: bool,
+    /// The day a summary of the week before goes out too
+    pub weekly_summary: Option<Weekday>,
+}
+
+impl Default for ReminderEmailConfig {
+    fn default() -> Self {
+        ReminderEmailConfig { lookahead_days: 7, quiet_tags: Vec::new(), completed_yesterday: false, weekly_summary: None }
+    }
+}
+
+impl ReminderEmailConfig {
+    pub fn is_quiet(&self, task: &Task) -> bool {
+        task.tags.iter().any(|tag| self.quiet_tags.contains(tag))
+    }
+}
+
+/// One address, or a list of them.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Addresses {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Addresses {
+    pub fn list(&self) -> &[String] {
+        match self {
+            Addresses::One(address) => std::slice::from_ref(address),
+            Addresses::Many(addresses) => addresses,
+        }
+    }
+}
+
+/// A `[[routes]]` table of email_config.toml: the tasks with one of `tags` go to `to`.
+///
+/// ```toml
+/// [[routes]]
+/// tags = ["family"]
+/// to = "family@example.com"
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EmailRoute {
+    pub tags: Vec<String>,
+    pub to: Addresses,
+}
+
+/// Everyone who may get an email, `to` first, each once.
+pub fn recipients<'a>(to: &'a Addresses, routes: &'a [EmailRoute]) -> Vec<&'a str> {
+    let mut recipients: Vec<&str> = Vec::new();
+    for address in to.list().iter().chain(routes.iter().flat_map(|route| route.to.list())) {
+        if !recipients.contains(&address.as_str()) {
+            recipients.push(address);
+        }
+    }
+    recipients
+}
+
+/// Whether `address` gets the task: the routes with one of its tags say who does, and
+/// `to` gets the tasks no route takes.
+pub fn receives(address: &str, to: &Addresses, routes: &[EmailRoute], task: &Task) -> bool {
+    let mut routed = routes.iter().filter(|route| task.tags.iter().any(|tag| route.tags.contains(tag))).peekable();
+    match routed.peek() {
+        Some(_) => routed.any(|route| route.to.list().iter().any(|a| a == address)),
+        None => to.list().iter().any(|a| a == address),
+    }
+}
+
+/// The sections with only the tasks `keep` takes, leaving out those it empties.
+pub fn only<'a>(sections: &[(Section, Vec<&'a Task>)], keep: impl Fn(&Task) -> bool) -> Vec<(Section, Vec<&'a Task>)> {
+    sections
+        .iter()
+        .map(|(section, tasks)| (*section, tasks.iter().copied().filter(|task| keep(task)).collect::<Vec<_>>()))
+        .filter(|(_, tasks)| !tasks.is_empty())
+        .collect()
+}
+
+/// A part of the reminder email, in the order they come.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    Overdue,
+    DueToday,
+    /// Reminder dates reached and repeating reminders due
+    Reminders,
+    /// Deadlines in the next days, for a look ahead; not reminders themselves
+    Upcoming,
+    DoneYesterday,
+}
+
+impl Section {
+    fn heading(self) -> &'static str {
+        match self {
+            Section::Overdue => "⚠ Overdue",
+            Section::DueToday => "📅 Due today",
+            Section::Reminders => "🔔 Reminders",
+            Section::Upcoming => "🗓 Coming up",
+            Section::DoneYesterday => "✅ Done yesterday",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Section::Overdue => "#c62828",
+            Section::DueToday => "#ef6c00",
+            Section::Reminders => "#1565c0",
+            Section::Upcoming => "#616161",
+            Section::DoneYesterday => "#2e7d32",
+        }
+    }
+}
+
+/// The tasks of the email on `today` by section, leaving out the empty ones and the quiet
+/// tasks. Tasks coming up are in deadline order, the rest as in tasks.md.
+pub fn sections<'a>(tasks: &'a [Task], today: NaiveDate, config: &ReminderEmailConfig) -> Vec<(Section, Vec<&'a Task>)> {
+    let horizon = today + Days::new(config.lookahead_days);
+    let yesterday = today - Days::new(1);
+    let mut sections: Vec<(Section, Vec<&Task>)> =
+        [Section::Overdue, Section::DueToday, Section::Reminders, Section::Upcoming, Section::DoneYesterday]
+            .map(|s| (s, Vec::new()))
+            .into();
+    for task in tasks.iter().filter(|task| !task.cancelled && !config.is_quiet(task)) {
+        let section = if recurrence::reminder_reason(task, today).is_some() {
+            match task.deadline {
+                Some(deadline) if deadline < today => Section::Overdue,
+                Some(deadline) if deadline == today => Section::DueToday,
+                _ => Section::Reminders,
+            }
+        } else if !task.completed && task.deadline.is_some_and(|deadline| deadline > today && deadline <= horizon) {
+            Section::Upcoming
+        } else if config.completed_yesterday && task.completed && task.done == Some(yesterday) {
+            Section::DoneYesterday
+        } else {
+            continue;
+        };
+        sections.iter_mut().find(|(s, _)| *s == section).expect("every section is there").1.push(task);
+    }
+    if let Some((_, upcoming)) = sections.iter_mut().find(|(s, _)| *s == Section::Upcoming) {
+        upcoming.sort_by_key(|task| task.deadline);
+    }
+    sections.retain(|(_, tasks)| !tasks.is_empty());
+    sections
+}
+
+/// The tasks by their first tag, tags in alphabetical order and the untagged last.
+fn by_tag<'a>(tasks: &[&'a Task]) -> Vec<(Option<&'a str>, Vec<&'a Task>)> {
+    let mut tagged: BTreeMap<&str, Vec<&Task>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for task in tasks {
+        match task.tags.first() {
+            Some(tag) => tagged.entry(tag.as_str()).or_default().push(*task),
+            None => untagged.push(*task),
+        }
+    }
+    let mut groups: Vec<(Option<&str>, Vec<&Task>)> = tagged.into_iter().map(|(tag, tasks)| (Some(tag), tasks)).collect();
+    if !untagged.is_empty() {
+        groups.push((None, untagged));
+    }
+    groups
+}
+
+/// Where the tasks in the email link to.
+#[derive(Default)]
+pub struct Links {
+    /// The Todoist ids of the synced tasks, by task id
+    pub todoist: HashMap<String, String>,
+    /// The url and secret of the done and snooze links, see `email_actions`
+    pub actions: Option<(String, String)>,
+}
+
+/// The look ahead and what was done, for the plain text email, which lists the reminders
+/// like the push digest.
+pub fn extra_text(sections: &[(Section, Vec<&Task>)]) -> String {
+    let mut text = String::new();
+    for (section, tasks) in sections.iter().filter(|(s, _)| matches!(s, Section::Upcoming | Section::DoneYesterday)) {
+        text.push_str(&format!("{}\n", section.heading()));
+        for task in tasks {
+            match task.deadline.filter(|_| *section == Section::Upcoming) {
+                Some(deadline) => text.push_str(&format!("  {}: {}\n", weekday_date(deadline), task.text)),
+                None => text.push_str(&format!("  {}\n", task.text)),
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// "Sat 2025-06-14", in the format and language of the `[dates]` section.
+fn weekday_date(date: NaiveDate) -> String {
+    let display = dates::display();
+    format!("{} {}", display.weekday(date), display.date(date))
+}
+
+fn task_html(task: &Task, section: Section, links: &Links, today: NaiveDate) -> String {
+    let text = escape_html(&task.text);
+    let mut html = match links.todoist.get(&task.id) {
+        Some(todoist_id) => format!(
+            "<li style=\"margin: 0 0 10px\"><a href=\"{}/{}\" style=\"color: #212121\">{}</a>",
+            TODOIST_TASK_URL,
+            escape_html(todoist_id),
+            text
+        ),
+        None => format!("<li style=\"margin: 0 0 10px\">{}", text),
+    };
+    let mut details = Vec::new();
+    if let Some(deadline) = task.deadline.filter(|_| section != Section::DoneYesterday) {
+        let days = (deadline - today).num_days();
+        let when = match days {
+            0 => "today".to_string(),
+            1 => "tomorrow".to_string(),
+            d if d < 0 => format!("{} day(s) ago", -d),
+            d => format!("in {} days", d),
+        };
+        details.push(format!("due {} ({})", weekday_date(deadline), when));
+    }
+    if let Some(reminder) = task.reminder.filter(|_| section == Section::Reminders) {
+        details.push(format!("reminder {}", weekday_date(reminder)));
+    }
+    if let Some(repeat) = task.repeat {
+        details.push(format!("🔁 {}", repeat));
+    }
+    if task.tags.len() > 1 {
+        details.push(task.tags.iter().skip(1).map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" "));
+    }
+    if !details.is_empty() {
+        html.push_str(&format!("<br><span style=\"color: #757575; font-size: 13px\">{}</span>", escape_html(&details.join(" · "))));
+    }
+    let open = matches!(section, Section::Overdue | Section::DueToday | Section::Reminders);
+    if let Some((url, secret)) = links.actions.as_ref().filter(|_| open) {
+        let (done, snooze) = email_actions::urls(url, secret, task, today);
+        let button = "display: inline-block; margin: 4px 6px 0 0; padding: 2px 10px; border: 1px solid #bdbdbd; \
+                      border-radius: 4px; color: #212121; text-decoration: none; font-size: 13px";
+        html.push_str(&format!(
+            "<br><a href=\"{}\" style=\"{}\">✅ Done</a><a href=\"{}\" style=\"{}\">😴 Tomorrow</a>",
+            escape_html(&done),
+            button,
+            escape_html(&snooze),
+            button
+        ));
+    }
+    html.push_str("</li>\n");
+    html
+}
+
+/// An email around `body`, under a heading.
+fn document(title: &str, subtitle: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"></head>\n\
+         <body style=\"font-family: -apple-system, 'Segoe UI', Roboto, sans-serif; color: #212121; max-width: 640px\">\n\
+         <h2 style=\"margin-bottom: 4px\">{}</h2>\n<p style=\"color: #757575; margin-top: 0\">{}</p>\n{}\
+         <p style=\"color: #9e9e9e; font-size: 12px\">Sent by yarmtl</p>\n</body></html>\n",
+        title,
+        subtitle,
+        body
+    )
+}
+
+/// The email as HTML: a section each, coloured by how pressing it is, and the tasks in it
+/// grouped by tag.
+pub fn html(sections: &[(Section, Vec<&Task>)], links: &Links, today: NaiveDate) -> String {
+    let mut html = String::new();
+    for (section, tasks) in sections {
+        html.push_str(&format!(
+            "<h3 style=\"color: {}; border-bottom: 2px solid {}; padding-bottom: 2px\">{} ({})</h3>\n",
+            section.color(),
+            section.color(),
+            section.heading(),
+            tasks.len()
+        ));
+        for (tag, tasks) in by_tag(tasks) {
+            if let Some(tag) = tag {
+                html.push_str(&format!("<h4 style=\"margin: 8px 0 4px; color: #424242\">#{}</h4>\n", escape_html(tag)));
+            }
+            html.push_str("<ul style=\"padding-left: 20px; margin-top: 4px\">\n");
+            for task in tasks {
+                html.push_str(&task_html(task, *section, links, today));
+            }
+            html.push_str("</ul>\n");
+        }
+    }
+    document("Task Reminders", &dates::display().long(today), &html)
+}
+
+/// How the week before went, for the weekly summary email.
+#[derive(Debug, PartialEq)]
+pub struct WeeklySummary<'a> {
+    /// The first day of the week summed up, a week before today
+    pub from: NaiveDate,
+    pub completed: Vec<&'a Task>,
+    pub added: usize,
+    pub overdue: usize,
+    /// Open tasks due in the week to come
+    pub due_next: usize,
+    /// Tasks completed by tag, the busiest first
+    pub by_tag: Vec<(String, usize)>,
+}
+
+/// The week up to yesterday, from the done and created dates of `tasks`. Quiet tasks count too.
+pub fn weekly_summary(tasks: &[Task], today: NaiveDate) -> WeeklySummary<'_> {
+    let from = today - Days::new(7);
+    let in_week = |day: Option<NaiveDate>| day.is_some_and(|day| day >= from && day < today);
+    let completed: Vec<&Task> = tasks.iter().filter(|task| task.completed && in_week(task.done)).collect();
+    let mut by_tag: BTreeMap<&str, usize> = BTreeMap::new();
+    for tag in completed.iter().flat_map(|task| &task.tags) {
+        *by_tag.entry(tag).or_default() += 1;
+    }
+    let mut by_tag: Vec<(String, usize)> = by_tag.into_iter().map(|(tag, count)| (tag.to_string(), count)).collect();
+    by_tag.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    let open: Vec<&Task> = tasks.iter().filter(|task| !task.completed && !task.cancelled).collect();
+    WeeklySummary {
+        from,
+        added: tasks.iter().filter(|task| in_week(task.created)).count(),
+        overdue: open.iter().filter(|task| task.deadline.is_some_and(|d| d < today)).count(),
+        due_next: open.iter().filter(|task| task.deadline.is_some_and(|d| d >= today && d < today + Days::new(7))).count(),
+        completed,
+        by_tag,
+    }
+}
+
+impl WeeklySummary<'_> {
+    fn week(&self) -> String {
+        let to = self.from + Days::new(6);
+        let display = dates::display();
+        let day = |date: NaiveDate| format!("{} {}", display.weekday(date), display.localized(date, "%b %-d"));
+        format!("{} to {}", day(self.from), day(to))
+    }
+
+    fn counts(&self) -> [String; 4] {
+        [
+            format!("✅ {} task(s) completed", self.completed.len()),
+            format!("➕ {} added", self.added),
+            format!("⚠ {} overdue now", self.overdue),
+            format!("📅 {} due in the week to come", self.due_next),
+        ]
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = format!("Weekly Summary, {}\n\n", self.week());
+        for count in self.counts() {
+            text.push_str(&format!("{}\n", count));
+        }
+        if !self.by_tag.is_empty() {
+            let tags: Vec<String> = self.by_tag.iter().map(|(tag, count)| format!("#{} {}", tag, count)).collect();
+            text.push_str(&format!("🏷️  {}\n", tags.join(", ")));
+        }
+        if !self.completed.is_empty() {
+            text.push_str("\nDone\n");
+            for task in &self.completed {
+                text.push_str(&format!("  {}\n", task.text));
+            }
+        }
+        text
+    }
+
+    pub fn html(&self) -> String {
+        let mut html = String::from("<ul style=\"padding-left: 20px\">\n");
+        for count in self.counts() {
+            html.push_str(&format!("<li>{}</li>\n", escape_html(&count)));
+        }
+        html.push_str("</ul>\n");
+        if !self.by_tag.is_empty() {
+            let tags: Vec<String> =
+                self.by_tag.iter().map(|(tag, count)| format!("<b>#{}</b> {}", escape_html(tag), count)).collect();
+            html.push_str(&format!("<p>🏷️ {}</p>\n", tags.join(" · ")));
+        }
+        if !self.completed.is_empty() {
+            let color = Section::DoneYesterday.color();
+            html.push_str(&format!("<h3 style=\"color: {}; border-bottom: 2px solid {}\">Done</h3>\n<ul style=\"padding-left: 20px\">\n", color, color));
+            for task in &self.completed {
+                html.push_str(&format!("<li>{}</li>\n", escape_html(&task.text)));
+            }
+            html.push_str("</ul>\n");
+        }
+        document("Weekly Summary", &self.week(), &html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_and_html() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 12).unwrap();
+        let mut filed = Task::parse("filed taxes !2025-06-13 [id:aaaa07]");
+        filed.set_completed(true, today - Days::new(1));
+        let tasks = vec![
+            Task::parse("call mum !2025-06-12 #family [id:aaaa02]"),
+            Task::parse("write report !2025-06-10 #work [id:aaaa01]"),
+            Task::parse("water plants @2025-06-12 [id:aaaa03]"),
+            Task::parse("book flights !2025-06-18 [id:aaaa05]"),
+            Task::parse("pay rent !2025-06-14 #home [id:aaaa04]"),
+            Task::parse("someday !2025-07-30 [id:aaaa06]"),
+            Task::parse("learn the banjo !2025-06-13 #someday [id:aaaa08]"),
+            filed,
+        ];
+        let config = ReminderEmailConfig { quiet_tags: vec!["someday".to_string()], completed_yesterday: true, ..Default::default() };
+        let sections = sections(&tasks, today, &config);
+        let ids: Vec<(Section, Vec<&str>)> =
+            sections.iter().map(|(s, tasks)| (*s, tasks.iter().map(|t| t.id.as_str()).collect())).collect();
+        assert_eq!(
+            ids,
+            [
+                (Section::Overdue, vec!["aaaa01"]),
+                (Section::DueToday, vec!["aaaa02"]),
+                (Section::Reminders, vec!["aaaa03"]),
+                (Section::Upcoming, vec!["aaaa04", "aaaa05"]),
+                (Section::DoneYesterday, vec!["aaaa07"]),
+            ]
+        );
+        assert_eq!(
+            extra_text(&sections),
+            "🗓 Coming up\n  Sat 2025-06-14: pay rent\n  Wed 2025-06-18: book flights\n\n✅ Done yesterday\n  filed taxes\n\n"
+        );
+
+        let links = Links { todoist: HashMap::from([("aaaa01".to_string(), "7654".to_string())]), actions: None };
+        let html = html(&sections, &links, today);
+        assert!(html.contains("<a href=\"https://app.todoist.com/app/task/7654\" style=\"color: #212121\">write report</a>"));
+        assert!(html.contains("<h4 style=\"margin: 8px 0 4px; color: #424242\">#work</h4>"));
+        assert!(html.contains("due Tue 2025-06-10 (2 day(s) ago)"));
+
+        let summary = weekly_summary(&tasks, today);
+        assert_eq!(summary.completed.len(), 1);
+        assert_eq!((summary.overdue, summary.due_next), (1, 4));
+        assert!(summary.text().starts_with("Weekly Summary, Thu Jun 5 to Wed Jun 11\n"));
+        let config: crate::config::Config = toml::from_str("[reminder_email]\nweekly_summary = \"sunday\"\n").unwrap();
+        assert_eq!(config.reminder_email.weekly_summary, Some(Weekday::Sun));
+    }
+
+    #[test]
+    fn test_routing_by_tag() {
+        #[derive(Deserialize)]
+        struct Routing {
+            to_email: Addresses,
+            routes: Vec<EmailRoute>,
+        }
+        let routing: Routing = toml::from_str(
+            r#"
+            to_email = ["me@example.com", "partner@example.com"]
+
+            [[routes]]
+            tags = ["family"]
+            to = "family@example.com"
+
+            [[routes]]
+            tags = ["work", "oncall"]
+            to = ["me@work.example.com", "me@example.com"]
+            "#,
+        )
+        .unwrap();
+        let (to, routes) = (&routing.to_email, &routing.routes);
+        assert_eq!(
+            recipients(to, routes),
+            ["me@example.com", "partner@example.com", "family@example.com", "me@work.example.com"]
+        );
+        let gets = |task: &str| -> Vec<&str> {
+            let task = Task::parse(task);
+            recipients(to, routes).into_iter().filter(|address| receives(address, to, routes, &task)).collect()
+        };
+        assert_eq!(gets("buy milk"), ["me@example.com", "partner@example.com"]);
+        assert_eq!(gets("call grandma #family"), ["family@example.com"]);
+        assert_eq!(gets("fix the build #work"), ["me@example.com", "me@work.example.com"]);
+        assert_eq!(gets("school run #family #oncall"), ["me@example.com", "family@example.com", "me@work.example.com"]);
+        assert_eq!(toml::from_str::<Routing>("to_email = \"me@example.com\"\nroutes = []").unwrap().to_email.list(), ["me@example.com"]);
+    }
+}
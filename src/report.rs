@@ -0,0 +1,533 @@
+use crate::dates;
+use crate::stats::{self, Completion};
+use crate::task::Task;
+use chrono::{Datelike, Months, NaiveDate};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const TOP_N: usize = 5;
+
+#[derive(Debug)]
+pub struct SlippedTask {
+    pub text: String,
+    pub deadline: NaiveDate,
+    pub completed_on: NaiveDate,
+    pub days_late: i64,
+}
+
+/// Completions per tag in the first and second half of the year.
+#[derive(Debug, PartialEq)]
+pub struct TagShift {
+    pub tag: String,
+    pub first_half: usize,
+    pub second_half: usize,
+}
+
+#[derive(Debug)]
+pub struct YearReport {
+    pub year: i32,
+    pub total_completed: usize,
+    /// Tasks cancelled this year, which count for nothing else in the report
+    pub total_cancelled: usize,
+    /// Completions per week of the year, for the chart
+    pub weekly: Vec<usize>,
+    /// Monday of the week and completions that week, busiest first
+    pub busiest_weeks: Vec<(NaiveDate, usize)>,
+    pub slipped: Vec<SlippedTask>,
+    pub tag_shifts: Vec<TagShift>,
+    pub longest_streak: Option<(NaiveDate, NaiveDate, usize)>,
+}
+
+pub fn build_year_report(completions: &[Completion], year: i32) -> YearReport {
+    let (cancelled, in_year): (Vec<&Completion>, Vec<&Completion>) = completions
+        .iter()
+        .filter(|c| c.completed_on.year() == year)
+        .partition(|c| c.task.cancelled);
+
+    let mut weekly = vec![0; 53];
+    let mut weeks: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for completion in &in_year {
+        weekly[completion.completed_on.ordinal0() as usize / 7] += 1;
+        *weeks.entry(dates::display().week_start(completion.completed_on)).or_default() += 1;
+    }
+
+    let mut busiest_weeks: Vec<(NaiveDate, usize)> = weeks.into_iter().collect();
+    busiest_weeks.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    busiest_weeks.truncate(TOP_N);
+
+    let mut slipped: Vec<SlippedTask> = in_year
+        .iter()
+        .filter_map(|c| {
+            let deadline = c.task.deadline?;
+            let days_late = (c.completed_on - deadline).num_days();
+            (days_late > 0).then(|| SlippedTask {
+                text: c.task.text.clone(),
+                deadline,
+                completed_on: c.completed_on,
+                days_late,
+            })
+        })
+        .collect();
+    slipped.sort_by_key(|s| std::cmp::Reverse(s.days_late));
+    slipped.truncate(TOP_N);
+
+    let mut tags: HashMap<String, (usize, usize)> = HashMap::new();
+    for completion in &in_year {
+        for tag in &completion.task.tags {
+            let counts = tags.entry(tag.clone()).or_default();
+            if completion.completed_on.month() <= 6 {
+                counts.0 += 1;
+            } else {
+                counts.1 += 1;
+            }
+        }
+    }
+    let mut tag_shifts: Vec<TagShift> = tags
+        .into_iter()
+        .map(|(tag, (first_half, second_half))| TagShift { tag, first_half, second_half })
+        .collect();
+    tag_shifts.sort_by(|a, b| {
+        (b.first_half + b.second_half)
+            .cmp(&(a.first_half + a.second_half))
+            .then_with(|| a.tag.cmp(&b.tag))
+    });
+    tag_shifts.truncate(TOP_N * 2);
+
+    let days: Vec<NaiveDate> = in_year.iter().map(|c| c.completed_on).collect();
+
+    YearReport {
+        year,
+        total_completed: in_year.len(),
+        total_cancelled: cancelled.len(),
+        weekly,
+        busiest_weeks,
+        slipped,
+        tag_shifts,
+        longest_streak: stats::longest_streak(&days),
+    }
+}
+
+fn format_shift(shift: &TagShift) -> String {
+    let change = shift.second_half as i64 - shift.first_half as i64;
+    match change {
+        0 => "±0".to_string(),
+        c if c > 0 => format!("+{}", c),
+        c => c.to_string(),
+    }
+}
+
+pub fn render_markdown(report: &YearReport) -> String {
+    let mut out = format!("# {} in review\n\n", report.year);
+    out.push_str(&format!("**{}** tasks completed{}.\n\n", report.total_completed, cancelled_note(report)));
+
+    if report.total_completed == 0 {
+        out.push_str("Nothing was completed this year, or tasks.md has no git history for it.\n");
+        return out;
+    }
+
+    out.push_str("## Completions per week\n\n");
+    out.push_str(&format!("```\n{}\n```\n\n", stats::sparkline(&report.weekly)));
+
+    out.push_str("## Busiest weeks\n\n");
+    for (monday, count) in &report.busiest_weeks {
+        out.push_str(&format!("- week of {}: {} completed\n", monday.format("%Y-%m-%d"), count));
+    }
+
+    out.push_str("\n## Longest streak\n\n");
+    if let Some((start, end, days)) = report.longest_streak {
+        out.push_str(&format!(
+            "{} in a row, {} to {}\n",
+            plural_days(days),
+            start.format("%Y-%m-%d"),
+            end.format("%Y-%m-%d")
+        ));
+    }
+
+    if !report.slipped.is_empty() {
+        out.push_str("\n## Biggest slipped deadlines\n\n");
+        for slipped in &report.slipped {
+            out.push_str(&format!(
+                "- {}: due {}, done {} ({} days late)\n",
+                slipped.text,
+                slipped.deadline.format("%Y-%m-%d"),
+                slipped.completed_on.format("%Y-%m-%d"),
+                slipped.days_late
+            ));
+        }
+    }
+
+    if !report.tag_shifts.is_empty() {
+        out.push_str("\n## Tags, first vs second half\n\n");
+        out.push_str("| tag | jan-jun | jul-dec | change |\n|---|---|---|---|\n");
+        for shift in &report.tag_shifts {
+            out.push_str(&format!(
+                "| #{} | {} | {} | {} |\n",
+                shift.tag,
+                shift.first_half,
+                shift.second_half,
+                format_shift(shift)
+            ));
+        }
+    }
+
+    out
+}
+
+/// ", 3 cancelled" after the number of completed tasks, when any were.
+fn cancelled_note(report: &YearReport) -> String {
+    match report.total_cancelled {
+        0 => String::new(),
+        cancelled => format!(", {} cancelled", cancelled),
+    }
+}
+
+fn plural_days(days: usize) -> String {
+    if days == 1 { "1 day".to_string() } else { format!("{} days", days) }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub fn render_html(report: &YearReport) -> String {
+    let mut body = format!("<h1>{} in review</h1>\n", report.year);
+    body.push_str(&format!(
+        "<p><strong>{}</strong> tasks completed{}.</p>\n",
+        report.total_completed,
+        cancelled_note(report)
+    ));
+
+    if report.total_completed > 0 {
+        let max = report.weekly.iter().copied().max().unwrap_or(1).max(1);
+        body.push_str("<h2>Completions per week</h2>\n<div class=\"chart\">");
+        for (week, count) in report.weekly.iter().enumerate() {
+            body.push_str(&format!(
+                "<div class=\"bar\" style=\"height:{}%\" title=\"week {}: {}\"></div>",
+                count * 100 / max,
+                week + 1,
+                count
+            ));
+        }
+        body.push_str("</div>\n");
+
+        body.push_str("<h2>Busiest weeks</h2>\n<ul>\n");
+        for (monday, count) in &report.busiest_weeks {
+            body.push_str(&format!("<li>week of {}: {} completed</li>\n", monday.format("%Y-%m-%d"), count));
+        }
+        body.push_str("</ul>\n");
+
+        if let Some((start, end, days)) = report.longest_streak {
+            body.push_str(&format!(
+                "<h2>Longest streak</h2>\n<p>{} in a row, {} to {}</p>\n",
+                plural_days(days),
+                start.format("%Y-%m-%d"),
+                end.format("%Y-%m-%d")
+            ));
+        }
+
+        if !report.slipped.is_empty() {
+            body.push_str("<h2>Biggest slipped deadlines</h2>\n<ul>\n");
+            for slipped in &report.slipped {
+                body.push_str(&format!(
+                    "<li>{}: due {}, done {} ({} days late)</li>\n",
+                    escape_html(&slipped.text),
+                    slipped.deadline.format("%Y-%m-%d"),
+                    slipped.completed_on.format("%Y-%m-%d"),
+                    slipped.days_late
+                ));
+            }
+            body.push_str("</ul>\n");
+        }
+
+        if !report.tag_shifts.is_empty() {
+            body.push_str("<h2>Tags, first vs second half</h2>\n<table>\n<tr><th>tag</th><th>jan-jun</th><th>jul-dec</th><th>change</th></tr>\n");
+            for shift in &report.tag_shifts {
+                body.push_str(&format!(
+                    "<tr><td>#{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&shift.tag),
+                    shift.first_half,
+                    shift.second_half,
+                    format_shift(shift)
+                ));
+            }
+            body.push_str("</table>\n");
+        }
+    }
+
+    html_page(&format!("{} in review", report.year), &body)
+}
+
+/// A whole page around `body`, styled for reading and printing.
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>yarmtl {title}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 50em; margin: 2em auto; }}\n\
+         .chart {{ display: flex; align-items: flex-end; height: 8em; gap: 2px; }}\n\
+         .bar {{ flex: 1; background: #ff6b8a; min-height: 1px; }}\n\
+         td, th {{ padding: 0.2em 1em; text-align: left; }}\n\
+         </style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        title = escape_html(title),
+        body = body
+    )
+}
+
+/// What a review looks back on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    /// Monday to Sunday
+    Week,
+    Month,
+}
+
+impl Period {
+    /// The first and last day of the period `day` is in.
+    pub fn bounds(self, day: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            Period::Week => {
+                let start = dates::display().week_start(day);
+                (start, start + chrono::Duration::days(6))
+            }
+            Period::Month => {
+                let first = day.with_day(1).unwrap_or(day);
+                (first, first + Months::new(1) - chrono::Duration::days(1))
+            }
+        }
+    }
+}
+
+/// A weekly or monthly review: what got done and added, and what is still open at the end.
+#[derive(Debug)]
+pub struct Review {
+    pub period: Period,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// With the day each was completed, in that order
+    pub completed: Vec<(Task, NaiveDate)>,
+    pub cancelled: usize,
+    /// With the day each was added, in that order
+    pub added: Vec<(Task, NaiveDate)>,
+    /// Open at the end of the period and past their deadline, with how many days, the
+    /// least late first
+    pub overdue: Vec<(Task, i64)>,
+    /// Open since before the period and still open, with the day each was added; the
+    /// overdue ones are left out, they're listed already
+    pub carried_over: Vec<(Task, NaiveDate)>,
+    /// Completed and added per tag, the busiest first
+    pub tags: Vec<(String, usize, usize)>,
+}
+
+impl Review {
+    /// "Week of 2025-06-02" or "June 2025".
+    pub fn title(&self) -> String {
+        match self.period {
+            Period::Week => format!("Week of {}", self.start.format("%Y-%m-%d")),
+            Period::Month => self.start.format("%B %Y").to_string(),
+        }
+    }
+}
+
+/// The review of the `period` `day` is in, from the versions of tasks.md oldest first,
+/// `tasks` being tasks.md as it is `today` (which may not be committed yet).
+pub fn build_review(history: &[(NaiveDate, Vec<Task>)], tasks: &[Task], period: Period, day: NaiveDate, today: NaiveDate) -> Review {
+    let (start, end) = period.bounds(day);
+    let mut history = history.to_vec();
+    history.push((today, tasks.to_vec()));
+
+    let (cancelled, completed): (Vec<Completion>, Vec<Completion>) = stats::completions_from_history(&history)
+        .into_iter()
+        .filter(|c| start <= c.completed_on && c.completed_on <= end)
+        .partition(|c| c.task.cancelled);
+    let completed: Vec<(Task, NaiveDate)> = completed.into_iter().map(|c| (c.task, c.completed_on)).collect();
+
+    // When each task was added: its created date, or the first version it is in
+    let mut first_seen: HashMap<String, (Task, NaiveDate)> = HashMap::new();
+    for (date, tasks) in &history {
+        for task in tasks {
+            first_seen.entry(task.id.clone()).or_insert_with(|| (task.clone(), task.created.unwrap_or(*date)));
+        }
+    }
+    let mut added: Vec<(Task, NaiveDate)> = first_seen.values().filter(|(_, added)| start <= *added && *added <= end).cloned().collect();
+    added.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.id.cmp(&b.0.id)));
+
+    let as_of = |day: NaiveDate| history.iter().rev().find(|(date, _)| *date <= day).map(|(_, tasks)| tasks.as_slice()).unwrap_or_default();
+    let is_open = |task: &&Task| !task.completed;
+    let past = (end + chrono::Duration::days(1)).min(today);
+    let mut overdue: Vec<(Task, i64)> = as_of(end)
+        .iter()
+        .filter(is_open)
+        .filter_map(|task| task.deadline.filter(|deadline| *deadline < past).map(|deadline| (task.clone(), (past - deadline).num_days())))
+        .collect();
+    overdue.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.id.cmp(&b.0.id)));
+
+    let open_before: HashSet<&str> = as_of(start - chrono::Duration::days(1)).iter().filter(is_open).map(|task| task.id.as_str()).collect();
+    let listed: HashSet<&str> = overdue.iter().map(|(task, _)| task.id.as_str()).collect();
+    let mut carried_over: Vec<(Task, NaiveDate)> = as_of(end)
+        .iter()
+        .filter(is_open)
+        .filter(|task| open_before.contains(task.id.as_str()) && !listed.contains(task.id.as_str()))
+        .map(|task| (task.clone(), first_seen.get(&task.id).map_or(start, |(_, added)| *added)))
+        .collect();
+    carried_over.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.id.cmp(&b.0.id)));
+
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (task, _) in &completed {
+        for tag in &task.tags {
+            counts.entry(tag).or_default().0 += 1;
+        }
+    }
+    for (task, _) in &added {
+        for tag in &task.tags {
+            counts.entry(tag).or_default().1 += 1;
+        }
+    }
+    let mut tags: Vec<(String, usize, usize)> = counts.into_iter().map(|(tag, (done, added))| (tag.to_string(), done, added)).collect();
+    tags.sort_by(|a, b| (b.1 + b.2).cmp(&(a.1 + a.2)).then_with(|| a.0.cmp(&b.0)));
+
+    Review { period, start, end, completed, cancelled: cancelled.len(), added, overdue, carried_over, tags }
+}
+
+/// ", 1 cancelled, 4 added" after the number of completed tasks.
+fn review_note(review: &Review) -> String {
+    let cancelled = match review.cancelled {
+        0 => String::new(),
+        cancelled => format!(", {} cancelled", cancelled),
+    };
+    format!("{}, {} added", cancelled, review.added.len())
+}
+
+/// Each section of a review: its heading and its lines, left out when it has none.
+fn review_sections(review: &Review) -> Vec<(&'static str, Vec<String>)> {
+    let day = |date: &NaiveDate| date.format("%Y-%m-%d").to_string();
+    let tags = |task: &Task| task.tags.iter().map(|tag| format!(" #{}", tag)).collect::<String>();
+    let sections: Vec<(&str, Vec<String>)> = vec![
+        ("Completed", review.completed.iter().map(|(task, on)| format!("{}{} ({})", task.text, tags(task), day(on))).collect()),
+        ("Added", review.added.iter().map(|(task, on)| format!("{}{} ({})", task.text, tags(task), day(on))).collect()),
+        (
+            "Still overdue",
+            review
+                .overdue
+                .iter()
+                .map(|(task, late)| {
+                    let deadline = task.deadline.map(|deadline| day(&deadline)).unwrap_or_default();
+                    format!("{}{}: due {} ({} late)", task.text, tags(task), deadline, plural_days(*late as usize))
+                })
+                .collect(),
+        ),
+        ("Carried over", review.carried_over.iter().map(|(task, since)| format!("{}{}, open since {}", task.text, tags(task), day(since))).collect()),
+    ];
+    sections.into_iter().filter(|(_, lines)| !lines.is_empty()).collect()
+}
+
+pub fn render_review_markdown(review: &Review) -> String {
+    let mut out = format!("# {}\n\n", review.title());
+    out.push_str(&format!(
+        "{} to {}: **{}** tasks completed{}.\n",
+        review.start.format("%Y-%m-%d"),
+        review.end.format("%Y-%m-%d"),
+        review.completed.len(),
+        review_note(review)
+    ));
+    for (heading, lines) in review_sections(review) {
+        out.push_str(&format!("\n## {}\n\n", heading));
+        for line in lines {
+            out.push_str(&format!("- {}\n", line));
+        }
+    }
+    if !review.tags.is_empty() {
+        out.push_str("\n## Tags\n\n| tag | completed | added |\n|---|---|---|\n");
+        for (tag, completed, added) in &review.tags {
+            out.push_str(&format!("| #{} | {} | {} |\n", tag, completed, added));
+        }
+    }
+    out
+}
+
+pub fn render_review_html(review: &Review) -> String {
+    let mut body = format!("<h1>{}</h1>\n", escape_html(&review.title()));
+    body.push_str(&format!(
+        "<p>{} to {}: <strong>{}</strong> tasks completed{}.</p>\n",
+        review.start.format("%Y-%m-%d"),
+        review.end.format("%Y-%m-%d"),
+        review.completed.len(),
+        review_note(review)
+    ));
+    for (heading, lines) in review_sections(review) {
+        body.push_str(&format!("<h2>{}</h2>\n<ul>\n", heading));
+        for line in lines {
+            body.push_str(&format!("<li>{}</li>\n", escape_html(&line)));
+        }
+        body.push_str("</ul>\n");
+    }
+    if !review.tags.is_empty() {
+        body.push_str("<h2>Tags</h2>\n<table>\n<tr><th>tag</th><th>completed</th><th>added</th></tr>\n");
+        for (tag, completed, added) in &review.tags {
+            body.push_str(&format!("<tr><td>#{}</td><td>{}</td><td>{}</td></tr>\n", escape_html(tag), completed, added));
+        }
+        body.push_str("</table>\n");
+    }
+    html_page(&review.title(), &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::Task;
+
+    fn completion(line: &str, completed_on: NaiveDate) -> Completion {
+        Completion { task: Task::parse(line), completed_on }
+    }
+
+    #[test]
+    fn test_year_report() {
+        let d = |m, day| NaiveDate::from_ymd_opt(2025, m, day).unwrap();
+        let mut dropped = completion("old plan !2025-01-10 #home", d(5, 2));
+        dropped.task.cancelled = true;
+        let completions = vec![
+            dropped,
+            completion("taxes !2025-03-01 #home", d(3, 20)),
+            completion("gym #health", d(3, 21)),
+            completion("gym again #health", d(8, 4)),
+            completion("ship it !2025-08-10 #work", d(8, 5)),
+            completion("last year", NaiveDate::from_ymd_opt(2024, 12, 31).unwrap()),
+        ];
+
+        let report = build_year_report(&completions, 2025);
+
+        assert_eq!((report.total_completed, report.total_cancelled), (4, 1));
+        assert_eq!(report.busiest_weeks[0], (d(3, 17), 2));
+        assert_eq!(report.slipped.len(), 1);
+        assert_eq!(report.slipped[0].days_late, 19);
+        assert_eq!(report.longest_streak, Some((d(3, 20), d(3, 21), 2)));
+        assert_eq!(report.tag_shifts[0], TagShift { tag: "health".to_string(), first_half: 1, second_half: 1 });
+
+        let markdown = render_markdown(&report);
+        assert!(markdown.contains("**4** tasks completed, 1 cancelled."));
+        assert!(markdown.contains("| #home | 1 | 0 | -1 |"));
+        assert!(render_html(&report).contains("<li>taxes: due 2025-03-01, done 2025-03-20 (19 days late)</li>"));
+    }
+
+    #[test]
+    fn test_week_review() {
+        let d = |day| NaiveDate::from_ymd_opt(2025, 6, day).unwrap();
+        let tasks = |content: &str| crate::task::parse_tasks(&format!("# tasks\n\n{}", content));
+        let history = vec![
+            (d(1), tasks("- [ ] taxes !2025-06-01 #home [id:aaaa01]\n- [ ] read book [id:aaaa02]\n")),
+            (d(3), tasks("- [ ] taxes !2025-06-01 #home [id:aaaa01]\n- [ ] read book [id:aaaa02]\n- [ ] ship it #work [id:aaaa03]\n")),
+            (d(4), tasks("- [ ] taxes !2025-06-01 #home [id:aaaa01]\n- [ ] read book [id:aaaa02]\n- [x] ship it #work [id:aaaa03]\n")),
+        ];
+        let now = tasks("- [ ] taxes !2025-06-01 #home [id:aaaa01]\n- [ ] read book [id:aaaa02]\n- [x] ship it #work [id:aaaa03]\n- [ ] next thing [id:aaaa04]\n");
+
+        assert_eq!(Period::Month.bounds(d(14)), (d(1), d(30)));
+        let review = build_review(&history, &now, Period::Week, d(4), d(12));
+        assert_eq!((review.start, review.end, review.title()), (d(2), d(8), "Week of 2025-06-02".to_string()));
+        assert_eq!(review.completed.iter().map(|(task, on)| (task.text.as_str(), *on)).collect::<Vec<_>>(), [("ship it", d(4))]);
+        assert_eq!(review.added.iter().map(|(task, _)| task.text.as_str()).collect::<Vec<_>>(), ["ship it"]);
+        assert_eq!(review.overdue.iter().map(|(task, late)| (task.text.as_str(), *late)).collect::<Vec<_>>(), [("taxes", 8)]);
+        assert_eq!(review.carried_over.iter().map(|(task, since)| (task.text.as_str(), *since)).collect::<Vec<_>>(), [("read book", d(1))]);
+        assert_eq!(review.tags, [("work".to_string(), 1, 1)]);
+
+        let markdown = render_review_markdown(&review);
+        assert!(markdown.contains("2025-06-02 to 2025-06-08: **1** tasks completed, 1 added."), "{}", markdown);
+        assert!(markdown.contains("- taxes #home: due 2025-06-01 (8 days late)"));
+        assert!(render_review_html(&review).contains("<li>read book, open since 2025-06-01</li>"));
+    }
+}
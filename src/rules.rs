@@ -0,0 +1,153 @@
+use crate::task::Task;
+use chrono::NaiveDate;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+/// An escalation rule from a `[[rules]]` table in config.toml. The conditions that are
+/// set must all hold for an open task; the actions only ever make it more urgent.
+///
+/// ```toml
+/// [[rules]]
+/// overdue_days = 7
+/// set_importance = 1
+/// add_tags = ["stale"]
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Rule {
+    /// Overdue by more than this many days
+    pub overdue_days: Option<u32>,
+    /// Added more than this many days ago (by its [created:] date)
+    pub age_days: Option<u32>,
+    /// Has this tag
+    pub tag: Option<String>,
+    /// Importance to raise the task to; already more important tasks keep theirs
+    #[serde(deserialize_with = "importance")]
+    pub set_importance: Option<u8>,
+    pub add_tags: Vec<String>,
+}
+
+/// Reads `set_importance`, which like `$1` to `$5` in tasks.md is 1 to 5.
+fn importance<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u8>, D::Error> {
+    let importance = Option::<u8>::deserialize(deserializer)?;
+    match importance {
+        Some(i) if !(1..=5).contains(&i) => Err(de::Error::custom(format!("set_importance is {}, not 1 to 5", i))),
+        _ => Ok(importance),
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, task: &Task, today: NaiveDate) -> bool {
+        let days_since = |date: NaiveDate| (today - date).num_days();
+        !task.completed
+            && self.overdue_days.is_none_or(|days| task.deadline.is_some_and(|d| days_since(d) > days as i64))
+            && self.age_days.is_none_or(|days| task.created.is_some_and(|c| days_since(c) > days as i64))
+            && self.tag.as_ref().is_none_or(|tag| task.tags.contains(tag))
+    }
+
+    /// What the rule says, for commit messages: "overdue > 7 days".
+    pub fn describe(&self) -> String {
+        let mut conditions = Vec::new();
+        if let Some(days) = self.overdue_days {
+            conditions.push(format!("overdue > {} days", days));
+        }
+        if let Some(days) = self.age_days {
+            conditions.push(format!("older than {} days", days));
+        }
+        if let Some(tag) = &self.tag {
+            conditions.push(format!("#{}", tag));
+        }
+        if conditions.is_empty() { "every open task".to_string() } else { conditions.join(", ") }
+    }
+}
+
+/// A task a rule changed, with what it did: "$1 #stale".
+#[derive(Debug, PartialEq)]
+pub struct Escalation {
+    pub task_index: usize,
+    pub changes: String,
+}
+
+/// Applies every rule to every task and reports the tasks that changed. Running it
+/// again right away changes nothing.
+pub fn apply_rules(rules: &[Rule], tasks: &mut [Task], today: NaiveDate) -> Vec<Escalation> {
+    let mut escalations = Vec::new();
+    for (task_index, task) in tasks.iter_mut().enumerate() {
+        let mut changes = Vec::new();
+        for rule in rules {
+            if !rule.matches(task, today) {
+                continue;
+            }
+            if let Some(importance) = rule.set_importance
+                && task.importance.is_none_or(|current| current > importance)
+            {
+                task.importance = Some(importance);
+                changes.push(format!("${}", importance));
+            }
+            for tag in &rule.add_tags {
+                if !task.tags.contains(tag) {
+                    task.tags.push(tag.clone());
+                    changes.push(format!("#{}", tag));
+                }
+            }
+        }
+        if !changes.is_empty() {
+            escalations.push(Escalation { task_index, changes: changes.join(" ") });
+        }
+    }
+    escalations
+}
+
+/// The first rule that holds for the task, the TUI marks those tasks as escalated.
+pub fn matching_rule<'a>(rules: &'a [Rule], task: &Task, today: NaiveDate) -> Option<&'a Rule> {
+    rules.iter().find(|rule| rule.matches(task, today))
+}
+
+/// "⚡ Escalated 2 task(s): write report ($1 #stale), call bob (#stale)"
+pub fn commit_message(tasks: &[Task], escalations: &[Escalation]) -> String {
+    let changed: Vec<String> = escalations
+        .iter()
+        .map(|e| format!("{} ({})", tasks[e.task_index].text, e.changes))
+        .collect();
+    format!("⚡ Escalated {} task(s): {}", escalations.len(), changed.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_escalate_once() {
+        let config: crate::config::Config = toml::from_str(
+            "[[rules]]\noverdue_days = 7\nset_importance = 1\nadd_tags = [\"stale\"]\n\n\
+             [[rules]]\nage_days = 30\ntag = \"someday\"\nadd_tags = [\"review\"]\n",
+        )
+        .unwrap();
+        let rules = config.rules;
+        let today = NaiveDate::from_ymd_opt(2025, 6, 30).unwrap();
+        let mut tasks = vec![
+            Task::parse("late !2025-06-20 $3"),
+            Task::parse("slightly late !2025-06-25"),
+            Task::parse("old idea #someday [created:2025-04-01]"),
+            Task::parse("already urgent !2025-06-01 $1 #stale"),
+        ];
+
+        let escalations = apply_rules(&rules, &mut tasks, today);
+        assert_eq!(
+            commit_message(&tasks, &escalations),
+            "⚡ Escalated 2 task(s): late ($1 #stale), old idea (#review)"
+        );
+        assert_eq!(tasks[0].importance, Some(1));
+        assert!(apply_rules(&rules, &mut tasks, today).is_empty());
+
+        assert_eq!(matching_rule(&rules, &tasks[3], today).map(Rule::describe).as_deref(), Some("overdue > 7 days"));
+        assert!(matching_rule(&rules, &tasks[1], today).is_none());
+    }
+    #[test]
+    fn test_importance_out_of_range_is_rejected() {
+        for importance in [0, 6, 9] {
+            let error = toml::from_str::<crate::config::Config>(&format!("[[rules]]\nset_importance = {}\n", importance)).unwrap_err();
+            assert!(error.to_string().contains("not 1 to 5"), "{}", error);
+        }
+        assert!(toml::from_str::<crate::config::Config>("[[rules]]\nset_importance = 5\n").is_ok());
+    }
+}
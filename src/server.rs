@@ -0,0 +1,591 @@
+use crate::error::YarmtlError;
+use crate::hooks::{self, HookPoint};
+use crate::store::TaskStore;
+use crate::task::Task;
+use crate::todoist_auth::SecretStore;
+use crate::config::load_config;
+use crate::git::git_commit_tasks_with_message;
+use crate::links::locate_task;
+use crate::paths::get_sync_dir;
+use crate::sync_engine::SyncBackend;
+use crate::{output, subtasks, trash};
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{Local, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// The token the /api routes want as `Authorization: Bearer ...`, made by the first `yarmtl serve`.
+const TOKEN_STORE: SecretStore = SecretStore {
+    service: "yarmtl-api",
+    username: "api-token",
+    file_name: ".api_token",
+};
+
+const SENSOR_ENTITY_ID: &str = "sensor.yarmtl_tasks";
+const SENSOR_ICON: &str = "mdi:format-list-checks";
+
+/// Attribute names exposed on the sensor, in the order Home Assistant should import them.
+const SENSOR_ATTRIBUTES: [&str; 11] = [
+    "open",
+    "overdue",
+    "due_today",
+    "upcoming",
+    "no_deadline",
+    "completed",
+    "cancelled",
+    "next_due_id",
+    "next_due_text",
+    "next_due_date",
+    "next_due_in_days",
+];
+
+/// Mirrors the state object Home Assistant returns from its own `/api/states/<entity_id>`,
+/// so a REST sensor can use `value_json.state` and `json_attributes_path: "$.attributes"` as-is.
+#[derive(Serialize, Debug)]
+pub struct HaSensorState {
+    pub entity_id: String,
+    pub state: String,
+    pub attributes: HaSensorAttributes,
+    pub last_updated: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct HaSensorAttributes {
+    pub friendly_name: String,
+    pub unit_of_measurement: String,
+    pub icon: String,
+    pub open: usize,
+    pub overdue: usize,
+    pub due_today: usize,
+    pub upcoming: usize,
+    pub no_deadline: usize,
+    pub completed: usize,
+    pub cancelled: usize,
+    pub next_due_id: Option<String>,
+    pub next_due_text: Option<String>,
+    pub next_due_date: Option<String>,
+    pub next_due_in_days: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct HaDiscovery {
+    pub name: String,
+    pub version: String,
+    pub sensors: Vec<HaRestSensorConfig>,
+}
+
+/// One entry of Home Assistant's `rest` sensor platform, ready to paste under `sensor:`.
+#[derive(Serialize, Debug)]
+pub struct HaRestSensorConfig {
+    pub platform: String,
+    pub name: String,
+    pub unique_id: String,
+    pub resource: String,
+    pub value_template: String,
+    pub json_attributes_path: String,
+    pub json_attributes: Vec<String>,
+    pub unit_of_measurement: String,
+    pub icon: String,
+    pub scan_interval: u32,
+}
+
+pub fn build_sensor_state(tasks: &[Task], today: NaiveDate) -> HaSensorState {
+    let mut attributes = HaSensorAttributes {
+        friendly_name: "yarmtl tasks".to_string(),
+        unit_of_measurement: "tasks".to_string(),
+        icon: SENSOR_ICON.to_string(),
+        ..HaSensorAttributes::default()
+    };
+
+    let mut next_due: Option<&Task> = None;
+
+    for task in tasks {
+        if task.cancelled {
+            attributes.cancelled += 1;
+            continue;
+        }
+        if task.completed {
+            attributes.completed += 1;
+            continue;
+        }
+
+        attributes.open += 1;
+        match task.deadline {
+            Some(deadline) if deadline < today => attributes.overdue += 1,
+            Some(deadline) if deadline == today => attributes.due_today += 1,
+            Some(_) => attributes.upcoming += 1,
+            None => attributes.no_deadline += 1,
+        }
+
+        // The earliest open deadline wins, so an overdue task is reported before today's
+        if task.deadline.is_some() && next_due.is_none_or(|current| task.deadline < current.deadline) {
+            next_due = Some(task);
+        }
+    }
+
+    if let Some(task) = next_due
+        && let Some(deadline) = task.deadline {
+        attributes.next_due_id = Some(task.id.clone());
+        attributes.next_due_text = Some(task.text.clone());
+        attributes.next_due_date = Some(deadline.format("%Y-%m-%d").to_string());
+        attributes.next_due_in_days = Some((deadline - today).num_days());
+    }
+
+    HaSensorState {
+        entity_id: SENSOR_ENTITY_ID.to_string(),
+        state: attributes.open.to_string(),
+        attributes,
+        last_updated: Utc::now().to_rfc3339(),
+    }
+}
+
+pub fn build_discovery(base_url: &str) -> HaDiscovery {
+    HaDiscovery {
+        name: "yarmtl".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        sensors: vec![HaRestSensorConfig {
+            platform: "rest".to_string(),
+            name: "yarmtl tasks".to_string(),
+            unique_id: "yarmtl_tasks".to_string(),
+            resource: format!("{}/ha/sensor", base_url.trim_end_matches('/')),
+            value_template: "{{ value_json.state }}".to_string(),
+            json_attributes_path: "$.attributes".to_string(),
+            json_attributes: SENSOR_ATTRIBUTES.iter().map(|a| a.to_string()).collect(),
+            unit_of_measurement: "tasks".to_string(),
+            icon: SENSOR_ICON.to_string(),
+            scan_interval: 60,
+        }],
+    }
+}
+
+async fn ha_sensor() -> Result<Json<HaSensorState>, ApiError> {
+    let store = TaskStore::open_default()?;
+    Ok(Json(build_sensor_state(store.tasks(), Local::now().date_naive())))
+}
+
+async fn ha_discovery(headers: HeaderMap) -> Json<HaDiscovery> {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("localhost");
+    Json(build_discovery(&format!("http://{}", host)))
+}
+
+/// A task as the /api routes and the hooks see it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiTask {
+    pub id: String,
+    pub text: String,
+    pub project: Option<String>,
+    /// The file tasks.md includes it from, None for tasks.md itself
+    pub source: Option<String>,
+    pub parent_id: Option<String>,
+    pub tags: Vec<String>,
+    pub contexts: Vec<String>,
+    pub deadline: Option<NaiveDate>,
+    pub reminder: Option<NaiveDate>,
+    /// Like "every monday"
+    pub repeat: Option<String>,
+    pub importance: Option<u8>,
+    pub notes: Option<String>,
+    pub completed: bool,
+    pub cancelled: bool,
+    pub pinned: bool,
+    pub created: Option<NaiveDate>,
+    pub done: Option<NaiveDate>,
+}
+
+impl From<&Task> for ApiTask {
+    fn from(task: &Task) -> Self {
+        ApiTask {
+            id: task.id.clone(),
+            text: task.text.clone(),
+            project: task.project.clone(),
+            source: task.source.clone(),
+            parent_id: task.parent_id.clone(),
+            tags: task.tags.clone(),
+            contexts: task.contexts.clone(),
+            deadline: task.deadline,
+            reminder: task.reminder,
+            repeat: task.repeat.map(|repeat| repeat.to_string()),
+            importance: task.importance,
+            notes: task.notes.clone(),
+            completed: task.completed,
+            cancelled: task.cancelled,
+            pinned: task.pinned,
+            created: task.created,
+            done: task.done,
+        }
+    }
+}
+
+/// What the /api routes answer with when they can't do what was asked, as `{"error": ...}`.
+#[derive(Debug)]
+pub enum ApiError {
+    Unauthorized,
+    NotFound(String),
+    BadRequest(String),
+    /// A hook turned the change down
+    Refused(String),
+    Internal(String),
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ApiError::Unauthorized => write!(f, "missing or wrong api token"),
+            ApiError::NotFound(message)
+            | ApiError::BadRequest(message)
+            | ApiError::Refused(message)
+            | ApiError::Internal(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<YarmtlError> for ApiError {
+    fn from(error: YarmtlError) -> Self {
+        match error {
+            YarmtlError::NoTask(_) | YarmtlError::AmbiguousId { .. } => ApiError::NotFound(error.to_string()),
+            error => ApiError::Internal(error.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Refused(_) => StatusCode::FORBIDDEN,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+struct Api {
+    token: String,
+    /// Held from loading tasks.md to saving it, so requests don't undo each other's changes
+    writes: Mutex<()>,
+}
+
+/// Whether the request carries `token` as its bearer token. Compared in full, so how long
+/// a wrong token takes doesn't tell how much of it was right.
+fn is_authorized(headers: &HeaderMap, token: &str) -> bool {
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    given.is_some_and(|given| {
+        given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    })
+}
+
+async fn require_token(State(api): State<Arc<Api>>, request: Request, next: Next) -> Response {
+    if !is_authorized(request.headers(), &api.token) {
+        return ApiError::Unauthorized.into_response();
+    }
+    next.run(request).await
+}
+
+/// Commits what was just saved, the way the CLI does; `TaskStore::open_default` already
+/// asked for a sync and the webhooks.
+fn commit(commit_message: &str) {
+    if let Err(e) = git_commit_tasks_with_message(Some(commit_message)) {
+        tracing::warn!("{}", e);
+    }
+}
+
+/// Which tasks to list.
+#[derive(Deserialize, Default)]
+pub struct ListQuery {
+    /// Include completed and cancelled tasks
+    #[serde(default)]
+    pub completed: bool,
+    pub project: Option<String>,
+    pub context: Option<String>,
+}
+
+impl ListQuery {
+    pub fn matches(&self, task: &Task) -> bool {
+        (self.completed || !task.completed)
+            && self.project.as_ref().is_none_or(|project| task.project.as_ref() == Some(project))
+            && self.context.as_ref().is_none_or(|context| task.contexts.contains(context))
+    }
+}
+
+async fn list_tasks(Query(query): Query<ListQuery>) -> Result<Json<Vec<ApiTask>>, ApiError> {
+    let store = TaskStore::open_default()?;
+    Ok(Json(store.tasks().iter().filter(|task| query.matches(task)).map(ApiTask::from).collect()))
+}
+
+async fn get_task(Path(id): Path<String>) -> Result<Json<ApiTask>, ApiError> {
+    let store = TaskStore::open_default()?;
+    Ok(Json(ApiTask::from(&store.tasks()[locate_task(store.tasks(), &id)?])))
+}
+
+#[derive(Deserialize)]
+pub struct NewTask {
+    /// With the usual markers, like on the command line
+    pub text: String,
+    /// `## ` heading to add it under, made when missing
+    pub project: Option<String>,
+}
+
+/// Adds the task to tasks.md, unless the on-add hook refuses it, and saves it, see `commit`.
+pub fn add(new: NewTask) -> Result<Task, ApiError> {
+    let mut task = Task::parse(&new.text);
+    if task.text.is_empty() {
+        return Err(ApiError::BadRequest("the task has no text".to_string()));
+    }
+    task.created = Some(Local::now().date_naive());
+    task.project = new.project;
+    let task = hooks::run_task_hook(&get_sync_dir(), HookPoint::Add, &task).map_err(ApiError::Refused)?;
+
+    let task = TaskStore::open_default()?.add(task)?;
+    commit(&format!("➕ Added task: \"{}\"", task.text));
+    Ok(task)
+}
+
+async fn add_task(State(api): State<Arc<Api>>, Json(new): Json<NewTask>) -> Result<(StatusCode, Json<ApiTask>), ApiError> {
+    let _writing = api.writes.lock().await;
+    let task = add(new)?;
+    Ok((StatusCode::CREATED, Json(ApiTask::from(&task))))
+}
+
+#[derive(Deserialize, Default)]
+pub struct TaskEdit {
+    /// Typed over the task, see `retyped`
+    pub text: Option<String>,
+    pub completed: Option<bool>,
+    pub cancelled: Option<bool>,
+}
+
+/// The task with `line` typed over it: the text and markers in the line replace the
+/// task's, while its id, dates, nesting, attachments and time spent stay.
+fn retyped(task: &Task, line: &str) -> Task {
+    let typed = Task::parse(line);
+    let mut attachments = task.attachments.clone();
+    attachments.extend(typed.attachments.iter().filter(|a| !task.attachments.contains(a)).cloned());
+    Task {
+        id: task.id.clone(),
+        last_fired: task.last_fired,
+        completed: task.completed,
+        cancelled: task.cancelled,
+        indent_level: task.indent_level,
+        parent_id: task.parent_id.clone(),
+        project: task.project.clone(),
+        attachments,
+        created: task.created,
+        done: task.done,
+        spent: task.spent,
+        ..typed
+    }
+}
+
+/// Changes the task `id` points at and saves it, see `commit`.
+pub fn edit(id: &str, edit: TaskEdit) -> Result<Task, ApiError> {
+    let today = Local::now().date_naive();
+    let mut store = TaskStore::open_default()?;
+    let index = locate_task(store.tasks(), id)?;
+    let tasks = store.tasks_mut();
+
+    let mut commit_message = format!("✏️ Edited task: \"{}\"", tasks[index].text);
+    if let Some(line) = &edit.text {
+        let task = retyped(&tasks[index], line);
+        if task.text.is_empty() {
+            return Err(ApiError::BadRequest("the task has no text".to_string()));
+        }
+        tasks[index] = task;
+    }
+    let task = &mut tasks[index];
+    if let Some(cancelled) = edit.cancelled.filter(|c| *c != task.cancelled) {
+        task.set_cancelled(cancelled, today);
+        let action = if cancelled { "🚫 Cancelled task" } else { "⏳ Reopened task" };
+        commit_message = format!("{}: \"{}\"", action, task.text);
+    }
+    if let Some(completed) = edit.completed.filter(|c| *c != task.completed) {
+        if completed {
+            *task = hooks::run_task_hook(&get_sync_dir(), HookPoint::Complete, task).map_err(ApiError::Refused)?;
+        }
+        task.set_completed(completed, today);
+        let action = if completed { "✅ Marked task complete" } else { "⏳ Marked task incomplete" };
+        commit_message = format!("{}: \"{}\"", action, task.text);
+        if completed && load_config().tasks.auto_complete_parents {
+            for parent in subtasks::complete_finished_parents(tasks, index, today) {
+                commit_message.push_str(&format!(", \"{}\"", tasks[parent].text));
+            }
+        }
+    }
+
+    let id = tasks[index].id.clone();
+    store.save()?;
+    commit(&commit_message);
+    let saved = store.tasks().iter().find(|t| t.id == id).cloned();
+    saved.ok_or_else(|| YarmtlError::NoTask(id).into())
+}
+
+async fn edit_task(
+    State(api): State<Arc<Api>>,
+    Path(id): Path<String>,
+    Json(changes): Json<TaskEdit>,
+) -> Result<Json<ApiTask>, ApiError> {
+    let _writing = api.writes.lock().await;
+    Ok(Json(ApiTask::from(&edit(&id, changes)?)))
+}
+
+/// Moves the task to the trash, like `d` in the TUI. Its subtasks stay, one level up.
+async fn delete_task(State(api): State<Arc<Api>>, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    let _writing = api.writes.lock().await;
+    let mut store = TaskStore::open_default()?;
+    let index = locate_task(store.tasks(), &id)?;
+    let task = subtasks::remove_task(store.tasks_mut(), index);
+    let commit_message = format!("🗑️ Deleted task: \"{}\"", task.text);
+    trash::move_to_trash(&get_sync_dir(), task, Local::now().date_naive())
+        .map_err(|e| ApiError::Internal(format!("couldn't move the task to the trash: {}", e)))?;
+    store.save()?;
+    commit(&commit_message);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct SyncQuery {
+    /// todoist (the default), caldav, google or github
+    backend: Option<String>,
+}
+
+/// Starts a `yarmtl sync` in the background; it commits what it changes like any other.
+async fn trigger_sync(Query(query): Query<SyncQuery>) -> Result<StatusCode, ApiError> {
+    let backend = query.backend.unwrap_or_else(|| "todoist".to_string());
+    SyncBackend::from_str(&backend, true).map_err(|_| ApiError::BadRequest(format!("no sync backend {}", backend)))?;
+    std::env::current_exe()
+        .and_then(|exe| {
+            Command::new(exe)
+                .args(["sync", "--backend", &backend])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+        })
+        .map_err(|e| ApiError::Internal(format!("couldn't start the sync: {}", e)))?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// The Home Assistant routes, open to anyone who can reach the server, and the /api
+/// routes, which want `token`.
+pub fn router(token: String) -> Router {
+    let api = Arc::new(Api { token, writes: Mutex::new(()) });
+    let tasks = Router::new()
+        .route("/api/tasks", get(list_tasks).post(add_task))
+        .route("/api/tasks/:id", get(get_task).patch(edit_task).delete(delete_task))
+        .route("/api/sync", post(trigger_sync))
+        .route_layer(middleware::from_fn_with_state(api.clone(), require_token))
+        .with_state(api);
+
+    Router::new()
+        .route("/ha/sensor", get(ha_sensor))
+        .route("/ha/discovery", get(ha_discovery))
+        .merge(tasks)
+}
+
+/// The api token, made and stored on the first run or with `renew`. The bool says
+/// whether it is a new one.
+fn api_token(renew: bool) -> Result<(String, bool), crate::todoist_auth::AuthError> {
+    if !renew && let Ok(token) = TOKEN_STORE.get() {
+        return Ok((token, false));
+    }
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    TOKEN_STORE.store(&token)?;
+    Ok((token, true))
+}
+
+pub async fn run_server(bind: &str, port: u16, new_token: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (token, created) = api_token(new_token)?;
+    let listener = tokio::net::TcpListener::bind((bind, port)).await?;
+    let addr = listener.local_addr()?;
+    output::say(&format!("🌐 Serving yarmtl api on http://{}", addr));
+    output::say(&format!("📋 Tasks: http://{}/api/tasks", addr));
+    if created {
+        output::say(&format!("🔑 New api token, send it as 'Authorization: Bearer {}'", token));
+    }
+    output::say(&format!("🏠 Home Assistant sensor: http://{}/ha/sensor", addr));
+    output::say("💡 Press Ctrl+C to stop");
+
+    // The links of the reminder emails, for when they point here rather than at the daemon
+    let actions = crate::email_actions::router(crate::email_actions::secret()?);
+    axum::serve(listener, router(token).merge(actions)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(text: &str, deadline: Option<NaiveDate>, completed: bool) -> Task {
+        let mut task = Task::parse(text);
+        task.deadline = deadline;
+        task.completed = completed;
+        task
+    }
+
+    #[test]
+    fn test_sensor_counts_and_next_due() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 10).unwrap();
+        let tasks = vec![
+            task("late", NaiveDate::from_ymd_opt(2025, 6, 8), false),
+            task("now", Some(today), false),
+            task("soon", NaiveDate::from_ymd_opt(2025, 6, 12), false),
+            task("someday", None, false),
+            task("done", Some(today), true),
+        ];
+
+        let sensor = build_sensor_state(&tasks, today);
+
+        assert_eq!(sensor.state, "4");
+        assert_eq!(sensor.attributes.overdue, 1);
+        assert_eq!(sensor.attributes.due_today, 1);
+        assert_eq!(sensor.attributes.upcoming, 1);
+        assert_eq!(sensor.attributes.no_deadline, 1);
+        assert_eq!(sensor.attributes.completed, 1);
+        assert_eq!(sensor.attributes.next_due_text.as_deref(), Some("late"));
+        assert_eq!(sensor.attributes.next_due_in_days, Some(-2));
+    }
+
+    #[test]
+    fn test_api_token_and_edits() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_authorized(&headers, "s3cret"));
+        headers.insert(header::AUTHORIZATION, "Bearer s3cre".parse().unwrap());
+        assert!(!is_authorized(&headers, "s3cret"));
+        headers.insert(header::AUTHORIZATION, "Bearer s3cret".parse().unwrap());
+        assert!(is_authorized(&headers, "s3cret"));
+
+        let mut task = Task::parse("call bank #home !2025-06-20 [id:aaaa01] [created:2025-06-01] [attach:form.pdf]");
+        task.project = Some("Home".to_string());
+        let edited = retyped(&task, "call the bank about fees #money $1 [id:bbbb02]");
+        assert_eq!((edited.id.as_str(), edited.text.as_str()), ("aaaa01", "call the bank about fees"));
+        assert_eq!((edited.tags, edited.deadline, edited.importance), (vec!["money".to_string()], None, Some(1)));
+        assert_eq!((edited.created, edited.project, edited.attachments), (task.created, task.project, task.attachments));
+    }
+
+    #[test]
+    fn test_discovery_points_at_sensor() {
+        let discovery = build_discovery("http://127.0.0.1:8765/");
+        let sensor = &discovery.sensors[0];
+
+        assert_eq!(sensor.resource, "http://127.0.0.1:8765/ha/sensor");
+        assert_eq!(sensor.json_attributes.len(), SENSOR_ATTRIBUTES.len());
+    }
+}
@@ -0,0 +1,208 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The name of the unit, agent or task.
+const NAME: &str = "yarmtl";
+
+/// launchd wants a reverse domain name.
+const LAUNCHD_LABEL: &str = "com.github.uraniumcovid.yarmtl";
+
+/// What starts the daemon at login on this platform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServiceKind {
+    /// A systemd user unit
+    Systemd,
+    /// A launchd agent, on macOS
+    Launchd,
+    /// A Scheduled Task run at logon, on Windows
+    ScheduledTask,
+}
+
+impl ServiceKind {
+    pub fn current() -> Self {
+        if cfg!(target_os = "macos") {
+            ServiceKind::Launchd
+        } else if cfg!(windows) {
+            ServiceKind::ScheduledTask
+        } else {
+            ServiceKind::Systemd
+        }
+    }
+
+    /// Where the service file goes; Scheduled Tasks have none, Windows keeps them.
+    fn path(self) -> Option<PathBuf> {
+        let home = PathBuf::from(std::env::var_os("HOME")?);
+        match self {
+            ServiceKind::Systemd => {
+                let config = std::env::var_os("XDG_CONFIG_HOME").map_or_else(|| home.join(".config"), PathBuf::from);
+                Some(config.join("systemd/user").join(format!("{}.service", NAME)))
+            }
+            ServiceKind::Launchd => Some(home.join("Library/LaunchAgents").join(format!("{}.plist", LAUNCHD_LABEL))),
+            ServiceKind::ScheduledTask => None,
+        }
+    }
+}
+
+/// What the daemon is started with.
+pub struct Service {
+    pub exe: PathBuf,
+    /// The tasks directory, which the daemon runs in
+    pub dir: PathBuf,
+    /// PATH as it is now, for git and the hooks; systemd and launchd start with a bare one
+    pub path_env: String,
+}
+
+impl Service {
+    /// The unit file, the plist, or the schtasks command line for a Scheduled Task.
+    pub fn definition(&self, kind: ServiceKind) -> String {
+        let exe = self.exe.display();
+        let dir = self.dir.display();
+        match kind {
+            ServiceKind::Systemd => format!(
+                "[Unit]\n\
+                 Description=yarmtl daemon: reminders, sync, notifications and webhooks\n\
+                 After=network-online.target\n\
+                 \n\
+                 [Service]\n\
+                 ExecStart=\"{exe}\" daemon start\n\
+                 WorkingDirectory={dir}\n\
+                 Environment=\"PATH={path}\"\n\
+                 Restart=on-failure\n\
+                 RestartSec=30\n\
+                 \n\
+                 [Install]\n\
+                 WantedBy=default.target\n",
+                path = self.path_env,
+            ),
+            ServiceKind::Launchd => format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+                 <plist version=\"1.0\">\n\
+                 <dict>\n\
+                 \t<key>Label</key>\n\t<string>{LAUNCHD_LABEL}</string>\n\
+                 \t<key>ProgramArguments</key>\n\t<array>\n\t\t<string>{exe}</string>\n\t\t<string>daemon</string>\n\t\t<string>start</string>\n\t</array>\n\
+                 \t<key>WorkingDirectory</key>\n\t<string>{dir}</string>\n\
+                 \t<key>EnvironmentVariables</key>\n\t<dict>\n\t\t<key>PATH</key>\n\t\t<string>{path}</string>\n\t</dict>\n\
+                 \t<key>RunAtLoad</key>\n\t<true/>\n\
+                 \t<key>KeepAlive</key>\n\t<dict>\n\t\t<key>SuccessfulExit</key>\n\t\t<false/>\n\t</dict>\n\
+                 \t<key>StandardErrorPath</key>\n\t<string>{stderr}</string>\n\
+                 </dict>\n\
+                 </plist>\n",
+                exe = xml_escape(&exe.to_string()),
+                dir = xml_escape(&dir.to_string()),
+                path = xml_escape(&self.path_env),
+                stderr = xml_escape(&self.dir.join("daemon.stderr.log").display().to_string()),
+            ),
+            ServiceKind::ScheduledTask => format!(
+                "schtasks /Create /TN {NAME} /TR \"\\\"{exe}\\\" daemon start\" /SC ONLOGON /RL LIMITED /F"
+            ),
+        }
+    }
+
+    /// Writes the service and starts it, and at every login from now on. Returns where it went.
+    pub fn install(&self, kind: ServiceKind) -> Result<String, String> {
+        match kind {
+            ServiceKind::Systemd | ServiceKind::Launchd => {
+                let path = kind.path().ok_or("HOME isn't set")?;
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| format!("couldn't create {}: {}", parent.display(), e))?;
+                }
+                fs::write(&path, self.definition(kind)).map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+                if kind == ServiceKind::Systemd {
+                    run("systemctl", &["--user", "daemon-reload"])?;
+                    run("systemctl", &["--user", "enable", "--now", &format!("{}.service", NAME)])?;
+                } else {
+                    // Loading an agent that is loaded already fails, it is started anew instead
+                    let _ = run("launchctl", &["unload", &path.display().to_string()]);
+                    run("launchctl", &["load", "-w", &path.display().to_string()])?;
+                }
+                Ok(path.display().to_string())
+            }
+            ServiceKind::ScheduledTask => {
+                let command = format!("\"{}\" daemon start", self.exe.display());
+                run("schtasks", &["/Create", "/TN", NAME, "/TR", &command, "/SC", "ONLOGON", "/RL", "LIMITED", "/F"])?;
+                run("schtasks", &["/Run", "/TN", NAME])?;
+                Ok(format!("the Scheduled Task {}", NAME))
+            }
+        }
+    }
+}
+
+/// Stops the service and takes it out. Returns what was removed.
+pub fn uninstall(kind: ServiceKind) -> Result<String, String> {
+    match kind {
+        ServiceKind::Systemd | ServiceKind::Launchd => {
+            let path = kind.path().ok_or("HOME isn't set")?;
+            if !path.exists() {
+                return Err(format!("{} isn't installed", path.display()));
+            }
+            if kind == ServiceKind::Systemd {
+                run("systemctl", &["--user", "disable", "--now", &format!("{}.service", NAME)])?;
+            } else {
+                run("launchctl", &["unload", "-w", &path.display().to_string()])?;
+            }
+            fs::remove_file(&path).map_err(|e| format!("couldn't remove {}: {}", path.display(), e))?;
+            if kind == ServiceKind::Systemd {
+                run("systemctl", &["--user", "daemon-reload"])?;
+            }
+            Ok(path.display().to_string())
+        }
+        ServiceKind::ScheduledTask => {
+            // Not running is fine
+            let _ = run("schtasks", &["/End", "/TN", NAME]);
+            run("schtasks", &["/Delete", "/TN", NAME, "/F"])?;
+            Ok(format!("the Scheduled Task {}", NAME))
+        }
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("couldn't run {}: {}", program, e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(format!("{} {} failed: {}", program, args.join(" "), String::from_utf8_lossy(&output.stderr).trim()))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// The service starting this binary, in the tasks directory `dir`.
+pub fn service_for(dir: &Path) -> Result<Service, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("couldn't find the yarmtl binary: {}", e))?;
+    Ok(Service {
+        exe,
+        dir: dir.to_path_buf(),
+        path_env: std::env::var("PATH").unwrap_or_else(|_| "/usr/local/bin:/usr/bin:/bin".to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_definitions() {
+        let service = Service {
+            exe: PathBuf::from("/home/me/.cargo/bin/yarmtl"),
+            dir: PathBuf::from("/home/me/R&D tasks"),
+            path_env: "/usr/bin:/bin".to_string(),
+        };
+        let unit = service.definition(ServiceKind::Systemd);
+        assert!(unit.contains("ExecStart=\"/home/me/.cargo/bin/yarmtl\" daemon start\n"));
+        assert!(unit.contains("WorkingDirectory=/home/me/R&D tasks\n"));
+
+        let plist = service.definition(ServiceKind::Launchd);
+        assert!(plist.contains("<string>/home/me/R&amp;D tasks</string>"));
+        assert!(plist.contains("<string>daemon</string>\n\t\t<string>start</string>"));
+        let options = roxmltree::ParsingOptions { allow_dtd: true, ..roxmltree::ParsingOptions::default() };
+        assert!(roxmltree::Document::parse_with_options(&plist, options).is_ok());
+
+        assert!(service.definition(ServiceKind::ScheduledTask).contains("/SC ONLOGON"));
+    }
+}
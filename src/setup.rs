@@ -0,0 +1,261 @@
+use crate::config::Config;
+use crate::todoist_auth::TodoistAuth;
+use crate::reminder_email::Addresses;
+use crate::email::EmailConfig;
+use crate::todoist_sync::TodoistConfig;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where yarmtl keeps its data unless the setup wizard picked another tasks directory:
+/// `$XDG_DATA_HOME/yarmtl`, or ~/.local/share/yarmtl. An install from before XDG_DATA_HOME
+/// was looked at stays where it is.
+pub fn data_dir() -> PathBuf {
+    let home = PathBuf::from(env::var("HOME").unwrap_or_default());
+    let before = home.join(".local/share/yarmtl");
+    match xdg_dir("XDG_DATA_HOME").map(|dir| dir.join("yarmtl")) {
+        Some(dir) if dir.exists() || !before.exists() => dir,
+        _ => before,
+    }
+}
+
+/// Where the config that only applies on this machine goes: `$XDG_CONFIG_HOME/yarmtl`, or
+/// ~/.config/yarmtl. What is the same everywhere, config.toml, stays in the tasks directory.
+pub fn config_dir() -> PathBuf {
+    let home = PathBuf::from(env::var("HOME").unwrap_or_default());
+    xdg_dir("XDG_CONFIG_HOME").unwrap_or_else(|| home.join(".config")).join("yarmtl")
+}
+
+/// An XDG base directory from `variable`; the spec says to ignore one that isn't absolute.
+fn xdg_dir(variable: &str) -> Option<PathBuf> {
+    env::var_os(variable).map(PathBuf::from).filter(|dir| dir.is_absolute())
+}
+
+pub fn default_tasks_dir() -> PathBuf {
+    data_dir().join("yarmtl-tasks")
+}
+
+/// Written by the setup wizard. It lives outside the tasks directory since it says where that is.
+pub fn settings_path() -> PathBuf {
+    data_dir().join("settings.toml")
+}
+
+/// The SQLite index of the tasks, `[index]` in config.toml; made again from tasks.md when deleted.
+pub fn index_path() -> PathBuf {
+    data_dir().join("index.sqlite")
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct Settings {
+    pub tasks_dir: PathBuf,
+}
+
+pub fn load_settings() -> Option<Settings> {
+    let content = fs::read_to_string(settings_path()).ok()?;
+    toml::from_str(&content).ok()
+}
+
+/// True on the very first run. Older versions only left a `.yarmtl_settings`
+/// marker in the tasks directory, those installs don't need the wizard either.
+pub fn needs_setup(tasks_dir: &Path) -> bool {
+    !settings_path().exists() && !tasks_dir.join(".yarmtl_settings").exists()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    Welcome,
+    TasksDir,
+    Git,
+    Email,
+    Todoist,
+    Summary,
+}
+
+const STEPS: [Step; 6] = [Step::Welcome, Step::TasksDir, Step::Git, Step::Email, Step::Todoist, Step::Summary];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenCheck {
+    Unchecked,
+    Checking,
+    Valid,
+    Invalid(String),
+}
+
+/// The answers collected by the first-run wizard, nothing is written until `apply`.
+pub struct SetupWizard {
+    pub step: Step,
+    pub tasks_dir: String,
+    pub git_auto_commit: bool,
+    pub email: String,
+    pub todoist_token: String,
+    pub token_check: TokenCheck,
+}
+
+impl Default for SetupWizard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SetupWizard {
+    pub fn new() -> Self {
+        SetupWizard {
+            step: Step::Welcome,
+            tasks_dir: default_tasks_dir().display().to_string(),
+            git_auto_commit: true,
+            email: String::new(),
+            todoist_token: String::new(),
+            token_check: TokenCheck::Unchecked,
+        }
+    }
+
+    /// 1-based position of the current step, for "step 2/6".
+    pub fn step_number(&self) -> (usize, usize) {
+        let position = STEPS.iter().position(|s| *s == self.step).unwrap_or(0);
+        (position + 1, STEPS.len())
+    }
+
+    pub fn next_step(&mut self) {
+        let (number, total) = self.step_number();
+        self.step = STEPS[number.min(total - 1)];
+    }
+
+    pub fn previous_step(&mut self) {
+        let (number, _) = self.step_number();
+        self.step = STEPS[number.saturating_sub(2)];
+    }
+
+    /// The text field of the current step, if it has one.
+    pub fn input_mut(&mut self) -> Option<&mut String> {
+        match self.step {
+            Step::TasksDir => Some(&mut self.tasks_dir),
+            Step::Email => Some(&mut self.email),
+            Step::Todoist => {
+                // Editing the token invalidates an earlier check
+                self.token_check = TokenCheck::Unchecked;
+                Some(&mut self.todoist_token)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn chosen_tasks_dir(&self) -> PathBuf {
+        let dir = self.tasks_dir.trim();
+        match dir.strip_prefix("~/") {
+            Some(rest) => PathBuf::from(env::var("HOME").unwrap_or_default()).join(rest),
+            None if dir.is_empty() => default_tasks_dir(),
+            None => PathBuf::from(dir),
+        }
+    }
+
+    /// What `apply` is going to do, shown on the last step.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Tasks directory: {}", self.chosen_tasks_dir().display()),
+            format!("Git auto-commit: {}", if self.git_auto_commit { "on" } else { "off" }),
+        ];
+        if self.email.trim().is_empty() {
+            lines.push("Email reminders: not set up".to_string());
+        } else {
+            lines.push(format!("Email reminders: to {} (store the SMTP password with yarmtl --setup-email)", self.email.trim()));
+        }
+        lines.push(match self.token_check {
+            TokenCheck::Valid => "Todoist: sync enabled".to_string(),
+            _ => "Todoist: not set up".to_string(),
+        });
+        lines
+    }
+
+    /// Writes the answers: config.toml in the tasks directory, the email and Todoist
+    /// configs when they were filled in, and finally `settings_path`, which marks setup done.
+    /// Returns the tasks directory.
+    pub fn apply(&self, settings_path: &Path, email_config_path: &Path) -> Result<PathBuf, String> {
+        let tasks_dir = self.chosen_tasks_dir();
+        fs::create_dir_all(&tasks_dir)
+            .map_err(|e| format!("couldn't create {}: {}", tasks_dir.display(), e))?;
+
+        // Keep whatever an existing config.toml already says
+        let config_path = tasks_dir.join("config.toml");
+        let mut config = Config::load(&config_path).unwrap_or_default();
+        config.git.auto_commit = self.git_auto_commit;
+        let content = toml::to_string_pretty(&config).map_err(|e| e.to_string())?;
+        fs::write(&config_path, content).map_err(|e| format!("couldn't write config.toml: {}", e))?;
+
+        let email = self.email.trim();
+        if !email.is_empty() {
+            let email_config = EmailConfig {
+                username: email.to_string(),
+                from_email: email.to_string(),
+                to_email: Addresses::One(email.to_string()),
+                ..EmailConfig::default()
+            };
+            let content = toml::to_string_pretty(&email_config).map_err(|e| e.to_string())?;
+            fs::write(email_config_path, content).map_err(|e| format!("couldn't write email config: {}", e))?;
+        }
+
+        if self.token_check == TokenCheck::Valid {
+            TodoistAuth::store_token(self.todoist_token.trim()).map_err(|e| e.to_string())?;
+            let content = toml::to_string_pretty(&TodoistConfig::default()).map_err(|e| e.to_string())?;
+            fs::write(tasks_dir.join("todoist_config.toml"), content)
+                .map_err(|e| format!("couldn't write todoist config: {}", e))?;
+        }
+
+        if let Some(parent) = settings_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let settings = Settings { tasks_dir: tasks_dir.clone() };
+        fs::write(settings_path, toml::to_string_pretty(&settings).map_err(|e| e.to_string())?)
+            .map_err(|e| format!("couldn't write {}: {}", settings_path.display(), e))?;
+
+        Ok(tasks_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_stop_at_both_ends() {
+        let mut wizard = SetupWizard::new();
+        wizard.previous_step();
+        assert_eq!(wizard.step, Step::Welcome);
+
+        for _ in 0..10 {
+            wizard.next_step();
+        }
+        assert_eq!(wizard.step, Step::Summary);
+        assert_eq!(wizard.step_number(), (6, 6));
+
+        wizard.previous_step();
+        assert_eq!(wizard.step, Step::Todoist);
+    }
+
+    #[test]
+    fn test_apply_writes_config_and_settings() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        let tasks_dir = root.join("tasks");
+        fs::create_dir_all(&tasks_dir).unwrap();
+        fs::write(tasks_dir.join("config.toml"), "[ui]\ntheme = \"light\"\n").unwrap();
+
+        let mut wizard = SetupWizard::new();
+        wizard.tasks_dir = tasks_dir.display().to_string();
+        wizard.git_auto_commit = false;
+        wizard.email = " me@example.com ".to_string();
+
+        let settings_path = root.join("settings.toml");
+        let email_path = root.join("email_config.toml");
+        assert_eq!(wizard.apply(&settings_path, &email_path).unwrap(), tasks_dir);
+
+        let config = Config::load(&tasks_dir.join("config.toml")).unwrap();
+        assert!(!config.git.auto_commit);
+        assert_eq!(config.ui.theme, crate::config::ThemeSetting::Light);
+
+        let settings: Settings = toml::from_str(&fs::read_to_string(&settings_path).unwrap()).unwrap();
+        assert_eq!(settings, Settings { tasks_dir: tasks_dir.clone() });
+        assert!(fs::read_to_string(&email_path).unwrap().contains("to_email = \"me@example.com\""));
+        assert!(!tasks_dir.join("todoist_config.toml").exists());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::email_actions::escape_html;
+use crate::task::Task;
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+
+/// One page of the site, at `path` under the directory it is written to.
+#[derive(Debug, PartialEq)]
+pub struct Page {
+    /// index.html, projects/<project>.html or tags/<tag>.html
+    pub path: String,
+    pub html: String,
+}
+
+/// The task list as a static site to publish, like a public roadmap: index.html with
+/// every task under its project, and a page per project and per tag. Only what a reader
+/// would see on a roadmap goes in: text, state, deadline, tags and subtasks, never notes,
+/// contexts or attachments.
+pub fn render(tasks: &[Task], today: NaiveDate) -> Vec<Page> {
+    let mut projects: Vec<(Option<&str>, Vec<&Task>)> = Vec::new();
+    for task in tasks {
+        match projects.iter_mut().find(|(project, _)| *project == task.project.as_deref()) {
+            Some((_, tasks)) => tasks.push(task),
+            None => projects.push((task.project.as_deref(), vec![task])),
+        }
+    }
+    let mut tags: BTreeMap<&str, Vec<&Task>> = BTreeMap::new();
+    for task in tasks {
+        for tag in &task.tags {
+            tags.entry(tag).or_default().push(task);
+        }
+    }
+
+    let nav = |root| navigation(&projects, &tags, root);
+    let mut index = String::new();
+    for (project, tasks) in &projects {
+        let heading = match project {
+            Some(project) => format!("<a href=\"projects/{}.html\">{}</a>", slug(project), escape_html(project)),
+            None => "Tasks".to_string(),
+        };
+        index.push_str(&format!("<h2>{}</h2>\n{}", heading, task_list(tasks, "", today)));
+    }
+    let mut pages = vec![Page { path: "index.html".to_string(), html: page("Tasks", "", &nav(""), &index, today) }];
+
+    for (project, tasks) in projects.iter().filter_map(|(project, tasks)| Some((project.as_ref()?, tasks))) {
+        let body = format!("<h2>{}</h2>\n{}", escape_html(project), task_list(tasks, "../", today));
+        pages.push(Page { path: format!("projects/{}.html", slug(project)), html: page(project, "../", &nav("../"), &body, today) });
+    }
+    for (tag, tasks) in &tags {
+        let title = format!("#{}", tag);
+        let body = format!("<h2>{}</h2>\n{}", escape_html(&title), task_list(tasks, "../", today));
+        pages.push(Page { path: format!("tags/{}.html", slug(tag)), html: page(&title, "../", &nav("../"), &body, today) });
+    }
+    pages
+}
+
+/// The links to every project and tag page, with how many tasks are open in each.
+fn navigation(projects: &[(Option<&str>, Vec<&Task>)], tags: &BTreeMap<&str, Vec<&Task>>, root: &str) -> String {
+    let open = |tasks: &[&Task]| tasks.iter().filter(|task| !task.completed).count();
+    let mut links: Vec<String> = projects
+        .iter()
+        .filter_map(|(project, tasks)| {
+            let project = project.as_ref()?;
+            Some(format!("<a href=\"{}projects/{}.html\">{}</a> {}", root, slug(project), escape_html(project), open(tasks)))
+        })
+        .collect();
+    links.extend(tags.iter().map(|(tag, tasks)| format!("<a href=\"{}tags/{}.html\">#{}</a> {}", root, slug(tag), escape_html(tag), open(tasks))));
+    match links.is_empty() {
+        true => String::new(),
+        false => format!("<nav>{}</nav>\n", links.join(" · ")),
+    }
+}
+
+/// The tasks as a list, each with its checkbox shown but not checkable, subtasks indented
+/// under their parent.
+fn task_list(tasks: &[&Task], root: &str, today: NaiveDate) -> String {
+    let mut out = String::from("<ul>\n");
+    for task in tasks {
+        let (class, checked) = match (task.completed, task.cancelled) {
+            (_, true) => ("cancelled", ""),
+            (true, false) => ("done", " checked"),
+            (false, false) => ("open", ""),
+        };
+        let mut line = format!(
+            "<li id=\"task-{}\" class=\"{}\" style=\"margin-left: {}em\"><input type=\"checkbox\" disabled{}> <span class=\"text\">{}</span>",
+            escape_html(&task.id),
+            class,
+            task.indent_level * 2,
+            checked,
+            escape_html(&task.text)
+        );
+        if let Some(deadline) = task.deadline {
+            let late = match !task.completed && deadline < today {
+                true => " late",
+                false => "",
+            };
+            line.push_str(&format!(" <span class=\"deadline{}\">due {}</span>", late, deadline.format("%Y-%m-%d")));
+        }
+        for tag in &task.tags {
+            line.push_str(&format!(" <a class=\"tag\" href=\"{}tags/{}.html\">#{}</a>", root, slug(tag), escape_html(tag)));
+        }
+        out.push_str(&line);
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ul>\n");
+    out
+}
+
+/// A whole page, `root` leading from it back to index.html.
+fn page(title: &str, root: &str, nav: &str, body: &str, today: NaiveDate) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 50em; margin: 2em auto; padding: 0 1em; color: #222; }}\n\
+         h1 a, h2 a {{ color: inherit; }}\n\
+         nav {{ margin-bottom: 1.5em; line-height: 1.8; }}\n\
+         ul {{ list-style: none; padding: 0; }}\n\
+         li {{ margin: 0.3em 0; }}\n\
+         .done .text, .cancelled .text {{ color: #888; text-decoration: line-through; }}\n\
+         .deadline {{ color: #666; font-size: 0.9em; }}\n\
+         .deadline.late {{ color: #c0392b; }}\n\
+         .tag {{ color: #ff6b8a; font-size: 0.9em; text-decoration: none; }}\n\
+         footer {{ margin-top: 3em; color: #888; font-size: 0.8em; }}\n\
+         </style>\n</head>\n<body>\n<h1><a href=\"{root}index.html\">{title}</a></h1>\n{nav}{body}\
+         <footer>updated {today}, made with yarmtl</footer>\n</body>\n</html>\n",
+        title = escape_html(title),
+        root = root,
+        nav = nav,
+        body = body,
+        today = today.format("%Y-%m-%d")
+    )
+}
+
+/// A project or tag as a file name: lowercase letters, digits and dashes.
+fn slug(name: &str) -> String {
+    let slug: String = name.to_lowercase().chars().map(|c| if c.is_alphanumeric() { c } else { '-' }).collect();
+    let slug = slug.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-");
+    match slug.is_empty() {
+        true => "untitled".to_string(),
+        false => slug,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task;
+
+    #[test]
+    fn test_site_of_a_roadmap() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        let tasks = task::parse_tasks(
+            "# tasks\n\n- [ ] sort out the <garage> #home // the code is 1234 [id:aaaa01]\n\
+             \n## Next Release\n- [x] dark mode #ui [id:aaaa02]\n- [ ] offline sync !2025-05-20 #ui [id:aaaa03]\n  - [-] old cache [id:aaaa04]\n",
+        );
+
+        let pages = render(&tasks, today);
+        let paths: Vec<&str> = pages.iter().map(|page| page.path.as_str()).collect();
+        assert_eq!(paths, ["index.html", "projects/next-release.html", "tags/home.html", "tags/ui.html"]);
+        let index = &pages[0].html;
+        assert!(index.contains("<span class=\"text\">sort out the &lt;garage&gt;</span>"));
+        assert!(!index.contains("1234"), "notes stay private");
+        assert!(index.contains("<li id=\"task-aaaa02\" class=\"done\" style=\"margin-left: 0em\"><input type=\"checkbox\" disabled checked>"));
+        assert!(index.contains("<span class=\"deadline late\">due 2025-05-20</span> <a class=\"tag\" href=\"tags/ui.html\">#ui</a>"));
+        assert!(index.contains("<a href=\"projects/next-release.html\">Next Release</a> 1 · "));
+        assert!(pages[3].html.contains("href=\"../tags/ui.html\""));
+        assert!(pages[1].html.contains("class=\"cancelled\" style=\"margin-left: 2em\""));
+    }
+}
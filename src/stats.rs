@@ -0,0 +1,224 @@
+use crate::error::YarmtlError;
+use crate::git;
+use crate::task::{Task, parse_tasks};
+use chrono::NaiveDate;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// A task and the day it was checked off, as recorded by the git history of tasks.md.
+/// Cancelled tasks are closed too, and keep `task.cancelled` set so they can be told apart.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub task: Task,
+    pub completed_on: NaiveDate,
+}
+
+/// Every committed version of tasks.md, oldest first, with its commit date.
+pub fn load_task_history(sync_dir: &Path) -> Result<Vec<(NaiveDate, Vec<Task>)>, YarmtlError> {
+    let history = git::file_history(sync_dir, "tasks.md")?;
+    Ok(history.into_iter().map(|version| (version.date, parse_tasks(&version.content))).collect())
+}
+
+/// Finds when each task was completed: its `done` date when it has one, otherwise the
+/// first snapshot showing it checked after one where it wasn't. Reopening a task drops
+/// its earlier completion, and cancelling a done task (or the other way round) replaces it.
+pub fn completions_from_history(history: &[(NaiveDate, Vec<Task>)]) -> Vec<Completion> {
+    let mut was_closed: HashMap<String, (bool, bool)> = HashMap::new();
+    let mut completions: HashMap<String, Completion> = HashMap::new();
+
+    for (date, tasks) in history {
+        for task in tasks {
+            let closed = (task.completed, task.cancelled);
+            let before = was_closed.insert(task.id.clone(), closed).unwrap_or((false, false));
+            if task.completed && closed != before {
+                let completed_on = task.done.unwrap_or(*date);
+                completions.insert(task.id.clone(), Completion { task: task.clone(), completed_on });
+            } else if !task.completed && before.0 {
+                completions.remove(&task.id);
+            }
+        }
+    }
+
+    let mut completions: Vec<Completion> = completions.into_values().collect();
+    completions.sort_by(|a, b| a.completed_on.cmp(&b.completed_on).then_with(|| a.task.id.cmp(&b.task.id)));
+    completions
+}
+
+/// A task line's checkbox and the first id on it, written as `[id:...]` or, in the obsidian
+/// format, after a 🆔. Both stand apart from the words around them, so `[[id:...]]` links
+/// and ids that only start with the one looked for don't count.
+static ID_LINE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^[ \t]*- \[([ x-])\] (?:.*?\s)?(?:\[id:([^\]\s]+)\]|🆔 (\S+))(?:\s|$)").unwrap());
+
+/// When a task without `created`/`done` dates was added and first checked off, going by
+/// the versions in git of tasks.md, the files it includes and archive.md. Reads them all,
+/// so only call it for one task at a time.
+pub fn dates_from_git(sync_dir: &Path, task_id: &str) -> (Option<NaiveDate>, Option<NaiveDate>) {
+    // tasks.md.age is looked at along with tasks.md
+    let files = git::task_files(sync_dir).into_iter().filter(|file| file != "tasks.md.age").chain(["archive.md".to_string()]);
+    let earliest = |found: Option<NaiveDate>, date: NaiveDate| Some(found.map_or(date, |found| found.min(date)));
+
+    let (mut created, mut done) = (None, None);
+    for file in files {
+        for version in git::file_history(sync_dir, &file).unwrap_or_default() {
+            for line in ID_LINE_RE.captures_iter(&version.content) {
+                if line.get(2).or(line.get(3)).map(|id| id.as_str()) != Some(task_id) {
+                    continue;
+                }
+                created = earliest(created, version.date);
+                if &line[1] == "x" {
+                    done = earliest(done, version.date);
+                }
+            }
+        }
+    }
+    (created, done)
+}
+
+/// "today", "yesterday", "3 days ago", "2 weeks ago", "5 months ago"
+pub fn relative_day(date: NaiveDate, today: NaiveDate) -> String {
+    match (today - date).num_days().max(0) {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        days @ 2..=13 => format!("{} days ago", days),
+        days @ 14..=59 => format!("{} weeks ago", days / 7),
+        days @ 60..=729 => format!("{} months ago", days / 30),
+        days => format!("{} years ago", days / 365),
+    }
+}
+
+/// The longest run of consecutive days with at least one completion, as (first day, last day, length).
+pub fn longest_streak(days: &[NaiveDate]) -> Option<(NaiveDate, NaiveDate, usize)> {
+    let mut days = days.to_vec();
+    days.sort();
+    days.dedup();
+
+    let mut best: Option<(NaiveDate, NaiveDate, usize)> = None;
+    let mut start = *days.first()?;
+    let mut length = 0;
+
+    for (i, day) in days.iter().enumerate() {
+        if i > 0 && (*day - days[i - 1]).num_days() == 1 {
+            length += 1;
+        } else {
+            start = *day;
+            length = 1;
+        }
+        if best.is_none_or(|(_, _, best_length)| length > best_length) {
+            best = Some((start, *day, length));
+        }
+    }
+
+    best
+}
+
+/// A one-line chart of `values` using block characters, scaled to the largest value.
+pub fn sparkline(values: &[usize]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&value| {
+            if max == 0 || value == 0 {
+                ' '
+            } else {
+                BARS[(value * (BARS.len() - 1)).div_ceil(max)]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, month, day).unwrap()
+    }
+
+    fn snapshot(tasks: &[(&str, bool)]) -> Vec<Task> {
+        tasks
+            .iter()
+            .map(|(line, completed)| {
+                let mut task = Task::parse(line);
+                task.completed = *completed;
+                task
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_completions_from_history() {
+        let history = vec![
+            (date(1, 1), snapshot(&[("a [id:aaaa0001]", false), ("b [id:bbbb0002]", false)])),
+            (date(1, 3), snapshot(&[("a [id:aaaa0001]", true), ("b [id:bbbb0002]", true)])),
+            // b was reopened and finished again later
+            (date(1, 4), snapshot(&[("a [id:aaaa0001]", true), ("b [id:bbbb0002]", false)])),
+            (date(2, 9), snapshot(&[("a [id:aaaa0001]", true), ("b [id:bbbb0002]", true)])),
+        ];
+
+        let completions = completions_from_history(&history);
+        assert_eq!(completions.len(), 2);
+        assert_eq!((completions[0].task.text.as_str(), completions[0].completed_on), ("a", date(1, 3)));
+        assert_eq!((completions[1].task.text.as_str(), completions[1].completed_on), ("b", date(2, 9)));
+    }
+
+    #[test]
+    fn test_streak_and_sparkline() {
+        let days = [date(3, 1), date(3, 2), date(3, 2), date(3, 3), date(3, 7), date(3, 8)];
+        assert_eq!(longest_streak(&days), Some((date(3, 1), date(3, 3), 3)));
+        assert_eq!(longest_streak(&[]), None);
+
+        assert_eq!(sparkline(&[0, 1, 4, 8]), " ▂▅█");
+    }
+
+    #[test]
+    fn test_done_date_wins_over_commit_date() {
+        let history = vec![
+            (date(1, 1), snapshot(&[("a [id:aaaa0001]", false)])),
+            // Checked off on the 2nd, committed on the 5th
+            (date(1, 5), snapshot(&[("a [id:aaaa0001] [done:2025-01-02]", true)])),
+        ];
+        assert_eq!(completions_from_history(&history)[0].completed_on, date(1, 2));
+
+        let task = Task::parse("a [id:aaaa0001] [created:2025-01-01] [done:2025-01-02] //note");
+        assert_eq!((task.created, task.done), (Some(date(1, 1)), Some(date(1, 2))));
+        assert_eq!(task.notes.as_deref(), Some("note"));
+        assert_eq!(Task::parse(&task.to_markdown()[6..]).done, Some(date(1, 2)));
+
+        assert_eq!(relative_day(date(1, 1), date(1, 4)), "3 days ago");
+        assert_eq!(relative_day(date(1, 3), date(1, 4)), "yesterday");
+    }
+
+    #[test]
+    fn test_dates_from_git_follow_the_exact_id_into_includes_and_the_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let commit = |day: u32, files: &[(&str, &str)]| {
+            let mut index = repo.index().unwrap();
+            for (file, content) in files {
+                std::fs::write(dir.path().join(file), content).unwrap();
+                index.add_path(Path::new(file)).unwrap();
+            }
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let when = git2::Time::new(date(6, day).and_hms_opt(12, 0, 0).unwrap().and_utc().timestamp(), 0);
+            let signature = git2::Signature::new("Ada", "ada@example.com", &when).unwrap();
+            let parents: Vec<git2::Commit> = repo.head().ok().map(|head| head.peel_to_commit().unwrap()).into_iter().collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, "tasks", &tree, &parents).unwrap();
+        };
+
+        commit(1, &[("tasks.md", "- [ ] see [[id:aaaa01]] [id:aaaa0100]\n<!-- include: work.md -->\n"), ("work.md", "")]);
+        commit(3, &[("work.md", "- [ ] pay rent [id:aaaa01]\n")]);
+        commit(5, &[("work.md", "- [x] pay rent [id:aaaa01]\n")]);
+        commit(9, &[("work.md", ""), ("archive.md", "# archive\n\n- [x] pay rent [id:aaaa01]\n")]);
+        assert_eq!(dates_from_git(dir.path(), "aaaa01"), (Some(date(6, 3)), Some(date(6, 5))));
+
+        commit(10, &[("archive.md", "# archive\n\n- [x] water plants 🆔 bbbb02\n")]);
+        assert_eq!(dates_from_git(dir.path(), "bbbb02"), (Some(date(6, 10)), Some(date(6, 10))));
+        assert_eq!(dates_from_git(dir.path(), "aaaa0"), (None, None));
+    }
+}
@@ -0,0 +1,246 @@
+use crate::recurrence;
+use crate::task::Task;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A piece of the TUI status row. Which widgets are shown, and in which order,
+/// comes from `status_line` in the `[ui]` section of `config.toml`.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusWidget {
+    /// NORMAL / EDITING
+    Mode,
+    /// Current view and tag filter
+    View,
+    /// Active / completed / cancelled / total task counts
+    Counts,
+    /// Progress on tasks due today (or overdue), as a braille bar
+    Goal,
+    /// Open tasks with neither a deadline nor a tag
+    Inbox,
+    /// Running or last Todoist sync, falling back to the time of the last sync
+    Sync,
+    /// Whether the tasks repository has uncommitted or unpushed changes
+    Git,
+    /// Open tasks with a reminder today, repeating ones included, hidden when there are none
+    Reminders,
+    /// Name of the tasks directory in use
+    Workspace,
+    /// Time left on the running pomodoro, hidden when none is running
+    Pomodoro,
+    /// Key hints
+    Keys,
+}
+
+pub fn default_widgets() -> Vec<StatusWidget> {
+    vec![
+        StatusWidget::Mode,
+        StatusWidget::Workspace,
+        StatusWidget::View,
+        StatusWidget::Counts,
+        StatusWidget::Sync,
+        StatusWidget::Git,
+        StatusWidget::Reminders,
+        StatusWidget::Keys,
+    ]
+}
+
+/// Everything a widget may need, gathered once per frame by the TUI.
+pub struct StatusContext<'a> {
+    pub tasks: &'a [Task],
+    pub today: NaiveDate,
+    pub mode: &'a str,
+    pub view: &'a str,
+    pub visible_count: usize,
+    pub sync_status: Option<&'a str>,
+    pub pomodoro_remaining: Option<Duration>,
+    pub last_sync: Option<DateTime<Utc>>,
+    /// None when the state is unknown, e.g. not a git repository
+    pub git: Option<GitSummary>,
+    pub workspace: &'a str,
+    pub now: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GitSummary {
+    pub changes: usize,
+    /// Commits not pushed yet, None without an upstream
+    pub ahead: Option<usize>,
+}
+
+/// The separator between segments; click handling relies on it to find segment bounds.
+pub const SEPARATOR: &str = " | ";
+
+/// Renders the widgets in order, skipping the ones with nothing to say,
+/// and keeps track of the widget each segment came from.
+pub fn render_segments(widgets: &[StatusWidget], ctx: &StatusContext) -> Vec<(StatusWidget, String)> {
+    widgets
+        .iter()
+        .filter_map(|widget| render_widget(*widget, ctx).map(|text| (*widget, text)))
+        .collect()
+}
+
+/// "just now", "5m ago", "3h ago", "2d ago"
+pub fn format_ago(then: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let minutes = (now - then).num_minutes().max(0);
+    match minutes {
+        0 => "just now".to_string(),
+        1..=59 => format!("{}m ago", minutes),
+        60..=1439 => format!("{}h ago", minutes / 60),
+        _ => format!("{}d ago", minutes / 1440),
+    }
+}
+
+impl StatusWidget {
+    /// Shown at the top of the segment's details popup.
+    pub fn description(&self) -> &'static str {
+        match self {
+            StatusWidget::Mode => "Input mode: NORMAL for commands, EDITING while typing a task",
+            StatusWidget::View => "The view being shown and the active tag filter",
+            StatusWidget::Counts => "Active, completed, cancelled and total tasks",
+            StatusWidget::Goal => "Tasks due today or earlier that are already done",
+            StatusWidget::Inbox => "Open tasks with neither a deadline nor a tag",
+            StatusWidget::Sync => "Todoist sync",
+            StatusWidget::Git => "Git versioning of the tasks directory",
+            StatusWidget::Reminders => "Open tasks with a reminder set for today",
+            StatusWidget::Workspace => "The tasks directory in use",
+            StatusWidget::Pomodoro => "Time left on the running pomodoro",
+            StatusWidget::Keys => "Most used keys, h shows all of them",
+        }
+    }
+}
+
+fn render_widget(widget: StatusWidget, ctx: &StatusContext) -> Option<String> {
+    match widget {
+        StatusWidget::Mode => Some(ctx.mode.to_string()),
+        StatusWidget::View => Some(ctx.view.to_string()),
+        StatusWidget::Counts => {
+            let cancelled = ctx.tasks.iter().filter(|t| t.cancelled).count();
+            let completed = ctx.tasks.iter().filter(|t| t.completed).count() - cancelled;
+            let cancelled = match cancelled {
+                0 => String::new(),
+                n => format!(", {} cancelled", n),
+            };
+            Some(format!(
+                "Tasks: {} active, {} completed{}, {} total",
+                ctx.visible_count, completed, cancelled, ctx.tasks.len()
+            ))
+        }
+        StatusWidget::Goal => {
+            let due: Vec<&Task> = ctx
+                .tasks
+                .iter()
+                .filter(|t| !t.cancelled && t.deadline.is_some_and(|d| d <= ctx.today))
+                .collect();
+            let done = due.iter().filter(|t| t.completed).count();
+            Some(format!("Today {} {}/{}", braille_bar(done, due.len(), 5), done, due.len()))
+        }
+        StatusWidget::Inbox => {
+            let inbox = ctx
+                .tasks
+                .iter()
+                .filter(|t| !t.completed && t.deadline.is_none() && t.tags.is_empty())
+                .count();
+            Some(format!("📥 {}", inbox))
+        }
+        StatusWidget::Sync => ctx
+            .sync_status
+            .map(|s| s.to_string())
+            .or_else(|| ctx.last_sync.map(|last| format!("⟳ synced {}", format_ago(last, ctx.now)))),
+        StatusWidget::Git => ctx.git.map(|git| {
+            let mut text = if git.changes == 0 {
+                "git ✓".to_string()
+            } else {
+                format!("git ✎{}", git.changes)
+            };
+            if let Some(ahead) = git.ahead.filter(|&a| a > 0) {
+                text.push_str(&format!(" ↑{}", ahead));
+            }
+            text
+        }),
+        StatusWidget::Reminders => {
+            let due = ctx
+                .tasks
+                .iter()
+                .filter(|t| recurrence::has_reminder_on(t, ctx.today))
+                .count();
+            (due > 0).then(|| format!("🔔 {}", due))
+        }
+        StatusWidget::Workspace => Some(format!("📁 {}", ctx.workspace)),
+        StatusWidget::Pomodoro => ctx.pomodoro_remaining.map(|remaining| {
+            let secs = remaining.as_secs();
+            format!("🍅 {:02}:{:02}", secs / 60, secs % 60)
+        }),
+        StatusWidget::Keys => Some("s:sync t:tags w:week h:help q:quit".to_string()),
+    }
+}
+
+/// A progress bar `width` cells wide. Each braille cell holds two steps
+/// (one dot column each), so the bar stays readable in a single row.
+pub fn braille_bar(done: usize, total: usize, width: usize) -> String {
+    if total == 0 {
+        return "⣀".repeat(width);
+    }
+
+    let steps = (done.min(total) * width * 2 + total / 2) / total;
+    (0..width)
+        .map(|cell| match steps.saturating_sub(cell * 2) {
+            0 => '⣀',
+            1 => '⡇',
+            _ => '⣿',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_braille_bar() {
+        assert_eq!(braille_bar(0, 4, 4), "⣀⣀⣀⣀");
+        assert_eq!(braille_bar(1, 4, 4), "⣿⣀⣀⣀");
+        assert_eq!(braille_bar(1, 2, 3), "⣿⡇⣀");
+        assert_eq!(braille_bar(4, 4, 4), "⣿⣿⣿⣿");
+        assert_eq!(braille_bar(0, 0, 2), "⣀⣀");
+    }
+
+    #[test]
+    fn test_widgets_skip_empty_and_keep_order() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 10).unwrap();
+        let mut done = Task::parse("done");
+        done.deadline = Some(today);
+        done.completed = true;
+        let mut open = Task::parse("open");
+        open.deadline = Some(today);
+        let tasks = vec![done, open, Task::parse("loose end")];
+
+        let ctx = StatusContext {
+            tasks: &tasks,
+            today,
+            mode: "NORMAL",
+            view: "All tasks",
+            visible_count: 2,
+            sync_status: None,
+            pomodoro_remaining: None,
+            last_sync: None,
+            git: Some(GitSummary { changes: 2, ahead: Some(1) }),
+            workspace: "tasks",
+            now: Utc::now(),
+        };
+
+        let widgets = [
+            StatusWidget::Inbox,
+            StatusWidget::Sync,
+            StatusWidget::Goal,
+            StatusWidget::Pomodoro,
+            StatusWidget::Reminders,
+            StatusWidget::Git,
+        ];
+        let segments = render_segments(&widgets, &ctx);
+        let texts: Vec<&str> = segments.iter().map(|(_, text)| text.as_str()).collect();
+        assert_eq!(texts, ["📥 1", "Today ⣿⣿⡇⣀⣀ 1/2", "git ✎2 ↑1"]);
+        assert_eq!(segments[2].0, StatusWidget::Git);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::document::{self, Document, Prefer};
+use crate::encryption;
+use crate::error::YarmtlError;
+use crate::ids;
+use crate::links::locate_task;
+use crate::paths::get_tasks_file_path;
+use crate::task::{self, Task};
+use crate::todoist_sync::request_sync_after_save;
+use crate::webhooks::request_webhooks_after_save;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+type Listener = Box<dyn FnMut(&[Task]) + Send + Sync>;
+
+/// tasks.md, read once and kept in memory: what the CLI, the TUI, the server and the syncs
+/// read the tasks from and save them through, so they all parse and merge it the same way.
+///
+/// The files tasks.md includes (see `document::include_line`) are read along with it, their
+/// tasks after its own and each with its file as `source`; a save writes every task back to
+/// its file, and only the files whose tasks changed. Their own includes aren't followed.
+pub struct TaskStore {
+    path: PathBuf,
+    /// The files tasks.md includes, as it names them
+    includes: Vec<String>,
+    /// The tasks as last read from or written to the files, what changes are merged against
+    base: Vec<Task>,
+    tasks: Vec<Task>,
+    /// When each file was last written, to tell whether something else wrote it since
+    modified: Vec<(PathBuf, Option<SystemTime>)>,
+    /// Each file as last read or written: reading or saving it again only parses and
+    /// renders the lines that changed
+    documents: HashMap<PathBuf, Document>,
+    listeners: Vec<Listener>,
+}
+
+impl TaskStore {
+    /// Reads the tasks at `path`. A file that isn't there yet holds no tasks.
+    pub fn open(path: &Path) -> Result<TaskStore, YarmtlError> {
+        let mut store = TaskStore {
+            path: path.to_path_buf(),
+            includes: Vec::new(),
+            base: Vec::new(),
+            tasks: Vec::new(),
+            modified: Vec::new(),
+            documents: HashMap::new(),
+            listeners: Vec::new(),
+        };
+        store.reload()?;
+        Ok(store)
+    }
+
+    /// The configured tasks.md, the way the command line and the server open it: every save
+    /// asks for a sync and the webhooks.
+    pub fn open_default() -> Result<TaskStore, YarmtlError> {
+        let mut store = TaskStore::open(&get_tasks_file_path())?;
+        store.on_change(|_| {
+            request_sync_after_save();
+            request_webhooks_after_save();
+        });
+        Ok(store)
+    }
+
+    /// The open tasks at `path` and in the files it includes, for reading only: see
+    /// `task::parse_open_tasks`. Much faster than `open` on a file holding years of done tasks.
+    pub fn open_tasks(path: &Path) -> Result<Vec<Task>, YarmtlError> {
+        let content = read(path)?;
+        let mut tasks = task::parse_open_tasks(&content);
+        for include in document::includes(&content) {
+            let included = task::parse_open_tasks(&read(&path.parent().unwrap_or(Path::new("")).join(&include))?);
+            tasks.extend(included.into_iter().map(|task| Task { source: Some(include.clone()), ..task }));
+        }
+        Ok(tasks)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where the tasks of `source` are: tasks.md, or a file it includes.
+    pub fn file_of(&self, source: Option<&str>) -> PathBuf {
+        match source {
+            Some(source) => self.path.parent().unwrap_or(Path::new("")).join(source),
+            None => self.path.clone(),
+        }
+    }
+
+    /// tasks.md and the files it includes.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.sources().into_iter().map(|source| self.file_of(source)).collect()
+    }
+
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
+
+    /// The tasks to change in memory; `save` writes them.
+    pub fn tasks_mut(&mut self) -> &mut Vec<Task> {
+        &mut self.tasks
+    }
+
+    /// When tasks.md or one of the files it includes was last written, None when there are
+    /// no files.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified.iter().filter_map(|(_, modified)| *modified).max()
+    }
+
+    /// Whether the tasks in memory differ from the file as last read or written.
+    pub fn is_changed(&self) -> bool {
+        self.tasks != self.base
+    }
+
+    /// Reads the files again, dropping what was changed in memory and not saved.
+    pub fn reload(&mut self) -> Result<(), YarmtlError> {
+        let content = read(&self.path)?;
+        self.includes = document::includes(&content);
+        self.base = self.parse(self.path.clone(), &content);
+        for include in self.includes.clone() {
+            let path = self.file_of(Some(&include));
+            let content = read(&path)?;
+            let tasks = self.parse(path, &content);
+            self.base.extend(tasks.into_iter().map(|task| Task { source: Some(include.clone()), ..task }));
+        }
+        self.tasks = self.base.clone();
+        self.modified = self.files_modified();
+        Ok(())
+    }
+
+    /// Whether something else wrote one of the files since they were last read or written.
+    pub fn is_stale(&self) -> bool {
+        self.files_modified() != self.modified
+    }
+
+    /// Reads the file again if something else wrote it since, keeping the changes in memory
+    /// on top of it. Returns whether it did.
+    pub fn refresh(&mut self) -> Result<bool, YarmtlError> {
+        if !self.is_stale() {
+            return Ok(false);
+        }
+        let changed = std::mem::take(&mut self.tasks);
+        let base = std::mem::take(&mut self.base);
+        self.reload()?;
+        if changed != base {
+            self.tasks = document::merge_tasks(&base, &changed, &self.base, Prefer::Memory);
+        }
+        Ok(true)
+    }
+
+    /// Adds `task`, with a new id if its own is taken, and saves. Returns the task as saved.
+    pub fn add(&mut self, mut task: Task) -> Result<Task, YarmtlError> {
+        let taken: HashSet<String> = self.tasks.iter().map(|t| t.id.clone()).collect();
+        if taken.contains(&task.id) {
+            task.id = ids::new_id(&taken);
+        }
+        let id = task.id.clone();
+        self.tasks.push(task);
+        self.save()?;
+        self.saved(&id)
+    }
+
+    /// Adds `task` to tasks.md at `path` without reading the tasks in it, quick on any
+    /// tasks.md: the line goes where `add` puts a task without a project. Its id isn't
+    /// checked against the ones taken, a made up one clashes about once in a billion, and
+    /// nobody is told of the change; that is up to the caller.
+    pub fn append(path: &Path, task: &Task) -> Result<(), YarmtlError> {
+        let _lock = document::lock(path).map_err(YarmtlError::io("lock", path))?;
+        TaskStore::append_locked(path, task)
+    }
+
+    /// `append` for a caller already holding `document::lock`.
+    pub fn append_locked(path: &Path, task: &Task) -> Result<(), YarmtlError> {
+        let content = read(path)?;
+        let content = document::insert_task_line(&content, &task.to_markdown());
+        encryption::write(path, &content).map_err(YarmtlError::io("write", path))
+    }
+
+    /// Changes the task `reference` points at, see `links::locate_task`, and saves.
+    /// Returns the task as saved.
+    pub fn update(&mut self, reference: &str, update: impl FnOnce(&mut Task)) -> Result<Task, YarmtlError> {
+        let index = locate_task(&self.tasks, reference)?;
+        update(&mut self.tasks[index]);
+        let id = self.tasks[index].id.clone();
+        self.save()?;
+        self.saved(&id)
+    }
+
+    /// Saves the tasks in memory, keeping what was written to the file since it was read;
+    /// where both changed a task, the change in memory wins.
+    pub fn save(&mut self) -> Result<&[Task], YarmtlError> {
+        self.save_preferring(Prefer::Memory)
+    }
+
+    /// Saves like `save`, with `prefer` settling the tasks changed both in memory and in
+    /// the file. Tells the listeners.
+    pub fn save_preferring(&mut self, prefer: Prefer) -> Result<&[Task], YarmtlError> {
+        let mut saved = Vec::new();
+        let sources: Vec<Option<String>> = self.sources().into_iter().map(|source| source.map(str::to_string)).collect();
+        for source in sources.iter().map(Option::as_deref) {
+            // Each file holds its tasks as if it were tasks.md
+            let of_source = |tasks: &[Task]| -> Vec<Task> {
+                let tasks = tasks.iter().filter(|task| self.source_of(task) == source);
+                tasks.map(|task| Task { source: None, ..task.clone() }).collect()
+            };
+            let (base, tasks) = (of_source(&self.base), of_source(&self.tasks));
+            let path = self.file_of(source);
+            let written = match tasks == base {
+                true => {
+                    let content = read(&path)?;
+                    self.parse(path, &content)
+                }
+                false => {
+                    let document = self.documents.entry(path.clone()).or_insert_with(|| Document::parse(""));
+                    document::save_document(&path, document, &base, &tasks, prefer).map_err(YarmtlError::io("write", &path))?
+                }
+            };
+            saved.extend(written.into_iter().map(|task| Task { source: source.map(str::to_string), ..task }));
+        }
+        self.modified = self.files_modified();
+        self.base = saved.clone();
+        self.tasks = saved;
+        for listener in &mut self.listeners {
+            listener(&self.tasks);
+        }
+        Ok(&self.tasks)
+    }
+
+    /// Calls `listener` with the tasks after every save.
+    pub fn on_change(&mut self, listener: impl FnMut(&[Task]) + Send + Sync + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// The tasks of the file at `path`, whose content is `content`.
+    fn parse(&mut self, path: PathBuf, content: &str) -> Vec<Task> {
+        let document = match self.documents.get(&path) {
+            Some(document) => document.reparse(content),
+            None => Document::parse(content),
+        };
+        let tasks = document.tasks();
+        self.documents.insert(path, document);
+        tasks
+    }
+
+    /// tasks.md, then the files it includes.
+    fn sources(&self) -> Vec<Option<&str>> {
+        std::iter::once(None).chain(self.includes.iter().map(|include| Some(include.as_str()))).collect()
+    }
+
+    /// The file the task is saved to; one tasks.md doesn't include is taken for tasks.md.
+    fn source_of<'a>(&self, task: &'a Task) -> Option<&'a str> {
+        task.source.as_deref().filter(|source| self.includes.iter().any(|include| include == source))
+    }
+
+    fn files_modified(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        let files = self.files();
+        files.into_iter().map(|file| (file.clone(), fs::metadata(&file).and_then(|m| m.modified()).ok())).collect()
+    }
+
+    /// The task `id` as saved; a merge may have taken it out.
+    fn saved(&self, id: &str) -> Result<Task, YarmtlError> {
+        let task = self.tasks.iter().find(|t| t.id == id);
+        task.cloned().ok_or_else(|| YarmtlError::NoTask(id.to_string()))
+    }
+}
+
+/// What the file at `path` holds; nothing when it isn't there yet.
+fn read(path: &Path) -> Result<String, YarmtlError> {
+    match encryption::read(path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(YarmtlError::io("read", path)(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_changes_go_through_the_store() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("tasks.md");
+
+        let mut store = TaskStore::open(&path).unwrap();
+        assert!(store.tasks().is_empty());
+        let saves = Arc::new(Mutex::new(Vec::new()));
+        let seen = saves.clone();
+        store.on_change(move |tasks| seen.lock().unwrap().push(tasks.len()));
+
+        store.add(Task::parse("pay rent [id:aaaa01]")).unwrap();
+        let added = store.add(Task::parse("water plants [id:aaaa01]")).unwrap();
+        assert_ne!(added.id, "aaaa01");
+        let paid = store.update("aaaa01", |task| task.text = "pay the rent".to_string()).unwrap();
+        assert_eq!(paid.text, "pay the rent");
+        assert!(matches!(store.update("ffff99", |_| {}), Err(YarmtlError::NoTask(_))));
+        assert_eq!(*saves.lock().unwrap(), vec![1, 2, 2]);
+
+        // Someone else adds a task while one is changed in memory; both stay
+        let mut other = TaskStore::open(&path).unwrap();
+        other.tasks_mut().push(Task::parse("call mum [id:cccc03]"));
+        other.save().unwrap();
+        store.tasks_mut()[0].completed = true;
+        assert!(store.refresh().unwrap() && store.is_changed());
+        assert!(!store.refresh().unwrap());
+        store.save().unwrap();
+        let reread = TaskStore::open(&path).unwrap();
+        assert_eq!(reread.tasks(), store.tasks());
+        assert!(reread.tasks()[0].completed && reread.tasks().len() == 3);
+    }
+
+    #[test]
+    fn test_included_tasks_go_back_to_their_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("work")).unwrap();
+        let path = dir.join("tasks.md");
+        let main = "# tasks\n\n- [ ] pay rent [id:aaaa01]\n\n<!-- include: work/tasks.md -->\n";
+        fs::write(&path, main).unwrap();
+        fs::write(dir.join("work/tasks.md"), "# work\n\n- [ ] file report [id:bbbb01]\n").unwrap();
+
+        let mut store = TaskStore::open(&path).unwrap();
+        assert_eq!(store.files(), vec![path.clone(), dir.join("work/tasks.md")]);
+        let sources: Vec<_> = store.tasks().iter().map(|task| task.source.as_deref()).collect();
+        assert_eq!(sources, vec![None, Some("work/tasks.md")]);
+
+        store.update("bbbb01", |task| task.completed = true).unwrap();
+        let mut added = Task::parse("book flights [id:bbbb02]");
+        added.source = Some("work/tasks.md".to_string());
+        store.add(added).unwrap();
+        // tasks.md didn't change, so it isn't written
+        assert_eq!(fs::read_to_string(&path).unwrap(), main);
+        let work = fs::read_to_string(dir.join("work/tasks.md")).unwrap();
+        assert!(work.contains("- [x] file report [id:bbbb01]") && work.contains("book flights [id:bbbb02]"));
+        assert_eq!(TaskStore::open(&path).unwrap().tasks(), store.tasks());
+    }
+}
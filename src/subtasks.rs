@@ -0,0 +1,237 @@
+use crate::task::Task;
+use chrono::NaiveDate;
+use std::ops::Range;
+
+/// A task with its subtasks, as nested in `tasks.md`.
+#[derive(Debug, Clone)]
+pub struct TaskNode {
+    pub task: Task,
+    pub children: Vec<TaskNode>,
+}
+
+impl TaskNode {
+    /// The task's line followed by its whole subtree, one level deeper per generation.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        self.write_markdown(0, &mut out);
+        out
+    }
+
+    fn write_markdown(&self, depth: usize, out: &mut String) {
+        out.push_str(&Task { indent_level: depth, ..self.task.clone() }.to_markdown());
+        out.push('\n');
+        for child in &self.children {
+            child.write_markdown(depth + 1, out);
+        }
+    }
+}
+
+/// Nests a flat list by `indent_level`. A task indented more than one level past
+/// the task above it becomes that task's child, so skipped levels close up.
+pub fn build_tree(tasks: Vec<Task>) -> Vec<TaskNode> {
+    let mut roots: Vec<TaskNode> = Vec::new();
+    // Path from a root to the last node added, as levels of the tasks on it
+    let mut path: Vec<usize> = Vec::new();
+
+    for task in tasks {
+        while path.last().is_some_and(|&level| level >= task.indent_level) {
+            path.pop();
+        }
+
+        let mut siblings = &mut roots;
+        for _ in 0..path.len() {
+            siblings = &mut siblings.last_mut().unwrap().children;
+        }
+        path.push(task.indent_level);
+        siblings.push(TaskNode { task, children: Vec::new() });
+    }
+
+    roots
+}
+
+/// Back to the flat order of `tasks.md`, with `indent_level` and `parent_id` taken from the tree.
+pub fn flatten(nodes: Vec<TaskNode>) -> Vec<Task> {
+    fn walk(nodes: Vec<TaskNode>, depth: usize, parent_id: Option<&str>, out: &mut Vec<Task>) {
+        for node in nodes {
+            let mut task = node.task;
+            task.indent_level = depth;
+            task.parent_id = parent_id.map(str::to_string);
+            let id = task.id.clone();
+            out.push(task);
+            walk(node.children, depth + 1, Some(&id), out);
+        }
+    }
+
+    let mut tasks = Vec::new();
+    walk(nodes, 0, None, &mut tasks);
+    tasks
+}
+
+/// Makes `indent_level` and `parent_id` agree with each other and with the nesting.
+pub fn link_parents(tasks: Vec<Task>) -> Vec<Task> {
+    flatten(build_tree(tasks))
+}
+
+/// Removes a task, moving its subtasks up a level so they stay under its parent.
+pub fn remove_task(tasks: &mut Vec<Task>, index: usize) -> Task {
+    let subtasks = subtask_range(tasks, index);
+    for subtask in &mut tasks[subtasks] {
+        subtask.indent_level -= 1;
+    }
+    let removed = tasks.remove(index);
+    *tasks = link_parents(std::mem::take(tasks));
+    removed
+}
+
+/// Moves a task with its subtasks under `parent_id`, in front of its subtask `before`
+/// or after the last one, or to the end as a top-level task when `parent_id` is None
+/// or isn't found outside the moved subtree. Returns the task's new index.
+pub fn move_subtree(tasks: &mut Vec<Task>, index: usize, parent_id: Option<&str>, before: Option<&str>) -> usize {
+    let mut moved: Vec<Task> = tasks.drain(index..subtask_range(tasks, index).end).collect();
+    let parent = parent_id.and_then(|id| tasks.iter().position(|t| t.id == id));
+    let (at, level) = match parent {
+        Some(parent) => {
+            let level = tasks[parent].indent_level + 1;
+            let subtasks = subtask_range(tasks, parent);
+            let at = before
+                .and_then(|id| tasks[subtasks.clone()].iter().position(|t| t.id == id && t.indent_level == level))
+                .map_or(subtasks.end, |offset| subtasks.start + offset);
+            (at, level)
+        }
+        None => (tasks.len(), 0),
+    };
+    let project = parent.map_or_else(|| moved[0].project.clone(), |parent| tasks[parent].project.clone());
+    let source = parent.map_or_else(|| moved[0].source.clone(), |parent| tasks[parent].source.clone());
+    let top = moved[0].indent_level;
+    for task in &mut moved {
+        task.indent_level = task.indent_level - top + level;
+        task.project = project.clone();
+        task.source = source.clone();
+    }
+    tasks.splice(at..at, moved);
+    *tasks = link_parents(std::mem::take(tasks));
+    at
+}
+
+/// Subtasks are the tasks indented below a task in `tasks.md`, up to the next
+/// task at the same or a shallower level.
+pub fn subtask_range(tasks: &[Task], index: usize) -> Range<usize> {
+    let level = tasks[index].indent_level;
+    let end = tasks[index + 1..]
+        .iter()
+        .position(|t| t.indent_level <= level)
+        .map_or(tasks.len(), |offset| index + 1 + offset);
+    index + 1..end
+}
+
+/// Done and total subtask counts (nested ones included), or None for a leaf task.
+pub fn subtask_progress(tasks: &[Task], index: usize) -> Option<(usize, usize)> {
+    let subtasks = &tasks[subtask_range(tasks, index)];
+    if subtasks.is_empty() {
+        return None;
+    }
+    let done = subtasks.iter().filter(|t| t.completed).count();
+    Some((done, subtasks.len()))
+}
+
+pub fn parent_index(tasks: &[Task], index: usize) -> Option<usize> {
+    let parent_id = tasks[index].parent_id.as_ref()?;
+    tasks[..index].iter().rposition(|t| &t.id == parent_id)
+}
+
+/// Completes every ancestor of `index` whose subtasks are now all done,
+/// returning the indices that changed.
+pub fn complete_finished_parents(tasks: &mut [Task], index: usize, today: NaiveDate) -> Vec<usize> {
+    let mut completed = Vec::new();
+    let mut current = index;
+
+    while let Some(parent) = parent_index(tasks, current) {
+        if tasks[parent].completed || subtask_progress(tasks, parent).is_some_and(|(done, total)| done < total) {
+            break;
+        }
+        tasks[parent].set_completed(true, today);
+        completed.push(parent);
+        current = parent;
+    }
+
+    completed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Vec<Task> {
+        let tasks = [("trip", 0), ("book flights", 1), ("pack", 1), ("socks", 2), ("other", 0)]
+            .iter()
+            .map(|(text, level)| {
+                let mut task = Task::parse(text);
+                task.indent_level = *level;
+                task
+            })
+            .collect();
+        link_parents(tasks)
+    }
+
+    #[test]
+    fn test_subtask_progress() {
+        let mut tasks = tree();
+        tasks[3].completed = true;
+
+        assert_eq!(subtask_range(&tasks, 0), 1..4);
+        assert_eq!(subtask_progress(&tasks, 0), Some((1, 3)));
+        assert_eq!(subtask_progress(&tasks, 2), Some((1, 1)));
+        assert_eq!(subtask_progress(&tasks, 4), None);
+        assert_eq!(parent_index(&tasks, 3), Some(2));
+        assert_eq!(parent_index(&tasks, 4), None);
+    }
+
+    #[test]
+    fn test_complete_finished_parents() {
+        let mut tasks = tree();
+        tasks[1].completed = true;
+        tasks[3].completed = true;
+
+        // "socks" finishes "pack", which finishes "trip"
+        let today = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        assert_eq!(complete_finished_parents(&mut tasks, 3, today), vec![2, 0]);
+        assert!(tasks[0].completed && tasks[2].completed);
+        assert_eq!(tasks[0].done, Some(today));
+        assert!(!tasks[4].completed);
+    }
+
+    #[test]
+    fn test_tree_round_trip() {
+        let mut tasks = tree();
+        assert_eq!(tasks[3].parent_id.as_ref(), Some(&tasks[2].id));
+        assert_eq!(tasks[4].parent_id, None);
+
+        let nodes = build_tree(tasks.clone());
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].children[1].children[0].task.text, "socks");
+        let markdown = nodes[0].to_markdown();
+        assert_eq!(markdown.lines().count(), 4);
+        assert!(markdown.lines().nth(3).unwrap().starts_with("    - [ ] socks"));
+
+        // Skipped levels close up under the task above
+        let mut skipped = tasks.clone();
+        skipped[3].indent_level = 5;
+        assert_eq!(link_parents(skipped)[3].indent_level, 2);
+
+        // "pack" and its "socks" go in front of "book flights", then to the top level
+        let mut moved = tasks.clone();
+        let trip_id = moved[0].id.clone();
+        let flights = moved[1].id.clone();
+        assert_eq!(move_subtree(&mut moved, 2, Some(&trip_id), Some(&flights)), 1);
+        let order: Vec<(&str, usize)> = moved.iter().map(|t| (t.text.as_str(), t.indent_level)).collect();
+        assert_eq!(order, [("trip", 0), ("pack", 1), ("socks", 2), ("book flights", 1), ("other", 0)]);
+        assert_eq!(move_subtree(&mut moved, 1, None, None), 3);
+        assert_eq!((moved[3].text.as_str(), moved[3].parent_id.as_ref(), moved[4].indent_level), ("pack", None, 1));
+
+        // Removing "pack" hands "socks" to "trip"
+        let trip = tasks[0].id.clone();
+        remove_task(&mut tasks, 2);
+        assert_eq!(tasks[2].text, "socks");
+        assert_eq!((tasks[2].indent_level, tasks[2].parent_id.as_ref()), (1, Some(&trip)));
+    }
+}
@@ -0,0 +1,1131 @@
+use crate::backup;
+use crate::config::load_config;
+use crate::document::Prefer;
+use crate::git::git_commit_tasks_with_message;
+use crate::hash::StableHasher;
+use crate::hooks;
+use crate::ids::{self, IdMap};
+use crate::journal::Journal;
+use crate::paths::{get_sync_dir, state_dir_of};
+use crate::store::TaskStore;
+use crate::subtasks;
+use crate::sync_metadata::{SyncMetadata, TaskSyncInfo};
+use crate::task::Task;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Contexts go to Todoist as labels with this prefix (`>home` is `@ctx_home` there),
+/// and to CalDAV as categories, so they come back as contexts rather than tags.
+pub const CONTEXT_LABEL_PREFIX: &str = "ctx_";
+
+/// Tag of both versions of a task kept by the keep-both conflict strategy. Unpaired
+/// tasks with it stay out of the remote side until the tag is removed.
+pub const CONFLICT_TAG: &str = "conflict";
+
+/// In the state directory of the tasks directory, locked for as long as a sync runs.
+const SYNC_LOCK_FILE: &str = ".sync.lock";
+
+/// The lock one sync at a time holds, whether it runs from the TUI, the CLI or the
+/// daemon; it is released when the returned file is dropped. None while another sync
+/// holds it.
+pub fn try_lock_sync(sync_dir: &Path) -> std::io::Result<Option<fs::File>> {
+    let state_dir = state_dir_of(sync_dir);
+    fs::create_dir_all(&state_dir)?;
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(state_dir.join(SYNC_LOCK_FILE))?;
+    match lock.try_lock() {
+        Ok(()) => Ok(Some(lock)),
+        Err(fs::TryLockError::WouldBlock) => Ok(None),
+        Err(fs::TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// What to do with a task edited both in tasks.md and on the remote side since the last
+/// sync, `conflict_strategy` in each backend's config file.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictStrategy {
+    PreferLocal,
+    PreferRemote,
+    /// The side changed last wins. tasks.md only has a modification time for the whole
+    /// file, and Todoist's REST API has none, so without one both are kept
+    NewestWins,
+    /// Keep the local task and add the remote version next to it, both tagged #conflict
+    #[default]
+    KeepBoth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictResolution {
+    Local,
+    Remote,
+    Both,
+}
+
+impl ConflictStrategy {
+    /// As written in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            ConflictStrategy::PreferLocal => "prefer-local",
+            ConflictStrategy::PreferRemote => "prefer-remote",
+            ConflictStrategy::NewestWins => "newest-wins",
+            ConflictStrategy::KeepBoth => "keep-both",
+        }
+    }
+
+    pub fn resolve(self, local_modified: Option<DateTime<Utc>>, remote_modified: Option<DateTime<Utc>>) -> ConflictResolution {
+        match self {
+            ConflictStrategy::PreferLocal => ConflictResolution::Local,
+            ConflictStrategy::PreferRemote => ConflictResolution::Remote,
+            ConflictStrategy::KeepBoth => ConflictResolution::Both,
+            ConflictStrategy::NewestWins => match (local_modified, remote_modified) {
+                (Some(local), Some(remote)) if local >= remote => ConflictResolution::Local,
+                (Some(_), Some(_)) => ConflictResolution::Remote,
+                _ => ConflictResolution::Both,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncReport {
+    /// What was synced with: "Todoist"
+    pub service: &'static str,
+    pub created_remote: usize,
+    pub created_in_yarmtl: usize,
+    pub updated_remote: usize,
+    pub updated_in_yarmtl: usize,
+    pub deleted_remote: usize,
+    pub deleted_in_yarmtl: usize,
+    pub conflicts_resolved: usize,
+    /// Actions turned down in an interactive sync, they come up again next time
+    pub skipped: usize,
+    /// Actions the remote side turned down even after retrying, they come up again next time
+    pub failed: usize,
+    /// Actions not tried because the remote side stopped answering, left for the next sync
+    pub remaining: usize,
+}
+
+impl SyncReport {
+    pub fn new(service: &'static str) -> Self {
+        SyncReport {
+            service,
+            created_remote: 0,
+            created_in_yarmtl: 0,
+            updated_remote: 0,
+            updated_in_yarmtl: 0,
+            deleted_remote: 0,
+            deleted_in_yarmtl: 0,
+            conflicts_resolved: 0,
+            skipped: 0,
+            failed: 0,
+            remaining: 0,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let summary = format!(
+            "↑{} ↓{} ⇅{} ✗{}",
+            self.created_remote + self.updated_remote,
+            self.created_in_yarmtl + self.updated_in_yarmtl,
+            self.conflicts_resolved,
+            self.deleted_remote + self.deleted_in_yarmtl
+        );
+        match self.failed + self.remaining {
+            0 => summary,
+            unfinished => format!("{} ⚠{}", summary, unfinished),
+        }
+    }
+
+    /// One line per kind of change that happened, like "2 created in Todoist".
+    pub fn details(&self) -> Vec<String> {
+        [
+            (self.created_remote, format!("created in {}", self.service)),
+            (self.updated_remote, format!("updated in {}", self.service)),
+            (self.deleted_remote, format!("deleted from {}", self.service)),
+            (self.created_in_yarmtl, "added to tasks.md".to_string()),
+            (self.updated_in_yarmtl, "updated in tasks.md".to_string()),
+            (self.deleted_in_yarmtl, "deleted from tasks.md".to_string()),
+            (self.conflicts_resolved, "conflicts resolved".to_string()),
+            (self.skipped, "skipped".to_string()),
+            (self.failed, "failed".to_string()),
+            (self.remaining, "left for the next sync".to_string()),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect()
+    }
+}
+
+/// Where a running sync is, reported as it goes so the TUI can draw a gauge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncProgress {
+    /// For another sync to finish
+    Waiting,
+    FetchingProjects,
+    FetchingTasks,
+    Applying { done: usize, total: usize },
+    Saving,
+}
+
+impl SyncProgress {
+    /// Steps finished and total steps; fetching counts as two steps ahead of the actions.
+    pub fn steps(&self) -> (usize, usize) {
+        match *self {
+            SyncProgress::Waiting | SyncProgress::FetchingProjects => (0, 3),
+            SyncProgress::FetchingTasks => (1, 3),
+            SyncProgress::Applying { done, total } => (2 + done, 3 + total),
+            SyncProgress::Saving => (1, 1),
+        }
+    }
+}
+
+/// A task on the other side of a sync: Todoist's, a CalDAV server's or Google's.
+pub trait RemoteTask {
+    /// Where the task lives, for messages: "Todoist"
+    const SERVICE: &'static str;
+
+    fn title(&self) -> &str;
+
+    /// What the remote side knows it by, None before it was created there.
+    fn remote_id(&self) -> Option<&str>;
+
+    /// The yarmtl id the task carries, when it was created from yarmtl, so a task the
+    /// pairing lost still finds its way back.
+    fn yarmtl_id(&self) -> Option<String> {
+        None
+    }
+
+    fn is_completed(&self) -> bool;
+
+    /// Among its siblings, where the remote side keeps an order.
+    fn order(&self) -> Option<i64> {
+        None
+    }
+
+    /// When it was last changed, for newest-wins.
+    fn modified_at(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+}
+
+/// What a sync does to one task, on the side of tasks.md or the remote one.
+#[derive(Debug)]
+pub enum SyncAction<R> {
+    CreateRemote(Task),
+    CreateInYarmtl(R),
+    UpdateRemote { yarmtl_id: String, task: Task },
+    UpdateYarmtl { task: R },
+    /// `title` is the remote task's, so the deletion can be described
+    DeleteRemote { remote_id: String, title: String },
+    /// `text` is the local task's, so the deletion can be described
+    DeleteFromYarmtl { yarmtl_id: String, text: String },
+    /// Puts the subtasks of `parent` on the remote side in the order they have in tasks.md
+    ReorderRemote { parent: String, remote_ids: Vec<String> },
+    /// Both sides changed since the last sync
+    Conflict { yarmtl_id: String, local: Task, remote: Box<R>, resolution: ConflictResolution },
+}
+
+impl<R: RemoteTask> SyncAction<R> {
+    /// What the action will do, for dry runs and confirmations: `delete from Todoist: "call bob"`.
+    pub fn describe(&self) -> String {
+        let (what, text) = match self {
+            SyncAction::CreateRemote(task) => (format!("create in {}", R::SERVICE), task.text.as_str()),
+            SyncAction::CreateInYarmtl(task) => ("add to tasks.md".to_string(), task.title()),
+            SyncAction::UpdateRemote { task, .. } => (format!("update in {}", R::SERVICE), task.text.as_str()),
+            SyncAction::UpdateYarmtl { task, .. } => ("update in tasks.md".to_string(), task.title()),
+            SyncAction::DeleteRemote { title, .. } => (format!("delete from {}", R::SERVICE), title.as_str()),
+            SyncAction::DeleteFromYarmtl { text, .. } => ("delete from tasks.md".to_string(), text.as_str()),
+            SyncAction::ReorderRemote { parent, .. } => (format!("reorder subtasks in {}", R::SERVICE), parent.as_str()),
+            SyncAction::Conflict { local, remote, resolution, .. } => match resolution {
+                ConflictResolution::Local => ("conflict, keep tasks.md's version".to_string(), local.text.as_str()),
+                ConflictResolution::Remote => (format!("conflict, keep {}'s version", R::SERVICE), remote.title()),
+                ConflictResolution::Both => ("conflict, keep both tagged #conflict".to_string(), local.text.as_str()),
+            },
+        };
+        format!("{}: \"{}\"", what, text)
+    }
+
+    /// Deletions can't be undone from yarmtl, an interactive sync points them out.
+    pub fn is_deletion(&self) -> bool {
+        matches!(self, SyncAction::DeleteRemote { .. } | SyncAction::DeleteFromYarmtl { .. })
+    }
+}
+
+/// What a provider gets to look at on the engine's side while it writes a task.
+pub struct SyncContext<'a, R> {
+    pub metadata: &'a SyncMetadata,
+    pub local_tasks: &'a [Task],
+    /// The remote tasks as fetched, by remote id
+    pub remote_tasks: &'a HashMap<String, R>,
+}
+
+/// A service tasks.md syncs with. The provider only talks to the service and converts
+/// tasks; what changed, what to do about it and what is paired with what is up to the
+/// `SyncEngine` driving it.
+// Syncs are awaited where they start, never sent to another thread, so the futures need not be Send
+#[allow(async_fn_in_trait)]
+pub trait SyncProvider {
+    type Remote: RemoteTask + Clone + std::fmt::Debug;
+    type Error: std::error::Error + 'static;
+
+    /// Where the pairs with this service are kept, in the state directory of the tasks directory
+    const METADATA_FILE: &'static str;
+    /// Whether subtasks nest on the remote side too, rather than staying in tasks.md
+    const SUBTASKS: bool;
+    /// Whether the remote side keeps subtasks in an order, which tasks.md's is kept in
+    const SUBTASK_ORDER: bool;
+    /// Whether completed tasks are fetched too. When they aren't, a paired task missing
+    /// from the remote side is deleted only while it is open here
+    const LISTS_COMPLETED: bool;
+    /// Whether a paired task deleted from tasks.md is deleted on the remote side too.
+    /// When it isn't, the remote task stays paired and is left alone
+    const DELETES: bool;
+
+    /// The service is refusing or out of reach, so the rest of a sync would fail the same way.
+    fn is_unavailable(error: &Self::Error) -> bool;
+
+    /// The remote tasks. `metadata` holds what an earlier sync left, like a sync token.
+    async fn fetch(
+        &mut self,
+        metadata: &mut SyncMetadata,
+        local_tasks: &[Task],
+        on_progress: &mut impl FnMut(SyncProgress),
+    ) -> Result<Vec<Self::Remote>, Self::Error>;
+
+    async fn create(&mut self, task: &Task, context: &SyncContext<'_, Self::Remote>) -> Result<Self::Remote, Self::Error>;
+
+    /// Sends the local version of a paired task, returning the remote one as it is now.
+    async fn update(
+        &mut self,
+        remote_id: &str,
+        task: &Task,
+        context: &SyncContext<'_, Self::Remote>,
+    ) -> Result<Self::Remote, Self::Error>;
+
+    /// Checks `remote` off or reopens it, after a create or update left its completion
+    /// different from the local task's.
+    async fn complete(&mut self, remote: &mut Self::Remote, completed: bool) -> Result<(), Self::Error>;
+
+    async fn delete(&mut self, remote_id: &str, context: &SyncContext<'_, Self::Remote>) -> Result<(), Self::Error>;
+
+    /// Puts subtasks in this order, for providers with `SUBTASK_ORDER`.
+    async fn reorder(&mut self, _remote_ids: &[String]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// A new local task for a remote one.
+    fn to_task(&self, remote: &Self::Remote) -> Task;
+
+    /// `local` with what the remote task carries taken from it, all but its completion.
+    fn pull(&self, local: &Task, remote: &Self::Remote) -> Task;
+
+    /// Hash of what is sent from a local task, to notice edits made in tasks.md.
+    fn local_hash(&self, task: &Task) -> String {
+        compute_task_hash(task)
+    }
+
+    /// Hash of what is read from a remote task, to notice edits made there.
+    fn remote_hash(&self, remote: &Self::Remote) -> String;
+
+    /// The remote id of the task's parent, looked up among `remote_tasks` where the
+    /// remote side names it some other way.
+    fn remote_parent(&self, _remote: &Self::Remote, _remote_tasks: &[&Self::Remote]) -> Option<String> {
+        None
+    }
+
+    /// Ids of the local tasks that sync.
+    fn local_scope(&self, tasks: &[Task]) -> HashSet<String> {
+        tasks.iter().map(|t| t.id.clone()).collect()
+    }
+
+    /// Ids of the remote tasks that sync, from `tasks` in parents-first order.
+    fn remote_scope(&self, tasks: &[&Self::Remote]) -> HashSet<String> {
+        tasks.iter().filter_map(|t| t.remote_id()).map(str::to_string).collect()
+    }
+
+    /// Whether checking a task off (or reopening it) on one side does so on the other.
+    fn syncs_completion(&self) -> bool {
+        true
+    }
+
+    /// Whether a local task never synced before is created on the remote side.
+    fn creates(&self, _task: &Task) -> bool {
+        true
+    }
+}
+
+/// Syncs tasks.md with whatever service `P` talks to: pairs local tasks with remote
+/// ones, tells from the hashes of the last sync which side changed, resolves conflicts
+/// and keeps subtasks under their parents.
+pub struct SyncEngine<P: SyncProvider> {
+    provider: P,
+    metadata: SyncMetadata,
+    metadata_path: PathBuf,
+    local_tasks: Vec<Task>,
+    /// tasks.md as read when the sync started, what edits made there during the sync are told apart by
+    store: Option<TaskStore>,
+    tasks_modified: bool,
+    /// The remote tasks as fetched, by remote id
+    remote_tasks: HashMap<String, P::Remote>,
+    conflict_strategy: ConflictStrategy,
+    /// When tasks.md was last written, for newest-wins
+    local_modified: Option<DateTime<Utc>>,
+}
+
+impl<P: SyncProvider> SyncEngine<P> {
+    pub fn new(provider: P, sync_dir: &Path, conflict_strategy: ConflictStrategy) -> Result<Self, Box<dyn std::error::Error>> {
+        let metadata_path = state_dir_of(sync_dir).join(P::METADATA_FILE);
+        let mut metadata = SyncMetadata::load(&metadata_path)?;
+        // Tasks that got a short id since the last sync keep their remote task
+        for (old, new) in IdMap::load(sync_dir).renamed {
+            metadata.rename(&old, &new);
+        }
+
+        Ok(SyncEngine {
+            provider,
+            metadata,
+            metadata_path,
+            local_tasks: Vec::new(),
+            store: None,
+            tasks_modified: false,
+            remote_tasks: HashMap::new(),
+            conflict_strategy,
+            local_modified: None,
+        })
+    }
+
+    /// The actions a sync would take right now, without taking any of them or saving anything.
+    pub async fn plan(&mut self, tasks_file: &Path) -> Result<Vec<SyncAction<P::Remote>>, Box<dyn std::error::Error>> {
+        self.fetch_and_detect(tasks_file, |_| {}).await
+    }
+
+    /// A sync that asks `approve` before each action. Turned down actions are counted as
+    /// skipped and, since nothing about them is recorded, planned again by the next sync.
+    pub async fn sync_with_approval(
+        &mut self,
+        tasks_file: &Path,
+        mut on_progress: impl FnMut(SyncProgress),
+        mut approve: impl FnMut(&SyncAction<P::Remote>) -> bool,
+    ) -> Result<SyncReport, Box<dyn std::error::Error>> {
+        let mut report = SyncReport::new(P::Remote::SERVICE);
+        let actions = self.fetch_and_detect(tasks_file, &mut on_progress).await?;
+
+        // A remote side emptied by mistake shouldn't take tasks.md with it
+        let deletions = actions.iter().filter(|action| matches!(action, SyncAction::DeleteFromYarmtl { .. })).count();
+        let threshold = load_config().backup.sync_deletions;
+        if threshold > 0 && deletions >= threshold {
+            backup::automatic("sync")?;
+        }
+
+        // Stopping halfway would leave tasks.md and the metadata out of step, `yarmtl recover`
+        // finishes the writes or takes them back
+        let mut journal = match actions.is_empty() {
+            true => None,
+            false => {
+                let sync_dir = self.metadata_path.parent().unwrap_or(Path::new(""));
+                let mut files = self.store.as_ref().map(TaskStore::files).unwrap_or_default();
+                files.push(self.metadata_path.clone());
+                Some(Journal::begin(sync_dir, &format!("{} sync", P::Remote::SERVICE), &files)?)
+            }
+        };
+
+        let total = actions.len();
+        on_progress(SyncProgress::Applying { done: 0, total });
+        for (done, action) in actions.into_iter().enumerate() {
+            let step = action.describe();
+            if !approve(&action) {
+                tracing::debug!("skipped: {}", step);
+                report.skipped += 1;
+            } else if let Err(e) = self.apply_action(action, &mut report).await {
+                tracing::info!("couldn't {}: {}", step, e);
+                report.failed += 1;
+                // Retrying is done by the clients, past that the rest would fail too. What
+                // is done so far is saved below, the next sync plans the rest again
+                if P::is_unavailable(&e) {
+                    report.remaining = total - done - 1;
+                    break;
+                }
+            } else {
+                tracing::info!("{}", step);
+                if let Some(journal) = &mut journal {
+                    journal.step(step)?;
+                }
+            }
+            on_progress(SyncProgress::Applying { done: done + 1, total });
+        }
+
+        on_progress(SyncProgress::Saving);
+        // What the sync changed, keeping the edits made to tasks.md while it ran; those
+        // win over the sync's and go to the remote side next time
+        if self.tasks_modified
+            && let Some(store) = &mut self.store
+        {
+            store.tasks_mut().clone_from(&self.local_tasks);
+            store.save_preferring(Prefer::File)?;
+            if let Some(journal) = &mut journal {
+                for file in store.files().iter().filter(|file| file.exists()) {
+                    journal.wrote(file)?;
+                }
+            }
+        }
+        self.metadata.update_last_sync();
+        match journal {
+            Some(mut journal) => {
+                journal.write(&self.metadata_path, serde_json::to_string_pretty(&self.metadata)?.as_bytes())?;
+                journal.finish()?;
+            }
+            None => self.metadata.save(&self.metadata_path)?,
+        }
+
+        Ok(report)
+    }
+
+    /// Fetches both sides and works out what needs doing, without changing anything yet.
+    async fn fetch_and_detect(
+        &mut self,
+        tasks_file: &Path,
+        mut on_progress: impl FnMut(SyncProgress),
+    ) -> Result<Vec<SyncAction<P::Remote>>, Box<dyn std::error::Error>> {
+        let store = TaskStore::open(tasks_file)?;
+        self.local_tasks = store.tasks().to_vec();
+        self.tasks_modified = false;
+        self.local_modified = store.modified().map(DateTime::from);
+        self.store = Some(store);
+
+        let remote_tasks = self.provider.fetch(&mut self.metadata, &self.local_tasks, &mut on_progress).await?;
+
+        // Pairs synced before remote hashes were kept start from the remote's current state
+        for remote in &remote_tasks {
+            if let Some(remote_id) = remote.remote_id()
+                && let Some(yarmtl_id) = self.metadata.get_yarmtl_id(remote_id)
+                && let Some(info) = self.metadata.task_mappings.get_mut(&yarmtl_id)
+                && info.remote_hash.is_none()
+            {
+                info.remote_hash = Some(self.provider.remote_hash(remote));
+            }
+        }
+
+        if !self.metadata.stable_hashes {
+            self.restart_hashes(&remote_tasks);
+        }
+
+        self.remote_tasks = remote_tasks
+            .iter()
+            .filter_map(|t| t.remote_id().map(|id| (id.to_string(), t.clone())))
+            .collect();
+
+        Ok(self.detect_changes(&self.local_tasks, &remote_tasks))
+    }
+
+    /// Hashes from before they were stable can't be compared with today's, so the pairs
+    /// start from both sides as they are now.
+    fn restart_hashes(&mut self, remote_tasks: &[P::Remote]) {
+        for remote in remote_tasks {
+            if let Some(remote_id) = remote.remote_id()
+                && let Some(yarmtl_id) = self.metadata.get_yarmtl_id(remote_id)
+                && let Some(info) = self.metadata.task_mappings.get_mut(&yarmtl_id)
+            {
+                info.remote_hash = Some(self.provider.remote_hash(remote));
+                if let Some(task) = self.local_tasks.iter().find(|t| t.id == yarmtl_id) {
+                    info.last_sync_hash = self.provider.local_hash(task);
+                }
+            }
+        }
+        self.metadata.stable_hashes = true;
+    }
+
+    /// The actions that bring both sides in line, from what changed on each since the last sync.
+    pub fn detect_changes(&self, local_tasks: &[Task], remote_tasks: &[P::Remote]) -> Vec<SyncAction<P::Remote>> {
+        let mut actions = Vec::new();
+        // Edits from the remote side go last, after the tasks they may move under are added
+        let mut pulls = Vec::new();
+
+        // Tasks the provider leaves out are as good as missing: one that was synced before
+        // is taken out of the remote side, and the remote side's own are left alone
+        let local_ids = self.provider.local_scope(local_tasks);
+
+        let remote_map: HashMap<&str, &P::Remote> =
+            remote_tasks.iter().filter_map(|t| t.remote_id().map(|id| (id, t))).collect();
+        let listed: Vec<&P::Remote> = remote_tasks.iter().collect();
+        let parents: HashMap<&str, String> = remote_map
+            .iter()
+            .filter_map(|(id, t)| self.provider.remote_parent(t, &listed).map(|parent| (*id, parent)))
+            .collect();
+        let parent_of = |task: &P::Remote| task.remote_id().and_then(|id| parents.get(id));
+
+        // Remote tasks, parents ahead of their subtasks
+        let depth = |task: &P::Remote| {
+            let mut depth = 0;
+            let mut parent = parent_of(task);
+            while let Some(parent_task) = parent.and_then(|id| remote_map.get(id.as_str())) {
+                depth += 1;
+                parent = parent_of(parent_task).filter(|_| depth < remote_tasks.len());
+            }
+            depth
+        };
+        let mut sorted: Vec<&P::Remote> = remote_tasks.iter().filter(|t| t.remote_id().is_some()).collect();
+        sorted.sort_by_key(|t| (depth(t), t.order()));
+        let remote_ids = self.provider.remote_scope(&sorted);
+
+        // Local tasks an unpaired remote task carries the id of, it gets paired with them
+        let claimed: HashSet<String> = sorted
+            .iter()
+            .filter(|t| t.remote_id().is_some_and(|id| remote_ids.contains(id) && self.metadata.get_yarmtl_id(id).is_none()))
+            .filter_map(|t| t.yarmtl_id())
+            .collect();
+
+        for local_task in local_tasks.iter().filter(|t| local_ids.contains(&t.id)) {
+            let Some(remote_id) = self.metadata.get_todoist_id(&local_task.id) else {
+                // Cancelled tasks were never done, #conflict ones wait for the user to pick a
+                // version, and completed ones never synced before stay local, like the
+                // ones the provider doesn't create
+                let skip = local_task.cancelled
+                    || local_task.tags.iter().any(|t| t == CONFLICT_TAG)
+                    || (local_task.completed && !self.provider.syncs_completion())
+                    || is_old_completed(local_task)
+                    || claimed.contains(&local_task.id)
+                    || !self.provider.creates(local_task);
+                if !skip {
+                    actions.push(SyncAction::CreateRemote(local_task.clone()));
+                }
+                continue;
+            };
+            let Some(remote) = remote_map.get(remote_id) else {
+                // Deleted on the remote side. Where completed tasks aren't listed, a
+                // completed one is done on both sides
+                if P::LISTS_COMPLETED || !local_task.completed {
+                    actions.push(SyncAction::DeleteFromYarmtl {
+                        yarmtl_id: local_task.id.clone(),
+                        text: local_task.text.clone(),
+                    });
+                }
+                continue;
+            };
+            if !remote_ids.contains(remote_id) {
+                continue;
+            }
+
+            // Both exist - check for changes on either side
+            let local_changed = self
+                .metadata
+                .get_hash(&local_task.id)
+                .is_none_or(|h| h != self.provider.local_hash(local_task));
+            let remote_changed = self
+                .metadata
+                .get_remote_hash(&local_task.id)
+                .is_some_and(|h| h != self.provider.remote_hash(remote));
+            match (local_changed, remote_changed) {
+                (true, false) => actions.push(SyncAction::UpdateRemote {
+                    yarmtl_id: local_task.id.clone(),
+                    task: local_task.clone(),
+                }),
+                (false, true) => pulls.push(SyncAction::UpdateYarmtl { task: (*remote).clone() }),
+                (true, true) => actions.push(SyncAction::Conflict {
+                    yarmtl_id: local_task.id.clone(),
+                    local: local_task.clone(),
+                    remote: Box::new((*remote).clone()),
+                    resolution: self.conflict_strategy.resolve(self.local_modified, remote.modified_at()),
+                }),
+                (false, false) => {}
+            }
+        }
+
+        for remote in &sorted {
+            let Some(remote_id) = remote.remote_id().filter(|id| remote_ids.contains(*id)) else {
+                continue;
+            };
+            match self.metadata.get_yarmtl_id(remote_id) {
+                // Completed ones are left be, tasks.md's may just have been archived
+                Some(yarmtl_id) if P::DELETES && !local_ids.contains(&yarmtl_id) && !remote.is_completed() => {
+                    actions.push(SyncAction::DeleteRemote {
+                        remote_id: remote_id.to_string(),
+                        title: remote.title().to_string(),
+                    })
+                }
+                Some(_) => {}
+                // Created from tasks.md and paired again, or new on the remote side. Tasks
+                // already completed there stay there, like old completed ones stay in tasks.md
+                None => match remote.yarmtl_id() {
+                    Some(id) if local_ids.contains(&id) => actions.push(SyncAction::UpdateYarmtl { task: (*remote).clone() }),
+                    _ if remote.is_completed() => {}
+                    _ => actions.push(SyncAction::CreateInYarmtl((*remote).clone())),
+                },
+            }
+        }
+        actions.append(&mut pulls);
+
+        // tasks.md decides the order of subtasks that are in both places
+        if P::SUBTASK_ORDER {
+            for (index, parent) in local_tasks.iter().enumerate() {
+                let Some(parent_remote_id) = self.metadata.get_todoist_id(&parent.id) else {
+                    continue;
+                };
+                let subtasks: Vec<&P::Remote> = local_tasks[subtasks::subtask_range(local_tasks, index)]
+                    .iter()
+                    .filter(|t| t.parent_id.as_ref() == Some(&parent.id) && local_ids.contains(&t.id))
+                    .filter_map(|t| self.metadata.get_todoist_id(&t.id))
+                    .filter(|id| remote_ids.contains(*id))
+                    .filter_map(|id| remote_map.get(id).copied())
+                    .filter(|t| parent_of(t).map(String::as_str) == Some(parent_remote_id) && !t.is_completed())
+                    .collect();
+                if subtasks.windows(2).any(|pair| pair[0].order() > pair[1].order()) {
+                    actions.push(SyncAction::ReorderRemote {
+                        parent: parent.text.clone(),
+                        remote_ids: subtasks.iter().filter_map(|t| t.remote_id()).map(str::to_string).collect(),
+                    });
+                }
+            }
+        }
+
+        actions
+    }
+
+    async fn apply_action(&mut self, action: SyncAction<P::Remote>, report: &mut SyncReport) -> Result<(), P::Error> {
+        match action {
+            SyncAction::CreateRemote(task) => {
+                let context = SyncContext {
+                    metadata: &self.metadata,
+                    local_tasks: &self.local_tasks,
+                    remote_tasks: &self.remote_tasks,
+                };
+                let mut created = self.provider.create(&task, &context).await?;
+                // The task is there either way and pairing it matters more, it is left open
+                // there when checking it off fails
+                let _ = self.sync_completion(&task, &mut created).await;
+                self.record_pair(task.id.clone(), &task, &created);
+                // Subtasks created later in the sync look their parent up here
+                if let Some(remote_id) = created.remote_id().map(str::to_string) {
+                    self.remote_tasks.insert(remote_id, created);
+                }
+                report.created_remote += 1;
+            }
+            SyncAction::CreateInYarmtl(remote) => {
+                let taken: HashSet<String> = self.local_tasks.iter().map(|t| t.id.clone()).collect();
+                let mut task = self.provider.to_task(&remote);
+                if taken.contains(&task.id) {
+                    task.id = ids::new_id(&taken);
+                }
+                // Added under its parent when that is a subtask
+                self.local_tasks.push(task);
+                let mut index = self.local_tasks.len() - 1;
+                if P::SUBTASKS {
+                    index = self.follow_remote_parent(index, &remote);
+                }
+                self.tasks_modified = true;
+                let task = self.local_tasks[index].clone();
+                self.record_pair(task.id.clone(), &task, &remote);
+                report.created_in_yarmtl += 1;
+            }
+            SyncAction::UpdateRemote { yarmtl_id, task } => {
+                self.push_update(yarmtl_id, &task).await?;
+                report.updated_remote += 1;
+            }
+            SyncAction::UpdateYarmtl { task, .. } => {
+                self.pull_update(&task);
+                report.updated_in_yarmtl += 1;
+            }
+            SyncAction::ReorderRemote { remote_ids, .. } => {
+                self.provider.reorder(&remote_ids).await?;
+                report.updated_remote += 1;
+            }
+            SyncAction::DeleteRemote { remote_id, .. } => {
+                let context = SyncContext {
+                    metadata: &self.metadata,
+                    local_tasks: &self.local_tasks,
+                    remote_tasks: &self.remote_tasks,
+                };
+                self.provider.delete(&remote_id, &context).await?;
+                if let Some(yarmtl_id) = self.metadata.get_yarmtl_id(&remote_id) {
+                    self.metadata.remove_mapping(&yarmtl_id);
+                }
+                report.deleted_remote += 1;
+            }
+            SyncAction::DeleteFromYarmtl { yarmtl_id, .. } => {
+                self.local_tasks.retain(|t| t.id != yarmtl_id);
+                self.tasks_modified = true;
+                self.metadata.remove_mapping(&yarmtl_id);
+                report.deleted_in_yarmtl += 1;
+            }
+            SyncAction::Conflict { yarmtl_id, local, remote, resolution } => {
+                match resolution {
+                    ConflictResolution::Local => self.push_update(yarmtl_id, &local).await?,
+                    ConflictResolution::Remote => self.pull_update(&remote),
+                    ConflictResolution::Both => self.keep_both(&yarmtl_id, &remote),
+                }
+                report.conflicts_resolved += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the local version of a paired task to the remote side.
+    async fn push_update(&mut self, yarmtl_id: String, task: &Task) -> Result<(), P::Error> {
+        let Some(remote_id) = self.metadata.get_todoist_id(&yarmtl_id).map(str::to_string) else {
+            return Ok(());
+        };
+        let context = SyncContext {
+            metadata: &self.metadata,
+            local_tasks: &self.local_tasks,
+            remote_tasks: &self.remote_tasks,
+        };
+        let mut updated = self.provider.update(&remote_id, task, &context).await?;
+        self.sync_completion(task, &mut updated).await?;
+        self.record_pair(yarmtl_id, task, &updated);
+        Ok(())
+    }
+
+    /// Checks the remote task off or reopens it with the local one, where the provider
+    /// didn't already. Cancelled tasks weren't done, the remote side leaves them be.
+    async fn sync_completion(&mut self, task: &Task, remote: &mut P::Remote) -> Result<(), P::Error> {
+        if self.provider.syncs_completion() && !task.cancelled && remote.is_completed() != task.completed {
+            self.provider.complete(remote, task.completed).await?;
+        }
+        Ok(())
+    }
+
+    /// Takes the remote version of a task into tasks.md.
+    fn pull_update(&mut self, remote: &P::Remote) {
+        let Some(remote_id) = remote.remote_id() else {
+            return;
+        };
+        // Tasks not paired yet are found by the yarmtl id they carry
+        let yarmtl_id = self.metadata.get_yarmtl_id(remote_id).or_else(|| remote.yarmtl_id());
+        let Some(index) = yarmtl_id.and_then(|id| self.local_tasks.iter().position(|t| t.id == id)) else {
+            return;
+        };
+        let mut updated = self.provider.pull(&self.local_tasks[index], remote);
+        // Only a change in completion moves `done`
+        if self.provider.syncs_completion() {
+            updated.set_completed(remote.is_completed(), chrono::Local::now().date_naive());
+        }
+        self.local_tasks[index] = updated;
+        self.tasks_modified = true;
+
+        let index = match P::SUBTASKS {
+            true => self.follow_remote_parent(index, remote),
+            false => index,
+        };
+        let task = self.local_tasks[index].clone();
+        self.record_pair(task.id.clone(), &task, remote);
+    }
+
+    /// Moves the local task at `index`, with its subtasks, to where the remote side has
+    /// it: under the task paired with its parent, among the other subtasks by the remote
+    /// order, or to the top level. A parent that isn't paired leaves it in place. Returns
+    /// its index.
+    fn follow_remote_parent(&mut self, index: usize, remote: &P::Remote) -> usize {
+        let task = &self.local_tasks[index];
+        let listed: Vec<&P::Remote> = self.remote_tasks.values().collect();
+        let parent_id = match self.provider.remote_parent(remote, &listed) {
+            // The remote side never had a parent that isn't paired
+            None if task.parent_id.as_ref().is_some_and(|id| self.metadata.get_todoist_id(id).is_none()) => return index,
+            None => None,
+            Some(remote_parent) => match self.metadata.get_yarmtl_id(&remote_parent) {
+                Some(id) if self.local_tasks.iter().any(|t| t.id == id) => Some(id),
+                _ => return index,
+            },
+        };
+        if task.parent_id == parent_id {
+            return index;
+        }
+
+        let remote_order = |id: &str| {
+            self.metadata
+                .get_todoist_id(id)
+                .and_then(|remote_id| self.remote_tasks.get(remote_id))
+                .and_then(|t| t.order())
+        };
+        let before = parent_id.as_ref().and_then(|parent| {
+            self.local_tasks
+                .iter()
+                .filter(|t| t.parent_id.as_ref() == Some(parent) && t.id != task.id)
+                .find(|t| remote_order(&t.id).is_some_and(|order| Some(order) > remote.order()))
+                .map(|t| t.id.clone())
+        });
+        subtasks::move_subtree(&mut self.local_tasks, index, parent_id.as_deref(), before.as_deref())
+    }
+
+    /// Tags the local task #conflict and adds the remote version after it (and its
+    /// subtasks), tagged the same, for the user to pick from. The remote side is left alone.
+    fn keep_both(&mut self, yarmtl_id: &str, remote: &P::Remote) {
+        let Some(index) = self.local_tasks.iter().position(|t| t.id == yarmtl_id) else {
+            return;
+        };
+        let taken: HashSet<String> = self.local_tasks.iter().map(|t| t.id.clone()).collect();
+        let local = &mut self.local_tasks[index];
+        if !local.tags.iter().any(|t| t == CONFLICT_TAG) {
+            local.tags.push(CONFLICT_TAG.to_string());
+        }
+        let local = local.clone();
+
+        let mut copy = Task { id: ids::new_id(&taken), ..self.provider.pull(&local, remote) };
+        if self.provider.syncs_completion() {
+            copy.set_completed(remote.is_completed(), chrono::Local::now().date_naive());
+        }
+        if !copy.tags.iter().any(|t| t == CONFLICT_TAG) {
+            copy.tags.push(CONFLICT_TAG.to_string());
+        }
+        let after = subtasks::subtask_range(&self.local_tasks, index).end;
+        self.local_tasks.insert(after, copy);
+        self.tasks_modified = true;
+
+        // The pair counts as synced, so neither version is pushed or pulled again
+        self.record_pair(local.id.clone(), &local, remote);
+    }
+
+    /// Pairs a local task with a remote one as they are now.
+    pub fn record_pair(&mut self, yarmtl_id: String, task: &Task, remote: &P::Remote) {
+        let Some(remote_id) = remote.remote_id() else {
+            return;
+        };
+        let info = TaskSyncInfo {
+            todoist_id: remote_id.to_string(),
+            last_modified: Utc::now(),
+            last_sync_hash: self.provider.local_hash(task),
+            remote_hash: Some(self.provider.remote_hash(remote)),
+        };
+        self.metadata.update_mapping(yarmtl_id, info);
+    }
+}
+
+/// Hash of what a sync sends from a local task, to notice edits made in tasks.md.
+pub fn compute_task_hash(task: &Task) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = StableHasher::default();
+    task.text.hash(&mut hasher);
+    task.deadline.hash(&mut hasher);
+    task.tags.iter().for_each(|t| t.hash(&mut hasher));
+    task.contexts.iter().for_each(|c| c.hash(&mut hasher));
+    task.reminder.hash(&mut hasher);
+    task.completed.hash(&mut hasher);
+    if let Some(ref notes) = task.notes {
+        notes.hash(&mut hasher);
+    }
+    task.importance.hash(&mut hasher);
+    if let Some(ref parent_id) = task.parent_id {
+        parent_id.hash(&mut hasher);
+    }
+    if let Some(ref project) = task.project {
+        project.hash(&mut hasher);
+    }
+
+    format!("{:x}", hasher.finish())
+}
+
+/// Completed tasks never synced before stay local, unless their deadline is in the
+/// future or within the last 30 days.
+pub fn is_old_completed(task: &Task) -> bool {
+    if !task.completed {
+        return false;
+    }
+    match task.deadline {
+        Some(deadline) => deadline < chrono::Local::now().date_naive() - chrono::Duration::days(30),
+        None => true,
+    }
+}
+
+/// What `yarmtl sync --backend` syncs with.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SyncBackend {
+    Todoist,
+    /// the task list in caldav_config.toml
+    Caldav,
+    /// the task list picked with --setup-google
+    Google,
+    /// issues assigned to you in the repositories picked with --setup-github
+    Github,
+}
+
+/// One sync at a time, whether with Todoist or CalDAV: waits for the lock, reporting
+/// `Waiting` meanwhile. This sync then starts from what the running one leaves behind.
+pub async fn wait_for_sync_lock(
+    mut on_progress: impl FnMut(SyncProgress),
+) -> std::io::Result<fs::File> {
+    loop {
+        match try_lock_sync(&get_sync_dir())? {
+            Some(lock) => return Ok(lock),
+            None => {
+                on_progress(SyncProgress::Waiting);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+/// Commits what a sync changed in tasks.md (silently) and runs the on-sync hook.
+pub fn after_sync(report: &SyncReport) {
+    if report.created_in_yarmtl + report.updated_in_yarmtl + report.deleted_in_yarmtl + report.conflicts_resolved > 0 {
+        let commit_msg = format!("🔄 Synced from {}: {}", report.service, report.summary());
+        let _ = git_commit_tasks_with_message(Some(&commit_msg));
+    }
+    hooks::run_sync_hook(&get_sync_dir(), report);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task;
+    use crate::todoist_sync::{SyncFilter, TodoistProvider};
+    use crate::todoist_types::TodoistTask;
+
+    fn todoist_task(id: &str, content: &str, parent_id: Option<&str>, order: i64) -> TodoistTask {
+        TodoistTask {
+            id: Some(id.to_string()),
+            content: content.to_string(),
+            description: None,
+            due: None,
+            due_date: None,
+            labels: None,
+            priority: Some(1),
+            is_completed: Some(false),
+            project_id: None,
+            section_id: None,
+            parent_id: parent_id.map(str::to_string),
+            order: Some(order),
+            updated_at: None,
+        }
+    }
+
+    /// A Todoist sync that has `local` and `remote` paired as of the last sync.
+    fn paired(filter: SyncFilter, local: &[Task], remote: &[TodoistTask]) -> SyncEngine<TodoistProvider> {
+        let temp = tempfile::tempdir().unwrap();
+        let sync_dir = temp.path();
+        let provider = TodoistProvider::new("token".to_string(), true, filter);
+        let mut sync = SyncEngine::new(provider, sync_dir, ConflictStrategy::KeepBoth).unwrap();
+        for (local, remote) in local.iter().zip(remote) {
+            sync.record_pair(local.id.clone(), local, remote);
+        }
+        sync
+    }
+
+    #[test]
+    fn test_edits_on_both_sides_are_a_conflict() {
+        let local = Task::parse("call bob [id:abc123]");
+        let remote = todoist_task("t1", "call bob", None, 1);
+        let mut sync = paired(SyncFilter::default(), std::slice::from_ref(&local), std::slice::from_ref(&remote));
+        assert!(sync.detect_changes(std::slice::from_ref(&local), std::slice::from_ref(&remote)).is_empty());
+
+        let edited_local = [Task::parse("call bob today [id:abc123]")];
+        let edited_remote = [TodoistTask { content: "call bob back".to_string(), ..remote }];
+        let actions = sync.detect_changes(&edited_local, &edited_remote);
+        assert!(matches!(actions[..], [SyncAction::Conflict { resolution: ConflictResolution::Both, .. }]));
+        assert!(matches!(sync.detect_changes(&[local], &edited_remote)[..], [SyncAction::UpdateYarmtl { .. }]));
+
+        let SyncAction::Conflict { yarmtl_id, remote, .. } = &actions[0] else { unreachable!() };
+        sync.local_tasks = edited_local.to_vec();
+        sync.keep_both(yarmtl_id, remote);
+        let texts: Vec<String> = sync.local_tasks.iter().map(|t| format!("{} {:?}", t.text, t.tags)).collect();
+        assert_eq!(texts, ["call bob today [\"conflict\"]", "call bob back [\"conflict\"]"]);
+        // Neither copy goes anywhere until the user picks one
+        assert!(sync.detect_changes(&sync.local_tasks.clone(), &edited_remote).is_empty());
+    }
+
+    #[test]
+    fn test_hashes_from_older_versions_start_over() {
+        let local = Task::parse("call bob [id:abc123]");
+        let remote = todoist_task("t1", "call bob", None, 1);
+        let mut sync = paired(SyncFilter::default(), std::slice::from_ref(&local), std::slice::from_ref(&remote));
+        for info in sync.metadata.task_mappings.values_mut() {
+            info.last_sync_hash = "5d1f0c3a9e".to_string();
+            info.remote_hash = Some("8b2e47a1c0".to_string());
+        }
+        sync.metadata.stable_hashes = false;
+        let actions = sync.detect_changes(std::slice::from_ref(&local), std::slice::from_ref(&remote));
+        assert!(matches!(actions[..], [SyncAction::Conflict { .. }]));
+
+        sync.local_tasks = vec![local.clone()];
+        sync.restart_hashes(std::slice::from_ref(&remote));
+        assert!(sync.metadata.stable_hashes);
+        assert!(sync.detect_changes(&[local], &[remote]).is_empty());
+    }
+
+    #[test]
+    fn test_completions_in_todoist_check_tasks_off() {
+        let local = Task::parse("water plants [id:abc123]");
+        let remote = todoist_task("t1", "water plants", None, 1);
+        let mut sync = paired(SyncFilter::default(), std::slice::from_ref(&local), std::slice::from_ref(&remote));
+
+        // Checked off on the phone: no longer listed, but found among the completed tasks
+        let completed = TodoistTask { is_completed: Some(true), ..remote };
+        let actions = sync.detect_changes(std::slice::from_ref(&local), std::slice::from_ref(&completed));
+        let [SyncAction::UpdateYarmtl { task, .. }] = &actions[..] else { panic!("{:?}", actions) };
+        sync.local_tasks = vec![local.clone()];
+        sync.pull_update(task);
+        assert!(sync.local_tasks[0].completed);
+
+        // Done on both sides is nothing to do, gone from Todoist while open here is a deletion
+        assert!(sync.detect_changes(&sync.local_tasks.clone(), &[]).is_empty());
+        assert!(matches!(sync.detect_changes(&[local], &[])[..], [SyncAction::DeleteFromYarmtl { .. }]));
+    }
+
+    #[test]
+    fn test_filtered_out_tasks_stay_local() {
+        let filter = toml::from_str::<SyncFilter>("exclude_tags = [\"private\"]").unwrap();
+        assert!(filter.completed);
+        assert_eq!(filter.describe(), "all tasks, not #private");
+        let remote = todoist_task("t1", "old plan", None, 1);
+        let sync = paired(filter.clone(), &[Task::parse("old plan [id:aaa111]")], std::slice::from_ref(&remote));
+
+        // Made private since the last sync, with a private task and its subtask next to it
+        let local = task::parse_tasks(
+            "- [ ] old plan #private [id:aaa111]\n- [ ] diary #private [id:bbb222]\n  - [ ] entry [id:ccc333]\n- [ ] plan #work [id:ddd444]\n",
+        );
+        assert_eq!(filter.local_scope(&local), HashSet::from(["ddd444".to_string()]));
+        let private_remote = TodoistTask { labels: Some(vec!["private".to_string()]), ..todoist_task("t2", "secret", None, 2) };
+        let actions = sync.detect_changes(&local, &[remote, private_remote]);
+        assert!(matches!(&actions[..], [
+            SyncAction::CreateRemote(task),
+            SyncAction::DeleteRemote { remote_id, .. },
+        ] if task.id == "ddd444" && remote_id == "t1"), "{:?}", actions);
+    }
+
+    #[test]
+    fn test_subtasks_keep_their_parent_and_order() {
+        let local = task::parse_tasks("- [ ] trip [id:abc123]\n  - [ ] book flights [id:b1b1b1]\n  - [ ] pack [id:p1p1p1]\n");
+        let mut remote = vec![
+            todoist_task("t1", "trip", None, 1),
+            todoist_task("t2", "book flights", Some("t1"), 1),
+            todoist_task("t3", "pack", Some("t1"), 3),
+        ];
+        let mut sync = paired(SyncFilter::default(), &local, &remote);
+        assert!(sync.detect_changes(&local, &remote).is_empty());
+
+        // Reordered in Todoist, tasks.md puts them back
+        remote[1].order = Some(4);
+        let actions = sync.detect_changes(&local, &remote);
+        let [SyncAction::ReorderRemote { parent, remote_ids }] = &actions[..] else { panic!("{:?}", actions) };
+        assert_eq!((parent.as_str(), remote_ids.as_slice()), ("trip", ["t2".to_string(), "t3".to_string()].as_slice()));
+
+        // A subtask added in Todoist lands between the two, by its order there
+        remote[1].order = Some(1);
+        sync.remote_tasks = remote.iter().map(|t| (t.id.clone().unwrap(), t.clone())).collect();
+        sync.local_tasks = local;
+        sync.local_tasks.push(Task::parse("socks"));
+        let index = sync.follow_remote_parent(3, &todoist_task("t4", "socks", Some("t1"), 2));
+        let texts: Vec<(&str, usize)> = sync.local_tasks.iter().map(|t| (t.text.as_str(), t.indent_level)).collect();
+        assert_eq!(index, 2);
+        assert_eq!(texts, [("trip", 0), ("book flights", 1), ("socks", 1), ("pack", 1)]);
+    }
+
+    #[test]
+    fn test_newest_wins_needs_both_times() {
+        let earlier = Utc::now() - chrono::Duration::hours(1);
+        let later = Utc::now();
+        let newest = ConflictStrategy::NewestWins;
+        assert_eq!(newest.resolve(Some(later), Some(earlier)), ConflictResolution::Local);
+        assert_eq!(newest.resolve(Some(earlier), Some(later)), ConflictResolution::Remote);
+        assert_eq!(newest.resolve(Some(later), None), ConflictResolution::Both);
+        assert_eq!(ConflictStrategy::PreferRemote.resolve(None, None), ConflictResolution::Remote);
+    }
+}
@@ -1,6 +1,7 @@
+use crate::todoist_types::{TodoistProject, TodoistSection, TodoistSyncResponse, TodoistTask};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::PathBuf;
 
@@ -8,13 +9,32 @@ use std::path::PathBuf;
 pub struct SyncMetadata {
     pub last_sync: DateTime<Utc>,
     pub task_mappings: HashMap<String, TaskSyncInfo>,
+    /// Where the next incremental sync picks up, None before the first one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_token: Option<String>,
+    /// Todoist's open tasks and its projects as of `sync_token`, by id
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub todoist_tasks: BTreeMap<String, TodoistTask>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub todoist_projects: BTreeMap<String, TodoistProject>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub todoist_sections: BTreeMap<String, TodoistSection>,
+    /// Whether the hashes are FNV-1a ones. Older versions kept hashes that could change
+    /// with the Rust release yarmtl was built with
+    #[serde(default)]
+    pub stable_hashes: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TaskSyncInfo {
+    /// The paired task's id: Todoist's, or the href of a CalDAV task
     pub todoist_id: String,
     pub last_modified: DateTime<Utc>,
     pub last_sync_hash: String,
+    /// Hash of the Todoist task as of the last sync, to tell edits made there. None in
+    /// metadata from older versions until the pair is synced again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_hash: Option<String>,
 }
 
 impl SyncMetadata {
@@ -22,6 +42,11 @@ impl SyncMetadata {
         SyncMetadata {
             last_sync: Utc::now(),
             task_mappings: HashMap::new(),
+            sync_token: None,
+            todoist_tasks: BTreeMap::new(),
+            todoist_projects: BTreeMap::new(),
+            todoist_sections: BTreeMap::new(),
+            stable_hashes: true,
         }
     }
 
@@ -37,6 +62,9 @@ impl SyncMetadata {
 
     pub fn save(&self, path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
         let content = serde_json::to_string_pretty(self)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
         fs::write(path, content)?;
         Ok(())
     }
@@ -62,6 +90,49 @@ impl SyncMetadata {
         self.task_mappings.remove(yarmtl_id);
     }
 
+    /// Moves the mapping of a task over to its new id.
+    pub fn rename(&mut self, old_id: &str, new_id: &str) {
+        if let Some(info) = self.task_mappings.remove(old_id) {
+            self.task_mappings.insert(new_id.to_string(), info);
+        }
+    }
+
+    /// Brings the cached Todoist tasks, projects and sections up to date with what the Sync API
+    /// sent. Completed and deleted tasks leave the cache, like they leave REST listings.
+    pub fn apply_sync_response(&mut self, response: TodoistSyncResponse) {
+        if response.full_sync {
+            self.todoist_tasks.clear();
+            self.todoist_projects.clear();
+            self.todoist_sections.clear();
+        }
+        for item in response.items {
+            self.todoist_tasks.remove(&item.id);
+            if item.is_active() {
+                self.todoist_tasks.insert(item.id.clone(), item.into());
+            }
+        }
+        for project in response.projects {
+            self.todoist_projects.remove(&project.id);
+            if !project.is_deleted && !project.is_archived {
+                let cached = TodoistProject {
+                    id: project.id.clone(),
+                    name: project.name,
+                    color: project.color,
+                    is_inbox_project: project.inbox_project,
+                };
+                self.todoist_projects.insert(project.id, cached);
+            }
+        }
+        for section in response.sections {
+            self.todoist_sections.remove(&section.id);
+            if section.is_active() {
+                let cached = TodoistSection { id: section.id.clone(), name: section.name, project_id: section.project_id };
+                self.todoist_sections.insert(section.id, cached);
+            }
+        }
+        self.sync_token = Some(response.sync_token);
+    }
+
     pub fn update_last_sync(&mut self) {
         self.last_sync = Utc::now();
     }
@@ -71,6 +142,12 @@ impl SyncMetadata {
             .get(yarmtl_id)
             .map(|info| info.last_sync_hash.as_str())
     }
+
+    pub fn get_remote_hash(&self, yarmtl_id: &str) -> Option<&str> {
+        self.task_mappings
+            .get(yarmtl_id)
+            .and_then(|info| info.remote_hash.as_deref())
+    }
 }
 
 impl Default for SyncMetadata {
@@ -96,6 +173,7 @@ mod tests {
             todoist_id: "todoist123".to_string(),
             last_modified: Utc::now(),
             last_sync_hash: "hash123".to_string(),
+            remote_hash: None,
         };
 
         metadata.update_mapping("yarmtl123".to_string(), info);
@@ -117,6 +195,7 @@ mod tests {
             todoist_id: "todoist123".to_string(),
             last_modified: Utc::now(),
             last_sync_hash: "hash123".to_string(),
+            remote_hash: None,
         };
 
         metadata.update_mapping("yarmtl123".to_string(), info);
@@ -124,4 +203,34 @@ mod tests {
 
         assert_eq!(metadata.get_todoist_id("yarmtl123"), None);
     }
+
+    #[test]
+    fn test_sync_responses_update_the_cache() {
+        let response = |json: &str| serde_json::from_str::<TodoistSyncResponse>(json).unwrap();
+        let mut metadata = SyncMetadata::new();
+        metadata.apply_sync_response(response(
+            r#"{"sync_token":"t1","full_sync":true,"projects":[{"id":"p1","name":"home"}],
+                "sections":[{"id":"s1","name":"garden","project_id":"p1"}],
+                "items":[{"id":"a","content":"call bob"},{"id":"b","content":"pay rent"}]}"#,
+        ));
+        metadata.apply_sync_response(response(
+            r#"{"sync_token":"t2","items":[{"id":"a","content":"call bob","checked":true},
+                {"id":"b","content":"pay the rent"},{"id":"c","content":"new","is_deleted":true}]}"#,
+        ));
+
+        let contents: Vec<&str> = metadata.todoist_tasks.values().map(|t| t.content.as_str()).collect();
+        assert_eq!(contents, ["pay the rent"]);
+        assert_eq!(metadata.todoist_projects["p1"].name, "home");
+        assert_eq!(metadata.todoist_sections["s1"].name, "garden");
+        assert_eq!(metadata.sync_token.as_deref(), Some("t2"));
+    }
+
+    #[test]
+    fn test_metadata_without_remote_hash_loads() {
+        let json = r#"{"last_sync":"2025-06-01T10:00:00Z","task_mappings":{"abc123":
+            {"todoist_id":"t1","last_modified":"2025-06-01T10:00:00Z","last_sync_hash":"h"}}}"#;
+        let metadata: SyncMetadata = serde_json::from_str(json).unwrap();
+        assert_eq!(metadata.get_hash("abc123"), Some("h"));
+        assert_eq!(metadata.get_remote_hash("abc123"), None);
+    }
 }
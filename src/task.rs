@@ -0,0 +1,1171 @@
+use crate::clock::{Clock, SystemClock};
+use crate::dates;
+use crate::encryption;
+use crate::ids;
+use crate::recurrence::Recurrence;
+use crate::subtasks;
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::cell::OnceCell;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// How tasks are written to tasks.md. Both are always read, and a task is written in the
+/// format set when it next changes. "obsidian" follows the Obsidian Tasks plugin: `📅 2025-07-01` due
+/// dates, `🔁 every week` recurrence, `⏫` priorities and `🆔` ids; markers it has no
+/// notion of stay in yarmtl's syntax.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskFormat {
+    #[default]
+    Yarmtl,
+    Obsidian,
+}
+
+static FORMAT: OnceLock<TaskFormat> = OnceLock::new();
+
+/// Set once at startup from `format` in the `[tasks]` section of config.toml.
+pub fn set_format(format: TaskFormat) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn format() -> TaskFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+static WARN_DAYS: OnceLock<u32> = OnceLock::new();
+
+/// Set once at startup from `warn_days` in the `[tasks]` section of config.toml.
+pub fn set_warn_days(days: u32) {
+    let _ = WARN_DAYS.set(days);
+}
+
+pub fn warn_days() -> u32 {
+    WARN_DAYS.get().copied().unwrap_or_default()
+}
+
+/// One line of tasks.md. The CLI, the TUI, the email reminders and the Todoist sync all share it.
+#[derive(Debug, Clone, PartialEq, std::hash::Hash)]
+pub struct Task {
+    pub id: String,
+    pub text: String,
+    pub deadline: Option<NaiveDate>,
+    pub tags: Vec<String>,
+    /// Where the task can be done (GTD contexts), written as >home or >"the office"
+    pub contexts: Vec<String>,
+    pub reminder: Option<NaiveDate>,
+    /// A reminder that repeats regardless of the deadline, written as @every monday
+    pub repeat: Option<Recurrence>,
+    /// Day the repeating reminder last went out, written as [fired:YYYY-MM-DD]
+    pub last_fired: Option<NaiveDate>,
+    /// Checked off, or cancelled: either way the task is no longer open
+    pub completed: bool,
+    /// Dropped rather than done, written as a `- [-]` checkbox. Cancelled tasks are
+    /// also `completed`, so everything that only wants open tasks skips them.
+    pub cancelled: bool,
+    pub notes: Option<String>,
+    pub importance: Option<u8>,
+    /// Nesting depth in tasks.md (two spaces per level); subtasks sit one level below their parent
+    pub indent_level: usize,
+    /// Id of the task this is a subtask of. Derived from the indentation, never written out
+    pub parent_id: Option<String>,
+    /// The `## ` heading of tasks.md the task is under. Derived from its position, never written out
+    pub project: Option<String>,
+    /// The file tasks.md includes the task from, as the include names it; None for tasks.md
+    /// itself. Derived from where it is read, never written out
+    pub source: Option<String>,
+    /// File names stored under attachments/<id>/, or paths and URLs elsewhere, written as [attach:name]
+    pub attachments: Vec<String>,
+    /// Day the task was added, written as [created:YYYY-MM-DD]; missing on tasks from older versions
+    pub created: Option<NaiveDate>,
+    /// Day the task was checked off, written as [done:YYYY-MM-DD]
+    pub done: Option<NaiveDate>,
+    /// How long the task should take, written as ~30m, ~2h or ~1h30m
+    pub estimate: Option<Duration>,
+    /// Pomodoro time spent on the task so far, written as [spent:1h30m]
+    pub spent: Option<Duration>,
+    /// Shown above everything else in every view, written as [pin]
+    pub pinned: bool,
+    /// A color name or an emoji to spot the task by, written as [flag:red] or [flag:🔥]
+    pub flag: Option<String>,
+    /// Steps done out of a target, (done, target), written as [count:3/10]
+    pub count: Option<(u32, u32)>,
+    /// Days ahead of the deadline the reminders start, written as [warn:3d]; [warn:0d]
+    /// keeps the `warn_days` of config.toml off the task
+    pub warn: Option<u32>,
+}
+
+impl Task {
+    /// Reads one task line (without the "- [ ] " checkbox). The line is split into
+    /// whitespace separated words and markers are only recognised at the start of a
+    /// word, so URLs, email addresses and `#` inside code are left alone. A backslash
+    /// makes the character after it a plain one (`\#1`), and "double quoted" or
+    /// `backticked` spans are kept as they are, spaces and marker characters included.
+    pub fn parse(input: &str) -> Self {
+        Self::parse_at(input, &SystemClock)
+    }
+
+    /// `parse`, with relative dates like `!tomorrow` read from the clock's today.
+    pub fn parse_at(input: &str, clock: &dyn Clock) -> Self {
+        let mut task = Self::parse_markers(input, clock);
+        if task.id.is_empty() {
+            task.id = ids::generate();
+        }
+        task
+    }
+
+    /// `parse_at`, leaving the id empty when the input has none.
+    fn parse_markers(input: &str, clock: &dyn Clock) -> Self {
+        // Only relative dates need the day, and reading the clock isn't free
+        let today = OnceCell::new();
+        let today = || *today.get_or_init(|| clock.today());
+        let mut words = split_words(input);
+        let mut task = Task {
+            id: String::new(),
+            text: String::new(),
+            deadline: None,
+            tags: Vec::new(),
+            contexts: Vec::new(),
+            reminder: None,
+            repeat: None,
+            last_fired: None,
+            completed: false,
+            cancelled: false,
+            notes: None,
+            importance: None,
+            indent_level: 0,
+            parent_id: None,
+            project: None,
+            source: None,
+            attachments: Vec::new(),
+            created: None,
+            done: None,
+            estimate: None,
+            spent: None,
+            pinned: false,
+            flag: None,
+            count: None,
+            warn: None,
+        };
+        let mut text: Vec<String> = Vec::new();
+        let mut notes: Vec<String> = Vec::new();
+        let mut in_notes = false;
+        let mut rule = None;
+
+        let raws: Vec<&str> = words.iter().map(|w| w.raw).collect();
+        let mut i = 0;
+        while i < words.len() {
+            if let Some((marker, consumed)) = marker_at(&raws, i, &today) {
+                match marker {
+                    Marker::Id(id) => task.id = id,
+                    Marker::Attachment(name) => task.attachments.push(name),
+                    Marker::Created(date) => task.created = Some(date),
+                    Marker::Done(date) => task.done = Some(date),
+                    Marker::Deadline(date) => task.deadline = task.deadline.or(Some(date)),
+                    Marker::Reminder(date) => task.reminder = task.reminder.or(Some(date)),
+                    Marker::Repeat(repeat) => task.repeat = Some(repeat),
+                    Marker::Rule(phrase) => rule = Some(phrase),
+                    Marker::Fired(date) => task.last_fired = Some(date),
+                    Marker::Tag(tag) => task.tags.push(tag),
+                    Marker::Context(context) => task.contexts.push(context),
+                    Marker::Importance(importance) => task.importance = Some(importance),
+                    Marker::Estimate(estimate) => task.estimate = Some(estimate),
+                    Marker::Spent(spent) => task.spent = Some(spent),
+                    Marker::Pin => task.pinned = true,
+                    Marker::Flag(flag) => task.flag = Some(flag),
+                    Marker::Count(done, target) => task.count = Some((done, target)),
+                    Marker::Warn(days) => task.warn = Some(days),
+                }
+                // Notes run until the next marker
+                in_notes = false;
+                i += consumed;
+                continue;
+            }
+
+            let word = &mut words[i];
+            if in_notes {
+                notes.push(std::mem::take(&mut word.value));
+            } else if word.raw.starts_with("//") {
+                in_notes = true;
+                notes.push(word.value[2..].to_string());
+            } else {
+                text.push(std::mem::take(&mut word.value));
+            }
+            i += 1;
+        }
+
+        if let Some(phrase) = rule {
+            // `every week` is the weekday it is due on
+            task.repeat = Recurrence::parse_rule(&phrase, task.deadline.unwrap_or_else(today));
+        }
+        task.text = text.join(" ");
+        task.notes = Some(notes.join(" ").trim_start().to_string()).filter(|n| !n.is_empty());
+        task
+    }
+
+    /// Checks or unchecks the task, keeping `done` in step. Unchecking a cancelled task
+    /// opens it again.
+    pub fn set_completed(&mut self, completed: bool, today: NaiveDate) {
+        if completed && !self.completed {
+            self.done = Some(today);
+        } else if !completed {
+            self.done = None;
+            self.cancelled = false;
+        }
+        self.completed = completed;
+    }
+
+    /// Cancels the task, closing it like a completed one with `done` as the day it was
+    /// dropped, or opens a cancelled task again.
+    pub fn set_cancelled(&mut self, cancelled: bool, today: NaiveDate) {
+        self.set_completed(cancelled, today);
+        self.cancelled = cancelled;
+    }
+
+    /// Moves the `[count:3/10]` counter by `step`, between 0 and the target. Reaching the
+    /// target checks the task off, dropping below it opens the task again. Returns false
+    /// for tasks without a counter.
+    pub fn bump(&mut self, step: i32, today: NaiveDate) -> bool {
+        let Some((done, target)) = self.count else { return false };
+        let done = done.saturating_add_signed(step).min(target);
+        self.count = Some((done, target));
+        self.set_completed(done == target, today);
+        true
+    }
+    
+    /// The day the reminders about the deadline start, `warn` days (or `warn_days` of
+    /// config.toml) before it. None without a deadline or with no warning wanted.
+    pub fn warning(&self) -> Option<NaiveDate> {
+        let days = self.warn.unwrap_or_else(warn_days);
+        self.deadline.filter(|_| days > 0).map(|deadline| deadline - Days::new(days.into()))
+    }
+
+    /// The task's line in tasks.md, in the format set in config.toml.
+    pub fn to_markdown(&self) -> String {
+        self.to_markdown_as(format())
+    }
+
+    /// The task's line in tasks.md. Parsing it gives back an equal task, as long as the
+    /// text and notes are single-spaced (`parse` always makes them so) and the id is one
+    /// `parse` accepts; the round trip tests below hold this in place.
+    pub fn to_markdown_as(&self, format: TaskFormat) -> String {
+        let checkbox = match (self.completed, self.cancelled) {
+            (_, true) => "[-]",
+            (true, false) => "[x]",
+            (false, false) => "[ ]",
+        };
+        let indent = "  ".repeat(self.indent_level);
+        let yarmtl = format == TaskFormat::Yarmtl;
+        let mut result = format!("{}- {} {}", indent, checkbox, escape_words(&self.text));
+        if yarmtl {
+            result.push_str(&format!(" [id:{}]", self.id));
+        }
+
+        if let Some(ref deadline) = self.deadline
+            && yarmtl
+        {
+            result.push_str(&format!(" !{}", deadline.format("%Y-%m-%d")));
+        }
+        
+        for tag in &self.tags {
+            if is_plain_tag(tag) {
+                result.push_str(&format!(" #{}", tag));
+            } else {
+                // Todoist labels may contain spaces and the like
+                result.push_str(&format!(" #\"{}\"", tag));
+            }
+        }
+        for context in &self.contexts {
+            if is_plain_tag(context) {
+                result.push_str(&format!(" >{}", context));
+            } else {
+                result.push_str(&format!(" >\"{}\"", context));
+            }
+        }
+        
+        if let Some(ref reminder) = self.reminder
+            && yarmtl
+        {
+            result.push_str(&format!(" @{}", reminder.format("%Y-%m-%d")));
+        }
+        if let Some(repeat) = self.repeat
+            && yarmtl
+        {
+            result.push_str(&format!(" @{}", repeat));
+        }
+
+        if let Some(estimate) = self.estimate {
+            result.push_str(&format!(" ~{}", format_duration(estimate)));
+        }
+
+        if let Some(created) = self.created
+            && yarmtl
+        {
+            result.push_str(&format!(" [created:{}]", created.format("%Y-%m-%d")));
+        }
+        if let Some(done) = self.done
+            && yarmtl
+        {
+            result.push_str(&format!(" [done:{}]", done.format("%Y-%m-%d")));
+        }
+        if let Some(spent) = self.spent {
+            result.push_str(&format!(" [spent:{}]", format_duration(spent)));
+        }
+        if let Some(fired) = self.last_fired {
+            result.push_str(&format!(" [fired:{}]", fired.format("%Y-%m-%d")));
+        }
+        if self.pinned {
+            result.push_str(" [pin]");
+        }
+        if let Some(ref flag) = self.flag {
+            result.push_str(&format!(" [flag:{}]", flag));
+        }
+        if let Some((done, target)) = self.count {
+            result.push_str(&format!(" [count:{}/{}]", done, target));
+        }
+        if let Some(days) = self.warn {
+            result.push_str(&format!(" [warn:{}d]", days));
+        }
+
+        // Before the notes, which run until the next metadata marker
+        for attachment in &self.attachments {
+            result.push_str(&format!(" [attach:{}]", attachment));
+        }
+
+        if let Some(ref notes) = self.notes {
+            result.push_str(&format!(" //{}", escape_words(notes)));
+        }
+
+        if !yarmtl {
+            // Obsidian Tasks reads these from the end of the line, in this order
+            result.push_str(&format!(" {} {}", ID, self.id));
+            if let Some(importance) = self.importance {
+                result.push_str(&format!(" {}", PRIORITIES[importance as usize - 1]));
+            }
+            if let Some(repeat) = self.repeat {
+                result.push_str(&format!(" {} {}", RECURRING, repeat.to_rule()));
+            }
+            let dates = [
+                (CREATED, self.created),
+                (SCHEDULED, self.reminder),
+                (DUE, self.deadline),
+                (if self.cancelled { CANCELLED } else { DONE }, self.done),
+            ];
+            for (signifier, date) in dates {
+                if let Some(date) = date {
+                    result.push_str(&format!(" {} {}", signifier, date.format("%Y-%m-%d")));
+                }
+            }
+        } else if let Some(importance) = self.importance {
+            result.push_str(&format!(" ${}", importance));
+        }
+
+        result
+    }
+}
+
+/// The longest phrase a natural language date may span, e.g. "!next friday".
+const MAX_DATE_WORDS: usize = 4;
+
+/// The Obsidian Tasks signifiers, each followed by a date, an id or a recurrence rule.
+const DUE: char = '📅';
+const SCHEDULED: char = '⏳';
+const CREATED: char = '➕';
+const DONE: char = '✅';
+const CANCELLED: char = '❌';
+const RECURRING: char = '🔁';
+const ID: char = '🆔';
+
+/// Obsidian Tasks' priorities, standing for importance $1 (highest) to $5 (lowest).
+const PRIORITIES: [char; 5] = ['🔺', '⏫', '🔼', '🔽', '⏬'];
+
+/// The longest recurrence rule after a `🔁`, "every month on the 15th when done".
+const MAX_RULE_WORDS: usize = 7;
+
+/// Characters a backslash in front of takes the special meaning from.
+const ESCAPABLE: [char; 23] = [
+    '!', '@', '#', '>', '$', '~', '/', '[', '"', '`', '\\',
+    DUE, SCHEDULED, CREATED, DONE, CANCELLED, RECURRING, ID,
+    PRIORITIES[0], PRIORITIES[1], PRIORITIES[2], PRIORITIES[3], PRIORITIES[4],
+];
+
+/// Characters that may come before a quote opening a span: `!"next friday"`, `#"to read"`.
+const SIGILS: [char; 5] = ['!', '@', '#', '>', '/'];
+
+/// A whitespace separated word of a task line.
+struct Word<'a> {
+    /// As written, escapes and quotes included
+    raw: &'a str,
+    /// With the escapes resolved
+    value: String,
+    /// Where the quoted span in `raw` ends, if there is one
+    span_end: Option<usize>,
+}
+
+enum Marker {
+    Id(String),
+    Attachment(String),
+    Created(NaiveDate),
+    Done(NaiveDate),
+    Deadline(NaiveDate),
+    Reminder(NaiveDate),
+    Repeat(Recurrence),
+    /// An Obsidian Tasks recurrence rule, read once the due date is known
+    Rule(String),
+    Fired(NaiveDate),
+    Tag(String),
+    Context(String),
+    Importance(u8),
+    Estimate(Duration),
+    Spent(Duration),
+    Pin,
+    Flag(String),
+    Count(u32, u32),
+    /// Days, from `[warn:3d]` or `[warn:1w]`
+    Warn(u32),
+}
+
+/// Splits on whitespace, except inside "quoted" and `backticked` spans and inside
+/// `[attach:...]`, whose file names may contain spaces. A span only opens at the
+/// start of a word, or after a run of `!`, `@`, `#` and `/` for quoted dates, tags and
+/// notes, and only when it is closed later on; otherwise the quote is an ordinary
+/// character. Outside spans a backslash makes the character after it a plain one.
+fn split_words(input: &str) -> Vec<Word<'_>> {
+    let mut words = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = input[pos..].find(|c: char| !c.is_whitespace()) {
+        let start = pos + offset;
+        // Most words have no escapes or spans to resolve
+        let end = input[start..].find(char::is_whitespace).map_or(input.len(), |length| start + length);
+        let raw = &input[start..end];
+        if !raw.contains(['\\', '"', '`']) && !raw.starts_with("[attach:") {
+            words.push(Word { raw, value: raw.to_string(), span_end: None });
+            pos = end;
+            continue;
+        }
+
+        let mut value = String::new();
+        let mut span_end = None;
+        let mut in_head = true;
+        let mut chars = input[start..].char_indices().map(|(i, c)| (start + i, c)).peekable();
+        pos = input.len();
+
+        while let Some((i, c)) = chars.next() {
+            if c.is_whitespace() {
+                pos = i;
+                break;
+            }
+            if c == '\\'
+                && let Some(&(_, next)) = chars.peek()
+                && ESCAPABLE.contains(&next)
+            {
+                chars.next();
+                value.push(next);
+                in_head = false;
+                continue;
+            }
+
+            let closing = match c {
+                '"' | '`' if in_head => Some(c),
+                '[' if i == start && input[i..].starts_with("[attach:") => Some(']'),
+                _ => None,
+            };
+            if let Some(close) = closing
+                && let Some(length) = input[i + 1..].find(close)
+            {
+                let end = i + 1 + length + close.len_utf8();
+                // Runs of whitespace still become a single space, like between words
+                value.push_str(&input[i..end].split_whitespace().collect::<Vec<_>>().join(" "));
+                while chars.next_if(|&(p, _)| p < end).is_some() {}
+                if c != '[' {
+                    span_end = Some(end - start);
+                }
+                in_head = false;
+                continue;
+            }
+
+            in_head &= SIGILS.contains(&c);
+            value.push(c);
+        }
+        words.push(Word { raw: &input[start..pos], value, span_end });
+    }
+    words
+}
+
+/// The marker starting at `words[i]` and how many words it takes up.
+fn marker_at(words: &[&str], i: usize, today: &dyn Fn() -> NaiveDate) -> Option<(Marker, usize)> {
+    let word = words[i];
+    let bracketed = |prefix: &str| word.strip_prefix(prefix).and_then(|rest| rest.strip_suffix(']'));
+    let iso_date = dates::parse_iso;
+
+    if let Some(id) = bracketed("[id:") {
+        return ids::is_valid(id).then(|| (Marker::Id(id.to_string()), 1));
+    }
+    if let Some(marker) = obsidian_marker_at(words, i, today) {
+        return Some(marker);
+    }
+    if let Some(name) = bracketed("[attach:") {
+        return (!name.is_empty() && !name.contains(']')).then(|| (Marker::Attachment(name.to_string()), 1));
+    }
+    if let Some(date) = bracketed("[created:") {
+        return iso_date(date).map(|d| (Marker::Created(d), 1));
+    }
+    if let Some(date) = bracketed("[done:") {
+        return iso_date(date).map(|d| (Marker::Done(d), 1));
+    }
+    if let Some(date) = bracketed("[fired:") {
+        return iso_date(date).map(|d| (Marker::Fired(d), 1));
+    }
+    if word == "[pin]" {
+        return Some((Marker::Pin, 1));
+    }
+    if let Some(flag) = bracketed("[flag:") {
+        let plain = !flag.is_empty() && !flag.contains([']', '"', '`', '\\']);
+        return plain.then(|| (Marker::Flag(flag.to_string()), 1));
+    }
+    if let Some(count) = bracketed("[count:") {
+        let (done, target) = count.split_once('/')?;
+        let (done, target) = (done.parse().ok()?, target.parse().ok()?);
+        return (target > 0 && done <= target).then_some((Marker::Count(done, target), 1));
+    }
+    if let Some(warn) = bracketed("[warn:") {
+        let days = |n: &str, per: u32| n.parse::<u32>().ok()?.checked_mul(per);
+        let days = match (warn.strip_suffix('d'), warn.strip_suffix('w')) {
+            (Some(n), _) => days(n, 1),
+            (_, Some(n)) => days(n, 7),
+            _ => None,
+        };
+        return days.map(|days| (Marker::Warn(days), 1));
+    }
+    if let Some(spent) = bracketed("[spent:") {
+        return parse_duration(spent).map(|d| (Marker::Spent(d), 1));
+    }
+    if let Some(estimate) = word.strip_prefix('~') {
+        return parse_duration(estimate).map(|d| (Marker::Estimate(d), 1));
+    }
+    // A plain name, or a quoted one that may hold anything
+    let name = |name: &str| {
+        let quoted = name.strip_prefix('"').and_then(|rest| rest.strip_suffix('"'));
+        match quoted {
+            Some(name) if !name.is_empty() => Some(name.to_string()),
+            _ => is_plain_tag(name).then(|| name.to_string()),
+        }
+    };
+    if let Some(tag) = word.strip_prefix('#') {
+        return name(tag).map(|tag| (Marker::Tag(tag), 1));
+    }
+    if let Some(context) = word.strip_prefix('>') {
+        return name(context).map(|context| (Marker::Context(context), 1));
+    }
+    if let Some(level) = word.strip_prefix('$') {
+        return match level.as_bytes() {
+            [digit @ b'1'..=b'5'] => Some((Marker::Importance(digit - b'0'), 1)),
+            _ => None,
+        };
+    }
+    if word.starts_with('!') {
+        return date_phrase(words, i, today).map(|(date, n)| (Marker::Deadline(date), n));
+    }
+    if word.starts_with('@') {
+        if let Some((repeat, n)) = repeat_phrase(words, i) {
+            return Some((Marker::Repeat(repeat), n));
+        }
+        return date_phrase(words, i, today).map(|(date, n)| (Marker::Reminder(date), n));
+    }
+    None
+}
+
+/// An Obsidian Tasks signifier at `words[i]`, with the word or words after it.
+fn obsidian_marker_at(words: &[&str], i: usize, today: &dyn Fn() -> NaiveDate) -> Option<(Marker, usize)> {
+    // Emoji are often followed by a variation selector
+    let mut chars = words[i].trim_end_matches('\u{fe0f}').chars();
+    let (signifier, None) = (chars.next()?, chars.next()) else { return None };
+    if let Some(level) = PRIORITIES.iter().position(|&p| p == signifier) {
+        return Some((Marker::Importance(level as u8 + 1), 1));
+    }
+    let next = words.get(i + 1)?;
+    let date = dates::parse_iso(next);
+    let marker = match signifier {
+        DUE => Marker::Deadline(date?),
+        SCHEDULED => Marker::Reminder(date?),
+        CREATED => Marker::Created(date?),
+        DONE | CANCELLED => Marker::Done(date?),
+        ID if ids::is_valid(next) => Marker::Id(next.to_string()),
+        RECURRING => {
+            let mut end = i + 1;
+            while end < words.len() && end - i <= MAX_RULE_WORDS && !words[end].starts_with(ESCAPABLE) {
+                end += 1;
+            }
+            return (i + 2..=end).rev().find_map(|phrase_end| {
+                let phrase = words[i + 1..phrase_end].join(" ");
+                Recurrence::parse_rule(&phrase, today()).map(|_| (Marker::Rule(phrase), phrase_end - i))
+            });
+        }
+        _ => return None,
+    };
+    Some((marker, 2))
+}
+
+/// `@every monday` at `words[i]`, or `@"every monday"`.
+fn repeat_phrase(words: &[&str], i: usize) -> Option<(Recurrence, usize)> {
+    let quoted = words[i][1..].strip_prefix('"').and_then(|rest| rest.strip_suffix('"'));
+    if let Some(phrase) = quoted {
+        let (every, when) = phrase.split_once(' ')?;
+        return (every == "every").then(|| Recurrence::parse(when.trim())).flatten().map(|r| (r, 1));
+    }
+    (words[i] == "@every").then(|| Recurrence::parse(words.get(i + 1)?)).flatten().map(|r| (r, 2))
+}
+
+/// A tag or context that can be written as `#tag`, other ones are quoted: `#"to read"`.
+fn is_plain_tag(tag: &str) -> bool {
+    !tag.is_empty() && tag.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// The date after the `!` or `@` of `words[i]`: a quoted phrase, or the longest run
+/// of following words that reads as a date. Words that could start a marker end the run.
+fn date_phrase(words: &[&str], i: usize, today: &dyn Fn() -> NaiveDate) -> Option<(NaiveDate, usize)> {
+    let first = &words[i][1..];
+    if let Some(quoted) = first.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        return parse_date_phrase(quoted, today).map(|date| (date, 1));
+    }
+    if !first.starts_with(|c: char| c.is_alphanumeric()) {
+        return None;
+    }
+
+    let mut end = i + 1;
+    while end < words.len()
+        && end - i < MAX_DATE_WORDS
+        && !words[end].starts_with(ESCAPABLE)
+    {
+        end += 1;
+    }
+    (i + 1..=end).rev().find_map(|phrase_end| {
+        let phrase = std::iter::once(first).chain(words[i + 1..phrase_end].iter().copied()).collect::<Vec<_>>();
+        parse_date_phrase(&phrase.join(" "), today).map(|date| (date, phrase_end - i))
+    })
+}
+
+fn parse_date_phrase(phrase: &str, today: &dyn Fn() -> NaiveDate) -> Option<NaiveDate> {
+    // Most are dates already
+    dates::parse_iso(phrase).or_else(|| dates::parse(phrase, today(), dates::dialect()))
+}
+
+/// Reads `2h`, `45m` or `1h30m`, whole minutes only; zero isn't an estimate.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let number = |s: &str| {
+        (!s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())).then(|| s.parse::<u64>().ok()).flatten()
+    };
+    let (hours, rest) = match text.split_once('h') {
+        Some((hours, rest)) => (number(hours)?, rest),
+        None => (0, text),
+    };
+    let minutes = match rest {
+        "" => 0,
+        _ => number(rest.strip_suffix('m')?)?,
+    };
+    let total = hours.checked_mul(60)?.checked_add(minutes)?;
+    (total > 0).then_some(Duration::from_secs(total.checked_mul(60)?))
+}
+
+/// The other way round: `2h`, `45m`, `1h30m`. Seconds are dropped.
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{}m", h, m),
+    }
+}
+
+/// Puts a backslash in front of every backslash that would otherwise escape the character after it.
+fn escape_backslashes(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek().is_some_and(|next| ESCAPABLE.contains(next)) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Escapes a single word of text so it reads back as itself: a leading backslash
+/// when it would be a marker (`special`), and one in front of a quote that would open a span.
+fn escape_word(word: &str, special: bool) -> String {
+    let mut out = String::new();
+    let mut in_head = true;
+    let mut chars = word.chars().peekable();
+    while let Some(c) = chars.next() {
+        let escapes_next = c == '\\' && chars.peek().is_some_and(|next| ESCAPABLE.contains(next));
+        if escapes_next || out.is_empty() && special || in_head && (c == '"' || c == '`') {
+            out.push('\\');
+            in_head = false;
+        }
+        in_head &= SIGILS.contains(&c);
+        out.push(c);
+    }
+    out
+}
+
+/// The inverse of what `Task::parse` does to the text and notes of a task: escapes
+/// whatever would be read as a marker, a span or an escape. Quoted spans that mean
+/// nothing special are written as they are.
+fn escape_words(text: &str) -> String {
+    // Split up the spans that need escaping, a backslash in front would undo them
+    let mut pieces: Vec<(&str, Option<usize>)> = Vec::new();
+    // Whether a phrase reads as a date doesn't depend on the day
+    let today = OnceCell::new();
+    let today = || *today.get_or_init(|| SystemClock.today());
+    let words = split_words(text);
+    let raws: Vec<&str> = words.iter().map(|w| w.raw).collect();
+    for (i, word) in words.iter().enumerate() {
+        let special = marker_at(&raws, i, &today).is_some() || word.raw.starts_with("//");
+        match word.span_end {
+            Some(end) if !special => pieces.push((word.raw, Some(end))),
+            _ => pieces.extend(word.raw.split(' ').map(|piece| (piece, None))),
+        }
+    }
+
+    let raws: Vec<&str> = pieces.iter().map(|(raw, _)| *raw).collect();
+    pieces
+        .iter()
+        .enumerate()
+        .map(|(i, (raw, span_end))| match span_end {
+            Some(end) => format!("{}{}", &raw[..*end], escape_backslashes(&raw[*end..])),
+            None => {
+                let special = marker_at(&raws, i, &today).is_some() || raw.starts_with("//") || raw.starts_with("[attach:");
+                escape_word(raw, special)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn load_tasks_from_file(task_file: &Path) -> Vec<Task> {
+    match encryption::read(task_file) {
+        Ok(content) => parse_tasks(&content),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Parses the task lines of a tasks.md file, ignoring everything else
+/// but the `## ` headings that say which project the tasks below them are in.
+pub fn parse_tasks(content: &str) -> Vec<Task> {
+    parse_lines(content.lines().map(|line| (line, true)))
+}
+
+/// The open tasks of a tasks.md file, and the completed ones they are subtasks of. The
+/// other completed tasks aren't parsed at all, which makes it much faster on a file holding
+/// years of them.
+pub fn parse_open_tasks(content: &str) -> Vec<Task> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut wanted = vec![true; lines.len()];
+    // The task lines the one at hand may be nested under, with their indentation
+    let mut above: Vec<(usize, usize)> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let text = line.trim_start();
+        let open = match text.get(..5) {
+            Some("- [ ]") => true,
+            Some("- [x]" | "- [-]") => false,
+            _ => continue,
+        };
+        let indent = line.chars().take_while(|&c| c == ' ').count() / 2;
+        while above.last().is_some_and(|&(_, above)| above >= indent) {
+            above.pop();
+        }
+        if open {
+            for &(parent, _) in above.iter().rev() {
+                if std::mem::replace(&mut wanted[parent], true) {
+                    break;
+                }
+            }
+        }
+        wanted[index] = open;
+        above.push((index, indent));
+    }
+    parse_lines(lines.into_iter().zip(wanted))
+}
+
+/// The tasks of the lines paired with true. Lines the same as one above are counted
+/// whether they are parsed or not, so a task without an id gets the same one either way.
+fn parse_lines<'a>(lines: impl Iterator<Item = (&'a str, bool)>) -> Vec<Task> {
+    let mut project = None;
+    let mut tasks = Vec::new();
+    let mut seen = LinesSeen::default();
+    for (line, wanted) in lines {
+        if let Some(heading) = project_heading(line) {
+            project = Some(heading.to_string());
+            continue;
+        }
+        let before = seen.count(line);
+        if let Some(mut task) = parse_task_line_after(line, before).filter(|_| wanted) {
+            task.project = project.clone();
+            tasks.push(task);
+        }
+    }
+    subtasks::link_parents(tasks)
+}
+
+/// How many times each line came up so far.
+#[derive(Default)]
+pub struct LinesSeen<'a> {
+    seen: HashMap<&'a str, usize>,
+}
+
+impl<'a> LinesSeen<'a> {
+    /// How many times `line` came up before, counting it from now on.
+    pub fn count(&mut self, line: &'a str) -> usize {
+        let count = self.seen.entry(line.trim()).or_insert(0);
+        *count += 1;
+        *count - 1
+    }
+}
+
+/// The project a `## Project` heading starts. Other heading levels don't start one.
+pub fn project_heading(line: &str) -> Option<&str> {
+    line.strip_prefix("## ").map(str::trim).filter(|name| !name.is_empty())
+}
+
+/// The task on a line of tasks.md, None for headings, notes and everything else.
+pub fn parse_task_line(line: &str) -> Option<Task> {
+    parse_task_line_after(line, 0)
+}
+
+/// `parse_task_line` for a line the same as `before` lines above it, which a task without
+/// an id tells apart by, see `ids::from_line`.
+pub fn parse_task_line_after(line: &str, before: usize) -> Option<Task> {
+    // Count leading spaces to determine indentation level
+    let indent_level = line.chars().take_while(|&c| c == ' ').count() / 2;
+    let mut task = checkbox_line(line.trim_start(), before)?;
+    task.indent_level = indent_level;
+    Some(task)
+}
+
+/// A task from a line starting with its checkbox: `- [ ]` open, `- [x]` completed or
+/// `- [-]` cancelled.
+pub fn parse_checkbox_line(line: &str) -> Option<Task> {
+    checkbox_line(line, 0)
+}
+
+fn checkbox_line(line: &str, before: usize) -> Option<Task> {
+    let (completed, cancelled) = match line.get(..5)? {
+        "- [ ]" => (false, false),
+        "- [x]" => (true, false),
+        "- [-]" => (true, true),
+        _ => return None,
+    };
+    let task_text = line[5..].strip_prefix(' ').unwrap_or(line);
+
+    let mut task = Task::parse_markers(task_text, &SystemClock);
+    if task.id.is_empty() {
+        task.id = ids::from_line(line.trim_end(), before);
+    }
+    task.completed = completed;
+    task.cancelled = cancelled;
+    Some(task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_open_tasks_keep_their_completed_parents() {
+        let content = "# tasks\n\n\
+            - [x] old trip [id:a1]\n\
+            \x20 - [x] book flights [id:b2]\n\
+            ## Work\n\
+            - [x] report [id:c3]\n\
+            \x20 - [-] charts [id:d4]\n\
+            \x20   - [ ] legend [id:e5]\n\
+            \x20 - [x] tables [id:f6]\n\
+            - [ ] call bob [id:g7]\n";
+
+        let open = parse_open_tasks(content);
+        let ids: Vec<&str> = open.iter().map(|task| task.id.as_str()).collect();
+        assert_eq!(ids, ["c3", "d4", "e5", "g7"]);
+        let all = parse_tasks(content);
+        for task in &open {
+            assert!(all.contains(task));
+        }
+    }
+
+    #[test]
+    fn test_tasks_without_ids_keep_theirs_between_reads() {
+        let content = "- [ ] water plants\n- [x] old plan\n  - [ ] water plants\n- [ ] water plants\n- [ ] pay rent [id:aaaa01]\n";
+        let ids = |tasks: Vec<Task>| -> Vec<String> { tasks.into_iter().map(|task| task.id).collect() };
+        let first = ids(parse_tasks(content));
+        assert_eq!(first, ids(parse_tasks(content)));
+        assert_eq!(first[4], "aaaa01");
+        // The same line again is another task, with another id
+        let unique: std::collections::HashSet<&String> = first.iter().collect();
+        assert_eq!(unique.len(), 5);
+        assert!(first.iter().all(|id| ids::is_valid(id)));
+        // Reading only the open tasks gives them the same ids
+        let open = ids(parse_open_tasks(content));
+        assert!(open.iter().all(|id| first.contains(id)));
+        // A task typed in gets a new one
+        assert_ne!(Task::parse("water plants").id, Task::parse("water plants").id);
+    }
+
+    #[test]
+    fn test_tasks_md_round_trip() {
+        let tasks_markdown = |tasks: &[Task]| crate::document::Document::parse("").render(tasks);
+
+        let content = "# tasks\n\n\
+            - [ ] plan trip !2025-07-01 #travel $2 [id:a1] [created:2025-06-01]\n\
+            \x20 - [x] book flights [id:b2] [done:2025-06-03]\n\
+            - [ ] call mum [id:c3] //about sunday\n";
+
+        let tasks = parse_tasks(content);
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[0].importance, Some(2));
+        assert_eq!(tasks[0].tags, ["travel"]);
+        assert_eq!(tasks[1].parent_id.as_deref(), Some("a1"));
+        assert!(tasks[1].completed);
+        assert_eq!(tasks[2].notes.as_deref(), Some("about sunday"));
+
+        let written = tasks_markdown(&tasks);
+        let reparsed = parse_tasks(&written);
+        assert_eq!(tasks_markdown(&reparsed), written);
+        assert_eq!(reparsed[1].done, NaiveDate::from_ymd_opt(2025, 6, 3));
+
+        let mut dropped = parse_task_line("- [-] old plan [id:d4] [done:2025-06-02]").unwrap();
+        assert!(dropped.cancelled && dropped.completed);
+        assert_eq!(dropped.to_markdown(), "- [-] old plan [id:d4] [done:2025-06-02]");
+        dropped.set_completed(false, NaiveDate::from_ymd_opt(2025, 6, 4).unwrap());
+        assert_eq!(dropped.to_markdown(), "- [ ] old plan [id:d4]");
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(y, m, d)
+    }
+
+    #[test]
+    fn test_markers() {
+        let task = Task::parse(
+            "plan trip !2025-07-01 #travel #far-away @2025-06-20 [id:a1b2] [attach:my ticket.pdf] [created:2025-06-01] //book early $4",
+        );
+        assert_eq!(task.text, "plan trip");
+        assert_eq!(task.id, "a1b2");
+        assert_eq!(task.deadline, date(2025, 7, 1));
+        assert_eq!(task.reminder, date(2025, 6, 20));
+        assert_eq!(task.tags, ["travel", "far-away"]);
+        assert_eq!(task.attachments, ["my ticket.pdf"]);
+        assert_eq!(task.created, date(2025, 6, 1));
+        assert_eq!(task.notes.as_deref(), Some("book early"));
+        assert_eq!(task.importance, Some(4));
+
+        let task = Task::parse(r#"buy stamps >errands >"post office" #home a > b"#);
+        assert_eq!(task.contexts, ["errands", "post office"]);
+        assert_eq!(task.tags, ["home"]);
+        assert_eq!(task.text, "buy stamps a > b");
+
+        let task = Task::parse("call mom [pin] [flag:🔥] about [flag:]");
+        assert!(task.pinned);
+        assert_eq!(task.flag.as_deref(), Some("🔥"));
+        assert_eq!(task.text, "call mom about [flag:]");
+
+        let today = NaiveDate::from_ymd_opt(2025, 6, 13).unwrap();
+        let mut task = Task::parse("pack boxes [count:8/10] and [count:3/0] [count:11/10]");
+        assert_eq!(task.text, "pack boxes and [count:3/0] [count:11/10]");
+        assert!(task.bump(1, today) && !task.completed);
+        assert!(task.bump(5, today) && task.completed);
+        assert_eq!((task.count, task.done), (Some((10, 10)), Some(today)));
+        task.bump(-1, today);
+        assert_eq!((task.count, task.completed), (Some((9, 10)), false));
+        assert!(!Task::parse("no counter").bump(1, today));
+
+        let task = Task::parse("file taxes !2025-07-31 [warn:2w] [warn:3]");
+        assert_eq!((task.warn, task.text.as_str()), (Some(14), "file taxes [warn:3]"));
+        assert_eq!(task.warning(), date(2025, 7, 17));
+        assert_eq!(task.to_markdown(), format!("- [ ] file taxes [warn:3] [id:{}] !2025-07-31 [warn:14d]", task.id));
+        assert_eq!(Task::parse("file taxes !2025-07-31 [warn:0d]").warning(), None);
+
+        // A Friday
+        let clock = FixedClock::on(NaiveDate::from_ymd_opt(2025, 6, 13).unwrap());
+        let parse = |line: &str| Task::parse_at(line, &clock);
+        let task = parse("water plants !tomorrow @today and more");
+        assert_eq!(task.deadline, date(2025, 6, 14));
+        assert_eq!(task.reminder, date(2025, 6, 13));
+        assert_eq!(task.text, "water plants and more");
+
+        let task = parse(r#"dentist !"next friday" #health"#);
+        assert_eq!(task.deadline, date(2025, 6, 20));
+        assert_eq!(task.text, "dentist");
+        assert_eq!(parse("dentist !next friday #health").deadline, task.deadline);
+
+        let task = parse("pay rent !end of month #home");
+        assert_eq!(task.deadline, date(2025, 6, 30));
+        assert_eq!(task.text, "pay rent");
+
+        let minutes = |m: u64| Some(Duration::from_secs(m * 60));
+        let task = Task::parse("write report ~1h30m [spent:45m] about ~5 things");
+        assert_eq!((task.estimate, task.spent), (minutes(90), minutes(45)));
+        assert_eq!(task.text, "write report about ~5 things");
+        assert_eq!(Task::parse("nap ~90m").estimate, minutes(90));
+        assert!(["~0m", "~h", "~+2h", "~2h30", "~2d"].iter().all(|e| parse_duration(&e[1..]).is_none()));
+    }
+
+    #[test]
+    fn test_nasty_inputs_stay_text() {
+        let cases = [
+            "read https://example.com/a//b#section",
+            "mail bob@example.com about C# and F#",
+            "fix `#include <stdio.h>` in main.c",
+            "fix `a // b @c #d` quickly",
+            r#"say "hello #world @home // now" twice"#,
+            "costs $300, or $6",
+            "wow!!! @ # $",
+            "link to [[id:3fa1aaaa]] stays",
+            "!important thing @someone",
+            r"C:\Users\me and \tmp\x",
+            "6\" screen, it's \"odd",
+        ];
+        for case in cases {
+            let task = Task::parse(case);
+            assert_eq!(task.text, case.split_whitespace().collect::<Vec<_>>().join(" "), "{}", case);
+        }
+
+        let task = Task::parse("read https://example.com //see https://docs.rs #rust");
+        assert_eq!(task.text, "read https://example.com");
+        assert_eq!(task.notes.as_deref(), Some("see https://docs.rs"));
+        assert_eq!(task.tags, ["rust"]);
+    }
+
+    #[test]
+    fn test_escaping_round_trips() {
+        let task = Task::parse(r"ticket \#42 \$3 \!tomorrow \//not-a-note \\#x //note with \#hash \@today");
+        assert_eq!(task.text, r"ticket #42 $3 !tomorrow //not-a-note \#x");
+        assert_eq!(task.notes.as_deref(), Some("note with #hash @today"));
+        assert!(task.tags.is_empty() && task.importance.is_none() && task.deadline.is_none());
+
+        let mut odd = Task::parse("x");
+        odd.text = r#"#tag $2 "open quote //slashes [id:abc] @next friday \[x]"#.to_string();
+        odd.notes = Some("\"unclosed #tag".to_string());
+        let reparsed = Task::parse(&odd.to_markdown()[6..]);
+        assert_eq!(reparsed.text, odd.text);
+        assert_eq!(reparsed.notes, odd.notes);
+        assert_eq!(reparsed.id, odd.id);
+        assert!(reparsed.tags.is_empty() && reparsed.reminder.is_none());
+    }
+
+    #[test]
+    fn test_obsidian_format() {
+        let line = "- [ ] water plants #garden ⏫ 🔁 every week ➕ 2025-06-01 📅 2025-07-01 🆔 abc123";
+        let task = parse_task_line(line).unwrap();
+        assert_eq!((task.text.as_str(), task.id.as_str()), ("water plants", "abc123"));
+        assert_eq!((task.deadline, task.created, task.importance), (date(2025, 7, 1), date(2025, 6, 1), Some(2)));
+        // 2025-07-01 is a Tuesday
+        assert_eq!(task.repeat, Some(Recurrence::Weekly(chrono::Weekday::Tue)));
+        assert_eq!(task.tags, ["garden"]);
+        assert_eq!(
+            task.to_markdown_as(TaskFormat::Obsidian),
+            "- [ ] water plants #garden 🆔 abc123 ⏫ 🔁 every week on Tuesday ➕ 2025-06-01 📅 2025-07-01"
+        );
+        assert_eq!(
+            task.to_markdown_as(TaskFormat::Yarmtl),
+            "- [ ] water plants [id:abc123] !2025-07-01 #garden @every tuesday [created:2025-06-01] $2"
+        );
+
+        let task = parse_task_line("- [-] old plan 🔁 every month on the 15th ❌ 2025-06-02 and \\📅 2025-01-01").unwrap();
+        assert_eq!((task.repeat, task.done), (Some(Recurrence::Monthly(15)), date(2025, 6, 2)));
+        assert_eq!(task.text, "old plan and 📅 2025-01-01");
+        assert!(task.to_markdown_as(TaskFormat::Obsidian).contains("\\📅 2025-01-01 🆔"));
+        assert!(task.to_markdown_as(TaskFormat::Obsidian).ends_with("🔁 every month on the 15th ❌ 2025-06-02"));
+    }
+
+    fn date_strategy() -> impl Strategy<Value = NaiveDate> {
+        (2000i32..2100, 1u32..=12, 1u32..=28).prop_map(|(y, m, d)| NaiveDate::from_ymd_opt(y, m, d).unwrap())
+    }
+
+    /// Single-spaced words full of marker characters.
+    fn words_strategy() -> impl Strategy<Value = String> {
+        prop::collection::vec(r#"[a-z0-9#@!$/"`\\\[\]:.-]{1,8}"#, 0..6).prop_map(|words| words.join(" "))
+    }
+
+    prop_compose! {
+        fn task_strategy()(
+            id in "[0-9a-hjkmnp-tv-z-]{1,36}",
+            text in words_strategy(),
+            deadline in prop::option::of(date_strategy()),
+            tags in prop::collection::vec("[a-z0-9_ -]{1,10}", 0..3),
+            contexts in prop::collection::vec("[a-z0-9_ -]{1,10}", 0..2),
+            reminder in prop::option::of(date_strategy()),
+            repeat in prop::option::of(prop_oneof![
+                Just(Recurrence::Daily),
+                Just(Recurrence::Weekdays),
+                (0u8..7).prop_map(|d| Recurrence::Weekly(chrono::Weekday::try_from(d).unwrap())),
+                (1u32..=31).prop_map(Recurrence::Monthly),
+            ]),
+            last_fired in prop::option::of(date_strategy()),
+            completed in any::<bool>(),
+            cancelled in any::<bool>(),
+            notes in words_strategy(),
+            importance in prop::option::of(1u8..=5),
+            attachments in prop::collection::vec(r##"[a-zA-Z0-9 "#@._-]{1,12}"##, 0..2),
+            created in prop::option::of(date_strategy()),
+            done in prop::option::of(date_strategy()),
+            estimate in prop::option::of(1u64..10_000),
+            spent in prop::option::of(1u64..10_000),
+            pinned in any::<bool>(),
+            flag in prop::option::of("[a-z0-9🔥⭐#!-]{1,6}"),
+            count in prop::option::of((1u32..100).prop_flat_map(|target| (0..=target, Just(target)))),
+            warn in prop::option::of(0u32..60),
+        ) -> Task {
+            Task {
+                id,
+                text,
+                deadline,
+                tags,
+                contexts,
+                reminder,
+                repeat,
+                last_fired,
+                completed: completed || cancelled,
+                cancelled,
+                notes: Some(notes).filter(|n| !n.is_empty()),
+                importance,
+                indent_level: 0,
+                parent_id: None,
+                project: None,
+                source: None,
+                attachments,
+                created,
+                done,
+                estimate: estimate.map(|minutes| Duration::from_secs(minutes * 60)),
+                spent: spent.map(|minutes| Duration::from_secs(minutes * 60)),
+                pinned,
+                flag,
+                count,
+                warn,
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_task_survives_a_round_trip(task in task_strategy()) {
+            let line = task.to_markdown();
+            prop_assert_eq!(parse_tasks(&line), vec![task], "{}", line);
+        }
+
+        #[test]
+        fn prop_obsidian_lines_round_trip(task in task_strategy()) {
+            let line = task.to_markdown_as(TaskFormat::Obsidian);
+            prop_assert_eq!(parse_tasks(&line), vec![task], "{}", line);
+        }
+
+        #[test]
+        fn prop_parsing_is_stable(input in r#"[a-z0-9 \t#@!$~>/"`\\\[\]:.é📅🔁⏫-]{0,40}"#) {
+            let task = Task::parse(&input);
+            let line = task.to_markdown();
+            prop_assert_eq!(parse_tasks(&line), vec![task], "{}", line);
+        }
+    }
+}
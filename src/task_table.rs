@@ -0,0 +1,78 @@
+use crate::dates;
+use chrono::NaiveDate;
+use ratatui::layout::Constraint;
+
+/// A column of the table layout of the task list (`layout = "table"` in `[ui]`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Checkbox,
+    Priority,
+    Text,
+    Due,
+    Tags,
+}
+
+/// Below this width the tags column is dropped, below `MIN_WIDTH_PRIORITY` the priority one too,
+/// so the task text always keeps a usable amount of room.
+const MIN_WIDTH_TAGS: u16 = 70;
+const MIN_WIDTH_PRIORITY: u16 = 45;
+
+impl Column {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Checkbox => "",
+            Column::Priority => "Pri",
+            Column::Text => "Task",
+            Column::Due => "Due",
+            Column::Tags => "Tags",
+        }
+    }
+
+    /// The column's width; the text column takes whatever is left.
+    fn constraint(&self, width: u16) -> Constraint {
+        match self {
+            Column::Checkbox => Constraint::Length(2),
+            Column::Priority => Constraint::Length(3),
+            Column::Text => Constraint::Min(10),
+            // "⚠ " and the longest date in the short format
+            Column::Due => {
+                let longest = NaiveDate::from_ymd_opt(2000, 9, 30).map_or(5, |date| dates::display().short(date).chars().count());
+                Constraint::Length((longest as u16 + 2).max(7))
+            }
+            Column::Tags => Constraint::Length((width / 5).clamp(10, 30)),
+        }
+    }
+}
+
+/// The columns that fit in `width` terminal cells, in display order, with their widths.
+pub fn columns_for_width(width: u16) -> Vec<(Column, Constraint)> {
+    [Column::Checkbox, Column::Priority, Column::Text, Column::Due, Column::Tags]
+        .into_iter()
+        .filter(|column| match column {
+            Column::Priority => width >= MIN_WIDTH_PRIORITY,
+            Column::Tags => width >= MIN_WIDTH_TAGS,
+            _ => true,
+        })
+        .map(|column| (column, column.constraint(width)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns_adapt_to_width() {
+        let names = |width| columns_for_width(width).into_iter().map(|(c, _)| c).collect::<Vec<_>>();
+
+        assert_eq!(
+            names(120),
+            [Column::Checkbox, Column::Priority, Column::Text, Column::Due, Column::Tags]
+        );
+        assert_eq!(names(60), [Column::Checkbox, Column::Priority, Column::Text, Column::Due]);
+        assert_eq!(names(30), [Column::Checkbox, Column::Text, Column::Due]);
+
+        let tags = columns_for_width(200).into_iter().find(|(c, _)| *c == Column::Tags).unwrap();
+        assert_eq!(tags.1, Constraint::Length(30));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::ids::{self, IdMap};
+use crate::sync_engine::CONTEXT_LABEL_PREFIX;
+use crate::task::Task;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Taskwarrior's timestamps, always in UTC.
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// UUIDs of tasks from yarmtl start with this ("yarmtl" in hex, version 8) and end in
+/// their id, six bits a character, so they turn back into it on import.
+const UUID_PREFIX: &str = "7961726d-746c-8000-8000-";
+
+/// A task as `task export` writes it and `task import` reads it. Attributes yarmtl has
+/// no place for are dropped on import.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TwTask {
+    pub uuid: String,
+    pub description: String,
+    /// "pending", "completed", "deleted", "waiting" or "recurring"
+    #[serde(default = "pending")]
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// "H", "M" or "L"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub entry: String,
+    pub description: String,
+}
+
+fn pending() -> String {
+    "pending".to_string()
+}
+
+/// How many tasks an import added and changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+}
+
+/// Every task, completed and cancelled ones too, as a JSON array like `task export`'s.
+/// Cancelled tasks are deleted ones, contexts are tags with a `ctx_` prefix and notes
+/// are an annotation.
+pub fn export(tasks: &[Task], id_map: &IdMap) -> String {
+    let today = Local::now().date_naive();
+    let lines: Vec<String> = tasks
+        .iter()
+        .map(|task| serde_json::to_string(&to_taskwarrior(task, id_map, today)).expect("tasks serialize"))
+        .collect();
+    format!("[\n{}\n]\n", lines.join(",\n"))
+}
+
+fn to_taskwarrior(task: &Task, id_map: &IdMap, today: NaiveDate) -> TwTask {
+    let entry = timestamp(task.created.unwrap_or(today));
+    let status = match (task.cancelled, task.completed) {
+        (true, _) => "deleted",
+        (false, true) => "completed",
+        (false, false) => "pending",
+    };
+    let tags = task
+        .tags
+        .iter()
+        .cloned()
+        .chain(task.contexts.iter().map(|context| format!("{}{}", CONTEXT_LABEL_PREFIX, context)))
+        .collect();
+    TwTask {
+        uuid: uuid_for(&task.id, id_map),
+        description: task.text.clone(),
+        status: status.to_string(),
+        end: task.completed.then(|| timestamp(task.done.unwrap_or(today))),
+        due: task.deadline.map(timestamp),
+        project: task.project.clone(),
+        priority: match task.importance {
+            Some(1) => Some("H".to_string()),
+            Some(2) => Some("M".to_string()),
+            Some(3) => Some("L".to_string()),
+            _ => None,
+        },
+        tags,
+        annotations: task
+            .notes
+            .iter()
+            .map(|notes| Annotation { entry: entry.clone(), description: notes.clone() })
+            .collect(),
+        entry: Some(entry),
+    }
+}
+
+/// Reads what `task export` writes, a JSON array, or one task a line like Taskwarrior's
+/// hooks get them.
+pub fn parse(input: &str) -> Result<Vec<TwTask>, serde_json::Error> {
+    if input.trim_start().starts_with('[') {
+        return serde_json::from_str(input);
+    }
+    input.lines().filter(|line| !line.trim().is_empty()).map(serde_json::from_str).collect()
+}
+
+/// Adds the Taskwarrior tasks to `tasks`, or updates the ones that came from there or
+/// were exported before. Tasks new from Taskwarrior get a short id, and their UUID is
+/// recorded in `id_map` so importing them again finds them.
+pub fn import(tasks: &mut Vec<Task>, imported: &[TwTask], id_map: &mut IdMap, today: NaiveDate) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+    for tw in imported {
+        let id = match id_from_uuid(&tw.uuid, id_map) {
+            Some(id) => id,
+            None => {
+                let taken: HashSet<String> = tasks.iter().map(|t| t.id.clone()).collect();
+                let id = ids::new_id(&taken);
+                id_map.renamed.insert(tw.uuid.clone(), id.clone());
+                id
+            }
+        };
+        match tasks.iter_mut().find(|t| t.id == id) {
+            Some(task) => {
+                let before = task.clone();
+                apply(task, tw, today);
+                if *task != before {
+                    summary.updated += 1;
+                }
+            }
+            None => {
+                let mut task = Task::parse("");
+                task.id = id;
+                task.created = tw.entry.as_deref().and_then(parse_timestamp).or(Some(today));
+                apply(&mut task, tw, today);
+                tasks.push(task);
+                summary.added += 1;
+            }
+        }
+    }
+    summary
+}
+
+/// What Taskwarrior carries, written over the task.
+fn apply(task: &mut Task, tw: &TwTask, today: NaiveDate) {
+    task.text = tw.description.split_whitespace().collect::<Vec<_>>().join(" ");
+    task.deadline = tw.due.as_deref().and_then(parse_timestamp);
+    task.project = tw.project.clone().filter(|p| !p.is_empty());
+    let (contexts, tags): (Vec<&String>, Vec<&String>) =
+        tw.tags.iter().partition(|tag| tag.starts_with(CONTEXT_LABEL_PREFIX) && tag.len() > CONTEXT_LABEL_PREFIX.len());
+    task.tags = tags.into_iter().cloned().collect();
+    task.contexts = contexts.into_iter().map(|c| c[CONTEXT_LABEL_PREFIX.len()..].to_string()).collect();
+    // Taskwarrior has three priorities; $4 and $5 stay as long as it has none
+    task.importance = match tw.priority.as_deref() {
+        Some("H") => Some(1),
+        Some("M") => Some(2),
+        Some("L") => Some(3),
+        _ => task.importance.filter(|i| *i > 3),
+    };
+    let notes: Vec<&str> = tw.annotations.iter().map(|a| a.description.as_str()).collect();
+    task.notes = Some(notes.join(" ").split_whitespace().collect::<Vec<_>>().join(" ")).filter(|n| !n.is_empty());
+
+    let ended = tw.end.as_deref().and_then(parse_timestamp).unwrap_or(today);
+    match tw.status.as_str() {
+        "completed" if !task.completed || task.cancelled => {
+            task.cancelled = false;
+            task.set_completed(true, ended);
+        }
+        "deleted" if !task.cancelled => task.set_cancelled(true, ended),
+        "completed" | "deleted" => {}
+        _ => task.set_completed(false, today),
+    }
+}
+
+/// The UUID a task is exported with: the one it came from Taskwarrior with, or one
+/// made from its id.
+fn uuid_for(id: &str, id_map: &IdMap) -> String {
+    let imported = id_map
+        .renamed
+        .iter()
+        .find(|(uuid, new)| *new == id && uuid::Uuid::parse_str(uuid).is_ok());
+    if let Some((uuid, _)) = imported {
+        return uuid.clone();
+    }
+    let mut bits: u64 = 0;
+    for (i, c) in id.bytes().take(8).enumerate() {
+        let symbol = match ids::ALPHABET.iter().position(|&a| a == c) {
+            Some(position) => position as u64 + 1,
+            None => 33,
+        };
+        bits |= symbol << (42 - 6 * i);
+    }
+    format!("{}{:012x}", UUID_PREFIX, bits)
+}
+
+/// The id of the task a UUID stands for, None for one yarmtl hasn't seen.
+fn id_from_uuid(uuid: &str, id_map: &IdMap) -> Option<String> {
+    if let Some(id) = id_map.renamed.get(uuid) {
+        return Some(id.clone());
+    }
+    let bits = u64::from_str_radix(uuid.strip_prefix(UUID_PREFIX)?, 16).ok()?;
+    let id: String = (0..8)
+        .map(|i| (bits >> (42 - 6 * i)) & 0x3f)
+        .take_while(|symbol| *symbol != 0)
+        .map(|symbol| match symbol {
+            33 => Some('-'),
+            symbol => ids::ALPHABET.get(symbol as usize - 1).map(|&c| c as char),
+        })
+        .collect::<Option<_>>()?;
+    Some(id).filter(|id| ids::is_valid(id))
+}
+
+/// Local midnight of the day, in UTC.
+fn timestamp(day: NaiveDate) -> String {
+    let midnight = day.and_time(NaiveTime::MIN);
+    let utc = Local.from_local_datetime(&midnight).earliest().map_or(midnight.and_utc(), |t| t.with_timezone(&Utc));
+    utc.format(TIMESTAMP_FORMAT).to_string()
+}
+
+/// The local day of a Taskwarrior timestamp, or of an ISO 8601 one like some scripts write.
+fn parse_timestamp(value: &str) -> Option<NaiveDate> {
+    let utc = NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT)
+        .map(|t| t.and_utc())
+        .or_else(|_| DateTime::parse_from_rfc3339(value).map(|t| t.with_timezone(&Utc)))
+        .ok()?;
+    Some(utc.with_timezone(&Local).date_naive())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exported_tasks_import_back() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        let mut tasks = vec![
+            Task::parse("call bob #work >phone !2025-06-10 $2 // ask about the report [id:abc123]"),
+            Task::parse("odd one $5 [id:x-1]"),
+        ];
+        tasks[0].set_completed(true, today);
+        let id_map = IdMap::default();
+        let exported = parse(&export(&tasks, &id_map)).unwrap();
+        assert_eq!(exported[0].uuid, "7961726d-746c-8000-8000-2cc3420c4000");
+        assert!(uuid::Uuid::parse_str(&exported[1].uuid).is_ok());
+        assert_eq!(exported[0].tags, ["work", "ctx_phone"]);
+        assert_eq!((exported[0].status.as_str(), exported[0].priority.as_deref()), ("completed", Some("M")));
+        assert_eq!(exported[1].priority, None);
+
+        let mut imported = tasks.clone();
+        let mut id_map = IdMap::default();
+        assert_eq!(import(&mut imported, &exported, &mut id_map, today), ImportSummary::default());
+        assert_eq!(imported, tasks);
+        assert!(id_map.renamed.is_empty());
+    }
+
+    #[test]
+    fn test_taskwarrior_tasks_are_added_once() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        let line = r#"{"uuid":"5f0c8a1e-2d6b-4c57-9d2e-3a8b6f1e7c90","description":"renew passport","status":"pending","entry":"20250520T080000Z","due":"20250701T120000Z","project":"Home","tags":["errand","ctx_town"],"priority":"H","urgency":9.2}"#;
+        let mut tasks = Vec::new();
+        let mut id_map = IdMap::default();
+        let summary = import(&mut tasks, &parse(line).unwrap(), &mut id_map, today);
+        assert_eq!(summary, ImportSummary { added: 1, updated: 0 });
+        let task = &tasks[0];
+        assert_eq!((task.text.as_str(), task.project.as_deref(), task.importance), ("renew passport", Some("Home"), Some(1)));
+        assert_eq!((&task.tags, &task.contexts), (&vec!["errand".to_string()], &vec!["town".to_string()]));
+        assert_eq!(task.deadline, NaiveDate::from_ymd_opt(2025, 7, 1));
+        assert_eq!(uuid_for(&task.id, &id_map), "5f0c8a1e-2d6b-4c57-9d2e-3a8b6f1e7c90");
+
+        let done = line.replace(r#""status":"pending""#, r#""status":"completed","end":"20250602T090000Z""#);
+        let summary = import(&mut tasks, &parse(&done).unwrap(), &mut id_map, today);
+        assert_eq!(summary, ImportSummary { added: 0, updated: 1 });
+        assert!(tasks[0].completed);
+        assert_eq!(tasks[0].done, NaiveDate::from_ymd_opt(2025, 6, 2));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::server::{self, NewTask};
+use crate::task::Task;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const API_URL: &str = "https://api.telegram.org";
+
+/// How long a getUpdates call waits for a message before coming back empty.
+const POLL_SECONDS: u64 = 50;
+
+const HELP: &str = "Send me a task to add it to tasks.md, with the usual markers: \
+                    buy milk !tomorrow #home >shop @friday $2 //the oat one";
+
+/// The Bot API of one bot, the token BotFather gave it.
+pub struct Bot {
+    client: Client,
+    token: String,
+}
+
+impl Bot {
+    pub fn new(token: &str) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(POLL_SECONDS + 10))
+            .user_agent(concat!("yarmtl/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("Failed to create HTTP client");
+        Bot { client, token: token.to_string() }
+    }
+
+    async fn call(&self, method: &str, body: Value) -> Result<Value, String> {
+        let url = format!("{}/bot{}/{}", API_URL, self.token, method);
+        let reply: Value = self
+            .client
+            .post(url)
+            .json(&body)
+            .send()
+            .await
+            // The error holds the URL, and with it the token
+            .map_err(|e| format!("telegram {} failed: {}", method, e.without_url()))?
+            .json()
+            .await
+            .map_err(|e| format!("telegram {} failed: {}", method, e.without_url()))?;
+        match reply["ok"].as_bool() {
+            Some(true) => Ok(reply["result"].clone()),
+            _ => Err(format!("telegram {} failed: {}", method, reply["description"].as_str().unwrap_or("no reason given"))),
+        }
+    }
+
+    pub async fn send_message(&self, chat_id: i64, text: &str) -> Result<(), String> {
+        self.call("sendMessage", json!({ "chat_id": chat_id, "text": text })).await.map(|_| ())
+    }
+
+    /// Answers the messages of `chat_id` until the process is stopped; anyone else is ignored.
+    /// Fails only when the token is no good.
+    pub async fn run(&self, chat_id: i64) -> Result<(), String> {
+        let me = self.call("getMe", json!({})).await?;
+        println!("🤖 @{} adds the tasks chat {} sends it, Ctrl+C to stop", me["username"].as_str().unwrap_or("bot"), chat_id);
+        let mut offset = 0;
+        loop {
+            let updates = match self.call("getUpdates", json!({ "offset": offset, "timeout": POLL_SECONDS })).await {
+                Ok(updates) => updates,
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    continue;
+                }
+            };
+            let (next, messages) = messages(&updates, chat_id);
+            offset = next.max(offset);
+            for text in messages {
+                let answer = answer(&text);
+                if let Err(e) = self.send_message(chat_id, &answer).await {
+                    tracing::warn!("{}", e);
+                }
+            }
+        }
+    }
+}
+
+/// The offset to ask for the updates after these, and the texts `chat_id` sent in them.
+/// Other chats are told about on stderr, in case a chat id is wrong.
+fn messages(updates: &Value, chat_id: i64) -> (i64, Vec<String>) {
+    let mut next = 0;
+    let mut texts = Vec::new();
+    for update in updates.as_array().into_iter().flatten() {
+        next = next.max(update["update_id"].as_i64().unwrap_or_default() + 1);
+        let message = &update["message"];
+        let (Some(from), Some(text)) = (message["chat"]["id"].as_i64(), message["text"].as_str()) else { continue };
+        if from == chat_id {
+            texts.push(text.to_string());
+        } else {
+            tracing::debug!("ignoring a message from chat {}", from);
+        }
+    }
+    (next, texts)
+}
+
+/// What the bot says to a message: a task added, or help.
+fn answer(text: &str) -> String {
+    let text = text.trim();
+    if text.is_empty() || text.starts_with('/') {
+        return HELP.to_string();
+    }
+    match server::add(NewTask { text: text.to_string(), project: None }) {
+        Ok(task) => format!("✓ added {}", describe(&task)),
+        Err(e) => format!("❌ not added, {}", e),
+    }
+}
+
+fn describe(task: &Task) -> String {
+    let mut line = format!("\"{}\"", task.text);
+    if let Some(deadline) = task.deadline {
+        line.push_str(&format!(", due {}", deadline.format("%a %Y-%m-%d")));
+    }
+    if let Some(reminder) = task.reminder {
+        line.push_str(&format!(", reminder {}", reminder.format("%a %Y-%m-%d")));
+    }
+    for tag in &task.tags {
+        line.push_str(&format!(" #{}", tag));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_configured_chat() {
+        let updates = json!([
+            { "update_id": 7, "message": { "chat": { "id": 42 }, "text": "buy milk !tomorrow #home" } },
+            { "update_id": 8, "message": { "chat": { "id": 666 }, "text": "rm -rf" } },
+            { "update_id": 9, "message": { "chat": { "id": 42 }, "sticker": {} } },
+        ]);
+        assert_eq!(messages(&updates, 42), (10, vec!["buy milk !tomorrow #home".to_string()]));
+        assert_eq!(messages(&json!([]), 42), (0, Vec::new()));
+        assert_eq!(answer("/start"), HELP);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::config::ThemeSetting;
+use ratatui::style::Color;
+
+/// Colors used by the TUI, picked once at startup for the terminal's background.
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    pub accent: Color,
+    pub text: Color,
+    pub muted: Color,
+    pub success: Color,
+    pub danger: Color,
+    pub surface: Color,
+    /// Colors for importance $1 (most important) to $5
+    pub importance: [Color; 5],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Background {
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn dark() -> Theme {
+        Theme {
+            accent: Color::Rgb(255, 107, 138),
+            text: Color::White,
+            muted: Color::DarkGray,
+            success: Color::Green,
+            danger: Color::Red,
+            surface: Color::Black,
+            importance: [Color::Red, Color::LightRed, Color::Yellow, Color::LightBlue, Color::DarkGray],
+        }
+    }
+
+    pub fn light() -> Theme {
+        Theme {
+            accent: Color::Rgb(196, 38, 92),
+            text: Color::Black,
+            muted: Color::Rgb(110, 110, 110),
+            success: Color::Rgb(0, 128, 0),
+            danger: Color::Rgb(190, 0, 0),
+            surface: Color::Rgb(228, 228, 228),
+            importance: [
+                Color::Rgb(190, 0, 0),
+                Color::Rgb(200, 80, 0),
+                Color::Rgb(150, 110, 0),
+                Color::Rgb(0, 90, 180),
+                Color::Rgb(110, 110, 110),
+            ],
+        }
+    }
+
+    pub fn for_background(background: Background) -> Theme {
+        match background {
+            Background::Dark => Theme::dark(),
+            Background::Light => Theme::light(),
+        }
+    }
+
+    /// Resolves the configured theme, detecting the background when set to auto.
+    /// Must be called with the terminal in raw mode so the OSC 11 reply isn't echoed.
+    pub fn from_setting(setting: ThemeSetting) -> Theme {
+        match setting {
+            ThemeSetting::Dark => Theme::dark(),
+            ThemeSetting::Light => Theme::light(),
+            ThemeSetting::Auto => Theme::for_background(detect_background().unwrap_or(Background::Dark)),
+        }
+    }
+
+    pub fn importance_color(&self, importance: u8) -> Color {
+        match importance {
+            1..=5 => self.importance[importance as usize - 1],
+            _ => self.text,
+        }
+    }
+}
+
+/// The color a `[flag:red]` names; emoji and other flags have none and are shown as they are.
+pub fn flag_color(flag: &str) -> Option<Color> {
+    Some(match flag.to_ascii_lowercase().as_str() {
+        "red" => Color::Red,
+        "orange" => Color::Rgb(255, 140, 0),
+        "yellow" => Color::Yellow,
+        "green" => Color::Green,
+        "blue" => Color::Blue,
+        "purple" | "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        _ => return None,
+    })
+}
+
+/// Checks COLORFGBG first since it is free, then asks the terminal directly.
+pub fn detect_background() -> Option<Background> {
+    if let Ok(value) = std::env::var("COLORFGBG")
+        && let Some(background) = background_from_colorfgbg(&value) {
+        return Some(background);
+    }
+
+    query_osc11().map(|(r, g, b)| background_from_rgb(r, g, b))
+}
+
+/// COLORFGBG is "fg;bg" (sometimes "fg;default;bg"), using the 16 ANSI color indices.
+pub fn background_from_colorfgbg(value: &str) -> Option<Background> {
+    let bg: u8 = value.rsplit(';').next()?.trim().parse().ok()?;
+    match bg {
+        7 | 9..=15 => Some(Background::Light),
+        0..=6 | 8 => Some(Background::Dark),
+        _ => None,
+    }
+}
+
+pub fn background_from_rgb(r: u8, g: u8, b: u8) -> Background {
+    let luminance = 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
+    if luminance > 127.5 {
+        Background::Light
+    } else {
+        Background::Dark
+    }
+}
+
+/// Parses a reply like `ESC ] 11 ; rgb:ffff/ffff/ffff BEL` into 8-bit channels.
+pub fn parse_osc11_response(response: &str) -> Option<(u8, u8, u8)> {
+    let start = response.find("rgb:")? + 4;
+    let body: String = response[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_hexdigit() || *c == '/')
+        .collect();
+
+    let mut channels = body.split('/').map(|part| {
+        // Channels can have 1 to 4 hex digits; scale each to 0-255
+        if part.len() > 4 {
+            return None;
+        }
+        let value = u32::from_str_radix(part, 16).ok()?;
+        let max = (1u32 << (4 * part.len() as u32)) - 1;
+        Some((value * 255 / max) as u8)
+    });
+
+    Some((channels.next()??, channels.next()??, channels.next()??))
+}
+
+#[cfg(unix)]
+fn query_osc11() -> Option<(u8, u8, u8)> {
+    use std::io::{IsTerminal, Write};
+    use std::os::fd::AsRawFd;
+    use std::time::{Duration, Instant};
+
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    if !stdin.is_terminal() || !stdout.is_terminal() {
+        return None;
+    }
+
+    stdout.write_all(b"\x1b]11;?\x1b\\").ok()?;
+    stdout.flush().ok()?;
+
+    // Terminals that don't support the query never answer, so only wait briefly
+    let fd = stdin.as_raw_fd();
+    let deadline = Instant::now() + Duration::from_millis(150);
+    let mut response = Vec::new();
+
+    while response.len() < 64 {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        let mut pollfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+        let ready = unsafe { libc::poll(&mut pollfd, 1, remaining.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            break;
+        }
+
+        let mut buf = [0u8; 64];
+        let read = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        if read <= 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..read as usize]);
+
+        if response.ends_with(b"\x07") || response.ends_with(b"\x1b\\") {
+            break;
+        }
+    }
+
+    parse_osc11_response(&String::from_utf8_lossy(&response))
+}
+
+#[cfg(not(unix))]
+fn query_osc11() -> Option<(u8, u8, u8)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colorfgbg() {
+        assert_eq!(background_from_colorfgbg("15;0"), Some(Background::Dark));
+        assert_eq!(background_from_colorfgbg("0;15"), Some(Background::Light));
+        assert_eq!(background_from_colorfgbg("0;default;7"), Some(Background::Light));
+        assert_eq!(background_from_colorfgbg("garbage"), None);
+    }
+
+    #[test]
+    fn test_osc11_response() {
+        let white = parse_osc11_response("\x1b]11;rgb:ffff/ffff/ffff\x07").unwrap();
+        assert_eq!(white, (255, 255, 255));
+        assert_eq!(background_from_rgb(white.0, white.1, white.2), Background::Light);
+
+        let dark = parse_osc11_response("\x1b]11;rgb:1e1e/1e1e/2e2e\x1b\\").unwrap();
+        assert_eq!(background_from_rgb(dark.0, dark.1, dark.2), Background::Dark);
+
+        assert_eq!(parse_osc11_response("\x1b]11;rgb:f/80/000\x07"), Some((255, 128, 0)));
+    }
+
+    #[test]
+    fn test_osc11_response_with_too_many_digits() {
+        assert_eq!(parse_osc11_response("\x1b]11;rgb:ffffffffff/0/0\x07"), None);
+        assert_eq!(parse_osc11_response("\x1b]11;rgb:0/ffffffff/0\x07"), None);
+        assert_eq!(parse_osc11_response("\x1b]11;rgb:0/0/fffff\x07"), None);
+        assert_eq!(parse_osc11_response("\x1b]11;rgb://\x07"), None);
+    }
+}
@@ -1,26 +1,27 @@
+use crate::setup;
 use keyring::Entry;
 use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::path::PathBuf;
 
-const KEYRING_SERVICE: &str = "yarmtl-todoist";
-const KEYRING_USERNAME: &str = "api-token";
+/// Where the Todoist API token is kept.
+const TOKEN_STORE: SecretStore = SecretStore {
+    service: "yarmtl-todoist",
+    username: "api-token",
+    file_name: ".todoist_token",
+};
 
 #[derive(Debug)]
 pub enum AuthError {
-    KeyringError(String),
     TokenNotFound,
-    InvalidToken,
     IoError(String),
 }
 
 impl fmt::Display for AuthError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            AuthError::KeyringError(msg) => write!(f, "Keyring error: {}", msg),
             AuthError::TokenNotFound => write!(f, "Todoist API token not found. Run 'yarmtl --setup-todoist' to configure."),
-            AuthError::InvalidToken => write!(f, "Invalid Todoist API token"),
             AuthError::IoError(msg) => write!(f, "IO error: {}", msg),
         }
     }
@@ -28,30 +29,29 @@ impl fmt::Display for AuthError {
 
 impl Error for AuthError {}
 
-pub struct TodoistAuth;
+/// A secret kept in the system keyring, or in a file readable only by the user when
+/// there is no keyring. The file is in ~/.local/share/yarmtl, not in the tasks directory.
+pub struct SecretStore {
+    pub service: &'static str,
+    pub username: &'static str,
+    pub file_name: &'static str,
+}
 
-impl TodoistAuth {
-    fn get_token_file_path() -> PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-        PathBuf::from(home)
-            .join(".local/share/yarmtl")
-            .join(".todoist_token")
+impl SecretStore {
+    fn file_path(&self) -> PathBuf {
+        setup::data_dir().join(self.file_name)
     }
 
-    pub fn store_token(token: &str) -> Result<(), AuthError> {
+    pub fn store(&self, secret: &str) -> Result<(), AuthError> {
         // Try keyring first
-        match Entry::new(KEYRING_SERVICE, KEYRING_USERNAME) {
-            Ok(entry) => {
-                if let Ok(()) = entry.set_password(token) {
-                    return Ok(());
-                }
-            }
-            Err(_) => {}
+        if let Ok(entry) = Entry::new(self.service, self.username)
+            && let Ok(()) = entry.set_password(secret) {
+            return Ok(());
         }
 
         // Fallback to file storage
-        eprintln!("⚠ System keyring not available, using file storage (less secure)");
-        let token_file = Self::get_token_file_path();
+        tracing::warn!("system keyring not available, using file storage (less secure)");
+        let token_file = self.file_path();
 
         // Create parent directory if needed
         if let Some(parent) = token_file.parent() {
@@ -60,7 +60,7 @@ impl TodoistAuth {
         }
 
         // Write token to file with restricted permissions
-        fs::write(&token_file, token)
+        fs::write(&token_file, secret)
             .map_err(|e| AuthError::IoError(e.to_string()))?;
 
         #[cfg(unix)]
@@ -73,19 +73,15 @@ impl TodoistAuth {
         Ok(())
     }
 
-    pub fn get_token() -> Result<String, AuthError> {
+    pub fn get(&self) -> Result<String, AuthError> {
         // Try keyring first
-        match Entry::new(KEYRING_SERVICE, KEYRING_USERNAME) {
-            Ok(entry) => {
-                if let Ok(token) = entry.get_password() {
-                    return Ok(token);
-                }
-            }
-            Err(_) => {}
+        if let Ok(entry) = Entry::new(self.service, self.username)
+            && let Ok(token) = entry.get_password() {
+            return Ok(token);
         }
 
         // Fallback to file storage
-        let token_file = Self::get_token_file_path();
+        let token_file = self.file_path();
         if !token_file.exists() {
             return Err(AuthError::TokenNotFound);
         }
@@ -95,17 +91,15 @@ impl TodoistAuth {
             .map_err(|_| AuthError::TokenNotFound)
     }
 
-    pub fn delete_token() -> Result<(), AuthError> {
+    #[cfg(test)]
+    pub fn delete(&self) -> Result<(), AuthError> {
         // Try keyring first
-        match Entry::new(KEYRING_SERVICE, KEYRING_USERNAME) {
-            Ok(entry) => {
-                let _ = entry.delete_password();
-            }
-            Err(_) => {}
+        if let Ok(entry) = Entry::new(self.service, self.username) {
+            let _ = entry.delete_password();
         }
 
         // Also delete file if exists
-        let token_file = Self::get_token_file_path();
+        let token_file = self.file_path();
         if token_file.exists() {
             fs::remove_file(&token_file)
                 .map_err(|e| AuthError::IoError(e.to_string()))?;
@@ -113,6 +107,33 @@ impl TodoistAuth {
 
         Ok(())
     }
+}
+
+/// Whether the system keyring answers; without one, secrets go to files in the data directory.
+pub fn keyring_available() -> Result<(), String> {
+    // Looking up an entry that isn't there reads nothing and writes nothing
+    let entry = Entry::new("yarmtl", "doctor").map_err(|e| e.to_string())?;
+    match entry.get_password() {
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub struct TodoistAuth;
+
+impl TodoistAuth {
+    pub fn store_token(token: &str) -> Result<(), AuthError> {
+        TOKEN_STORE.store(token)
+    }
+
+    pub fn get_token() -> Result<String, AuthError> {
+        TOKEN_STORE.get()
+    }
+
+    #[cfg(test)]
+    pub fn delete_token() -> Result<(), AuthError> {
+        TOKEN_STORE.delete()
+    }
 
     pub async fn verify_token(token: &str) -> Result<bool, Box<dyn Error>> {
         let client = reqwest::Client::new();
@@ -1,10 +1,25 @@
-use crate::todoist_types::{TodoistTask, TodoistLabel, TodoistProject};
+use crate::todoist_types::{
+    TodoistCompletedResponse, TodoistTask, TodoistProject, TodoistSection, TodoistSyncResponse,
+};
+use chrono::{DateTime, Utc};
 use reqwest::Client;
 use serde_json::json;
 use std::time::Duration;
 use thiserror::Error;
 
-const API_BASE_URL: &str = "https://api.todoist.com/rest/v2";
+const API_HOST: &str = "https://api.todoist.com";
+
+const REST_API_PATH: &str = "/rest/v2";
+
+/// The Sync API, for what the REST API can't do: moving and reordering tasks, and
+/// fetching only what changed since the last sync.
+const SYNC_API_PATH: &str = "/sync/v9/sync";
+
+/// Tasks checked off, which the REST API doesn't list.
+const COMPLETED_API_PATH: &str = "/sync/v9/completed/get_all";
+
+/// Completed tasks per request, the most Todoist sends at once.
+const COMPLETED_PAGE_SIZE: usize = 200;
 
 #[derive(Error, Debug)]
 pub enum TodoistError {
@@ -27,19 +42,57 @@ pub enum TodoistError {
     SerializationError(#[from] serde_json::Error),
 }
 
+/// Tries per request, the first one included.
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Longest wait before a retry, however long Todoist asks for.
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(60);
+
+/// How long to wait before trying again after the `attempt`th try (from 0) failed with
+/// `error`, None when it isn't worth another try. Rate limits wait as long as Todoist
+/// says, server and network errors 1s, 2s, 4s...
+fn retry_delay(error: &TodoistError, attempt: u32) -> Option<Duration> {
+    if attempt + 1 >= MAX_ATTEMPTS || !error.is_unavailable() {
+        return None;
+    }
+    match error {
+        TodoistError::RateLimitExceeded { retry_after } => Some(Duration::from_secs(*retry_after).min(MAX_RETRY_WAIT)),
+        _ => Some(Duration::from_secs(1 << attempt)),
+    }
+}
+
+impl TodoistError {
+    /// Todoist is refusing or out of reach, so the rest of a sync would fail the same way.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            TodoistError::RateLimitExceeded { .. } => true,
+            TodoistError::ApiError { status, .. } => *status >= 500,
+            TodoistError::NetworkError(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+}
+
 pub struct TodoistClient {
     client: Client,
     api_token: String,
+    /// Where the API is, api.todoist.com but for tests
+    host: String,
 }
 
 impl TodoistClient {
     pub fn new(api_token: String) -> Self {
+        TodoistClient::at(API_HOST, api_token)
+    }
+
+    /// A client of the Todoist API served at `host` instead, like a fake one in tests.
+    pub fn at(host: &str, api_token: String) -> Self {
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
             .build()
             .expect("Failed to create HTTP client");
 
-        TodoistClient { client, api_token }
+        TodoistClient { client, api_token, host: host.trim_end_matches('/').to_string() }
     }
 
     async fn make_request<T: serde::de::DeserializeOwned>(
@@ -48,16 +101,49 @@ impl TodoistClient {
         endpoint: &str,
         body: Option<serde_json::Value>,
     ) -> Result<T, TodoistError> {
-        let url = format!("{}{}", API_BASE_URL, endpoint);
+        let url = format!("{}{}{}", self.host, REST_API_PATH, endpoint);
+        self.request_url(method, &url, endpoint, body).await
+    }
+
+    /// Sends the request, again after a wait when Todoist is rate limiting, failing
+    /// with a 5xx or unreachable, up to `MAX_ATTEMPTS` times in all. Every attempt
+    /// carries the same X-Request-Id, so Todoist does a retried change only once.
+    async fn request_url<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        endpoint: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<T, TodoistError> {
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let mut attempt = 0;
+        loop {
+            let result = self.send_once(method.clone(), url, endpoint, body.as_ref(), &request_id).await;
+            match result.as_ref().err().and_then(|error| retry_delay(error, attempt)) {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return result,
+            }
+            attempt += 1;
+        }
+    }
 
+    async fn send_once<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        endpoint: &str,
+        body: Option<&serde_json::Value>,
+        request_id: &str,
+    ) -> Result<T, TodoistError> {
         let mut request = self
             .client
-            .request(method, &url)
+            .request(method, url)
             .header("Authorization", format!("Bearer {}", self.api_token))
-            .header("Content-Type", "application/json");
+            .header("Content-Type", "application/json")
+            .header("X-Request-Id", request_id);
 
         if let Some(body) = body {
-            request = request.json(&body);
+            request = request.json(body);
         }
 
         let response = request.send().await?;
@@ -65,8 +151,10 @@ impl TodoistClient {
         let status = response.status();
 
         if status.is_success() {
-            let result = response.json::<T>().await?;
-            Ok(result)
+            // Closing, reopening and deleting answer 204 without a body
+            let bytes = response.bytes().await?;
+            let body: &[u8] = if bytes.is_empty() { b"null" } else { &bytes };
+            Ok(serde_json::from_slice(body)?)
         } else if status.as_u16() == 429 {
             let retry_after = response
                 .headers()
@@ -98,10 +186,34 @@ impl TodoistClient {
             .await
     }
 
-    pub async fn get_task(&self, task_id: &str) -> Result<TodoistTask, TodoistError> {
-        let endpoint = format!("/tasks/{}", task_id);
-        self.make_request(reqwest::Method::GET, &endpoint, None)
-            .await
+    /// The tasks checked off since `since`, newest first, as the REST API would list them.
+    pub async fn list_completed_tasks(&self, since: DateTime<Utc>) -> Result<Vec<TodoistTask>, TodoistError> {
+        let mut tasks = Vec::new();
+        let mut offset = 0;
+        loop {
+            let url = format!(
+                "{}{}?annotate_items=true&limit={}&offset={}&since={}",
+                self.host,
+                COMPLETED_API_PATH,
+                COMPLETED_PAGE_SIZE,
+                offset,
+                since.format("%Y-%m-%dT%H:%M:%S")
+            );
+            let page: TodoistCompletedResponse = self
+                .request_url(reqwest::Method::GET, &url, "/completed/get_all", None)
+                .await?;
+            offset += page.items.len();
+            let last_page = page.items.len() < COMPLETED_PAGE_SIZE;
+            tasks.extend(
+                page.items
+                    .into_iter()
+                    .filter_map(|completed| completed.item_object)
+                    .map(|item| TodoistTask { is_completed: Some(true), ..item.into() }),
+            );
+            if last_page {
+                return Ok(tasks);
+            }
+        }
     }
 
     pub async fn create_task(&self, task: &TodoistTask) -> Result<TodoistTask, TodoistError> {
@@ -142,24 +254,83 @@ impl TodoistClient {
         Ok(())
     }
 
-    pub async fn list_labels(&self) -> Result<Vec<TodoistLabel>, TodoistError> {
-        self.make_request(reqwest::Method::GET, "/labels", None)
+    /// Tasks, projects and sections changed since `sync_token`, all of them for `*`.
+    pub async fn sync_resources(&self, sync_token: &str) -> Result<TodoistSyncResponse, TodoistError> {
+        let body = json!({ "sync_token": sync_token, "resource_types": ["items", "projects", "sections"] });
+        self.request_url(reqwest::Method::POST, &self.sync_url(), "/sync", Some(body))
             .await
     }
 
-    pub async fn create_label(&self, name: &str) -> Result<TodoistLabel, TodoistError> {
-        let body = json!({
-            "name": name
-        });
-        self.make_request(reqwest::Method::POST, "/labels", Some(body))
+    /// Makes a task a subtask of `parent_id`, or else a top-level task of `section_id`,
+    /// or else of `project_id` outside its sections.
+    pub async fn move_task(
+        &self,
+        task_id: &str,
+        parent_id: Option<&str>,
+        section_id: Option<&str>,
+        project_id: Option<&str>,
+    ) -> Result<(), TodoistError> {
+        let args = match (parent_id, section_id) {
+            (Some(parent_id), _) => json!({ "id": task_id, "parent_id": parent_id }),
+            (None, Some(section_id)) => json!({ "id": task_id, "section_id": section_id }),
+            (None, None) => json!({ "id": task_id, "project_id": project_id }),
+        };
+        self.run_commands(vec![json!({ "type": "item_move", "uuid": uuid::Uuid::new_v4().to_string(), "args": args })])
             .await
     }
 
+    /// Puts sibling tasks in the given order.
+    pub async fn reorder_tasks(&self, task_ids: &[String]) -> Result<(), TodoistError> {
+        let items: Vec<serde_json::Value> = task_ids
+            .iter()
+            .enumerate()
+            .map(|(order, id)| json!({ "id": id, "child_order": order + 1 }))
+            .collect();
+        self.run_commands(vec![json!({
+            "type": "item_reorder",
+            "uuid": uuid::Uuid::new_v4().to_string(),
+            "args": { "items": items },
+        })])
+        .await
+    }
+
+    fn sync_url(&self) -> String {
+        format!("{}{}", self.host, SYNC_API_PATH)
+    }
+
+    async fn run_commands(&self, commands: Vec<serde_json::Value>) -> Result<(), TodoistError> {
+        let response: serde_json::Value = self
+            .request_url(reqwest::Method::POST, &self.sync_url(), "/sync", Some(json!({ "commands": commands })))
+            .await?;
+        // Each command reports "ok" or an error object of its own
+        let failed = response["sync_status"]
+            .as_object()
+            .and_then(|statuses| statuses.values().find(|status| *status != "ok"));
+        match failed {
+            Some(error) => Err(TodoistError::ApiError { status: 200, message: error.to_string() }),
+            None => Ok(()),
+        }
+    }
+
     pub async fn list_projects(&self) -> Result<Vec<TodoistProject>, TodoistError> {
         self.make_request(reqwest::Method::GET, "/projects", None)
             .await
     }
 
+    pub async fn list_sections(&self) -> Result<Vec<TodoistSection>, TodoistError> {
+        self.make_request(reqwest::Method::GET, "/sections", None)
+            .await
+    }
+
+    pub async fn create_section(&self, name: &str, project_id: &str) -> Result<TodoistSection, TodoistError> {
+        let body = json!({
+            "name": name,
+            "project_id": project_id
+        });
+        self.make_request(reqwest::Method::POST, "/sections", Some(body))
+            .await
+    }
+
     pub async fn create_project(&self, name: &str) -> Result<TodoistProject, TodoistError> {
         let body = json!({
             "name": name
@@ -178,4 +349,18 @@ mod tests {
         let client = TodoistClient::new("test-token".to_string());
         assert_eq!(client.api_token, "test-token");
     }
+
+    #[test]
+    fn test_retry_delay() {
+        let rate_limited = TodoistError::RateLimitExceeded { retry_after: 5 };
+        let server_error = TodoistError::ApiError { status: 503, message: String::new() };
+        let bad_request = TodoistError::ApiError { status: 400, message: String::new() };
+
+        assert_eq!(retry_delay(&rate_limited, 0), Some(Duration::from_secs(5)));
+        assert_eq!(retry_delay(&TodoistError::RateLimitExceeded { retry_after: 3600 }, 0), Some(MAX_RETRY_WAIT));
+        assert_eq!(retry_delay(&server_error, 2), Some(Duration::from_secs(4)));
+        assert_eq!(retry_delay(&server_error, MAX_ATTEMPTS - 1), None);
+        assert_eq!(retry_delay(&bad_request, 0), None);
+        assert!(server_error.is_unavailable() && !bad_request.is_unavailable());
+    }
 }
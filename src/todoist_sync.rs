@@ -1,378 +1,160 @@
-use crate::sync_metadata::{SyncMetadata, TaskSyncInfo};
-use crate::todoist_client::TodoistClient;
-use crate::todoist_types::{TodoistTask, TodoistDue, YarmtlMetadata};
-use chrono::{NaiveDate, Utc};
+use crate::clock;
+use crate::error::YarmtlError;
+use crate::git;
+use crate::hash::StableHasher;
+use crate::ids;
+use crate::paths::{get_state_dir, get_sync_dir, get_tasks_file_path, get_todoist_config_path};
+use crate::sync_engine::{self, RemoteTask, SyncContext, SyncProgress, SyncProvider, CONTEXT_LABEL_PREFIX, after_sync, wait_for_sync_lock};
+use crate::sync_metadata::SyncMetadata;
+use crate::task::Task;
+use crate::todoist_auth;
+use crate::todoist_client::{TodoistClient, TodoistError};
+use crate::todoist_types::{self, TodoistSection, TodoistTask, YarmtlMetadata};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs;
 use std::path::PathBuf;
-
-// Import Task from main
-use crate::Task;
-
-#[derive(Debug)]
-pub struct SyncReport {
-    pub created_in_todoist: usize,
-    pub created_in_yarmtl: usize,
-    pub updated_in_todoist: usize,
-    pub updated_in_yarmtl: usize,
-    pub deleted_in_todoist: usize,
-    pub deleted_in_yarmtl: usize,
-    pub conflicts_resolved: usize,
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Which tasks sync, the `[sync]` table of todoist_config.toml. Tasks left out are
+/// neither sent to Todoist nor taken from it.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SyncFilter {
+    /// Only tasks with one of these tags, when there are any
+    pub include_tags: Vec<String>,
+    /// Tasks with one of these tags stay in tasks.md
+    pub exclude_tags: Vec<String>,
+    /// The one Todoist project to mirror: every task goes to it, its tags all as labels,
+    /// and tasks in other projects are left alone
+    pub project: Option<String>,
+    /// Whether checking a task off (or reopening it) on one side does so on the other
+    pub completed: bool,
 }
 
-impl SyncReport {
-    pub fn new() -> Self {
-        SyncReport {
-            created_in_todoist: 0,
-            created_in_yarmtl: 0,
-            updated_in_todoist: 0,
-            updated_in_yarmtl: 0,
-            deleted_in_todoist: 0,
-            deleted_in_yarmtl: 0,
-            conflicts_resolved: 0,
+impl Default for SyncFilter {
+    fn default() -> Self {
+        SyncFilter {
+            include_tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            project: None,
+            completed: true,
         }
     }
-
-    pub fn summary(&self) -> String {
-        format!(
-            "↑{} ↓{} ⇅{} ✗{}",
-            self.created_in_todoist + self.updated_in_todoist,
-            self.created_in_yarmtl + self.updated_in_yarmtl,
-            self.conflicts_resolved,
-            self.deleted_in_todoist + self.deleted_in_yarmtl
-        )
-    }
 }
 
-#[derive(Debug)]
-pub enum SyncAction {
-    CreateInTodoist(Task),
-    CreateInYarmtl(TodoistTask),
-    UpdateTodoist { yarmtl_id: String, task: Task },
-    UpdateYarmtl { todoist_id: String, task: TodoistTask },
-    DeleteFromTodoist { todoist_id: String },
-    DeleteFromYarmtl { yarmtl_id: String },
-}
-
-pub struct TodoistSync {
-    client: TodoistClient,
-    metadata: SyncMetadata,
-    metadata_path: PathBuf,
-    local_tasks: Vec<Task>,
-    tasks_modified: bool,
-    projects: HashMap<String, String>, // project_name -> project_id
-}
-
-impl TodoistSync {
-    pub fn new(api_token: String, sync_dir: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
-        let client = TodoistClient::new(api_token);
-        let metadata_path = sync_dir.join(".sync_metadata.json");
-        let metadata = SyncMetadata::load(&metadata_path)?;
-
-        Ok(TodoistSync {
-            client,
-            metadata,
-            metadata_path,
-            local_tasks: Vec::new(),
-            tasks_modified: false,
-            projects: HashMap::new(),
-        })
+impl SyncFilter {
+    /// Whether a task with `tags` syncs. A subtask goes with its parent, `parent` says
+    /// whether that one syncs, unless a tag of its own is excluded.
+    pub fn allows(&self, tags: &[String], parent: Option<bool>) -> bool {
+        let excluded = tags.iter().any(|t| self.exclude_tags.contains(t));
+        let included = self.include_tags.is_empty() || tags.iter().any(|t| self.include_tags.contains(t));
+        !excluded && parent.unwrap_or(included)
     }
 
-    pub async fn sync(&mut self, tasks_file: &PathBuf) -> Result<SyncReport, Box<dyn std::error::Error>> {
-        let mut report = SyncReport::new();
-
-        // Fetch all projects from Todoist
-        let projects = self.client.list_projects().await?;
-        self.projects = projects
-            .into_iter()
-            .map(|p| (p.name.clone(), p.id.clone()))
-            .collect();
-
-        // Fetch all tasks from Todoist
-        let todoist_tasks = self.client.list_tasks().await?;
-
-        // Load local tasks
-        self.local_tasks = self.load_local_tasks(tasks_file)?;
-        self.tasks_modified = false;
-
-        // Detect changes
-        let actions = self.detect_changes(&self.local_tasks.clone(), &todoist_tasks);
-
-        // Apply actions (silently - no console output to avoid breaking TUI)
-        for action in actions {
-            match self.apply_action(action).await {
-                Ok(action_type) => {
-                    match action_type {
-                        ActionType::CreatedInTodoist => report.created_in_todoist += 1,
-                        ActionType::CreatedInYarmtl => report.created_in_yarmtl += 1,
-                        ActionType::UpdatedInTodoist => report.updated_in_todoist += 1,
-                        ActionType::UpdatedInYarmtl => report.updated_in_yarmtl += 1,
-                        ActionType::DeletedFromTodoist => report.deleted_in_todoist += 1,
-                        ActionType::DeletedFromYarmtl => report.deleted_in_yarmtl += 1,
-                    }
-                }
-                Err(_e) => {
-                    // Silently continue - errors are reflected in the report
-                }
-            }
+    /// What the filter lets through, for `--sync-status`: "#work, not #private, in Todoist's Mirror".
+    pub fn describe(&self) -> String {
+        let tags = |tags: &[String]| tags.iter().map(|t| format!("#{}", t)).collect::<Vec<_>>().join(" ");
+        let mut parts = Vec::new();
+        parts.push(match self.include_tags.is_empty() {
+            true => "all tasks".to_string(),
+            false => tags(&self.include_tags),
+        });
+        if !self.exclude_tags.is_empty() {
+            parts.push(format!("not {}", tags(&self.exclude_tags)));
         }
-
-        // Write back local tasks if modified
-        if self.tasks_modified {
-            self.save_local_tasks(tasks_file)?;
+        if let Some(project) = &self.project {
+            parts.push(format!("in Todoist's {}", project));
         }
-
-        // Update last sync timestamp
-        self.metadata.update_last_sync();
-
-        // Save metadata
-        self.metadata.save(&self.metadata_path)?;
-
-        Ok(report)
-    }
-
-    fn save_local_tasks(&self, tasks_file: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-        let mut content = String::from("# tasks\n\n");
-
-        for task in &self.local_tasks {
-            content.push_str(&format!("{}\n", task.to_markdown()));
+        if !self.completed {
+            parts.push("completion stays local".to_string());
         }
-
-        fs::write(tasks_file, content)?;
-        Ok(())
+        parts.join(", ")
     }
 
-    fn load_local_tasks(&self, tasks_file: &PathBuf) -> Result<Vec<Task>, Box<dyn std::error::Error>> {
-        if !tasks_file.exists() {
-            return Ok(Vec::new());
-        }
-
-        let content = fs::read_to_string(tasks_file)?;
-        let mut tasks = Vec::new();
-
-        for line in content.lines() {
-            let trimmed = line.trim();
-            if trimmed.starts_with("- [ ]") || trimmed.starts_with("- [x]") {
-                let task_text = trimmed
-                    .strip_prefix("- [ ] ")
-                    .or_else(|| trimmed.strip_prefix("- [x] "))
-                    .unwrap_or(trimmed);
-
-                let mut task = Task::parse(task_text);
-                task.completed = trimmed.starts_with("- [x]");
-                tasks.push(task);
+    /// Ids of the tasks of tasks.md that sync.
+    pub fn local_scope(&self, tasks: &[Task]) -> HashSet<String> {
+        let mut scope = HashSet::new();
+        for task in tasks {
+            let parent = task.parent_id.as_ref().map(|id| scope.contains(id));
+            if self.allows(&task.tags, parent) {
+                scope.insert(task.id.clone());
             }
         }
-
-        Ok(tasks)
+        scope
     }
+}
 
-    fn detect_changes(&self, local_tasks: &[Task], todoist_tasks: &[TodoistTask]) -> Vec<SyncAction> {
-        let mut actions = Vec::new();
-
-        // Build sets for quick lookup
-        let local_ids: HashSet<_> = local_tasks.iter().map(|t| t.id.clone()).collect();
-        let todoist_ids: HashSet<_> = todoist_tasks
-            .iter()
-            .filter_map(|t| t.id.clone())
-            .collect();
-
-        // Map of todoist_id -> task (for future use)
-        let _todoist_map: HashMap<_, _> = todoist_tasks
-            .iter()
-            .filter_map(|t| t.id.as_ref().map(|id| (id.clone(), t)))
-            .collect();
-
-        // Check local tasks
-        for local_task in local_tasks {
-            if let Some(todoist_id) = self.metadata.get_todoist_id(&local_task.id) {
-                // Task is mapped
-                if todoist_ids.contains(todoist_id) {
-                    // Both exist - check for changes
-                    let local_hash = self.compute_task_hash(local_task);
-                    let stored_hash = self.metadata.get_hash(&local_task.id);
-
-                    if stored_hash.map(|h| h != local_hash).unwrap_or(true) {
-                        // Local changed, update Todoist
-                        actions.push(SyncAction::UpdateTodoist {
-                            yarmtl_id: local_task.id.clone(),
-                            task: local_task.clone(),
-                        });
-                    }
-                } else {
-                    // Todoist task was deleted
-                    actions.push(SyncAction::DeleteFromYarmtl {
-                        yarmtl_id: local_task.id.clone(),
-                    });
-                }
-            } else {
-                // Task not in metadata - could be new, or old completed task
-                // Skip completed tasks (don't sync old completed tasks to Todoist)
-                if local_task.completed {
-                    // Only sync completed tasks if they have a deadline in the future
-                    // or within the last 30 days
-                    let should_skip = if let Some(deadline) = local_task.deadline {
-                        let today = chrono::Local::now().date_naive();
-                        let thirty_days_ago = today - chrono::Duration::days(30);
-                        deadline < thirty_days_ago
-                    } else {
-                        // No deadline - skip all old completed tasks
-                        true
-                    };
-
-                    if should_skip {
-                        continue;
-                    }
-                }
-
-                // New local task - create in Todoist
-                actions.push(SyncAction::CreateInTodoist(local_task.clone()));
-            }
-        }
-
-        // Check Todoist tasks
-        for todoist_task in todoist_tasks {
-            if let Some(todoist_id) = &todoist_task.id {
-                if let Some(yarmtl_id) = self.metadata.get_yarmtl_id(todoist_id) {
-                    // Already mapped, handled above
-                    if !local_ids.contains(&yarmtl_id) {
-                        // Local was deleted
-                        actions.push(SyncAction::DeleteFromTodoist {
-                            todoist_id: todoist_id.clone(),
-                        });
-                    }
-                } else {
-                    // Check if this is a new Todoist task or has yarmtl metadata
-                    if let Some(meta) = self.extract_yarmtl_metadata(todoist_task) {
-                        // Has yarmtl metadata, might be an update
-                        if local_ids.contains(&meta.id) {
-                            // Update local
-                            actions.push(SyncAction::UpdateYarmtl {
-                                todoist_id: todoist_id.clone(),
-                                task: todoist_task.clone(),
-                            });
-                        } else {
-                            // Create new local
-                            actions.push(SyncAction::CreateInYarmtl(todoist_task.clone()));
-                        }
-                    } else {
-                        // New Todoist task without metadata
-                        actions.push(SyncAction::CreateInYarmtl(todoist_task.clone()));
-                    }
-                }
-            }
-        }
+impl RemoteTask for TodoistTask {
+    const SERVICE: &'static str = "Todoist";
 
-        actions
+    fn title(&self) -> &str {
+        &self.content
     }
 
-    async fn apply_action(&mut self, action: SyncAction) -> Result<ActionType, Box<dyn std::error::Error>> {
-        match action {
-            SyncAction::CreateInTodoist(task) => {
-                // Ensure project exists if task has tags
-                if !task.tags.is_empty() {
-                    self.get_or_create_project(&task.tags[0]).await;
-                }
-
-                let todoist_task = self.convert_yarmtl_to_todoist(&task);
-                let created = self.client.create_task(&todoist_task).await?;
-
-                if let Some(todoist_id) = created.id {
-                    // If task is completed, close it in Todoist
-                    if task.completed {
-                        let _ = self.client.close_task(&todoist_id).await;
-                    }
-
-                    let info = TaskSyncInfo {
-                        todoist_id: todoist_id.clone(),
-                        last_modified: Utc::now(),
-                        last_sync_hash: self.compute_task_hash(&task),
-                    };
-                    self.metadata.update_mapping(task.id, info);
-                }
-
-                Ok(ActionType::CreatedInTodoist)
-            }
-            SyncAction::CreateInYarmtl(todoist_task) => {
-                let yarmtl_task = self.convert_todoist_to_yarmtl(&todoist_task);
-
-                if let Some(todoist_id) = todoist_task.id {
-                    let info = TaskSyncInfo {
-                        todoist_id,
-                        last_modified: Utc::now(),
-                        last_sync_hash: self.compute_task_hash(&yarmtl_task),
-                    };
-                    self.metadata.update_mapping(yarmtl_task.id.clone(), info);
-                }
+    fn remote_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
 
-                // Add to local tasks
-                self.local_tasks.push(yarmtl_task);
-                self.tasks_modified = true;
+    /// From the metadata sent along in the description
+    fn yarmtl_id(&self) -> Option<String> {
+        self.description.as_deref().and_then(YarmtlMetadata::parse).map(|m| m.id)
+    }
 
-                Ok(ActionType::CreatedInYarmtl)
-            }
-            SyncAction::UpdateTodoist { yarmtl_id, task } => {
-                if let Some(todoist_id) = self.metadata.get_todoist_id(&yarmtl_id).map(|s| s.to_string()) {
-                    // Ensure project exists if task has tags
-                    if !task.tags.is_empty() {
-                        self.get_or_create_project(&task.tags[0]).await;
-                    }
-
-                    let todoist_task = self.convert_yarmtl_to_todoist(&task);
-                    self.client.update_task(&todoist_id, &todoist_task).await?;
-
-                    // Handle completion status changes
-                    if task.completed {
-                        let _ = self.client.close_task(&todoist_id).await;
-                    } else {
-                        let _ = self.client.reopen_task(&todoist_id).await;
-                    }
-
-                    let info = TaskSyncInfo {
-                        todoist_id: todoist_id.clone(),
-                        last_modified: Utc::now(),
-                        last_sync_hash: self.compute_task_hash(&task),
-                    };
-                    self.metadata.update_mapping(yarmtl_id, info);
-                }
+    fn is_completed(&self) -> bool {
+        self.is_completed == Some(true)
+    }
 
-                Ok(ActionType::UpdatedInTodoist)
-            }
-            SyncAction::UpdateYarmtl { todoist_id, task } => {
-                let yarmtl_task = self.convert_todoist_to_yarmtl(&task);
+    fn order(&self) -> Option<i64> {
+        self.order
+    }
 
-                // Find and update the local task
-                if let Some(local_task) = self.local_tasks.iter_mut().find(|t| t.id == yarmtl_task.id) {
-                    *local_task = yarmtl_task.clone();
-                    self.tasks_modified = true;
-                }
+    fn modified_at(&self) -> Option<DateTime<Utc>> {
+        let updated = DateTime::parse_from_rfc3339(self.updated_at.as_deref()?).ok()?;
+        Some(updated.with_timezone(&Utc))
+    }
+}
 
-                // Update metadata
-                let info = TaskSyncInfo {
-                    todoist_id,
-                    last_modified: Utc::now(),
-                    last_sync_hash: self.compute_task_hash(&yarmtl_task),
-                };
-                self.metadata.update_mapping(yarmtl_task.id, info);
+/// Todoist for the `SyncEngine`: the first tag of a task is its project there, the other
+/// tags and the contexts labels, `## ` headings sections, and subtasks keep their order.
+pub struct TodoistProvider {
+    client: TodoistClient,
+    projects: HashMap<String, String>, // project_name -> project_id
+    /// Where tasks without a tag go in Todoist
+    inbox_project_id: Option<String>,
+    /// What the `## ` headings of tasks.md are in each project
+    sections: Vec<TodoistSection>,
+    /// Fetch changes through the Sync API rather than everything through REST
+    incremental: bool,
+    filter: SyncFilter,
+}
 
-                Ok(ActionType::UpdatedInYarmtl)
-            }
-            SyncAction::DeleteFromTodoist { todoist_id } => {
-                self.client.delete_task(&todoist_id).await?;
-                Ok(ActionType::DeletedFromTodoist)
-            }
-            SyncAction::DeleteFromYarmtl { yarmtl_id } => {
-                // Remove from local tasks
-                self.local_tasks.retain(|t| t.id != yarmtl_id);
-                self.tasks_modified = true;
+impl TodoistProvider {
+    pub fn new(api_token: String, incremental: bool, filter: SyncFilter) -> Self {
+        TodoistProvider::with_client(TodoistClient::new(api_token), incremental, filter)
+    }
 
-                self.metadata.remove_mapping(&yarmtl_id);
-                Ok(ActionType::DeletedFromYarmtl)
-            }
+    /// Syncs through `client`, which may talk to another server than Todoist's.
+    pub fn with_client(client: TodoistClient, incremental: bool, filter: SyncFilter) -> Self {
+        TodoistProvider {
+            client,
+            projects: HashMap::new(),
+            inbox_project_id: None,
+            sections: Vec::new(),
+            incremental,
+            filter,
         }
     }
 
+    /// The Todoist project of a task: the mirrored one when there is one, else the one
+    /// named after its first tag.
+    fn project_name(&self, task: &Task) -> Option<String> {
+        self.filter.project.clone().or_else(|| task.tags.first().cloned())
+    }
+
     async fn get_or_create_project(&mut self, project_name: &str) -> Option<String> {
         // Check if project already exists in cache
         if let Some(project_id) = self.projects.get(project_name) {
@@ -392,35 +174,53 @@ impl TodoistSync {
         }
     }
 
-    fn convert_yarmtl_to_todoist(&self, task: &Task) -> TodoistTask {
-        // Set due_date as string for API requests
-        let due_date = task.deadline.map(|d| d.format("%Y-%m-%d").to_string());
+    /// The section named after a `## ` heading in a project (the inbox for None), added
+    /// to the project when it has none by that name. None without a heading.
+    async fn section_for(&mut self, project_id: Option<&str>, heading: Option<&str>) -> Option<String> {
+        let heading = heading?;
+        let project_id = project_id.map(str::to_string).or_else(|| self.inbox_project_id.clone())?;
+        if let Some(section) = self.sections.iter().find(|s| s.project_id == project_id && s.name == heading) {
+            return Some(section.id.clone());
+        }
 
-        // First tag becomes project, rest become labels
-        let (project_id, labels) = if task.tags.is_empty() {
-            (None, None)
-        } else {
-            let project_name = &task.tags[0];
-            let project_id = self.projects.get(project_name).cloned();
+        // Silently fail like projects do, the task is synced outside any section then
+        let section = self.client.create_section(heading, &project_id).await.ok()?;
+        let id = section.id.clone();
+        self.sections.push(section);
+        Some(id)
+    }
 
-            // Rest of tags become labels (if any)
-            let labels = if task.tags.len() > 1 {
-                Some(task.tags[1..].to_vec())
-            } else {
-                None
-            };
+    fn convert_yarmtl_to_todoist(&self, task: &Task, context: &SyncContext<'_, TodoistTask>) -> TodoistTask {
+        // Set due_date as string for API requests
+        let due_date = task.deadline.map(|d| d.format("%Y-%m-%d").to_string());
 
-            (project_id, labels)
-        };
+        // First tag becomes project, the rest and the contexts become labels
+        let project_id = self.project_name(task).and_then(|name| self.projects.get(&name).cloned());
+        let labels: Vec<String> = task
+            .tags
+            .iter()
+            .skip(if self.filter.project.is_some() { 0 } else { 1 })
+            .cloned()
+            .chain(task.contexts.iter().map(|context| format!("{}{}", CONTEXT_LABEL_PREFIX, context)))
+            .collect();
+        let labels = (!labels.is_empty()).then_some(labels);
 
-        // Convert importance: yarmtl 1-5 (1=most) -> todoist 1-4 (4=most)
-        let priority = task.importance.map(|i| match i {
-            1 => 4,
-            2 => 3,
-            3 => 2,
-            _ => 1,
+        // Subtasks go under their parent's Todoist task, in the order of tasks.md
+        let parent_id = task
+            .parent_id
+            .as_ref()
+            .and_then(|id| context.metadata.get_todoist_id(id))
+            .map(str::to_string);
+        let order = parent_id.as_ref().and_then(|_| {
+            context.local_tasks
+                .iter()
+                .filter(|t| t.parent_id == task.parent_id)
+                .position(|t| t.id == task.id)
+                .map(|position| position as i64 + 1)
         });
 
+        let priority = Some(importance_to_priority(task.importance));
+
         let metadata = YarmtlMetadata {
             id: task.id.clone(),
             deadline: task.deadline.map(|d| d.format("%Y-%m-%d").to_string()),
@@ -441,6 +241,10 @@ impl TodoistSync {
             priority,
             is_completed: None, // Don't set here, use close_task/reopen_task instead
             project_id,
+            section_id: None, // Only known once the section is looked up or added
+            parent_id,
+            order,
+            updated_at: None,
         }
     }
 
@@ -453,13 +257,14 @@ impl TodoistSync {
         let id = metadata
             .as_ref()
             .map(|m| m.id.clone())
-            .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string()[..8].to_string());
+            .unwrap_or_else(ids::generate);
 
         // Prefer deadline from Todoist's due field, fall back to metadata
         let deadline = todoist_task
             .due
             .as_ref()
-            .and_then(|d| NaiveDate::parse_from_str(&d.date, "%Y-%m-%d").ok())
+            // A due time with an offset may fall on another day here
+            .and_then(|d| d.datetime.as_deref().and_then(clock::local_day).or_else(|| NaiveDate::parse_from_str(&d.date, "%Y-%m-%d").ok()))
             .or_else(|| {
                 metadata
                     .as_ref()
@@ -470,19 +275,30 @@ impl TodoistSync {
         // Tags: project comes first, then labels
         let mut tags = Vec::new();
 
-        // Add project name as first tag
-        if let Some(project_id) = &todoist_task.project_id {
+        // Add project name as first tag, unless all tasks are in the mirrored project
+        if let Some(project_id) = todoist_task.project_id.as_ref().filter(|_| self.filter.project.is_none()) {
             // Find project name from project_id
             if let Some((name, _)) = self.projects.iter().find(|(_, id)| id == &project_id) {
                 tags.push(name.clone());
             }
         }
 
-        // Add labels as additional tags
-        if let Some(labels) = &todoist_task.labels {
-            tags.extend(labels.clone());
+        // Add labels as additional tags, or as contexts when they have the prefix
+        let mut contexts = Vec::new();
+        for label in todoist_task.labels.iter().flatten() {
+            match label.strip_prefix(CONTEXT_LABEL_PREFIX) {
+                Some(context) if !context.is_empty() => contexts.push(context.to_string()),
+                _ => tags.push(label.clone()),
+            }
         }
 
+        // The section is the `## ` heading the task goes under
+        let project = todoist_task
+            .section_id
+            .as_ref()
+            .and_then(|id| self.sections.iter().find(|s| &s.id == id))
+            .map(|s| s.name.clone());
+
         let reminder = metadata
             .as_ref()
             .and_then(|m| m.reminder.as_ref())
@@ -490,51 +306,504 @@ impl TodoistSync {
 
         let notes = metadata.as_ref().and_then(|m| m.notes.clone());
 
-        let importance = metadata.as_ref().and_then(|m| m.importance);
+        let importance = priority_to_importance(todoist_task.priority, metadata.as_ref().and_then(|m| m.importance));
+
+        // Todoist doesn't tell us, so the dates are when yarmtl first saw the task
+        let today = chrono::Local::now().date_naive();
+        let completed = todoist_task.is_completed.unwrap_or(false);
 
         Task {
             id,
             text: todoist_task.content.clone(),
             deadline,
             tags,
+            contexts,
             reminder,
-            completed: todoist_task.is_completed.unwrap_or(false),
+            repeat: None,
+            last_fired: None,
+            completed,
+            cancelled: false,
             notes,
             importance,
+            indent_level: 0,
+            parent_id: None,
+            project,
+            source: None,
+            attachments: Vec::new(),
+            created: Some(today),
+            done: completed.then_some(today),
+            estimate: None,
+            spent: None,
+            pinned: false,
+            flag: None,
+            count: None,
+            warn: None,
         }
     }
+}
 
-    fn extract_yarmtl_metadata(&self, todoist_task: &TodoistTask) -> Option<YarmtlMetadata> {
-        todoist_task
-            .description
+impl SyncProvider for TodoistProvider {
+    type Remote = TodoistTask;
+    type Error = TodoistError;
+
+    const METADATA_FILE: &'static str = ".sync_metadata.json";
+    const SUBTASKS: bool = true;
+    const SUBTASK_ORDER: bool = true;
+    /// Only open tasks are listed, the paired ones checked off since the last sync are
+    /// asked for on their own
+    const LISTS_COMPLETED: bool = false;
+    const DELETES: bool = true;
+
+    fn is_unavailable(error: &TodoistError) -> bool {
+        error.is_unavailable()
+    }
+
+    async fn fetch(
+        &mut self,
+        metadata: &mut SyncMetadata,
+        local_tasks: &[Task],
+        on_progress: &mut impl FnMut(SyncProgress),
+    ) -> Result<Vec<TodoistTask>, TodoistError> {
+        on_progress(SyncProgress::FetchingProjects);
+        let (projects, mut todoist_tasks) = if self.incremental {
+            // Only what changed since the last sync, on top of what it left in the metadata
+            let sync_token = metadata.sync_token.clone().unwrap_or_else(|| "*".to_string());
+            let response = self.client.sync_resources(&sync_token).await?;
+            metadata.apply_sync_response(response);
+            on_progress(SyncProgress::FetchingTasks);
+            self.sections = metadata.todoist_sections.values().cloned().collect();
+            (
+                metadata.todoist_projects.values().cloned().collect(),
+                metadata.todoist_tasks.values().cloned().collect(),
+            )
+        } else {
+            // Fetch all projects, sections and tasks from Todoist
+            let projects = self.client.list_projects().await?;
+            self.sections = self.client.list_sections().await?;
+            on_progress(SyncProgress::FetchingTasks);
+            (projects, self.client.list_tasks().await?)
+        };
+        self.inbox_project_id = projects.iter().find(|p| p.is_inbox_project).map(|p| p.id.clone());
+        self.projects = projects
+            .into_iter()
+            .map(|p| (p.name.clone(), p.id.clone()))
+            .collect();
+
+        // The paired tasks checked off in Todoist since the last sync are asked for, so
+        // they are checked off here too rather than deleted
+        let listed: HashSet<String> = todoist_tasks.iter().filter_map(|t| t.id.clone()).collect();
+        let unlisted: HashSet<String> = local_tasks
+            .iter()
+            .filter(|t| !t.completed)
+            .filter_map(|t| metadata.get_todoist_id(&t.id))
+            .filter(|id| !listed.contains(*id))
+            .map(str::to_string)
+            .collect();
+        if !unlisted.is_empty() {
+            let since = metadata.last_sync - chrono::Duration::days(1);
+            let mut found = HashSet::new();
+            for completed in self.client.list_completed_tasks(since).await? {
+                // A task checked off more than once counts with its latest completion
+                if completed.id.as_ref().is_some_and(|id| unlisted.contains(id) && found.insert(id.clone())) {
+                    todoist_tasks.push(completed);
+                }
+            }
+        }
+
+        Ok(todoist_tasks)
+    }
+
+    async fn create(&mut self, task: &Task, context: &SyncContext<'_, TodoistTask>) -> Result<TodoistTask, TodoistError> {
+        // Ensure project exists if task has tags
+        let project_id = match self.project_name(task) {
+            Some(name) => self.get_or_create_project(&name).await,
+            None => None,
+        };
+
+        let mut todoist_task = self.convert_yarmtl_to_todoist(task, context);
+        // Subtasks are in their parent's section
+        if todoist_task.parent_id.is_none() {
+            todoist_task.section_id = self.section_for(project_id.as_deref(), task.project.as_deref()).await;
+        }
+        self.client.create_task(&todoist_task).await
+    }
+
+    async fn update(
+        &mut self,
+        todoist_id: &str,
+        task: &Task,
+        context: &SyncContext<'_, TodoistTask>,
+    ) -> Result<TodoistTask, TodoistError> {
+        // Ensure project exists if task has tags
+        if let Some(name) = self.project_name(task) {
+            self.get_or_create_project(&name).await;
+        }
+
+        // Updates don't take a parent or an order, moving is a call of its own
+        let todoist_task = TodoistTask { parent_id: None, order: None, ..self.convert_yarmtl_to_todoist(task, context) };
+        let mut updated = self.client.update_task(todoist_id, &todoist_task).await?;
+
+        let parent_id = task
+            .parent_id
             .as_ref()
-            .and_then(|d| YarmtlMetadata::parse(d))
+            .and_then(|id| context.metadata.get_todoist_id(id))
+            .map(str::to_string);
+        // The heading's section in whichever project the task is in, subtasks are in their parent's
+        let section_id = match parent_id {
+            Some(_) => None,
+            None => self.section_for(updated.project_id.clone().as_deref(), task.project.as_deref()).await,
+        };
+        let remote = context.remote_tasks.get(todoist_id);
+        let moved = remote.and_then(|t| t.parent_id.as_ref()) != parent_id.as_ref()
+            || (parent_id.is_none() && remote.and_then(|t| t.section_id.as_ref()) != section_id.as_ref());
+        if moved {
+            self.client
+                .move_task(todoist_id, parent_id.as_deref(), section_id.as_deref(), updated.project_id.as_deref())
+                .await?;
+        }
+        updated.parent_id = parent_id;
+        updated.section_id = section_id;
+        Ok(updated)
+    }
+
+    async fn complete(&mut self, todoist_task: &mut TodoistTask, completed: bool) -> Result<(), TodoistError> {
+        let Some(todoist_id) = todoist_task.id.clone() else {
+            return Ok(());
+        };
+        match completed {
+            true => self.client.close_task(&todoist_id).await?,
+            false => self.client.reopen_task(&todoist_id).await?,
+        }
+        todoist_task.is_completed = Some(completed);
+        Ok(())
+    }
+
+    async fn delete(&mut self, todoist_id: &str, _context: &SyncContext<'_, TodoistTask>) -> Result<(), TodoistError> {
+        self.client.delete_task(todoist_id).await
+    }
+
+    async fn reorder(&mut self, todoist_ids: &[String]) -> Result<(), TodoistError> {
+        self.client.reorder_tasks(todoist_ids).await
+    }
+
+    fn to_task(&self, todoist_task: &TodoistTask) -> Task {
+        self.convert_todoist_to_yarmtl(todoist_task)
+    }
+
+    fn pull(&self, local: &Task, todoist_task: &TodoistTask) -> Task {
+        let yarmtl_task = self.convert_todoist_to_yarmtl(todoist_task);
+        // Keep the local dates, time tracking and nesting. Subtasks are under their
+        // parent's heading whatever the section
+        let project = match local.parent_id {
+            Some(_) => local.project.clone(),
+            None => yarmtl_task.project.clone(),
+        };
+        Task {
+            id: local.id.clone(),
+            indent_level: local.indent_level,
+            parent_id: local.parent_id.clone(),
+            project,
+            source: local.source.clone(),
+            completed: local.completed,
+            cancelled: local.cancelled,
+            created: local.created,
+            done: local.done,
+            estimate: local.estimate,
+            repeat: local.repeat,
+            last_fired: local.last_fired,
+            spent: local.spent,
+            pinned: local.pinned,
+            flag: local.flag.clone(),
+            count: local.count,
+            warn: local.warn,
+            ..yarmtl_task
+        }
+    }
+
+    fn remote_hash(&self, todoist_task: &TodoistTask) -> String {
+        compute_remote_hash(todoist_task)
     }
 
-    fn compute_task_hash(&self, task: &Task) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
+    fn remote_parent(&self, todoist_task: &TodoistTask, _remote_tasks: &[&TodoistTask]) -> Option<String> {
+        todoist_task.parent_id.clone()
+    }
 
-        let mut hasher = DefaultHasher::new();
-        task.text.hash(&mut hasher);
-        task.deadline.hash(&mut hasher);
-        task.tags.iter().for_each(|t| t.hash(&mut hasher));
-        task.reminder.hash(&mut hasher);
-        task.completed.hash(&mut hasher);
-        if let Some(ref notes) = task.notes {
-            notes.hash(&mut hasher);
+    fn local_scope(&self, tasks: &[Task]) -> HashSet<String> {
+        self.filter.local_scope(tasks)
+    }
+
+    /// The ones in the mirrored project, when there is one, that the filter lets through.
+    fn remote_scope(&self, tasks: &[&TodoistTask]) -> HashSet<String> {
+        let in_project = |task: &TodoistTask| match &self.filter.project {
+            Some(name) => self.projects.get(name).is_some_and(|id| task.project_id.as_ref() == Some(id)),
+            None => true,
+        };
+        let mut scope = HashSet::new();
+        for task in tasks {
+            let Some(id) = &task.id else { continue };
+            if !in_project(task) {
+                continue;
+            }
+            let parent = task.parent_id.as_ref().map(|id| scope.contains(id));
+            if self.filter.allows(&self.convert_todoist_to_yarmtl(task).tags, parent) {
+                scope.insert(id.clone());
+            }
         }
-        task.importance.hash(&mut hasher);
+        scope
+    }
+
+    fn syncs_completion(&self) -> bool {
+        self.filter.completed
+    }
+}
 
-        format!("{:x}", hasher.finish())
+/// yarmtl importance 1-5 (1 = most) to Todoist priority 1-4 (4 = most). No importance
+/// is priority 1, which Todoist shows as none, so clearing it clears it there too.
+fn importance_to_priority(importance: Option<u8>) -> u8 {
+    match importance {
+        Some(1) => 4,
+        Some(2) => 3,
+        Some(3) => 2,
+        _ => 1,
     }
 }
 
-enum ActionType {
-    CreatedInTodoist,
-    CreatedInYarmtl,
-    UpdatedInTodoist,
-    UpdatedInYarmtl,
-    DeletedFromTodoist,
-    DeletedFromYarmtl,
+/// Todoist priority back to importance. `known` is the importance sent along in the
+/// task's description: as long as it gives the same priority it is kept, since 4, 5 and
+/// none all become priority 1.
+fn priority_to_importance(priority: Option<u8>, known: Option<u8>) -> Option<u8> {
+    let Some(priority) = priority else {
+        return known;
+    };
+    if importance_to_priority(known) == priority {
+        return known;
+    }
+    match priority {
+        4 => Some(1),
+        3 => Some(2),
+        2 => Some(3),
+        _ => None,
+    }
+}
+
+/// Hash of what yarmtl reads from a Todoist task, to notice edits made in Todoist.
+fn compute_remote_hash(todoist_task: &TodoistTask) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = StableHasher::default();
+    todoist_task.content.hash(&mut hasher);
+    todoist_task.description.hash(&mut hasher);
+    todoist_task
+        .due
+        .as_ref()
+        .map(|d| d.date.as_str())
+        .or(todoist_task.due_date.as_deref())
+        .hash(&mut hasher);
+    let mut labels = todoist_task.labels.clone().unwrap_or_default();
+    labels.sort();
+    labels.hash(&mut hasher);
+    todoist_task.priority.hash(&mut hasher);
+    todoist_task.is_completed.unwrap_or(false).hash(&mut hasher);
+    todoist_task.project_id.hash(&mut hasher);
+    // Left out when missing, so tasks hash as they did before subtasks and sections synced
+    match (&todoist_task.parent_id, &todoist_task.section_id) {
+        (Some(parent_id), _) => parent_id.hash(&mut hasher),
+        // A subtask's section is its parent's business
+        (None, Some(section_id)) => section_id.hash(&mut hasher),
+        (None, None) => {}
+    }
+
+    format!("{:x}", hasher.finish())
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TodoistConfig {
+    pub enabled: bool,
+    pub project_id: Option<String>,
+    pub auto_sync: bool,
+    pub last_sync_timestamp: Option<String>,
+    #[serde(default)]
+    pub conflict_strategy: sync_engine::ConflictStrategy,
+    /// Fetch only what changed since the last sync, through Todoist's Sync API
+    #[serde(default = "incremental_by_default")]
+    pub incremental: bool,
+    /// How often the daemon syncs, 0 for never
+    #[serde(default = "sync_interval_by_default")]
+    pub sync_interval_minutes: u64,
+    /// How long after the last save in the TUI or the CLI its sync starts
+    #[serde(default = "debounce_by_default")]
+    pub debounce_seconds: u64,
+    /// Which tasks sync
+    #[serde(default)]
+    pub sync: SyncFilter,
+}
+
+fn incremental_by_default() -> bool {
+    true
+}
+
+fn sync_interval_by_default() -> u64 {
+    15
+}
+
+fn debounce_by_default() -> u64 {
+    5
+}
+
+impl Default for TodoistConfig {
+    fn default() -> Self {
+        TodoistConfig {
+            enabled: true,
+            project_id: None,
+            auto_sync: true,
+            last_sync_timestamp: None,
+            conflict_strategy: sync_engine::ConflictStrategy::default(),
+            incremental: true,
+            sync_interval_minutes: sync_interval_by_default(),
+            debounce_seconds: debounce_by_default(),
+            sync: SyncFilter::default(),
+        }
+    }
+}
+
+/// todoist_config.toml, None until `--setup-todoist` wrote it (or when it can't be read).
+pub fn load_todoist_config() -> Option<TodoistConfig> {
+    let content = fs::read_to_string(get_todoist_config_path()).ok()?;
+    toml::from_str(&content).ok()
+}
+
+pub fn is_todoist_sync_enabled() -> bool {
+    load_todoist_config().is_some_and(|config| config.enabled && config.auto_sync)
+}
+
+/// How long saves have to stop for before the sync they ask for starts.
+pub fn todoist_sync_debounce() -> Duration {
+    let seconds = load_todoist_config().map_or_else(debounce_by_default, |config| config.debounce_seconds);
+    Duration::from_secs(seconds)
+}
+
+/// Written by each CLI save that asks for a sync, read back by the `sync --after-save` it starts.
+fn sync_request_path() -> PathBuf {
+    get_state_dir().join(".sync_request")
+}
+
+/// Asks for a Todoist sync once the CLI has been left alone for the debounce time. The
+/// `yarmtl sync --after-save` started here waits it out in the background, and only the
+/// one started by the last save goes on to sync.
+pub fn request_sync_after_save() {
+    if !is_todoist_sync_enabled() {
+        return;
+    }
+    let request = ids::generate();
+    if let Err(e) = fs::write(sync_request_path(), &request) {
+        tracing::warn!("couldn't ask for a Todoist sync: {}", e);
+        return;
+    }
+    let started = env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .args(["sync", "--after-save", &request])
+            .args((!git::is_enabled()).then_some("--no-git"))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    });
+    if let Err(e) = started {
+        tracing::warn!("couldn't start a Todoist sync: {}", e);
+    }
+}
+
+/// `yarmtl sync --after-save`: syncs after the debounce time, unless a later save asked
+/// for a sync in the meantime.
+pub async fn sync_after_save(request: &str) {
+    tokio::time::sleep(todoist_sync_debounce()).await;
+    if fs::read_to_string(sync_request_path()).ok().as_deref() == Some(request) {
+        let _ = trigger_todoist_sync().await;
+    }
+}
+
+/// Runs a Todoist sync if it is configured. Returns None when sync is disabled or no token is set.
+pub async fn trigger_todoist_sync() -> Result<Option<sync_engine::SyncReport>, YarmtlError> {
+    trigger_todoist_sync_with_progress(|_| {}).await
+}
+
+pub async fn trigger_todoist_sync_with_progress(
+    on_progress: impl FnMut(sync_engine::SyncProgress),
+) -> Result<Option<sync_engine::SyncReport>, YarmtlError> {
+    if !is_todoist_sync_enabled() {
+        return Ok(None);
+    }
+
+    let api_token = match todoist_auth::TodoistAuth::get_token() {
+        Ok(token) => token,
+        Err(_) => return Ok(None), // No token configured, skip sync
+    };
+
+    run_todoist_sync(api_token, on_progress, |_| true).await.map(Some)
+}
+
+/// Syncs with Todoist, taking the actions `approve` agrees to, and commits what the
+/// sync changed in tasks.md.
+pub async fn run_todoist_sync(
+    api_token: String,
+    mut on_progress: impl FnMut(sync_engine::SyncProgress),
+    approve: impl FnMut(&sync_engine::SyncAction<todoist_types::TodoistTask>) -> bool,
+) -> Result<sync_engine::SyncReport, YarmtlError> {
+    let sync_dir = get_sync_dir();
+    let tasks_file = get_tasks_file_path();
+    let _lock = wait_for_sync_lock(&mut on_progress).await.map_err(YarmtlError::io("lock", &sync_dir))?;
+
+    let config = load_todoist_config().unwrap_or_default();
+    let provider = TodoistProvider::new(api_token, config.incremental, config.sync);
+    let failed = |e: Box<dyn std::error::Error>| YarmtlError::Sync(e.to_string());
+    let mut sync = sync_engine::SyncEngine::new(provider, &sync_dir, config.conflict_strategy).map_err(failed)?;
+    let report = sync.sync_with_approval(&tasks_file, on_progress, approve).await.map_err(failed)?;
+    after_sync(&report);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_are_headings() {
+        let mut provider = TodoistProvider::new("token".to_string(), true, SyncFilter::default());
+        provider.sections = vec![TodoistSection { id: "s1".to_string(), name: "Home".to_string(), project_id: "p1".to_string() }];
+        let local = Task::parse("call bob [id:abc123]");
+        let remote = TodoistTask {
+            id: Some("t1".to_string()),
+            content: "call bob".to_string(),
+            description: None,
+            due: None,
+            due_date: None,
+            labels: None,
+            priority: Some(1),
+            is_completed: Some(false),
+            project_id: None,
+            section_id: None,
+            parent_id: None,
+            order: Some(1),
+            updated_at: None,
+        };
+
+        // Moved into a section in Todoist, under its heading here
+        let moved = TodoistTask { section_id: Some("s1".to_string()), ..remote.clone() };
+        assert_ne!(provider.remote_hash(&moved), provider.remote_hash(&remote));
+        let pulled = provider.pull(&local, &moved);
+        assert_eq!((pulled.id.as_str(), pulled.project.as_deref()), ("abc123", Some("Home")));
+        assert_eq!(provider.to_task(&moved).project.as_deref(), Some("Home"));
+    }
+
+    #[test]
+    fn test_priority_round_trips_into_importance() {
+        for importance in [None, Some(1), Some(2), Some(3), Some(4), Some(5)] {
+            assert_eq!(priority_to_importance(Some(importance_to_priority(importance)), importance), importance);
+        }
+        // Changed in Todoist
+        assert_eq!(priority_to_importance(Some(4), Some(3)), Some(1));
+        assert_eq!(priority_to_importance(Some(1), Some(2)), None);
+        assert_eq!(priority_to_importance(Some(3), None), Some(2));
+        assert_eq!(priority_to_importance(None, Some(5)), Some(5));
+    }
 }
@@ -1,5 +1,14 @@
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+/// The markers of the yarmtl metadata in a todoist task's description, compiled once:
+/// a sync reads every task's.
+static ID_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[yarmtl:([0-9a-z-]+)\]").unwrap());
+static DEADLINE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"!(\d{4}-\d{2}-\d{2})").unwrap());
+static REMINDER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"@(\d{4}-\d{2}-\d{2})").unwrap());
+static IMPORTANCE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$([1-5])").unwrap());
+static NOTES_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"//([^$@!\[]+)").unwrap());
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TodoistTask {
@@ -20,6 +29,18 @@ pub struct TodoistTask {
     pub is_completed: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_id: Option<String>,
+    /// Section of the project the task is in, subtasks are in their parent's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section_id: Option<String>,
+    /// The task this is a subtask of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// Position among the tasks with the same parent, only taken when creating a task
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<i64>,
+    /// RFC 3339, only sent by the newer Todoist APIs; newest-wins conflict resolution needs it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,11 +52,122 @@ pub struct TodoistDue {
     pub timezone: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TodoistLabel {
+/// A task as the Sync API sends it, changed since the last sync token.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TodoistItem {
+    pub id: String,
+    pub content: String,
+    #[serde(default)]
+    pub description: String,
+    pub due: Option<TodoistDue>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    pub priority: Option<u8>,
+    #[serde(default)]
+    pub checked: bool,
+    #[serde(default)]
+    pub is_deleted: bool,
+    pub project_id: Option<String>,
+    pub section_id: Option<String>,
+    pub parent_id: Option<String>,
+    pub child_order: Option<i64>,
+    pub updated_at: Option<String>,
+}
+
+impl TodoistItem {
+    /// Still listed by the REST API: not completed and not deleted.
+    pub fn is_active(&self) -> bool {
+        !self.checked && !self.is_deleted
+    }
+}
+
+impl From<TodoistItem> for TodoistTask {
+    /// The task the REST API would have sent, so hashes of either compare.
+    fn from(item: TodoistItem) -> Self {
+        // The Sync API puts the time of day in `date`, REST in `datetime`
+        let due = item.due.map(|due| match due.date.split_once('T') {
+            Some((date, _)) => TodoistDue {
+                date: date.to_string(),
+                datetime: due.datetime.or(Some(due.date.clone())),
+                timezone: due.timezone,
+            },
+            None => due,
+        });
+        TodoistTask {
+            id: Some(item.id),
+            content: item.content,
+            description: Some(item.description),
+            due,
+            due_date: None,
+            labels: Some(item.labels),
+            priority: item.priority,
+            is_completed: Some(item.checked),
+            project_id: item.project_id,
+            section_id: item.section_id,
+            parent_id: item.parent_id,
+            order: item.child_order,
+            updated_at: item.updated_at,
+        }
+    }
+}
+
+/// A task checked off in Todoist, as the Sync API's completed tasks endpoint sends it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TodoistCompletedItem {
+    /// The task itself, sent with `annotate_items`
+    pub item_object: Option<TodoistItem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TodoistCompletedResponse {
+    #[serde(default)]
+    pub items: Vec<TodoistCompletedItem>,
+}
+
+/// A project as the Sync API sends it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TodoistSyncProject {
     pub id: String,
     pub name: String,
-    pub color: String,
+    pub color: Option<String>,
+    #[serde(default)]
+    pub inbox_project: bool,
+    #[serde(default)]
+    pub is_deleted: bool,
+    #[serde(default)]
+    pub is_archived: bool,
+}
+
+/// A section as the Sync API sends it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TodoistSyncSection {
+    pub id: String,
+    pub name: String,
+    pub project_id: String,
+    #[serde(default)]
+    pub is_deleted: bool,
+    #[serde(default)]
+    pub is_archived: bool,
+}
+
+impl TodoistSyncSection {
+    pub fn is_active(&self) -> bool {
+        !self.is_deleted && !self.is_archived
+    }
+}
+
+/// What changed since the sync token sent, or everything when `full_sync`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TodoistSyncResponse {
+    pub sync_token: String,
+    #[serde(default)]
+    pub full_sync: bool,
+    #[serde(default)]
+    pub items: Vec<TodoistItem>,
+    #[serde(default)]
+    pub projects: Vec<TodoistSyncProject>,
+    #[serde(default)]
+    pub sections: Vec<TodoistSyncSection>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,6 +176,17 @@ pub struct TodoistProject {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub color: Option<String>,
+    /// Where tasks without a project go
+    #[serde(default)]
+    pub is_inbox_project: bool,
+}
+
+/// A part of a project, what a `## ` heading of tasks.md becomes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TodoistSection {
+    pub id: String,
+    pub name: String,
+    pub project_id: String,
 }
 
 #[derive(Debug, Clone)]
@@ -87,32 +230,27 @@ impl YarmtlMetadata {
 
     pub fn parse(description: &str) -> Option<Self> {
         // Extract yarmtl ID - if not present, this isn't a yarmtl task
-        let id_re = Regex::new(r"\[yarmtl:([a-f0-9-]+)\]").ok()?;
-        let id = id_re.captures(description)
+        let id = ID_RE.captures(description)
             .and_then(|cap| cap.get(1))
             .map(|m| m.as_str().to_string())?;
 
         // Extract deadline (!date)
-        let deadline_re = Regex::new(r"!(\d{4}-\d{2}-\d{2})").ok()?;
-        let deadline = deadline_re.captures(description)
+        let deadline = DEADLINE_RE.captures(description)
             .and_then(|cap| cap.get(1))
             .map(|m| m.as_str().to_string());
 
         // Extract reminder (@date)
-        let reminder_re = Regex::new(r"@(\d{4}-\d{2}-\d{2})").ok()?;
-        let reminder = reminder_re.captures(description)
+        let reminder = REMINDER_RE.captures(description)
             .and_then(|cap| cap.get(1))
             .map(|m| m.as_str().to_string());
 
         // Extract importance ($1-5)
-        let importance_re = Regex::new(r"\$([1-5])").ok()?;
-        let importance = importance_re.captures(description)
+        let importance = IMPORTANCE_RE.captures(description)
             .and_then(|cap| cap.get(1))
             .and_then(|m| m.as_str().parse().ok());
 
         // Extract notes (//text)
-        let notes_re = Regex::new(r"//([^$@!\[]+)").ok()?;
-        let notes = notes_re.captures(description)
+        let notes = NOTES_RE.captures(description)
             .and_then(|cap| cap.get(1))
             .map(|m| m.as_str().trim().to_string());
 
@@ -151,12 +289,36 @@ mod tests {
         let decoded = YarmtlMetadata::parse(&encoded).unwrap();
 
         assert_eq!(decoded.id, "abc12345");
+        assert_eq!(YarmtlMetadata::parse("[yarmtl:x7kq2m]").unwrap().id, "x7kq2m");
+        // A task as Todoist returns it: its own id is alphanumeric, ours is base32
+        let task: TodoistTask = serde_json::from_str(
+            r#"{"id":"6X7rM8997g3RQmvh","content":"call bob","description":"//ask about the keys [yarmtl:zq3v9w]"}"#,
+        ).unwrap();
+        assert_eq!(task.id.as_deref(), Some("6X7rM8997g3RQmvh"));
+        assert_eq!(YarmtlMetadata::parse(&task.description.unwrap()).unwrap().id, "zq3v9w");
         assert_eq!(decoded.deadline, Some("2026-01-30".to_string()));
         assert_eq!(decoded.reminder, Some("2026-01-28".to_string()));
         assert_eq!(decoded.notes, Some("Important task".to_string()));
         assert_eq!(decoded.importance, Some(3));
     }
 
+    #[test]
+    fn test_sync_item_reads_like_a_rest_task() {
+        let response: TodoistSyncResponse = serde_json::from_str(
+            r#"{"sync_token":"abc","full_sync":false,"items":[{"id":"6X7","content":"call bob",
+                "description":"","due":{"date":"2025-06-01T10:00:00","timezone":null},"labels":[],
+                "priority":1,"checked":false,"is_deleted":false,"project_id":"2203","parent_id":null,
+                "child_order":3}]}"#,
+        )
+        .unwrap();
+        let item = response.items[0].clone();
+        assert!(item.is_active());
+        let task = TodoistTask::from(item);
+        let due = task.due.unwrap();
+        assert_eq!((due.date.as_str(), due.datetime.as_deref()), ("2025-06-01", Some("2025-06-01T10:00:00")));
+        assert_eq!((task.order, task.is_completed, task.description.as_deref()), (Some(3), Some(false), Some("")));
+    }
+
     #[test]
     fn test_metadata_parse_none() {
         let description = "Regular task description without metadata";
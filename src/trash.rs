@@ -0,0 +1,139 @@
+use crate::encryption;
+use crate::task::{self, Task};
+use chrono::NaiveDate;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Deleted tasks are kept this long before the purge drops them for good.
+pub const TRASH_RETENTION_DAYS: i64 = 30;
+
+/// A deleted task and the day it was deleted. In `.trash.md` tasks are grouped
+/// under a `## YYYY-MM-DD` heading per deletion day, newest first.
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    pub deleted: NaiveDate,
+    pub task: Task,
+}
+
+pub fn trash_file(tasks_dir: &Path) -> PathBuf {
+    encryption::file_in(tasks_dir, ".trash.md")
+}
+
+pub fn parse_trash(content: &str) -> Vec<TrashEntry> {
+    let mut entries = Vec::new();
+    let mut deleted = None;
+
+    for line in content.lines() {
+        if let Some(date) = line.strip_prefix("## ") {
+            deleted = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok();
+            continue;
+        }
+
+        if let (Some(deleted), Some(task)) = (deleted, task::parse_checkbox_line(line)) {
+            entries.push(TrashEntry { deleted, task });
+        }
+    }
+
+    entries
+}
+
+pub fn render_trash(entries: &[TrashEntry]) -> String {
+    let mut sorted: Vec<&TrashEntry> = entries.iter().collect();
+    sorted.sort_by_key(|entry| std::cmp::Reverse(entry.deleted));
+
+    let mut content = String::from("# trash\n");
+    let mut current = None;
+    for entry in sorted {
+        if current != Some(entry.deleted) {
+            content.push_str(&format!("\n## {}\n", entry.deleted.format("%Y-%m-%d")));
+            current = Some(entry.deleted);
+        }
+        // Subtasks come back as top-level tasks, their parent may be long gone
+        let task = Task { indent_level: 0, parent_id: None, ..entry.task.clone() };
+        content.push_str(&format!("{}\n", task.to_markdown()));
+    }
+
+    content
+}
+
+/// Newest deletions first.
+pub fn load_trash(tasks_dir: &Path) -> Vec<TrashEntry> {
+    let mut entries = encryption::read(&trash_file(tasks_dir))
+        .map(|content| parse_trash(&content))
+        .unwrap_or_default();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted));
+    entries
+}
+
+pub fn save_trash(tasks_dir: &Path, entries: &[TrashEntry]) -> io::Result<()> {
+    encryption::write(&trash_file(tasks_dir), &render_trash(entries))
+}
+
+pub fn move_to_trash(tasks_dir: &Path, task: Task, today: NaiveDate) -> io::Result<()> {
+    let mut entries = load_trash(tasks_dir);
+    entries.push(TrashEntry { deleted: today, task });
+    save_trash(tasks_dir, &entries)
+}
+
+/// Drops entries deleted more than `TRASH_RETENTION_DAYS` ago, along with
+/// their attachments, and returns how many were purged.
+pub fn purge_expired(tasks_dir: &Path, today: NaiveDate) -> io::Result<usize> {
+    let cutoff = today - chrono::Duration::days(TRASH_RETENTION_DAYS);
+    let (kept, expired): (Vec<TrashEntry>, Vec<TrashEntry>) = load_trash(tasks_dir)
+        .into_iter()
+        .partition(|entry| entry.deleted >= cutoff);
+
+    if expired.is_empty() {
+        return Ok(0);
+    }
+
+    for entry in &expired {
+        crate::attachments::remove_attachments(tasks_dir, &entry.task.id)?;
+    }
+    save_trash(tasks_dir, &kept)?;
+    Ok(expired.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    #[test]
+    fn test_trash_round_trip() {
+        let mut done = Task::parse("old errand [id:aaaa1111]");
+        done.completed = true;
+        let entries = vec![
+            TrashEntry { deleted: date(1), task: done },
+            TrashEntry { deleted: date(9), task: Task::parse("call bank [id:bbbb2222] #home") },
+        ];
+
+        let content = render_trash(&entries);
+        assert!(content.find("## 2025-06-09").unwrap() < content.find("## 2025-06-01").unwrap());
+
+        let parsed = parse_trash(&content);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].task.id, "bbbb2222");
+        assert_eq!(parsed[0].task.tags, vec!["home"]);
+        assert_eq!(parsed[1].deleted, date(1));
+        assert!(parsed[1].task.completed);
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let temp = tempfile::tempdir().unwrap();
+        let tasks_dir = temp.path();
+
+        let today = NaiveDate::from_ymd_opt(2025, 7, 15).unwrap();
+        move_to_trash(tasks_dir, Task::parse("ancient"), date(1)).unwrap();
+        move_to_trash(tasks_dir, Task::parse("recent"), date(20)).unwrap();
+
+        assert_eq!(purge_expired(tasks_dir, today).unwrap(), 1);
+        let remaining = load_trash(tasks_dir);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].task.text, "recent");
+    }
+}
@@ -1,41 +1,223 @@
-use crate::{Task, git_commit_tasks_with_message, is_todoist_sync_enabled, trigger_todoist_sync};
+use yarmtl::status_line::{self, GitSummary, StatusContext, StatusWidget};
+use yarmtl::keymap::{self, HelpLine};
+use yarmtl::links::{self, LinkTarget};
+use yarmtl::trash::{self, TrashEntry};
+use yarmtl::watch::FileWatcher;
+use yarmtl::{archive, attachments, dates, effort, encryption, ids, logging, output, recurrence, stats, subtasks};
+use yarmtl::hooks::{self, HookPoint};
+use yarmtl::rules::{self, Rule};
+use yarmtl::clock::{Clock, SystemClock};
+use yarmtl::theme::{self, Theme};
+use yarmtl::calendar::{self, CalendarEvent, CalendarSubscription};
+use yarmtl::config::{CommitPolicy, ListLayout};
+use yarmtl::error::YarmtlError;
+use yarmtl::store::TaskStore;
+use yarmtl::task::{Task, format_duration};
+use yarmtl::task_table::{self, Column};
+use yarmtl::setup::{self, SetupWizard, Step, TokenCheck};
+use yarmtl::todoist_auth::TodoistAuth;
+use yarmtl::sync_engine::{SyncProgress, SyncReport};
+use yarmtl::sync_metadata::SyncMetadata;
+use yarmtl::config::load_config;
+use yarmtl::git::{self, GitCommitOutcome, GitState, PullOutcome, git_commit_tasks_with_message, git_state};
+use yarmtl::paths::{get_email_config_path, state_dir_of};
+use yarmtl::todoist_sync::{is_todoist_sync_enabled, todoist_sync_debounce, trigger_todoist_sync_with_progress};
+use yarmtl::webhooks::request_webhooks_after_save;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{
-        Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap,
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap,
     },
     Frame, Terminal,
 };
+use chrono::NaiveDate;
 use std::{
-    fs,
+    collections::{HashMap, HashSet},
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant},
 };
 
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+const ERROR_TOAST_DURATION: Duration = Duration::from_secs(6);
+const MAX_TOASTS: usize = 3;
+const GIT_STATUS_INTERVAL: Duration = Duration::from_secs(10);
+/// How old the calendar events may get before opening the week agenda fetches them again
+const CALENDAR_REFRESH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// When a task was added and completed, as git remembers it.
+type GitDates = (Option<NaiveDate>, Option<NaiveDate>);
+
 pub struct App {
     pub tasks: Vec<Task>,
+    /// tasks.md as last read or written, what a save merges against
+    store: Option<TaskStore>,
+    /// Tells when something else writes tasks.md
+    watcher: Option<FileWatcher>,
     pub list_state: ListState,
     pub input_mode: InputMode,
     pub input: String,
     pub show_completed: bool,
+    /// Only cancelled tasks are listed, otherwise they are never shown
+    pub show_cancelled: bool,
     pub show_help: bool,
-    pub show_splash: bool,
-    pub splash_timer: std::time::Instant,
+    pub help_scroll: u16,
+    pub help_filter: String,
+    /// Typing into the help filter rather than scrolling
+    pub help_filtering: bool,
+    /// What was logged while the TUI runs, with the newest line at the bottom
+    pub show_log: bool,
+    pub log_scroll: u16,
+    /// The first-run wizard, shown instead of everything else while it's open
+    pub setup: Option<SetupWizard>,
     pub show_notes: bool,
     pub selected_task_for_notes: Option<usize>,
+    /// Which of the notes popup's links/backlinks Enter jumps to
+    pub notes_link_cursor: usize,
     pub working_dir: PathBuf,
     pub view_mode: ViewMode,
     pub tags_list_state: ListState,
     pub selected_tag: Option<String>,
+    /// Context filter picked from the same menu, never set together with `selected_tag`
+    pub selected_context: Option<String>,
+    /// Sections by `## ` project heading instead of by deadline
+    pub group_by_project: bool,
     pub sync_status: Option<String>,
+    pub theme: Theme,
+    pub toasts: Vec<Toast>,
+    pub confirm: Option<Confirm>,
+    pub status_widgets: Vec<StatusWidget>,
+    pub layout: ListLayout,
+    pub pomodoro_length: Duration,
+    pub pomodoro_started: Option<Instant>,
+    /// Id of the task the running pomodoro is logged on
+    pub pomodoro_task: Option<String>,
+    /// From `daily_capacity_hours`, None when the warning is off
+    pub daily_capacity: Option<Duration>,
+    /// Whether the work due today was over capacity at the last check, so the warning shows once
+    over_capacity: bool,
+    pub auto_complete_parents: bool,
+    pub archive_after_days: u32,
+    /// `[[rules]]` from config.toml, applied at startup; tasks they hold for are marked ⚡
+    pub rules: Vec<Rule>,
+    /// Where today comes from for the due-date grouping, reminders and new tasks
+    pub clock: Box<dyn Clock>,
+    /// Added/completed dates looked up in git for tasks that don't record them, by task id
+    pub dates_from_git: HashMap<String, GitDates>,
+    /// The dates of the task whose notes are open, as git finds them
+    dates_rx: Option<mpsc::Receiver<(String, GitDates)>>,
+    pub trash: Vec<TrashEntry>,
+    pub trash_list_state: ListState,
+    /// Selected line of the zen view
+    pub zen_cursor: usize,
+    pub last_sync: Option<chrono::DateTime<chrono::Utc>>,
+    pub git_state: Option<GitState>,
+    git_checked: Option<Instant>,
+    /// Status bar segment whose details popup is open
+    pub status_detail: Option<StatusWidget>,
+    /// The status bar segments as last drawn, for clicks and the details popup
+    status_segments: Vec<StatusSegment>,
+    sync_rx: Option<mpsc::Receiver<SyncEvent>>,
+    /// When the sync asked for by the last save starts
+    sync_due: Option<Instant>,
+    /// `commit` and `debounce_seconds` of the [git] table of config.toml
+    pub commit_policy: CommitPolicy,
+    pub commit_debounce: Duration,
+    /// The messages of the saves the commit policy hasn't committed yet; None when there are none
+    pending_commit: Option<Vec<String>>,
+    /// When the debounced commit of those saves is made
+    commit_due: Option<Instant>,
+    /// Result of the wizard's Todoist token check
+    token_check_rx: Option<mpsc::Receiver<Result<bool, String>>>,
+    /// `[[calendars]]` from config.toml, shown in the week agenda
+    pub calendars: Vec<CalendarSubscription>,
+    /// Their events of the coming week, as last fetched
+    pub events: Vec<CalendarEvent>,
+    events_fetched: Option<Instant>,
+    events_rx: Option<mpsc::Receiver<(Vec<CalendarEvent>, Vec<String>)>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ToastKind {
+    Info,
+    Success,
+    Error,
+}
+
+/// A short-lived message drawn over the UI, used instead of printing to the terminal.
+pub struct Toast {
+    pub message: String,
+    pub kind: ToastKind,
+    pub created: Instant,
+}
+
+impl Toast {
+    pub fn new(kind: ToastKind, message: impl Into<String>) -> Toast {
+        // Git and network errors often span several lines; a toast has room for one
+        let message = message.into().split_whitespace().collect::<Vec<_>>().join(" ");
+        Toast {
+            message,
+            kind,
+            created: Instant::now(),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        let lifetime = match self.kind {
+            ToastKind::Error => ERROR_TOAST_DURATION,
+            _ => TOAST_DURATION,
+        };
+        self.created.elapsed() >= lifetime
+    }
+}
+
+struct StatusSegment {
+    widget: StatusWidget,
+    text: String,
+    row: u16,
+    columns: std::ops::Range<u16>,
+}
+
+enum SyncEvent {
+    Progress(SyncProgress),
+    Finished(Result<Option<SyncReport>, String>),
+}
+
+fn format_sync_progress(progress: SyncProgress) -> String {
+    let (done, total) = progress.steps();
+    let stage = match progress {
+        SyncProgress::Waiting => "waiting for another sync".to_string(),
+        SyncProgress::FetchingProjects => "projects".to_string(),
+        SyncProgress::FetchingTasks => "tasks".to_string(),
+        SyncProgress::Applying { done, total } => format!("{}/{} changes", done, total),
+        SyncProgress::Saving => "saving".to_string(),
+    };
+    format!("🔄 Syncing {} {}", status_line::braille_bar(done, total, 5), stage)
+}
+
+pub enum ConfirmAction {
+    DeleteTask { task_id: String },
+    /// tasks.md changed on disk while changes here weren't saved
+    ReloadTasks,
+}
+
+/// A pending yes/no question; the action only runs once the user answers `y`.
+pub struct Confirm {
+    pub prompt: String,
+    pub action: ConfirmAction,
 }
 
 #[derive(Clone, PartialEq)]
@@ -48,106 +230,418 @@ pub enum InputMode {
 pub enum ViewMode {
     Tasks,
     TagsMenu,
+    Week,
+    Trash,
+    /// Full screen list of what's due today, most important first
+    Zen,
+}
+
+/// One line of the task list: section headers and spacers are part of the
+/// list so the selection index maps onto rows, not onto tasks.
+#[derive(Clone, PartialEq)]
+pub enum DisplayRow {
+    Header(String),
+    Task(usize),
+    Placeholder(String),
+    /// A calendar event under its day in the week agenda
+    Event(String),
+    Spacer,
 }
 
 impl Default for App {
     fn default() -> App {
         App {
             tasks: Vec::new(),
+            store: None,
+            watcher: None,
             list_state: ListState::default(),
             input_mode: InputMode::Normal,
             input: String::new(),
             show_completed: false,
+            show_cancelled: false,
+            group_by_project: false,
             show_help: false,
-            show_splash: true,
-            splash_timer: std::time::Instant::now(),
+            help_scroll: 0,
+            help_filter: String::new(),
+            help_filtering: false,
+            show_log: false,
+            log_scroll: 0,
+            setup: None,
             show_notes: false,
             selected_task_for_notes: None,
-            working_dir: std::env::current_dir().unwrap(),
+            notes_link_cursor: 0,
+            working_dir: std::env::current_dir().unwrap_or_default(),
             view_mode: ViewMode::Tasks,
             tags_list_state: ListState::default(),
             selected_tag: None,
+            selected_context: None,
             sync_status: None,
+            theme: Theme::dark(),
+            toasts: Vec::new(),
+            confirm: None,
+            status_widgets: status_line::default_widgets(),
+            layout: ListLayout::Inline,
+            pomodoro_length: Duration::from_secs(25 * 60),
+            pomodoro_started: None,
+            pomodoro_task: None,
+            daily_capacity: None,
+            over_capacity: false,
+            auto_complete_parents: false,
+            archive_after_days: 0,
+            rules: Vec::new(),
+            clock: Box::new(SystemClock),
+            dates_from_git: HashMap::new(),
+            dates_rx: None,
+            trash: Vec::new(),
+            trash_list_state: ListState::default(),
+            zen_cursor: 0,
+            last_sync: None,
+            git_state: None,
+            git_checked: None,
+            status_detail: None,
+            status_segments: Vec::new(),
+            sync_rx: None,
+            sync_due: None,
+            commit_policy: CommitPolicy::Immediate,
+            commit_debounce: Duration::from_secs(30),
+            pending_commit: None,
+            commit_due: None,
+            token_check_rx: None,
+            calendars: Vec::new(),
+            events: Vec::new(),
+            events_fetched: None,
+            events_rx: None,
         }
     }
 }
 
 impl App {
-    pub fn new(working_dir: &PathBuf) -> App {
-        let mut app = App::default();
-        app.working_dir = working_dir.clone();
-        
-        // Check if this is the first run
-        let settings_file = working_dir.join(".yarmtl_settings");
-        let is_first_run = !settings_file.exists();
-        
-        if is_first_run {
-            // Create settings file to track that app has been run
-            let _ = std::fs::write(&settings_file, "first_run_completed=true\n");
-            app.show_splash = true;
-        } else {
-            app.show_splash = false;
+    pub fn new(working_dir: &Path) -> App {
+        let mut app = App {
+            working_dir: working_dir.to_path_buf(),
+            ..App::default()
+        };
+
+        if setup::needs_setup(working_dir) {
+            app.setup = Some(SetupWizard::new());
         }
-        
+
         app.load_tasks();
         if !app.tasks.is_empty() {
             app.list_state.select(Some(0));
         }
+
+        app.refresh_status_info();
+
+        // Purge on startup so the trash never holds more than a month of deletions
+        match trash::purge_expired(&app.working_dir, app.clock.today()) {
+            Ok(0) => {}
+            Ok(purged) => app.notify(ToastKind::Info, format!("🗑️ Purged {} old task(s) from trash", purged)),
+            Err(e) => app.notify(ToastKind::Error, format!("⚠ Failed to purge trash: {}", e)),
+        }
         app
     }
 
     pub fn load_tasks(&mut self) {
-        let task_file = self.working_dir.join("tasks.md");
-        
-        if !task_file.exists() {
-            return;
+        let task_file = encryption::file_in(&self.working_dir, "tasks.md");
+        let store = TaskStore::open(&task_file);
+        // tasks.md is watched even when it can't be read, to try again once it changes
+        let files = store.as_ref().map_or_else(|_| vec![task_file], |store| store.files());
+        if self.watcher.as_ref().is_none_or(|watcher| watcher.paths() != files) {
+            match FileWatcher::new(&files) {
+                Ok(watcher) => self.watcher = Some(watcher),
+                Err(e) => self.notify(ToastKind::Error, format!("⚠ {}", e)),
+            }
         }
-        
-        let content = match fs::read_to_string(&task_file) {
-            Ok(content) => content,
-            Err(_) => return,
+        let store = match store {
+            Ok(store) => store,
+            Err(e) => {
+                self.notify(ToastKind::Error, format!("⚠ {}", e));
+                return;
+            }
         };
-        
-        self.tasks.clear();
-        for line in content.lines() {
-            // Count leading spaces to determine indentation level
-            let _indent_level = line.chars().take_while(|&c| c == ' ').count() / 2;
-            let trimmed_line = line.trim_start();
-            
-            if trimmed_line.starts_with("- [ ]") || trimmed_line.starts_with("- [x]") {
-                let completed = trimmed_line.starts_with("- [x]");
-                let task_text = if completed {
-                    trimmed_line.strip_prefix("- [x] ").unwrap_or(trimmed_line)
-                } else {
-                    trimmed_line.strip_prefix("- [ ] ").unwrap_or(trimmed_line)
-                };
-                
-                let mut task = Task::parse(task_text);
-                task.completed = completed;
-                self.tasks.push(task);
+        self.tasks = store.tasks().to_vec();
+        self.store = Some(store);
+        match ids::fix_task_ids(&self.working_dir, &mut self.tasks) {
+            Ok(renames) if !renames.is_empty() => {
+                let message = format!("🔑 Gave {} task(s) new ids", renames.len());
+                self.save_tasks_with_message(Some(&message));
             }
+            Ok(_) => {}
+            Err(e) => self.notify(ToastKind::Error, format!("⚠ Failed to save {}: {}", ids::FILE_NAME, e)),
         }
+        self.check_capacity();
     }
 
+    /// Reloads tasks.md once something else wrote it: an editor, a `git pull`, a sync. When
+    /// changes made here couldn't be saved, asks first, as reloading drops them.
+    pub fn update_file_watch(&mut self) {
+        if !self.watcher.as_mut().is_some_and(|watcher| watcher.changed()) {
+            return;
+        }
+        let Some(store) = &self.store else {
+            self.load_tasks();
+            return;
+        };
+        // Our own saves are seen too
+        if !store.is_stale() || self.confirm.is_some() {
+            return;
+        }
+        if store.is_changed() || store.tasks() != self.tasks {
+            self.confirm = Some(Confirm {
+                prompt: "tasks.md changed on disk, reload it and drop the unsaved changes? (y/n)".to_string(),
+                action: ConfirmAction::ReloadTasks,
+            });
+            return;
+        }
+        self.reload_changed_tasks();
+    }
 
-    pub fn save_tasks_with_message(&self, commit_message: Option<&str>) {
-        let task_file = self.working_dir.join("tasks.md");
-        let mut content = String::from("# tasks\n\n");
+    /// Reads tasks.md again, keeping the selected task selected.
+    fn reload_changed_tasks(&mut self) {
+        let selected = self.selected_task_index().map(|index| self.tasks[index].id.clone());
+        self.load_tasks();
+        if let Some(index) = selected.and_then(|id| self.tasks.iter().position(|t| t.id == id)) {
+            self.select_task(index);
+        }
+        self.refresh_status_info();
+        self.notify(ToastKind::Info, "↻ tasks.md changed on disk, reloaded");
+    }
 
-        for task in &self.tasks {
-            content.push_str(&format!("{}\n", task.to_markdown()));
+    /// Warns once the estimates of the work due today add up to more than the daily capacity.
+    pub fn check_capacity(&mut self) {
+        let planned = effort::planned_for(&self.tasks, self.clock.today());
+        let over = self.daily_capacity.is_some_and(|capacity| planned > capacity);
+        if over && !self.over_capacity {
+            self.notify(
+                ToastKind::Error,
+                format!("⏱ Work due today: {}", effort::describe_planned(planned, self.daily_capacity)),
+            );
+        }
+        self.over_capacity = over;
+    }
+
+
+    pub fn notify(&mut self, kind: ToastKind, message: impl Into<String>) {
+        self.toasts.push(Toast::new(kind, message));
+        if self.toasts.len() > MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+    }
+
+    /// Collects toasts sent from background tasks and drops the ones that have timed out.
+    pub fn update_toasts(&mut self) {
+        // Warnings logged while the TUI has the screen; the rest waits in the log pane
+        for line in logging::take_alerts() {
+            self.notify(ToastKind::Error, format!("⚠ {}", line.message));
+        }
+        self.toasts.retain(|t| !t.is_expired());
+        if self.toasts.len() > MAX_TOASTS {
+            let excess = self.toasts.len() - MAX_TOASTS;
+            self.toasts.drain(..excess);
+        }
+    }
+
+    /// Re-reads the last Todoist sync time and the git state for the status bar.
+    pub fn refresh_status_info(&mut self) {
+        let metadata_path = state_dir_of(&self.working_dir).join(".sync_metadata.json");
+        self.last_sync = if metadata_path.exists() {
+            SyncMetadata::load(&metadata_path).ok().map(|m| m.last_sync)
+        } else {
+            None
+        };
+        self.git_state = git_state(&self.working_dir).ok();
+        self.git_checked = Some(Instant::now());
+    }
+
+    /// Git can change behind our back (a manual commit or push), so look again now and then.
+    pub fn update_status_info(&mut self) {
+        if self.git_checked.is_none_or(|checked| checked.elapsed() >= GIT_STATUS_INTERVAL) {
+            self.refresh_status_info();
+        }
+    }
+
+    pub fn open_status_details(&mut self) {
+        self.status_detail = self.status_segments.first().map(|segment| segment.widget);
+    }
+
+    /// Moves the details popup to the next (or previous) visible segment.
+    pub fn cycle_status_detail(&mut self, forward: bool) {
+        let widgets: Vec<StatusWidget> = self.status_segments.iter().map(|segment| segment.widget).collect();
+        let Some(current) = self.status_detail.and_then(|w| widgets.iter().position(|&x| x == w)) else {
+            return;
+        };
+        let next = if forward {
+            (current + 1) % widgets.len()
+        } else {
+            (current + widgets.len() - 1) % widgets.len()
+        };
+        self.status_detail = Some(widgets[next]);
+    }
+
+    /// Clicking a status bar segment opens its details; clicking anywhere else closes them.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+            return;
+        }
+        self.status_detail = self
+            .status_segments
+            .iter()
+            .find(|segment| segment.row == mouse.row && segment.columns.contains(&mouse.column))
+            .map(|segment| segment.widget);
+    }
+
+    /// The task under the cursor of the task list, the week agenda or zen mode.
+    fn selected_task_index(&self) -> Option<usize> {
+        match self.view_mode {
+            ViewMode::Zen => self.get_zen_tasks().get(self.zen_cursor).copied(),
+            _ => self.get_task_index_from_display_position(self.list_state.selected()?),
+        }
+    }
+
+    /// Opens the first attachment or link of the selected task.
+    pub fn open_selected_attachment(&mut self) {
+        let Some(task_index) = self.selected_task_index() else { return };
+        let task = &self.tasks[task_index];
+        let result = match attachments::task_attachments(&self.working_dir, task).into_iter().next() {
+            Some(attachment) => attachments::open(&attachment.target)
+                .map(|()| (ToastKind::Info, format!("📎 Opening {}", attachment.label)))
+                .unwrap_or_else(|e| (ToastKind::Error, format!("⚠ Couldn't open {}: {}", attachment.target, e))),
+            None => (ToastKind::Info, format!("\"{}\" has no attachments or links", task.text)),
+        };
+        self.notify(result.0, result.1);
+    }
+
+    /// Cancels the selected task, or opens it again when it already is.
+    pub fn toggle_cancelled(&mut self) {
+        let Some(task_index) = self.selected_task_index() else { return };
+        let today = self.clock.today();
+        let task = &mut self.tasks[task_index];
+        task.set_cancelled(!task.cancelled, today);
+        let message = if task.cancelled {
+            format!("🚫 Cancelled task: \"{}\"", task.text)
+        } else {
+            format!("⏳ Marked task incomplete: \"{}\"", task.text)
+        };
+        self.save_tasks_with_message(Some(&message));
+    }
+
+    /// Switches the list between cancelled tasks only and the usual ones.
+    pub fn toggle_show_cancelled(&mut self) {
+        self.show_cancelled = !self.show_cancelled;
+        self.list_state.select(Some(0));
+    }
+
+    /// Pins the selected task on top of every view, or unpins it.
+    pub fn toggle_pin(&mut self) {
+        let Some(task_index) = self.selected_task_index() else { return };
+        let task = &mut self.tasks[task_index];
+        task.pinned = !task.pinned;
+        let message = if task.pinned {
+            format!("📌 Pinned task: {}", task.text)
+        } else {
+            format!("Unpinned task: {}", task.text)
+        };
+        self.save_tasks_with_message(Some(&message));
+        // It moved to or from the pinned section
+        self.select_task(task_index);
+    }
+
+    /// Starts a pomodoro on the selected task, or stops the running one. Either way
+    /// the time worked is added to the task's `[spent:...]`.
+    pub fn toggle_pomodoro(&mut self) {
+        if let Some(started) = self.pomodoro_started.take() {
+            self.notify(ToastKind::Info, "🍅 Pomodoro stopped");
+            self.log_pomodoro(started.elapsed().min(self.pomodoro_length));
+        } else {
+            self.pomodoro_started = Some(Instant::now());
+            self.pomodoro_task = self.selected_task_index().map(|i| self.tasks[i].id.clone());
+            let minutes = self.pomodoro_length.as_secs() / 60;
+            let on = match self.selected_task_index() {
+                Some(i) => format!(" on \"{}\"", self.tasks[i].text),
+                None => String::new(),
+            };
+            self.notify(ToastKind::Info, format!("🍅 Pomodoro started ({} min){}", minutes, on));
         }
+    }
+
+    /// Adds the whole minutes worked to the pomodoro's task.
+    fn log_pomodoro(&mut self, worked: Duration) {
+        let Some(id) = self.pomodoro_task.take() else { return };
+        let minutes = Duration::from_secs(worked.as_secs() / 60 * 60);
+        if minutes.is_zero() {
+            return;
+        }
+        let Some(task) = self.tasks.iter_mut().find(|t| t.id == id) else { return };
+        task.spent = Some(task.spent.unwrap_or_default() + minutes);
+        let commit_message = format!("🍅 Logged {} on: \"{}\"", format_duration(minutes), task.text);
+        self.save_tasks_with_message(Some(&commit_message));
+    }
+
+    pub fn pomodoro_remaining(&self) -> Option<Duration> {
+        self.pomodoro_started
+            .map(|started| self.pomodoro_length.saturating_sub(started.elapsed()))
+    }
+
+    /// Ends the pomodoro once its time is up.
+    pub fn update_pomodoro(&mut self) {
+        if self.pomodoro_remaining().is_some_and(|remaining| remaining.is_zero()) {
+            self.pomodoro_started = None;
+            self.notify(ToastKind::Success, "🍅 Pomodoro done, take a break");
+            self.log_pomodoro(self.pomodoro_length);
+        }
+    }
+
+    pub fn save_tasks_with_message(&mut self, commit_message: Option<&str>) {
+        if self.store.is_none() {
+            match TaskStore::open(&encryption::file_in(&self.working_dir, "tasks.md")) {
+                Ok(store) => self.store = Some(store),
+                Err(e) => {
+                    self.notify(ToastKind::Error, format!("⚠ Tasks not saved, {}", e));
+                    return;
+                }
+            }
+        }
+        let Some(store) = self.store.as_mut() else { return };
 
-        let _ = fs::write(&task_file, content);
+        // A sync may have written the file since, its changes are kept
+        store.tasks_mut().clone_from(&self.tasks);
+        if let Err(e) = store.save() {
+            self.notify(ToastKind::Error, format!("⚠ Tasks not saved, {}", e));
+            return;
+        }
+        if store.tasks() != self.tasks {
+            self.tasks = store.tasks().to_vec();
+        }
 
-        // Auto-commit the task changes with custom message (silently)
-        let _ = git_commit_tasks_with_message(commit_message);
+        // Auto-commit the task changes with custom message, now or as the commit policy says
+        let pending = self.pending_commit.get_or_insert_with(Vec::new);
+        pending.extend(commit_message.map(str::to_string));
+        let mut message = commit_message.unwrap_or("✓ Saved tasks").to_string();
+        match self.commit_policy {
+            CommitPolicy::Immediate => match self.commit_pending() {
+                Some(Ok(outcome)) => {
+                    if outcome.pushing {
+                        message.push_str(" (pushing)");
+                    }
+                    self.notify(ToastKind::Success, message);
+                }
+                Some(Err(e)) => self.notify(ToastKind::Error, format!("⚠ {}", e)),
+                None => {}
+            },
+            CommitPolicy::Debounce => {
+                self.commit_due = Some(Instant::now() + self.commit_debounce);
+                self.notify(ToastKind::Success, message);
+            }
+            CommitPolicy::Session => self.notify(ToastKind::Success, message),
+        }
+        self.refresh_status_info();
+        self.check_capacity();
+        request_webhooks_after_save();
 
-        // Trigger Todoist sync in background (silently)
+        // Sync once the saves stop for a moment rather than after each of them
         if is_todoist_sync_enabled() {
-            tokio::spawn(async move {
-                let _ = trigger_todoist_sync().await;
-            });
+            self.sync_due = Some(Instant::now() + todoist_sync_debounce());
         }
     }
 
@@ -190,21 +684,149 @@ impl App {
     }
 
     pub fn toggle_completed(&mut self) {
-        if let Some(selected) = self.list_state.selected() {
-            if let Some(task_index) = self.get_task_index_from_display_position(selected) {
-                let task = &mut self.tasks[task_index];
-                task.completed = !task.completed;
-                
-                let action = if task.completed { "✅ Marked task complete" } else { "⏳ Marked task incomplete" };
-                let commit_message = format!("{}: \"{}\"", action, task.text);
-                self.save_tasks_with_message(Some(&commit_message));
+        if let Some(selected) = self.list_state.selected()
+            && let Some(task_index) = self.get_task_index_from_display_position(selected) {
+            self.toggle_task_completed(task_index);
+        }
+    }
+
+    fn toggle_task_completed(&mut self, task_index: usize) {
+        let today = self.clock.today();
+        if !self.tasks[task_index].completed {
+            match hooks::run_task_hook(&self.working_dir, HookPoint::Complete, &self.tasks[task_index]) {
+                Ok(task) => self.tasks[task_index] = task,
+                Err(e) => {
+                    self.notify(ToastKind::Error, format!("⚠ {}", e));
+                    return;
+                }
+            }
+        }
+        let task = &mut self.tasks[task_index];
+        task.set_completed(!task.completed, today);
+
+        let action = if task.completed { "✅ Marked task complete" } else { "⏳ Marked task incomplete" };
+        let mut commit_message = format!("{}: \"{}\"", action, task.text);
+
+        if task.completed && self.auto_complete_parents {
+            for parent in subtasks::complete_finished_parents(&mut self.tasks, task_index, today) {
+                commit_message.push_str(&format!(", \"{}\"", self.tasks[parent].text));
+            }
+        }
+
+        self.save_tasks_with_message(Some(&commit_message));
+    }
+
+    /// Moves the selected task's `[count:]` counter by `step`, checking it off at the target.
+    pub fn bump_selected(&mut self, step: i32) {
+        let Some(task_index) = self.selected_task_index() else { return };
+        let today = self.clock.today();
+        let original = self.tasks[task_index].clone();
+        let was_completed = original.completed;
+        let task = &mut self.tasks[task_index];
+        if !task.bump(step, today) {
+            let text = format!("\"{}\" has no [count:] counter", task.text);
+            self.notify(ToastKind::Info, text);
+            return;
+        }
+        if task.completed && !was_completed {
+            match hooks::run_task_hook(&self.working_dir, HookPoint::Complete, task) {
+                Ok(hooked) => *task = hooked,
+                Err(e) => {
+                    self.tasks[task_index] = original;
+                    self.notify(ToastKind::Error, format!("⚠ {}", e));
+                    return;
+                }
+            }
+        }
+        let task = &self.tasks[task_index];
+
+        let (done, target) = task.count.unwrap_or_default();
+        let mut commit_message = if task.completed && !was_completed {
+            format!("✅ Marked task complete: \"{}\" ({}/{})", task.text, done, target)
+        } else {
+            format!("🔢 Counted \"{}\" to {}/{}", task.text, done, target)
+        };
+        if task.completed && !was_completed && self.auto_complete_parents {
+            for parent in subtasks::complete_finished_parents(&mut self.tasks, task_index, today) {
+                commit_message.push_str(&format!(", \"{}\"", self.tasks[parent].text));
             }
         }
+        self.save_tasks_with_message(Some(&commit_message));
+    }
+
+    /// Open tasks that are pinned or due today or overdue: pinned ones first, then the
+    /// most important, then oldest deadline first.
+    pub fn get_zen_tasks(&self) -> Vec<usize> {
+        let today = self.clock.today();
+        let mut due: Vec<usize> = (0..self.tasks.len())
+            .filter(|&i| {
+                let task = &self.tasks[i];
+                !task.completed && (task.pinned || task.deadline.is_some_and(|d| d <= today))
+            })
+            .collect();
+        // Tasks without importance go after the least important ones
+        due.sort_by_key(|&i| {
+            let task = &self.tasks[i];
+            (!task.pinned, task.importance.unwrap_or(u8::MAX), task.deadline)
+        });
+        due
+    }
+
+    pub fn toggle_zen_mode(&mut self) {
+        self.view_mode = if self.view_mode == ViewMode::Zen {
+            ViewMode::Tasks
+        } else {
+            ViewMode::Zen
+        };
+        self.zen_cursor = 0;
+    }
+
+    pub fn next_zen_task(&mut self) {
+        let count = self.get_zen_tasks().len();
+        if count > 0 {
+            self.zen_cursor = (self.zen_cursor + 1) % count;
+        }
+    }
+
+    pub fn previous_zen_task(&mut self) {
+        let count = self.get_zen_tasks().len();
+        if count > 0 {
+            self.zen_cursor = (self.zen_cursor + count - 1) % count;
+        }
+    }
+
+    /// Done tasks leave the zen list, so the cursor lands on the next one.
+    pub fn complete_zen_task(&mut self) {
+        let zen_tasks = self.get_zen_tasks();
+        if let Some(&task_index) = zen_tasks.get(self.zen_cursor) {
+            self.toggle_task_completed(task_index);
+            self.zen_cursor = self.zen_cursor.min(zen_tasks.len().saturating_sub(2));
+        }
     }
 
     pub fn add_new_task(&mut self) {
         if !self.input.trim().is_empty() {
-            let new_task = Task::parse(&self.input);
+            let mut new_task = Task::parse_at(&self.input, self.clock.as_ref());
+            new_task.created = Some(self.clock.today());
+            // While grouped by project, new tasks join the project of the selected one, in its file
+            if self.group_by_project
+                && let Some(selected) = self.list_state.selected()
+                && let Some(task_index) = self.get_task_index_from_display_position(selected) {
+                new_task.project = self.tasks[task_index].project.clone();
+                new_task.source = self.tasks[task_index].source.clone();
+            }
+            let mut new_task = match hooks::run_task_hook(&self.working_dir, HookPoint::Add, &new_task) {
+                Ok(task) => task,
+                Err(e) => {
+                    // The input stays, to be fixed and entered again
+                    self.notify(ToastKind::Error, format!("⚠ {}", e));
+                    return;
+                }
+            };
+            let taken: HashSet<String> = self.tasks.iter().map(|t| t.id.clone()).collect();
+            if taken.contains(&new_task.id) {
+                new_task.id = ids::new_id(&taken);
+            }
             let task_text = new_task.text.clone();
             self.tasks.push(new_task);
             
@@ -222,23 +844,40 @@ impl App {
         }
     }
 
+    /// Completed tasks are listed when `c` shows them, cancelled ones only in their own list.
+    fn is_listed(&self, task: &Task) -> bool {
+        !task.cancelled && (self.show_completed || !task.completed)
+    }
+
     pub fn get_visible_tasks(&self) -> Vec<usize> {
         self.tasks
             .iter()
             .enumerate()
-            .filter(|(_, task)| self.show_completed || !task.completed)
+            .filter(|(_, task)| self.is_listed(task))
             .map(|(i, _)| i)
             .collect()
     }
 
+    /// Cancelled tasks, within the tag or context filter when there is one.
+    pub fn get_cancelled_tasks(&self) -> Vec<usize> {
+        (0..self.tasks.len())
+            .filter(|&i| {
+                let task = &self.tasks[i];
+                task.cancelled
+                    && self.selected_tag.as_ref().is_none_or(|tag| task.tags.contains(tag))
+                    && self.selected_context.as_ref().is_none_or(|context| task.contexts.contains(context))
+            })
+            .collect()
+    }
+
     pub fn get_grouped_tasks(&self) -> Vec<(String, Vec<usize>)> {
-        let today = chrono::Local::now().date_naive();
+        let today = self.clock.today();
         let mut overdue_today = Vec::new();
         let mut upcoming = Vec::new();
         let mut no_deadline = Vec::new();
 
         for (i, task) in self.tasks.iter().enumerate() {
-            if !self.show_completed && task.completed {
+            if !self.is_listed(task) {
                 continue;
             }
 
@@ -271,134 +910,754 @@ impl App {
         result
     }
 
-    pub fn get_total_display_items(&self) -> usize {
-        let grouped_tasks = self.get_grouped_tasks();
-        let mut count = 0;
-        
-        for (_, task_indices) in grouped_tasks {
-            if !task_indices.is_empty() {
-                count += 1; // Section header
-                count += task_indices.len(); // Tasks
-                count += 1; // Spacing after section
+    /// One section per project, in the order they first show up in tasks.md.
+    pub fn get_project_groups(&self) -> Vec<(String, Vec<usize>)> {
+        let mut groups: Vec<(Option<&str>, Vec<usize>)> = Vec::new();
+        for i in self.get_visible_tasks() {
+            let project = self.tasks[i].project.as_deref();
+            match groups.iter_mut().find(|(p, _)| *p == project) {
+                Some((_, indices)) => indices.push(i),
+                None => groups.push((project, vec![i])),
             }
         }
-        
-        count
+
+        groups
+            .into_iter()
+            .map(|(project, indices)| match project {
+                Some(project) => (format!("PROJECT: {}", project), indices),
+                None => ("NO PROJECT".to_string(), indices),
+            })
+            .collect()
     }
 
-    pub fn get_task_index_from_display_position(&self, display_pos: usize) -> Option<usize> {
-        let grouped_tasks = self.get_grouped_tasks();
-        let mut current_pos = 0;
-        
-        for (_, task_indices) in grouped_tasks {
-            if !task_indices.is_empty() {
-                // Skip section header
-                current_pos += 1;
-                
-                // Check if we're in the task range for this section
-                for &task_idx in &task_indices {
-                    if current_pos == display_pos {
-                        return Some(task_idx);
+    /// Overdue tasks pinned on top, then one section per day for the coming week.
+    /// Empty days are kept so the week reads as a calendar.
+    pub fn get_week_sections(&self) -> Vec<(String, Vec<usize>)> {
+        let today = self.clock.today();
+        let visible: Vec<usize> = self.get_visible_tasks();
+
+        let mut overdue: Vec<usize> = visible
+            .iter()
+            .copied()
+            .filter(|&i| self.tasks[i].deadline.is_some_and(|d| d < today))
+            .collect();
+        overdue.sort_by_key(|&i| self.tasks[i].deadline);
+
+        let mut sections = vec![("OVERDUE".to_string(), overdue)];
+
+        for offset in 0..7 {
+            let day = today + chrono::Duration::days(offset);
+            let label = match offset {
+                0 => {
+                    let planned = effort::planned_for(&self.tasks, today);
+                    if planned.is_zero() {
+                        format!("{} (today)", week_day(day))
+                    } else {
+                        let effort = effort::describe_planned(planned, self.daily_capacity);
+                        format!("{} (today) {}", week_day(day), effort)
                     }
-                    current_pos += 1;
                 }
-                
-                // Skip spacing after section
-                current_pos += 1;
-            }
+                1 => format!("{} (tomorrow)", week_day(day)),
+                _ => week_day(day).to_string(),
+            };
+            let due: Vec<usize> = visible
+                .iter()
+                .copied()
+                .filter(|&i| self.tasks[i].deadline == Some(day))
+                .collect();
+            sections.push((label, due));
         }
-        
-        None
+
+        sections
     }
 
-    pub fn delete_selected_task(&mut self) {
-        if let Some(selected) = self.list_state.selected() {
-            if let Some(task_index) = self.get_task_index_from_display_position(selected) {
-                let task_text = self.tasks[task_index].text.clone();
-                self.tasks.remove(task_index);
-                
-                let commit_message = format!("🗑️ Deleted task: \"{}\"", task_text);
-                self.save_tasks_with_message(Some(&commit_message));
-                
-                // Adjust selection
-                let new_total_items = self.get_total_display_items();
-                if new_total_items == 0 {
-                    self.list_state.select(None);
-                } else if selected >= new_total_items {
-                    self.list_state.select(Some(new_total_items - 1));
+    /// Open pinned tasks among `candidates`, which every view shows in a section on top.
+    fn pinned_tasks(&self, candidates: impl IntoIterator<Item = usize>) -> Vec<usize> {
+        candidates
+            .into_iter()
+            .filter(|&i| self.tasks[i].pinned && !self.tasks[i].completed)
+            .collect()
+    }
+
+    /// The rows of whichever task list is currently shown.
+    pub fn get_display_rows(&self) -> Vec<DisplayRow> {
+        let mut rows = Vec::new();
+        let push_pinned = |rows: &mut Vec<DisplayRow>, pinned: &[usize]| {
+            if !pinned.is_empty() {
+                rows.push(DisplayRow::Header("📌 PINNED".to_string()));
+                rows.extend(pinned.iter().copied().map(DisplayRow::Task));
+                rows.push(DisplayRow::Spacer);
+            }
+        };
+
+        if self.view_mode == ViewMode::Week {
+            // Pinned tasks show up whatever their deadline, and only there
+            let pinned = self.pinned_tasks(self.get_visible_tasks());
+            push_pinned(&mut rows, &pinned);
+            let today = self.clock.today();
+            for (index, (label, mut task_indices)) in self.get_week_sections().into_iter().enumerate() {
+                task_indices.retain(|i| !pinned.contains(i));
+                // Nothing overdue is good news, not a section worth showing
+                if index == 0 && task_indices.is_empty() {
+                    continue;
+                }
+                rows.push(DisplayRow::Header(label));
+                // The day's events go first, what is due has to fit around them
+                let day = today + chrono::Duration::days(index as i64 - 1);
+                let events: Vec<&CalendarEvent> = self.events.iter().filter(|e| index > 0 && e.day == day).collect();
+                rows.extend(events.iter().map(|e| DisplayRow::Event(e.describe())));
+                if task_indices.is_empty() && events.is_empty() {
+                    rows.push(DisplayRow::Placeholder("nothing due".to_string()));
                 }
+                rows.extend(task_indices.into_iter().map(DisplayRow::Task));
+                rows.push(DisplayRow::Spacer);
             }
+            return rows;
         }
-    }
 
-    pub fn get_all_tags(&self) -> Vec<String> {
-        let mut tags = std::collections::HashSet::new();
-        for task in &self.tasks {
-            for tag in &task.tags {
-                tags.insert(tag.clone());
+        if self.show_cancelled {
+            rows.push(DisplayRow::Header("🚫 CANCELLED".to_string()));
+            let cancelled = self.get_cancelled_tasks();
+            if cancelled.is_empty() {
+                rows.push(DisplayRow::Placeholder("nothing cancelled".to_string()));
             }
+            rows.extend(cancelled.into_iter().map(DisplayRow::Task));
+            return rows;
         }
-        let mut sorted_tags: Vec<String> = tags.into_iter().collect();
-        sorted_tags.sort();
-        sorted_tags
+
+        let grouped_tasks = if let Some(tag) = &self.selected_tag {
+            vec![(format!("TAG: #{}", tag), self.get_tasks_by_tag(tag))]
+        } else if let Some(context) = &self.selected_context {
+            vec![(format!("CONTEXT: >{}", context), self.get_tasks_by_context(context))]
+        } else if self.group_by_project {
+            self.get_project_groups()
+        } else {
+            self.get_grouped_tasks()
+        };
+
+        let pinned = self.pinned_tasks(grouped_tasks.iter().flat_map(|(_, indices)| indices.iter().copied()));
+        push_pinned(&mut rows, &pinned);
+        for (section_name, mut task_indices) in grouped_tasks {
+            task_indices.retain(|i| !pinned.contains(i));
+            if !task_indices.is_empty() {
+                rows.push(DisplayRow::Header(section_name));
+                rows.extend(task_indices.into_iter().map(DisplayRow::Task));
+                rows.push(DisplayRow::Spacer);
+            }
+        }
+
+        rows
     }
 
-    pub fn get_tasks_by_tag(&self, tag: &str) -> Vec<usize> {
-        self.tasks
-            .iter()
-            .enumerate()
-            .filter(|(_, task)| {
-                task.tags.contains(&tag.to_string()) && (self.show_completed || !task.completed)
-            })
-            .map(|(i, _)| i)
-            .collect()
+    pub fn get_total_display_items(&self) -> usize {
+        self.get_display_rows().len()
     }
 
-    pub fn toggle_view_mode(&mut self) {
-        match self.view_mode {
-            ViewMode::Tasks => {
-                self.view_mode = ViewMode::TagsMenu;
-                let tags = self.get_all_tags();
-                if !tags.is_empty() {
-                    self.tags_list_state.select(Some(0));
-                }
-            }
-            ViewMode::TagsMenu => {
-                self.view_mode = ViewMode::Tasks;
-                self.selected_tag = None;
-            }
+    pub fn get_task_index_from_display_position(&self, display_pos: usize) -> Option<usize> {
+        match self.get_display_rows().get(display_pos) {
+            Some(DisplayRow::Task(task_index)) => Some(*task_index),
+            _ => None,
         }
     }
 
-    pub fn manual_sync(&mut self) {
-        if !is_todoist_sync_enabled() {
-            self.sync_status = Some("⚠ Todoist sync not enabled".to_string());
-            return;
+    /// Moves the selection onto the given task, if it is currently displayed.
+    pub fn select_task(&mut self, task_index: usize) {
+        if let Some(pos) = self
+            .get_display_rows()
+            .iter()
+            .position(|row| *row == DisplayRow::Task(task_index))
+        {
+            self.list_state.select(Some(pos));
         }
+    }
 
-        self.sync_status = Some("🔄 Syncing...".to_string());
+    pub fn toggle_week_view(&mut self) {
+        self.view_mode = if self.view_mode == ViewMode::Week {
+            ViewMode::Tasks
+        } else {
+            ViewMode::Week
+        };
+        self.list_state.select(Some(0));
+        if self.view_mode == ViewMode::Week && self.events_fetched.is_none_or(|at| at.elapsed() >= CALENDAR_REFRESH_INTERVAL) {
+            self.start_calendar_fetch();
+        }
+    }
+
+    /// Fetches the coming week's events of the calendars in the background; they arrive
+    /// through `events_rx` and are picked up by `update_calendars`.
+    pub fn start_calendar_fetch(&mut self) {
+        if self.calendars.is_empty() || self.events_rx.is_some() {
+            return;
+        }
+        let (tx, rx) = mpsc::channel();
+        self.events_rx = Some(rx);
+        let calendars = self.calendars.clone();
+        let today = self.clock.today();
+        tokio::spawn(async move {
+            let _ = tx.send(calendar::fetch_events(&calendars, today, today + chrono::Duration::days(6)).await);
+        });
+    }
+
+    pub fn update_calendars(&mut self) {
+        let Some(rx) = &self.events_rx else {
+            return;
+        };
+        let (events, errors) = match rx.try_recv() {
+            Ok(fetched) => fetched,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => (self.events.clone(), vec!["calendar fetch stopped".to_string()]),
+        };
+        self.events_rx = None;
+        self.events_fetched = Some(Instant::now());
+        // Calendars that failed keep nothing rather than last time's events
+        self.events = events;
+        for error in errors {
+            self.notify(ToastKind::Error, format!("⚠ Couldn't read calendar {}", error));
+        }
+    }
+
+    /// Shifts the selected task's deadline by `days`, never earlier than today.
+    /// Overdue tasks count as due yesterday, so moving them forward lands on today.
+    pub fn move_selected_task(&mut self, days: i64) {
+        let today = self.clock.today();
+
+        if let Some(selected) = self.list_state.selected()
+            && let Some(task_index) = self.get_task_index_from_display_position(selected) {
+            let task = &mut self.tasks[task_index];
+            let base = match task.deadline {
+                Some(deadline) if deadline >= today => deadline,
+                _ => today - chrono::Duration::days(1),
+            };
+            let new_deadline = (base + chrono::Duration::days(days)).max(today);
+            if task.deadline == Some(new_deadline) {
+                return;
+            }
+            task.deadline = Some(new_deadline);
+
+            let commit_message = format!("📅 Moved task to {}: \"{}\"", new_deadline.format("%Y-%m-%d"), task.text);
+            self.save_tasks_with_message(Some(&commit_message));
+            self.select_task(task_index);
+        }
+    }
+
+    /// Asks for confirmation before deleting the selected task.
+    pub fn request_delete_selected_task(&mut self) {
+        if let Some(selected) = self.list_state.selected()
+            && let Some(task_index) = self.get_task_index_from_display_position(selected) {
+            let task = &self.tasks[task_index];
+            // Attachments stay with the task in the trash, see `delete_task`
+            let days = trash::TRASH_RETENTION_DAYS;
+            let prompt = match task.attachments.len() {
+                0 => format!("Delete '{}'? (y/n)", task.text),
+                1 => format!("Delete '{}'? Its attachment is kept in the trash for {} days. (y/n)", task.text, days),
+                n => format!("Delete '{}'? Its {} attachments are kept in the trash for {} days. (y/n)", task.text, n, days),
+            };
+            self.confirm = Some(Confirm {
+                prompt,
+                action: ConfirmAction::DeleteTask { task_id: task.id.clone() },
+            });
+        }
+    }
+
+    pub fn confirm_pending_action(&mut self) {
+        if let Some(confirm) = self.confirm.take() {
+            match confirm.action {
+                ConfirmAction::DeleteTask { task_id } => self.delete_task(&task_id),
+                ConfirmAction::ReloadTasks => self.reload_changed_tasks(),
+            }
+        }
+    }
+
+    pub fn cancel_pending_action(&mut self) {
+        if self.confirm.take().is_some() {
+            self.notify(ToastKind::Info, "Cancelled");
+        }
+    }
+
+    pub fn delete_task(&mut self, task_id: &str) {
+        if let Some(task_index) = self.tasks.iter().position(|t| t.id == task_id) {
+            let selected = self.list_state.selected().unwrap_or(0);
+            // Its subtasks stay, one level up
+            let task = subtasks::remove_task(&mut self.tasks, task_index);
+            let task_text = task.text.clone();
+
+            // Attachments stay on disk until the trash entry is purged
+            let today = self.clock.today();
+            if let Err(e) = trash::move_to_trash(&self.working_dir, task, today) {
+                self.notify(ToastKind::Error, format!("⚠ Failed to move task to trash: {}", e));
+            }
+                
+            let commit_message = format!("🗑️ Deleted task: \"{}\"", task_text);
+            self.save_tasks_with_message(Some(&commit_message));
+                
+            // Adjust selection
+            let new_total_items = self.get_total_display_items();
+            if new_total_items == 0 {
+                self.list_state.select(None);
+            } else if selected >= new_total_items {
+                self.list_state.select(Some(new_total_items - 1));
+            }
+        }
+    }
+
+    pub fn handle_help_key(&mut self, code: KeyCode) {
+        if self.help_filtering {
+            match code {
+                KeyCode::Char(c) => self.help_filter.push(c),
+                KeyCode::Backspace => {
+                    self.help_filter.pop();
+                }
+                KeyCode::Enter => self.help_filtering = false,
+                KeyCode::Esc => {
+                    self.help_filtering = false;
+                    self.help_filter.clear();
+                }
+                _ => {}
+            }
+            self.help_scroll = 0;
+            return;
+        }
+
+        match code {
+            KeyCode::Char('j') | KeyCode::Down => self.help_scroll = self.help_scroll.saturating_add(1),
+            KeyCode::Char('k') | KeyCode::Up => self.help_scroll = self.help_scroll.saturating_sub(1),
+            KeyCode::PageDown => self.help_scroll = self.help_scroll.saturating_add(10),
+            KeyCode::PageUp => self.help_scroll = self.help_scroll.saturating_sub(10),
+            KeyCode::Char('/') => self.help_filtering = true,
+            KeyCode::Esc if !self.help_filter.is_empty() => {
+                self.help_filter.clear();
+                self.help_scroll = 0;
+            }
+            KeyCode::Char('h') | KeyCode::Char('q') | KeyCode::F(1) | KeyCode::Esc => {
+                self.show_help = false;
+                self.help_filter.clear();
+                self.help_scroll = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_log_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('j') | KeyCode::Down => self.log_scroll = self.log_scroll.saturating_add(1),
+            KeyCode::Char('k') | KeyCode::Up => self.log_scroll = self.log_scroll.saturating_sub(1),
+            KeyCode::PageDown => self.log_scroll = self.log_scroll.saturating_add(10),
+            KeyCode::PageUp => self.log_scroll = self.log_scroll.saturating_sub(10),
+            KeyCode::Char('!') | KeyCode::Char('q') | KeyCode::Esc => self.show_log = false,
+            _ => {}
+        }
+    }
+
+    /// Tasks reachable from the notes popup: the ones it links to, then the ones linking to it.
+    pub fn notes_links(&self) -> Vec<usize> {
+        let Some(task_index) = self.selected_task_for_notes else {
+            return Vec::new();
+        };
+
+        let mut targets: Vec<usize> = links::task_links(&self.tasks[task_index])
+            .iter()
+            .filter_map(|reference| match links::resolve(&self.tasks, reference) {
+                LinkTarget::Found(target) => Some(target),
+                _ => None,
+            })
+            .collect();
+        for backlink in links::backlinks(&self.tasks, task_index) {
+            if !targets.contains(&backlink) {
+                targets.push(backlink);
+            }
+        }
+        targets
+    }
+
+    /// Opens the notes popup of the task, with its links and dates.
+    pub fn open_notes(&mut self, task_index: usize) {
+        self.selected_task_for_notes = Some(task_index);
+        self.notes_link_cursor = 0;
+        self.show_notes = true;
+
+        // Older tasks don't record their dates; ask git once per task, in the background
+        // since it reads the whole history of tasks.md
+        let task = &self.tasks[task_index];
+        let missing_dates = task.created.is_none() || (task.completed && task.done.is_none());
+        if missing_dates && !self.dates_from_git.contains_key(&task.id) {
+            let (tx, rx) = mpsc::channel();
+            self.dates_rx = Some(rx);
+            let working_dir = self.working_dir.clone();
+            let id = task.id.clone();
+            tokio::task::spawn_blocking(move || {
+                let dates = stats::dates_from_git(&working_dir, &id);
+                let _ = tx.send((id, dates));
+            });
+        }
+    }
+
+    pub fn update_dates_from_git(&mut self) {
+        let Some(rx) = &self.dates_rx else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok((id, dates)) => {
+                self.dates_from_git.insert(id, dates);
+            }
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {}
+        }
+        self.dates_rx = None;
+    }
+
+    /// Moves long-finished tasks to archive.md when `archive_after_days` is set.
+    pub fn archive_old_tasks(&mut self) {
+        if self.archive_after_days == 0 {
+            return;
+        }
+
+        let today = self.clock.today();
+        let (kept, archived) = archive::split_archivable(self.tasks.clone(), today, self.archive_after_days);
+        if archived.is_empty() {
+            return;
+        }
+
+        if let Err(e) = archive::append_to_archive(&self.working_dir, &archived) {
+            self.notify(ToastKind::Error, format!("⚠ Failed to archive tasks: {}", e));
+            return;
+        }
+        self.tasks = kept;
+        self.list_state.select(if self.tasks.is_empty() { None } else { Some(0) });
+        let message = format!("📦 Archived {} completed task(s)", archived.len());
+        self.save_tasks_with_message(Some(&message));
+        self.notify(ToastKind::Info, message);
+    }
+
+    /// Applies the escalation rules and commits what they changed.
+    pub fn triage(&mut self) {
+        let escalations = rules::apply_rules(&self.rules, &mut self.tasks, self.clock.today());
+        if escalations.is_empty() {
+            return;
+        }
+
+        let message = rules::commit_message(&self.tasks, &escalations);
+        self.save_tasks_with_message(Some(&message));
+        self.notify(ToastKind::Info, format!("⚡ Escalated {} task(s)", escalations.len()));
+    }
+
+    /// Closes the notes popup and selects the task in the main list, clearing
+    /// whatever filter would hide it.
+    pub fn jump_to_task(&mut self, task_index: usize) {
+        self.show_notes = false;
+        self.selected_task_for_notes = None;
+        self.view_mode = ViewMode::Tasks;
+        self.selected_tag = None;
+        self.selected_context = None;
+        self.show_cancelled = self.tasks[task_index].cancelled;
+        if self.tasks[task_index].completed {
+            self.show_completed = true;
+        }
+        self.select_task(task_index);
+    }
+
+    pub fn open_trash(&mut self) {
+        self.trash = trash::load_trash(&self.working_dir);
+        self.trash_list_state.select(if self.trash.is_empty() { None } else { Some(0) });
+        self.view_mode = ViewMode::Trash;
+    }
+
+    pub fn close_trash(&mut self) {
+        self.view_mode = ViewMode::Tasks;
+    }
+
+    pub fn next_trash_entry(&mut self) {
+        if self.trash.is_empty() {
+            return;
+        }
+        let i = match self.trash_list_state.selected() {
+            Some(i) if i + 1 < self.trash.len() => i + 1,
+            _ => 0,
+        };
+        self.trash_list_state.select(Some(i));
+    }
+
+    pub fn previous_trash_entry(&mut self) {
+        if self.trash.is_empty() {
+            return;
+        }
+        let i = match self.trash_list_state.selected() {
+            Some(0) | None => self.trash.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.trash_list_state.select(Some(i));
+    }
+
+    /// Puts the selected trash entry back at the end of the task list.
+    pub fn restore_selected_trash_entry(&mut self) {
+        let Some(selected) = self.trash_list_state.selected() else {
+            return;
+        };
+        if selected >= self.trash.len() {
+            return;
+        }
+
+        let entry = self.trash.remove(selected);
+        if let Err(e) = trash::save_trash(&self.working_dir, &self.trash) {
+            self.trash.insert(selected, entry);
+            self.notify(ToastKind::Error, format!("⚠ Failed to update trash: {}", e));
+            return;
+        }
+
+        let commit_message = format!("♻️ Restored task: \"{}\"", entry.task.text);
+        self.tasks.push(Task { indent_level: 0, parent_id: None, ..entry.task });
+        self.save_tasks_with_message(Some(&commit_message));
+
+        if self.trash.is_empty() {
+            self.trash_list_state.select(None);
+        } else if selected >= self.trash.len() {
+            self.trash_list_state.select(Some(self.trash.len() - 1));
+        }
+    }
+
+    pub fn handle_setup_key(&mut self, code: KeyCode) {
+        let Some(wizard) = self.setup.as_mut() else {
+            return;
+        };
+        if wizard.token_check == TokenCheck::Checking {
+            return;
+        }
+
+        match (wizard.step, code) {
+            // Skipping the wizard keeps the defaults
+            (Step::Welcome, KeyCode::Esc) => self.finish_setup(),
+            (_, KeyCode::Esc) => wizard.previous_step(),
+            (Step::Summary, KeyCode::Enter) => self.finish_setup(),
+            (Step::Todoist, KeyCode::Enter)
+                if !wizard.todoist_token.trim().is_empty() && wizard.token_check != TokenCheck::Valid =>
+            {
+                self.start_token_check();
+            }
+            (_, KeyCode::Enter) => wizard.next_step(),
+            (Step::Git, KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right) => {
+                wizard.git_auto_commit = !wizard.git_auto_commit;
+            }
+            (Step::Git, KeyCode::Char('y')) => wizard.git_auto_commit = true,
+            (Step::Git, KeyCode::Char('n')) => wizard.git_auto_commit = false,
+            (_, KeyCode::Char(c)) => {
+                if let Some(input) = wizard.input_mut() {
+                    input.push(c);
+                }
+            }
+            (_, KeyCode::Backspace) => {
+                if let Some(input) = wizard.input_mut() {
+                    input.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Verifies the Todoist token in the background so the wizard keeps drawing.
+    fn start_token_check(&mut self) {
+        let Some(wizard) = self.setup.as_mut() else {
+            return;
+        };
+        wizard.token_check = TokenCheck::Checking;
+        let token = wizard.todoist_token.trim().to_string();
+
+        let (tx, rx) = mpsc::channel();
+        self.token_check_rx = Some(rx);
+        tokio::spawn(async move {
+            let result = TodoistAuth::verify_token(&token).await.map_err(|e| e.to_string());
+            let _ = tx.send(result);
+        });
+    }
+
+    pub fn update_token_check(&mut self) {
+        let Some(rx) = &self.token_check_rx else {
+            return;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => Err("token check stopped".to_string()),
+        };
+        self.token_check_rx = None;
+
+        let Some(wizard) = self.setup.as_mut() else {
+            return;
+        };
+        match result {
+            Ok(true) => {
+                wizard.token_check = TokenCheck::Valid;
+                wizard.next_step();
+            }
+            Ok(false) => wizard.token_check = TokenCheck::Invalid("Todoist didn't accept this token".to_string()),
+            Err(e) => wizard.token_check = TokenCheck::Invalid(format!("Couldn't reach Todoist: {}", e)),
+        }
+    }
+
+    fn finish_setup(&mut self) {
+        let Some(wizard) = self.setup.take() else {
+            return;
+        };
+
+        match wizard.apply(&setup::settings_path(), &get_email_config_path()) {
+            Ok(tasks_dir) => {
+                if tasks_dir != self.working_dir {
+                    self.working_dir = tasks_dir;
+                    self.load_tasks();
+                    self.list_state.select(if self.tasks.is_empty() { None } else { Some(0) });
+                    self.refresh_status_info();
+                }
+                self.notify(ToastKind::Success, format!("✓ Tasks live in {}", self.working_dir.display()));
+            }
+            Err(e) => {
+                self.notify(ToastKind::Error, format!("⚠ Setup failed: {}", e));
+                self.setup = Some(wizard);
+            }
+        }
+    }
+
+    pub fn get_all_tags(&self) -> Vec<String> {
+        let mut tags = std::collections::HashSet::new();
+        for task in &self.tasks {
+            for tag in &task.tags {
+                tags.insert(tag.clone());
+            }
+        }
+        let mut sorted_tags: Vec<String> = tags.into_iter().collect();
+        sorted_tags.sort();
+        sorted_tags
+    }
+
+    pub fn get_all_contexts(&self) -> Vec<String> {
+        let mut contexts: Vec<String> = self.tasks.iter().flat_map(|t| t.contexts.iter().cloned()).collect();
+        contexts.sort();
+        contexts.dedup();
+        contexts
+    }
+
+    pub fn get_tasks_by_context(&self, context: &str) -> Vec<usize> {
+        self.get_visible_tasks()
+            .into_iter()
+            .filter(|&i| self.tasks[i].contexts.iter().any(|c| c == context))
+            .collect()
+    }
+
+    pub fn get_tasks_by_tag(&self, tag: &str) -> Vec<usize> {
+        self.tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| {
+                task.tags.contains(&tag.to_string()) && self.is_listed(task)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn toggle_view_mode(&mut self) {
+        match self.view_mode {
+            ViewMode::Tasks | ViewMode::Week => {
+                self.view_mode = ViewMode::TagsMenu;
+                let tags = self.get_all_tags();
+                if !tags.is_empty() {
+                    self.tags_list_state.select(Some(0));
+                }
+            }
+            ViewMode::TagsMenu => {
+                self.view_mode = ViewMode::Tasks;
+                self.selected_tag = None;
+                self.selected_context = None;
+            }
+            ViewMode::Trash | ViewMode::Zen => {}
+        }
+    }
+
+    /// Starts a Todoist sync in the background; progress and the result arrive
+    /// through `sync_rx` and are picked up by `update_sync`.
+    pub fn start_sync(&mut self) {
+        if !is_todoist_sync_enabled() {
+            self.sync_status = Some("⚠ Todoist sync not enabled".to_string());
+            return;
+        }
+        if self.sync_rx.is_some() {
+            self.notify(ToastKind::Info, "🔄 Sync already running");
+            return;
+        }
 
-        // Run sync in blocking manner
-        let result = tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                trigger_todoist_sync().await
+        let (tx, rx) = mpsc::channel();
+        self.sync_rx = Some(rx);
+        self.sync_status = Some(format_sync_progress(SyncProgress::FetchingProjects));
+
+        tokio::spawn(async move {
+            let progress_tx = tx.clone();
+            let result = trigger_todoist_sync_with_progress(move |progress| {
+                let _ = progress_tx.send(SyncEvent::Progress(progress));
             })
+            .await
+            .map_err(|e| e.to_string());
+            let _ = tx.send(SyncEvent::Finished(result));
         });
+    }
+
+    /// Commits the saves held back by the saves that came after them, once those stop.
+    pub fn update_commit(&mut self) {
+        if self.commit_due.is_none_or(|due| Instant::now() < due) {
+            return;
+        }
+        match self.commit_pending() {
+            Some(Ok(outcome)) if outcome.committed => self.notify(ToastKind::Info, "📝 Committed the changes"),
+            Some(Err(e)) => self.notify(ToastKind::Error, format!("⚠ {}", e)),
+            _ => {}
+        }
+        self.refresh_status_info();
+    }
+
+    /// Commits the saves not committed yet, in one commit listing their messages. None when
+    /// there were none.
+    pub fn commit_pending(&mut self) -> Option<Result<GitCommitOutcome, YarmtlError>> {
+        let messages = self.pending_commit.take()?;
+        self.commit_due = None;
+        Some(git_commit_tasks_with_message(git::combined_message(&messages).as_deref()))
+    }
+
+    pub fn update_sync(&mut self) {
+        if self.sync_rx.is_none() && self.sync_due.is_some_and(|due| Instant::now() >= due) {
+            self.sync_due = None;
+            self.start_sync();
+        }
+
+        let Some(rx) = &self.sync_rx else {
+            return;
+        };
+
+        let mut finished = None;
+        while let Ok(event) = rx.try_recv() {
+            match event {
+                SyncEvent::Progress(progress) => self.sync_status = Some(format_sync_progress(progress)),
+                SyncEvent::Finished(result) => finished = Some(result),
+            }
+        }
+
+        let Some(result) = finished else {
+            return;
+        };
+        self.sync_rx = None;
 
         match result {
-            Ok(_) => {
+            Ok(report) => {
                 self.load_tasks(); // Reload to show synced tasks
-                self.sync_status = Some("✓ Synced with Todoist".to_string());
+                self.refresh_status_info();
+                let summary = report.map(|r| r.summary()).unwrap_or_default();
+                self.sync_status = Some(format!("✓ Todoist {}", summary).trim_end().to_string());
+                self.notify(ToastKind::Success, format!("✓ Synced with Todoist {}", summary).trim_end().to_string());
             }
             Err(e) => {
                 self.sync_status = Some(format!("⚠ Sync failed: {}", e));
+                self.notify(ToastKind::Error, format!("⚠ Sync failed: {}", e));
             }
         }
     }
 
     pub fn next_tag(&mut self) {
-        let tags = self.get_all_tags();
-        let total_items = tags.len() + 1; // +1 for "All Tasks" option
+        // +1 for "All Tasks" option
+        let total_items = self.get_all_tags().len() + self.get_all_contexts().len() + 1;
         
         let i = match self.tags_list_state.selected() {
             Some(i) => {
@@ -414,8 +1673,8 @@ impl App {
     }
 
     pub fn previous_tag(&mut self) {
-        let tags = self.get_all_tags();
-        let total_items = tags.len() + 1; // +1 for "All Tasks" option
+        // +1 for "All Tasks" option
+        let total_items = self.get_all_tags().len() + self.get_all_contexts().len() + 1;
         
         let i = match self.tags_list_state.selected() {
             Some(i) => {
@@ -430,32 +1689,69 @@ impl App {
         self.tags_list_state.select(Some(i));
     }
 
+    /// Picks the tag or context under the cursor; the contexts are listed after the tags.
     pub fn select_tag(&mut self) {
         let tags = self.get_all_tags();
         if let Some(selected) = self.tags_list_state.selected() {
-            if selected == 0 {
-                // "All Tasks" option selected
-                self.selected_tag = None;
-            } else if let Some(tag) = tags.get(selected - 1) {
-                // Adjust index by -1 since "All Tasks" is at index 0
+            self.selected_tag = None;
+            self.selected_context = None;
+            // Adjust index by -1 since "All Tasks" is at index 0
+            if let Some(tag) = selected.checked_sub(1).and_then(|i| tags.get(i)) {
                 self.selected_tag = Some(tag.clone());
+            } else if selected > 0 {
+                self.selected_context = self.get_all_contexts().get(selected - 1 - tags.len()).cloned();
             }
             self.view_mode = ViewMode::Tasks;
         }
     }
 }
 
-pub fn run_tui(working_dir: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    // Setup terminal
+pub fn run_tui(working_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config();
+
+    // Catch up with the other machines before showing the tasks
+    let pulled = config.git.remote.as_ref().map(|_| {
+        println!("⬇️ Pulling tasks from the remote...");
+        git::git_pull(working_dir)
+    });
+
+    // Setup terminal; from here on stderr would write over the screen
+    logging::set_sink(logging::Sink::Buffer);
     enable_raw_mode()?;
+    // Detect the palette while still in raw mode and before anything reads stdin
+    let theme = Theme::from_setting(config.ui.theme);
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // Create app and run it
-    let app = App::new(working_dir);
-    let res = run_app(&mut terminal, app);
+    let mut app = App::new(working_dir);
+    app.theme = theme;
+    app.status_widgets = config.ui.status_line;
+    app.layout = config.ui.layout;
+    app.pomodoro_length = Duration::from_secs(config.ui.pomodoro_minutes * 60);
+    app.auto_complete_parents = config.tasks.auto_complete_parents;
+    app.archive_after_days = config.tasks.archive_after_days;
+    app.daily_capacity = effort::capacity(config.tasks.daily_capacity_hours);
+    app.rules = config.rules;
+    app.calendars = config.calendars;
+    app.commit_policy = config.git.commit;
+    app.commit_debounce = Duration::from_secs(config.git.debounce_seconds);
+    app.start_calendar_fetch();
+    app.archive_old_tasks();
+    app.triage();
+    app.check_capacity();
+    match pulled {
+        Some(Ok(PullOutcome::FastForwarded)) => app.notify(ToastKind::Info, "⬇ Pulled tasks from the remote"),
+        Some(Ok(PullOutcome::Merged)) => {
+            git::request_push_after_commit();
+            app.notify(ToastKind::Info, "🔀 Merged tasks from the remote");
+        }
+        Some(Err(e)) => app.notify(ToastKind::Error, format!("⚠ {}", e)),
+        _ => {}
+    }
+    let res = run_app(&mut terminal, &mut app);
 
     // Restore terminal
     disable_raw_mode()?;
@@ -465,150 +1761,340 @@ pub fn run_tui(working_dir: &PathBuf) -> Result<(), Box<dyn std::error::Error>>
         DisableMouseCapture
     )?;
     terminal.show_cursor()?;
+    logging::set_sink(logging::Sink::Stderr);
 
     if let Err(err) = res {
         println!("{err:?}");
     }
 
+    // What the commit policy held back until now
+    match app.commit_pending() {
+        Some(Ok(outcome)) if outcome.committed => println!("📝 Committed the changes of this session"),
+        Some(Err(e)) => tracing::warn!("{}", e),
+        _ => {}
+    }
+
     Ok(())
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
+/// Under --ascii, each cell holding an emoji or a symbol gets an ASCII character instead. The
+/// cell after a wide emoji stays blank, so nothing moves.
+/// A day of the week agenda, "Mon 10/06" or as the `[dates]` section has it.
+fn week_day(day: NaiveDate) -> String {
+    format!("{} {}", dates::display().weekday(day), dates::display().short(day))
+}
+
+fn ascii_cells(buffer: &mut Buffer) {
+    for cell in buffer.content.iter_mut() {
+        if !cell.symbol().is_ascii()
+            && let Some(c) = output::ascii_cell(cell.symbol()) {
+            cell.set_char(c);
+        }
+    }
+}
+
+fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
     loop {
-        // Check if splash screen should be dismissed
-        if app.show_splash && app.splash_timer.elapsed().as_secs() >= 2 {
-            app.show_splash = false;
+        app.update_token_check();
+        app.update_pomodoro();
+        app.update_commit();
+        app.update_sync();
+        app.update_file_watch();
+        app.update_calendars();
+        app.update_dates_from_git();
+        app.update_status_info();
+        app.update_toasts();
+        terminal.draw(|f| {
+            ui(f, app);
+            if output::is_ascii() {
+                ascii_cells(f.buffer_mut());
+            }
+        })?;
+
+        // Poll instead of blocking so toasts expire even without input
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
         }
 
-        terminal.draw(|f| ui(f, &mut app))?;
+        let event = event::read()?;
+        if let Event::Mouse(mouse) = event {
+            app.handle_mouse(mouse);
+            continue;
+        }
 
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                // Any key dismisses splash screen
-                if app.show_splash {
-                    app.show_splash = false;
-                    continue;
+        if let Event::Key(key) = event
+            && key.kind == KeyEventKind::Press {
+            if app.setup.is_some() {
+                app.handle_setup_key(key.code);
+                continue;
+            }
+
+            // A pending confirmation swallows every key except its answers
+            if app.confirm.is_some() {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => app.confirm_pending_action(),
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.cancel_pending_action(),
+                    _ => {}
                 }
+                continue;
+            }
 
-                // Any key dismisses notes popup
-                if app.show_notes {
-                    app.show_notes = false;
-                    app.selected_task_for_notes = None;
-                    continue;
+            if app.show_help {
+                app.handle_help_key(key.code);
+                continue;
+            }
+
+            if app.show_log {
+                app.handle_log_key(key.code);
+                continue;
+            }
+
+            if app.status_detail.is_some() {
+                match key.code {
+                    KeyCode::Right | KeyCode::Char('l') | KeyCode::Tab => app.cycle_status_detail(true),
+                    KeyCode::Left | KeyCode::Char('h') | KeyCode::BackTab => app.cycle_status_detail(false),
+                    _ => app.status_detail = None,
                 }
+                continue;
+            }
 
-                match app.input_mode {
-                    InputMode::Normal => match app.view_mode {
-                        ViewMode::Tasks => match key.code {
-                            KeyCode::Char('q') => return Ok(()),
-                            KeyCode::Char('a') | KeyCode::Char('i') => {
-                                app.input_mode = InputMode::Editing;
-                            }
-                            KeyCode::Char('j') | KeyCode::Down => {
-                                app.next_task();
-                            }
-                            KeyCode::Char('k') | KeyCode::Up => {
-                                app.previous_task();
-                            }
-                            KeyCode::Char(' ') | KeyCode::Enter => {
-                                app.toggle_completed();
-                            }
-                            KeyCode::Char('d') | KeyCode::Delete => {
-                                app.delete_selected_task();
-                            }
-                            KeyCode::Char('c') => {
-                                app.show_completed = !app.show_completed;
-                            }
-                            KeyCode::Char('h') | KeyCode::F(1) => {
-                                app.show_help = !app.show_help;
-                            }
-                            KeyCode::Char('r') => {
-                                app.load_tasks();
-                            }
-                            KeyCode::Char('n') => {
-                                if let Some(selected) = app.list_state.selected() {
-                                    if let Some(task_index) = app.get_task_index_from_display_position(selected) {
-                                        app.selected_task_for_notes = Some(task_index);
-                                        app.show_notes = true;
-                                    }
-                                }
-                            }
-                            KeyCode::Char('t') => {
-                                app.toggle_view_mode();
-                            }
-                            KeyCode::Char('s') => {
-                                // Trigger manual Todoist sync
-                                app.manual_sync();
-                            }
-                            KeyCode::Esc => {
-                                app.selected_tag = None;
-                            }
-                            _ => {}
+            // The notes popup walks its links with j/k and jumps with Enter; any other key dismisses it
+            if app.show_notes {
+                let link_count = app.notes_links().len();
+                match key.code {
+                    KeyCode::Char('j') | KeyCode::Down if link_count > 0 => {
+                        app.notes_link_cursor = (app.notes_link_cursor + 1) % link_count;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up if link_count > 0 => {
+                        app.notes_link_cursor = (app.notes_link_cursor + link_count - 1) % link_count;
+                    }
+                    KeyCode::Enter if link_count > 0 => {
+                        let target = app.notes_links()[app.notes_link_cursor];
+                        app.jump_to_task(target);
+                    }
+                    _ => {
+                        app.show_notes = false;
+                        app.selected_task_for_notes = None;
+                    }
+                }
+                continue;
+            }
+
+            match app.input_mode {
+                InputMode::Normal => match app.view_mode {
+                    ViewMode::Tasks | ViewMode::Week => match key.code {
+                        KeyCode::Char('q') => return Ok(()),
+                        KeyCode::Char('a') | KeyCode::Char('i') => {
+                            app.input_mode = InputMode::Editing;
                         }
-                        ViewMode::TagsMenu => match key.code {
-                            KeyCode::Char('q') => return Ok(()),
-                            KeyCode::Char('j') | KeyCode::Down => {
-                                app.next_tag();
-                            }
-                            KeyCode::Char('k') | KeyCode::Up => {
-                                app.previous_tag();
-                            }
-                            KeyCode::Enter => {
-                                app.select_tag();
-                            }
-                            KeyCode::Char('t') | KeyCode::Esc => {
-                                app.toggle_view_mode();
-                            }
-                            KeyCode::Char('h') | KeyCode::F(1) => {
-                                app.show_help = !app.show_help;
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app.next_task();
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app.previous_task();
+                        }
+                        KeyCode::Char(' ') | KeyCode::Enter => {
+                            app.toggle_completed();
+                        }
+                        KeyCode::Char('d') | KeyCode::Delete => {
+                            app.request_delete_selected_task();
+                        }
+                        KeyCode::Char('c') => {
+                            app.show_completed = !app.show_completed;
+                        }
+                        KeyCode::Char('h') | KeyCode::F(1) => {
+                            app.show_help = !app.show_help;
+                        }
+                        KeyCode::Char('!') => {
+                            app.show_log = true;
+                            // Opens on the newest lines
+                            app.log_scroll = u16::MAX;
+                        }
+                        KeyCode::Char('r') => {
+                            app.load_tasks();
+                        }
+                        KeyCode::Char('n') => {
+                            if let Some(selected) = app.list_state.selected()
+                                && let Some(task_index) = app.get_task_index_from_display_position(selected) {
+                                app.open_notes(task_index);
                             }
-                            _ => {}
                         }
+                        KeyCode::Char('t') => {
+                            app.toggle_view_mode();
+                        }
+                        KeyCode::Char('o') => {
+                            app.open_selected_attachment();
+                        }
+                        KeyCode::Char('s') | KeyCode::Char('S') => {
+                            // Runs in the background, progress shows in the status line
+                            app.start_sync();
+                        }
+                        KeyCode::Char('w') => {
+                            app.toggle_week_view();
+                        }
+                        KeyCode::Char('p') => {
+                            app.toggle_pomodoro();
+                        }
+                        KeyCode::Char('*') => {
+                            app.toggle_pin();
+                        }
+                        KeyCode::Char('x') => {
+                            app.toggle_cancelled();
+                        }
+                        KeyCode::Char('X') => {
+                            app.toggle_show_cancelled();
+                        }
+                        KeyCode::Char('+') => {
+                            app.bump_selected(1);
+                        }
+                        KeyCode::Char('-') => {
+                            app.bump_selected(-1);
+                        }
+                        KeyCode::Char('P') => {
+                            app.group_by_project = !app.group_by_project;
+                            app.list_state.select(Some(0));
+                        }
+                        KeyCode::Char('T') => {
+                            app.open_trash();
+                        }
+                        KeyCode::Char('Z') => {
+                            app.toggle_zen_mode();
+                        }
+                        KeyCode::Char('b') => {
+                            app.open_status_details();
+                        }
+                        KeyCode::Char('<') | KeyCode::Char('H') if app.view_mode == ViewMode::Week => {
+                            app.move_selected_task(-1);
+                        }
+                        KeyCode::Char('>') | KeyCode::Char('L') if app.view_mode == ViewMode::Week => {
+                            app.move_selected_task(1);
+                        }
+                        KeyCode::Esc if app.view_mode == ViewMode::Week => {
+                            app.toggle_week_view();
+                        }
+                        KeyCode::Esc => {
+                            app.selected_tag = None;
+                            app.selected_context = None;
+                        }
+                        _ => {}
                     }
-                    InputMode::Editing => match key.code {
+                    ViewMode::TagsMenu => match key.code {
+                        KeyCode::Char('q') => return Ok(()),
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app.next_tag();
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app.previous_tag();
+                        }
                         KeyCode::Enter => {
-                            app.add_new_task();
+                            app.select_tag();
                         }
-                        KeyCode::Char(c) => {
-                            app.input.push(c);
+                        KeyCode::Char('t') | KeyCode::Esc => {
+                            app.toggle_view_mode();
                         }
-                        KeyCode::Backspace => {
-                            app.input.pop();
+                        KeyCode::Char('h') | KeyCode::F(1) => {
+                            app.show_help = !app.show_help;
                         }
-                        KeyCode::Esc => {
-                            app.input_mode = InputMode::Normal;
-                            app.input.clear();
+                        _ => {}
+                    }
+                    ViewMode::Trash => match key.code {
+                        KeyCode::Char('q') => return Ok(()),
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app.next_trash_entry();
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app.previous_trash_entry();
+                        }
+                        KeyCode::Enter | KeyCode::Char('r') => {
+                            app.restore_selected_trash_entry();
+                        }
+                        KeyCode::Char('T') | KeyCode::Esc => {
+                            app.close_trash();
+                        }
+                        KeyCode::Char('h') | KeyCode::F(1) => {
+                            app.show_help = !app.show_help;
+                        }
+                        _ => {}
+                    }
+                    ViewMode::Zen => match key.code {
+                        KeyCode::Char('q') => return Ok(()),
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app.next_zen_task();
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app.previous_zen_task();
+                        }
+                        KeyCode::Char(' ') | KeyCode::Enter => {
+                            app.complete_zen_task();
+                        }
+                        KeyCode::Char('p') => {
+                            app.toggle_pomodoro();
+                        }
+                        KeyCode::Char('Z') | KeyCode::Esc => {
+                            app.toggle_zen_mode();
+                        }
+                        KeyCode::Char('h') | KeyCode::F(1) => {
+                            app.show_help = !app.show_help;
                         }
                         _ => {}
                     }
                 }
+                InputMode::Editing => match key.code {
+                    KeyCode::Enter => {
+                        app.add_new_task();
+                    }
+                    KeyCode::Char(c) => {
+                        app.input.push(c);
+                    }
+                    KeyCode::Backspace => {
+                        app.input.pop();
+                    }
+                    KeyCode::Esc => {
+                        app.input_mode = InputMode::Normal;
+                        app.input.clear();
+                    }
+                    _ => {}
+                }
             }
         }
     }
 }
 
-fn ui(f: &mut Frame, app: &mut App) {
-    // Splash screen
-    if app.show_splash {
-        draw_splash_screen(f);
+pub fn ui(f: &mut Frame, app: &mut App) {
+    // First-run setup
+    if app.setup.is_some() {
+        draw_setup_wizard(f, app);
+        draw_toasts(f, app);
         return;
     }
 
-    // Help popup
     if app.show_help {
-        draw_help_popup(f);
-        return;
+        // Help popup
+        draw_help_popup(f, app);
+    } else if app.show_log {
+        draw_log_popup(f, app);
+    } else if app.show_notes {
+        // Notes popup
+        draw_notes_popup(f, app);
+    } else {
+        draw_main_view(f, app);
     }
 
-    // Notes popup
-    if app.show_notes {
-        draw_notes_popup(f, app);
-        return;
+    if let Some(widget) = app.status_detail {
+        draw_status_details(f, app, widget);
+    }
+
+    if let Some(confirm) = &app.confirm {
+        draw_confirm_dialog(f, confirm, &app.theme);
     }
 
+    draw_toasts(f, app);
+}
+
+fn draw_main_view(f: &mut Frame, app: &mut App) {
     // Main layout
     match app.view_mode {
-        ViewMode::Tasks => {
+        ViewMode::Tasks | ViewMode::Week => {
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
@@ -631,208 +2117,598 @@ fn ui(f: &mut Frame, app: &mut App) {
                 ])
                 .split(f.size());
 
-            draw_tags_menu(f, app, chunks[0]);
-            draw_status_line(f, app, chunks[1]);
-        }
+            draw_tags_menu(f, app, chunks[0]);
+            draw_status_line(f, app, chunks[1]);
+        }
+        ViewMode::Trash => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(3),    // Trash list
+                    Constraint::Length(1), // Status line
+                ])
+                .split(f.size());
+
+            draw_trash(f, app, chunks[0]);
+            draw_status_line(f, app, chunks[1]);
+        }
+        ViewMode::Zen => draw_zen_view(f, app),
+    }
+}
+
+/// "What should I do right now": only today's tasks, big and centered, no chrome.
+fn draw_zen_view(f: &mut Frame, app: &App) {
+    let theme = app.theme;
+    let area = f.size();
+    let zen_tasks = app.get_zen_tasks();
+    let today = app.clock.today();
+
+    let planned = effort::planned_for(&app.tasks, today);
+    let mut header = dates::display().long(today);
+    if !planned.is_zero() {
+        header.push_str(&format!("  {}", effort::describe_planned(planned, app.daily_capacity)));
+    }
+    let mut lines = vec![
+        Line::from(Span::styled(header, Style::default().fg(theme.muted))),
+        Line::from(""),
+    ];
+
+    if zen_tasks.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "Nothing due today 🎉",
+            Style::default().fg(theme.success).add_modifier(Modifier::BOLD),
+        )));
+    }
+
+    for (position, &task_index) in zen_tasks.iter().enumerate() {
+        let task = &app.tasks[task_index];
+        let selected = position == app.zen_cursor;
+        let mut style = Style::default().fg(if selected { theme.accent } else { theme.text });
+        if selected {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+
+        let mut spans = Vec::new();
+        if selected {
+            spans.push(Span::styled("► ", style));
+        }
+        spans.push(Span::styled(task.text.clone(), style));
+        if let Some(importance) = task.importance {
+            spans.push(Span::styled(
+                format!("  ${}", importance),
+                Style::default().fg(theme.importance_color(importance)),
+            ));
+        }
+        if task.deadline.is_some_and(|d| d < today) {
+            spans.push(Span::styled("  overdue", Style::default().fg(theme.danger)));
+        }
+        // Balances the marker so the centered text doesn't shift when selected
+        if selected {
+            spans.push(Span::raw("  "));
+        }
+
+        lines.push(Line::from(spans));
+        // Room to breathe between tasks
+        lines.push(Line::from(""));
+    }
+
+    let mut footer = String::from("j/k: move  Enter: done  Z/Esc: back");
+    if let Some(remaining) = app.pomodoro_remaining() {
+        let secs = remaining.as_secs();
+        footer = format!("🍅 {:02}:{:02}   {}", secs / 60, secs % 60, footer);
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(footer, Style::default().fg(theme.muted))));
+
+    // Vertically centered, clamped so long lists still start at the top
+    let height = (lines.len() as u16).min(area.height);
+    let top = area.y + (area.height - height) / 2;
+    let zen_area = ratatui::layout::Rect { y: top, height, ..area };
+
+    f.render_widget(
+        Paragraph::new(lines).alignment(Alignment::Center).wrap(Wrap { trim: true }),
+        zen_area,
+    );
+}
+
+fn task_list_item(app: &App, task_index: usize) -> ListItem<'static> {
+    let theme = app.theme;
+    let task = &app.tasks[task_index];
+    let mut spans = Vec::new();
+    
+    // Checkbox, indented like the subtask is in tasks.md
+    let (checkbox, color) = checkbox(task, theme);
+    spans.push(Span::styled(
+        format!("{}{} ", "  ".repeat(task.indent_level), checkbox),
+        Style::default().fg(color)
+    ));
+
+    // Task text with ID and subtask progress
+    spans.extend(task_text_spans(app, task_index));
+    spans.extend(escalation_span(app, task));
+
+    spans.extend(marker_spans(task, theme, app.clock.today()));
+
+    ListItem::new(Line::from(spans))
+}
+
+/// Deadline, tags, contexts, reminders, estimate, attachments, notes and importance,
+/// as the task list shows them after the text.
+fn marker_spans(task: &Task, theme: Theme, today: NaiveDate) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+
+    if task.pinned {
+        spans.push(Span::styled(" 📌", Style::default().fg(theme.accent)));
+    }
+
+    if let Some((done, target)) = task.count {
+        let color = if done == target { theme.success } else { theme.accent };
+        spans.push(Span::styled(
+            format!(" [{}/{}] {}", done, target, status_line::braille_bar(done as usize, target as usize, 3)),
+            Style::default().fg(color)
+        ));
+    }
+
+    // Deadline indicator
+    if let Some(deadline) = task.deadline {
+        let (indicator, color) = if deadline < today {
+            (" ⚠️ OVERDUE", theme.danger)
+        } else if deadline == today {
+            (" 🔴 DUE TODAY", theme.accent)
+        } else {
+            (" 📅", theme.accent)
+        };
+        
+        spans.push(Span::styled(
+            output::symbols(&format!("{} {}", indicator, dates::display().short(deadline))),
+            Style::default().fg(color)
+        ));
+    }
+
+    // Tags
+    for tag in &task.tags {
+        spans.push(Span::styled(
+            output::symbols(&format!(" 🏷️#{}", tag)),
+            Style::default().fg(theme.success)
+        ));
+    }
+
+    // Contexts
+    for context in &task.contexts {
+        spans.push(Span::styled(
+            format!(" 📍>{}", context),
+            Style::default().fg(theme.accent)
+        ));
+    }
+
+    // Reminder
+    if let Some(reminder) = task.reminder {
+        spans.push(Span::styled(
+            format!(" 🔔{}", dates::display().short(reminder)),
+            Style::default().fg(theme.accent)
+        ));
+    }
+    if let Some(repeat) = task.repeat {
+        spans.push(Span::styled(
+            format!(" 🔁{}", repeat),
+            Style::default().fg(theme.accent)
+        ));
+    }
+
+    spans.extend(estimate_span(task, theme));
+
+    // Attachments
+    if !task.attachments.is_empty() {
+        spans.push(Span::styled(
+            format!(" 📎{}", task.attachments.len()),
+            Style::default().fg(theme.muted)
+        ));
+    }
+
+    // Notes - displayed last like a comment
+    if let Some(ref notes) = task.notes {
+        spans.push(Span::styled(
+            format!(" //{}", notes),
+            Style::default().fg(theme.muted).add_modifier(Modifier::ITALIC)
+        ));
+    }
+
+    // Importance
+    if let Some(importance) = task.importance {
+        let color = theme.importance_color(importance);
+        // Very important tasks stand out even without color
+        let style = if importance == 1 { Modifier::BOLD } else { Modifier::empty() };
+        spans.push(Span::styled(
+            format!(" ⭐${}", importance),
+            Style::default().fg(color).add_modifier(style)
+        ));
     }
+    spans
 }
 
-fn draw_task_list(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
-    let grouped_tasks = if let Some(tag) = &app.selected_tag {
-        // Show tasks filtered by tag
-        let task_indices = app.get_tasks_by_tag(tag);
-        if task_indices.is_empty() {
-            vec![]
-        } else {
-            vec![(format!("TAG: #{}", tag), task_indices)]
-        }
+/// The task's flag, text and ID and, for parents, the subtask progress.
+fn task_text_spans(app: &App, task_index: usize) -> Vec<Span<'static>> {
+    let theme = app.theme;
+    let task = &app.tasks[task_index];
+
+    let text_style = if task.completed {
+        Style::default()
+            .fg(theme.muted)
+            .add_modifier(Modifier::CROSSED_OUT)
     } else {
-        app.get_grouped_tasks()
+        Style::default().fg(theme.text)
     };
-    
-    let mut items: Vec<ListItem> = Vec::new();
-    
-    // Add section headers and tasks
-    for (section_name, task_indices) in grouped_tasks {
-        if !task_indices.is_empty() {
-            // Add section header
-            items.push(ListItem::new(Line::from(vec![
-                Span::styled(
-                    format!("━━━ {} ━━━", section_name),
-                    Style::default().fg(Color::Rgb(255, 107, 138)).add_modifier(Modifier::BOLD)
-                )
-            ])));
-            
-            // Add tasks in this section
-            for &i in &task_indices {
-                let task = &app.tasks[i];
-                let mut spans = Vec::new();
-                
-                // Indentation for subtasks
-                let indent = "";
-                if !indent.is_empty() {
-                    spans.push(Span::styled(indent, Style::default()));
-                }
-                
-                // Checkbox
-                let checkbox = if task.completed { "☑" } else { "☐" };
-                spans.push(Span::styled(
-                    format!("{} ", checkbox),
-                    if task.completed {
-                        Style::default().fg(Color::Green)
-                    } else {
-                        Style::default().fg(Color::Rgb(255, 107, 138))
-                    }
-                ));
-
-                // Task text with ID
-                let text_style = if task.completed {
-                    Style::default()
-                        .fg(Color::DarkGray)
-                        .add_modifier(Modifier::CROSSED_OUT)
-                } else {
-                    Style::default().fg(Color::White)
-                };
-                let id_display = if task.id.len() > 8 { &task.id[..8] } else { &task.id };
-                spans.push(Span::styled(
-                    format!("{} [{}]", task.text, id_display), 
-                    text_style
-                ));
+    let mut spans: Vec<Span> = flag_span(task).into_iter().collect();
+    spans.extend(linked_text_spans(app, &task.text, text_style));
+    spans.push(Span::styled(
+        format!(" [{}]", task.id),
+        text_style
+    ));
+
+    if let Some((done, total)) = subtasks::subtask_progress(&app.tasks, task_index) {
+        let color = if done == total { theme.success } else { theme.muted };
+        spans.push(Span::styled(
+            format!(" [{}/{}] {}", done, total, status_line::braille_bar(done, total, 3)),
+            Style::default().fg(color)
+        ));
+    }
+    spans
+}
 
-                // Deadline indicator
-                if let Some(deadline) = task.deadline {
-                    let today = chrono::Local::now().date_naive();
-                    let (indicator, color) = if deadline < today {
-                        (" ⚠️ OVERDUE", Color::Red)
-                    } else if deadline == today {
-                        (" 🔴 DUE TODAY", Color::Rgb(255, 107, 138))
-                    } else {
-                        (" 📅", Color::Rgb(255, 107, 138))
-                    };
-                    
-                    spans.push(Span::styled(
-                        format!("{} {}", indicator, deadline.format("%m/%d")),
-                        Style::default().fg(color)
-                    ));
-                }
+/// ☐ open, ☑ completed or ☒ cancelled, with its color.
+fn checkbox(task: &Task, theme: Theme) -> (&'static str, Color) {
+    match (task.completed, task.cancelled) {
+        (_, true) => ("☒", theme.muted),
+        (true, false) => ("☑", theme.success),
+        (false, false) => ("☐", theme.accent),
+    }
+}
 
-                // Tags
-                for tag in &task.tags {
-                    spans.push(Span::styled(
-                        format!(" 🏷️#{}", tag),
-                        Style::default().fg(Color::Green)
-                    ));
-                }
+/// "● " in the color of a `[flag:red]`, or the flag itself for `[flag:🔥]`.
+fn flag_span(task: &Task) -> Option<Span<'static>> {
+    let flag = task.flag.as_ref()?;
+    Some(match theme::flag_color(flag) {
+        Some(color) => Span::styled("● ", Style::default().fg(color)),
+        None => Span::raw(format!("{} ", flag)),
+    })
+}
 
-                // Reminder
-                if let Some(reminder) = task.reminder {
-                    spans.push(Span::styled(
-                        format!(" 🔔{}", reminder.format("%m/%d")),
-                        Style::default().fg(Color::Rgb(255, 107, 138))
-                    ));
-                }
+/// " ⚡overdue > 7 days" on tasks an escalation rule holds for.
+fn escalation_span(app: &App, task: &Task) -> Option<Span<'static>> {
+    let rule = rules::matching_rule(&app.rules, task, app.clock.today())?;
+    Some(Span::styled(
+        format!(" ⚡{}", rule.describe()),
+        Style::default().fg(app.theme.danger).add_modifier(Modifier::BOLD),
+    ))
+}
 
-                // Notes - displayed last like a comment
-                if let Some(ref notes) = task.notes {
-                    spans.push(Span::styled(
-                        format!(" //{}", notes),
-                        Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC)
-                    ));
-                }
+/// " ⏱~2h", or " ⏱1h30m/~2h" once pomodoro time has been spent on the task.
+fn estimate_span(task: &Task, theme: Theme) -> Option<Span<'static>> {
+    let text = match (task.spent, task.estimate) {
+        (None, None) => return None,
+        (Some(spent), Some(estimate)) => format!(" ⏱{}/~{}", format_duration(spent), format_duration(estimate)),
+        (Some(spent), None) => format!(" ⏱{}", format_duration(spent)),
+        (None, Some(estimate)) => format!(" ⏱~{}", format_duration(estimate)),
+    };
+    let color = if task.spent > task.estimate && task.estimate.is_some() { theme.danger } else { theme.muted };
+    Some(Span::styled(text, Style::default().fg(color)))
+}
 
-                // Importance
-                if let Some(importance) = task.importance {
-                    let (color, style) = match importance {
-                        1 => (Color::Red, Modifier::BOLD),      // Very important
-                        2 => (Color::LightRed, Modifier::empty()), // High importance
-                        3 => (Color::Yellow, Modifier::empty()),   // Medium importance
-                        4 => (Color::LightBlue, Modifier::empty()), // Low importance
-                        5 => (Color::DarkGray, Modifier::empty()),  // Not important
-                        _ => (Color::White, Modifier::empty()),
-                    };
-                    spans.push(Span::styled(
-                        format!(" ⭐${}", importance),
-                        Style::default().fg(color).add_modifier(style)
-                    ));
-                }
+/// One row of the table layout, with a cell per visible column.
+fn task_table_row(app: &App, task_index: usize, columns: &[Column]) -> Row<'static> {
+    let theme = app.theme;
+    let task = &app.tasks[task_index];
+    let today = app.clock.today();
 
-                items.push(ListItem::new(Line::from(spans)));
+    let cells = columns.iter().map(|column| match column {
+        Column::Checkbox => {
+            let (checkbox, color) = checkbox(task, theme);
+            Cell::from(Span::styled(checkbox, Style::default().fg(color)))
+        }
+        Column::Priority => match task.importance {
+            Some(importance) => {
+                let style = if importance == 1 { Modifier::BOLD } else { Modifier::empty() };
+                Cell::from(Span::styled(
+                    format!("${}", importance),
+                    Style::default().fg(theme.importance_color(importance)).add_modifier(style),
+                ))
             }
-            
-            // Add spacing between sections
-            items.push(ListItem::new(Line::from("")));
+            None => Cell::from(""),
+        },
+        Column::Text => {
+            let mut spans = vec![Span::raw("  ".repeat(task.indent_level))];
+            spans.extend(task_text_spans(app, task_index));
+            spans.extend(escalation_span(app, task));
+            if let Some(reminder) = task.reminder {
+                spans.push(Span::styled(format!(" 🔔{}", dates::display().short(reminder)), Style::default().fg(theme.accent)));
+            }
+            if let Some(repeat) = task.repeat {
+                spans.push(Span::styled(format!(" 🔁{}", repeat), Style::default().fg(theme.accent)));
+            }
+            spans.extend(estimate_span(task, theme));
+            if !task.attachments.is_empty() {
+                spans.push(Span::styled(format!(" 📎{}", task.attachments.len()), Style::default().fg(theme.muted)));
+            }
+            if let Some(ref notes) = task.notes {
+                spans.push(Span::styled(
+                    format!(" //{}", notes),
+                    Style::default().fg(theme.muted).add_modifier(Modifier::ITALIC),
+                ));
+            }
+            Cell::from(Line::from(spans))
         }
-    }
+        Column::Due => match task.deadline {
+            Some(deadline) if task.completed => {
+                Cell::from(Span::styled(dates::display().short(deadline), Style::default().fg(theme.muted)))
+            }
+            Some(deadline) if deadline < today => Cell::from(Span::styled(
+                format!("⚠ {}", dates::display().short(deadline)),
+                Style::default().fg(theme.danger),
+            )),
+            Some(deadline) if deadline == today => {
+                Cell::from(Span::styled("today", Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)))
+            }
+            Some(deadline) => {
+                Cell::from(Span::styled(dates::display().short(deadline), Style::default().fg(theme.accent)))
+            }
+            None => Cell::from(""),
+        },
+        Column::Tags => Cell::from(Span::styled(
+            task.tags
+                .iter()
+                .map(|tag| format!("#{}", tag))
+                .chain(task.contexts.iter().map(|context| format!(">{}", context)))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Style::default().fg(theme.success),
+        )),
+    });
+
+    Row::new(cells.collect::<Vec<_>>())
+}
 
-    let title = if let Some(tag) = &app.selected_tag {
-        if app.show_completed {
-            format!("Tasks: #{} (All)", tag)
-        } else {
-            format!("Tasks: #{} (Active)", tag)
+/// Splits text into spans, showing [[id:...]] references as the linked task's text.
+fn linked_text_spans(app: &App, text: &str, style: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut last = 0;
+
+    for caps in links::LINK_RE.captures_iter(text) {
+        let link = caps.get(0).unwrap();
+        if link.start() > last {
+            spans.push(Span::styled(text[last..link.start()].to_string(), style));
         }
+        spans.push(match links::resolve(&app.tasks, &caps[1]) {
+            LinkTarget::Found(target) => Span::styled(
+                format!("↪{}", app.tasks[target].text),
+                Style::default().fg(app.theme.accent).add_modifier(Modifier::UNDERLINED),
+            ),
+            _ => Span::styled(link.as_str().to_string(), Style::default().fg(app.theme.danger)),
+        });
+        last = link.end();
+    }
+
+    if last < text.len() {
+        spans.push(Span::styled(text[last..].to_string(), style));
+    }
+    spans
+}
+
+fn draw_task_list(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let theme = app.theme;
+    let shown = if app.show_cancelled {
+        "Cancelled"
     } else if app.show_completed {
-        "Tasks (All)".to_string()
+        "All"
     } else {
-        "Tasks (Active)".to_string()
+        "Active"
     };
+    let title = if app.view_mode == ViewMode::Week {
+        "Week Agenda".to_string()
+    } else if let Some(filter) = app.selected_tag.as_ref().map(|tag| format!("#{}", tag))
+        .or_else(|| app.selected_context.as_ref().map(|context| format!(">{}", context)))
+    {
+        format!("Tasks: {} ({})", filter, shown)
+    } else {
+        format!("Tasks ({})", shown)
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(Style::default().fg(theme.accent));
+
+    if app.layout == ListLayout::Table {
+        draw_task_table(f, app, area, block);
+        return;
+    }
+
+    let items: Vec<ListItem> = app
+        .get_display_rows()
+        .into_iter()
+        .map(|row| match row {
+            DisplayRow::Header(section_name) => ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("━━━ {} ━━━", section_name),
+                    Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+                )
+            ])),
+            DisplayRow::Task(task_index) => task_list_item(app, task_index),
+            DisplayRow::Placeholder(text) => ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("  {}", text),
+                    Style::default().fg(theme.muted).add_modifier(Modifier::ITALIC)
+                )
+            ])),
+            DisplayRow::Event(text) => ListItem::new(Line::from(vec![
+                Span::styled(format!("  {}", text), Style::default().fg(theme.muted))
+            ])),
+            DisplayRow::Spacer => ListItem::new(Line::from("")),
+        })
+        .collect();
 
     let tasks_list = List::new(items)
-        .block(Block::default()
-            .borders(Borders::ALL)
-            .title(title)
-            .border_style(Style::default().fg(Color::Rgb(255, 107, 138))))
-        .highlight_style(Style::default().bg(Color::Black).fg(Color::Rgb(255, 107, 138)))
+        .block(block)
+        .highlight_style(Style::default().bg(theme.surface).fg(theme.accent))
         .highlight_symbol("► ");
 
     f.render_stateful_widget(tasks_list, area, &mut app.list_state);
 }
 
+/// The table layout: the same rows as the list, with aligned columns that
+/// drop the less important ones when the terminal is narrow.
+fn draw_task_table(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect, block: Block) {
+    let theme = app.theme;
+    // Borders and the highlight symbol take 4 cells
+    let (columns, widths): (Vec<Column>, Vec<Constraint>) =
+        task_table::columns_for_width(area.width.saturating_sub(4)).into_iter().unzip();
+
+    // Headers and placeholders go in the text column, the others stay empty
+    let text_row = |span: Span<'static>| {
+        Row::new(columns.iter().map(|column| {
+            if *column == Column::Text { Cell::from(span.clone()) } else { Cell::from("") }
+        }))
+    };
+
+    let rows: Vec<Row> = app
+        .get_display_rows()
+        .into_iter()
+        .map(|row| match row {
+            DisplayRow::Header(section_name) => text_row(Span::styled(
+                format!("━━━ {} ━━━", section_name),
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+            )),
+            DisplayRow::Task(task_index) => task_table_row(app, task_index, &columns),
+            DisplayRow::Placeholder(text) => text_row(Span::styled(
+                text,
+                Style::default().fg(theme.muted).add_modifier(Modifier::ITALIC)
+            )),
+            DisplayRow::Event(text) => text_row(Span::styled(text, Style::default().fg(theme.muted))),
+            DisplayRow::Spacer => text_row(Span::raw("")),
+        })
+        .collect();
+
+    let header = Row::new(columns.iter().map(|column| column.title()))
+        .style(Style::default().fg(theme.muted).add_modifier(Modifier::BOLD));
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(block)
+        .highlight_style(Style::default().bg(theme.surface).fg(theme.accent))
+        .highlight_symbol("► ");
+
+    // Selection lives in list_state for both layouts, so navigation stays the same
+    let mut state = TableState::default()
+        .with_offset(app.list_state.offset())
+        .with_selected(app.list_state.selected());
+    f.render_stateful_widget(table, area, &mut state);
+    *app.list_state.offset_mut() = state.offset();
+}
+
 fn draw_tags_menu(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let theme = app.theme;
     let tags = app.get_all_tags();
     let mut items: Vec<ListItem> = Vec::new();
 
     // Add "All Tasks" option
     items.push(ListItem::new(Line::from(vec![
-        Span::styled("📋 All Tasks", Style::default().fg(Color::White))
+        Span::styled("📋 All Tasks", Style::default().fg(theme.text))
     ])));
 
     // Add individual tags
     for tag in &tags {
         let task_count = app.get_tasks_by_tag(tag).len();
         items.push(ListItem::new(Line::from(vec![
-            Span::styled("🏷️  #", Style::default().fg(Color::Green)),
-            Span::styled(tag, Style::default().fg(Color::Green)),
-            Span::styled(format!(" ({})", task_count), Style::default().fg(Color::DarkGray)),
+            Span::styled("🏷️  #", Style::default().fg(theme.success)),
+            Span::styled(tag, Style::default().fg(theme.success)),
+            Span::styled(format!(" ({})", task_count), Style::default().fg(theme.muted)),
         ])));
     }
 
-    if tags.is_empty() {
+    // Then the contexts
+    let contexts = app.get_all_contexts();
+    for context in &contexts {
+        let task_count = app.get_tasks_by_context(context).len();
         items.push(ListItem::new(Line::from(vec![
-            Span::styled("No tags found", Style::default().fg(Color::DarkGray))
+            Span::styled("📍 >", Style::default().fg(theme.accent)),
+            Span::styled(context, Style::default().fg(theme.accent)),
+            Span::styled(format!(" ({})", task_count), Style::default().fg(theme.muted)),
+        ])));
+    }
+
+    if tags.is_empty() && contexts.is_empty() {
+        items.push(ListItem::new(Line::from(vec![
+            Span::styled("No tags found", Style::default().fg(theme.muted))
         ])));
     }
 
     let tags_list = List::new(items)
         .block(Block::default()
             .borders(Borders::ALL)
-            .title("Tags Menu")
-            .border_style(Style::default().fg(Color::Rgb(255, 107, 138))))
-        .highlight_style(Style::default().bg(Color::Black).fg(Color::Rgb(255, 107, 138)))
+            .title("Tags & Contexts")
+            .border_style(Style::default().fg(theme.accent)))
+        .highlight_style(Style::default().bg(theme.surface).fg(theme.accent))
         .highlight_symbol("► ");
 
     f.render_stateful_widget(tags_list, area, &mut app.tags_list_state);
 }
 
+fn draw_trash(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let theme = app.theme;
+    let mut items: Vec<ListItem> = app
+        .trash
+        .iter()
+        .map(|entry| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{}  ", dates::display().date(entry.deleted)), Style::default().fg(theme.muted)),
+                Span::styled(entry.task.text.clone(), Style::default().fg(theme.text)),
+            ]))
+        })
+        .collect();
+
+    if items.is_empty() {
+        items.push(ListItem::new(Line::from(vec![
+            Span::styled("Trash is empty", Style::default().fg(theme.muted))
+        ])));
+    }
+
+    let title = format!(
+        "Trash (kept {} days) - Enter/r: restore, T/Esc: back",
+        trash::TRASH_RETENTION_DAYS
+    );
+    let trash_list = List::new(items)
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(Style::default().fg(theme.accent)))
+        .highlight_style(Style::default().bg(theme.surface).fg(theme.accent))
+        .highlight_symbol("► ");
+
+    f.render_stateful_widget(trash_list, area, &mut app.trash_list_state);
+}
+
 fn draw_input(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
+    let theme = app.theme;
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .title("Add Task")
+        .border_style(Style::default().fg(theme.accent));
+
+    // What the task will be read as, so a marker that wasn't meant as one shows before it's saved
+    if app.input_mode == InputMode::Editing && !app.input.trim().is_empty() {
+        let preview = Task::parse_at(&app.input, app.clock.as_ref());
+        let mut spans = vec![Span::styled(" → ", Style::default().fg(theme.muted))];
+        spans.extend(flag_span(&preview));
+        spans.push(Span::styled(preview.text.clone(), Style::default().fg(theme.text)));
+        spans.extend(marker_spans(&preview, theme, app.clock.today()));
+        spans.push(Span::raw(" "));
+        block = block.title_bottom(Line::from(spans));
+    }
+
     let input = Paragraph::new(app.input.as_str())
         .style(match app.input_mode {
-            InputMode::Normal => Style::default().fg(Color::White),
-            InputMode::Editing => Style::default().fg(Color::Rgb(255, 107, 138)),
+            InputMode::Normal => Style::default().fg(theme.text),
+            InputMode::Editing => Style::default().fg(theme.accent),
         })
-        .block(Block::default()
-            .borders(Borders::ALL)
-            .title("Add Task")
-            .border_style(Style::default().fg(Color::Rgb(255, 107, 138))))
+        .block(block)
         .wrap(Wrap { trim: true });
     
     f.render_widget(input, area);
@@ -845,10 +2721,8 @@ fn draw_input(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
     }
 }
 
-fn draw_status_line(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
-    let visible_count = app.get_visible_tasks().len();
-    let total_count = app.tasks.len();
-    let completed_count = app.tasks.iter().filter(|t| t.completed).count();
+fn draw_status_line(f: &mut Frame, app: &mut App, area: ratatui::layout::Rect) {
+    let theme = app.theme;
 
     let mode_text = match app.input_mode {
         InputMode::Normal => "NORMAL",
@@ -859,187 +2733,586 @@ fn draw_status_line(f: &mut Frame, app: &App, area: ratatui::layout::Rect) {
         ViewMode::Tasks => {
             if let Some(tag) = &app.selected_tag {
                 format!("Filtered by #{}", tag)
+            } else if let Some(context) = &app.selected_context {
+                format!("Filtered by >{}", context)
+            } else if app.group_by_project {
+                "By project".to_string()
             } else {
                 "All tasks".to_string()
             }
         }
         ViewMode::TagsMenu => "Tags menu".to_string(),
+        ViewMode::Week => "Week agenda".to_string(),
+        ViewMode::Trash => format!("Trash: {} deleted", app.trash.len()),
+        ViewMode::Zen => "Zen".to_string(),
     };
 
-    let sync_info = if let Some(ref sync_status) = app.sync_status {
-        format!(" | {}", sync_status)
-    } else {
-        String::new()
+    let workspace = app
+        .working_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| app.working_dir.display().to_string());
+
+    let ctx = StatusContext {
+        tasks: &app.tasks,
+        today: app.clock.today(),
+        mode: mode_text,
+        view: &view_info,
+        visible_count: app.get_visible_tasks().len(),
+        sync_status: app.sync_status.as_deref(),
+        pomodoro_remaining: app.pomodoro_remaining(),
+        last_sync: app.last_sync,
+        git: app.git_state.as_ref().map(|git| GitSummary { changes: git.changes.len(), ahead: git.ahead }),
+        workspace: &workspace,
+        now: app.clock.now().to_utc(),
     };
+    let segments = status_line::render_segments(&app.status_widgets, &ctx);
+
+    // Remember where each segment landed so a click can open its details
+    let separator_width = Span::raw(status_line::SEPARATOR).width() as u16;
+    let mut x = area.x;
+    let mut status_segments = Vec::new();
+    for (widget, text) in segments {
+        let width = Span::raw(text.as_str()).width() as u16;
+        status_segments.push(StatusSegment { widget, text, row: area.y, columns: x..x + width });
+        x += width + separator_width;
+    }
 
-    let status_text = format!(
-        "{} | {} | Tasks: {} active, {} completed, {} total{} | s:sync t:tags h:help q:quit",
-        mode_text, view_info, visible_count, completed_count, total_count, sync_info
-    );
+    let status_text = status_segments
+        .iter()
+        .map(|segment| segment.text.as_str())
+        .collect::<Vec<_>>()
+        .join(status_line::SEPARATOR);
+    app.status_segments = status_segments;
 
     let status = Paragraph::new(status_text)
-        .style(Style::default().fg(Color::White).bg(Color::Black));
+        .style(Style::default().fg(theme.text).bg(theme.surface));
 
     f.render_widget(status, area);
 }
 
-fn draw_splash_screen(f: &mut Frame) {
-    let splash_art = vec![
+fn draw_status_details(f: &mut Frame, app: &App, widget: StatusWidget) {
+    let theme = app.theme;
+    let now = app.clock.now().to_utc();
+    let today = app.clock.today();
+
+    let mut lines = vec![
+        Line::from(Span::styled(widget.description(), Style::default().fg(theme.accent))),
+        Line::from(""),
+    ];
+    let mut push = |text: String| lines.push(Line::from(Span::styled(text, Style::default().fg(theme.text))));
+
+    match widget {
+        StatusWidget::Sync => {
+            if !is_todoist_sync_enabled() {
+                push("Not enabled, run yarmtl --setup-todoist".to_string());
+            }
+            match app.last_sync {
+                Some(last) => push(format!(
+                    "Last sync: {} ({})",
+                    last.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                    status_line::format_ago(last, now)
+                )),
+                None => push("Never synced".to_string()),
+            }
+            if let Some(status) = &app.sync_status {
+                push(format!("This session: {}", status));
+            }
+            push("Press s to sync now".to_string());
+        }
+        StatusWidget::Git => match &app.git_state {
+            Some(git) => {
+                push(format!("Repository: {}", app.working_dir.display()));
+                if git.changes.is_empty() {
+                    push("No uncommitted changes".to_string());
+                } else {
+                    push(format!("{} uncommitted change(s):", git.changes.len()));
+                    for change in &git.changes {
+                        push(format!("  {}", change));
+                    }
+                }
+                match git.ahead {
+                    Some(0) => push("Up to date with the remote".to_string()),
+                    Some(ahead) => push(format!("{} commit(s) not pushed yet", ahead)),
+                    None => push("No remote branch to push to".to_string()),
+                }
+            }
+            None => push(format!("{} is not a git repository", app.working_dir.display())),
+        },
+        StatusWidget::Reminders => {
+            let reminders: Vec<&Task> = app
+                .tasks
+                .iter()
+                .filter(|t| recurrence::has_reminder_on(t, today))
+                .collect();
+            if reminders.is_empty() {
+                push("No reminders today".to_string());
+            }
+            for task in reminders {
+                push(format!("🔔 {} [{}]", task.text, task.id));
+            }
+        }
+        StatusWidget::Workspace => {
+            push(format!("Directory: {}", app.working_dir.display()));
+            push(format!("Tasks file: {}", encryption::file_in(&app.working_dir, "tasks.md").display()));
+            push(format!("{} tasks loaded", app.tasks.len()));
+        }
+        _ => {
+            if let Some(segment) = app.status_segments.iter().find(|segment| segment.widget == widget) {
+                push(segment.text.clone());
+            }
+        }
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "←/→ other segments, any other key closes",
+        Style::default().fg(theme.muted),
+    )));
+
+    let popup_area = centered_rect(60, 40, f.size());
+    f.render_widget(Clear, popup_area);
+    let details = Paragraph::new(lines)
+        .block(Block::default()
+            .title("Status")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent)))
+        .wrap(Wrap { trim: false });
+    f.render_widget(details, popup_area);
+}
+
+/// The logo and feature box, shown on the wizard's welcome page.
+fn logo_lines(theme: &Theme) -> Vec<Line<'static>> {
+    vec![
         Line::from(""),
         Line::from(vec![
-            Span::styled("    ██    ██  █████  ██████  ███    ███ ████████ ██      ", Style::default().fg(Color::Rgb(255, 107, 138))),
+            Span::styled("    ██    ██  █████  ██████  ███    ███ ████████ ██      ", Style::default().fg(theme.accent)),
         ]),
         Line::from(vec![
-            Span::styled("     ██  ██  ██   ██ ██   ██ ████  ████    ██    ██      ", Style::default().fg(Color::Rgb(255, 107, 138))),
+            Span::styled("     ██  ██  ██   ██ ██   ██ ████  ████    ██    ██      ", Style::default().fg(theme.accent)),
         ]),
         Line::from(vec![
-            Span::styled("      ████   ███████ ██████  ██ ████ ██    ██    ██      ", Style::default().fg(Color::Rgb(255, 107, 138))),
+            Span::styled("      ████   ███████ ██████  ██ ████ ██    ██    ██      ", Style::default().fg(theme.accent)),
         ]),
         Line::from(vec![
-            Span::styled("       ██    ██   ██ ██   ██ ██  ██  ██    ██    ██      ", Style::default().fg(Color::Rgb(255, 107, 138))),
+            Span::styled("       ██    ██   ██ ██   ██ ██  ██  ██    ██    ██      ", Style::default().fg(theme.accent)),
         ]),
         Line::from(vec![
-            Span::styled("       ██    ██   ██ ██   ██ ██      ██    ██    ███████ ", Style::default().fg(Color::Green)),
+            Span::styled("       ██    ██   ██ ██   ██ ██      ██    ██    ███████ ", Style::default().fg(theme.success)),
         ]),
         Line::from(""),
         Line::from(vec![
-            Span::styled("              Yet Another Rust Markdown Todo List", Style::default().fg(Color::White)),
+            Span::styled("              Yet Another Rust Markdown Todo List", Style::default().fg(theme.text)),
         ]),
         Line::from(""),
         Line::from(vec![
-            Span::styled("    ┌─────────────────────────────────────────────────────────────┐", Style::default().fg(Color::Rgb(255, 107, 138))),
+            Span::styled("    ┌─────────────────────────────────────────────────────────────┐", Style::default().fg(theme.accent)),
         ]),
         Line::from(vec![
-            Span::styled("    │  📝 ", Style::default().fg(Color::Rgb(255, 107, 138))),
-            Span::styled("Organize your tasks with deadlines and projects    ", Style::default().fg(Color::White)),
-            Span::styled("│", Style::default().fg(Color::Rgb(255, 107, 138))),
+            Span::styled("    │  📝 ", Style::default().fg(theme.accent)),
+            Span::styled("Organize your tasks with deadlines and projects    ", Style::default().fg(theme.text)),
+            Span::styled("│", Style::default().fg(theme.accent)),
         ]),
         Line::from(vec![
-            Span::styled("    │  ⚡ ", Style::default().fg(Color::Rgb(255, 107, 138))),
-            Span::styled("Fast, lightweight, and markdown-based             ", Style::default().fg(Color::White)),
-            Span::styled("│", Style::default().fg(Color::Rgb(255, 107, 138))),
+            Span::styled("    │  ⚡ ", Style::default().fg(theme.accent)),
+            Span::styled("Fast, lightweight, and markdown-based             ", Style::default().fg(theme.text)),
+            Span::styled("│", Style::default().fg(theme.accent)),
         ]),
         Line::from(vec![
-            Span::styled("    │  🎯 ", Style::default().fg(Color::Rgb(255, 107, 138))),
-            Span::styled("Visual deadline tracking and email reminders      ", Style::default().fg(Color::White)),
-            Span::styled("│", Style::default().fg(Color::Rgb(255, 107, 138))),
+            Span::styled("    │  🎯 ", Style::default().fg(theme.accent)),
+            Span::styled("Visual deadline tracking and email reminders      ", Style::default().fg(theme.text)),
+            Span::styled("│", Style::default().fg(theme.accent)),
         ]),
         Line::from(vec![
-            Span::styled("    └─────────────────────────────────────────────────────────────┘", Style::default().fg(Color::Rgb(255, 107, 138))),
+            Span::styled("    └─────────────────────────────────────────────────────────────┘", Style::default().fg(theme.accent)),
         ]),
-        Line::from(""),
+    ]
+}
+
+fn draw_setup_wizard(f: &mut Frame, app: &App) {
+    let theme = app.theme;
+    let Some(wizard) = &app.setup else {
+        return;
+    };
+
+    let heading = |text: &str| {
+        Line::from(Span::styled(text.to_string(), Style::default().fg(theme.text).add_modifier(Modifier::BOLD)))
+    };
+    let hint = |text: &str| {
+        Line::from(Span::styled(text.to_string(), Style::default().fg(theme.muted).add_modifier(Modifier::ITALIC)))
+    };
+    let input = |text: &str| {
         Line::from(vec![
-            Span::styled("                    Press any key to continue...", Style::default().fg(Color::Rgb(255, 107, 138)).add_modifier(Modifier::ITALIC)),
-        ]),
-    ];
+            Span::styled(format!("  {}", text), Style::default().fg(theme.accent)),
+            Span::styled("▏", Style::default().fg(theme.accent).add_modifier(Modifier::SLOW_BLINK)),
+        ])
+    };
 
-    let splash_paragraph = Paragraph::new(splash_art)
-        .block(Block::default())
-        .wrap(Wrap { trim: true });
+    let mut lines = match wizard.step {
+        Step::Welcome => {
+            let mut lines = logo_lines(&theme);
+            lines.push(Line::from(""));
+            lines.push(heading("    Welcome! A few questions to get you set up."));
+            lines.push(hint("    Enter: start, Esc: skip and use the defaults"));
+            lines
+        }
+        Step::TasksDir => vec![
+            heading("Where should your tasks live?"),
+            Line::from(""),
+            input(&wizard.tasks_dir),
+            Line::from(""),
+            hint("tasks.md, the trash and attachments are kept in this directory; it's created if needed."),
+        ],
+        Step::Git => {
+            let choice = |label: &str, on: bool| {
+                let style = if on {
+                    Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(theme.muted)
+                };
+                Span::styled(format!("  {} {}", if on { "◉" } else { "○" }, label), style)
+            };
+            vec![
+                heading("Commit every change to tasks.md with git?"),
+                Line::from(""),
+                Line::from(vec![
+                    choice("yes", wizard.git_auto_commit),
+                    choice("no", !wizard.git_auto_commit),
+                ]),
+                Line::from(""),
+                hint("Gives you history, undo and the year in review, and pushes when a remote is set up."),
+                hint("Space/y/n to choose."),
+            ]
+        }
+        Step::Email => vec![
+            heading("Email address for daily reminders (optional)"),
+            Line::from(""),
+            input(&wizard.email),
+            Line::from(""),
+            hint("Leave empty to skip. Store the SMTP password with yarmtl --setup-email afterwards."),
+        ],
+        Step::Todoist => {
+            let mut lines = vec![
+                heading("Todoist API token (optional)"),
+                Line::from(""),
+                input(&"•".repeat(wizard.todoist_token.chars().count())),
+                Line::from(""),
+            ];
+            lines.push(match &wizard.token_check {
+                TokenCheck::Unchecked => hint("Find it at https://todoist.com/app/settings/integrations, or leave empty to skip."),
+                TokenCheck::Checking => Line::from(Span::styled("⏳ Verifying token...", Style::default().fg(theme.accent))),
+                TokenCheck::Valid => Line::from(Span::styled("✓ Token verified", Style::default().fg(theme.success))),
+                TokenCheck::Invalid(reason) => {
+                    Line::from(Span::styled(format!("✗ {}", reason), Style::default().fg(theme.danger)))
+                }
+            });
+            lines
+        }
+        Step::Summary => {
+            let mut lines = vec![heading("Ready to go:"), Line::from("")];
+            lines.extend(wizard.summary().into_iter().map(|line| {
+                Line::from(Span::styled(format!("  • {}", line), Style::default().fg(theme.text)))
+            }));
+            lines.push(Line::from(""));
+            lines.push(hint("Enter saves the configuration, Esc goes back."));
+            lines
+        }
+    };
+
+    if !matches!(wizard.step, Step::Welcome | Step::Summary) {
+        lines.push(Line::from(""));
+        lines.push(hint("Enter: next   Esc: back"));
+    }
 
+    let (number, total) = wizard.step_number();
     let area = centered_rect(90, 90, f.size());
     f.render_widget(Clear, area);
-    f.render_widget(splash_paragraph, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .title(format!("yarmtl setup ({}/{})", number, total))
+                .border_style(Style::default().fg(theme.accent)))
+            .wrap(Wrap { trim: false }),
+        area,
+    );
 }
 
 fn draw_notes_popup(f: &mut Frame, app: &App) {
-    if let Some(task_index) = app.selected_task_for_notes {
-        if let Some(task) = app.tasks.get(task_index) {
-            let popup_area = centered_rect(60, 50, f.size());
+    let theme = app.theme;
+    if let Some(task_index) = app.selected_task_for_notes
+        && let Some(task) = app.tasks.get(task_index) {
+        let popup_area = centered_rect(60, 50, f.size());
             
-            f.render_widget(Clear, popup_area);
+        f.render_widget(Clear, popup_area);
             
-            let notes_text = if let Some(ref notes) = task.notes {
-                notes.clone()
-            } else {
-                "No notes for this task.".to_string()
-            };
+        let notes_text = if let Some(ref notes) = task.notes {
+            notes.clone()
+        } else {
+            "No notes for this task.".to_string()
+        };
             
-            let notes_lines = vec![
-                Line::from(vec![
-                    Span::styled("Task: ", Style::default().fg(Color::Rgb(255, 107, 138))),
-                    Span::styled(&task.text, Style::default().fg(Color::White)),
-                ]),
-                Line::from(""),
-                Line::from(vec![
-                    Span::styled("Notes:", Style::default().fg(Color::Rgb(255, 107, 138))),
-                ]),
-                Line::from(""),
-                Line::from(vec![
-                    Span::styled(notes_text, Style::default().fg(Color::White)),
-                ]),
-                Line::from(""),
-                Line::from(vec![
-                    Span::styled("Press any key to close", Style::default().fg(Color::DarkGray)),
-                ]),
-            ];
+        let mut notes_lines = vec![
+            Line::from(vec![
+                Span::styled("Task: ", Style::default().fg(theme.accent)),
+                Span::styled(&task.text, Style::default().fg(theme.text)),
+            ]),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Notes:", Style::default().fg(theme.accent)),
+            ]),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled(notes_text, Style::default().fg(theme.text)),
+            ]),
+            Line::from(""),
+        ];
+
+        // Recorded dates first, git history for tasks from before they were recorded
+        let today = app.clock.today();
+        let (git_created, git_done) = app.dates_from_git.get(&task.id).copied().unwrap_or_default();
+        let mut dates = Vec::new();
+        match (task.created, git_created) {
+            (Some(created), _) => dates.push(format!("added {}", stats::relative_day(created, today))),
+            (None, Some(created)) => dates.push(format!("added {} (from git)", stats::relative_day(created, today))),
+            _ => {}
+        }
+        if task.cancelled
+            && let Some(done) = task.done
+        {
+            dates.push(format!("cancelled {}", stats::relative_day(done, today)));
+        } else if task.completed {
+            match (task.done, git_done) {
+                (Some(done), _) => dates.push(format!("completed {}", stats::relative_day(done, today))),
+                (None, Some(done)) => dates.push(format!("completed {} (from git)", stats::relative_day(done, today))),
+                _ => {}
+            }
+        }
+        if !dates.is_empty() {
+            notes_lines.insert(1, Line::from(vec![
+                Span::styled(format!("📆 {}", dates.join(", ")), Style::default().fg(theme.muted)),
+            ]));
+        }
+        if let Some(source) = &task.source {
+            notes_lines.insert(1, Line::from(vec![
+                Span::styled(format!("📄 from {}", source), Style::default().fg(theme.muted)),
+            ]));
+        }
 
-            let notes_paragraph = Paragraph::new(notes_lines)
-                .block(Block::default()
-                    .title("Task Notes")
-                    .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::Rgb(255, 107, 138))))
-                .wrap(Wrap { trim: true });
+        let link_targets = app.notes_links();
+        let outgoing = links::task_links(task)
+            .iter()
+            .filter(|reference| matches!(links::resolve(&app.tasks, reference), LinkTarget::Found(_)))
+            .count();
+        let broken: Vec<String> = links::task_links(task)
+            .into_iter()
+            .filter(|reference| !matches!(links::resolve(&app.tasks, reference), LinkTarget::Found(_)))
+            .collect();
+
+        if !link_targets.is_empty() || !broken.is_empty() {
+            notes_lines.push(Line::from(vec![
+                Span::styled("Links (j/k to select, Enter to jump):", Style::default().fg(theme.accent)),
+            ]));
+            for (i, &target) in link_targets.iter().enumerate() {
+                let arrow = if i < outgoing { "↪" } else { "↩" };
+                let style = if i == app.notes_link_cursor {
+                    Style::default().fg(theme.accent).bg(theme.surface).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(theme.text)
+                };
+                notes_lines.push(Line::from(vec![
+                    Span::styled(format!("{} {} [{}]", arrow, app.tasks[target].text, app.tasks[target].id), style),
+                ]));
+            }
+            for reference in broken {
+                notes_lines.push(Line::from(vec![
+                    Span::styled(format!("✗ [[id:{}]] (broken, see yarmtl doctor)", reference), Style::default().fg(theme.danger)),
+                ]));
+            }
+            notes_lines.push(Line::from(""));
+        }
 
-            f.render_widget(notes_paragraph, popup_area);
+        let task_attachments = attachments::task_attachments(&app.working_dir, task);
+        if !task_attachments.is_empty() {
+            notes_lines.push(Line::from(vec![
+                Span::styled("Attachments (o opens the first):", Style::default().fg(theme.accent)),
+            ]));
+            for attachment in &task_attachments {
+                let text = if attachment.label == attachment.target {
+                    format!("📎 {}", attachment.target)
+                } else {
+                    format!("📎 {}: {}", attachment.label, attachment.target)
+                };
+                notes_lines.push(Line::from(vec![Span::styled(text, Style::default().fg(theme.text))]));
+            }
+            notes_lines.push(Line::from(""));
         }
+
+        notes_lines.push(Line::from(vec![
+            Span::styled("Press any key to close", Style::default().fg(theme.muted)),
+        ]));
+
+        let notes_paragraph = Paragraph::new(notes_lines)
+            .block(Block::default()
+                .title("Task Notes")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.accent)))
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(notes_paragraph, popup_area);
     }
 }
 
-fn draw_help_popup(f: &mut Frame) {
+fn draw_help_popup(f: &mut Frame, app: &mut App) {
+    let theme = app.theme;
     let popup_area = centered_rect(70, 80, f.size());
     
     f.render_widget(Clear, popup_area);
-    
-    let help_text = vec![
-        Line::from("YARMTL - Help"),
-        Line::from(""),
-        Line::from("Navigation:"),
-        Line::from("  j/↓    - Next task"),
-        Line::from("  k/↑    - Previous task"),
-        Line::from("  Enter  - Toggle task completion"),
-        Line::from("  Space  - Toggle task completion"),
-        Line::from(""),
-        Line::from("Task Management:"),
-        Line::from("  a/i    - Add new task"),
-        Line::from("  d/Del  - Delete selected task"),
-        Line::from("  c      - Toggle show completed tasks"),
-        Line::from("  r      - Reload tasks from file"),
-        Line::from("  n      - View task notes"),
-        Line::from("  s      - Sync with Todoist"),
-        Line::from("  t      - Toggle tags menu"),
-        Line::from("  Esc    - Clear tag filter"),
-        Line::from(""),
-        Line::from("Task Syntax:"),
-        Line::from("  !2025-10-01    - Set deadline"),
-        Line::from("  @today         - Set reminder for today"),
-        Line::from("  @tomorrow      - Set reminder for tomorrow"),
-        Line::from("  @2025-10-01    - Set reminder for date"),
-        Line::from("  #work #urgent  - Add multiple tags"),
-        Line::from("  //note text    - Add task notes"),
-        Line::from("  $1             - Set importance (1=very important, 5=not important)"),
-        Line::from(""),
-        Line::from("Examples:"),
-        Line::from("  \"Finish report !2025-10-01 @today #work #urgent $1 //Important meeting\""),
-        Line::from("  \"Review docs #work $3 //Check formatting\""),
-        Line::from(""),
-        Line::from("Other:"),
-        Line::from("  h/F1   - Toggle this help"),
-        Line::from("  q      - Quit"),
-        Line::from(""),
-        Line::from("Press any key to close help"),
-    ];
+
+    let keys_width = keymap::keys_column_width();
+    let mut help_text: Vec<Line> = keymap::help_lines(&app.help_filter)
+        .into_iter()
+        .map(|line| match line {
+            HelpLine::Section(title) => Line::from(Span::styled(
+                format!("{}:", title),
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+            )),
+            HelpLine::Entry(entry) => Line::from(vec![
+                Span::styled(format!("  {:<width$}", entry.keys, width = keys_width), Style::default().fg(theme.accent)),
+                Span::styled(format!("  {}", entry.action), Style::default().fg(theme.text)),
+            ]),
+            HelpLine::Blank => Line::from(""),
+        })
+        .collect();
+
+    if help_text.is_empty() {
+        help_text.push(Line::from(Span::styled(
+            format!("Nothing matches \"{}\"", app.help_filter),
+            Style::default().fg(theme.muted),
+        )));
+    }
+
+    // Keep the last page in view instead of scrolling into blank space
+    let visible_height = popup_area.height.saturating_sub(2) as usize;
+    let max_scroll = help_text.len().saturating_sub(visible_height) as u16;
+    app.help_scroll = app.help_scroll.min(max_scroll);
+
+    let title = if app.help_filtering {
+        format!("Help - filter: /{}█", app.help_filter)
+    } else if !app.help_filter.is_empty() {
+        format!("Help - filter: /{} (Esc to clear)", app.help_filter)
+    } else {
+        "Help - j/k scroll, / filter, h/q close".to_string()
+    };
 
     let help_paragraph = Paragraph::new(help_text)
         .block(Block::default()
-            .title("Help")
+            .title(title)
             .borders(Borders::ALL)
-            .style(Style::default().bg(Color::Black)))
-        .wrap(Wrap { trim: true });
+            .style(Style::default().bg(theme.surface)))
+        .scroll((app.help_scroll, 0));
 
     f.render_widget(help_paragraph, popup_area);
 }
 
+fn draw_log_popup(f: &mut Frame, app: &mut App) {
+    let theme = app.theme;
+    let popup_area = centered_rect(80, 80, f.size());
+
+    f.render_widget(Clear, popup_area);
+
+    let mut log_text: Vec<Line> = logging::recent()
+        .iter()
+        .map(|line| {
+            let color = match line.level {
+                tracing::Level::ERROR | tracing::Level::WARN => theme.danger,
+                tracing::Level::INFO => theme.text,
+                _ => theme.muted,
+            };
+            Line::from(vec![
+                Span::styled(line.at.format("%H:%M:%S ").to_string(), Style::default().fg(theme.muted)),
+                Span::styled(line.text(), Style::default().fg(color)),
+            ])
+        })
+        .collect();
+
+    if log_text.is_empty() {
+        log_text.push(Line::from(Span::styled("Nothing logged yet", Style::default().fg(theme.muted))));
+    }
+
+    let visible_height = popup_area.height.saturating_sub(2) as usize;
+    let max_scroll = log_text.len().saturating_sub(visible_height) as u16;
+    app.log_scroll = app.log_scroll.min(max_scroll);
+
+    let log_paragraph = Paragraph::new(log_text)
+        .block(Block::default()
+            .title("Log - j/k scroll, !/q close")
+            .borders(Borders::ALL)
+            .style(Style::default().bg(theme.surface)))
+        .scroll((app.log_scroll, 0));
+
+    f.render_widget(log_paragraph, popup_area);
+}
+
+fn draw_confirm_dialog(f: &mut Frame, confirm: &Confirm, theme: &Theme) {
+    let area = f.size();
+    let width = (confirm.prompt.chars().count() as u16 + 4).max(20).min(area.width);
+    let popup_area = ratatui::layout::Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + area.height.saturating_sub(3) / 2,
+        width,
+        height: 3.min(area.height),
+    };
+
+    f.render_widget(Clear, popup_area);
+
+    let dialog = Paragraph::new(Line::from(vec![
+        Span::styled(confirm.prompt.as_str(), Style::default().fg(theme.text).add_modifier(Modifier::BOLD)),
+    ]))
+    .block(Block::default()
+        .title("Confirm")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.danger)));
+
+    f.render_widget(dialog, popup_area);
+}
+
+fn draw_toasts(f: &mut Frame, app: &App) {
+    let theme = app.theme;
+    let area = f.size();
+    // Stack toasts upwards from just above the status line, newest at the bottom
+    let mut y = area.height.saturating_sub(2);
+
+    for toast in app.toasts.iter().rev() {
+        if y < 3 {
+            break;
+        }
+
+        let color = match toast.kind {
+            ToastKind::Info => theme.accent,
+            ToastKind::Success => theme.success,
+            ToastKind::Error => theme.danger,
+        };
+        let width = (toast.message.chars().count() as u16 + 4).min(area.width.saturating_sub(2)).min(60);
+        let toast_area = ratatui::layout::Rect {
+            x: area.width.saturating_sub(width + 1),
+            y: y - 3,
+            width,
+            height: 3,
+        };
+
+        f.render_widget(Clear, toast_area);
+        f.render_widget(
+            Paragraph::new(toast.message.as_str())
+                .style(Style::default().fg(theme.text))
+                .block(Block::default().borders(Borders::ALL).border_style(Style::default().fg(color))),
+            toast_area,
+        );
+
+        y -= 3;
+    }
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: ratatui::layout::Rect) -> ratatui::layout::Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
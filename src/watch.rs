@@ -0,0 +1,112 @@
+use crate::error::YarmtlError;
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// How long a change has to settle: editors and git write a file in several steps.
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// Watches files, tasks.md and the ones it includes, for being written, replaced or removed,
+/// as editors, `git pull` and the syncs do. Watches their directories, so a file saved by
+/// renaming another over it is seen too.
+pub struct FileWatcher {
+    paths: Vec<PathBuf>,
+    changes: mpsc::UnboundedReceiver<()>,
+    /// Stops watching when dropped
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+    pub fn new(paths: &[PathBuf]) -> Result<FileWatcher, YarmtlError> {
+        let (tx, changes) = mpsc::unbounded_channel();
+        let watched: Vec<PathBuf> = paths.iter().map(|path| absolute(path)).collect();
+        let wanted = watched.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event
+                && is_change(&event.kind)
+                && event.paths.iter().any(|changed| wanted.contains(&absolute(changed)))
+            {
+                let _ = tx.send(());
+            }
+        })
+        .map_err(|e| YarmtlError::Watch { path: paths.first().cloned().unwrap_or_default(), message: e.to_string() })?;
+        // A file included from a directory that isn't there yet is left out
+        let mut dirs: Vec<&Path> = watched.iter().filter_map(|path| path.parent()).filter(|dir| dir.is_dir()).collect();
+        dirs.sort();
+        dirs.dedup();
+        for dir in dirs {
+            let failed = |e: notify::Error| YarmtlError::Watch { path: dir.to_path_buf(), message: e.to_string() };
+            watcher.watch(dir, RecursiveMode::NonRecursive).map_err(failed)?;
+        }
+        Ok(FileWatcher { paths: paths.to_vec(), changes, _watcher: watcher })
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Whether one of the files changed since last asked, without waiting.
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        while self.changes.try_recv().is_ok() {
+            changed = true;
+        }
+        changed
+    }
+
+    /// Waits for one of the files to change, and for the change to settle.
+    pub async fn wait(&mut self) {
+        if self.changes.recv().await.is_none() {
+            // The watcher is gone, nothing will change any more
+            std::future::pending::<()>().await;
+        }
+        tokio::time::sleep(SETTLE).await;
+        self.changed();
+    }
+}
+
+/// `path` from the root, the way the events name the files.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).map(|path| path.components().collect()).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Whether an event changes what the file holds; reading it or touching its metadata doesn't.
+fn is_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Create(_) | EventKind::Remove(_) => true,
+        EventKind::Modify(ModifyKind::Metadata(_)) => false,
+        EventKind::Modify(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, DataChange, MetadataKind};
+
+    #[test]
+    fn test_changes_are_writes_not_reads() {
+        assert!(is_change(&EventKind::Modify(ModifyKind::Data(DataChange::Content))));
+        assert!(is_change(&EventKind::Create(CreateKind::File)));
+        assert!(!is_change(&EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime))));
+        assert!(!is_change(&EventKind::Access(AccessKind::Read)));
+    }
+
+    #[tokio::test]
+    async fn test_sees_the_file_and_not_its_neighbours() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("tasks.md");
+        std::fs::write(&path, "# tasks\n").unwrap();
+        let mut watcher = FileWatcher::new(std::slice::from_ref(&path)).unwrap();
+
+        std::fs::write(dir.join(".tasks.md.swp"), "").unwrap();
+        tokio::time::sleep(SETTLE).await;
+        assert!(!watcher.changed());
+        std::fs::write(&path, "# tasks\n\n- [ ] water plants\n").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), watcher.wait()).await.unwrap();
+    }
+}
@@ -0,0 +1,339 @@
+use crate::config::load_config;
+use crate::task::Task;
+use chrono::{Local, NaiveDate};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// What each task was like when the webhooks last went out, in the state directory.
+const STATE_FILE: &str = ".webhook_state.json";
+
+/// Locked while the events are worked out, so two runs don't send the same ones.
+const LOCK_FILE: &str = ".webhooks.lock";
+
+/// The deliveries that failed every attempt, one json object per line.
+pub const DEAD_LETTER_FILE: &str = "webhooks_failed.jsonl";
+
+/// Tries per delivery before it goes to the dead-letter log.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry, doubled for each one after it.
+const FIRST_RETRY: Duration = Duration::from_secs(2);
+
+/// Sent as X-Yarmtl-Signature: `sha256=` and the HMAC of the body, keyed with the secret.
+const SIGNATURE_HEADER: &str = "X-Yarmtl-Signature";
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskEvent {
+    Created,
+    Completed,
+    /// The day after an open task was due; sent again when the deadline moves and passes again
+    DeadlineMissed,
+}
+
+impl TaskEvent {
+    /// The `event` field of the payload and the X-Yarmtl-Event header.
+    pub fn name(self) -> &'static str {
+        match self {
+            TaskEvent::Created => "task.created",
+            TaskEvent::Completed => "task.completed",
+            TaskEvent::DeadlineMissed => "task.deadline_missed",
+        }
+    }
+}
+
+/// A URL task events are POSTed to as json, one `[[webhooks]]` table of config.toml.
+///
+/// ```toml
+/// [[webhooks]]
+/// url = "https://homeassistant.local:8123/api/webhook/yarmtl"
+/// secret = "shared with the receiver"
+/// events = ["completed", "deadline_missed"]
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Signs each payload, see `SIGNATURE_HEADER`
+    pub secret: Option<String>,
+    /// The events to send, all of them when empty
+    #[serde(default)]
+    pub events: Vec<TaskEvent>,
+}
+
+impl WebhookConfig {
+    fn wants(&self, event: TaskEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct State {
+    tasks: BTreeMap<String, Seen>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+struct Seen {
+    completed: bool,
+    /// The deadline a missed-deadline event went out for
+    #[serde(default)]
+    missed: Option<NaiveDate>,
+}
+
+impl State {
+    fn load(state_dir: &Path) -> Option<Self> {
+        serde_json::from_str(&fs::read_to_string(state_dir.join(STATE_FILE)).ok()?).ok()
+    }
+
+    fn save(&self, state_dir: &Path) -> std::io::Result<()> {
+        fs::write(state_dir.join(STATE_FILE), serde_json::to_string(self)?)
+    }
+}
+
+/// The events `tasks` holds since `state` was taken, and the state to keep for next time.
+/// Without a state, on the first run, everything already there is taken as it is.
+pub fn detect<'a>(tasks: &'a [Task], state: Option<&State>, today: NaiveDate) -> (Vec<(TaskEvent, &'a Task)>, State) {
+    let mut events = Vec::new();
+    let mut next = State::default();
+    for task in tasks {
+        let before = state.map(|state| state.tasks.get(&task.id).copied());
+        let mut seen = before.flatten().unwrap_or_default();
+        let missed = task.deadline.filter(|deadline| !task.completed && *deadline < today);
+        match before {
+            None => seen.missed = missed,
+            Some(None) => events.push((TaskEvent::Created, task)),
+            Some(Some(_)) if task.completed && !seen.completed && !task.cancelled => {
+                events.push((TaskEvent::Completed, task))
+            }
+            _ => {}
+        }
+        if missed.is_some() && seen.missed != missed {
+            events.push((TaskEvent::DeadlineMissed, task));
+            seen.missed = missed;
+        }
+        seen.completed = task.completed;
+        next.tasks.insert(task.id.clone(), seen);
+    }
+    (events, next)
+}
+
+/// The json POSTed for an event.
+pub fn payload(event: TaskEvent, task: &Task) -> serde_json::Value {
+    let day = |date: Option<NaiveDate>| date.map(|d| d.format("%Y-%m-%d").to_string());
+    json!({
+        "event": event.name(),
+        "timestamp": Local::now().to_rfc3339(),
+        "task": {
+            "id": task.id,
+            "text": task.text,
+            "project": task.project,
+            "tags": task.tags,
+            "contexts": task.contexts,
+            "deadline": day(task.deadline),
+            "importance": task.importance,
+            "completed": task.completed,
+            "cancelled": task.cancelled,
+            "created": day(task.created),
+            "done": day(task.done),
+            "notes": task.notes,
+        },
+    })
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `body`, for the receiver to check the payload by.
+pub fn signature(secret: &str, body: &str) -> Result<String, openssl::error::ErrorStack> {
+    let key = PKey::hmac(secret.as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(body.as_bytes())?;
+    let hex: String = signer.sign_to_vec()?.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("sha256={}", hex))
+}
+
+/// POSTs `body` to the webhook, retrying with backoff on network errors and 5xx or 429
+/// replies. Other replies are the receiver turning it down, trying again won't help.
+async fn deliver(client: &Client, hook: &WebhookConfig, event: &str, body: &str) -> Result<(), String> {
+    let mut wait = FIRST_RETRY;
+    let mut attempt = 1;
+    loop {
+        let mut request = client
+            .post(&hook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Yarmtl-Event", event)
+            .body(body.to_string());
+        if let Some(secret) = &hook.secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret, body).map_err(|e| e.to_string())?);
+        }
+        let error = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                let status = response.status();
+                let error = format!("{} replied {}", hook.url, status);
+                if !status.is_server_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    return Err(error);
+                }
+                error
+            }
+            Err(e) => e.to_string(),
+        };
+        if attempt == MAX_ATTEMPTS {
+            return Err(format!("{} (after {} attempts)", error, attempt));
+        }
+        tokio::time::sleep(wait).await;
+        wait *= 2;
+        attempt += 1;
+    }
+}
+
+/// One line of the dead-letter log.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct DeadLetter {
+    failed_at: String,
+    url: String,
+    error: String,
+    payload: serde_json::Value,
+}
+
+fn record_failure(state_dir: &Path, url: &str, error: &str, payload: &serde_json::Value) -> std::io::Result<()> {
+    let letter = DeadLetter {
+        failed_at: Local::now().to_rfc3339(),
+        url: url.to_string(),
+        error: error.to_string(),
+        payload: payload.clone(),
+    };
+    let mut log = fs::OpenOptions::new().create(true).append(true).open(state_dir.join(DEAD_LETTER_FILE))?;
+    writeln!(log, "{}", serde_json::to_string(&letter)?)
+}
+
+fn client() -> Client {
+    Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent(concat!("yarmtl/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Failed to create HTTP client")
+}
+
+/// Sends the events in `tasks` since the last run to every webhook that wants them.
+/// Deliveries that fail every attempt go to the dead-letter log. Returns how many went out.
+pub async fn dispatch(state_dir: &Path, tasks: &[Task], hooks: &[WebhookConfig], today: NaiveDate) -> std::io::Result<usize> {
+    let events = {
+        let lock = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(state_dir.join(LOCK_FILE))?;
+        lock.lock()?;
+        let (events, state) = detect(tasks, State::load(state_dir).as_ref(), today);
+        // Saved before sending, a run started meanwhile mustn't send them again
+        state.save(state_dir)?;
+        events
+    };
+
+    let client = client();
+    let mut sent = 0;
+    for (event, task) in events {
+        let payload = payload(event, task);
+        let body = payload.to_string();
+        for hook in hooks.iter().filter(|hook| hook.wants(event)) {
+            match deliver(&client, hook, event.name(), &body).await {
+                Ok(()) => sent += 1,
+                Err(error) => record_failure(state_dir, &hook.url, &error, &payload)?,
+            }
+        }
+    }
+    Ok(sent)
+}
+
+/// Sends the dead letters again, to their webhook if it is still configured. The ones
+/// failing again stay in the log. Returns how many went out and how many are left.
+pub async fn retry_failed(state_dir: &Path, hooks: &[WebhookConfig]) -> std::io::Result<(usize, usize)> {
+    let path = state_dir.join(DEAD_LETTER_FILE);
+    let Ok(content) = fs::read_to_string(&path) else { return Ok((0, 0)) };
+    let letters: Vec<DeadLetter> = content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+    fs::remove_file(&path)?;
+
+    let client = client();
+    let mut sent = 0;
+    for letter in &letters {
+        let event = letter.payload["event"].as_str().unwrap_or_default();
+        let result = match hooks.iter().find(|hook| hook.url == letter.url) {
+            Some(hook) => deliver(&client, hook, event, &letter.payload.to_string()).await,
+            None => Err(format!("{} isn't in config.toml any more", letter.url)),
+        };
+        match result {
+            Ok(()) => sent += 1,
+            Err(error) => record_failure(state_dir, &letter.url, &error, &letter.payload)?,
+        }
+    }
+    Ok((sent, letters.len() - sent))
+}
+
+/// Posts the webhooks for what the save changed from a `yarmtl webhooks` in the
+/// background, so retries don't hold up the CLI or the TUI.
+pub fn request_webhooks_after_save() {
+    if load_config().webhooks.is_empty() {
+        return;
+    }
+    let started = env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .arg("webhooks")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    });
+    if let Err(e) = started {
+        tracing::warn!("couldn't start sending webhooks: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_since_last_run() {
+        let date = |d| NaiveDate::from_ymd_opt(2025, 6, d).unwrap();
+        let mut tasks = vec![
+            Task::parse("write report !2025-06-10 [id:aaaa01]"),
+            Task::parse("call mum [id:aaaa02]"),
+        ];
+
+        // The first run only takes note of what is there
+        let (events, state) = detect(&tasks, None, date(12));
+        assert!(events.is_empty());
+
+        tasks[1].set_completed(true, date(12));
+        tasks.push(Task::parse("buy milk !2025-06-12 [id:aaaa03]"));
+        let (events, state) = detect(&tasks, Some(&state), date(13));
+        let names: Vec<(TaskEvent, &str)> = events.iter().map(|(event, task)| (*event, task.id.as_str())).collect();
+        assert_eq!(
+            names,
+            [(TaskEvent::Completed, "aaaa02"), (TaskEvent::Created, "aaaa03"), (TaskEvent::DeadlineMissed, "aaaa03")]
+        );
+        assert!(detect(&tasks, Some(&state), date(14)).0.is_empty());
+
+        // A deadline moved and missed again is reported again
+        tasks[0].deadline = Some(date(13));
+        let (events, _) = detect(&tasks, Some(&state), date(14));
+        assert!(matches!(&events[..], [(TaskEvent::DeadlineMissed, task)] if task.id == "aaaa01"));
+
+        let body = payload(TaskEvent::Completed, &tasks[1]);
+        assert_eq!(body["event"], "task.completed");
+        assert_eq!(body["task"]["done"], "2025-06-12");
+        // The example of RFC 4231, test case 2
+        assert_eq!(
+            signature("Jefe", "what do ya want for nothing?").unwrap(),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
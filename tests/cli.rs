@@ -0,0 +1,130 @@
+mod common;
+
+use common::{Workspace, redacted};
+use predicates::str::contains;
+
+#[test]
+fn test_adding_tasks_writes_tasks_md() {
+    let workspace = Workspace::new();
+    workspace.yarmtl().arg("buy milk #home >shop $2").assert().success().stdout(contains("added task: \"buy milk\""));
+    workspace.yarmtl().args(["add", "--project", "Work", "write report !2030-01-10 //the q3 one"]).assert().success();
+    workspace.yarmtl().args(["--project", "Work", "call the printer guy"]).assert().success();
+
+    redacted().bind(|| insta::assert_snapshot!(workspace.tasks_md()));
+}
+
+#[test]
+fn test_listing_and_exporting() {
+    let workspace = Workspace::with_tasks(
+        "# tasks\n\n\
+         - [ ] water plants [id:aaaa01] >home [created:2025-01-01]\n\
+         - [x] pay rent [id:aaaa02] #bills [created:2025-01-01] [done:2025-01-03]\n\
+         \n## Work\n\
+         - [ ] review pull requests [id:aaaa03] #code [created:2025-01-02]\n  \
+           - [ ] the parser one [id:aaaa04] [created:2025-01-02]\n",
+    );
+
+    let listed = workspace.yarmtl().arg("--list").assert().success();
+    let listed = String::from_utf8_lossy(&listed.get_output().stdout).into_owned();
+    redacted().bind(|| insta::assert_snapshot!("list", listed));
+    let home = workspace.yarmtl().args(["--list", "--context", "home"]).assert().success();
+    let home = String::from_utf8_lossy(&home.get_output().stdout).into_owned();
+    assert!(home.contains("water plants") && !home.contains("review pull requests"));
+
+    // Export writes nothing but the json to stdout
+    let exported = workspace.yarmtl().arg("export").assert().success();
+    let tasks: serde_json::Value = serde_json::from_slice(&exported.get_output().stdout).unwrap();
+    insta::assert_json_snapshot!("export", tasks);
+}
+
+#[test]
+fn test_doctor_finds_duplicate_ids() {
+    let workspace = Workspace::with_tasks("- [ ] one [id:aaaa01]\n- [ ] two [id:aaaa01]\n");
+    workspace.yarmtl().arg("doctor").assert().failure().stdout(contains("1 problem(s) and"));
+
+    let workspace = Workspace::with_tasks("- [ ] one [id:aaaa01]\n");
+    workspace.yarmtl().arg("doctor").assert().success().stdout(contains("No problems found in 1 tasks"));
+}
+
+#[test]
+fn test_check_exit_codes() {
+    let workspace = Workspace::with_tasks("- [ ] file taxes !2020-04-15 [id:aaaa01]\n- [ ] someday [id:aaaa02]\n");
+    workspace.yarmtl().arg("check").assert().code(2).stdout(contains("1 overdue"));
+    workspace.yarmtl().args(["--list", "-q"]).assert().success().stdout(contains("  file taxes [aaaa01] !2020-04-15 (overdue)"));
+
+    let workspace = Workspace::with_tasks("- [ ] someday [id:aaaa02]\n");
+    workspace.yarmtl().args(["check", "--quiet"]).assert().code(0).stdout("Nothing overdue or due today\n");
+    workspace.yarmtl().arg("--no-such-flag").assert().code(64);
+}
+
+#[test]
+fn test_check_counts_encrypted_tasks_like_the_index() {
+    use age::secrecy::ExposeSecret;
+
+    // A finished trip, kept in the open tasks for its open subtask, isn't overdue
+    let tasks = "- [x] trip !2020-01-01 [id:aaaa01]\n  - [ ] write postcards [id:aaaa02]\n";
+    let workspace = Workspace::with_tasks(tasks);
+    workspace.yarmtl().arg("check").assert().code(0);
+
+    let key = workspace.tasks_dir().join("key.txt");
+    std::fs::write(&key, age::x25519::Identity::generate().to_string().expose_secret()).unwrap();
+    workspace.write("config.toml", &format!("[encryption]\nidentity_file = {:?}\n", key.display().to_string()));
+    workspace.yarmtl().arg("encrypt").assert().success();
+    assert!(workspace.tasks_dir().join("tasks.md.age").exists());
+    workspace.yarmtl().arg("check").assert().code(0).stdout(contains("Nothing overdue or due today"));
+    workspace.yarmtl().args(["prompt", "--format", "{overdue}/{today}"]).assert().success().stdout("0/0\n");
+}
+
+#[test]
+fn test_capture_from_args_and_stdin() {
+    let workspace = Workspace::with_tasks("# tasks\n\n- [ ] one [id:aaaa01]\n\n## Work\n- [ ] two [id:aaaa02]\n");
+    workspace.yarmtl().args(["capture", "call", "bob", "#phone"]).assert().success().stdout("");
+    workspace.yarmtl().arg("capture").write_stdin("buy milk\nnot this\n").assert().success().stdout("");
+    workspace.yarmtl().arg("capture").write_stdin("").assert().success();
+    redacted().bind(|| insta::assert_snapshot!(workspace.tasks_md()));
+}
+
+#[test]
+fn test_quick_captures_are_committed_with_their_own_messages() {
+    let workspace = Workspace::new();
+    let with_git = |args: &[&str]| workspace.yarmtl().env_remove("YARMTL_NO_GIT").args(args).assert().success();
+    with_git(&["add", "first"]);
+    // The second comes before the commit of the first, made in the background
+    with_git(&["capture", "call bob"]);
+    with_git(&["capture", "buy milk"]);
+
+    let repo = git2::Repository::open(workspace.tasks_dir()).unwrap();
+    let tasks_md = |commit: &git2::Commit| -> String {
+        let entry = commit.tree().unwrap().get_path(std::path::Path::new("tasks.md")).unwrap();
+        String::from_utf8(repo.find_blob(entry.id()).unwrap().content().to_vec()).unwrap()
+    };
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !tasks_md(&repo.head().unwrap().peel_to_commit().unwrap()).contains("buy milk") {
+        assert!(std::time::Instant::now() < deadline, "the captures weren't committed");
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    // Whichever commit took the second capture's line is done; let the other one finish
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    let mut walk = repo.revwalk().unwrap();
+    walk.push_head().unwrap();
+    let mut credited = Vec::new();
+    for id in walk {
+        let commit = repo.find_commit(id.unwrap()).unwrap();
+        let Ok(parent) = commit.parent(0) else { continue };
+        let before = tasks_md(&parent);
+        let added: Vec<String> = tasks_md(&commit)
+            .lines()
+            .filter(|line| line.starts_with("- [ ] ") && !before.lines().any(|old| old == *line))
+            .map(|line| line["- [ ] ".len()..].split(" [").next().unwrap().to_string())
+            .collect();
+        let message = commit.message().unwrap();
+        for text in &added {
+            assert!(message.contains(&format!("Added task: \"{}\"", text)), "{:?} doesn't credit {}", message, text);
+        }
+        assert_eq!(message.matches("Added task").count(), added.len(), "{:?}", message);
+        credited.extend(added);
+    }
+    credited.sort();
+    assert_eq!(credited, ["buy milk", "call bob"]);
+}
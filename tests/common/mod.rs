@@ -0,0 +1,90 @@
+//! What the integration tests share: a tasks directory in a home of its own, so nothing
+//! reads or writes the real one, and yarmtl run against it.
+
+#![allow(dead_code)]
+
+use assert_cmd::Command;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+/// A temporary home with settings.toml pointing at a tasks directory next to it. Both go
+/// when it's dropped.
+pub struct Workspace {
+    root: TempDir,
+}
+
+impl Workspace {
+    pub fn new() -> Workspace {
+        let workspace = Workspace { root: tempfile::tempdir().unwrap() };
+        fs::create_dir_all(workspace.tasks_dir()).unwrap();
+        fs::create_dir_all(workspace.data_dir()).unwrap();
+        let settings = format!("tasks_dir = {:?}\n", workspace.tasks_dir().display().to_string());
+        fs::write(workspace.data_dir().join("settings.toml"), settings).unwrap();
+        workspace
+    }
+
+    /// A workspace whose tasks.md is `tasks`.
+    pub fn with_tasks(tasks: &str) -> Workspace {
+        let workspace = Workspace::new();
+        fs::write(workspace.tasks_file(), tasks).unwrap();
+        workspace
+    }
+
+    pub fn home(&self) -> PathBuf {
+        self.root.path().join("home")
+    }
+
+    pub fn tasks_dir(&self) -> PathBuf {
+        self.root.path().join("tasks")
+    }
+
+    pub fn tasks_file(&self) -> PathBuf {
+        self.tasks_dir().join("tasks.md")
+    }
+
+    /// $XDG_DATA_HOME/yarmtl, where settings.toml and the state of the tasks directory are.
+    pub fn data_dir(&self) -> PathBuf {
+        self.home().join(".local/share/yarmtl")
+    }
+
+    pub fn tasks_md(&self) -> String {
+        fs::read_to_string(self.tasks_file()).unwrap()
+    }
+
+    pub fn write(&self, file: &str, content: &str) {
+        let path = self.tasks_dir().join(file);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(path, content).unwrap();
+    }
+
+    /// The yarmtl binary, run in the tasks directory with this home, without git, and away
+    /// from the keyring and the terminal.
+    pub fn yarmtl(&self) -> Command {
+        let mut command = Command::cargo_bin("yarmtl").unwrap();
+        command
+            .current_dir(self.tasks_dir())
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .env("HOME", self.home())
+            .env("XDG_DATA_HOME", self.home().join(".local/share"))
+            .env("XDG_CONFIG_HOME", self.home().join(".config"))
+            .env("YARMTL_NO_GIT", "1")
+            .env("TZ", "UTC");
+        command
+    }
+}
+
+/// Replaces what changes from run to run, the ids made up for new tasks and the day they
+/// were added or how long ago that was, for snapshots.
+pub fn redacted() -> insta::Settings {
+    let mut settings = insta::Settings::clone_current();
+    settings.add_filter(r"\[id:[0-9a-z]{6}\]", "[id:ID]");
+    settings.add_filter(r"\[[0-9a-z]{6}\]", "[ID]");
+    settings.add_filter(r"\[created:\d{4}-\d{2}-\d{2}\]", "[created:TODAY]");
+    settings.add_filter(r"\(added [a-z0-9 ]+\)", "(added some time ago)");
+    settings
+}
+
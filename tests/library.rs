@@ -0,0 +1,24 @@
+use chrono::NaiveDate;
+use yarmtl::document::{self, Prefer};
+use yarmtl::task::load_tasks_from_file;
+use yarmtl::{Task, recurrence};
+
+#[test]
+fn test_tasks_md_through_the_library() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path();
+    let path = dir.join("tasks.md");
+    std::fs::write(&path, "# tasks\n\n- [ ] pay rent !2025-07-01 #home [id:aaaa01]\n").unwrap();
+
+    let base = load_tasks_from_file(&path);
+    let mut tasks = base.clone();
+    tasks.push(Task::parse("water plants @2025-06-30 [id:aaaa02]"));
+    // Someone else checks the rent off meanwhile
+    std::fs::write(&path, "# tasks\n\n- [x] pay rent !2025-07-01 #home [id:aaaa01]\n").unwrap();
+    let saved = document::save_merged(&path, &base, &tasks, Prefer::Memory).unwrap();
+
+    assert_eq!(saved, load_tasks_from_file(&path));
+    assert!(saved[0].completed);
+    let today = NaiveDate::from_ymd_opt(2025, 6, 30).unwrap();
+    assert_eq!(recurrence::reminder_reason(&saved[1], today).as_deref(), Some("reminder date reached"));
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::{Workspace, redacted};
+use serde_json::json;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use yarmtl::paths::state_dir_of;
+use yarmtl::sync_engine::{ConflictStrategy, SyncEngine};
+use yarmtl::todoist_client::TodoistClient;
+use yarmtl::todoist_sync::{SyncFilter, TodoistProvider};
+
+/// A Todoist with an inbox and "call bob" in it, taking the tasks created in it.
+async fn fake_todoist() -> MockServer {
+    let server = MockServer::start().await;
+    let inbox = json!([{ "id": "p1", "name": "Inbox", "is_inbox_project": true }]);
+    Mock::given(method("GET")).and(path("/rest/v2/projects")).respond_with(ResponseTemplate::new(200).set_body_json(inbox)).mount(&server).await;
+    Mock::given(method("GET")).and(path("/rest/v2/sections")).respond_with(ResponseTemplate::new(200).set_body_json(json!([]))).mount(&server).await;
+    let tasks = json!([{ "id": "t1", "content": "call bob", "priority": 1, "project_id": "p1", "order": 1 }]);
+    Mock::given(method("GET")).and(path("/rest/v2/tasks")).respond_with(ResponseTemplate::new(200).set_body_json(tasks)).mount(&server).await;
+    let created = json!({ "id": "t2", "content": "buy milk", "priority": 1, "project_id": "p1", "order": 2 });
+    Mock::given(method("POST"))
+        .and(path("/rest/v2/tasks"))
+        .and(body_partial_json(json!({ "content": "buy milk" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(created))
+        .expect(1)
+        .mount(&server)
+        .await;
+    server
+}
+
+fn engine(server: &MockServer, workspace: &Workspace) -> SyncEngine<TodoistProvider> {
+    let client = TodoistClient::at(&server.uri(), "token".to_string());
+    let provider = TodoistProvider::with_client(client, false, SyncFilter::default());
+    SyncEngine::new(provider, &workspace.tasks_dir(), ConflictStrategy::KeepBoth).unwrap()
+}
+
+#[tokio::test]
+async fn test_sync_with_a_fake_todoist() {
+    let server = fake_todoist().await;
+    let workspace = Workspace::with_tasks("# tasks\n\n- [ ] buy milk [id:aaaa01]\n");
+
+    let report = engine(&server, &workspace).sync_with_approval(&workspace.tasks_file(), |_| {}, |_| true).await.unwrap();
+    assert_eq!((report.created_remote, report.created_in_yarmtl, report.failed), (1, 1, 0));
+    redacted().bind(|| insta::assert_snapshot!(workspace.tasks_md()));
+
+    // Checked off on the phone: no longer listed, but among the completed tasks
+    let done = json!({ "items": [{ "item_object": { "id": "t2", "content": "buy milk", "checked": true, "project_id": "p1" } }] });
+    Mock::given(method("GET"))
+        .and(path("/sync/v9/completed/get_all"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(done))
+        .mount(&server)
+        .await;
+    let report = engine(&server, &workspace).sync_with_approval(&workspace.tasks_file(), |_| {}, |_| true).await.unwrap();
+    assert_eq!((report.created_remote, report.updated_in_yarmtl, report.failed), (0, 1, 0));
+    assert!(workspace.tasks_md().contains("- [x] buy milk [id:aaaa01]"), "{}", workspace.tasks_md());
+
+    std::fs::remove_dir_all(state_dir_of(&workspace.tasks_dir())).unwrap();
+}